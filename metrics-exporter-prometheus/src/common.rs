@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use crate::exemplar::{BucketExemplars, Exemplar};
 use crate::{distribution::Distribution, PrometheusRecorder};
 
 use crate::formatting::sanitize_metric_name;
@@ -41,6 +42,128 @@ impl Matcher {
     }
 }
 
+/// Text format used when rendering metrics.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ExpositionFormat {
+    /// The Prometheus [text-based exposition format], version 0.0.4.
+    ///
+    /// [text-based exposition format]: https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+    #[default]
+    Prometheus,
+
+    /// The [OpenMetrics] text format, version 1.0.0.
+    ///
+    /// Counters are rendered as metric families whose name lacks the `_total` suffix, with their
+    /// samples carrying it instead, so a counter named `requests` is exposed as `requests_total`.
+    /// Units, if given when describing a metric, are rendered as `# UNIT` lines when the metric
+    /// name ends with the unit, as required by the format.
+    ///
+    /// [OpenMetrics]: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md
+    OpenMetrics,
+}
+
+impl ExpositionFormat {
+    /// Gets the value of the `Content-Type` header for payloads rendered in this format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExpositionFormat::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            ExpositionFormat::OpenMetrics => {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            }
+        }
+    }
+
+    /// Selects a format based on the value of an `Accept` header.
+    ///
+    /// The format with the highest quality value is selected, preferring OpenMetrics when both are
+    /// equally acceptable.  If the header doesn't name either format, `None` is returned, and the
+    /// caller should fall back to its default format.
+    pub fn from_accept(accept: &str) -> Option<ExpositionFormat> {
+        AcceptQualities::parse(accept).text_format()
+    }
+}
+
+/// Whether an `Accept` header prefers the Prometheus protobuf format over the text formats.
+///
+/// Protobuf is preferred when it's acceptable, and at least as acceptable as either text format.
+#[cfg_attr(not(any(feature = "http-listener", feature = "tower")), allow(dead_code))]
+pub(crate) fn prefers_protobuf(accept: &str) -> bool {
+    let qualities = AcceptQualities::parse(accept);
+    let text = qualities.openmetrics.into_iter().chain(qualities.prometheus).fold(0.0, f64::max);
+    qualities.protobuf.map_or(false, |protobuf| protobuf > 0.0 && protobuf >= text)
+}
+
+/// Whether an `Accept` header allows the given text format, either by naming it or via a wildcard.
+///
+/// The most specific media range matching the format determines its quality, so `*/*` doesn't
+/// allow the Prometheus text format when `text/plain;q=0` is also given.
+#[cfg_attr(not(feature = "http-listener"), allow(dead_code))]
+pub(crate) fn accepts_format(accept: &str, format: ExpositionFormat) -> bool {
+    let qualities = AcceptQualities::parse(accept);
+    let quality = match format {
+        ExpositionFormat::Prometheus => qualities.prometheus.or(qualities.text_wildcard),
+        ExpositionFormat::OpenMetrics => qualities.openmetrics.or(qualities.application_wildcard),
+    };
+    quality.or(qualities.wildcard).map_or(false, |q| q > 0.0)
+}
+
+/// The highest quality value given to each supported format by an `Accept` header.
+#[derive(Default)]
+struct AcceptQualities {
+    openmetrics: Option<f64>,
+    prometheus: Option<f64>,
+    protobuf: Option<f64>,
+    text_wildcard: Option<f64>,
+    application_wildcard: Option<f64>,
+    wildcard: Option<f64>,
+}
+
+impl AcceptQualities {
+    fn parse(accept: &str) -> AcceptQualities {
+        let mut qualities = AcceptQualities::default();
+        for media_range in accept.split(',') {
+            let mut params = media_range.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default();
+            let params = params.collect::<Vec<_>>();
+            let quality = params
+                .iter()
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f64>().ok())
+                .unwrap_or(1.0);
+
+            let slot = if media_type.eq_ignore_ascii_case("application/openmetrics-text") {
+                &mut qualities.openmetrics
+            } else if media_type.eq_ignore_ascii_case("text/plain") {
+                &mut qualities.prometheus
+            } else if media_type.eq_ignore_ascii_case("application/vnd.google.protobuf")
+                && params.contains(&"proto=io.prometheus.client.MetricFamily")
+                && params.contains(&"encoding=delimited")
+            {
+                &mut qualities.protobuf
+            } else if media_type.eq_ignore_ascii_case("text/*") {
+                &mut qualities.text_wildcard
+            } else if media_type.eq_ignore_ascii_case("application/*") {
+                &mut qualities.application_wildcard
+            } else if media_type == "*/*" {
+                &mut qualities.wildcard
+            } else {
+                continue;
+            };
+            *slot = Some(slot.map_or(quality, |q| q.max(quality)));
+        }
+        qualities
+    }
+
+    fn text_format(&self) -> Option<ExpositionFormat> {
+        match (self.openmetrics, self.prometheus) {
+            (Some(om), Some(prom)) if prom > om => Some(ExpositionFormat::Prometheus),
+            (Some(om), _) if om > 0.0 => Some(ExpositionFormat::OpenMetrics),
+            (_, Some(prom)) if prom > 0.0 => Some(ExpositionFormat::Prometheus),
+            _ => None,
+        }
+    }
+}
+
 /// Errors that could occur while building or installing a Prometheus recorder/exporter.
 #[derive(Debug, Error)]
 pub enum BuildError {
@@ -60,10 +183,23 @@ pub enum BuildError {
     #[error("failed to parse address as a valid IP address/subnet: {0}")]
     InvalidAllowlistAddress(String),
 
+    /// The TLS configuration of the scrape endpoint, or of the push gateway client, is not valid,
+    /// such as when its certificates or private key could not be loaded.
+    #[error("invalid TLS configuration: {0}")]
+    InvalidTlsConfiguration(String),
+
     /// The given push gateway endpoint is not a valid URI.
     #[error("push gateway endpoint is not valid: {0}")]
     InvalidPushGatewayEndpoint(String),
 
+    /// The given push gateway credentials cannot be sent in an HTTP header.
+    #[error("push gateway credentials are not valid: {0}")]
+    InvalidPushGatewayCredentials(String),
+
+    /// The given Remote Write endpoint is not a valid URI.
+    #[error("remote write endpoint is not valid: {0}")]
+    InvalidRemoteWriteEndpoint(String),
+
     /// No exporter configuration was present.
     ///
     /// This generally only occurs when HTTP listener support is disabled, but no push gateway
@@ -80,8 +216,86 @@ pub enum BuildError {
     ZeroBucketDuration,
 }
 
+/// Values of a counter, and their exemplars, by label set.
+pub type CounterValues = HashMap<Vec<String>, (u64, Option<Exemplar>)>;
+
+/// Distributions of a histogram or summary, and the exemplars of their buckets, by label set.
+pub type DistributionValues = IndexMap<Vec<String>, (Distribution, BucketExemplars)>;
+
 pub struct Snapshot {
-    pub counters: HashMap<String, HashMap<Vec<String>, u64>>,
+    pub counters: HashMap<String, CounterValues>,
     pub gauges: HashMap<String, HashMap<Vec<String>, f64>>,
-    pub distributions: HashMap<String, IndexMap<Vec<String>, Distribution>>,
+    pub distributions: HashMap<String, DistributionValues>,
+    /// Creation times of counters and distributions, as Unix timestamps in seconds, if rendered.
+    pub created: HashMap<String, HashMap<Vec<String>, f64>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accepts_format, prefers_protobuf, ExpositionFormat};
+
+    #[test]
+    fn test_exposition_format_from_accept() {
+        use ExpositionFormat::{OpenMetrics, Prometheus};
+
+        // The header sent by Prometheus itself when scraping.
+        const PROMETHEUS_ACCEPT: &str = concat!(
+            "application/openmetrics-text;version=1.0.0,",
+            "application/openmetrics-text;version=0.0.1;q=0.75,",
+            "text/plain;version=0.0.4;q=0.5,*/*;q=0.1",
+        );
+
+        let cases = &[
+            (PROMETHEUS_ACCEPT, Some(OpenMetrics)),
+            ("text/plain;q=0.9, application/openmetrics-text;q=0.5", Some(Prometheus)),
+            ("text/plain", Some(Prometheus)),
+            ("application/openmetrics-text;q=0, text/plain;q=0.1", Some(Prometheus)),
+            ("*/*", None),
+            ("", None),
+        ];
+
+        for (accept, expected) in cases {
+            assert_eq!(ExpositionFormat::from_accept(accept), *expected, "{accept}");
+        }
+    }
+
+    #[test]
+    fn test_prefers_protobuf() {
+        // The header sent by Prometheus when scraping with native histograms enabled.
+        const PROMETHEUS_ACCEPT: &str = concat!(
+            "application/vnd.google.protobuf;proto=io.prometheus.client.MetricFamily;",
+            "encoding=delimited,application/openmetrics-text;version=1.0.0;q=0.8,",
+            "text/plain;version=0.0.4;q=0.2,*/*;q=0.1",
+        );
+
+        assert!(prefers_protobuf(PROMETHEUS_ACCEPT));
+        assert_eq!(
+            ExpositionFormat::from_accept(PROMETHEUS_ACCEPT),
+            Some(ExpositionFormat::OpenMetrics)
+        );
+        assert!(!prefers_protobuf("application/vnd.google.protobuf;q=0.5,text/plain;q=0.9"));
+        assert!(!prefers_protobuf("application/vnd.google.protobuf"));
+        assert!(!prefers_protobuf("text/plain"));
+    }
+
+    #[test]
+    fn test_accepts_format() {
+        use ExpositionFormat::{OpenMetrics, Prometheus};
+
+        let cases = &[
+            ("text/plain", Prometheus, true),
+            ("text/plain", OpenMetrics, false),
+            ("text/*", Prometheus, true),
+            ("application/*", OpenMetrics, true),
+            ("application/*", Prometheus, false),
+            ("*/*", OpenMetrics, true),
+            ("text/plain;q=0, */*", Prometheus, false),
+            ("application/json", Prometheus, false),
+            ("", Prometheus, false),
+        ];
+
+        for (accept, format, expected) in cases {
+            assert_eq!(accepts_format(accept, *format), *expected, "{accept} {format:?}");
+        }
+    }
 }
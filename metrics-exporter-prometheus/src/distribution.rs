@@ -6,7 +6,7 @@ use quanta::Instant;
 
 use crate::common::Matcher;
 
-use metrics_util::{Histogram, Quantile, Summary};
+use metrics_util::{ExponentialHistogram, Histogram, Quantile, Summary, SummaryWindow};
 
 const DEFAULT_SUMMARY_BUCKET_COUNT: NonZeroU32 = match NonZeroU32::new(3) {
     Some(v) => v,
@@ -14,6 +14,9 @@ const DEFAULT_SUMMARY_BUCKET_COUNT: NonZeroU32 = match NonZeroU32::new(3) {
 };
 const DEFAULT_SUMMARY_BUCKET_DURATION: Duration = Duration::from_secs(20);
 
+/// The highest schema, or scale, supported by Prometheus native histograms.
+const NATIVE_HISTOGRAM_MAX_SCHEMA: i32 = 8;
+
 /// Distribution type.
 #[derive(Clone)]
 pub enum Distribution {
@@ -29,6 +32,13 @@ pub enum Distribution {
     /// requests were faster than 200ms, and 99% of requests were faster than
     /// 1000ms, etc.
     Summary(RollingSummary, Arc<Vec<Quantile>>, f64),
+    /// A Prometheus native histogram.
+    ///
+    /// Counts samples in exponentially sized buckets, whose resolution is lowered as needed to keep
+    /// the number of buckets bounded, so that a single series can expose the whole distribution.
+    /// Native histograms can only be exposed as such via the protobuf format, and are exposed as
+    /// regular histograms, with a bucket for each of their exponential buckets, otherwise.
+    NativeHistogram(ExponentialHistogram),
 }
 
 impl Distribution {
@@ -48,6 +58,14 @@ impl Distribution {
         Distribution::Summary(RollingSummary::new(bucket_count, bucket_duration), quantiles, 0.0)
     }
 
+    /// Creates a native histogram distribution, using at most `max_buckets` buckets for each sign.
+    pub fn new_native_histogram(max_buckets: usize) -> Distribution {
+        Distribution::NativeHistogram(ExponentialHistogram::new(
+            max_buckets,
+            NATIVE_HISTOGRAM_MAX_SCHEMA,
+        ))
+    }
+
     /// Records the given `samples` in the current distribution.
     pub fn record_samples(&mut self, samples: &[(f64, Instant)]) {
         match self {
@@ -60,10 +78,46 @@ impl Distribution {
                     *sum += *sample;
                 }
             }
+            Distribution::NativeHistogram(hist) => {
+                for (sample, _ts) in samples {
+                    hist.record(*sample);
+                }
+            }
         }
     }
 }
 
+/// Gets the cumulative count of each bucket of a native histogram, as a regular histogram would
+/// expose it, along with the upper bound of the bucket.
+///
+/// Negative values come first, followed by zeroes, in a bucket bounded by `0`, which is only present
+/// if there are any such values.  The `+Inf` bucket isn't included.
+pub(crate) fn native_histogram_buckets(histogram: &ExponentialHistogram) -> Vec<(f64, u64)> {
+    let negative = histogram.negative().iter().collect::<Vec<_>>();
+    let mut buckets = Vec::new();
+    let mut cumulative = 0;
+
+    // Negative buckets hold absolute values, so the most negative values are in the last bucket.
+    for (index, count) in negative.into_iter().rev() {
+        cumulative += count;
+        let (lower, _) = histogram.bucket_bounds(index);
+        buckets.push((-lower, cumulative));
+    }
+
+    if cumulative > 0 || histogram.zero_count() > 0 {
+        cumulative += histogram.zero_count();
+        buckets.push((0.0, cumulative));
+    }
+
+    for (index, count) in histogram.positive().iter() {
+        cumulative += count;
+        let (_, upper) = histogram.bucket_bounds(index);
+        buckets.push((upper, cumulative));
+    }
+
+    buckets
+}
+
 /// Builds distributions for metric names based on a set of configured overrides.
 #[derive(Debug)]
 pub struct DistributionBuilder {
@@ -72,6 +126,9 @@ pub struct DistributionBuilder {
     bucket_duration: Option<Duration>,
     bucket_count: Option<NonZeroU32>,
     bucket_overrides: Option<Vec<(Matcher, Vec<f64>)>>,
+    summary_window_overrides: Option<Vec<(Matcher, SummaryWindow)>>,
+    quantile_overrides: Option<Vec<(Matcher, Arc<Vec<Quantile>>)>>,
+    native_histogram_max_buckets: Option<usize>,
 }
 
 impl DistributionBuilder {
@@ -93,9 +150,120 @@ impl DistributionBuilder {
                 matchers.sort_by(|a, b| a.0.cmp(&b.0));
                 matchers
             }),
+            summary_window_overrides: None,
+            quantile_overrides: None,
+            native_histogram_max_buckets: None,
+        }
+    }
+
+    /// Sets the summary windows to use for metrics matching specific patterns.
+    ///
+    /// Metrics which are rendered as summaries, and which match one of the given matchers, use the
+    /// given window instead of the default window.
+    #[must_use]
+    pub fn with_summary_window_overrides<I>(mut self, overrides: I) -> DistributionBuilder
+    where
+        I: IntoIterator<Item = (Matcher, SummaryWindow)>,
+    {
+        let mut matchers = overrides.into_iter().collect::<Vec<_>>();
+        matchers.sort_by(|a, b| a.0.cmp(&b.0));
+        self.summary_window_overrides = Some(matchers);
+        self
+    }
+
+    /// Sets the quantiles to use for metrics matching specific patterns.
+    ///
+    /// Metrics which are rendered as summaries, and which match one of the given matchers, use the
+    /// given quantiles instead of the default quantiles.
+    #[must_use]
+    pub fn with_quantile_overrides<I>(mut self, overrides: I) -> DistributionBuilder
+    where
+        I: IntoIterator<Item = (Matcher, Vec<Quantile>)>,
+    {
+        let mut matchers = overrides
+            .into_iter()
+            .map(|(matcher, quantiles)| (matcher, Arc::new(quantiles)))
+            .collect::<Vec<_>>();
+        matchers.sort_by(|a, b| a.0.cmp(&b.0));
+        self.quantile_overrides = Some(matchers);
+        self
+    }
+
+    /// Sets the maximum number of buckets, for each sign, of native histograms.
+    ///
+    /// When set, metrics are exposed as native histograms, unless buckets were set specifically for
+    /// them.
+    #[must_use]
+    pub fn with_native_histograms(mut self, max_buckets: usize) -> DistributionBuilder {
+        self.native_histogram_max_buckets = Some(max_buckets);
+        self
+    }
+
+    /// Sets the buckets to use for metrics matching a specific pattern, replacing any buckets
+    /// previously set for the same pattern.
+    pub fn set_bucket_override(&mut self, matcher: Matcher, buckets: Vec<f64>) {
+        let overrides = self.bucket_overrides.get_or_insert_with(Vec::new);
+        match overrides.binary_search_by(|(existing, _)| existing.cmp(&matcher)) {
+            Ok(i) => overrides[i].1 = buckets,
+            Err(i) => overrides.insert(i, (matcher, buckets)),
+        }
+    }
+
+    /// Removes the buckets set for metrics matching a specific pattern.
+    ///
+    /// Returns `true` if buckets were set for the pattern.
+    pub fn remove_bucket_override(&mut self, matcher: &Matcher) -> bool {
+        let Some(overrides) = self.bucket_overrides.as_mut() else {
+            return false;
+        };
+        match overrides.binary_search_by(|(existing, _)| existing.cmp(matcher)) {
+            Ok(i) => {
+                overrides.remove(i);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Sets the quantiles to use for metrics matching a specific pattern, replacing any quantiles
+    /// previously set for the same pattern.
+    pub fn set_quantile_override(&mut self, matcher: Matcher, quantiles: Vec<Quantile>) {
+        let overrides = self.quantile_overrides.get_or_insert_with(Vec::new);
+        match overrides.binary_search_by(|(existing, _)| existing.cmp(&matcher)) {
+            Ok(i) => overrides[i].1 = Arc::new(quantiles),
+            Err(i) => overrides.insert(i, (matcher, Arc::new(quantiles))),
+        }
+    }
+
+    /// Removes the quantiles set for metrics matching a specific pattern.
+    ///
+    /// Returns `true` if quantiles were set for the pattern.
+    pub fn remove_quantile_override(&mut self, matcher: &Matcher) -> bool {
+        let Some(overrides) = self.quantile_overrides.as_mut() else {
+            return false;
+        };
+        match overrides.binary_search_by(|(existing, _)| existing.cmp(matcher)) {
+            Ok(i) => {
+                overrides.remove(i);
+                true
+            }
+            Err(_) => false,
         }
     }
 
+    /// Returns the quantiles of summaries for the given metric key.
+    pub fn get_quantiles(&self, name: &str) -> Arc<Vec<Quantile>> {
+        if let Some(ref overrides) = self.quantile_overrides {
+            for (matcher, quantiles) in overrides {
+                if matcher.matches(name) {
+                    return quantiles.clone();
+                }
+            }
+        }
+
+        self.quantiles.clone()
+    }
+
     /// Returns a distribution for the given metric key.
     pub fn get_distribution(&self, name: &str) -> Distribution {
         if let Some(ref overrides) = self.bucket_overrides {
@@ -106,19 +274,42 @@ impl DistributionBuilder {
             }
         }
 
+        if let Some(max_buckets) = self.native_histogram_max_buckets {
+            return Distribution::new_native_histogram(max_buckets);
+        }
+
         if let Some(ref buckets) = self.buckets {
             return Distribution::new_histogram(buckets);
         }
 
+        self.get_summary(name)
+    }
+
+    /// Returns a summary for the given metric key, regardless of any buckets configured for it.
+    ///
+    /// Used for metrics registered as summaries, rather than as histograms.
+    pub fn get_summary(&self, name: &str) -> Distribution {
+        if let Some(ref overrides) = self.summary_window_overrides {
+            for (matcher, window) in overrides {
+                if matcher.matches(name) {
+                    return Distribution::new_summary(
+                        self.get_quantiles(name),
+                        window.bucket_duration(),
+                        window.bucket_count(),
+                    );
+                }
+            }
+        }
+
         let b_duration = self.bucket_duration.map_or(DEFAULT_SUMMARY_BUCKET_DURATION, |d| d);
         let b_count = self.bucket_count.map_or(DEFAULT_SUMMARY_BUCKET_COUNT, |c| c);
 
-        Distribution::new_summary(self.quantiles.clone(), b_duration, b_count)
+        Distribution::new_summary(self.get_quantiles(name), b_duration, b_count)
     }
 
     /// Returns the distribution type for the given metric key.
     pub fn get_distribution_type(&self, name: &str) -> &str {
-        if self.buckets.is_some() {
+        if self.buckets.is_some() || self.native_histogram_max_buckets.is_some() {
             return "histogram";
         }
 
@@ -182,6 +373,11 @@ impl RollingSummary {
         }
     }
 
+    /// Create a new `RollingSummary` covering the given window.
+    pub fn with_window(window: SummaryWindow) -> RollingSummary {
+        RollingSummary::new(window.bucket_count(), window.bucket_duration())
+    }
+
     /// Add a sample `value` to the `RollingSummary` at the time `now`.
     ///
     /// Any values that expire at the `value_ts` are removed from the `RollingSummary`.
@@ -281,6 +477,7 @@ impl RollingSummary {
 mod tests {
     use super::*;
 
+    use metrics_util::parse_quantiles;
     use quanta::Clock;
 
     #[test]
@@ -293,6 +490,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::float_cmp)]
     fn empty_snapshot() {
         let (clock, _mock) = Clock::mock();
         let summary = RollingSummary::default();
@@ -305,6 +503,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::float_cmp)]
     fn snapshot() {
         let (clock, mock) = Clock::mock();
         mock.increment(Duration::from_secs(3600));
@@ -367,6 +566,76 @@ mod tests {
         assert_eq!(3, summary.buckets().len());
     }
 
+    #[test]
+    fn summary_window_overrides() {
+        let window =
+            SummaryWindow::from_duration(Duration::from_secs(900), NonZeroU32::new(15).unwrap());
+        let mut overrides = HashMap::new();
+        overrides.insert(Matcher::Suffix("_slow".to_owned()), window);
+
+        let builder = DistributionBuilder::new(parse_quantiles(&[0.5]), None, None, None, None)
+            .with_summary_window_overrides(overrides);
+
+        match builder.get_distribution("requests_slow") {
+            Distribution::Summary(summary, _, _) => {
+                assert_eq!(summary.max_buckets, 15);
+                assert_eq!(summary.max_bucket_duration, Duration::from_secs(900));
+            }
+            _ => panic!("expected a summary"),
+        }
+
+        match builder.get_distribution("requests") {
+            Distribution::Summary(summary, _, _) => {
+                assert_eq!(summary.max_bucket_duration, Duration::from_secs(60));
+            }
+            _ => panic!("expected a summary"),
+        }
+    }
+
+    #[test]
+    fn bucket_override_updates() {
+        let matcher = Matcher::Prefix("requests".to_owned());
+        let mut builder = DistributionBuilder::new(parse_quantiles(&[0.5]), None, None, None, None);
+
+        builder.set_bucket_override(matcher.clone(), vec![1.0]);
+        builder.set_bucket_override(matcher.clone(), vec![1.0, 2.0]);
+        match builder.get_distribution("requests_total") {
+            Distribution::Histogram(histogram) => assert_eq!(histogram.buckets().len(), 2),
+            _ => panic!("expected a histogram"),
+        }
+
+        assert!(builder.remove_bucket_override(&matcher));
+        assert!(!builder.remove_bucket_override(&matcher));
+        assert_eq!(builder.get_distribution_type("requests_total"), "summary");
+    }
+
+    #[test]
+    fn quantile_override_updates() {
+        fn values(quantiles: &[Quantile]) -> Vec<f64> {
+            quantiles.iter().map(Quantile::value).collect()
+        }
+
+        let matcher = Matcher::Suffix("latency".to_owned());
+        let mut builder = DistributionBuilder::new(parse_quantiles(&[0.5]), None, None, None, None)
+            .with_quantile_overrides([(
+                Matcher::Full("db_latency".to_owned()),
+                parse_quantiles(&[0.9]),
+            )]);
+
+        builder.set_quantile_override(matcher.clone(), parse_quantiles(&[0.999]));
+        assert_eq!(values(&builder.get_quantiles("db_latency")), vec![0.9]);
+        assert_eq!(values(&builder.get_quantiles("http_latency")), vec![0.999]);
+        assert_eq!(values(&builder.get_quantiles("requests")), vec![0.5]);
+        match builder.get_distribution("http_latency") {
+            Distribution::Summary(_, quantiles, _) => assert_eq!(values(&quantiles), vec![0.999]),
+            _ => panic!("expected a summary"),
+        }
+
+        assert!(builder.remove_quantile_override(&matcher));
+        assert!(!builder.remove_quantile_override(&matcher));
+        assert_eq!(values(&builder.get_quantiles("http_latency")), vec![0.5]);
+    }
+
     #[test]
     fn add_value_ts_before_first_bucket() {
         let (clock, mock) = Clock::mock();
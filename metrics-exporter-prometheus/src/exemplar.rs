@@ -0,0 +1,144 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use metrics::Label;
+
+use crate::formatting::{sanitize_label_key, sanitize_label_value};
+
+/// The maximum combined length, in characters, of the label names and values of an exemplar.
+///
+/// This is the limit set by the OpenMetrics specification, and exemplars over it are dropped.
+const MAX_EXEMPLAR_LABELS_LENGTH: usize = 128;
+
+/// A hook returning the labels of the exemplar to attach to a measurement made without one.
+pub type ExemplarHook = Arc<dyn Fn() -> Option<Vec<Label>> + Send + Sync>;
+
+/// An exemplar of a measurement, rendered in the OpenMetrics text format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exemplar {
+    labels: Vec<(String, String)>,
+    value: f64,
+    timestamp: f64,
+    rendered: String,
+}
+
+impl Exemplar {
+    /// Creates an exemplar with the given labels, for a measurement of `value` taken now.
+    ///
+    /// If the labels exceed the length allowed by the OpenMetrics specification, `None` is returned.
+    pub fn new(labels: &[Label], value: f64) -> Option<Exemplar> {
+        let length: usize = labels
+            .iter()
+            .map(|label| label.key().chars().count() + label.value().chars().count())
+            .sum();
+        if length > MAX_EXEMPLAR_LABELS_LENGTH {
+            return None;
+        }
+
+        let labels = labels
+            .iter()
+            .map(|label| (sanitize_label_key(label.key()), label.value().to_string()))
+            .collect::<Vec<_>>();
+
+        let mut rendered = String::from("{");
+        for (i, (key, value)) in labels.iter().enumerate() {
+            if i > 0 {
+                rendered.push(',');
+            }
+            rendered.push_str(key);
+            rendered.push_str("=\"");
+            rendered.push_str(&sanitize_label_value(value));
+            rendered.push('"');
+        }
+
+        let timestamp =
+            SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |since| since.as_secs_f64());
+        let _ = write!(rendered, "}} {value} {timestamp:.3}");
+
+        Some(Exemplar { labels, value, timestamp, rendered })
+    }
+
+    /// Gets the labels of the exemplar, with their keys sanitized.
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.labels
+    }
+
+    /// Gets the value of the measurement.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Gets the time the measurement was taken at, in seconds since the Unix epoch.
+    pub fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+
+    /// Gets the exemplar as rendered in the OpenMetrics text format, without the leading `#`.
+    pub fn as_str(&self) -> &str {
+        &self.rendered
+    }
+}
+
+/// The most recent exemplar of each bucket of a histogram.
+#[derive(Clone, Debug, Default)]
+pub struct BucketExemplars {
+    // One slot per bucket bound, followed by one for the `+Inf` bucket.
+    exemplars: Vec<Option<Exemplar>>,
+}
+
+impl BucketExemplars {
+    /// Records the given exemplars into the buckets with the given upper bounds, replacing the
+    /// previous exemplar of each bucket they fall into.
+    pub fn record<I>(&mut self, bounds: &[f64], exemplars: I)
+    where
+        I: IntoIterator<Item = Exemplar>,
+    {
+        self.exemplars.resize(bounds.len() + 1, None);
+        for exemplar in exemplars {
+            let bucket =
+                bounds.iter().position(|bound| exemplar.value <= *bound).unwrap_or(bounds.len());
+            self.exemplars[bucket] = Some(exemplar);
+        }
+    }
+
+    /// Iterates over the exemplar of every bucket which has one, in order of bucket.
+    pub fn iter(&self) -> impl Iterator<Item = &Exemplar> {
+        self.exemplars.iter().flatten()
+    }
+
+    /// Gets the exemplar of the given bucket, if any.
+    ///
+    /// The `+Inf` bucket comes after the buckets of each bound.
+    pub fn get(&self, bucket: usize) -> Option<&Exemplar> {
+        self.exemplars.get(bucket).and_then(Option::as_ref)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::Label;
+
+    use super::{BucketExemplars, Exemplar};
+
+    #[test]
+    fn test_exemplar() {
+        let exemplar = Exemplar::new(&[Label::new("trace_id", "a\"b")], 0.5).unwrap();
+        assert!(exemplar.as_str().starts_with("{trace_id=\"a\\\"b\"} 0.5 "));
+
+        let too_long = Label::new("trace_id", "f".repeat(121));
+        assert_eq!(Exemplar::new(&[too_long], 1.0), None);
+    }
+
+    #[test]
+    fn test_bucket_exemplars() {
+        let exemplar = |value| Exemplar::new(&[Label::new("value", "x")], value).unwrap();
+
+        let mut exemplars = BucketExemplars::default();
+        exemplars.record(&[1.0, 10.0], vec![exemplar(0.5), exemplar(10.0), exemplar(11.0)]);
+        exemplars.record(&[1.0, 10.0], vec![exemplar(2.0)]);
+
+        let values = (0..4).map(|i| exemplars.get(i).map(Exemplar::value)).collect::<Vec<_>>();
+        assert_eq!(values, vec![Some(0.5), Some(2.0), Some(11.0), None]);
+    }
+}
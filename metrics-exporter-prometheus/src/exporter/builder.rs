@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 #[cfg(feature = "push-gateway")]
 use std::convert::TryFrom;
 #[cfg(feature = "http-listener")]
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::num::NonZeroU32;
-use std::sync::RwLock;
+#[cfg(any(feature = "tls", feature = "push-gateway"))]
+use std::path::Path;
+#[cfg(all(feature = "http-listener", unix))]
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
 #[cfg(any(feature = "http-listener", feature = "push-gateway"))]
 use std::thread;
 use std::time::Duration;
@@ -14,39 +18,82 @@ use hyper::Uri;
 use indexmap::IndexMap;
 #[cfg(feature = "http-listener")]
 use ipnet::IpNet;
+use metrics::{Key, Label};
 use quanta::Clock;
 
 use metrics_util::{
     parse_quantiles,
     registry::{GenerationalStorage, Recency, Registry},
-    MetricKindMask, Quantile,
+    MetricKindMask, Quantile, SummaryWindow,
 };
 
-use crate::common::Matcher;
+use crate::common::{ExpositionFormat, Matcher};
 use crate::distribution::DistributionBuilder;
+use crate::exemplar::ExemplarHook;
+#[cfg(feature = "push-gateway")]
+use crate::formatting::sanitize_label_key;
+use crate::formatting::sanitize_metric_name;
 use crate::recorder::{Inner, PrometheusRecorder};
 use crate::registry::AtomicStorage;
+use crate::self_metrics::SelfMetrics;
 use crate::{common::BuildError, PrometheusHandle};
 
+#[cfg(feature = "http-listener")]
+use super::http_listener::HealthCheck;
+#[cfg(feature = "push-gateway")]
+use super::push_gateway::PushSettings;
+#[cfg(feature = "tls")]
+use super::tls::TlsConfig;
 use super::ExporterConfig;
 #[cfg(any(feature = "http-listener", feature = "push-gateway"))]
 use super::ExporterFuture;
 
 /// Builder for creating and installing a Prometheus recorder/exporter.
+#[allow(clippy::struct_excessive_bools)]
 pub struct PrometheusBuilder {
     #[cfg_attr(not(any(feature = "http-listener", feature = "push-gateway")), allow(dead_code))]
     exporter_config: ExporterConfig,
     #[cfg(feature = "http-listener")]
     allowed_addresses: Option<Vec<IpNet>>,
+    #[cfg(feature = "http-listener")]
+    negotiate_exposition_format: bool,
+    #[cfg(feature = "http-listener")]
+    reject_unacceptable_formats: bool,
+    #[cfg(feature = "http-listener")]
+    authorization: Vec<String>,
+    #[cfg(feature = "http-listener")]
+    scrape_path: Option<String>,
+    #[cfg(feature = "http-listener")]
+    health_routes: Vec<(String, HealthCheck)>,
+    #[cfg(feature = "compression")]
+    compression: bool,
+    #[cfg(feature = "tls")]
+    tls: TlsConfig,
+    #[cfg(feature = "push-gateway")]
+    push: PushSettings,
+    #[cfg(feature = "remote-write")]
+    remote_write_batch_size: usize,
+    exposition_format: ExpositionFormat,
     quantiles: Vec<Quantile>,
     bucket_duration: Option<Duration>,
     bucket_count: Option<NonZeroU32>,
     buckets: Option<Vec<f64>>,
     bucket_overrides: Option<HashMap<Matcher, Vec<f64>>>,
+    summary_window_overrides: Option<HashMap<Matcher, SummaryWindow>>,
+    quantile_overrides: Option<HashMap<Matcher, Vec<Quantile>>>,
+    native_histogram_max_buckets: Option<usize>,
     idle_timeout: Option<Duration>,
     upkeep_timeout: Duration,
+    spawn_upkeep_task: bool,
+    upkeep_removes_idle: bool,
     recency_mask: MetricKindMask,
+    idle_timeout_overrides: Vec<(Matcher, MetricKindMask, Option<Duration>)>,
     global_labels: Option<IndexMap<String, String>>,
+    exemplar_hook: Option<ExemplarHook>,
+    self_metrics: bool,
+    total_suffix: bool,
+    unit_suffix: bool,
+    created_timestamps: bool,
 }
 
 impl PrometheusBuilder {
@@ -67,21 +114,52 @@ impl PrometheusBuilder {
             exporter_config,
             #[cfg(feature = "http-listener")]
             allowed_addresses: None,
+            #[cfg(feature = "http-listener")]
+            negotiate_exposition_format: false,
+            #[cfg(feature = "http-listener")]
+            reject_unacceptable_formats: false,
+            #[cfg(feature = "http-listener")]
+            authorization: Vec::new(),
+            #[cfg(feature = "http-listener")]
+            scrape_path: None,
+            #[cfg(feature = "http-listener")]
+            health_routes: Vec::new(),
+            #[cfg(feature = "compression")]
+            compression: true,
+            #[cfg(feature = "tls")]
+            tls: TlsConfig::default(),
+            #[cfg(feature = "push-gateway")]
+            push: PushSettings::default(),
+            #[cfg(feature = "remote-write")]
+            remote_write_batch_size: 2000,
+            exposition_format: ExpositionFormat::default(),
             quantiles,
             bucket_duration: None,
             bucket_count: None,
             buckets: None,
             bucket_overrides: None,
+            summary_window_overrides: None,
+            quantile_overrides: None,
+            native_histogram_max_buckets: None,
             idle_timeout: None,
             upkeep_timeout,
+            spawn_upkeep_task: true,
+            upkeep_removes_idle: false,
             recency_mask: MetricKindMask::NONE,
+            idle_timeout_overrides: Vec::new(),
             global_labels: None,
+            exemplar_hook: None,
+            self_metrics: false,
+            total_suffix: false,
+            unit_suffix: false,
+            created_timestamps: false,
         }
     }
 
     /// Configures the exporter to expose an HTTP listener that functions as a [scrape endpoint].
     ///
-    /// The HTTP listener that is spawned will respond to GET requests on any request path.
+    /// The HTTP listener that is spawned will respond to GET requests on any request path, unless a
+    /// path is set via [`set_scrape_path`][Self::set_scrape_path].
     ///
     /// Running in HTTP listener mode is mutually exclusive with the push gateway i.e. enabling the
     /// HTTP listener will disable the push gateway, and vise versa.
@@ -97,6 +175,28 @@ impl PrometheusBuilder {
         self
     }
 
+    /// Configures the exporter to expose an HTTP listener, that functions as a [scrape endpoint], on a
+    /// Unix domain socket at the given path.
+    ///
+    /// This is useful when scrapes are made by an agent running alongside the application, such as
+    /// a sidecar, and binding a TCP port is undesirable.  Any existing socket at the path is removed
+    /// before binding.  The IP-based allowlist, if any, does not apply to connections made over the
+    /// socket, so access to it should be controlled via its file permissions instead.
+    ///
+    /// Running in this mode is mutually exclusive with the push gateway and the TCP-based HTTP
+    /// listener.
+    ///
+    /// Defaults to disabled.
+    ///
+    /// [scrape endpoint]: https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+    #[cfg(all(feature = "http-listener", unix))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "http-listener", unix))))]
+    #[must_use]
+    pub fn with_http_uds_listener(mut self, path: impl Into<PathBuf>) -> Self {
+        self.exporter_config = ExporterConfig::HttpUdsListener { path: path.into() };
+        self
+    }
+
     /// Configures the exporter to push periodic requests to a Prometheus [push gateway].
     ///
     /// Running in push gateway mode is mutually exclusive with the HTTP listener i.e. enabling the
@@ -133,6 +233,192 @@ impl PrometheusBuilder {
         Ok(self)
     }
 
+    /// Configures the exporter to push metrics to an endpoint speaking the Prometheus [Remote Write]
+    /// protocol, such as Grafana Mimir, `VictoriaMetrics` or Thanos Receive, every `interval`.
+    ///
+    /// This allows sending metrics to such backends without running a Prometheus server alongside
+    /// the application.  Series are sent in batches of at most
+    /// [`set_remote_write_batch_size`][Self::set_remote_write_batch_size] series, one batch at a
+    /// time, and metrics are only snapshotted again once the previous snapshot was sent, so a slow
+    /// backend delays pushes rather than letting them pile up.  Failed requests are retried as for
+    /// the push gateway, and, if the backend stays unavailable, the rest of the snapshot is dropped,
+    /// as the next one supersedes it.
+    ///
+    /// The bearer token, TLS and retry settings of the push gateway also apply to this endpoint.
+    ///
+    /// Running in this mode is mutually exclusive with the push gateway and the HTTP listener.
+    ///
+    /// Defaults to disabled.
+    ///
+    /// ## Errors
+    ///
+    /// If the given endpoint cannot be parsed into a valid URI, an error variant will be
+    /// returned describing the error.
+    ///
+    /// [Remote Write]: https://prometheus.io/docs/concepts/remote_write_spec/
+    #[cfg(feature = "remote-write")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "remote-write")))]
+    pub fn with_remote_write<T>(
+        mut self,
+        endpoint: T,
+        interval: Duration,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> Result<Self, BuildError>
+    where
+        T: AsRef<str>,
+    {
+        self.exporter_config = ExporterConfig::RemoteWrite {
+            endpoint: Uri::try_from(endpoint.as_ref())
+                .map_err(|e| BuildError::InvalidRemoteWriteEndpoint(e.to_string()))?,
+            interval,
+            username,
+            password,
+        };
+
+        Ok(self)
+    }
+
+    /// Sets the maximum number of series sent in a single Remote Write request.
+    ///
+    /// Histograms and summaries are made of several series, one per bucket or quantile, along with
+    /// their sum and count.
+    ///
+    /// Defaults to 2000.
+    #[cfg(feature = "remote-write")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "remote-write")))]
+    #[must_use]
+    pub fn set_remote_write_batch_size(mut self, max_series: usize) -> Self {
+        self.remote_write_batch_size = max_series;
+        self
+    }
+
+    /// Configures the exporter to authenticate to the push gateway with the given bearer token.
+    ///
+    /// The token is sent in the `Authorization` header of every request, instead of any username
+    /// and password given to [`with_push_gateway`][Self::with_push_gateway].
+    ///
+    /// Defaults to disabled.
+    #[cfg(feature = "push-gateway")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "push-gateway")))]
+    #[must_use]
+    pub fn with_push_gateway_bearer_token(mut self, token: &str) -> Self {
+        self.push.bearer_token = Some(token.to_string());
+        self
+    }
+
+    /// Configures the exporter to trust the CA certificates in the PEM file at the given path when
+    /// connecting to the push gateway over TLS, in addition to the system's trusted certificates.
+    ///
+    /// Defaults to only trusting the system's trusted certificates.
+    ///
+    /// ## Errors
+    ///
+    /// If the certificates cannot be read from the given file, an error variant will be returned
+    /// describing the error.
+    #[cfg(feature = "push-gateway")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "push-gateway")))]
+    pub fn with_push_gateway_tls_root_certificates<P>(mut self, ca: P) -> Result<Self, BuildError>
+    where
+        P: AsRef<Path>,
+    {
+        self.push.add_root_certificates(ca.as_ref())?;
+        Ok(self)
+    }
+
+    /// Configures the exporter to present a client certificate when connecting to the push gateway
+    /// over TLS.
+    ///
+    /// `cert` is the path to a PEM file holding the certificate chain, leaf certificate first, and
+    /// `key` is the path to a PEM file holding its private key, in PKCS #8 format.
+    ///
+    /// Defaults to disabled.
+    ///
+    /// ## Errors
+    ///
+    /// If the certificates or private key cannot be read from the given files, an error variant will
+    /// be returned describing the error.
+    #[cfg(feature = "push-gateway")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "push-gateway")))]
+    pub fn with_push_gateway_tls_identity<C, K>(
+        mut self,
+        cert: C,
+        key: K,
+    ) -> Result<Self, BuildError>
+    where
+        C: AsRef<Path>,
+        K: AsRef<Path>,
+    {
+        self.push.set_identity(cert.as_ref(), key.as_ref())?;
+        Ok(self)
+    }
+
+    /// Adds a label to the grouping key under which metrics are pushed to the push gateway.
+    ///
+    /// Grouping labels are appended to the path of the push gateway endpoint, after the job given to
+    /// [`with_push_gateway`][Self::with_push_gateway], in the order they were added.  Values which
+    /// can't be used as is in a path are encoded in base64, as supported by the push gateway.
+    ///
+    /// Defaults to no grouping labels.
+    #[cfg(feature = "push-gateway")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "push-gateway")))]
+    #[must_use]
+    pub fn add_push_gateway_grouping_label<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: AsRef<str>,
+        V: Into<String>,
+    {
+        let key = sanitize_label_key(key.as_ref());
+        self.push.grouping_labels.push((key, value.into()));
+        self
+    }
+
+    /// Configures whether metrics are pushed to the push gateway with `POST` requests, instead of
+    /// `PUT` requests.
+    ///
+    /// `PUT` requests replace all the metrics in the group they're pushed to, while `POST` requests
+    /// only replace the metrics with the same names as the pushed ones.
+    ///
+    /// Defaults to disabled, pushing with `PUT` requests.
+    #[cfg(feature = "push-gateway")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "push-gateway")))]
+    #[must_use]
+    pub fn set_push_gateway_http_post(mut self, enabled: bool) -> Self {
+        self.push.use_http_post = enabled;
+        self
+    }
+
+    /// Sets the maximum number of times a failed request to the push gateway is retried.
+    ///
+    /// Requests are retried when they fail to be sent, or when the push gateway responds with a
+    /// server error or a 429 Too Many Requests status, waiting half a second before the first retry
+    /// and twice as long before each further one, up to the push interval.
+    ///
+    /// Defaults to 3.
+    #[cfg(feature = "push-gateway")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "push-gateway")))]
+    #[must_use]
+    pub fn set_push_gateway_retries(mut self, max_retries: u32) -> Self {
+        self.push.max_retries = max_retries;
+        self
+    }
+
+    /// Configures whether the exporter deletes its metrics from the push gateway on shutdown.
+    ///
+    /// When enabled, calling [`Recorder::shutdown`](metrics::Recorder::shutdown) on the recorder
+    /// deletes the group the exporter pushed metrics to from the push gateway, instead of pushing a
+    /// final snapshot, before the exporter stops.  This avoids leaving stale metrics in the push
+    /// gateway once the process exits.
+    ///
+    /// Defaults to disabled, pushing a final snapshot on shutdown.
+    #[cfg(feature = "push-gateway")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "push-gateway")))]
+    #[must_use]
+    pub fn set_push_gateway_delete_on_shutdown(mut self, enabled: bool) -> Self {
+        self.push.delete_on_shutdown = enabled;
+        self
+    }
+
     /// Adds an IP address or subnet to the allowlist for the scrape endpoint.
     ///
     /// If a client makes a request to the scrape endpoint and their IP is not present in the
@@ -166,6 +452,197 @@ impl PrometheusBuilder {
         Ok(self)
     }
 
+    /// Configures the scrape endpoint to require HTTP Basic authentication with the given username
+    /// and password.
+    ///
+    /// Requests without valid credentials receive a 401 Unauthorized response, except for requests
+    /// to `/health`.  If called multiple times, or along with
+    /// [`with_bearer_token`][Self::with_bearer_token], requests presenting any of the configured
+    /// credentials are allowed.
+    ///
+    /// Credentials are sent in the clear unless TLS is enabled, or the endpoint is otherwise only
+    /// reachable over a trusted network.
+    ///
+    /// Defaults to disabled.
+    #[cfg(feature = "http-listener")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http-listener")))]
+    #[must_use]
+    pub fn with_basic_auth(mut self, username: &str, password: &str) -> Self {
+        use base64::{prelude::BASE64_STANDARD, Engine};
+
+        let credentials = BASE64_STANDARD.encode(format!("{username}:{password}"));
+        self.authorization.push(format!("Basic {credentials}"));
+        self
+    }
+
+    /// Configures the scrape endpoint to require the given bearer token.
+    ///
+    /// Requests without the token in their `Authorization` header receive a 401 Unauthorized
+    /// response, except for requests to `/health`.  As with
+    /// [`with_basic_auth`][Self::with_basic_auth], requests presenting any of the configured
+    /// credentials are allowed.
+    ///
+    /// Defaults to disabled.
+    #[cfg(feature = "http-listener")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http-listener")))]
+    #[must_use]
+    pub fn with_bearer_token(mut self, token: &str) -> Self {
+        self.authorization.push(format!("Bearer {token}"));
+        self
+    }
+
+    /// Sets the path on which the HTTP listener serves metrics, such as `/metrics`.
+    ///
+    /// Requests to any other path, other than `/health` and the routes added via
+    /// [`add_health_route`][Self::add_health_route], receive a 404 Not Found response.
+    ///
+    /// Defaults to serving metrics on any request path.
+    #[cfg(feature = "http-listener")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http-listener")))]
+    #[must_use]
+    pub fn set_scrape_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.scrape_path = Some(path.into());
+        self
+    }
+
+    /// Adds a route to the HTTP listener, such as `/healthz` or `/ready`, answered by the given
+    /// check.
+    ///
+    /// Requests to the route receive a 200 OK response when the check returns `true`, and a 503
+    /// Service Unavailable response otherwise, which makes it suitable for liveness and readiness
+    /// probes.  Like `/health`, the route doesn't require the credentials configured via
+    /// [`with_basic_auth`][Self::with_basic_auth] or [`with_bearer_token`][Self::with_bearer_token].
+    ///
+    /// The check is called on every request to the route, so it should be cheap.  Routes take
+    /// precedence over the scrape path, and adding a route for an existing path replaces its check.
+    #[cfg(feature = "http-listener")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http-listener")))]
+    #[must_use]
+    pub fn add_health_route<P, F>(mut self, path: P, check: F) -> Self
+    where
+        P: Into<String>,
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        let path = path.into();
+        self.health_routes.retain(|(route, _)| *route != path);
+        self.health_routes.push((path, Arc::new(check)));
+        self
+    }
+
+    /// Configures the scrape endpoint to only accept connections over TLS.
+    ///
+    /// `cert` is the path to a PEM file holding the certificate chain presented to clients, leaf
+    /// certificate first, and `key` is the path to a PEM file holding its private key.
+    ///
+    /// Defaults to disabled.
+    ///
+    /// ## Errors
+    ///
+    /// If the certificates or private key cannot be read from the given files, an error variant will
+    /// be returned describing the error.
+    #[cfg(feature = "tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+    pub fn with_tls<C, K>(mut self, cert: C, key: K) -> Result<Self, BuildError>
+    where
+        C: AsRef<Path>,
+        K: AsRef<Path>,
+    {
+        self.tls.set_identity(cert.as_ref(), key.as_ref())?;
+        Ok(self)
+    }
+
+    /// Configures the scrape endpoint to require clients to present a certificate, signed by one of
+    /// the CA certificates in the PEM file at the given path.
+    ///
+    /// This requires TLS to be enabled via [`with_tls`][Self::with_tls], and connections from
+    /// clients which don't present a valid certificate are rejected during the TLS handshake.
+    ///
+    /// Defaults to disabled.
+    ///
+    /// ## Errors
+    ///
+    /// If the CA certificates cannot be read from the given file, an error variant will be returned
+    /// describing the error.
+    #[cfg(feature = "tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+    pub fn with_tls_client_auth<P>(mut self, ca: P) -> Result<Self, BuildError>
+    where
+        P: AsRef<Path>,
+    {
+        self.tls.set_client_roots(ca.as_ref())?;
+        Ok(self)
+    }
+
+    /// Sets the format in which metrics are rendered.
+    ///
+    /// This is the format used by [`PrometheusHandle::render`], when pushing to a push gateway, and
+    /// when responding to scrapes, unless the format is negotiated via
+    /// [`negotiate_exposition_format`][Self::negotiate_exposition_format].
+    ///
+    /// Defaults to the Prometheus exposition format.
+    #[must_use]
+    pub fn set_exposition_format(mut self, format: ExpositionFormat) -> Self {
+        self.exposition_format = format;
+        self
+    }
+
+    /// Configures the scrape endpoint to select the exposition format based on the `Accept` header
+    /// of each request.
+    ///
+    /// Requests which accept neither format, or which have no `Accept` header, are answered in the
+    /// format set via [`set_exposition_format`][Self::set_exposition_format].  Requests which
+    /// prefer the Prometheus protobuf format are answered in it, which is how native histograms,
+    /// configured via [`set_native_histograms`][Self::set_native_histograms], are scraped.
+    ///
+    /// Recent versions of Prometheus prefer the OpenMetrics format, in which counters whose name
+    /// lacks a `_total` suffix are exposed with one, so enabling this may change the names of the
+    /// series stored by Prometheus.
+    ///
+    /// Defaults to disabled.
+    #[cfg(feature = "http-listener")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http-listener")))]
+    #[must_use]
+    pub fn negotiate_exposition_format(mut self, enabled: bool) -> Self {
+        self.negotiate_exposition_format = enabled;
+        self
+    }
+
+    /// Configures the scrape endpoint to reject requests whose `Accept` header allows none of the
+    /// supported formats.
+    ///
+    /// When enabled, along with [`negotiate_exposition_format`][Self::negotiate_exposition_format],
+    /// requests with an `Accept` header which neither names a supported format nor allows the
+    /// default format via a wildcard, such as `*/*`, receive a 406 Not Acceptable response.
+    /// Requests without an `Accept` header are always answered in the default format.
+    ///
+    /// Defaults to disabled, falling back to the format set via
+    /// [`set_exposition_format`][Self::set_exposition_format].
+    #[cfg(feature = "http-listener")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http-listener")))]
+    #[must_use]
+    pub fn reject_unacceptable_formats(mut self, enabled: bool) -> Self {
+        self.reject_unacceptable_formats = enabled;
+        self
+    }
+
+    /// Configures whether the scrape endpoint compresses its responses.
+    ///
+    /// When enabled, responses are compressed with gzip, or zstd if the `compression-zstd` feature
+    /// is enabled, when the `Accept-Encoding` header of the request allows it.  Small responses,
+    /// where the savings would be negligible, are never compressed.
+    ///
+    /// Defaults to enabled.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[must_use]
+    pub fn set_response_compression(mut self, enabled: bool) -> Self {
+        self.compression = enabled;
+        self
+    }
+
     /// Sets the quantiles to use when rendering histograms.
     ///
     /// Quantiles represent a scale of 0 to 1, where percentiles represent a scale of 1 to 100, so
@@ -190,6 +667,35 @@ impl PrometheusBuilder {
         Ok(self)
     }
 
+    /// Sets the quantiles to use when rendering histograms for a specific pattern.
+    ///
+    /// The match pattern can be a full match (equality), prefix match, or suffix match, and
+    /// matchers are applied in the same order as described in
+    /// [`set_buckets_for_metric`][Self::set_buckets_for_metric].  Matching metrics use the given
+    /// quantiles instead of the ones set via [`set_quantiles`][Self::set_quantiles], which allows
+    /// rendering more quantiles, such as the 99.99th percentile, for a handful of critical metrics.
+    ///
+    /// This only affects metrics which are rendered as summaries.  Quantiles can also be set, or
+    /// removed, for a pattern once the recorder is running, via
+    /// [`PrometheusHandle::set_quantiles_for_metric`].
+    ///
+    /// ## Errors
+    ///
+    /// If `quantiles` is empty, an error variant will be thrown.
+    pub fn set_quantiles_for_metric(
+        mut self,
+        matcher: Matcher,
+        quantiles: &[f64],
+    ) -> Result<Self, BuildError> {
+        if quantiles.is_empty() {
+            return Err(BuildError::EmptyBucketsOrQuantiles);
+        }
+
+        let overrides = self.quantile_overrides.get_or_insert_with(HashMap::new);
+        overrides.insert(matcher.sanitized(), parse_quantiles(quantiles));
+        Ok(self)
+    }
+
     /// Sets the bucket width when using summaries.
     ///
     /// Summaries are rolling, which means that they are divided into buckets of a fixed duration
@@ -239,6 +745,41 @@ impl PrometheusBuilder {
         self
     }
 
+    /// Sets the window over which summaries compute quantiles.
+    ///
+    /// This sets both the bucket count and the bucket width, as described in
+    /// [`set_bucket_duration`][Self::set_bucket_duration] and
+    /// [`set_bucket_count`][Self::set_bucket_count], from a single [`SummaryWindow`].  For example,
+    /// `SummaryWindow::from_duration(Duration::from_secs(900), count)` computes quantiles over the
+    /// last 15 minutes.
+    ///
+    /// Defaults to 3 buckets of 20 seconds each.
+    #[must_use]
+    pub fn set_summary_window(mut self, window: SummaryWindow) -> Self {
+        self.bucket_duration = Some(window.bucket_duration());
+        self.bucket_count = Some(window.bucket_count());
+        self
+    }
+
+    /// Sets the window over which summaries compute quantiles for a specific pattern.
+    ///
+    /// The match pattern can be a full match (equality), prefix match, or suffix match, and
+    /// matchers are applied in the same order as described in
+    /// [`set_buckets_for_metric`][Self::set_buckets_for_metric].  Matching metrics use the given
+    /// window instead of the one set via [`set_summary_window`][Self::set_summary_window].
+    ///
+    /// This only affects metrics which are rendered as summaries.
+    #[must_use]
+    pub fn set_summary_window_for_metric(
+        mut self,
+        matcher: Matcher,
+        window: SummaryWindow,
+    ) -> Self {
+        let windows = self.summary_window_overrides.get_or_insert_with(HashMap::new);
+        windows.insert(matcher.sanitized(), window);
+        self
+    }
+
     /// Sets the buckets to use when rendering histograms.
     ///
     /// Buckets values represent the higher bound of each buckets.  If buckets are set, then all
@@ -269,6 +810,9 @@ impl PrometheusBuilder {
     /// This option changes the observer's output of histogram-type metric into summaries.
     /// It only affects matching metrics if [`set_buckets`][Self::set_buckets] was not used.
     ///
+    /// Buckets can also be set, or removed, for a pattern once the recorder is running, via
+    /// [`PrometheusHandle::set_buckets_for_metric`].
+    ///
     /// ## Errors
     ///
     /// If `values` is empty, an error variant will be thrown.
@@ -286,12 +830,32 @@ impl PrometheusBuilder {
         Ok(self)
     }
 
+    /// Configures histograms to be rendered as Prometheus native histograms.
+    ///
+    /// Native histograms count values in exponentially sized buckets, using at most `max_buckets`
+    /// buckets for positive values, and as many for negative values.  The resolution of the buckets
+    /// is lowered as values are recorded, whenever more buckets would be needed otherwise.
+    ///
+    /// Native histograms are only exposed as such in the protobuf format, via
+    /// [`PrometheusHandle::render_protobuf`], or when negotiated by the scrape endpoint.  Otherwise,
+    /// they're rendered as regular histograms, with one bucket for each of their non-empty buckets.
+    ///
+    /// Histograms matched by [`set_buckets_for_metric`][Self::set_buckets_for_metric] are still
+    /// rendered as regular histograms, with the given buckets.
+    #[must_use]
+    pub fn set_native_histograms(mut self, max_buckets: usize) -> Self {
+        self.native_histogram_max_buckets = Some(max_buckets);
+        self
+    }
+
     /// Sets the idle timeout for metrics.
     ///
     /// If a metric hasn't been updated within this timeout, it will be removed from the registry
     /// and in turn removed from the normal scrape output until the metric is emitted again.  This
     /// behavior is driven by requests to generate rendered output, and so metrics will not be
-    /// removed unless a request has been made recently enough to prune the idle metrics.
+    /// removed unless a request has been made recently enough to prune the idle metrics, or upkeep
+    /// is configured to prune them via
+    /// [`upkeep_removes_idle_metrics`][Self::upkeep_removes_idle_metrics].
     ///
     /// Further, the metric kind "mask" configures which metrics will be considered by the idle
     /// timeout.  If the kind of a metric being considered for idle timeout is not of a kind
@@ -299,24 +863,83 @@ impl PrometheusBuilder {
     /// for exceeding the idle timeout.
     ///
     /// Refer to the documentation for [`MetricKindMask`](metrics_util::MetricKindMask) for more
-    /// information on defining a metric kind mask.
+    /// information on defining a metric kind mask.  Different timeouts can be set for metrics
+    /// matching specific patterns via [`idle_timeout_for_metric`][Self::idle_timeout_for_metric].
+    #[must_use]
+    pub fn idle_timeout(mut self, mask: MetricKindMask, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self.recency_mask = if self.idle_timeout.is_none() { MetricKindMask::NONE } else { mask };
+        self
+    }
+
+    /// Sets the idle timeout for metrics matching a specific pattern.
+    ///
+    /// Metrics matching the pattern, and whose kind is represented by the mask, are removed once
+    /// they haven't been updated within the given timeout, as described in
+    /// [`idle_timeout`][Self::idle_timeout], or never removed if the timeout is `None`.  This
+    /// allows, for example, expiring per-request histograms after five minutes, while never
+    /// expiring `build_info` gauges, regardless of the idle timeout set for all other metrics.
+    ///
+    /// The match pattern can be a full match (equality), prefix match, or suffix match, and
+    /// matchers are applied in the same order as described in
+    /// [`set_buckets_for_metric`][Self::set_buckets_for_metric].  Setting a timeout for the same
+    /// pattern and mask again replaces the previous one.
     #[must_use]
-    pub fn idle_timeout(mut self, mask: MetricKindMask, timeout: Option<Duration>) -> Self {
-        self.idle_timeout = timeout;
-        self.recency_mask = if self.idle_timeout.is_none() { MetricKindMask::NONE } else { mask };
+    pub fn idle_timeout_for_metric(
+        mut self,
+        matcher: Matcher,
+        mask: MetricKindMask,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let matcher = matcher.sanitized();
+        self.idle_timeout_overrides
+            .retain(|(existing, existing_mask, _)| *existing != matcher || *existing_mask != mask);
+        self.idle_timeout_overrides.push((matcher, mask, timeout));
         self
     }
 
     /// Sets the upkeep interval.
     ///
     /// The upkeep task handles periodic maintenance operations, such as draining histogram data,
-    /// to ensure that all recorded data is up-to-date and prevent unbounded memory growth.
+    /// to ensure that all recorded data is up-to-date and prevent unbounded memory growth.  Refer
+    /// to [`PrometheusHandle::run_upkeep`] for the operations performed.
+    ///
+    /// Defaults to 5 seconds.
     #[must_use]
     pub fn upkeep_timeout(mut self, timeout: Duration) -> Self {
         self.upkeep_timeout = timeout;
         self
     }
 
+    /// Configures whether the exporter spawns a task running upkeep periodically.
+    ///
+    /// When disabled, upkeep must be driven by calling [`PrometheusHandle::run_upkeep`] instead,
+    /// such as on a schedule already run by the application.  The upkeep task is only spawned by
+    /// [`build`][Self::build] and [`install`][Self::install], so the recorder built by
+    /// [`build_recorder`][Self::build_recorder] or [`install_recorder`][Self::install_recorder]
+    /// always requires calling it.
+    ///
+    /// Defaults to enabled.
+    #[must_use]
+    pub fn spawn_upkeep_task(mut self, enabled: bool) -> Self {
+        self.spawn_upkeep_task = enabled;
+        self
+    }
+
+    /// Configures whether upkeep removes metrics which went idle, per the idle timeouts set via
+    /// [`idle_timeout`][Self::idle_timeout] and
+    /// [`idle_timeout_for_metric`][Self::idle_timeout_for_metric].
+    ///
+    /// Idle metrics are otherwise only removed when rendering, so metrics which are never rendered,
+    /// such as when scrapes stop, are held until the next render.
+    ///
+    /// Defaults to disabled.
+    #[must_use]
+    pub fn upkeep_removes_idle_metrics(mut self, enabled: bool) -> Self {
+        self.upkeep_removes_idle = enabled;
+        self
+    }
+
     /// Adds a global label to this exporter.
     ///
     /// Global labels are applied to all metrics.  Labels defined on the metric key itself have precedence
@@ -333,6 +956,119 @@ impl PrometheusBuilder {
         self
     }
 
+    /// Adds a set of global labels to this exporter, such as the service, instance and environment
+    /// that metrics are exported from.
+    ///
+    /// This behaves like calling [`add_global_label`][Self::add_global_label] for each label, in
+    /// order, so labels defined on the metric key itself still have precedence.
+    #[must_use]
+    pub fn add_global_labels<I, K, V>(mut self, labels: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let global_labels = self.global_labels.get_or_insert_with(IndexMap::new);
+        global_labels.extend(labels.into_iter().map(|(key, value)| (key.into(), value.into())));
+        self
+    }
+
+    /// Sets a hook which provides exemplars for counter increments and histogram observations that
+    /// are made without one.
+    ///
+    /// The hook is called on every such increment or observation, and returns the labels of the
+    /// exemplar to attach, such as the ID of the current trace, or `None` to attach no exemplar.
+    /// This allows exemplars to be sourced from the active trace context, without passing them to
+    /// [`Counter::increment_with_exemplar`](metrics::Counter::increment_with_exemplar) or
+    /// [`Histogram::record_with_exemplar`](metrics::Histogram::record_with_exemplar) explicitly.
+    ///
+    /// Exemplars are only rendered in the OpenMetrics format, on counters and on the buckets of
+    /// histograms.  As the hook is called on a hot path, it should be cheap.
+    #[must_use]
+    pub fn set_exemplar_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn() -> Option<Vec<Label>> + Send + Sync + 'static,
+    {
+        self.exemplar_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Configures whether metrics about the exporter itself are exported alongside the metrics of
+    /// the application.
+    ///
+    /// When enabled, the following metrics are exported, under the reserved
+    /// `metrics_exporter_prometheus_` prefix, and labelled with any global labels:
+    ///
+    /// - `render_duration_seconds`, the time taken by the previous render of the metrics
+    /// - `rendered_series`, the number of metrics, by label set, in the current render
+    /// - `expired_series_total`, the number of metrics, by label set, removed after going idle,
+    ///   per [`idle_timeout`][Self::idle_timeout]
+    /// - `push_failures_total`, the number of pushes to a push gateway, or to a Remote Write
+    ///   endpoint, which failed after exhausting their retries
+    /// - `http_responses_total`, the number of responses sent by the HTTP listener, labelled by
+    ///   their status `code`
+    ///
+    /// This allows monitoring the path by which metrics are collected itself.  Metrics recorded by
+    /// the application under the same prefix are overwritten.
+    ///
+    /// Defaults to disabled.
+    #[must_use]
+    pub fn set_self_metrics(mut self, enabled: bool) -> Self {
+        self.self_metrics = enabled;
+        self
+    }
+
+    /// Configures whether `_total` is appended to the names of counters.
+    ///
+    /// When enabled, counters whose names lack the `_total` suffix are exported with it, as
+    /// recommended by the Prometheus naming conventions, e.g. `requests` as `requests_total`.
+    /// Descriptions are exported under the suffixed names as well.
+    ///
+    /// Metric names are otherwise exported as recorded, except that dots and any other characters
+    /// not valid in a metric name are always replaced with underscores, as the exposition formats
+    /// require.
+    ///
+    /// Defaults to disabled.
+    #[must_use]
+    pub fn set_counter_total_suffix(mut self, enabled: bool) -> Self {
+        self.total_suffix = enabled;
+        self
+    }
+
+    /// Configures whether the unit of a metric is appended to its name.
+    ///
+    /// When enabled, metrics described with a unit are exported with the unit as a suffix of their
+    /// names, e.g. a histogram `latency` described in [`Unit::Seconds`](metrics::Unit::Seconds) as
+    /// `latency_seconds`, unless their names already end with it.  For counters, the unit goes
+    /// before any `_total` suffix.  [`Unit::Count`](metrics::Unit::Count) is never appended.
+    ///
+    /// Metrics are only renamed once described, so metrics should be described before being
+    /// recorded to, to avoid exporting them under both names.
+    ///
+    /// Defaults to disabled.
+    #[must_use]
+    pub fn set_unit_suffix(mut self, enabled: bool) -> Self {
+        self.unit_suffix = enabled;
+        self
+    }
+
+    /// Configures whether the creation times of counters, histograms and summaries are rendered.
+    ///
+    /// When enabled, a `_created` sample is rendered alongside each of them, holding the time at
+    /// which it was registered, as a Unix timestamp in seconds.  This allows telling apart a
+    /// counter which was reset, such as after the process restarted, or after it was removed for
+    /// being idle and then recorded to again, from one which kept increasing.
+    ///
+    /// Creation times are only rendered in the OpenMetrics format, as
+    /// [`ExpositionFormat::OpenMetrics`] is the only format supporting them.
+    ///
+    /// Defaults to disabled.
+    #[must_use]
+    pub fn set_created_timestamps(mut self, enabled: bool) -> Self {
+        self.created_timestamps = enabled;
+        self
+    }
+
     /// Builds the recorder and exporter and installs them globally.
     ///
     /// When called from within a Tokio runtime, the exporter future is spawned directly
@@ -422,43 +1158,82 @@ impl PrometheusBuilder {
     #[cfg_attr(not(feature = "http-listener"), allow(unused_mut))]
     pub fn build(mut self) -> Result<(PrometheusRecorder, ExporterFuture), BuildError> {
         #[cfg(feature = "http-listener")]
-        let allowed_addresses = self.allowed_addresses.take();
+        let listener_settings = super::http_listener::HttpListenerSettings {
+            allowed_addresses: self.allowed_addresses.take(),
+            negotiate_exposition_format: self.negotiate_exposition_format,
+            reject_unacceptable_formats: self.reject_unacceptable_formats,
+            authorization: std::mem::take(&mut self.authorization),
+            scrape_path: self.scrape_path.take(),
+            health_routes: std::mem::take(&mut self.health_routes),
+            #[cfg(feature = "compression")]
+            compression: self.compression,
+            #[cfg(feature = "tls")]
+            tls_acceptor: std::mem::take(&mut self.tls).into_acceptor()?,
+        };
+        #[cfg(feature = "push-gateway")]
+        let push_settings = std::mem::take(&mut self.push);
+        #[cfg(feature = "remote-write")]
+        let remote_write_batch_size = self.remote_write_batch_size;
         let exporter_config = self.exporter_config.clone();
         let upkeep_timeout = self.upkeep_timeout;
+        let spawn_upkeep_task = self.spawn_upkeep_task;
 
-        let recorder = self.build_recorder();
+        #[cfg_attr(not(feature = "push-gateway"), allow(unused_mut))]
+        let mut recorder = self.build_recorder();
         let handle = recorder.handle();
 
-        let recorder_handle = handle.clone();
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(upkeep_timeout).await;
-                recorder_handle.run_upkeep();
-            }
-        });
-
-        Ok((
-            recorder,
-            match exporter_config {
-                ExporterConfig::Unconfigured => Err(BuildError::MissingExporterConfiguration)?,
-
-                #[cfg(feature = "http-listener")]
-                ExporterConfig::HttpListener { listen_address } => {
-                    super::http_listener::new_http_listener(
-                        handle,
-                        listen_address,
-                        allowed_addresses,
-                    )?
+        if spawn_upkeep_task {
+            let recorder_handle = handle.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(upkeep_timeout).await;
+                    recorder_handle.run_upkeep();
                 }
+            });
+        }
 
-                #[cfg(feature = "push-gateway")]
-                ExporterConfig::PushGateway { endpoint, interval, username, password } => {
-                    super::push_gateway::new_push_gateway(
-                        endpoint, interval, username, password, handle,
-                    )
-                }
-            },
-        ))
+        let exporter = match exporter_config {
+            ExporterConfig::Unconfigured => Err(BuildError::MissingExporterConfiguration)?,
+
+            #[cfg(feature = "http-listener")]
+            ExporterConfig::HttpListener { listen_address } => {
+                super::http_listener::new_http_listener(handle, listen_address, listener_settings)?
+            }
+
+            #[cfg(all(feature = "http-listener", unix))]
+            ExporterConfig::HttpUdsListener { path } => {
+                super::http_listener::new_http_uds_listener(handle, &path, listener_settings)?
+            }
+
+            #[cfg(feature = "push-gateway")]
+            ExporterConfig::PushGateway { endpoint, interval, username, password } => {
+                super::push_gateway::new_push_gateway(
+                    &endpoint,
+                    interval,
+                    username.as_deref(),
+                    password.as_deref(),
+                    handle,
+                    &push_settings,
+                    recorder.push_requests(),
+                )?
+            }
+
+            #[cfg(feature = "remote-write")]
+            ExporterConfig::RemoteWrite { endpoint, interval, username, password } => {
+                super::remote_write::new_remote_write(
+                    endpoint,
+                    interval,
+                    username.as_deref(),
+                    password.as_deref(),
+                    handle,
+                    &push_settings,
+                    remote_write_batch_size,
+                    recorder.push_requests(),
+                )?
+            }
+        };
+
+        Ok((recorder, exporter))
     }
 
     /// Builds the recorder and returns it.
@@ -467,19 +1242,47 @@ impl PrometheusBuilder {
     }
 
     pub(crate) fn build_with_clock(self, clock: Clock) -> PrometheusRecorder {
+        let mut distribution_builder = DistributionBuilder::new(
+            self.quantiles,
+            self.bucket_duration,
+            self.buckets,
+            self.bucket_count,
+            self.bucket_overrides,
+        );
+        if let Some(overrides) = self.summary_window_overrides {
+            distribution_builder = distribution_builder.with_summary_window_overrides(overrides);
+        }
+        if let Some(overrides) = self.quantile_overrides {
+            distribution_builder = distribution_builder.with_quantile_overrides(overrides);
+        }
+        if let Some(max_buckets) = self.native_histogram_max_buckets {
+            distribution_builder = distribution_builder.with_native_histograms(max_buckets);
+        }
+
+        let descriptions =
+            if self.self_metrics { SelfMetrics::descriptions().collect() } else { HashMap::new() };
+        let storage = AtomicStorage::with_exemplar_hook(self.exemplar_hook);
         let inner = Inner {
-            registry: Registry::new(GenerationalStorage::new(AtomicStorage)),
-            recency: Recency::new(clock, self.recency_mask, self.idle_timeout),
-            distributions: RwLock::new(HashMap::new()),
-            distribution_builder: DistributionBuilder::new(
-                self.quantiles,
-                self.bucket_duration,
-                self.buckets,
-                self.bucket_count,
-                self.bucket_overrides,
+            registry: Registry::new(GenerationalStorage::new(storage)),
+            recency: build_recency(
+                clock,
+                self.recency_mask,
+                self.idle_timeout,
+                self.idle_timeout_overrides,
             ),
-            descriptions: RwLock::new(HashMap::new()),
+            distributions: RwLock::new(HashMap::new()),
+            distribution_builder: RwLock::new(distribution_builder),
+            summaries: RwLock::new(HashSet::new()),
+            descriptions: RwLock::new(descriptions),
+            units: RwLock::new(HashMap::new()),
+            exposition_format: self.exposition_format,
             global_labels: self.global_labels.unwrap_or_default(),
+            self_metrics: self.self_metrics.then(SelfMetrics::default),
+            total_suffix: self.total_suffix,
+            unit_suffix: self.unit_suffix,
+            created_timestamps: self.created_timestamps,
+            upkeep_removes_idle: self.upkeep_removes_idle,
+            federated: RwLock::new(Vec::new()),
         };
 
         PrometheusRecorder::from(inner)
@@ -492,17 +1295,42 @@ impl Default for PrometheusBuilder {
     }
 }
 
+/// Builds the recency tracker for the given idle timeout, and idle timeouts of specific patterns.
+fn build_recency(
+    clock: Clock,
+    mask: MetricKindMask,
+    timeout: Option<Duration>,
+    mut overrides: Vec<(Matcher, MetricKindMask, Option<Duration>)>,
+) -> Recency<Key> {
+    if overrides.is_empty() {
+        return Recency::new(clock, mask, timeout);
+    }
+
+    // Sorting is stable, and matchers are ordered by precedence, so the first matching override is
+    // the one to apply.
+    overrides.sort_by(|a, b| a.0.cmp(&b.0));
+    Recency::with_idle_timeout_fn(clock, MetricKindMask::ALL, move |key: &Key, kind| {
+        let name = sanitize_metric_name(key.name());
+        overrides
+            .iter()
+            .find(|(matcher, mask, _)| mask.matches(kind) && matcher.matches(&name))
+            .map_or_else(|| timeout.filter(|_| mask.matches(kind)), |(_, _, timeout)| *timeout)
+    })
+}
+
 #[cfg(test)]
 #[allow(clippy::approx_constant)]
 mod tests {
-    use std::time::Duration;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     use quanta::Clock;
 
-    use metrics::{Key, KeyName, Label, Recorder};
+    use metrics::{Key, KeyName, Label, Recorder, Unit};
     use metrics_util::MetricKindMask;
 
-    use super::{Matcher, PrometheusBuilder};
+    use super::{ExpositionFormat, Matcher, PrometheusBuilder};
 
     static METADATA: metrics::Metadata =
         metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
@@ -528,8 +1356,7 @@ mod tests {
         gauge1.set(-3.14);
         let rendered = handle.render();
         let expected_gauge = format!(
-            "{}# TYPE basic_gauge gauge\nbasic_gauge{{wutang=\"forever\"}} -3.14\n\n",
-            expected_counter
+            "{expected_counter}# TYPE basic_gauge gauge\nbasic_gauge{{wutang=\"forever\"}} -3.14\n\n",
         );
 
         assert_eq!(rendered, expected_gauge);
@@ -547,11 +1374,136 @@ mod tests {
             "basic_histogram_count 1\n",
             "\n"
         );
-        let expected_histogram = format!("{}{}", expected_gauge, histogram_data);
+        let expected_histogram = format!("{expected_gauge}{histogram_data}");
 
         assert_eq!(rendered, expected_histogram);
     }
 
+    #[test]
+    fn test_render_openmetrics() {
+        let recorder = PrometheusBuilder::new()
+            .set_exposition_format(ExpositionFormat::OpenMetrics)
+            .set_quantiles(&[0.0, 1.0])
+            .unwrap()
+            .build_recorder();
+
+        recorder.describe_counter("requests_total".into(), None, "Requests served.".into());
+        let counter = recorder.register_counter(&Key::from_name("requests_total"), &METADATA);
+        counter.increment(3);
+
+        recorder.describe_histogram(
+            "request_duration_seconds".into(),
+            Some(Unit::Seconds),
+            "Time \"spent\".".into(),
+        );
+        let key = Key::from_name("request_duration_seconds");
+        let histogram = recorder.register_histogram(&key, &METADATA);
+        histogram.record(0.5);
+
+        let handle = recorder.handle();
+        let expected = concat!(
+            "# HELP requests Requests served.\n",
+            "# TYPE requests counter\n",
+            "requests_total 3\n",
+            "# HELP request_duration_seconds Time \\\"spent\\\".\n",
+            "# TYPE request_duration_seconds summary\n",
+            "# UNIT request_duration_seconds seconds\n",
+            "request_duration_seconds{quantile=\"0\"} 0.5\n",
+            "request_duration_seconds{quantile=\"1\"} 0.5\n",
+            "request_duration_seconds_sum 0.5\n",
+            "request_duration_seconds_count 1\n",
+            "# EOF\n",
+        );
+        assert_eq!(handle.exposition_format(), ExpositionFormat::OpenMetrics);
+        assert_eq!(handle.render(), expected);
+
+        let rendered = handle.render_with_format(ExpositionFormat::Prometheus);
+        assert!(rendered.starts_with(concat!(
+            "# HELP requests_total Requests served.\n",
+            "# TYPE requests_total counter\n",
+            "requests_total 3\n\n",
+        )));
+        assert!(!rendered.contains("# EOF"));
+    }
+
+    #[test]
+    fn test_render_exemplars() {
+        // Exemplar timestamps depend on the current time, so they're stripped before comparing.
+        fn strip_timestamps(rendered: &str) -> String {
+            rendered
+                .lines()
+                .map(|line| {
+                    if line.contains(" # {") {
+                        line.rsplit_once(' ').map_or(line, |(rest, _)| rest)
+                    } else {
+                        line
+                    }
+                })
+                .map(|line| format!("{line}\n"))
+                .collect()
+        }
+
+        let recorder = PrometheusBuilder::new()
+            .set_exposition_format(ExpositionFormat::OpenMetrics)
+            .set_buckets(&[1.0, 10.0])
+            .unwrap()
+            .set_exemplar_hook(|| Some(vec![Label::new("trace_id", "hooked")]))
+            .build_recorder();
+
+        let counter = recorder.register_counter(&Key::from_name("requests"), &METADATA);
+        counter.increment_with_exemplar(2, vec![Label::new("trace_id", "abc")]);
+
+        let histogram = recorder.register_histogram(&Key::from_name("latency"), &METADATA);
+        histogram.record_with_exemplar(0.5, vec![Label::new("trace_id", "def")]);
+        histogram.record(20.0);
+
+        let handle = recorder.handle();
+        let expected = concat!(
+            "# TYPE requests counter\n",
+            "requests_total 2 # {trace_id=\"abc\"} 2\n",
+            "# TYPE latency histogram\n",
+            "latency_bucket{le=\"1\"} 1 # {trace_id=\"def\"} 0.5\n",
+            "latency_bucket{le=\"10\"} 1\n",
+            "latency_bucket{le=\"+Inf\"} 2 # {trace_id=\"hooked\"} 20\n",
+            "latency_sum 20.5\n",
+            "latency_count 2\n",
+            "# EOF\n",
+        );
+        assert_eq!(strip_timestamps(&handle.render()), expected);
+
+        // Exemplars are left out of the Prometheus exposition format.
+        let rendered = handle.render_with_format(ExpositionFormat::Prometheus);
+        assert!(!rendered.contains(" # {"));
+    }
+
+    #[test]
+    fn test_render_native_histograms() {
+        // Two buckets only fit the values below at schema 0, whose bucket bounds are powers of 2.
+        let recorder = PrometheusBuilder::new().set_native_histograms(2).build_recorder();
+
+        let histogram = recorder.register_histogram(&Key::from_name("latency"), &METADATA);
+        histogram.record_many(&[-1.0, 0.0, 1.0, 2.0, 2.0]);
+
+        let handle = recorder.handle();
+        let expected = concat!(
+            "# TYPE latency histogram\n",
+            "latency_bucket{le=\"-0.5\"} 1\n",
+            "latency_bucket{le=\"0\"} 2\n",
+            "latency_bucket{le=\"1\"} 3\n",
+            "latency_bucket{le=\"2\"} 5\n",
+            "latency_bucket{le=\"+Inf\"} 5\n",
+            "latency_sum 4\n",
+            "latency_count 5\n",
+            "\n",
+        );
+        assert_eq!(handle.render(), expected);
+
+        // Native histograms are only exposed as such in the protobuf format.
+        let rendered = handle.render_protobuf();
+        assert_eq!(usize::from(rendered[0]), rendered.len() - 1);
+        assert!(rendered.windows(2).any(|field| field == [0x28, 0]));
+    }
+
     #[test]
     fn test_buckets() {
         const DEFAULT_VALUES: [f64; 3] = [10.0, 100.0, 1000.0];
@@ -641,6 +1593,143 @@ mod tests {
         assert!(rendered.contains(default_data));
     }
 
+    #[test]
+    fn test_summaries_ignore_buckets() {
+        let recorder = PrometheusBuilder::new()
+            .set_quantiles(&[0.0, 1.0])
+            .unwrap()
+            .set_buckets(&[10.0, 100.0])
+            .expect("bounds should not be empty")
+            .build_recorder();
+
+        let summary = recorder.register_summary(&Key::from_name("basic_summary"), &METADATA);
+        summary.record(12.0);
+        let histogram = recorder.register_histogram(&Key::from_name("basic_histogram"), &METADATA);
+        histogram.record(12.0);
+
+        let rendered = recorder.handle().render();
+
+        let summary_data = concat!(
+            "# TYPE basic_summary summary\n",
+            "basic_summary{quantile=\"0\"} 12\n",
+            "basic_summary{quantile=\"1\"} 12\n",
+            "basic_summary_sum 12\n",
+            "basic_summary_count 1\n",
+        );
+        assert!(rendered.contains(summary_data));
+        assert!(rendered.contains("# TYPE basic_histogram histogram\n"));
+    }
+
+    #[test]
+    fn test_runtime_buckets() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        let key = Key::from_name("latency");
+        let histogram = recorder.register_histogram(&key, &METADATA);
+        histogram.record(5.0);
+        assert!(handle.render().contains("# TYPE latency summary\n"));
+
+        handle
+            .set_buckets_for_metric(Matcher::Full("latency".to_owned()), &[1.0, 10.0])
+            .expect("bounds should not be empty");
+        histogram.record(5.0);
+
+        let expected = concat!(
+            "# TYPE latency histogram\n",
+            "latency_bucket{le=\"1\"} 0\n",
+            "latency_bucket{le=\"10\"} 1\n",
+            "latency_bucket{le=\"+Inf\"} 1\n",
+            "latency_sum 5\n",
+            "latency_count 1\n",
+        );
+        assert!(handle.render().contains(expected));
+
+        assert!(handle.remove_buckets_for_metric(Matcher::Full("latency".to_owned())));
+        assert!(!handle.remove_buckets_for_metric(Matcher::Full("latency".to_owned())));
+        histogram.record(5.0);
+
+        let rendered = handle.render();
+        assert!(rendered.contains("# TYPE latency summary\n"));
+        assert!(rendered.contains("latency_count 1\n"));
+    }
+
+    #[test]
+    fn test_quantiles_for_metric() {
+        let recorder = PrometheusBuilder::new()
+            .set_quantiles(&[0.5])
+            .unwrap()
+            .set_quantiles_for_metric(Matcher::Suffix("latency".to_owned()), &[0.99, 0.999])
+            .unwrap()
+            .build_recorder();
+        let handle = recorder.handle();
+
+        let latency = recorder.register_histogram(&Key::from_name("db_latency"), &METADATA);
+        latency.record(5.0);
+        let size = recorder.register_histogram(&Key::from_name("response_size"), &METADATA);
+        size.record(5.0);
+
+        let rendered = handle.render();
+        assert!(rendered.contains("db_latency{quantile=\"0.99\"} "));
+        assert!(rendered.contains("db_latency{quantile=\"0.999\"} "));
+        assert!(!rendered.contains("db_latency{quantile=\"0.5\"}"));
+        assert!(rendered.contains("response_size{quantile=\"0.5\"} "));
+
+        handle
+            .set_quantiles_for_metric(Matcher::Full("db_latency".to_owned()), &[0.9999])
+            .expect("quantiles should not be empty");
+        let rendered = handle.render();
+        assert!(rendered.contains("db_latency{quantile=\"0.9999\"} "));
+        assert!(rendered.contains("db_latency_count 1\n"));
+
+        assert!(handle.remove_quantiles_for_metric(Matcher::Full("db_latency".to_owned())));
+        assert!(!handle.remove_quantiles_for_metric(Matcher::Full("db_latency".to_owned())));
+        assert!(handle.render().contains("db_latency{quantile=\"0.999\"} "));
+    }
+
+    #[test]
+    fn test_idle_timeout_for_metric() {
+        let (clock, mock) = Clock::mock();
+
+        let recorder = PrometheusBuilder::new()
+            .idle_timeout(MetricKindMask::ALL, Some(Duration::from_secs(10)))
+            .idle_timeout_for_metric(
+                Matcher::Suffix("_seconds".to_owned()),
+                MetricKindMask::HISTOGRAM,
+                Some(Duration::from_secs(300)),
+            )
+            .idle_timeout_for_metric(
+                Matcher::Full("build_info".to_owned()),
+                MetricKindMask::GAUGE,
+                None,
+            )
+            .build_with_clock(clock);
+
+        let build_info = recorder.register_gauge(&Key::from_name("build_info"), &METADATA);
+        build_info.set(1.0);
+        let queue_depth = recorder.register_gauge(&Key::from_name("queue_depth"), &METADATA);
+        queue_depth.set(1.0);
+        let latency = recorder.register_histogram(&Key::from_name("request_seconds"), &METADATA);
+        latency.record(1.0);
+
+        let handle = recorder.handle();
+        let rendered = handle.render();
+        assert!(rendered.contains("build_info 1\n"));
+        assert!(rendered.contains("queue_depth 1\n"));
+        assert!(rendered.contains("request_seconds_count 1\n"));
+
+        mock.increment(Duration::from_secs(11));
+        let rendered = handle.render();
+        assert!(rendered.contains("build_info 1\n"));
+        assert!(!rendered.contains("queue_depth"));
+        assert!(rendered.contains("request_seconds_count 1\n"));
+
+        mock.increment(Duration::from_secs(300));
+        let rendered = handle.render();
+        assert!(rendered.contains("build_info 1\n"));
+        assert!(!rendered.contains("request_seconds"));
+    }
+
     #[test]
     fn test_idle_timeout_all() {
         let (clock, mock) = Clock::mock();
@@ -907,6 +1996,32 @@ mod tests {
         assert_eq!(rendered, "");
     }
 
+    #[test]
+    fn test_callback_gauge() {
+        let (clock, mock) = Clock::mock();
+
+        let recorder = PrometheusBuilder::new()
+            .idle_timeout(MetricKindMask::ALL, Some(Duration::from_secs(10)))
+            .build_with_clock(clock);
+
+        let depth = Arc::new(AtomicU32::new(3));
+        let key = Key::from_name("queue_depth");
+        let gauge = recorder.register_gauge(&key, &METADATA);
+        gauge.set(1.0);
+        let callback_depth = Arc::clone(&depth);
+        gauge.set_callback(move || f64::from(callback_depth.load(Ordering::Relaxed)));
+
+        let handle = recorder.handle();
+        let rendered = handle.render();
+        assert_eq!(rendered, "# TYPE queue_depth gauge\nqueue_depth 3\n\n");
+
+        // Callback gauges are evaluated at render time, and never go idle.
+        depth.store(5, Ordering::Relaxed);
+        mock.increment(Duration::from_secs(11));
+        let rendered = handle.render();
+        assert_eq!(rendered, "# TYPE queue_depth gauge\nqueue_depth 5\n\n");
+    }
+
     #[test]
     pub fn test_global_labels() {
         let recorder = PrometheusBuilder::new()
@@ -924,6 +2039,170 @@ mod tests {
         assert_eq!(rendered, expected_counter);
     }
 
+    #[test]
+    pub fn test_add_global_labels() {
+        let recorder = PrometheusBuilder::new()
+            .add_global_label("env", "dev")
+            .add_global_labels([("service", "api"), ("env", "prod")])
+            .build_recorder();
+        let key = Key::from_name("requests");
+        let histogram = recorder.register_histogram(&key, &METADATA);
+        histogram.record(1.0);
+
+        let rendered = recorder.handle().render();
+        assert!(rendered.contains("requests_count{env=\"prod\",service=\"api\"} 1\n"));
+    }
+
+    #[test]
+    fn test_federation() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let plugin = PrometheusBuilder::new().add_global_label("plugin", "a").build_recorder();
+        plugin.describe_counter("requests".into(), None, "Requests served.".into());
+        recorder.register_counter(&Key::from_name("requests"), &METADATA).increment(1);
+        plugin.register_counter(&Key::from_name("requests"), &METADATA).increment(2);
+        plugin.register_histogram(&Key::from_name("latency"), &METADATA).record(0.5);
+
+        let handle = recorder.handle();
+        let plugin_handle = plugin.handle();
+        assert!(handle.add_federated(&plugin_handle, Some("plugin")));
+
+        let rendered = handle.render();
+        assert!(rendered.contains("\nrequests 1\n"));
+        assert!(rendered.contains("# HELP plugin_requests Requests served.\n"));
+        assert!(rendered.contains("\nplugin_requests{plugin=\"a\"} 2\n"));
+        assert!(rendered.contains("\nplugin_latency_count{plugin=\"a\"} 1\n"));
+
+        // Recorders can't render their own metrics, or each other's.
+        assert!(!handle.add_federated(&handle, None));
+        assert!(!plugin_handle.add_federated(&handle, None));
+
+        assert!(handle.remove_federated(&plugin_handle));
+        assert!(!handle.remove_federated(&plugin_handle));
+        assert!(!handle.render().contains("plugin_"));
+    }
+
+    #[test]
+    fn test_upkeep_removes_idle_metrics() {
+        for removes_idle in [false, true] {
+            let (clock, mock) = Clock::mock();
+            let recorder = PrometheusBuilder::new()
+                .idle_timeout(MetricKindMask::ALL, Some(Duration::from_secs(10)))
+                .upkeep_removes_idle_metrics(removes_idle)
+                .set_self_metrics(true)
+                .build_with_clock(clock);
+
+            let key = Key::from_name("basic_counter");
+            recorder.register_counter(&key, &METADATA).increment(42);
+            let handle = recorder.handle();
+            assert!(handle.render().contains("\nbasic_counter 42\n"));
+
+            // Once removed, the counter is recreated from zero.
+            mock.increment(Duration::from_secs(11));
+            handle.run_upkeep();
+            recorder.register_counter(&key, &METADATA).increment(1);
+
+            let rendered = handle.render();
+            let (value, expired) = if removes_idle { (1, 1) } else { (43, 0) };
+            assert!(rendered.contains(&format!("\nbasic_counter {value}\n")));
+            let expired = format!("\nmetrics_exporter_prometheus_expired_series_total {expired}\n");
+            assert!(rendered.contains(&expired));
+        }
+    }
+
+    #[test]
+    fn test_self_metrics() {
+        let (clock, mock) = Clock::mock();
+
+        let recorder = PrometheusBuilder::new()
+            .idle_timeout(MetricKindMask::ALL, Some(Duration::from_secs(10)))
+            .set_self_metrics(true)
+            .build_with_clock(clock);
+
+        let key = Key::from_name("basic_counter");
+        let counter1 = recorder.register_counter(&key, &METADATA);
+        counter1.increment(42);
+
+        let handle = recorder.handle();
+        let rendered = handle.render();
+        assert!(rendered.contains("\nbasic_counter 42\n"));
+        assert!(rendered.contains("\nmetrics_exporter_prometheus_rendered_series 1\n"));
+        assert!(rendered.contains("\nmetrics_exporter_prometheus_expired_series_total 0\n"));
+        assert!(rendered.contains("# HELP metrics_exporter_prometheus_render_duration_seconds "));
+
+        mock.increment(Duration::from_secs(11));
+
+        let rendered = handle.render();
+        assert!(!rendered.contains("basic_counter"));
+        assert!(rendered.contains("\nmetrics_exporter_prometheus_rendered_series 0\n"));
+        assert!(rendered.contains("\nmetrics_exporter_prometheus_expired_series_total 1\n"));
+
+        let rendered = PrometheusBuilder::new().build_recorder().handle().render();
+        assert!(!rendered.contains("metrics_exporter_prometheus_"));
+    }
+
+    #[test]
+    fn test_naming_conventions() {
+        let recorder = PrometheusBuilder::new()
+            .set_counter_total_suffix(true)
+            .set_unit_suffix(true)
+            .build_recorder();
+
+        recorder.describe_counter("requests".into(), None, "Requests served.".into());
+        recorder.describe_counter("sent".into(), Some(Unit::Bytes), "Bytes sent.".into());
+        recorder.describe_histogram("latency".into(), Some(Unit::Seconds), "Latency.".into());
+        recorder.describe_gauge("memory.used".into(), Some(Unit::Count), "Memory used.".into());
+
+        recorder.register_counter(&Key::from_name("requests"), &METADATA).increment(1);
+        recorder.register_counter(&Key::from_name("sent_total"), &METADATA).increment(2);
+        recorder.register_counter(&Key::from_name("sent"), &METADATA).increment(3);
+        recorder.register_histogram(&Key::from_name("latency"), &METADATA).record(0.5);
+        recorder.register_gauge(&Key::from_name("memory.used"), &METADATA).set(4.0);
+
+        let rendered = recorder.handle().render();
+        assert!(rendered.contains("# HELP requests_total Requests served.\n"));
+        assert!(rendered.contains("\nrequests_total 1\n"));
+        assert!(rendered.contains("\nsent_total 2\n"));
+        assert!(rendered.contains("# HELP sent_bytes_total Bytes sent.\n"));
+        assert!(rendered.contains("\nsent_bytes_total 3\n"));
+        assert!(rendered.contains("# HELP latency_seconds Latency.\n"));
+        assert!(rendered.contains("\nlatency_seconds_count 1\n"));
+        assert!(rendered.contains("\nmemory_used 4\n"));
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        recorder.describe_histogram("latency".into(), Some(Unit::Seconds), "Latency.".into());
+        recorder.register_counter(&Key::from_name("requests"), &METADATA).increment(1);
+        recorder.register_histogram(&Key::from_name("latency"), &METADATA).record(0.5);
+
+        let rendered = recorder.handle().render();
+        assert!(rendered.contains("\nrequests 1\n"));
+        assert!(rendered.contains("\nlatency_count 1\n"));
+    }
+
+    #[test]
+    fn test_created_timestamps() {
+        let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+
+        let before = now();
+        let recorder = PrometheusBuilder::new().set_created_timestamps(true).build_recorder();
+        recorder.register_counter(&Key::from_name("requests"), &METADATA).increment(1);
+        recorder.register_histogram(&Key::from_name("latency"), &METADATA).record(0.5);
+        let after = now();
+
+        let handle = recorder.handle();
+        let rendered = handle.render_with_format(ExpositionFormat::OpenMetrics);
+        for name in ["requests_created", "latency_created"] {
+            let line = rendered.lines().find(|line| line.starts_with(name)).unwrap();
+            let created = line[name.len() + 1..].parse::<f64>().unwrap();
+            assert!(before <= created && created <= after);
+        }
+        assert!(!handle.render().contains("_created"));
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        recorder.register_counter(&Key::from_name("requests"), &METADATA).increment(1);
+        let rendered = recorder.handle().render_with_format(ExpositionFormat::OpenMetrics);
+        assert!(!rendered.contains("_created"));
+    }
+
     #[test]
     pub fn test_global_labels_overrides() {
         let recorder = PrometheusBuilder::new().add_global_label("foo", "foo").build_recorder();
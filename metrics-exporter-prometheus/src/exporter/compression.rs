@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+
+/// Responses shorter than this aren't compressed, as the savings would be negligible.
+pub(crate) const MIN_COMPRESSED_LENGTH: usize = 1024;
+
+/// A content coding used to compress scrape responses.
+///
+/// Codings are ordered from least to most preferred, for when they're equally acceptable.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ContentEncoding {
+    Gzip,
+    #[cfg(feature = "compression-zstd")]
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// Selects a content coding based on the value of an `Accept-Encoding` header.
+    ///
+    /// The supported coding with the highest quality value is selected, preferring zstd when
+    /// multiple codings are equally acceptable.  If no supported coding is acceptable, `None` is
+    /// returned, and the response should be left uncompressed.
+    pub fn from_accept_encoding(accept_encoding: &str) -> Option<ContentEncoding> {
+        let mut selected: Option<(ContentEncoding, f64)> = None;
+        for coding in accept_encoding.split(',') {
+            let mut params = coding.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default();
+            let quality = params
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f64>().ok())
+                .unwrap_or(1.0);
+
+            let encoding = match name {
+                _ if name.eq_ignore_ascii_case("gzip") => ContentEncoding::Gzip,
+                #[cfg(feature = "compression-zstd")]
+                _ if name.eq_ignore_ascii_case("zstd") => ContentEncoding::Zstd,
+                _ => continue,
+            };
+
+            let preferred = match selected {
+                Some((current, q)) => match quality.partial_cmp(&q) {
+                    Some(Ordering::Greater) => true,
+                    Some(Ordering::Equal) => current < encoding,
+                    _ => false,
+                },
+                None => true,
+            };
+            if quality > 0.0 && preferred {
+                selected = Some((encoding, quality));
+            }
+        }
+        selected.map(|(encoding, _)| encoding)
+    }
+
+    /// Gets the value of the `Content-Encoding` header for responses compressed with this coding.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            #[cfg(feature = "compression-zstd")]
+            ContentEncoding::Zstd => "zstd",
+        }
+    }
+
+    /// Compresses the given body.
+    pub fn encode(self, body: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            ContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "compression-zstd")]
+            ContentEncoding::Zstd => zstd::bulk::compress(body, zstd::DEFAULT_COMPRESSION_LEVEL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::ContentEncoding;
+
+    #[test]
+    fn test_content_encoding_from_accept_encoding() {
+        let cases = &[
+            ("gzip", Some(ContentEncoding::Gzip)),
+            ("deflate, GZIP;q=0.5", Some(ContentEncoding::Gzip)),
+            ("gzip;q=0", None),
+            ("identity", None),
+            ("", None),
+        ];
+
+        for (accept_encoding, expected) in cases {
+            let encoding = ContentEncoding::from_accept_encoding(accept_encoding);
+            assert_eq!(encoding, *expected, "{accept_encoding}");
+        }
+
+        #[cfg(feature = "compression-zstd")]
+        {
+            let encoding = ContentEncoding::from_accept_encoding("gzip, zstd");
+            assert_eq!(encoding, Some(ContentEncoding::Zstd));
+            let encoding = ContentEncoding::from_accept_encoding("gzip, zstd;q=0.5");
+            assert_eq!(encoding, Some(ContentEncoding::Gzip));
+        }
+    }
+
+    #[test]
+    fn test_gzip_encode() {
+        let body = "metric 1\n".repeat(1000);
+        let encoded = ContentEncoding::Gzip.encode(body.as_bytes()).unwrap();
+        assert!(encoded.len() < body.len());
+
+        let mut decoded = String::new();
+        GzDecoder::new(&encoded[..]).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, body);
+    }
+}
@@ -1,25 +1,79 @@
 use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Arc;
 
 use http_body_util::Full;
+#[cfg(feature = "compression")]
+use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING, VARY};
 use hyper::{
-    body::{self, Bytes, Incoming},
+    body::{Bytes, Incoming},
+    header::{HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
     server::conn::http1::Builder as HyperHttpBuilder,
     service::service_fn,
     Request, Response, StatusCode,
 };
 use hyper_util::rt::TokioIo;
 use ipnet::IpNet;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+#[cfg(feature = "tls")]
+use tokio_rustls::TlsAcceptor;
 use tracing::warn;
 
-use crate::{common::BuildError, ExporterFuture, PrometheusHandle};
+#[cfg(feature = "compression")]
+use super::compression::{ContentEncoding, MIN_COMPRESSED_LENGTH};
+use crate::common::{accepts_format, prefers_protobuf, BuildError};
+use crate::{ExporterFuture, ExpositionFormat, PrometheusHandle, PROTOBUF_CONTENT_TYPE};
+
+/// A check reporting whether the application is healthy, or ready, served on a route of the HTTP
+/// listener.
+pub(crate) type HealthCheck = Arc<dyn Fn() -> bool + Send + Sync>;
+
+/// Settings for the HTTP listener, regardless of what it listens on.
+pub(crate) struct HttpListenerSettings {
+    pub allowed_addresses: Option<Vec<IpNet>>,
+    pub negotiate_exposition_format: bool,
+    pub reject_unacceptable_formats: bool,
+    pub authorization: Vec<String>,
+    pub scrape_path: Option<String>,
+    pub health_routes: Vec<(String, HealthCheck)>,
+    #[cfg(feature = "compression")]
+    pub compression: bool,
+    #[cfg(feature = "tls")]
+    pub tls_acceptor: Option<TlsAcceptor>,
+}
 
 struct HttpListeningExporter {
-    handle: PrometheusHandle,
     allowed_addresses: Option<Vec<IpNet>>,
+    responder: Responder,
+    #[cfg(feature = "tls")]
+    tls_acceptor: Option<TlsAcceptor>,
 }
 
 impl HttpListeningExporter {
+    fn new(handle: PrometheusHandle, settings: HttpListenerSettings) -> HttpListeningExporter {
+        let responder = Responder {
+            handle,
+            negotiate_exposition_format: settings.negotiate_exposition_format,
+            reject_unacceptable_formats: settings.reject_unacceptable_formats,
+            authorization: settings.authorization.into(),
+            scrape_path: settings.scrape_path.map(Into::into),
+            health_routes: settings.health_routes.into(),
+            #[cfg(feature = "compression")]
+            compression: settings.compression,
+        };
+
+        HttpListeningExporter {
+            allowed_addresses: settings.allowed_addresses,
+            responder,
+            #[cfg(feature = "tls")]
+            tls_acceptor: settings.tls_acceptor,
+        }
+    }
+
     async fn serve(&self, listener: tokio::net::TcpListener) -> Result<(), hyper::Error> {
         loop {
             let stream = match listener.accept().await {
@@ -47,35 +101,60 @@ impl HttpListeningExporter {
         }
     }
 
-    async fn process_stream(&self, stream: TcpStream, is_allowed: bool) {
-        let handle = self.handle.clone();
-        let service = service_fn(move |req: Request<body::Incoming>| {
-            let handle = handle.clone();
-            async move { Ok::<_, hyper::Error>(Self::handle_http_request(is_allowed, &handle, &req)) }
-        });
+    #[cfg(unix)]
+    async fn serve_uds(&self, listener: UnixListener) -> Result<(), hyper::Error> {
+        loop {
+            // Connections over a Unix domain socket have no remote IP address, so the allowlist
+            // doesn't apply to them.
+            match listener.accept().await {
+                Ok((stream, _)) => self.process_stream(stream, true).await,
+                Err(e) => warn!(error = ?e, "Error accepting connection. Ignoring request."),
+            }
+        }
+    }
+
+    async fn process_stream<S>(&self, stream: S, is_allowed: bool)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let responder = self.responder.clone();
+        #[cfg(feature = "tls")]
+        let tls_acceptor = self.tls_acceptor.clone();
 
+        // The TLS handshake is done in the spawned task, so that slow clients can't hold up
+        // accepting other connections.
         tokio::spawn(async move {
-            if let Err(err) =
-                HyperHttpBuilder::new().serve_connection(TokioIo::new(stream), service).await
-            {
-                warn!(error = ?err, "Error serving connection.");
-            };
+            #[cfg(feature = "tls")]
+            if let Some(acceptor) = tls_acceptor {
+                match acceptor.accept(stream).await {
+                    Ok(stream) => Self::serve_connection(stream, responder, is_allowed).await,
+                    Err(e) => warn!(error = ?e, "Error completing TLS handshake."),
+                }
+                return;
+            }
+
+            Self::serve_connection(stream, responder, is_allowed).await;
         });
     }
 
-    fn handle_http_request(
-        is_allowed: bool,
-        handle: &PrometheusHandle,
-        req: &Request<Incoming>,
-    ) -> Response<Full<Bytes>> {
-        if is_allowed {
-            Response::new(match req.uri().path() {
-                "/health" => "OK".into(),
-                _ => handle.render().into(),
-            })
-        } else {
-            Self::new_forbidden_response()
-        }
+    async fn serve_connection<S>(stream: S, responder: Responder, is_allowed: bool)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let service = service_fn(move |req: Request<Incoming>| {
+            let responder = responder.clone();
+            async move {
+                let response = responder.respond(is_allowed, &req);
+                responder.handle.record_http_response(response.status().as_u16());
+                Ok::<_, hyper::Error>(response)
+            }
+        });
+
+        if let Err(err) =
+            HyperHttpBuilder::new().serve_connection(TokioIo::new(stream), service).await
+        {
+            warn!(error = ?err, "Error serving connection.");
+        };
     }
 
     fn new_forbidden_response() -> Response<Full<Bytes>> {
@@ -84,6 +163,157 @@ impl HttpListeningExporter {
         // will have to suffice to detect if this fails to hold true.
         Response::builder().status(StatusCode::FORBIDDEN).body(Full::<Bytes>::default()).unwrap()
     }
+
+    fn new_unauthorized_response(authorization: &[String]) -> Response<Full<Bytes>> {
+        // Clients are challenged with Basic authentication when it's accepted, as it's the scheme
+        // browsers know how to prompt for.
+        let accepts_basic =
+            authorization.iter().any(|credentials| credentials.starts_with("Basic "));
+        let challenge = if accepts_basic { "Basic realm=\"metrics\"" } else { "Bearer" };
+
+        let mut response = Response::new(Full::<Bytes>::default());
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        response.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static(challenge));
+        response
+    }
+}
+
+/// Responds to the requests made to the HTTP listener.
+#[derive(Clone)]
+struct Responder {
+    handle: PrometheusHandle,
+    negotiate_exposition_format: bool,
+    reject_unacceptable_formats: bool,
+    authorization: Arc<[String]>,
+    scrape_path: Option<Arc<str>>,
+    health_routes: Arc<[(String, HealthCheck)]>,
+    #[cfg(feature = "compression")]
+    compression: bool,
+}
+
+impl Responder {
+    fn respond<B>(&self, is_allowed: bool, req: &Request<B>) -> Response<Full<Bytes>> {
+        if !is_allowed {
+            return HttpListeningExporter::new_forbidden_response();
+        }
+
+        let path = req.uri().path();
+        if path == "/health" {
+            return Response::new("OK".into());
+        }
+
+        // Health routes are exempt from authentication, like `/health`, as they're usually
+        // requested by orchestrators which don't hold any credentials.
+        if let Some((_, check)) = self.health_routes.iter().find(|(route, _)| route == path) {
+            return if check() {
+                Response::new("OK".into())
+            } else {
+                let mut response = Response::new("Service Unavailable".into());
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                response
+            };
+        }
+
+        if !is_authorized(&self.authorization, req.headers().get(AUTHORIZATION)) {
+            return HttpListeningExporter::new_unauthorized_response(&self.authorization);
+        }
+
+        if self.scrape_path.as_deref().map_or(false, |scrape_path| scrape_path != path) {
+            let mut response = Response::new(Full::<Bytes>::default());
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return response;
+        }
+
+        let accept = self
+            .negotiate_exposition_format
+            .then(|| req.headers().get(ACCEPT))
+            .flatten()
+            .and_then(|accept| accept.to_str().ok());
+
+        let (body, content_type) = if accept.map_or(false, prefers_protobuf) {
+            (self.handle.render_protobuf(), PROTOBUF_CONTENT_TYPE)
+        } else {
+            let default_format = self.handle.exposition_format();
+            let negotiated = accept.and_then(ExpositionFormat::from_accept);
+            if negotiated.is_none()
+                && self.reject_unacceptable_formats
+                && accept.map_or(false, |accept| !accepts_format(accept, default_format))
+            {
+                let mut response = Response::new(Full::<Bytes>::default());
+                *response.status_mut() = StatusCode::NOT_ACCEPTABLE;
+                return response;
+            }
+
+            let format = negotiated.unwrap_or(default_format);
+            (self.handle.render_with_format(format).into_bytes(), format.content_type())
+        };
+
+        #[cfg(feature = "compression")]
+        if self.compression {
+            return Self::compressed_response(req, body, content_type);
+        }
+
+        let mut response = Response::new(body.into());
+        response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        response
+    }
+
+    #[cfg(feature = "compression")]
+    fn compressed_response<B>(
+        req: &Request<B>,
+        body: Vec<u8>,
+        content_type: &'static str,
+    ) -> Response<Full<Bytes>> {
+        let encoding = req
+            .headers()
+            .get(ACCEPT_ENCODING)
+            .and_then(|accept_encoding| accept_encoding.to_str().ok())
+            .and_then(ContentEncoding::from_accept_encoding)
+            .filter(|_| body.len() >= MIN_COMPRESSED_LENGTH);
+
+        // Responses are sent uncompressed if compressing them fails for any reason.
+        let compressed = encoding.and_then(|encoding| match encoding.encode(&body) {
+            Ok(compressed) => Some((encoding, compressed)),
+            Err(e) => {
+                warn!(error = ?e, "Error compressing response.");
+                None
+            }
+        });
+
+        let mut response = match compressed {
+            Some((encoding, compressed)) => {
+                let mut response = Response::new(compressed.into());
+                let encoding = HeaderValue::from_static(encoding.as_str());
+                response.headers_mut().insert(CONTENT_ENCODING, encoding);
+                response
+            }
+            None => Response::new(body.into()),
+        };
+
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers.insert(VARY, HeaderValue::from_static("accept-encoding"));
+        response
+    }
+}
+
+/// Whether the value of the `Authorization` header of a request matches any of the accepted
+/// credentials, which are the full values of the header, including the scheme.
+///
+/// Every request is authorized if no credentials are accepted.
+fn is_authorized(authorization: &[String], header: Option<&HeaderValue>) -> bool {
+    if authorization.is_empty() {
+        return true;
+    }
+
+    // The header is compared in constant time, so that its value can't be guessed by timing.
+    let header = header.map_or(&[][..], HeaderValue::as_bytes);
+    authorization.iter().fold(false, |authorized, credentials| {
+        let credentials = credentials.as_bytes();
+        let matches = credentials.len() == header.len()
+            && credentials.iter().zip(header).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+        authorized | matches
+    })
 }
 
 /// Creates an `ExporterFuture` implementing a http listener that servies prometheus metrics.
@@ -93,7 +323,7 @@ impl HttpListeningExporter {
 pub(crate) fn new_http_listener(
     handle: PrometheusHandle,
     listen_address: SocketAddr,
-    allowed_addresses: Option<Vec<IpNet>>,
+    settings: HttpListenerSettings,
 ) -> Result<ExporterFuture, BuildError> {
     let listener = std::net::TcpListener::bind(listen_address)
         .and_then(|listener| {
@@ -103,17 +333,191 @@ pub(crate) fn new_http_listener(
         .map_err(|e| BuildError::FailedToCreateHTTPListener(e.to_string()))?;
     let listener = TcpListener::from_std(listener).unwrap();
 
-    let exporter = HttpListeningExporter { handle, allowed_addresses };
+    let exporter = HttpListeningExporter::new(handle, settings);
 
     Ok(Box::pin(async move { exporter.serve(listener).await }))
 }
 
+/// Creates an `ExporterFuture` implementing a http listener that serves prometheus metrics over a
+/// Unix domain socket.
+///
+/// # Errors
+/// Will return Err if it cannot bind to the socket path
+#[cfg(unix)]
+pub(crate) fn new_http_uds_listener(
+    handle: PrometheusHandle,
+    path: &Path,
+    settings: HttpListenerSettings,
+) -> Result<ExporterFuture, BuildError> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // Sockets left behind by a previous run would otherwise make binding fail.
+    if std::fs::symlink_metadata(path).map_or(false, |metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path)
+            .map_err(|e| BuildError::FailedToCreateHTTPListener(e.to_string()))?;
+    }
+
+    let listener = std::os::unix::net::UnixListener::bind(path)
+        .and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        })
+        .map_err(|e| BuildError::FailedToCreateHTTPListener(e.to_string()))?;
+    let listener = UnixListener::from_std(listener).unwrap();
+
+    let exporter = HttpListeningExporter::new(handle, settings);
+
+    Ok(Box::pin(async move { exporter.serve_uds(listener).await }))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::exporter::http_listener::HttpListeningExporter;
+    use hyper::header::HeaderValue;
+
+    use crate::exporter::http_listener::{is_authorized, HttpListeningExporter};
 
     #[test]
     fn new_forbidden_response_always_succeeds() {
         HttpListeningExporter::new_forbidden_response(); // doesn't panic
     }
+
+    #[test]
+    fn test_is_authorized() {
+        let authorization = ["Basic dXNlcjpwYXNz".to_string(), "Bearer token".to_string()];
+        let header = HeaderValue::from_static;
+
+        assert!(is_authorized(&[], None));
+        assert!(is_authorized(&authorization, Some(&header("Basic dXNlcjpwYXNz"))));
+        assert!(is_authorized(&authorization, Some(&header("Bearer token"))));
+        assert!(!is_authorized(&authorization, Some(&header("Bearer toke"))));
+        assert!(!is_authorized(&authorization, None));
+
+        let response = HttpListeningExporter::new_unauthorized_response(&authorization);
+        assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_routes() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        use hyper::{Request, StatusCode};
+
+        use crate::exporter::http_listener::{HealthCheck, Responder};
+        use crate::PrometheusBuilder;
+
+        let ready = Arc::new(AtomicBool::new(false));
+        let check = Arc::clone(&ready);
+        let responder = Responder {
+            handle: PrometheusBuilder::new().build_recorder().handle(),
+            negotiate_exposition_format: false,
+            reject_unacceptable_formats: false,
+            authorization: vec!["Bearer token".to_string()].into(),
+            scrape_path: Some("/metrics".into()),
+            health_routes: vec![(
+                "/ready".to_string(),
+                Arc::new(move || check.load(Ordering::Relaxed)) as HealthCheck,
+            )]
+            .into(),
+            #[cfg(feature = "compression")]
+            compression: false,
+        };
+        let status = |path: &str| {
+            let req = Request::get(path).header("authorization", "Bearer token").body(()).unwrap();
+            responder.respond(true, &req).status()
+        };
+
+        assert_eq!(status("/metrics"), StatusCode::OK);
+        assert_eq!(status("/health"), StatusCode::OK);
+        assert_eq!(status("/other"), StatusCode::NOT_FOUND);
+        assert_eq!(status("/ready"), StatusCode::SERVICE_UNAVAILABLE);
+        ready.store(true, Ordering::Relaxed);
+        assert_eq!(status("/ready"), StatusCode::OK);
+
+        let req = Request::get("/ready").body(()).unwrap();
+        assert_eq!(responder.respond(true, &req).status(), StatusCode::OK);
+        let req = Request::get("/metrics").body(()).unwrap();
+        assert_eq!(responder.respond(true, &req).status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_reject_unacceptable_formats() {
+        use hyper::header::CONTENT_TYPE;
+        use hyper::{Request, StatusCode};
+
+        use crate::exporter::http_listener::Responder;
+        use crate::PrometheusBuilder;
+
+        let responder = Responder {
+            handle: PrometheusBuilder::new().build_recorder().handle(),
+            negotiate_exposition_format: true,
+            reject_unacceptable_formats: true,
+            authorization: Vec::new().into(),
+            scrape_path: None,
+            health_routes: Vec::new().into(),
+            #[cfg(feature = "compression")]
+            compression: false,
+        };
+        let respond = |accept: Option<&str>| {
+            let mut req = Request::get("/metrics");
+            if let Some(accept) = accept {
+                req = req.header("accept", accept);
+            }
+            responder.respond(true, &req.body(()).unwrap())
+        };
+
+        let response = respond(Some("application/openmetrics-text"));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[CONTENT_TYPE].to_str().unwrap().contains("openmetrics"));
+        assert_eq!(respond(Some("*/*")).status(), StatusCode::OK);
+        assert_eq!(respond(None).status(), StatusCode::OK);
+        assert_eq!(respond(Some("application/json")).status(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_uds_listener() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+
+        use crate::exporter::http_listener::{new_http_uds_listener, HttpListenerSettings};
+        use crate::PrometheusBuilder;
+
+        let file_name = format!("metrics-exporter-prometheus-{}.sock", std::process::id());
+        let path = std::env::temp_dir().join(file_name);
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+        let _guard = runtime.enter();
+
+        let handle = PrometheusBuilder::new().build_recorder().handle();
+        let settings = HttpListenerSettings {
+            allowed_addresses: None,
+            negotiate_exposition_format: false,
+            reject_unacceptable_formats: false,
+            authorization: Vec::new(),
+            scrape_path: None,
+            health_routes: Vec::new(),
+            #[cfg(feature = "compression")]
+            compression: true,
+            #[cfg(feature = "tls")]
+            tls_acceptor: None,
+        };
+        let exporter = new_http_uds_listener(handle, &path, settings).unwrap();
+        runtime.spawn(exporter);
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nOK"), "{}", response);
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
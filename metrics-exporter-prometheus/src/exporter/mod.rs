@@ -2,6 +2,8 @@
 use std::future::Future;
 #[cfg(feature = "http-listener")]
 use std::net::SocketAddr;
+#[cfg(all(feature = "http-listener", unix))]
+use std::path::PathBuf;
 #[cfg(any(feature = "http-listener", feature = "push-gateway"))]
 use std::pin::Pin;
 #[cfg(feature = "push-gateway")]
@@ -20,6 +22,10 @@ enum ExporterConfig {
     #[cfg(feature = "http-listener")]
     HttpListener { listen_address: SocketAddr },
 
+    // Run an HTTP listener on a Unix domain socket at the given `path`.
+    #[cfg(all(feature = "http-listener", unix))]
+    HttpUdsListener { path: PathBuf },
+
     // Run a push gateway task sending to the given `endpoint` after `interval` time has elapsed,
     // infinitely.
     #[cfg(feature = "push-gateway")]
@@ -30,6 +36,16 @@ enum ExporterConfig {
         password: Option<String>,
     },
 
+    // Run a task pushing to the given Remote Write `endpoint` after `interval` time has elapsed,
+    // infinitely.
+    #[cfg(feature = "remote-write")]
+    RemoteWrite {
+        endpoint: Uri,
+        interval: Duration,
+        username: Option<String>,
+        password: Option<String>,
+    },
+
     #[allow(dead_code)]
     Unconfigured,
 }
@@ -40,8 +56,12 @@ impl ExporterConfig {
         match self {
             #[cfg(feature = "http-listener")]
             Self::HttpListener { .. } => "http-listener",
+            #[cfg(all(feature = "http-listener", unix))]
+            Self::HttpUdsListener { .. } => "http-uds-listener",
             #[cfg(feature = "push-gateway")]
             Self::PushGateway { .. } => "push-gateway",
+            #[cfg(feature = "remote-write")]
+            Self::RemoteWrite { .. } => "remote-write",
             Self::Unconfigured => "unconfigured,",
         }
     }
@@ -52,5 +72,16 @@ mod http_listener;
 
 #[cfg(feature = "push-gateway")]
 mod push_gateway;
+#[cfg(feature = "push-gateway")]
+pub(crate) use self::push_gateway::PushRequest;
+
+#[cfg(feature = "remote-write")]
+mod remote_write;
+
+#[cfg(feature = "tls")]
+mod tls;
+
+#[cfg(feature = "compression")]
+mod compression;
 
 pub(crate) mod builder;
@@ -1,74 +1,367 @@
+use std::fmt::Write as _;
+use std::future::{poll_fn, Future};
+use std::path::Path;
+use std::pin::pin;
+use std::sync::mpsc::SyncSender;
+use std::task::Poll;
 use std::time::Duration;
 
 use http_body_util::{BodyExt, Collected, Full};
 use hyper::body::Bytes;
-use hyper::{header::HeaderValue, Method, Request, Uri};
+use hyper::http::uri::PathAndQuery;
+use hyper::{
+    header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    Method, Request, StatusCode, Uri,
+};
+use hyper_tls::native_tls::{Certificate, Identity, TlsConnector};
 use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
 use hyper_util::{client::legacy::Client, rt::TokioExecutor};
-use tracing::error;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::{error, warn};
 
 use super::ExporterFuture;
+use crate::common::BuildError;
 use crate::PrometheusHandle;
 
-// Creates an ExporterFuture implementing a push gateway.
-pub(super) fn new_push_gateway(
+/// The delay before the first retry of a failed push, which doubles with every further retry.
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// A request from the recorder to a push-based exporter, acknowledged once it has been handled.
+pub(crate) enum PushRequest {
+    /// Push a snapshot of the metrics right away.
+    Flush(SyncSender<()>),
+
+    /// Push a final snapshot of the metrics, and stop pushing.
+    Shutdown(SyncSender<()>),
+}
+
+impl PushRequest {
+    /// Acknowledges the request, returning whether the exporter should stop pushing.
+    pub(super) fn acknowledge(self) -> bool {
+        let (shutting_down, ack) = match self {
+            PushRequest::Flush(ack) => (false, ack),
+            PushRequest::Shutdown(ack) => (true, ack),
+        };
+        let _ = ack.send(());
+        shutting_down
+    }
+}
+
+/// Settings for pushing metrics, beyond the endpoint they're pushed to and the interval between
+/// pushes.
+///
+/// The grouping labels, the HTTP method and deleting metrics on shutdown only apply to push
+/// gateways.
+pub(crate) struct PushSettings {
+    pub bearer_token: Option<String>,
+    pub grouping_labels: Vec<(String, String)>,
+    pub use_http_post: bool,
+    pub max_retries: u32,
+    pub delete_on_shutdown: bool,
+    root_certificates: Vec<Certificate>,
+    identity: Option<Identity>,
+}
+
+impl Default for PushSettings {
+    fn default() -> Self {
+        PushSettings {
+            bearer_token: None,
+            grouping_labels: Vec::new(),
+            use_http_post: false,
+            max_retries: 3,
+            delete_on_shutdown: false,
+            root_certificates: Vec::new(),
+            identity: None,
+        }
+    }
+}
+
+impl PushSettings {
+    /// Trusts the CA certificates in the given PEM file, in addition to the system's.
+    pub fn add_root_certificates(&mut self, ca_path: &Path) -> Result<(), BuildError> {
+        let pem = read(ca_path)?;
+        let certificate = Certificate::from_pem(&pem).map_err(|e| invalid(ca_path, &e))?;
+        self.root_certificates.push(certificate);
+        Ok(())
+    }
+
+    /// Sets the certificate chain and private key presented to the push gateway, loaded from the
+    /// given PEM files.
+    pub fn set_identity(&mut self, cert_path: &Path, key_path: &Path) -> Result<(), BuildError> {
+        let identity = Identity::from_pkcs8(&read(cert_path)?, &read(key_path)?)
+            .map_err(|e| invalid(cert_path, &e))?;
+        self.identity = Some(identity);
+        Ok(())
+    }
+
+    fn connector(&self) -> Result<HttpsConnector<HttpConnector>, BuildError> {
+        let mut tls = TlsConnector::builder();
+        for certificate in &self.root_certificates {
+            tls.add_root_certificate(certificate.clone());
+        }
+        if let Some(identity) = &self.identity {
+            tls.identity(identity.clone());
+        }
+        let tls = tls.build().map_err(|e| BuildError::InvalidTlsConfiguration(e.to_string()))?;
+
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        Ok(HttpsConnector::from((http, tls.into())))
+    }
+}
+
+/// The outcome of a push request.
+pub(super) enum Outcome {
+    Succeeded,
+    Retryable,
+    Failed,
+}
+
+/// A client pushing metrics to an HTTP endpoint, such as a push gateway.
+pub(super) struct PushClient {
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
     endpoint: Uri,
-    interval: Duration,
-    username: Option<String>,
-    password: Option<String>,
-    handle: PrometheusHandle,
-) -> ExporterFuture {
-    Box::pin(async move {
-        let https = HttpsConnector::new();
-        let client: Client<_, Full<Bytes>> = Client::builder(TokioExecutor::new())
+    authorization: Option<HeaderValue>,
+    max_retries: u32,
+    max_backoff: Duration,
+}
+
+impl PushClient {
+    /// Creates a client pushing to the given endpoint, authenticating with the given credentials,
+    /// unless a bearer token was set, and waiting at most `max_backoff` between retries.
+    pub fn new(
+        endpoint: Uri,
+        username: Option<&str>,
+        password: Option<&str>,
+        settings: &PushSettings,
+        max_backoff: Duration,
+    ) -> Result<PushClient, BuildError> {
+        let authorization = match (&settings.bearer_token, username) {
+            (Some(token), _) => Some(bearer_auth(token)?),
+            (None, Some(name)) => Some(basic_auth(name, password)),
+            (None, None) => None,
+        };
+        let client = Client::builder(TokioExecutor::new())
             .pool_idle_timeout(Duration::from_secs(30))
-            .build(https);
+            .build(settings.connector()?);
 
-        let auth = username.as_ref().map(|name| basic_auth(name, password.as_deref()));
+        Ok(PushClient {
+            client,
+            endpoint,
+            authorization,
+            max_retries: settings.max_retries,
+            max_backoff,
+        })
+    }
 
+    /// Sends a request to the endpoint, retrying with an exponential backoff while it fails in a
+    /// way that may be transient.
+    ///
+    /// Returns the outcome of the last attempt.
+    pub async fn send_with_retries(
+        &self,
+        method: Method,
+        headers: &[(HeaderName, HeaderValue)],
+        body: Bytes,
+    ) -> Outcome {
+        let mut backoff = INITIAL_RETRY_BACKOFF;
+        let mut attempt = 0;
         loop {
-            // Sleep for `interval` amount of time, and then do a push.
-            tokio::time::sleep(interval).await;
+            match self.send(method.clone(), headers, body.clone()).await {
+                Outcome::Retryable if attempt < self.max_retries => {
+                    attempt += 1;
+                    warn!(attempt, endpoint = %self.endpoint, "retrying push request");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                }
+                outcome => return outcome,
+            }
+        }
+    }
 
-            let mut builder = Request::builder();
-            if let Some(auth) = &auth {
-                builder = builder.header("authorization", auth.clone());
+    async fn send(
+        &self,
+        method: Method,
+        headers: &[(HeaderName, HeaderValue)],
+        body: Bytes,
+    ) -> Outcome {
+        let mut builder = Request::builder().method(method).uri(self.endpoint.clone());
+        for (name, value) in headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(auth) = &self.authorization {
+            builder = builder.header(AUTHORIZATION, auth.clone());
+        }
+
+        let req = match builder.body(Full::from(body)) {
+            Ok(req) => req,
+            Err(e) => {
+                error!("failed to build push request: {}", e);
+                return Outcome::Failed;
             }
+        };
 
-            let output = handle.render();
-            let result = builder.method(Method::PUT).uri(endpoint.clone()).body(Full::from(output));
-            let req = match result {
-                Ok(req) => req,
-                Err(e) => {
-                    error!("failed to build push gateway request: {}", e);
-                    continue;
+        match self.client.request(req).await {
+            Ok(response) => {
+                let status = response.status();
+                if status.is_success() {
+                    return Outcome::Succeeded;
                 }
-            };
 
-            match client.request(req).await {
-                Ok(response) => {
-                    if !response.status().is_success() {
-                        let status = response.status();
-                        let status = status.canonical_reason().unwrap_or_else(|| status.as_str());
-                        let body = response
-                            .into_body()
-                            .collect()
-                            .await
-                            .map(Collected::to_bytes)
-                            .map_err(|_| ())
-                            .and_then(|b| String::from_utf8(b[..].to_vec()).map_err(|_| ()))
-                            .unwrap_or_else(|()| String::from("<failed to read response body>"));
-                        error!(
-                            message = "unexpected status after pushing metrics to push gateway",
-                            status,
-                            %body,
-                        );
-                    }
+                let reason = status.canonical_reason().unwrap_or_else(|| status.as_str());
+                let body = response
+                    .into_body()
+                    .collect()
+                    .await
+                    .map(Collected::to_bytes)
+                    .map_err(|_| ())
+                    .and_then(|b| String::from_utf8(b[..].to_vec()).map_err(|_| ()))
+                    .unwrap_or_else(|()| String::from("<failed to read response body>"));
+                error!(
+                    message = "unexpected status after pushing metrics",
+                    endpoint = %self.endpoint,
+                    status = reason,
+                    %body,
+                );
+
+                if is_retryable(status) {
+                    Outcome::Retryable
+                } else {
+                    Outcome::Failed
                 }
-                Err(e) => error!("error sending request to push gateway: {:?}", e),
+            }
+            Err(e) => {
+                error!("error sending request to {}: {:?}", self.endpoint, e);
+                Outcome::Retryable
+            }
+        }
+    }
+}
+
+/// Waits until the next push is due, either because `interval` elapsed, or because the recorder
+/// requested one, in which case the request is returned.
+///
+/// Once the recorder has been dropped, pushes are only driven by the interval.
+pub(super) async fn next_push(
+    requests: &mut Option<UnboundedReceiver<PushRequest>>,
+    interval: Duration,
+) -> Option<PushRequest> {
+    let mut sleep = pin!(tokio::time::sleep(interval));
+    poll_fn(|cx| {
+        if let Some(receiver) = requests.as_mut() {
+            match receiver.poll_recv(cx) {
+                Poll::Ready(Some(request)) => return Poll::Ready(Some(request)),
+                Poll::Ready(None) => *requests = None,
+                Poll::Pending => {}
             }
         }
+        sleep.as_mut().poll(cx).map(|()| None)
     })
+    .await
+}
+
+// Creates an ExporterFuture implementing a push gateway.
+pub(super) fn new_push_gateway(
+    endpoint: &Uri,
+    interval: Duration,
+    username: Option<&str>,
+    password: Option<&str>,
+    handle: PrometheusHandle,
+    settings: &PushSettings,
+    requests: UnboundedReceiver<PushRequest>,
+) -> Result<ExporterFuture, BuildError> {
+    let endpoint = grouping_key_uri(endpoint, &settings.grouping_labels)?;
+    let client = PushClient::new(endpoint, username, password, settings, interval)?;
+    let method = if settings.use_http_post { Method::POST } else { Method::PUT };
+    let delete_on_shutdown = settings.delete_on_shutdown;
+    let mut requests = Some(requests);
+
+    Ok(Box::pin(async move {
+        loop {
+            // Wait for `interval` amount of time, or for the recorder to request a push, and then
+            // do a push.
+            let request = next_push(&mut requests, interval).await;
+
+            let request = match request {
+                // Pushing a final snapshot is moot if the group is deleted right after.
+                Some(request @ PushRequest::Shutdown(_)) if delete_on_shutdown => {
+                    client.send_with_retries(Method::DELETE, &[], Bytes::new()).await;
+                    request.acknowledge();
+                    return Ok(());
+                }
+                request => request,
+            };
+
+            let format = handle.exposition_format();
+            let output = handle.render_with_format(format);
+            let headers = [(CONTENT_TYPE, HeaderValue::from_static(format.content_type()))];
+            let outcome = client.send_with_retries(method.clone(), &headers, output.into()).await;
+            if !matches!(outcome, Outcome::Succeeded) {
+                handle.record_push_failure();
+            }
+
+            if request.is_some_and(PushRequest::acknowledge) {
+                return Ok(());
+            }
+        }
+    }))
+}
+
+/// Whether a request which got a response with the given status may succeed if retried.
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Appends the given grouping labels to the path of the endpoint, as `/<name>/<value>` segments.
+///
+/// Values which are empty, or which contain characters that would need to be escaped, are encoded
+/// in URL-safe base64, as `/<name>@base64/<encoded value>`.
+fn grouping_key_uri(endpoint: &Uri, labels: &[(String, String)]) -> Result<Uri, BuildError> {
+    use base64::{prelude::BASE64_URL_SAFE, Engine};
+
+    if labels.is_empty() {
+        return Ok(endpoint.clone());
+    }
+
+    let mut path_and_query = endpoint.path().trim_end_matches('/').to_string();
+    for (name, value) in labels {
+        let unreserved =
+            |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~');
+        let _ = if value.is_empty() {
+            write!(path_and_query, "/{name}@base64/=")
+        } else if value.bytes().all(unreserved) {
+            write!(path_and_query, "/{name}/{value}")
+        } else {
+            write!(path_and_query, "/{name}@base64/{}", BASE64_URL_SAFE.encode(value))
+        };
+    }
+    if let Some(query) = endpoint.query() {
+        path_and_query.push('?');
+        path_and_query.push_str(query);
+    }
+
+    let mut parts = endpoint.clone().into_parts();
+    let path_and_query = path_and_query
+        .parse::<PathAndQuery>()
+        .map_err(|e| BuildError::InvalidPushGatewayEndpoint(e.to_string()))?;
+    parts.path_and_query = Some(path_and_query);
+    Uri::from_parts(parts).map_err(|e| BuildError::InvalidPushGatewayEndpoint(e.to_string()))
+}
+
+fn bearer_auth(token: &str) -> Result<HeaderValue, BuildError> {
+    let mut header = HeaderValue::from_str(&format!("Bearer {token}"))
+        .map_err(|e| BuildError::InvalidPushGatewayCredentials(e.to_string()))?;
+    header.set_sensitive(true);
+    Ok(header)
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, BuildError> {
+    std::fs::read(path).map_err(|e| invalid(path, &e))
+}
+
+fn invalid(path: &Path, error: &dyn std::fmt::Display) -> BuildError {
+    BuildError::InvalidTlsConfiguration(format!("{}: {}", path.display(), error))
 }
 
 #[cfg(feature = "push-gateway")]
@@ -92,7 +385,9 @@ fn basic_auth(username: &str, password: Option<&str>) -> HeaderValue {
 
 #[cfg(all(test))]
 mod tests {
-    use super::basic_auth;
+    use hyper::{StatusCode, Uri};
+
+    use super::{basic_auth, grouping_key_uri, is_retryable, next_push, PushRequest};
 
     #[test]
     #[allow(clippy::similar_names)] // reader vs header, sheesh clippy
@@ -125,4 +420,59 @@ mod tests {
         assert_eq!(b"metrics:123!_@ABC", &result[..]);
         assert!(header.is_sensitive());
     }
+
+    #[test]
+    fn test_grouping_key_uri() {
+        let endpoint = Uri::from_static("https://gateway:9091/metrics/job/batch/");
+        assert_eq!(grouping_key_uri(&endpoint, &[]).unwrap(), endpoint);
+
+        let labels = [
+            ("instance".to_string(), "host-1.local".to_string()),
+            ("path".to_string(), "/var/tmp".to_string()),
+            ("zone".to_string(), String::new()),
+        ];
+        let uri = grouping_key_uri(&endpoint, &labels).unwrap();
+        assert_eq!(
+            uri,
+            "https://gateway:9091/metrics/job/batch/instance/host-1.local/path@base64/L3Zhci90bXA=/zone@base64/="
+        );
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_next_push() {
+        use std::sync::mpsc::sync_channel;
+        use std::time::Duration;
+        use tokio::sync::mpsc::unbounded_channel;
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            // Requests are handled right away, rather than once the interval elapses.
+            let (sender, receiver) = unbounded_channel();
+            let mut requests = Some(receiver);
+            let (ack, acked) = sync_channel(1);
+            sender.send(PushRequest::Flush(ack)).unwrap();
+            let request = next_push(&mut requests, Duration::from_secs(3600)).await.unwrap();
+            assert!(!request.acknowledge());
+            acked.recv().unwrap();
+
+            let (ack, _acked) = sync_channel(1);
+            sender.send(PushRequest::Shutdown(ack)).unwrap();
+            let request = next_push(&mut requests, Duration::from_secs(3600)).await.unwrap();
+            assert!(request.acknowledge());
+
+            // Once the recorder is dropped, pushes are only driven by the interval.
+            drop(sender);
+            assert!(next_push(&mut requests, Duration::from_millis(10)).await.is_none());
+            assert!(requests.is_none());
+            assert!(next_push(&mut requests, Duration::from_millis(10)).await.is_none());
+        });
+    }
 }
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use hyper::header::{HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE, USER_AGENT};
+use hyper::{Method, Uri};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::error;
+
+use super::push_gateway::{next_push, Outcome, PushClient, PushRequest, PushSettings};
+use super::ExporterFuture;
+use crate::common::BuildError;
+use crate::remote_write::{REMOTE_WRITE_CONTENT_TYPE, REMOTE_WRITE_VERSION};
+use crate::PrometheusHandle;
+
+const REMOTE_WRITE_VERSION_HEADER: HeaderName =
+    HeaderName::from_static("x-prometheus-remote-write-version");
+
+// Creates an ExporterFuture implementing a Remote Write client.
+pub(super) fn new_remote_write(
+    endpoint: Uri,
+    interval: Duration,
+    username: Option<&str>,
+    password: Option<&str>,
+    handle: PrometheusHandle,
+    settings: &PushSettings,
+    max_series: usize,
+    requests: UnboundedReceiver<PushRequest>,
+) -> Result<ExporterFuture, BuildError> {
+    let client = PushClient::new(endpoint, username, password, settings, interval)?;
+    let user_agent = concat!("metrics-exporter-prometheus/", env!("CARGO_PKG_VERSION"));
+    let headers = [
+        (CONTENT_TYPE, HeaderValue::from_static(REMOTE_WRITE_CONTENT_TYPE)),
+        (CONTENT_ENCODING, HeaderValue::from_static("snappy")),
+        (USER_AGENT, HeaderValue::from_static(user_agent)),
+        (REMOTE_WRITE_VERSION_HEADER, HeaderValue::from_static(REMOTE_WRITE_VERSION)),
+    ];
+
+    Ok(Box::pin(async move {
+        let mut encoder = snap::raw::Encoder::new();
+        let mut requests = Some(requests);
+        loop {
+            // Wait for `interval` amount of time, or for the recorder to request a push, and then
+            // push a snapshot of the metrics.
+            let request = next_push(&mut requests, interval).await;
+
+            // Requests are sent one at a time, so that a slow backend delays pushes, rather than
+            // letting requests pile up.
+            for request in handle.render_remote_write(max_series) {
+                let body = match encoder.compress_vec(&request) {
+                    Ok(body) => body,
+                    Err(e) => {
+                        error!("failed to compress remote write request: {}", e);
+                        continue;
+                    }
+                };
+
+                // The rest of the snapshot is dropped if the backend is unavailable, as the next
+                // snapshot supersedes it.
+                let outcome = client.send_with_retries(Method::POST, &headers, body.into()).await;
+                if !matches!(outcome, Outcome::Succeeded) {
+                    handle.record_push_failure();
+                }
+                if let Outcome::Retryable = outcome {
+                    break;
+                }
+            }
+
+            if request.is_some_and(PushRequest::acknowledge) {
+                return Ok(());
+            }
+        }
+    }))
+}
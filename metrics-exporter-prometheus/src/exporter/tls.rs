@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::common::BuildError;
+
+/// TLS configuration for the scrape endpoint.
+#[derive(Default)]
+pub(crate) struct TlsConfig {
+    identity: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    client_roots: Option<RootCertStore>,
+}
+
+impl TlsConfig {
+    /// Sets the certificate chain and private key presented by the scrape endpoint, loaded from the
+    /// given PEM files.
+    pub fn set_identity(&mut self, cert_path: &Path, key_path: &Path) -> Result<(), BuildError> {
+        let cert_chain = load_certs(cert_path)?;
+        let key = rustls_pemfile::private_key(&mut open(key_path)?)
+            .map_err(|e| invalid(key_path, &e))?
+            .ok_or_else(|| invalid(key_path, &"no private key found"))?;
+
+        self.identity = Some((cert_chain, key));
+        Ok(())
+    }
+
+    /// Requires clients to present a certificate signed by one of the CA certificates in the given
+    /// PEM file.
+    pub fn set_client_roots(&mut self, ca_path: &Path) -> Result<(), BuildError> {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(ca_path)? {
+            roots.add(cert).map_err(|e| invalid(ca_path, &e))?;
+        }
+
+        self.client_roots = Some(roots);
+        Ok(())
+    }
+
+    /// Builds an acceptor for TLS connections, if TLS was configured.
+    pub fn into_acceptor(self) -> Result<Option<TlsAcceptor>, BuildError> {
+        let (cert_chain, key) = match self.identity {
+            Some(identity) => identity,
+            None if self.client_roots.is_some() => {
+                return Err(BuildError::InvalidTlsConfiguration(
+                    "client certificate verification requires a server certificate".to_string(),
+                ))
+            }
+            None => return Ok(None),
+        };
+
+        let builder = ServerConfig::builder();
+        let builder = match self.client_roots {
+            Some(roots) => {
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                    .build()
+                    .map_err(|e| BuildError::InvalidTlsConfiguration(e.to_string()))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder
+            .with_single_cert(cert_chain, key)
+            .map_err(|e| BuildError::InvalidTlsConfiguration(e.to_string()))?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Some(TlsAcceptor::from(Arc::new(config))))
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, BuildError> {
+    let certs = rustls_pemfile::certs(&mut open(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid(path, &e))?;
+    if certs.is_empty() {
+        return Err(invalid(path, &"no certificates found"));
+    }
+    Ok(certs)
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, BuildError> {
+    File::open(path).map(BufReader::new).map_err(|e| invalid(path, &e))
+}
+
+fn invalid(path: &Path, error: &dyn std::fmt::Display) -> BuildError {
+    BuildError::InvalidTlsConfiguration(format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::TlsConfig;
+    use crate::common::BuildError;
+
+    #[test]
+    fn test_invalid_tls_config() {
+        let mut config = TlsConfig::default();
+        let missing = Path::new("/nonexistent/cert.pem");
+        let result = config.set_identity(missing, missing);
+        assert!(matches!(result, Err(BuildError::InvalidTlsConfiguration(_))));
+
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let result = config.set_client_roots(&manifest);
+        assert!(matches!(result, Err(BuildError::InvalidTlsConfiguration(_))));
+
+        assert!(matches!(TlsConfig::default().into_acceptor(), Ok(None)));
+    }
+}
@@ -38,6 +38,20 @@ pub fn write_help_line(buffer: &mut String, name: &str, desc: &str) {
     buffer.push('\n');
 }
 
+/// Writes a help (description) line in the [OpenMetrics] text format.
+///
+/// Unlike the Prometheus exposition format, double quotes in the description are escaped as well.
+///
+/// [OpenMetrics]: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md#metricfamily
+pub fn write_openmetrics_help_line(buffer: &mut String, name: &str, desc: &str) {
+    buffer.push_str("# HELP ");
+    buffer.push_str(name);
+    buffer.push(' ');
+    let desc = sanitize_label_value(desc);
+    buffer.push_str(&desc);
+    buffer.push('\n');
+}
+
 /// Writes a metric type line in the Prometheus [exposition format].
 ///
 /// [exposition format]: https://github.com/prometheus/docs/blob/main/content/docs/instrumenting/exposition_formats.md#text-format-details
@@ -49,6 +63,20 @@ pub fn write_type_line(buffer: &mut String, name: &str, metric_type: &str) {
     buffer.push('\n');
 }
 
+/// Writes a unit line in the [OpenMetrics] text format.
+///
+/// The format requires the name of the metric family to end with the unit, such as
+/// `request_duration_seconds` for a unit of `seconds`, which is left to the caller to check.
+///
+/// [OpenMetrics]: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md#metricfamily
+pub fn write_unit_line(buffer: &mut String, name: &str, unit: &str) {
+    buffer.push_str("# UNIT ");
+    buffer.push_str(name);
+    buffer.push(' ');
+    buffer.push_str(unit);
+    buffer.push('\n');
+}
+
 /// Writes a metric in the Prometheus [exposition format].
 ///
 /// When `suffix` is specified, it is appended to the `name`, which is useful for writing summary
@@ -67,6 +95,28 @@ pub fn write_metric_line<T, T2>(
 ) where
     T: std::fmt::Display,
     T2: std::fmt::Display,
+{
+    write_metric_line_with_exemplar(buffer, name, suffix, labels, additional_label, value, None);
+}
+
+/// Writes a metric in the [OpenMetrics] text format, with an optional exemplar.
+///
+/// This behaves like [`write_metric_line`], except that `exemplar`, when specified, is appended to
+/// the line after a `#`.  It must already be rendered in the format, such as
+/// `{trace_id="abc123"} 0.67 1520879607.789`.
+///
+/// [OpenMetrics]: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md#exemplars
+pub fn write_metric_line_with_exemplar<T, T2>(
+    buffer: &mut String,
+    name: &str,
+    suffix: Option<&'static str>,
+    labels: &[String],
+    additional_label: Option<(&'static str, T)>,
+    value: T2,
+    exemplar: Option<&str>,
+) where
+    T: std::fmt::Display,
+    T2: std::fmt::Display,
 {
     buffer.push_str(name);
     if let Some(suffix) = suffix {
@@ -102,6 +152,10 @@ pub fn write_metric_line<T, T2>(
 
     buffer.push(' ');
     buffer.push_str(value.to_string().as_str());
+    if let Some(exemplar) = exemplar {
+        buffer.push_str(" # ");
+        buffer.push_str(exemplar);
+    }
     buffer.push('\n');
 }
 
@@ -321,7 +375,7 @@ mod tests {
             let as_chars = result.chars().collect::<Vec<_>>();
 
             if let Some(c) = as_chars.first() {
-                assert_eq!(false, invalid_metric_name_start_character(*c),
+                assert!(!invalid_metric_name_start_character(*c),
                     "first character of metric name was not valid");
             }
 
@@ -335,7 +389,7 @@ mod tests {
             let as_chars = result.chars().collect::<Vec<_>>();
 
             if let Some(c) = as_chars.first() {
-                assert_eq!(false, invalid_label_key_start_character(*c),
+                assert!(!invalid_label_key_start_character(*c),
                     "first character of label key was not valid");
             }
 
@@ -369,7 +423,7 @@ mod tests {
             let as_chars = delayered_backslashes.chars().collect::<Vec<_>>();
 
             // If the first character is a double quote, then we messed up.
-            assert!(as_chars.first().map(|c| *c != '"').unwrap_or(true),
+            assert!(as_chars.first().map_or(true, |c| *c != '"'),
                 "first character cannot be a double quote: {}", result);
 
             // Now look for unescaped characters in the rest of the string, in a windowed fashion.
@@ -8,13 +8,22 @@
 //!
 //! ## High-level features
 //!
-//! - scrape endpoint support
+//! - scrape endpoint support, over TCP or a Unix domain socket
 //! - push gateway support
+//! - pushing to Prometheus Remote Write backends, such as Mimir, `VictoriaMetrics` or Thanos
+//!   Receive
 //! - IP-based allowlist for scrape endpoint
+//! - Basic or bearer token authentication for scrape endpoint
+//! - TLS for scrape endpoint, with optional client certificate verification
 //! - ability to push histograms as either aggregated summaries or aggregated histograms, with
 //!   configurable quantiles/buckets
 //! - ability to control bucket configuration on a per-metric basis
 //! - configurable global labels (applied to all metrics, overridden by metric's own labels if present)
+//! - rendering in either the Prometheus exposition format or the OpenMetrics text format, optionally
+//!   negotiated per scrape
+//! - exemplars on counters and histogram buckets, when rendering in the OpenMetrics format
+//! - rendering in the Prometheus protobuf format, including native histograms
+//! - serving metrics from an existing HTTP server, as a `tower` service
 //!
 //! ## Behavior
 //!
@@ -95,6 +104,15 @@
 //! to create or build an exporter, at least one of these feature flags must be enabled.  Builder
 //! methods that require certain feature flags will be documented as such.
 //!
+//! Additionally, the **`tls`** feature flag allows serving the scrape endpoint over TLS, optionally
+//! verifying client certificates, via `rustls` (_disabled by default_).  The **`compression`**
+//! feature flag allows compressing scrape responses with gzip, and the **`compression-zstd`**
+//! feature flag additionally with zstd, when accepted by the scraper (_both disabled by default_).
+//! The **`remote-write`** feature flag allows pushing metrics to a backend speaking the Prometheus
+//! Remote Write protocol (_disabled by default_).  The **`tower`** feature flag allows serving
+//! metrics from an existing `hyper` or `axum` server, via [`PrometheusHandle::as_service`]
+//! (_disabled by default_).
+//!
 //! [metrics]: https://docs.rs/metrics/latest/metrics/
 //! [data model]: https://prometheus.io/docs/concepts/data_model/
 //! [exposition format]: https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
@@ -105,10 +123,13 @@
 #![deny(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg), deny(rustdoc::broken_intra_doc_links))]
 mod common;
-pub use self::common::{BuildError, Matcher};
+pub use self::common::{BuildError, ExpositionFormat, Matcher};
 
 mod distribution;
 pub use distribution::{Distribution, DistributionBuilder};
+pub use metrics_util::SummaryWindow;
+
+mod exemplar;
 
 mod exporter;
 pub use self::exporter::builder::PrometheusBuilder;
@@ -117,8 +138,22 @@ pub use self::exporter::builder::PrometheusBuilder;
 pub use self::exporter::ExporterFuture;
 
 pub mod formatting;
+
+mod protobuf;
+pub use self::protobuf::PROTOBUF_CONTENT_TYPE;
+
 mod recorder;
 
 mod registry;
 
+#[cfg(feature = "remote-write")]
+mod remote_write;
+
+mod self_metrics;
+
+#[cfg(feature = "tower")]
+mod service;
+#[cfg(feature = "tower")]
+pub use self::service::PrometheusService;
+
 pub use self::recorder::{PrometheusHandle, PrometheusRecorder};
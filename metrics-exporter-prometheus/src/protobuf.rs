@@ -0,0 +1,434 @@
+//! Helpers for rendering metrics in the Prometheus protobuf exposition format.
+//!
+//! Metric families are encoded as length-delimited `io.prometheus.client.MetricFamily` messages, as
+//! defined by [`metrics.proto`], which is simple enough that the encoding is done by hand.
+//!
+//! [`metrics.proto`]: https://github.com/prometheus/client_model/blob/master/io/prometheus/client/metrics.proto
+
+use std::collections::HashMap;
+use std::iter;
+
+use indexmap::IndexMap;
+use metrics_util::{ExponentialBuckets, ExponentialHistogram};
+use quanta::Instant;
+
+use crate::distribution::{native_histogram_buckets, Distribution};
+use crate::exemplar::{BucketExemplars, Exemplar};
+
+/// The value of the `Content-Type` header for payloads in the Prometheus protobuf exposition
+/// format.
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/vnd.google.protobuf; \
+    proto=io.prometheus.client.MetricFamily; encoding=delimited";
+
+/// The lowest schema supported by Prometheus native histograms.
+///
+/// Native histograms whose scale went below it are exposed as regular histograms instead.
+const NATIVE_HISTOGRAM_MIN_SCHEMA: i32 = -4;
+
+const WIRE_TYPE_VARINT: u32 = 0;
+const WIRE_TYPE_FIXED64: u32 = 1;
+const WIRE_TYPE_LENGTH_DELIMITED: u32 = 2;
+
+const METRIC_TYPE_COUNTER: u64 = 0;
+const METRIC_TYPE_GAUGE: u64 = 1;
+const METRIC_TYPE_SUMMARY: u64 = 2;
+const METRIC_TYPE_HISTOGRAM: u64 = 4;
+
+/// Writes a counter metric family.
+#[allow(clippy::cast_precision_loss)]
+pub fn write_counter_family(
+    buffer: &mut Vec<u8>,
+    name: &str,
+    help: Option<&str>,
+    metrics: &HashMap<Vec<String>, (u64, Option<Exemplar>)>,
+) {
+    write_metric_family(buffer, name, help, METRIC_TYPE_COUNTER, |family| {
+        for (labels, (value, exemplar)) in metrics {
+            write_metric(family, labels, |metric| {
+                write_message_field(metric, 3, |counter| {
+                    write_double_field(counter, 1, *value as f64);
+                    if let Some(exemplar) = exemplar {
+                        write_exemplar(counter, 2, exemplar);
+                    }
+                });
+            });
+        }
+    });
+}
+
+/// Writes a gauge metric family.
+pub fn write_gauge_family(
+    buffer: &mut Vec<u8>,
+    name: &str,
+    help: Option<&str>,
+    metrics: &HashMap<Vec<String>, f64>,
+) {
+    write_metric_family(buffer, name, help, METRIC_TYPE_GAUGE, |family| {
+        for (labels, value) in metrics {
+            write_metric(family, labels, |metric| {
+                write_message_field(metric, 2, |gauge| write_double_field(gauge, 1, *value));
+            });
+        }
+    });
+}
+
+/// Writes a summary or histogram metric family.
+///
+/// The type of the family is determined by its first distribution, as every distribution of a
+/// given metric is of the same type.
+pub fn write_distribution_family(
+    buffer: &mut Vec<u8>,
+    name: &str,
+    help: Option<&str>,
+    metrics: &IndexMap<Vec<String>, (Distribution, BucketExemplars)>,
+) {
+    let metric_type = match metrics.values().next() {
+        Some((Distribution::Summary(..), _)) => METRIC_TYPE_SUMMARY,
+        _ => METRIC_TYPE_HISTOGRAM,
+    };
+
+    write_metric_family(buffer, name, help, metric_type, |family| {
+        for (labels, (distribution, exemplars)) in metrics {
+            write_metric(family, labels, |metric| match distribution {
+                Distribution::Summary(summary, quantiles, sum) => {
+                    write_message_field(metric, 4, |message| {
+                        let snapshot = summary.snapshot(Instant::now());
+                        write_uint64_field(message, 1, summary.count() as u64);
+                        write_double_field(message, 2, *sum);
+                        for quantile in quantiles.iter() {
+                            let value = snapshot.quantile(quantile.value()).unwrap_or(0.0);
+                            write_message_field(message, 3, |message| {
+                                write_double_field(message, 1, quantile.value());
+                                write_double_field(message, 2, value);
+                            });
+                        }
+                    });
+                }
+                Distribution::Histogram(histogram) => {
+                    write_message_field(metric, 7, |message| {
+                        write_classic_histogram(
+                            message,
+                            &histogram.buckets(),
+                            histogram.count(),
+                            histogram.sum(),
+                            |bucket| exemplars.get(bucket),
+                        );
+                    });
+                }
+                Distribution::NativeHistogram(histogram) => {
+                    write_message_field(metric, 7, |message| {
+                        write_native_histogram(message, histogram, exemplars);
+                    });
+                }
+            });
+        }
+    });
+}
+
+fn write_metric_family<F>(
+    buffer: &mut Vec<u8>,
+    name: &str,
+    help: Option<&str>,
+    metric_type: u64,
+    write_metrics: F,
+) where
+    F: FnOnce(&mut Vec<u8>),
+{
+    let mut family = Vec::new();
+    write_string_field(&mut family, 1, name);
+    if let Some(help) = help {
+        write_string_field(&mut family, 2, help);
+    }
+    write_uint64_field(&mut family, 3, metric_type);
+    write_metrics(&mut family);
+
+    write_varint(buffer, family.len() as u64);
+    buffer.extend_from_slice(&family);
+}
+
+/// Writes a metric, with the given preformatted labels, to a metric family.
+fn write_metric<F>(family: &mut Vec<u8>, labels: &[String], write_value: F)
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    write_message_field(family, 4, |metric| {
+        for label in labels {
+            let (name, value) = parse_label(label);
+            write_message_field(metric, 1, |pair| {
+                write_string_field(pair, 1, name);
+                write_string_field(pair, 2, &value);
+            });
+        }
+        write_value(metric);
+    });
+}
+
+/// Splits a label formatted as `name="value"` by [`key_to_parts`](crate::formatting::key_to_parts)
+/// into its name and unescaped value.
+pub fn parse_label(label: &str) -> (&str, String) {
+    let (name, value) = label.split_once('=').unwrap_or((label, ""));
+    let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    (name, unescaped)
+}
+
+fn write_classic_histogram<'a, F>(
+    message: &mut Vec<u8>,
+    buckets: &[(f64, u64)],
+    count: u64,
+    sum: f64,
+    exemplar: F,
+) where
+    F: Fn(usize) -> Option<&'a Exemplar>,
+{
+    write_uint64_field(message, 1, count);
+    write_double_field(message, 2, sum);
+
+    let inf = iter::once((f64::INFINITY, count));
+    for (i, (le, cumulative_count)) in buckets.iter().copied().chain(inf).enumerate() {
+        write_message_field(message, 3, |bucket| {
+            write_uint64_field(bucket, 1, cumulative_count);
+            write_double_field(bucket, 2, le);
+            if let Some(exemplar) = exemplar(i) {
+                write_exemplar(bucket, 3, exemplar);
+            }
+        });
+    }
+}
+
+fn write_native_histogram(
+    message: &mut Vec<u8>,
+    histogram: &ExponentialHistogram,
+    exemplars: &BucketExemplars,
+) {
+    // The exemplars of native histograms are all held by the `+Inf` bucket.
+    if histogram.scale() < NATIVE_HISTOGRAM_MIN_SCHEMA {
+        let buckets = native_histogram_buckets(histogram);
+        let inf = buckets.len();
+        let exemplar = |bucket| exemplars.get(0).filter(|_| bucket == inf);
+        write_classic_histogram(message, &buckets, histogram.count(), histogram.sum(), exemplar);
+        return;
+    }
+
+    write_uint64_field(message, 1, histogram.count());
+    write_double_field(message, 2, histogram.sum());
+    write_sint_field(message, 5, i64::from(histogram.scale()));
+    // Only values which are exactly zero are counted as such.
+    write_double_field(message, 6, 0.0);
+    write_uint64_field(message, 7, histogram.zero_count());
+
+    let has_negative = write_buckets(message, 9, 10, histogram.negative());
+    let has_positive = write_buckets(message, 12, 13, histogram.positive());
+    if !has_negative && !has_positive {
+        // Histograms without any spans are taken to be regular histograms, so an empty one is
+        // added, as the official client libraries do.
+        write_message_field(message, 12, |span| {
+            write_sint_field(span, 1, 0);
+            write_uint64_field(span, 2, 0);
+        });
+    }
+
+    for exemplar in exemplars.iter() {
+        write_exemplar(message, 16, exemplar);
+    }
+}
+
+/// Writes the spans and deltas of the non-empty buckets of a native histogram, returning whether
+/// any spans were written.
+#[allow(clippy::cast_possible_wrap)]
+fn write_buckets(
+    message: &mut Vec<u8>,
+    span_field: u32,
+    delta_field: u32,
+    buckets: &ExponentialBuckets,
+) -> bool {
+    let mut spans: Vec<(i32, u32)> = Vec::new();
+    let mut deltas = Vec::new();
+    let mut previous: Option<(i32, u64)> = None;
+    for (index, count) in buckets.iter() {
+        // Bucket `i` has an upper bound of `base^i` in Prometheus, and `base^(i + 1)` otherwise.
+        let index = index + 1;
+        match (previous, spans.last_mut()) {
+            (Some((previous_index, _)), Some((_, length))) if index == previous_index + 1 => {
+                *length += 1;
+            }
+            (Some((previous_index, _)), _) => spans.push((index - previous_index - 1, 1)),
+            (None, _) => spans.push((index, 1)),
+        }
+
+        let previous_count = previous.map_or(0, |(_, count)| count);
+        deltas.push(count as i64 - previous_count as i64);
+        previous = Some((index, count));
+    }
+
+    for (offset, length) in &spans {
+        write_message_field(message, span_field, |span| {
+            write_sint_field(span, 1, i64::from(*offset));
+            write_uint64_field(span, 2, u64::from(*length));
+        });
+    }
+    for delta in deltas {
+        write_sint_field(message, delta_field, delta);
+    }
+
+    !spans.is_empty()
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn write_exemplar(message: &mut Vec<u8>, field: u32, exemplar: &Exemplar) {
+    write_message_field(message, field, |message| {
+        for (name, value) in exemplar.labels() {
+            write_message_field(message, 1, |pair| {
+                write_string_field(pair, 1, name);
+                write_string_field(pair, 2, value);
+            });
+        }
+        write_double_field(message, 2, exemplar.value());
+
+        let timestamp = exemplar.timestamp();
+        write_message_field(message, 3, |message| {
+            write_uint64_field(message, 1, timestamp.trunc() as u64);
+            write_uint64_field(message, 2, (timestamp.fract() * 1e9) as u64);
+        });
+    });
+}
+
+pub fn write_message_field<F>(buffer: &mut Vec<u8>, field: u32, write_message: F)
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    let mut message = Vec::new();
+    write_message(&mut message);
+
+    write_tag(buffer, field, WIRE_TYPE_LENGTH_DELIMITED);
+    write_varint(buffer, message.len() as u64);
+    buffer.extend_from_slice(&message);
+}
+
+pub fn write_string_field(buffer: &mut Vec<u8>, field: u32, value: &str) {
+    write_tag(buffer, field, WIRE_TYPE_LENGTH_DELIMITED);
+    write_varint(buffer, value.len() as u64);
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+pub fn write_double_field(buffer: &mut Vec<u8>, field: u32, value: f64) {
+    write_tag(buffer, field, WIRE_TYPE_FIXED64);
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_uint64_field(buffer: &mut Vec<u8>, field: u32, value: u64) {
+    write_tag(buffer, field, WIRE_TYPE_VARINT);
+    write_varint(buffer, value);
+}
+
+#[allow(clippy::cast_sign_loss)]
+#[cfg_attr(not(feature = "remote-write"), allow(dead_code))]
+pub fn write_int64_field(buffer: &mut Vec<u8>, field: u32, value: i64) {
+    write_tag(buffer, field, WIRE_TYPE_VARINT);
+    write_varint(buffer, value as u64);
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn write_sint_field(buffer: &mut Vec<u8>, field: u32, value: i64) {
+    write_tag(buffer, field, WIRE_TYPE_VARINT);
+    write_varint(buffer, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+fn write_tag(buffer: &mut Vec<u8>, field: u32, wire_type: u32) {
+    write_varint(buffer, u64::from((field << 3) | wire_type));
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use indexmap::IndexMap;
+
+    use super::{parse_label, write_distribution_family, write_gauge_family};
+    use crate::distribution::Distribution;
+    use crate::exemplar::BucketExemplars;
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(parse_label("path=\"/\""), ("path", "/".to_string()));
+        assert_eq!(parse_label("q=\"a\\\"b\\\\c\\nd\""), ("q", "a\"b\\c\nd".to_string()));
+    }
+
+    #[test]
+    fn test_write_gauge_family() {
+        let mut metrics = HashMap::new();
+        metrics.insert(vec!["a=\"b\"".to_string()], 1.0);
+
+        let mut buffer = Vec::new();
+        write_gauge_family(&mut buffer, "g", Some("h"), &metrics);
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            29, // length of the family
+            0x0a, 1, b'g', // name
+            0x12, 1, b'h', // help
+            0x18, 1, // type
+            0x22, 19, // metric
+                0x0a, 6, 0x0a, 1, b'a', 0x12, 1, b'b', // label
+                0x12, 9, 0x09, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f, // gauge
+        ];
+        assert_eq!(buffer, expected);
+    }
+
+    #[test]
+    fn test_write_native_histogram() {
+        let mut distribution = Distribution::new_native_histogram(1000);
+        if let Distribution::NativeHistogram(histogram) = &mut distribution {
+            // At the highest schema, 1 and 2 are the upper bounds of buckets 0 and 256.
+            for value in [1.0, 2.0, 2.0] {
+                histogram.record(value);
+            }
+        }
+
+        let mut metrics = IndexMap::new();
+        metrics.insert(Vec::new(), (distribution, BucketExemplars::default()));
+
+        let mut buffer = Vec::new();
+        write_distribution_family(&mut buffer, "h", None, &metrics);
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            0x32, // length of the family
+            0x0a, 1, b'h', // name
+            0x18, 4, // type
+            0x22, 0x2b, 0x3a, 0x29, // metric and histogram
+                0x08, 3, // sample count
+                0x11, 0, 0, 0, 0, 0, 0, 0x14, 0x40, // sample sum
+                0x28, 16, // schema
+                0x31, 0, 0, 0, 0, 0, 0, 0, 0, // zero threshold
+                0x38, 0, // zero count
+                0x62, 4, 0x08, 0, 0x10, 1, // span at 0
+                0x62, 5, 0x08, 0xfe, 0x03, 0x10, 1, // span at 256
+                0x68, 2, // delta of 1
+                0x68, 2, // delta of 1
+        ];
+        assert_eq!(buffer, expected);
+    }
+}
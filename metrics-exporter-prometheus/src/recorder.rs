@@ -1,56 +1,99 @@
-use std::collections::HashMap;
-use std::sync::atomic::Ordering;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ptr;
+#[cfg(feature = "push-gateway")]
+use std::sync::mpsc::{self as std_mpsc, SyncSender};
 use std::sync::Arc;
-use std::sync::{PoisonError, RwLock};
+use std::sync::{Mutex, PoisonError, RwLock};
 
 use indexmap::IndexMap;
-use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use metrics::{
+    Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary, Unit,
+};
+use metrics_util::parse_quantiles;
 use metrics_util::registry::{Recency, Registry};
+use metrics_util::MetricKind;
 use quanta::Instant;
+#[cfg(feature = "push-gateway")]
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
-use crate::common::Snapshot;
-use crate::distribution::{Distribution, DistributionBuilder};
+use crate::common::{BuildError, DistributionValues, ExpositionFormat, Matcher, Snapshot};
+use crate::distribution::{native_histogram_buckets, Distribution, DistributionBuilder};
+use crate::exemplar::{BucketExemplars, Exemplar};
+#[cfg(feature = "push-gateway")]
+use crate::exporter::PushRequest;
 use crate::formatting::{
-    key_to_parts, sanitize_metric_name, write_help_line, write_metric_line, write_type_line,
+    key_to_parts, sanitize_metric_name, write_help_line, write_metric_line,
+    write_metric_line_with_exemplar, write_openmetrics_help_line, write_type_line, write_unit_line,
 };
+use crate::protobuf;
 use crate::registry::GenerationalAtomicStorage;
+#[cfg(feature = "remote-write")]
+use crate::remote_write;
+use crate::self_metrics::SelfMetrics;
+#[cfg(feature = "tower")]
+use crate::PrometheusService;
 
+#[allow(clippy::struct_excessive_bools)]
 pub(crate) struct Inner {
     pub registry: Registry<Key, GenerationalAtomicStorage>,
     pub recency: Recency<Key>,
-    pub distributions: RwLock<HashMap<String, IndexMap<Vec<String>, Distribution>>>,
-    pub distribution_builder: DistributionBuilder,
+    pub distributions: RwLock<HashMap<String, DistributionValues>>,
+    pub distribution_builder: RwLock<DistributionBuilder>,
+    pub summaries: RwLock<HashSet<String>>,
     pub descriptions: RwLock<HashMap<String, SharedString>>,
+    pub units: RwLock<HashMap<String, Unit>>,
+    pub exposition_format: ExpositionFormat,
     pub global_labels: IndexMap<String, String>,
+    pub self_metrics: Option<SelfMetrics>,
+    pub total_suffix: bool,
+    pub unit_suffix: bool,
+    pub created_timestamps: bool,
+    pub upkeep_removes_idle: bool,
+    pub federated: RwLock<Vec<Federated>>,
 }
 
 impl Inner {
     fn get_recent_metrics(&self) -> Snapshot {
+        let units = self.units.read().unwrap_or_else(PoisonError::into_inner);
+        let mut expired = 0;
         let mut counters = HashMap::new();
+        let mut created = HashMap::new();
         let counter_handles = self.registry.get_counter_handles();
         for (key, counter) in counter_handles {
             let gen = counter.get_generation();
             if !self.recency.should_store_counter(&key, gen, &self.registry) {
+                expired += 1;
                 continue;
             }
 
             let (name, labels) = key_to_parts(&key, Some(&self.global_labels));
-            let value = counter.get_inner().load(Ordering::Acquire);
-            let entry =
-                counters.entry(name).or_insert_with(HashMap::new).entry(labels).or_insert(0);
-            *entry = value;
+            let name = self.exposed_name(name, MetricKind::Counter, &units);
+            if self.created_timestamps {
+                let by_labels = created.entry(name.clone()).or_insert_with(HashMap::new);
+                by_labels.insert(labels.clone(), counter.get_inner().created());
+            }
+            let value = counter.get_inner().value();
+            let exemplar = counter.get_inner().exemplar();
+            counters.entry(name).or_insert_with(HashMap::new).insert(labels, (value, exemplar));
         }
 
         let mut gauges = HashMap::new();
         let gauge_handles = self.registry.get_gauge_handles();
         for (key, gauge) in gauge_handles {
+            // Gauges backed by a callback are never updated through their handle, so they can't go
+            // idle, and are always rendered.
             let gen = gauge.get_generation();
-            if !self.recency.should_store_gauge(&key, gen, &self.registry) {
+            if !gauge.get_inner().has_callback()
+                && !self.recency.should_store_gauge(&key, gen, &self.registry)
+            {
+                expired += 1;
                 continue;
             }
 
             let (name, labels) = key_to_parts(&key, Some(&self.global_labels));
-            let value = f64::from_bits(gauge.get_inner().load(Ordering::Acquire));
+            let name = self.exposed_name(name, MetricKind::Gauge, &units);
+            let value = gauge.get_inner().value();
             let entry =
                 gauges.entry(name).or_insert_with(HashMap::new).entry(labels).or_insert(0.0);
             *entry = value;
@@ -63,32 +106,168 @@ impl Inner {
         for (key, histogram) in histogram_handles {
             let gen = histogram.get_generation();
             if !self.recency.should_store_histogram(&key, gen, &self.registry) {
-                // Since we store aggregated distributions directly, when we're told that a metric
-                // is not recent enough and should be/was deleted from the registry, we also need to
-                // delete it on our side as well.
+                self.remove_distribution(&key);
+                expired += 1;
+                continue;
+            }
+
+            if self.created_timestamps {
                 let (name, labels) = key_to_parts(&key, Some(&self.global_labels));
-                let mut wg = self.distributions.write().unwrap_or_else(PoisonError::into_inner);
-                let delete_by_name = if let Some(by_name) = wg.get_mut(&name) {
-                    by_name.swap_remove(&labels);
-                    by_name.is_empty()
-                } else {
-                    false
-                };
+                let name = self.exposed_name(name, MetricKind::Histogram, &units);
+                let by_labels = created.entry(name).or_insert_with(HashMap::new);
+                by_labels.insert(labels, histogram.get_inner().created());
+            }
+        }
 
-                // If there's no more variants in the per-metric-name distribution map, then delete
-                // it entirely, otherwise we end up with weird empty output during render.
-                if delete_by_name {
-                    wg.remove(&name);
-                }
+        // Distributions are stored under the sanitized names of their metrics, which bucket and
+        // quantile matchers apply to, so they're only renamed in the snapshot.
+        let mut distributions =
+            self.distributions.read().unwrap_or_else(PoisonError::into_inner).clone();
+        if self.unit_suffix {
+            distributions = distributions
+                .into_iter()
+                .map(|(name, by_labels)| {
+                    (self.exposed_name(name, MetricKind::Histogram, &units), by_labels)
+                })
+                .collect();
+        }
+        drop(units);
 
-                continue;
+        let mut snapshot = Snapshot { counters, gauges, distributions, created };
+        for federated in self.federated.read().unwrap_or_else(PoisonError::into_inner).iter() {
+            federated.merge_into(&mut snapshot);
+        }
+        if let Some(self_metrics) = &self.self_metrics {
+            self_metrics.record_expired(expired);
+            self_metrics.write_to(&mut snapshot, &self.global_labels);
+        }
+        snapshot
+    }
+
+    /// Gets the name under which the metric with the given sanitized name is exposed, applying the
+    /// configured naming conventions.
+    ///
+    /// With unit suffixes, the unit of the metric, if described with one, is appended to its name,
+    /// before the `_total` suffix of counters, unless the name already ends with it.  With total
+    /// suffixes, `_total` is appended to the names of counters which lack it.
+    fn exposed_name(
+        &self,
+        mut name: String,
+        kind: MetricKind,
+        units: &HashMap<String, Unit>,
+    ) -> String {
+        let unit = units.get(&name).filter(|unit| self.unit_suffix && **unit != Unit::Count);
+        if let Some(unit) = unit {
+            let (stem, total) = match name.strip_suffix("_total") {
+                Some(stem) if kind == MetricKind::Counter => (stem, "_total"),
+                _ => (name.as_str(), ""),
+            };
+            let suffix = unit.as_str();
+            let has_suffix =
+                stem.strip_suffix(suffix).map_or(false, |prefix| prefix.ends_with('_'));
+            if !has_suffix {
+                name = format!("{stem}_{suffix}{total}");
             }
         }
 
-        let distributions =
-            self.distributions.read().unwrap_or_else(PoisonError::into_inner).clone();
+        if self.total_suffix && kind == MetricKind::Counter && !name.ends_with("_total") {
+            name.push_str("_total");
+        }
+        name
+    }
+
+    /// Gets the given per-metric map, such as the descriptions of metrics, merged with the same map
+    /// of each federated recorder, under the names their metrics are rendered as.
+    fn with_federated<'a, T, F>(
+        &self,
+        own: &'a HashMap<String, T>,
+        map: F,
+    ) -> Cow<'a, HashMap<String, T>>
+    where
+        T: Clone,
+        F: Fn(&Inner) -> &RwLock<HashMap<String, T>> + Copy,
+    {
+        let federated = self.federated.read().unwrap_or_else(PoisonError::into_inner);
+        if federated.is_empty() {
+            return Cow::Borrowed(own);
+        }
 
-        Snapshot { counters, gauges, distributions }
+        let mut merged = own.clone();
+        for federated in federated.iter() {
+            let other = map(&federated.inner).read().unwrap_or_else(PoisonError::into_inner);
+            let other = federated.inner.with_federated(&other, map);
+            for (name, value) in other.iter() {
+                merged.entry(federated.name(name.clone())).or_insert_with(|| value.clone());
+            }
+        }
+        Cow::Owned(merged)
+    }
+
+    /// Whether the metrics of `other` are rendered by this recorder, directly or through
+    /// federation.
+    fn federates(&self, other: &Inner) -> bool {
+        let federated = self.federated.read().unwrap_or_else(PoisonError::into_inner);
+        ptr::eq(self, other) || federated.iter().any(|federated| federated.inner.federates(other))
+    }
+
+    /// Removes the distribution of an idle histogram.
+    fn remove_distribution(&self, key: &Key) {
+        // Since we store aggregated distributions directly, when we're told that a metric is not
+        // recent enough and should be/was deleted from the registry, we also need to delete it on
+        // our side as well.
+        let (name, labels) = key_to_parts(key, Some(&self.global_labels));
+        let mut wg = self.distributions.write().unwrap_or_else(PoisonError::into_inner);
+        let delete_by_name = if let Some(by_name) = wg.get_mut(&name) {
+            by_name.swap_remove(&labels);
+            by_name.is_empty()
+        } else {
+            false
+        };
+
+        // If there's no more variants in the per-metric-name distribution map, then delete it
+        // entirely, otherwise we end up with weird empty output during render.
+        if delete_by_name {
+            wg.remove(&name);
+        }
+    }
+
+    /// Removes the metrics which went idle, per the configured idle timeouts, without rendering.
+    fn remove_idle_metrics(&self) {
+        let mut expired = 0;
+        for (key, counter) in self.registry.get_counter_handles() {
+            let gen = counter.get_generation();
+            if !self.recency.should_store_counter(&key, gen, &self.registry) {
+                expired += 1;
+            }
+        }
+
+        for (key, gauge) in self.registry.get_gauge_handles() {
+            let gen = gauge.get_generation();
+            if !gauge.get_inner().has_callback()
+                && !self.recency.should_store_gauge(&key, gen, &self.registry)
+            {
+                expired += 1;
+            }
+        }
+
+        for (key, histogram) in self.registry.get_histogram_handles() {
+            let gen = histogram.get_generation();
+            if !self.recency.should_store_histogram(&key, gen, &self.registry) {
+                self.remove_distribution(&key);
+                expired += 1;
+            }
+        }
+
+        if let Some(self_metrics) = &self.self_metrics {
+            self_metrics.record_expired(expired);
+        }
+    }
+
+    /// Records the time taken by a render which started at `start`.
+    fn record_render(&self, start: Instant) {
+        if let Some(self_metrics) = &self.self_metrics {
+            self_metrics.record_render(Instant::now().duration_since(start));
+        }
     }
 
     /// Drains histogram samples into distribution.
@@ -98,54 +277,112 @@ impl Inner {
             let (name, labels) = key_to_parts(&key, Some(&self.global_labels));
 
             let mut wg = self.distributions.write().unwrap_or_else(PoisonError::into_inner);
-            let entry = wg
-                .entry(name.clone())
-                .or_default()
-                .entry(labels)
-                .or_insert_with(|| self.distribution_builder.get_distribution(name.as_str()));
+            let (distribution, exemplars) =
+                wg.entry(name.clone()).or_default().entry(labels).or_insert_with(|| {
+                    let distribution_builder =
+                        self.distribution_builder.read().unwrap_or_else(PoisonError::into_inner);
+                    let summaries = self.summaries.read().unwrap_or_else(PoisonError::into_inner);
+                    let distribution = if summaries.contains(key.name()) {
+                        distribution_builder.get_summary(name.as_str())
+                    } else {
+                        distribution_builder.get_distribution(name.as_str())
+                    };
+                    (distribution, BucketExemplars::default())
+                });
+
+            histogram.get_inner().clear_with(|samples| distribution.record_samples(samples));
 
-            histogram.get_inner().clear_with(|samples| entry.record_samples(samples));
+            // Exemplars are only exposed for the buckets of histograms, so those observed by
+            // summaries are dropped.
+            let pending = histogram.get_inner().drain_exemplars();
+            if pending.is_empty() {
+                continue;
+            }
+            match distribution {
+                Distribution::Histogram(histogram) => {
+                    let bounds = histogram.buckets().iter().map(|(le, _)| *le).collect::<Vec<_>>();
+                    exemplars.record(&bounds, pending);
+                }
+                Distribution::NativeHistogram(_) => exemplars.record(&[], pending),
+                Distribution::Summary(..) => {}
+            }
         }
     }
 
-    fn render(&self) -> String {
-        let Snapshot { mut counters, mut distributions, mut gauges } = self.get_recent_metrics();
+    fn render(&self, format: ExpositionFormat) -> String {
+        let start = Instant::now();
+        let Snapshot { mut counters, mut distributions, mut gauges, created } =
+            self.get_recent_metrics();
 
+        let openmetrics = format == ExpositionFormat::OpenMetrics;
         let mut output = String::new();
         let descriptions = self.descriptions.read().unwrap_or_else(PoisonError::into_inner);
+        let descriptions = self.with_federated(&descriptions, |inner| &inner.descriptions);
+        let units = self.units.read().unwrap_or_else(PoisonError::into_inner);
+        let units = self.with_federated(&units, |inner| &inner.units);
+        let metadata = FamilyMetadata { format, descriptions: &descriptions, units: &units };
+        // Creation times are only supported by the OpenMetrics format.
+        let created_at = |name: &str, labels: &[String]| {
+            created.get(name).and_then(|by_labels| by_labels.get(labels)).filter(|_| openmetrics)
+        };
 
         for (name, mut by_labels) in counters.drain() {
-            if let Some(desc) = descriptions.get(name.as_str()) {
-                write_help_line(&mut output, name.as_str(), desc);
-            }
+            // OpenMetrics counter families are named without the `_total` suffix, which is instead
+            // carried by each of their samples.
+            let (family, suffix) = if openmetrics {
+                (name.strip_suffix("_total").unwrap_or(&name), Some("total"))
+            } else {
+                (name.as_str(), None)
+            };
 
-            write_type_line(&mut output, name.as_str(), "counter");
-            for (labels, value) in by_labels.drain() {
-                write_metric_line::<&str, u64>(&mut output, &name, None, &labels, None, value);
+            metadata.write(&mut output, &name, family, "counter");
+            for (labels, (value, exemplar)) in by_labels.drain() {
+                // Exemplars are only supported by the OpenMetrics format.
+                let exemplar = exemplar.as_ref().filter(|_| openmetrics).map(Exemplar::as_str);
+                write_metric_line_with_exemplar::<&str, u64>(
+                    &mut output,
+                    family,
+                    suffix,
+                    &labels,
+                    None,
+                    value,
+                    exemplar,
+                );
+                if let Some(created) = created_at(&name, &labels) {
+                    write_metric_line::<&str, f64>(
+                        &mut output,
+                        family,
+                        Some("created"),
+                        &labels,
+                        None,
+                        *created,
+                    );
+                }
+            }
+            if !openmetrics {
+                output.push('\n');
             }
-            output.push('\n');
         }
 
         for (name, mut by_labels) in gauges.drain() {
-            if let Some(desc) = descriptions.get(name.as_str()) {
-                write_help_line(&mut output, name.as_str(), desc);
-            }
-
-            write_type_line(&mut output, name.as_str(), "gauge");
+            metadata.write(&mut output, &name, &name, "gauge");
             for (labels, value) in by_labels.drain() {
                 write_metric_line::<&str, f64>(&mut output, &name, None, &labels, None, value);
             }
-            output.push('\n');
+            if !openmetrics {
+                output.push('\n');
+            }
         }
 
         for (name, mut by_labels) in distributions.drain() {
-            if let Some(desc) = descriptions.get(name.as_str()) {
-                write_help_line(&mut output, name.as_str(), desc);
-            }
-
-            let distribution_type = self.distribution_builder.get_distribution_type(name.as_str());
-            write_type_line(&mut output, name.as_str(), distribution_type);
-            for (labels, distribution) in by_labels.drain(..) {
+            // All distributions of a metric are of the same type, as they're built from the same
+            // configuration.
+            let distribution_type = match by_labels.first() {
+                Some((_, (Distribution::Summary(..), _))) => "summary",
+                _ => "histogram",
+            };
+            metadata.write(&mut output, &name, &name, distribution_type);
+            for (labels, (distribution, exemplars)) in by_labels.drain(..) {
                 let (sum, count) = match distribution {
                     Distribution::Summary(summary, quantiles, sum) => {
                         let snapshot = summary.snapshot(Instant::now());
@@ -164,23 +401,38 @@ impl Inner {
                         (sum, summary.count() as u64)
                     }
                     Distribution::Histogram(histogram) => {
-                        for (le, count) in histogram.buckets() {
-                            write_metric_line(
-                                &mut output,
-                                &name,
-                                Some("bucket"),
-                                &labels,
-                                Some(("le", le)),
-                                count,
-                            );
-                        }
-                        write_metric_line(
+                        let buckets = histogram.buckets();
+                        let exemplar = |bucket| {
+                            exemplars.get(bucket).filter(|_| openmetrics).map(Exemplar::as_str)
+                        };
+                        write_bucket_lines(
+                            &mut output,
+                            &name,
+                            &labels,
+                            &buckets,
+                            histogram.count(),
+                            exemplar,
+                        );
+
+                        (histogram.sum(), histogram.count())
+                    }
+                    Distribution::NativeHistogram(histogram) => {
+                        // The buckets of native histograms change as values are recorded, so their
+                        // exemplars are all held by the `+Inf` bucket.
+                        let buckets = native_histogram_buckets(&histogram);
+                        let exemplar = |bucket| {
+                            exemplars
+                                .get(0)
+                                .filter(|_| openmetrics && bucket == buckets.len())
+                                .map(Exemplar::as_str)
+                        };
+                        write_bucket_lines(
                             &mut output,
                             &name,
-                            Some("bucket"),
                             &labels,
-                            Some(("le", "+Inf")),
+                            &buckets,
                             histogram.count(),
+                            exemplar,
                         );
 
                         (histogram.sum(), histogram.count())
@@ -196,16 +448,224 @@ impl Inner {
                     None,
                     count,
                 );
+                if let Some(created) = created_at(&name, &labels) {
+                    write_metric_line::<&str, f64>(
+                        &mut output,
+                        &name,
+                        Some("created"),
+                        &labels,
+                        None,
+                        *created,
+                    );
+                }
             }
 
-            output.push('\n');
+            if !openmetrics {
+                output.push('\n');
+            }
+        }
+
+        if openmetrics {
+            output.push_str("# EOF\n");
         }
 
+        self.record_render(start);
         output
     }
 
+    fn render_protobuf(&self) -> Vec<u8> {
+        let start = Instant::now();
+        let Snapshot { counters, distributions, gauges, .. } = self.get_recent_metrics();
+
+        let mut output = Vec::new();
+        let descriptions = self.descriptions.read().unwrap_or_else(PoisonError::into_inner);
+        let descriptions = self.with_federated(&descriptions, |inner| &inner.descriptions);
+        let help = |name: &str| descriptions.get(name).map(SharedString::as_ref);
+
+        for (name, by_labels) in &counters {
+            protobuf::write_counter_family(&mut output, name, help(name), by_labels);
+        }
+
+        for (name, by_labels) in &gauges {
+            protobuf::write_gauge_family(&mut output, name, help(name), by_labels);
+        }
+
+        for (name, by_labels) in &distributions {
+            protobuf::write_distribution_family(&mut output, name, help(name), by_labels);
+        }
+
+        self.record_render(start);
+        output
+    }
+
+    #[cfg(feature = "remote-write")]
+    #[allow(clippy::cast_possible_truncation)]
+    fn render_remote_write(&self, max_series: usize) -> Vec<Vec<u8>> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let start = Instant::now();
+        let snapshot = self.get_recent_metrics();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as i64);
+        let requests = remote_write::write_requests(&snapshot, max_series, timestamp);
+        self.record_render(start);
+        requests
+    }
+
     fn run_upkeep(&self) {
         self.drain_histograms_to_distributions();
+        if self.upkeep_removes_idle {
+            self.remove_idle_metrics();
+        }
+    }
+
+    /// Updates the distribution builder, and resets the distributions of metrics matching the given
+    /// pattern so that they are recreated by the updated builder.
+    fn update_distribution_builder<F>(&self, matcher: &Matcher, update: F)
+    where
+        F: FnOnce(&mut DistributionBuilder) -> bool,
+    {
+        // The distributions are locked first, like when draining histograms, so that no
+        // distribution is created from the outdated builder in the meantime.
+        let mut distributions = self.distributions.write().unwrap_or_else(PoisonError::into_inner);
+        let mut distribution_builder =
+            self.distribution_builder.write().unwrap_or_else(PoisonError::into_inner);
+        if update(&mut distribution_builder) {
+            distributions.retain(|name, _| !matcher.matches(name));
+        }
+    }
+
+    /// Updates the distribution builder, and the quantiles of the summaries of metrics matching the
+    /// given pattern.
+    ///
+    /// Unlike buckets, quantiles are only computed when rendering, so summaries keep the values
+    /// recorded so far.
+    fn update_quantiles<F>(&self, matcher: &Matcher, update: F)
+    where
+        F: FnOnce(&mut DistributionBuilder) -> bool,
+    {
+        let mut distributions = self.distributions.write().unwrap_or_else(PoisonError::into_inner);
+        let mut distribution_builder =
+            self.distribution_builder.write().unwrap_or_else(PoisonError::into_inner);
+        if !update(&mut distribution_builder) {
+            return;
+        }
+
+        for (name, by_labels) in distributions.iter_mut() {
+            if !matcher.matches(name) {
+                continue;
+            }
+
+            let updated = distribution_builder.get_quantiles(name);
+            for (distribution, _) in by_labels.values_mut() {
+                if let Distribution::Summary(_, quantiles, _) = distribution {
+                    *quantiles = updated.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Serializes changes to the recorders federated by each recorder, so that concurrent changes can't
+/// make recorders render each other's metrics.
+static FEDERATION: Mutex<()> = Mutex::new(());
+
+/// A recorder whose metrics are rendered alongside those of another recorder.
+pub(crate) struct Federated {
+    inner: Arc<Inner>,
+    prefix: Option<String>,
+}
+
+impl Federated {
+    /// Gets the name under which a metric of the federated recorder is rendered.
+    fn name(&self, name: String) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{prefix}_{name}"),
+            None => name,
+        }
+    }
+
+    /// Adds the metrics of the federated recorder to the given snapshot.
+    fn merge_into(&self, snapshot: &mut Snapshot) {
+        let Snapshot { counters, gauges, distributions, created } = self.inner.get_recent_metrics();
+        for (name, by_labels) in counters {
+            snapshot.counters.entry(self.name(name)).or_default().extend(by_labels);
+        }
+        for (name, by_labels) in gauges {
+            snapshot.gauges.entry(self.name(name)).or_default().extend(by_labels);
+        }
+        for (name, by_labels) in distributions {
+            snapshot.distributions.entry(self.name(name)).or_default().extend(by_labels);
+        }
+        for (name, by_labels) in created {
+            snapshot.created.entry(self.name(name)).or_default().extend(by_labels);
+        }
+    }
+}
+
+/// Writes the bucket lines of a histogram, followed by the line of the `+Inf` bucket.
+///
+/// `exemplar` gets the exemplar to render for a bucket, given its position.
+fn write_bucket_lines<'a, F>(
+    output: &mut String,
+    name: &str,
+    labels: &[String],
+    buckets: &[(f64, u64)],
+    count: u64,
+    exemplar: F,
+) where
+    F: Fn(usize) -> Option<&'a str>,
+{
+    for (i, (le, bucket_count)) in buckets.iter().enumerate() {
+        write_metric_line_with_exemplar(
+            output,
+            name,
+            Some("bucket"),
+            labels,
+            Some(("le", le)),
+            bucket_count,
+            exemplar(i),
+        );
+    }
+    write_metric_line_with_exemplar(
+        output,
+        name,
+        Some("bucket"),
+        labels,
+        Some(("le", "+Inf")),
+        count,
+        exemplar(buckets.len()),
+    );
+}
+
+/// Descriptions and units of metrics, for writing the metadata lines of each metric family.
+struct FamilyMetadata<'a> {
+    format: ExpositionFormat,
+    descriptions: &'a HashMap<String, SharedString>,
+    units: &'a HashMap<String, Unit>,
+}
+
+impl FamilyMetadata<'_> {
+    /// Writes the metadata lines for the metric family `family`, of the metric named `name`.
+    fn write(&self, output: &mut String, name: &str, family: &str, metric_type: &str) {
+        if let Some(desc) = self.descriptions.get(name) {
+            match self.format {
+                ExpositionFormat::Prometheus => write_help_line(output, family, desc),
+                ExpositionFormat::OpenMetrics => write_openmetrics_help_line(output, family, desc),
+            }
+        }
+        write_type_line(output, family, metric_type);
+
+        if self.format == ExpositionFormat::OpenMetrics {
+            // The unit must be a suffix of the family name, so units which aren't are left out.
+            let unit = self.units.get(name).map(Unit::as_str).filter(|unit| {
+                family.strip_suffix(unit).map_or(false, |prefix| prefix.ends_with('_'))
+            });
+            if let Some(unit) = unit {
+                write_unit_line(output, family, unit);
+            }
+        }
     }
 }
 
@@ -214,8 +674,16 @@ impl Inner {
 /// Most users will not need to interact directly with the recorder, and can simply deal with the
 /// builder methods on [`PrometheusBuilder`](crate::PrometheusBuilder) for building and installing
 /// the recorder/exporter.
+///
+/// When the recorder was built along with a push gateway or Remote Write exporter,
+/// [`Recorder::flush`] makes the exporter push a snapshot of the metrics right away, and
+/// [`Recorder::shutdown`] makes it push a final snapshot and stop.  Both wait until the push has
+/// completed, unless they're called from within a single-threaded Tokio runtime, where waiting
+/// would keep the exporter from ever running.
 pub struct PrometheusRecorder {
     inner: Arc<Inner>,
+    #[cfg(feature = "push-gateway")]
+    push_requests: Option<UnboundedSender<PushRequest>>,
 }
 
 impl PrometheusRecorder {
@@ -224,36 +692,92 @@ impl PrometheusRecorder {
         PrometheusHandle { inner: self.inner.clone() }
     }
 
-    fn add_description_if_missing(&self, key_name: &KeyName, description: SharedString) {
+    fn add_description_if_missing(
+        &self,
+        key_name: &KeyName,
+        kind: MetricKind,
+        unit: Option<Unit>,
+        description: SharedString,
+    ) {
         let sanitized = sanitize_metric_name(key_name.as_str());
+        let mut units = self.inner.units.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(unit) = unit {
+            units.entry(sanitized.clone()).or_insert(unit);
+        }
+
+        // Descriptions and units are looked up under the names metrics are exposed as when
+        // rendering, which differ from their sanitized names if naming conventions are applied.
+        let exposed = self.inner.exposed_name(sanitized.clone(), kind, &units);
+        if exposed != sanitized {
+            if let Some(unit) = units.get(&sanitized).cloned() {
+                units.entry(exposed.clone()).or_insert(unit);
+            }
+        }
+        drop(units);
+
         let mut descriptions =
             self.inner.descriptions.write().unwrap_or_else(PoisonError::into_inner);
-        descriptions.entry(sanitized).or_insert(description);
+        descriptions.entry(exposed).or_insert(description);
+    }
+
+    /// Creates the channel over which the recorder requests pushes from the exporter pushing its
+    /// metrics, returning the receiving end for the exporter.
+    #[cfg(feature = "push-gateway")]
+    pub(crate) fn push_requests(&mut self) -> UnboundedReceiver<PushRequest> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.push_requests = Some(sender);
+        receiver
+    }
+
+    /// Sends a request to the exporter pushing the metrics of this recorder, if any, and waits
+    /// until it has been handled.
+    #[cfg(feature = "push-gateway")]
+    fn request_push(&self, request: fn(SyncSender<()>) -> PushRequest) {
+        use tokio::runtime::{Handle, RuntimeFlavor};
+
+        let Some(requests) = &self.push_requests else {
+            return;
+        };
+        let (ack, acked) = std_mpsc::sync_channel(1);
+        if requests.send(request(ack)).is_err() {
+            // The exporter has already stopped, or was dropped without ever running.
+            return;
+        }
+
+        match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            // Blocking here would block the very thread the exporter has to run on.
+            Ok(RuntimeFlavor::CurrentThread) => {}
+            Ok(_) => {
+                let _ = tokio::task::block_in_place(|| acked.recv());
+            }
+            Err(_) => {
+                let _ = acked.recv();
+            }
+        }
     }
 }
 
 impl From<Inner> for PrometheusRecorder {
     fn from(inner: Inner) -> Self {
-        PrometheusRecorder { inner: Arc::new(inner) }
+        PrometheusRecorder {
+            inner: Arc::new(inner),
+            #[cfg(feature = "push-gateway")]
+            push_requests: None,
+        }
     }
 }
 
 impl Recorder for PrometheusRecorder {
-    fn describe_counter(&self, key_name: KeyName, _unit: Option<Unit>, description: SharedString) {
-        self.add_description_if_missing(&key_name, description);
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.add_description_if_missing(&key_name, MetricKind::Counter, unit, description);
     }
 
-    fn describe_gauge(&self, key_name: KeyName, _unit: Option<Unit>, description: SharedString) {
-        self.add_description_if_missing(&key_name, description);
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.add_description_if_missing(&key_name, MetricKind::Gauge, unit, description);
     }
 
-    fn describe_histogram(
-        &self,
-        key_name: KeyName,
-        _unit: Option<Unit>,
-        description: SharedString,
-    ) {
-        self.add_description_if_missing(&key_name, description);
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.add_description_if_missing(&key_name, MetricKind::Histogram, unit, description);
     }
 
     fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
@@ -267,6 +791,31 @@ impl Recorder for PrometheusRecorder {
     fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
         self.inner.registry.get_or_create_histogram(key, |c| c.clone().into())
     }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.add_description_if_missing(&key_name, MetricKind::Histogram, unit, description);
+    }
+
+    // Summaries share the storage of histograms, but are always rendered as summaries, even when
+    // buckets are configured for their name.
+    fn register_summary(&self, key: &Key, _metadata: &Metadata<'_>) -> Summary {
+        let summaries = &self.inner.summaries;
+        if !summaries.read().unwrap_or_else(PoisonError::into_inner).contains(key.name()) {
+            summaries.write().unwrap_or_else(PoisonError::into_inner).insert(key.name().to_owned());
+        }
+
+        self.inner.registry.get_or_create_histogram(key, |c| c.clone().into())
+    }
+
+    fn flush(&self) {
+        #[cfg(feature = "push-gateway")]
+        self.request_push(PushRequest::Flush);
+    }
+
+    fn shutdown(&self) {
+        #[cfg(feature = "push-gateway")]
+        self.request_push(PushRequest::Shutdown);
+    }
 }
 
 /// Handle for accessing metrics stored via [`PrometheusRecorder`].
@@ -282,14 +831,214 @@ pub struct PrometheusHandle {
 
 impl PrometheusHandle {
     /// Takes a snapshot of the metrics held by the recorder and generates a payload conforming to
-    /// the Prometheus exposition format.
+    /// the configured exposition format.
+    ///
+    /// The format defaults to the Prometheus exposition format, and can be changed via
+    /// [`PrometheusBuilder::set_exposition_format`](crate::PrometheusBuilder::set_exposition_format).
     pub fn render(&self) -> String {
-        self.inner.render()
+        self.inner.render(self.inner.exposition_format)
+    }
+
+    /// Takes a snapshot of the metrics held by the recorder and generates a payload conforming to
+    /// the given exposition format.
+    pub fn render_with_format(&self, format: ExpositionFormat) -> String {
+        self.inner.render(format)
+    }
+
+    /// Takes a snapshot of the metrics held by the recorder and generates a payload conforming to
+    /// the Prometheus [protobuf exposition format].
+    ///
+    /// The payload is a sequence of length-delimited `io.prometheus.client.MetricFamily` messages,
+    /// to be served with a `Content-Type` of [`PROTOBUF_CONTENT_TYPE`](crate::PROTOBUF_CONTENT_TYPE).
+    /// This is the only format in which native histograms are exposed as such.
+    ///
+    /// [protobuf exposition format]: https://prometheus.io/docs/instrumenting/exposition_formats/#protobuf-format
+    pub fn render_protobuf(&self) -> Vec<u8> {
+        self.inner.render_protobuf()
+    }
+
+    /// Takes a snapshot of the metrics held by the recorder and encodes it as uncompressed Remote
+    /// Write requests, each holding at most `max_series` series.
+    #[cfg(feature = "remote-write")]
+    pub(crate) fn render_remote_write(&self, max_series: usize) -> Vec<Vec<u8>> {
+        self.inner.render_remote_write(max_series)
+    }
+
+    /// Records a response sent by the HTTP listener, if self-metrics are enabled.
+    #[cfg(feature = "http-listener")]
+    pub(crate) fn record_http_response(&self, status: u16) {
+        if let Some(self_metrics) = &self.inner.self_metrics {
+            self_metrics.record_http_response(status);
+        }
+    }
+
+    /// Records a push which failed, if self-metrics are enabled.
+    #[cfg(feature = "push-gateway")]
+    pub(crate) fn record_push_failure(&self) {
+        if let Some(self_metrics) = &self.inner.self_metrics {
+            self_metrics.record_push_failure();
+        }
+    }
+
+    /// Gets a [`Service`](tower_service::Service) which renders the metrics held by the recorder in
+    /// response to each request.
+    ///
+    /// This allows mounting the metrics on an existing `hyper` or `axum` server, such as at
+    /// `/metrics`, instead of running the HTTP listener of the exporter on a separate port.  Refer
+    /// to [`PrometheusService`] for more information.
+    #[cfg(feature = "tower")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+    pub fn as_service(&self) -> PrometheusService {
+        PrometheusService::new(self.clone())
+    }
+
+    /// Gets the exposition format used by [`render`](Self::render).
+    pub fn exposition_format(&self) -> ExpositionFormat {
+        self.inner.exposition_format
     }
 
     /// Performs upkeeping operations to ensure metrics held by recorder are up-to-date and do not
     /// grow unboundedly.
+    ///
+    /// This drains the values recorded to histograms into their distributions, and removes idle
+    /// metrics if configured via
+    /// [`PrometheusBuilder::upkeep_removes_idle_metrics`](crate::PrometheusBuilder::upkeep_removes_idle_metrics).
+    ///
+    /// Upkeep is run periodically by the exporter built via
+    /// [`PrometheusBuilder::build`](crate::PrometheusBuilder::build), unless disabled via
+    /// [`PrometheusBuilder::spawn_upkeep_task`](crate::PrometheusBuilder::spawn_upkeep_task).  When
+    /// only the recorder is built, such as in command-line tools or cron jobs running without an
+    /// async runtime, this should be called periodically instead.
     pub fn run_upkeep(&self) {
         self.inner.run_upkeep();
     }
+
+    /// Sets the buckets for a specific pattern, while the recorder is running.
+    ///
+    /// This behaves like
+    /// [`PrometheusBuilder::set_buckets_for_metric`](crate::PrometheusBuilder::set_buckets_for_metric),
+    /// replacing any buckets previously set for the same pattern.  The histograms and summaries of
+    /// metrics matching the pattern are reset, so their next render only reflects values recorded
+    /// from then on, which Prometheus treats like a process restart.
+    ///
+    /// ## Errors
+    ///
+    /// If `values` is empty, an error variant will be thrown.
+    pub fn set_buckets_for_metric(
+        &self,
+        matcher: Matcher,
+        values: &[f64],
+    ) -> Result<(), BuildError> {
+        if values.is_empty() {
+            return Err(BuildError::EmptyBucketsOrQuantiles);
+        }
+
+        let matcher = matcher.sanitized();
+        let buckets = values.to_vec();
+        self.inner.update_distribution_builder(&matcher, |distribution_builder| {
+            distribution_builder.set_bucket_override(matcher.clone(), buckets);
+            true
+        });
+        Ok(())
+    }
+
+    /// Removes the buckets set for a specific pattern, while the recorder is running.
+    ///
+    /// Metrics matching the pattern are reset, as with
+    /// [`set_buckets_for_metric`](Self::set_buckets_for_metric), and rendered as if no buckets had
+    /// been set for the pattern.  Buckets are left untouched for any other pattern.
+    ///
+    /// Returns `true` if buckets were set for the pattern.
+    pub fn remove_buckets_for_metric(&self, matcher: Matcher) -> bool {
+        let matcher = matcher.sanitized();
+        let mut removed = false;
+        self.inner.update_distribution_builder(&matcher, |distribution_builder| {
+            removed = distribution_builder.remove_bucket_override(&matcher);
+            removed
+        });
+        removed
+    }
+
+    /// Sets the quantiles for a specific pattern, while the recorder is running.
+    ///
+    /// This behaves like
+    /// [`PrometheusBuilder::set_quantiles_for_metric`](crate::PrometheusBuilder::set_quantiles_for_metric),
+    /// replacing any quantiles previously set for the same pattern.  Summaries of metrics matching
+    /// the pattern keep their recorded values, and render the new quantiles from then on.
+    ///
+    /// ## Errors
+    ///
+    /// If `quantiles` is empty, an error variant will be thrown.
+    pub fn set_quantiles_for_metric(
+        &self,
+        matcher: Matcher,
+        quantiles: &[f64],
+    ) -> Result<(), BuildError> {
+        if quantiles.is_empty() {
+            return Err(BuildError::EmptyBucketsOrQuantiles);
+        }
+
+        let matcher = matcher.sanitized();
+        let quantiles = parse_quantiles(quantiles);
+        self.inner.update_quantiles(&matcher, |distribution_builder| {
+            distribution_builder.set_quantile_override(matcher.clone(), quantiles);
+            true
+        });
+        Ok(())
+    }
+
+    /// Removes the quantiles set for a specific pattern, while the recorder is running.
+    ///
+    /// Summaries of metrics matching the pattern render the quantiles set via
+    /// [`PrometheusBuilder::set_quantiles`](crate::PrometheusBuilder::set_quantiles) from then on,
+    /// unless other quantiles were set for a pattern matching them.
+    ///
+    /// Returns `true` if quantiles were set for the pattern.
+    pub fn remove_quantiles_for_metric(&self, matcher: Matcher) -> bool {
+        let matcher = matcher.sanitized();
+        let mut removed = false;
+        self.inner.update_quantiles(&matcher, |distribution_builder| {
+            removed = distribution_builder.remove_quantile_override(&matcher);
+            removed
+        });
+        removed
+    }
+
+    /// Renders the metrics of the recorder behind `handle` alongside the metrics of this recorder.
+    ///
+    /// This allows serving the metrics of several recorders, such as ones isolated per plugin, from
+    /// a single exporter.  The names of the metrics of the federated recorder are prefixed by
+    /// `prefix`, if given, followed by an underscore, and the metrics are labelled with the global
+    /// labels of that recorder, so that they can be told apart from those of other recorders.
+    /// Metrics of different kinds must not end up with the same name, as they would be rendered as
+    /// a single metric family.
+    ///
+    /// Federating a recorder which was already federated replaces its prefix.
+    ///
+    /// Returns `false`, leaving this recorder unchanged, if `handle` is a handle to this recorder,
+    /// or to a recorder already rendering the metrics of this one, directly or through federation.
+    pub fn add_federated(&self, handle: &PrometheusHandle, prefix: Option<&str>) -> bool {
+        let _guard = FEDERATION.lock().unwrap_or_else(PoisonError::into_inner);
+        if handle.inner.federates(&self.inner) {
+            return false;
+        }
+
+        let mut federated = self.inner.federated.write().unwrap_or_else(PoisonError::into_inner);
+        federated.retain(|federated| !Arc::ptr_eq(&federated.inner, &handle.inner));
+        let prefix = prefix.map(sanitize_metric_name);
+        federated.push(Federated { inner: handle.inner.clone(), prefix });
+        true
+    }
+
+    /// Stops rendering the metrics of the recorder behind `handle` alongside the metrics of this
+    /// recorder.
+    ///
+    /// Returns `true` if the recorder was federated via [`add_federated`](Self::add_federated).
+    pub fn remove_federated(&self, handle: &PrometheusHandle) -> bool {
+        let _guard = FEDERATION.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut federated = self.inner.federated.write().unwrap_or_else(PoisonError::into_inner);
+        let len = federated.len();
+        federated.retain(|federated| !Arc::ptr_eq(&federated.inner, &handle.inner));
+        federated.len() != len
+    }
 }
@@ -1,40 +1,196 @@
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use metrics::{atomics::AtomicU64, HistogramFn};
+use metrics::{
+    atomics::AtomicU64, CounterFn, GaugeCallback, GaugeFn, HistogramFn, Label, SummaryFn,
+};
 use metrics_util::{registry::GenerationalStorage, AtomicBucket};
 use quanta::Instant;
 
+use crate::exemplar::{Exemplar, ExemplarHook};
+
+/// The maximum number of exemplars held by a histogram between drains.
+const MAX_PENDING_EXEMPLARS: usize = 64;
+
 pub type GenerationalAtomicStorage = GenerationalStorage<AtomicStorage>;
 
 /// Atomic metric storage for the prometheus exporter.
-pub struct AtomicStorage;
+#[derive(Default)]
+pub struct AtomicStorage {
+    exemplar_hook: Option<ExemplarHook>,
+}
+
+impl AtomicStorage {
+    /// Creates a new `AtomicStorage` whose counters and histograms get an exemplar from the given
+    /// hook whenever they're updated without one.
+    pub fn with_exemplar_hook(exemplar_hook: Option<ExemplarHook>) -> AtomicStorage {
+        AtomicStorage { exemplar_hook }
+    }
+}
 
 impl<K> metrics_util::registry::Storage<K> for AtomicStorage {
-    type Counter = Arc<AtomicU64>;
-    type Gauge = Arc<AtomicU64>;
+    type Counter = Arc<ExemplarCounter>;
+    type Gauge = Arc<CallbackGauge>;
     type Histogram = Arc<AtomicBucketInstant<f64>>;
 
     fn counter(&self, _: &K) -> Self::Counter {
-        Arc::new(AtomicU64::new(0))
+        Arc::new(ExemplarCounter::new(self.exemplar_hook.clone()))
     }
 
     fn gauge(&self, _: &K) -> Self::Gauge {
-        Arc::new(AtomicU64::new(0))
+        Arc::new(CallbackGauge::new())
     }
 
     fn histogram(&self, _: &K) -> Self::Histogram {
-        Arc::new(AtomicBucketInstant::new())
+        Arc::new(AtomicBucketInstant::new(self.exemplar_hook.clone()))
+    }
+}
+
+/// Gets the current time as a Unix timestamp, in seconds.
+fn unix_timestamp() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |since| since.as_secs_f64())
+}
+
+/// A counter which holds the exemplar of its most recent increment, if it had one.
+pub struct ExemplarCounter {
+    value: AtomicU64,
+    exemplar: Mutex<Option<Exemplar>>,
+    exemplar_hook: Option<ExemplarHook>,
+    created: f64,
+}
+
+impl ExemplarCounter {
+    fn new(exemplar_hook: Option<ExemplarHook>) -> ExemplarCounter {
+        Self {
+            value: AtomicU64::new(0),
+            exemplar: Mutex::new(None),
+            exemplar_hook,
+            created: unix_timestamp(),
+        }
+    }
+
+    /// Gets the current value of the counter.
+    pub fn value(&self) -> u64 {
+        self.value.load(Ordering::Acquire)
+    }
+
+    /// Gets the time the counter was created at, as a Unix timestamp in seconds.
+    pub fn created(&self) -> f64 {
+        self.created
+    }
+
+    /// Gets the exemplar of the most recent increment which had one.
+    pub fn exemplar(&self) -> Option<Exemplar> {
+        self.exemplar.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn set_exemplar(&self, value: u64, labels: &[Label]) {
+        if let Some(exemplar) = Exemplar::new(labels, value as f64) {
+            *self.exemplar.lock().unwrap_or_else(PoisonError::into_inner) = Some(exemplar);
+        }
+    }
+}
+
+impl CounterFn for ExemplarCounter {
+    fn increment(&self, value: u64) {
+        CounterFn::increment(&self.value, value);
+        if let Some(labels) = self.exemplar_hook.as_ref().and_then(|hook| hook()) {
+            self.set_exemplar(value, &labels);
+        }
+    }
+
+    fn absolute(&self, value: u64) {
+        CounterFn::absolute(&self.value, value);
+    }
+
+    fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+        CounterFn::increment(&self.value, value);
+        self.set_exemplar(value, exemplar);
+    }
+}
+
+/// A gauge which can be backed by a callback.
+///
+/// Once a callback has been set, it takes precedence over any value the gauge was set to, and is
+/// invoked every time the value of the gauge is read.
+pub struct CallbackGauge {
+    value: AtomicU64,
+    callback: RwLock<Option<GaugeCallback>>,
+}
+
+impl CallbackGauge {
+    fn new() -> CallbackGauge {
+        Self { value: AtomicU64::new(0), callback: RwLock::new(None) }
+    }
+
+    /// Whether or not the gauge is backed by a callback.
+    pub fn has_callback(&self) -> bool {
+        self.callback.read().unwrap_or_else(PoisonError::into_inner).is_some()
+    }
+
+    /// Gets the current value of the gauge, invoking its callback if it has one.
+    pub fn value(&self) -> f64 {
+        let callback = self.callback.read().unwrap_or_else(PoisonError::into_inner).clone();
+        match callback {
+            Some(callback) => callback.value(),
+            None => f64::from_bits(self.value.load(Ordering::Acquire)),
+        }
+    }
+}
+
+impl GaugeFn for CallbackGauge {
+    fn increment(&self, value: f64) {
+        GaugeFn::increment(&self.value, value);
+    }
+
+    fn decrement(&self, value: f64) {
+        GaugeFn::decrement(&self.value, value);
+    }
+
+    fn set(&self, value: f64) {
+        GaugeFn::set(&self.value, value);
+    }
+
+    fn set_max(&self, value: f64) {
+        GaugeFn::set_max(&self.value, value);
+    }
+
+    fn set_min(&self, value: f64) {
+        GaugeFn::set_min(&self.value, value);
+    }
+
+    fn set_callback(&self, callback: GaugeCallback) {
+        *self.callback.write().unwrap_or_else(PoisonError::into_inner) = Some(callback);
     }
 }
 
 /// An `AtomicBucket` newtype wrapper that tracks the time of value insertion.
+///
+/// The exemplars of the most recent observations which had one are held alongside the values, up
+/// to a limit, until they're drained.
 pub struct AtomicBucketInstant<T> {
     inner: AtomicBucket<(T, Instant)>,
+    exemplars: Mutex<VecDeque<Exemplar>>,
+    exemplar_hook: Option<ExemplarHook>,
+    created: f64,
 }
 
 impl<T> AtomicBucketInstant<T> {
-    fn new() -> AtomicBucketInstant<T> {
-        Self { inner: AtomicBucket::new() }
+    fn new(exemplar_hook: Option<ExemplarHook>) -> AtomicBucketInstant<T> {
+        Self {
+            inner: AtomicBucket::new(),
+            exemplars: Mutex::default(),
+            exemplar_hook,
+            created: unix_timestamp(),
+        }
+    }
+
+    /// Gets the time the histogram was created at, as a Unix timestamp in seconds.
+    pub fn created(&self) -> f64 {
+        self.created
     }
 
     pub fn clear_with<F>(&self, f: F)
@@ -43,11 +199,58 @@ impl<T> AtomicBucketInstant<T> {
     {
         self.inner.clear_with(f);
     }
+
+    /// Takes the exemplars held since the last time they were drained, oldest first.
+    pub fn drain_exemplars(&self) -> VecDeque<Exemplar> {
+        std::mem::take(&mut *self.exemplars.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn push_exemplar(&self, value: f64, labels: &[Label]) {
+        if let Some(exemplar) = Exemplar::new(labels, value) {
+            let mut exemplars = self.exemplars.lock().unwrap_or_else(PoisonError::into_inner);
+            if exemplars.len() == MAX_PENDING_EXEMPLARS {
+                exemplars.pop_front();
+            }
+            exemplars.push_back(exemplar);
+        }
+    }
+
+    fn push_exemplar_from_hook(&self, value: f64) {
+        if let Some(labels) = self.exemplar_hook.as_ref().and_then(|hook| hook()) {
+            self.push_exemplar(value, &labels);
+        }
+    }
 }
 
 impl HistogramFn for AtomicBucketInstant<f64> {
     fn record(&self, value: f64) {
         let now = Instant::now();
         self.inner.push((value, now));
+        self.push_exemplar_from_hook(value);
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        let now = Instant::now();
+        self.inner.push((value, now));
+        self.push_exemplar(value, exemplar);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        let now = Instant::now();
+        for &value in values {
+            self.inner.push((value, now));
+        }
+
+        // The hook is only consulted once per batch, for the last value in it.
+        if let Some(&value) = values.last() {
+            self.push_exemplar_from_hook(value);
+        }
+    }
+}
+
+impl SummaryFn for AtomicBucketInstant<f64> {
+    fn record(&self, value: f64) {
+        // Exemplars are never exposed for summaries, so there's no need to consult the hook.
+        self.inner.push((value, Instant::now()));
     }
 }
@@ -0,0 +1,196 @@
+//! Helpers for encoding metrics as Prometheus [Remote Write] requests.
+//!
+//! Requests are `prometheus.WriteRequest` messages, as defined by [`remote.proto`], which, like the
+//! protobuf exposition format, are simple enough that the encoding is done by hand.
+//!
+//! [Remote Write]: https://prometheus.io/docs/concepts/remote_write_spec/
+//! [`remote.proto`]: https://github.com/prometheus/prometheus/blob/main/prompb/remote.proto
+
+use quanta::Instant;
+
+use crate::common::Snapshot;
+use crate::distribution::{native_histogram_buckets, Distribution};
+use crate::protobuf::{
+    parse_label, write_double_field, write_int64_field, write_message_field, write_string_field,
+};
+
+/// The value of the `Content-Type` header of Remote Write requests.
+pub const REMOTE_WRITE_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// The version of the Remote Write protocol spoken by the exporter.
+pub const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+/// Encodes the metrics of a snapshot as Remote Write requests, each holding at most `max_series`
+/// series, whose samples are all taken at `timestamp`, in milliseconds since the Unix epoch.
+///
+/// Metrics are flattened into series as they would be when scraped in the Prometheus exposition
+/// format, so histograms and summaries are each written as several series.
+#[allow(clippy::cast_precision_loss)]
+pub fn write_requests(snapshot: &Snapshot, max_series: usize, timestamp: i64) -> Vec<Vec<u8>> {
+    let mut requests = WriteRequests::new(max_series, timestamp);
+
+    for (name, by_labels) in &snapshot.counters {
+        for (labels, (value, _)) in by_labels {
+            requests.push(name, labels, None, *value as f64);
+        }
+    }
+
+    for (name, by_labels) in &snapshot.gauges {
+        for (labels, value) in by_labels {
+            requests.push(name, labels, None, *value);
+        }
+    }
+
+    for (name, by_labels) in &snapshot.distributions {
+        let bucket_name = format!("{name}_bucket");
+        let sum_name = format!("{name}_sum");
+        let count_name = format!("{name}_count");
+
+        for (labels, (distribution, _)) in by_labels {
+            let (sum, count) = match distribution {
+                Distribution::Summary(summary, quantiles, sum) => {
+                    let snapshot = summary.snapshot(Instant::now());
+                    for quantile in quantiles.iter() {
+                        let value = snapshot.quantile(quantile.value()).unwrap_or(0.0);
+                        let label = ("quantile", quantile.value().to_string());
+                        requests.push(name, labels, Some(label), value);
+                    }
+                    (*sum, summary.count() as u64)
+                }
+                Distribution::Histogram(histogram) => {
+                    let buckets = histogram.buckets();
+                    requests.push_buckets(&bucket_name, labels, &buckets, histogram.count());
+                    (histogram.sum(), histogram.count())
+                }
+                Distribution::NativeHistogram(histogram) => {
+                    let buckets = native_histogram_buckets(histogram);
+                    requests.push_buckets(&bucket_name, labels, &buckets, histogram.count());
+                    (histogram.sum(), histogram.count())
+                }
+            };
+
+            requests.push(&sum_name, labels, None, sum);
+            requests.push(&count_name, labels, None, count as f64);
+        }
+    }
+
+    requests.finish()
+}
+
+/// Batches series into `WriteRequest` messages.
+struct WriteRequests {
+    max_series: usize,
+    timestamp: i64,
+    current: Vec<u8>,
+    current_series: usize,
+    requests: Vec<Vec<u8>>,
+}
+
+impl WriteRequests {
+    fn new(max_series: usize, timestamp: i64) -> WriteRequests {
+        WriteRequests {
+            max_series: max_series.max(1),
+            timestamp,
+            current: Vec::new(),
+            current_series: 0,
+            requests: Vec::new(),
+        }
+    }
+
+    /// Adds a series with a single sample, given its name, its labels as formatted by
+    /// [`key_to_parts`](crate::formatting::key_to_parts), and an additional label, if any.
+    fn push(&mut self, name: &str, labels: &[String], extra: Option<(&str, String)>, value: f64) {
+        // Labels must be sorted by name, and `__name__` sorts before any valid label name.
+        let mut pairs = labels.iter().map(|label| parse_label(label)).collect::<Vec<_>>();
+        pairs.extend(extra);
+        pairs.sort_by(|a, b| a.0.cmp(b.0));
+
+        let timestamp = self.timestamp;
+        write_message_field(&mut self.current, 1, |series| {
+            write_message_field(series, 1, |label| {
+                write_string_field(label, 1, "__name__");
+                write_string_field(label, 2, name);
+            });
+            for (name, value) in &pairs {
+                write_message_field(series, 1, |label| {
+                    write_string_field(label, 1, name);
+                    write_string_field(label, 2, value);
+                });
+            }
+            write_message_field(series, 2, |sample| {
+                write_double_field(sample, 1, value);
+                write_int64_field(sample, 2, timestamp);
+            });
+        });
+
+        self.current_series += 1;
+        if self.current_series == self.max_series {
+            self.requests.push(std::mem::take(&mut self.current));
+            self.current_series = 0;
+        }
+    }
+
+    /// Adds a series for each of the given buckets, with an `le` label set to their upper bound,
+    /// followed by one for the `+Inf` bucket.
+    #[allow(clippy::cast_precision_loss)]
+    fn push_buckets(&mut self, name: &str, labels: &[String], buckets: &[(f64, u64)], count: u64) {
+        for (le, bucket_count) in buckets {
+            self.push(name, labels, Some(("le", le.to_string())), *bucket_count as f64);
+        }
+        self.push(name, labels, Some(("le", "+Inf".to_string())), count as f64);
+    }
+
+    fn finish(mut self) -> Vec<Vec<u8>> {
+        if self.current_series > 0 {
+            self.requests.push(self.current);
+        }
+        self.requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::write_requests;
+    use crate::common::Snapshot;
+
+    #[test]
+    fn test_write_requests() {
+        let mut gauges = HashMap::new();
+        gauges.insert("g".to_string(), HashMap::from([(vec!["a=\"b\"".to_string()], 1.0)]));
+        let snapshot = Snapshot {
+            counters: HashMap::new(),
+            gauges,
+            distributions: HashMap::new(),
+            created: HashMap::new(),
+        };
+
+        let mut expected = vec![0x0a, 0x24, 0x0a, 0x0d, 0x0a, 0x08];
+        expected.extend_from_slice(b"__name__");
+        expected.extend_from_slice(&[0x12, 0x01, b'g']);
+        expected.extend_from_slice(&[0x0a, 0x06, 0x0a, 0x01, b'a', 0x12, 0x01, b'b']);
+        expected.extend_from_slice(&[0x12, 0x0b, 0x09]);
+        expected.extend_from_slice(&1.0f64.to_le_bytes());
+        expected.extend_from_slice(&[0x10, 0x05]);
+
+        assert_eq!(write_requests(&snapshot, 10, 5), vec![expected]);
+    }
+
+    #[test]
+    fn test_write_requests_batching() {
+        let mut gauges = HashMap::new();
+        for name in ["a", "b", "c"] {
+            gauges.insert(name.to_string(), HashMap::from([(Vec::new(), 1.0)]));
+        }
+        let snapshot = Snapshot {
+            counters: HashMap::new(),
+            gauges,
+            distributions: HashMap::new(),
+            created: HashMap::new(),
+        };
+
+        assert_eq!(write_requests(&snapshot, 2, 0).len(), 2);
+        assert_eq!(write_requests(&snapshot, 3, 0).len(), 1);
+    }
+}
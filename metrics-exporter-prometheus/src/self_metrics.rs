@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use indexmap::IndexMap;
+use metrics::{Key, Label, SharedString};
+
+use crate::common::Snapshot;
+use crate::formatting::key_to_parts;
+
+// The names of the self-metrics all start with the reserved `metrics_exporter_prometheus_` prefix.
+const RENDER_DURATION: &str = "metrics_exporter_prometheus_render_duration_seconds";
+const RENDERED_SERIES: &str = "metrics_exporter_prometheus_rendered_series";
+const EXPIRED_SERIES: &str = "metrics_exporter_prometheus_expired_series_total";
+const PUSH_FAILURES: &str = "metrics_exporter_prometheus_push_failures_total";
+const HTTP_RESPONSES: &str = "metrics_exporter_prometheus_http_responses_total";
+
+static DESCRIPTIONS: [(&str, &str); 5] = [
+    (RENDER_DURATION, "Time taken by the previous render of the metrics, in seconds."),
+    (RENDERED_SERIES, "Number of metrics, by label set, in this render of the metrics."),
+    (EXPIRED_SERIES, "Number of metrics, by label set, removed after going idle."),
+    (PUSH_FAILURES, "Number of pushes which failed, after exhausting their retries."),
+    (HTTP_RESPONSES, "Number of responses sent by the HTTP listener, by status code."),
+];
+
+/// Operational metrics of the exporter, added to each snapshot of the metrics held by the recorder.
+#[derive(Default)]
+pub(crate) struct SelfMetrics {
+    render_duration: AtomicU64,
+    expired_series: AtomicU64,
+    push_failures: AtomicU64,
+    http_responses: Mutex<BTreeMap<u16, u64>>,
+}
+
+impl SelfMetrics {
+    /// Gets the descriptions of the self-metrics, rendered as their help text.
+    pub fn descriptions() -> impl Iterator<Item = (String, SharedString)> {
+        DESCRIPTIONS
+            .iter()
+            .map(|&(name, description)| (name.to_string(), SharedString::const_str(description)))
+    }
+
+    /// Records the time taken to render the metrics.
+    pub fn record_render(&self, duration: Duration) {
+        self.render_duration.store(duration.as_secs_f64().to_bits(), Ordering::Relaxed);
+    }
+
+    /// Records metrics which were removed after going idle.
+    pub fn record_expired(&self, count: u64) {
+        self.expired_series.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Records a push which failed, after exhausting its retries.
+    #[cfg_attr(not(feature = "push-gateway"), allow(dead_code))]
+    pub fn record_push_failure(&self) {
+        self.push_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a response sent by the HTTP listener.
+    #[cfg_attr(not(feature = "http-listener"), allow(dead_code))]
+    pub fn record_http_response(&self, status: u16) {
+        let mut http_responses = self.http_responses.lock().unwrap_or_else(PoisonError::into_inner);
+        *http_responses.entry(status).or_default() += 1;
+    }
+
+    /// Adds the self-metrics to the given snapshot, labelled with the given global labels.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn write_to(&self, snapshot: &mut Snapshot, global_labels: &IndexMap<String, String>) {
+        let series = snapshot.counters.values().map(HashMap::len).sum::<usize>()
+            + snapshot.gauges.values().map(HashMap::len).sum::<usize>()
+            + snapshot.distributions.values().map(IndexMap::len).sum::<usize>();
+
+        let mut gauge = |name: &'static str, value: f64| {
+            let (name, labels) = key_to_parts(&Key::from_static_name(name), Some(global_labels));
+            snapshot.gauges.entry(name).or_default().insert(labels, value);
+        };
+        gauge(RENDER_DURATION, f64::from_bits(self.render_duration.load(Ordering::Relaxed)));
+        gauge(RENDERED_SERIES, series as f64);
+
+        let mut counter = |key: Key, value: u64| {
+            let (name, labels) = key_to_parts(&key, Some(global_labels));
+            snapshot.counters.entry(name).or_default().insert(labels, (value, None));
+        };
+        counter(Key::from_static_name(EXPIRED_SERIES), self.expired_series.load(Ordering::Relaxed));
+        counter(Key::from_static_name(PUSH_FAILURES), self.push_failures.load(Ordering::Relaxed));
+
+        let http_responses = self.http_responses.lock().unwrap_or_else(PoisonError::into_inner);
+        for (status, count) in http_responses.iter() {
+            let labels = vec![Label::new("code", status.to_string())];
+            counter(Key::from_parts(HTTP_RESPONSES, labels), *count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use indexmap::IndexMap;
+
+    use super::SelfMetrics;
+    use crate::common::Snapshot;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_write_to() {
+        let self_metrics = SelfMetrics::default();
+        self_metrics.record_render(Duration::from_millis(250));
+        self_metrics.record_expired(2);
+        self_metrics.record_push_failure();
+        self_metrics.record_http_response(200);
+        self_metrics.record_http_response(200);
+        self_metrics.record_http_response(401);
+
+        let mut snapshot = Snapshot {
+            counters: HashMap::new(),
+            gauges: HashMap::from([(
+                "queue_depth".to_string(),
+                HashMap::from([(Vec::new(), 1.0), (vec!["queue=\"a\"".to_string()], 2.0)]),
+            )]),
+            distributions: HashMap::new(),
+            created: HashMap::new(),
+        };
+        let mut global_labels = IndexMap::new();
+        global_labels.insert("instance".to_string(), "a".to_string());
+        self_metrics.write_to(&mut snapshot, &global_labels);
+
+        let instance = vec!["instance=\"a\"".to_string()];
+        let gauges = &snapshot.gauges;
+        assert_eq!(gauges["metrics_exporter_prometheus_render_duration_seconds"][&instance], 0.25);
+        assert_eq!(gauges["metrics_exporter_prometheus_rendered_series"][&instance], 2.0);
+
+        let counters = &snapshot.counters;
+        assert_eq!(counters["metrics_exporter_prometheus_expired_series_total"][&instance].0, 2);
+        assert_eq!(counters["metrics_exporter_prometheus_push_failures_total"][&instance].0, 1);
+        let http_responses = &counters["metrics_exporter_prometheus_http_responses_total"];
+        let code = |code: &str| vec!["instance=\"a\"".to_string(), format!("code=\"{code}\"")];
+        assert_eq!(http_responses[&code("200")].0, 2);
+        assert_eq!(http_responses[&code("401")].0, 1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::{HeaderValue, ACCEPT, CONTENT_TYPE};
+use hyper::{Request, Response};
+use tower_service::Service;
+
+use crate::common::prefers_protobuf;
+use crate::{ExpositionFormat, PrometheusHandle, PROTOBUF_CONTENT_TYPE};
+
+/// A [`Service`] rendering the metrics held by a [`PrometheusRecorder`](crate::PrometheusRecorder)
+/// in response to each request.
+///
+/// This allows serving the metrics from an existing HTTP server, such as one built with `hyper` or
+/// `axum`, instead of running the HTTP listener of the exporter on a separate port.  With `axum`,
+/// for example, it can be mounted via `Router::route_service("/metrics", handle.as_service())`.
+///
+/// Every request is answered with the metrics, regardless of its method or path, so routing is up
+/// to the server.  Rendering happens synchronously when the service is called.
+///
+/// Created via [`PrometheusHandle::as_service`].
+#[derive(Clone)]
+pub struct PrometheusService {
+    handle: PrometheusHandle,
+    negotiate_exposition_format: bool,
+}
+
+impl PrometheusService {
+    pub(crate) fn new(handle: PrometheusHandle) -> PrometheusService {
+        PrometheusService { handle, negotiate_exposition_format: false }
+    }
+
+    /// Configures the service to select the exposition format based on the `Accept` header of each
+    /// request.
+    ///
+    /// This behaves like
+    /// [`PrometheusBuilder::negotiate_exposition_format`](crate::PrometheusBuilder::negotiate_exposition_format)
+    /// does for the HTTP listener.
+    ///
+    /// Defaults to disabled, answering every request in the format used by
+    /// [`PrometheusHandle::render`].
+    #[must_use]
+    pub fn negotiate_exposition_format(mut self, enabled: bool) -> Self {
+        self.negotiate_exposition_format = enabled;
+        self
+    }
+
+    fn respond<B>(&self, req: &Request<B>) -> Response<Full<Bytes>> {
+        let accept = self
+            .negotiate_exposition_format
+            .then(|| req.headers().get(ACCEPT))
+            .flatten()
+            .and_then(|accept| accept.to_str().ok());
+
+        let (body, content_type) = if accept.map_or(false, prefers_protobuf) {
+            (self.handle.render_protobuf(), PROTOBUF_CONTENT_TYPE)
+        } else {
+            let format = accept
+                .and_then(ExpositionFormat::from_accept)
+                .unwrap_or_else(|| self.handle.exposition_format());
+            (self.handle.render_with_format(format).into_bytes(), format.content_type())
+        };
+
+        let mut response = Response::new(body.into());
+        response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        response
+    }
+}
+
+impl<B> Service<Request<B>> for PrometheusService {
+    type Response = Response<Full<Bytes>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        ready(Ok(self.respond(&req)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::CONTENT_TYPE;
+    use hyper::Request;
+
+    use crate::{PrometheusBuilder, PROTOBUF_CONTENT_TYPE};
+
+    #[test]
+    fn test_respond() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let service = recorder.handle().as_service();
+        let protobuf = "application/vnd.google.protobuf;proto=io.prometheus.client.MetricFamily;\
+                        encoding=delimited";
+
+        let req = Request::get("/metrics").header("accept", protobuf).body(()).unwrap();
+        let response = service.respond(&req);
+        assert!(response.headers()[CONTENT_TYPE].to_str().unwrap().starts_with("text/plain"));
+
+        let service = service.negotiate_exposition_format(true);
+        let response = service.respond(&req);
+        assert_eq!(response.headers()[CONTENT_TYPE], PROTOBUF_CONTENT_TYPE);
+
+        let req = Request::get("/").header("accept", "application/openmetrics-text").body(());
+        let response = service.respond(&req.unwrap());
+        let content_type = response.headers()[CONTENT_TYPE].to_str().unwrap();
+        assert!(content_type.starts_with("application/openmetrics-text"));
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::approx_constant)]
 #[cfg(all(test, feature = "http-listener"))]
 mod http_listener_test {
     use http_body_util::{BodyExt, Collected, Empty};
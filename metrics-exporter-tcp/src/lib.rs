@@ -63,7 +63,7 @@ use bytes::Bytes;
 use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
 use metrics::{
     Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
-    SetRecorderError, SharedString, Unit,
+    SetRecorderError, SharedString, Summary, SummaryFn, Unit,
 };
 use mio::{
     net::{TcpListener, TcpStream},
@@ -89,12 +89,18 @@ enum MetricOperation {
     IncrementGauge(f64),
     DecrementGauge(f64),
     SetGauge(f64),
+    SetGaugeMax(f64),
+    SetGaugeMin(f64),
     RecordHistogram(f64),
 }
 
 enum Event {
     Metadata(KeyName, MetricType, Option<Unit>, SharedString),
     Metric(Key, MetricOperation),
+    // Acknowledged once every event sent before it has been handed off to the clients.
+    Flush(Sender<()>),
+    // Like `Flush`, but the transport stops once it has been acknowledged.
+    Shutdown(Sender<()>),
 }
 
 /// Errors that could occur while installing a TCP recorder/exporter.
@@ -221,6 +227,14 @@ impl GaugeFn for Handle {
     fn set(&self, value: f64) {
         self.state.push_metric(&self.key, MetricOperation::SetGauge(value))
     }
+
+    fn set_max(&self, value: f64) {
+        self.state.push_metric(&self.key, MetricOperation::SetGaugeMax(value))
+    }
+
+    fn set_min(&self, value: f64) {
+        self.state.push_metric(&self.key, MetricOperation::SetGaugeMin(value))
+    }
 }
 
 impl HistogramFn for Handle {
@@ -229,11 +243,33 @@ impl HistogramFn for Handle {
     }
 }
 
+// The protocol has no notion of summaries, so their values are sent as histogram records.
+impl SummaryFn for Handle {
+    fn record(&self, value: f64) {
+        self.state.push_metric(&self.key, MetricOperation::RecordHistogram(value))
+    }
+}
+
 /// A TCP recorder.
+///
+/// [`Recorder::flush`] waits until every metric recorded before it has been handed off to the
+/// connected clients, and [`Recorder::shutdown`] does the same before stopping the exporter and
+/// closing its listener.
 pub struct TcpRecorder {
     state: Arc<State>,
 }
 
+impl TcpRecorder {
+    fn request(&self, event: fn(Sender<()>) -> Event) {
+        let (ack, acked) = bounded(1);
+        if self.state.tx.send(event(ack)).is_ok() {
+            self.state.wake();
+            // The transport may have stopped in the meantime, which drops the acknowledgement.
+            let _ = acked.recv();
+        }
+    }
+}
+
 /// Builder for creating and installing a TCP recorder/exporter.
 pub struct TcpBuilder {
     listen_addr: SocketAddr,
@@ -343,6 +379,22 @@ impl Recorder for TcpRecorder {
     fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
         Histogram::from_arc(Arc::new(Handle::new(key.clone(), self.state.clone())))
     }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.state.register_metric(key_name, MetricType::Histogram, unit, description);
+    }
+
+    fn register_summary(&self, key: &Key, _metadata: &Metadata<'_>) -> Summary {
+        Summary::from_arc(Arc::new(Handle::new(key.clone(), self.state.clone())))
+    }
+
+    fn flush(&self) {
+        self.request(Event::Flush)
+    }
+
+    fn shutdown(&self) {
+        self.request(Event::Shutdown)
+    }
 }
 
 #[allow(clippy::mutable_key_type)]
@@ -360,6 +412,8 @@ fn run_transport(
     let mut metadata = HashMap::new();
     let mut next_token = START_TOKEN;
     let mut buffered_pmsgs = VecDeque::with_capacity(buffer_limit);
+    let mut acks = Vec::new();
+    let mut shutting_down = false;
 
     loop {
         let _span = trace_span!("transport");
@@ -416,11 +470,16 @@ fn run_transport(
                                     Err(e) => error!(error = ?e, "error encoding metric"),
                                 }
                             }
+                            Event::Flush(ack) => acks.push(ack),
+                            Event::Shutdown(ack) => {
+                                acks.push(ack);
+                                shutting_down = true;
+                            }
                         }
                     }
                     drop(_mrxspan);
 
-                    if buffered_pmsgs.is_empty() {
+                    if buffered_pmsgs.is_empty() && acks.is_empty() {
                         trace!("woken for metrics but no pmsgs buffered");
                         continue;
                     }
@@ -470,6 +529,14 @@ fn run_transport(
                             state.decrement_clients();
                         }
                     }
+
+                    // Every metric sent before a flush or shutdown has now been handed off.
+                    for ack in acks.drain(..) {
+                        let _ = ack.send(());
+                    }
+                    if shutting_down {
+                        return;
+                    }
                 }
                 LISTENER => {
                     // Accept as many new connections as we can.
@@ -522,9 +589,13 @@ fn generate_metadata_messages(
 ) -> VecDeque<Bytes> {
     let mut bufs = VecDeque::new();
     for (key_name, (metric_type, unit, desc)) in metadata.iter() {
-        let msg =
-            convert_metadata_to_protobuf_encoded(key_name, *metric_type, *unit, desc.as_ref())
-                .expect("failed to encode metadata buffer");
+        let msg = convert_metadata_to_protobuf_encoded(
+            key_name,
+            *metric_type,
+            unit.as_ref(),
+            desc.as_ref(),
+        )
+        .expect("failed to encode metadata buffer");
         bufs.push_back(msg);
     }
     bufs
@@ -579,7 +650,7 @@ fn drive_connection(
 fn convert_metadata_to_protobuf_encoded(
     key_name: &KeyName,
     metric_type: MetricType,
-    unit: Option<Unit>,
+    unit: Option<&Unit>,
     desc: Option<&SharedString>,
 ) -> Result<Bytes, EncodeError> {
     let name = key_name.as_str().to_string();
@@ -611,6 +682,8 @@ fn convert_metric_to_protobuf_encoded(
         MetricOperation::IncrementGauge(v) => proto::metric::Operation::IncrementGauge(v),
         MetricOperation::DecrementGauge(v) => proto::metric::Operation::DecrementGauge(v),
         MetricOperation::SetGauge(v) => proto::metric::Operation::SetGauge(v),
+        MetricOperation::SetGaugeMax(v) => proto::metric::Operation::SetGaugeMax(v),
+        MetricOperation::SetGaugeMin(v) => proto::metric::Operation::SetGaugeMin(v),
         MetricOperation::RecordHistogram(v) => proto::metric::Operation::RecordHistogram(v),
     };
 
@@ -0,0 +1,109 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parser;
+use syn::{Error, ItemFn, LitStr, ReturnType};
+
+#[derive(Default)]
+struct InstrumentArgs {
+    name: Option<LitStr>,
+    labels: Vec<(LitStr, LitStr)>,
+    errors: bool,
+}
+
+impl InstrumentArgs {
+    fn parse_meta(&mut self, meta: ParseNestedMeta<'_>) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("labels") {
+            meta.parse_nested_meta(|label| {
+                let key =
+                    label.path.get_ident().ok_or_else(|| label.error("expected a label key"))?;
+                let value = label.value()?.parse()?;
+                self.labels.push((LitStr::new(&key.to_string(), key.span()), value));
+                Ok(())
+            })?;
+        } else if meta.path.is_ident("errors") {
+            self.errors = true;
+        } else {
+            return Err(meta.error("unsupported argument, expected `name`, `labels`, or `errors`"));
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn expand(args: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    let mut parsed = InstrumentArgs::default();
+    syn::meta::parser(|meta| parsed.parse_meta(meta)).parse2(args)?;
+
+    let ItemFn { attrs, vis, sig, block } = syn::parse2(item)?;
+
+    let name = parsed.name.as_ref().map_or_else(|| sig.ident.to_string(), LitStr::value);
+    let calls_name = format!("{}_calls_total", name);
+    let duration_name = format!("{}_duration_seconds", name);
+    let errors_name = format!("{}_errors_total", name);
+
+    let label_keys = parsed.labels.iter().map(|(key, _)| key);
+    let label_values = parsed.labels.iter().map(|(_, value)| value);
+    let labels = quote! { #(, #label_keys => #label_values)* };
+
+    let prelude = quote! {
+        let __metrics_timer = ::metrics::timer!(#duration_name #labels).start();
+        ::metrics::counter!(#calls_name #labels).increment(1);
+    };
+
+    if !parsed.errors {
+        return Ok(quote! {
+            #(#attrs)*
+            #vis #sig {
+                #prelude
+                #block
+            }
+        });
+    }
+
+    let return_type = match &sig.output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => {
+            return Err(Error::new_spanned(
+                &sig,
+                "`errors` can only be used on functions which return a `Result`",
+            ))
+        }
+    };
+
+    // The body is wrapped so that early returns, and the `?` operator, still pass through the error
+    // check.  For `async` functions, the unreachable return pins down the output type of the block,
+    // which the compiler can't otherwise infer when the body uses the `?` operator.
+    let result = if sig.asyncness.is_some() {
+        quote! {
+            async move {
+                #[allow(unreachable_code, clippy::diverging_sub_expression)]
+                if false {
+                    let __metrics_unreachable: #return_type = loop {};
+                    return __metrics_unreachable;
+                }
+                #block
+            }
+            .await
+        }
+    } else {
+        quote! {
+            (|| -> #return_type #block)()
+        }
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            #prelude
+            #[allow(clippy::redundant_closure_call)]
+            let __metrics_result: #return_type = #result;
+            if __metrics_result.is_err() {
+                ::metrics::counter!(#errors_name #labels).increment(1);
+            }
+            __metrics_result
+        }
+    })
+}
@@ -0,0 +1,100 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, GenericParam, Lifetime, LifetimeParam, LitStr};
+
+/// How a field is turned into a label.
+enum FieldLabel {
+    Rename(LitStr),
+    Skip,
+}
+
+fn parse_field_attrs(field: &syn::Field) -> syn::Result<Option<FieldLabel>> {
+    let mut label = None;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("label")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                label = Some(FieldLabel::Rename(meta.value()?.parse()?));
+            } else if meta.path.is_ident("skip") {
+                label = Some(FieldLabel::Skip);
+            } else {
+                return Err(meta.error("unsupported argument, expected `rename` or `skip`"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(label)
+}
+
+pub(crate) fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+    let DeriveInput { ident, generics, data, .. } = syn::parse2(input)?;
+
+    let fields = match data {
+        Data::Struct(data) => match data.fields {
+            Fields::Named(fields) => fields.named,
+            Fields::Unit => Default::default(),
+            Fields::Unnamed(fields) => {
+                return Err(Error::new_spanned(
+                    fields,
+                    "`MetricLabels` can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "`MetricLabels` can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let mut keys = Vec::new();
+    let mut members = Vec::new();
+    for field in &fields {
+        let member = field.ident.as_ref().expect("named fields always have an identifier");
+        let key = match parse_field_attrs(field)? {
+            Some(FieldLabel::Skip) => continue,
+            Some(FieldLabel::Rename(key)) => key,
+            None => {
+                let name = member.to_string();
+                LitStr::new(name.strip_prefix("r#").unwrap_or(&name), member.span())
+            }
+        };
+        keys.push(key);
+        members.push(member);
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Labels can be taken by reference, as in `counter!("requests", &labels)`, which goes through
+    // the blanket implementation of `IntoLabels` for references to types that can be iterated over.
+    let mut ref_generics = generics.clone();
+    let lifetime = Lifetime::new("'__metrics_labels", proc_macro2::Span::call_site());
+    ref_generics.params.insert(0, GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())));
+    let (ref_impl_generics, _, _) = ref_generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::metrics::IntoLabels for #ident #ty_generics #where_clause {
+            fn into_labels(self) -> ::metrics::__private::vec::Vec<::metrics::Label> {
+                ::metrics::__private::vec![
+                    #(::metrics::Label::typed(#keys, self.#members)),*
+                ]
+            }
+        }
+
+        impl #ref_impl_generics ::core::iter::IntoIterator for &#lifetime #ident #ty_generics
+        #where_clause
+        {
+            type Item = ::metrics::Label;
+            type IntoIter = ::metrics::__private::vec::IntoIter<::metrics::Label>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                let labels: ::metrics::__private::vec::Vec<::metrics::Label> = ::metrics::__private::vec![
+                    #(::metrics::Label::typed(#keys, ::core::clone::Clone::clone(&self.#members))),*
+                ];
+                labels.into_iter()
+            }
+        }
+    })
+}
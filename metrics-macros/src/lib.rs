@@ -0,0 +1,96 @@
+//! Procedural macros for the [`metrics`](https://docs.rs/metrics) crate.
+//!
+//! This crate is not meant to be used directly: enable the `macros` feature of `metrics` and use the
+//! macros re-exported from there instead.  As the generated code refers to `metrics` by name, the
+//! `metrics` crate must be a direct dependency of the crate using the macros, and must not be
+//! renamed.
+#![deny(missing_docs)]
+
+use proc_macro::TokenStream;
+
+mod instrument;
+mod labels;
+
+/// Instruments a function with metrics.
+///
+/// Every call to the instrumented function increments a counter named `<name>_calls_total`, and
+/// records the time spent in the function, in seconds, into a histogram named
+/// `<name>_duration_seconds`.  The time is recorded when the function returns, whether via an early
+/// `return`, the `?` operator, or a panic.  Both synchronous and `async` functions are supported:
+/// for `async` functions, the time spent is measured from the first time the future is polled until
+/// it completes, including any time spent waiting.
+///
+/// # Arguments
+///
+/// - `name = "..."` sets the prefix of the metric names, which defaults to the name of the function.
+/// - `labels(key = "value", ...)` attaches the given labels to every metric.
+/// - `errors` additionally increments a counter named `<name>_errors_total` whenever the function
+///   returns an error, as determined by `Result::is_err`.  The function must return a `Result`.
+///
+/// # Examples
+///
+/// ```
+/// # use std::io;
+/// // Records `load_config_calls_total` and `load_config_duration_seconds`.
+/// #[metrics::instrument]
+/// fn load_config() -> String {
+///     String::from("verbose = true")
+/// }
+///
+/// // Records `db_query_calls_total`, `db_query_duration_seconds`, and `db_query_errors_total`,
+/// // each with a `table` label.
+/// #[metrics::instrument(name = "db_query", labels(table = "users"), errors)]
+/// async fn fetch_user(id: u64) -> io::Result<String> {
+///     if id == 0 {
+///         return Err(io::Error::new(io::ErrorKind::NotFound, "no such user"));
+///     }
+///
+///     Ok(format!("user-{}", id))
+/// }
+/// # fn main() {
+/// #     let _ = load_config();
+/// #     let _ = fetch_user(1);
+/// # }
+/// ```
+#[proc_macro_attribute]
+pub fn instrument(args: TokenStream, item: TokenStream) -> TokenStream {
+    instrument::expand(args.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives a label set from the fields of a struct.
+///
+/// Each field becomes a label, keyed by the name of the field, with the value of the field, which
+/// must implement `IntoLabelValue`, as its value.  The struct can then be passed to the emission
+/// macros in place of a list of labels, either by value or by reference, as it implements
+/// `IntoLabels`, and iterating over a reference to it yields its labels.  Fields are cloned when
+/// the struct is passed by reference.
+///
+/// # Attributes
+///
+/// - `#[label(rename = "...")]` uses the given label key instead of the name of the field.
+/// - `#[label(skip)]` leaves the field out of the label set.
+///
+/// # Examples
+///
+/// ```
+/// #[derive(metrics::MetricLabels)]
+/// struct RequestLabels {
+///     method: &'static str,
+///     #[label(rename = "status_code")]
+///     status: u16,
+///     #[label(skip)]
+///     request_id: u64,
+/// }
+///
+/// # fn main() {
+/// let labels = RequestLabels { method: "GET", status: 200, request_id: 1234 };
+/// metrics::counter!("requests", &labels).increment(1);
+/// metrics::histogram!("request_duration_seconds", labels).record(0.25);
+/// # }
+/// ```
+#[proc_macro_derive(MetricLabels, attributes(label))]
+pub fn derive_metric_labels(input: TokenStream) -> TokenStream {
+    labels::expand(input.into()).unwrap_or_else(syn::Error::into_compile_error).into()
+}
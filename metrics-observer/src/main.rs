@@ -103,10 +103,10 @@ fn main() -> Result<(), Box<dyn Error>> {
 
                 let display_value = match value {
                     MetricData::Counter(value) => {
-                        format!("total: {}", u64_to_displayable(value, unit))
+                        format!("total: {}", u64_to_displayable(value, unit.as_ref()))
                     }
                     MetricData::Gauge(value) => {
-                        format!("current: {}", f64_to_displayable(value, unit))
+                        format!("current: {}", f64_to_displayable(value, unit.as_ref()))
                     }
                     MetricData::Histogram(value) => {
                         let min = value.min();
@@ -119,11 +119,11 @@ fn main() -> Result<(), Box<dyn Error>> {
 
                         format!(
                             "min: {} p50: {} p99: {} p999: {} max: {}",
-                            f64_to_displayable(min, unit),
-                            f64_to_displayable(p50, unit),
-                            f64_to_displayable(p99, unit),
-                            f64_to_displayable(p999, unit),
-                            f64_to_displayable(max, unit),
+                            f64_to_displayable(min, unit.as_ref()),
+                            f64_to_displayable(p50, unit.as_ref()),
+                            f64_to_displayable(p99, unit.as_ref()),
+                            f64_to_displayable(p999, unit.as_ref()),
+                            f64_to_displayable(max, unit.as_ref()),
                         )
                     }
                 };
@@ -164,7 +164,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn u64_to_displayable(value: u64, unit: Option<Unit>) -> String {
+fn u64_to_displayable(value: u64, unit: Option<&Unit>) -> String {
     let unit = match unit {
         None => return value.to_string(),
         Some(inner) => inner,
@@ -182,7 +182,7 @@ fn u64_to_displayable(value: u64, unit: Option<Unit>) -> String {
     format!("{}{}", value, label)
 }
 
-fn f64_to_displayable(value: f64, unit: Option<Unit>) -> String {
+fn f64_to_displayable(value: f64, unit: Option<&Unit>) -> String {
     let unit = match unit {
         None => return value.to_string(),
         Some(inner) => inner,
@@ -200,11 +200,11 @@ fn f64_to_displayable(value: f64, unit: Option<Unit>) -> String {
     format!("{:.2}{}", value, label)
 }
 
-fn u64_data_to_displayable(value: u64, unit: Unit) -> String {
+fn u64_data_to_displayable(value: u64, unit: &Unit) -> String {
     f64_data_to_displayable(value as f64, unit)
 }
 
-fn f64_data_to_displayable(value: f64, unit: Unit) -> String {
+fn f64_data_to_displayable(value: f64, unit: &Unit) -> String {
     let delimiter = 1024_f64;
     let units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
     let unit_idx_max = units.len() as u32 - 1;
@@ -228,7 +228,7 @@ fn f64_data_to_displayable(value: f64, unit: Unit) -> String {
     format!("{:.2} {}", scaled, unit)
 }
 
-fn u64_time_to_displayable(value: u64, unit: Unit) -> String {
+fn u64_time_to_displayable(value: u64, unit: &Unit) -> String {
     let dur = match unit {
         Unit::Nanoseconds => Duration::from_nanos(value),
         Unit::Microseconds => Duration::from_micros(value),
@@ -241,7 +241,7 @@ fn u64_time_to_displayable(value: u64, unit: Unit) -> String {
     format!("{:?}", TruncatedDuration(dur))
 }
 
-fn f64_time_to_displayable(value: f64, unit: Unit) -> String {
+fn f64_time_to_displayable(value: f64, unit: &Unit) -> String {
     // Calculate how much we need to scale the value by, since `Duration` only takes f64 values if
     // they are at the seconds granularity, although obviously they could contain significant digits
     // for subsecond precision.
@@ -74,7 +74,7 @@ impl Client {
             .map(|(k, v)| {
                 let metakey = (k.kind(), k.key().name().to_string());
                 let (unit, desc) = match metadata.get(&metakey) {
-                    Some((unit, desc)) => (*unit, desc.clone()),
+                    Some((unit, desc)) => (unit.clone(), desc.clone()),
                     None => (None, None),
                 };
 
@@ -200,7 +200,10 @@ impl Runner {
                                     .map(|u| match u {
                                         proto::metadata::Unit::UnitValue(u) => u,
                                     })
-                                    .and_then(|s| Unit::from_string(s.as_str()));
+                                    .map(|s| {
+                                        Unit::from_string(s.as_str())
+                                            .unwrap_or_else(|| Unit::Custom(s.into()))
+                                    });
                                 *dentry = metadata.description.map(|d| match d {
                                     proto::metadata::Description::DescriptionValue(ds) => ds,
                                 });
@@ -265,6 +268,26 @@ impl Runner {
                                             *inner = value;
                                         }
                                     }
+                                    Operation::SetGaugeMax(value) => {
+                                        let key = CompositeKey::new(MetricKind::Gauge, key_data);
+                                        let mut metrics = self.metrics.write().unwrap();
+                                        let gauge = metrics
+                                            .entry(key)
+                                            .or_insert_with(|| MetricData::Gauge(value));
+                                        if let MetricData::Gauge(inner) = gauge {
+                                            *inner = inner.max(value);
+                                        }
+                                    }
+                                    Operation::SetGaugeMin(value) => {
+                                        let key = CompositeKey::new(MetricKind::Gauge, key_data);
+                                        let mut metrics = self.metrics.write().unwrap();
+                                        let gauge = metrics
+                                            .entry(key)
+                                            .or_insert_with(|| MetricData::Gauge(value));
+                                        if let MetricData::Gauge(inner) = gauge {
+                                            *inner = inner.min(value);
+                                        }
+                                    }
                                     Operation::RecordHistogram(value) => {
                                         let key =
                                             CompositeKey::new(MetricKind::Histogram, key_data);
@@ -102,7 +102,8 @@
 #![cfg_attr(docsrs, feature(doc_cfg), deny(rustdoc::broken_intra_doc_links))]
 
 use metrics::{
-    Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString, Unit,
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString,
+    Summary, Unit,
 };
 use metrics_util::layers::Layer;
 
@@ -240,4 +241,30 @@ where
         let key = new_key.as_ref().unwrap_or(key);
         self.inner.register_histogram(key, metadata)
     }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.enhance_key(key);
+        let key = new_key.as_ref().unwrap_or(key);
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
 }
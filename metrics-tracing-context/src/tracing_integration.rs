@@ -93,12 +93,13 @@ impl AsRef<Map> for Labels {
     }
 }
 
+type WithLabelsFn = fn(&Dispatch, &Id, f: &mut dyn FnMut(&Labels) -> Option<Key>) -> Option<Key>;
+
 /// [`MetricsLayer`] is a [`tracing_subscriber::Layer`] that captures the span
 /// fields and allows them to be later on used as metrics labels.
 #[derive(Default)]
 pub struct MetricsLayer {
-    with_labels:
-        Option<fn(&Dispatch, &Id, f: &mut dyn FnMut(&Labels) -> Option<Key>) -> Option<Key>>,
+    with_labels: Option<WithLabelsFn>,
 }
 
 impl MetricsLayer {
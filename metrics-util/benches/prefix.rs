@@ -7,9 +7,9 @@ fn layer_benchmark(c: &mut Criterion) {
     group.bench_function("basic", |b| {
         let prefix_layer = PrefixLayer::new("prefix");
         let recorder = prefix_layer.layer(NoopRecorder);
-        static KEY_NAME: &'static str = "simple_key";
+        static KEY_NAME: &str = "simple_key";
         static KEY_LABELS: [Label; 1] = [Label::from_static_parts("foo", "bar")];
-        static KEY_DATA: Key = Key::from_static_parts(&KEY_NAME, &KEY_LABELS);
+        static KEY_DATA: Key = Key::from_static_parts(KEY_NAME, &KEY_LABELS);
         static METADATA: metrics::Metadata =
             metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
 
@@ -19,9 +19,9 @@ fn layer_benchmark(c: &mut Criterion) {
     });
     group.bench_function("noop recorder overhead (increment_counter)", |b| {
         let recorder = NoopRecorder;
-        static KEY_NAME: &'static str = "simple_key";
+        static KEY_NAME: &str = "simple_key";
         static KEY_LABELS: [Label; 1] = [Label::from_static_parts("foo", "bar")];
-        static KEY_DATA: Key = Key::from_static_parts(&KEY_NAME, &KEY_LABELS);
+        static KEY_DATA: Key = Key::from_static_parts(KEY_NAME, &KEY_LABELS);
         static METADATA: metrics::Metadata =
             metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
 
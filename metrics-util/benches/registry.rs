@@ -1,27 +1,29 @@
+use std::time::{Duration, Instant};
+
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
-use metrics::{Key, Label};
-use metrics_util::registry::Registry;
+use metrics::{CounterFn, HistogramFn, Key, Label};
+use metrics_util::registry::{AtomicStorage, Registry, ShardedStorage, Storage};
 
 fn registry_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("registry");
     group.bench_function("cached op (basic)", |b| {
         let registry = Registry::atomic();
-        static KEY_NAME: &'static str = "simple_key";
-        static KEY_DATA: Key = Key::from_static_name(&KEY_NAME);
+        static KEY_NAME: &str = "simple_key";
+        static KEY_DATA: Key = Key::from_static_name(KEY_NAME);
 
         b.iter(|| registry.get_or_create_counter(&KEY_DATA, |_| ()))
     });
     group.bench_function("cached op (labels)", |b| {
         let registry = Registry::atomic();
-        static KEY_NAME: &'static str = "simple_key";
+        static KEY_NAME: &str = "simple_key";
         static KEY_LABELS: [Label; 1] = [Label::from_static_parts("type", "http")];
-        static KEY_DATA: Key = Key::from_static_parts(&KEY_NAME, &KEY_LABELS);
+        static KEY_DATA: Key = Key::from_static_parts(KEY_NAME, &KEY_LABELS);
 
         b.iter(|| registry.get_or_create_counter(&KEY_DATA, |_| ()))
     });
     group.bench_function("uncached op (basic)", |b| {
         b.iter_batched_ref(
-            || Registry::atomic(),
+            Registry::atomic,
             |registry| {
                 let key = "simple_key".into();
                 registry.get_or_create_counter(&key, |_| ())
@@ -31,7 +33,7 @@ fn registry_benchmark(c: &mut Criterion) {
     });
     group.bench_function("uncached op (labels)", |b| {
         b.iter_batched_ref(
-            || Registry::atomic(),
+            Registry::atomic,
             |registry| {
                 let labels = vec![Label::new("type", "http")];
                 let key = ("simple_key", labels).into();
@@ -45,15 +47,15 @@ fn registry_benchmark(c: &mut Criterion) {
     });
     group.bench_function("const key overhead (basic)", |b| {
         b.iter(|| {
-            static KEY_NAME: &'static str = "simple_key";
-            Key::from_static_name(&KEY_NAME)
+            static KEY_NAME: &str = "simple_key";
+            Key::from_static_name(KEY_NAME)
         })
     });
     group.bench_function("const key data overhead (labels)", |b| {
         b.iter(|| {
-            static KEY_NAME: &'static str = "simple_key";
+            static KEY_NAME: &str = "simple_key";
             static LABELS: [Label; 1] = [Label::from_static_parts("type", "http")];
-            Key::from_static_parts(&KEY_NAME, &LABELS)
+            Key::from_static_parts(KEY_NAME, &LABELS)
         })
     });
     group.bench_function("owned key overhead (basic)", |b| b.iter(|| Key::from_name("simple_key")));
@@ -67,5 +69,69 @@ fn registry_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, registry_benchmark);
+/// Runs `iters` updates on each of `threads` threads, all updating the same metric, and returns the
+/// time it took for all of them to finish.
+fn contended<S, F>(registry: &Registry<Key, S>, threads: usize, iters: u64, op: F) -> Duration
+where
+    S: Storage<Key> + Sync,
+    S::Counter: Send + Sync,
+    S::Gauge: Send + Sync,
+    S::Histogram: Send + Sync,
+    F: Fn(&Registry<Key, S>) + Sync,
+{
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                for _ in 0..iters {
+                    op(registry);
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn contention_benchmark(c: &mut Criterion) {
+    static KEY: Key = Key::from_static_name("contended_key");
+
+    let mut group = c.benchmark_group("registry contention");
+    for threads in [1, 4, 16] {
+        group.bench_function(format!("counter increment (atomic, {} threads)", threads), |b| {
+            let registry = Registry::new(AtomicStorage);
+            b.iter_custom(|iters| {
+                contended(&registry, threads, iters, |registry| {
+                    registry.get_or_create_counter(&KEY, |c| c.increment(1))
+                })
+            })
+        });
+        group.bench_function(format!("counter increment (sharded, {} threads)", threads), |b| {
+            let registry = Registry::new(ShardedStorage::new());
+            b.iter_custom(|iters| {
+                contended(&registry, threads, iters, |registry| {
+                    registry.get_or_create_counter(&KEY, |c| c.increment(1))
+                })
+            })
+        });
+        group.bench_function(format!("histogram record (atomic, {} threads)", threads), |b| {
+            let registry = Registry::new(AtomicStorage);
+            b.iter_custom(|iters| {
+                contended(&registry, threads, iters, |registry| {
+                    registry.get_or_create_histogram(&KEY, |h| h.record(1.0))
+                })
+            })
+        });
+        group.bench_function(format!("histogram record (sharded, {} threads)", threads), |b| {
+            let registry = Registry::new(ShardedStorage::new());
+            b.iter_custom(|iters| {
+                contended(&registry, threads, iters, |registry| {
+                    registry.get_or_create_histogram(&KEY, |h| h.record(1.0))
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, registry_benchmark, contention_benchmark);
 criterion_main!(benches);
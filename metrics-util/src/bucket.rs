@@ -1,11 +1,12 @@
 use crossbeam_epoch::{pin as epoch_pin, Atomic, Guard, Owned, Shared};
 use crossbeam_utils::Backoff;
+use metrics::atomics::AtomicU64;
 use std::{
     cell::UnsafeCell,
     cmp::min,
     mem::{self, MaybeUninit},
     slice,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
 #[cfg(target_pointer_width = "16")]
@@ -166,6 +167,75 @@ impl<T> std::fmt::Debug for Block<T> {
     }
 }
 
+/// Policy for values pushed into a bounded [`AtomicBucket`] which is already at capacity.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Drops values pushed while the bucket is at capacity.
+    DropNew,
+
+    /// Evicts the oldest values in the bucket to make room for new ones.
+    ///
+    /// Values are evicted a whole block at a time, so a bucket at capacity may briefly hold up to a
+    /// block's worth of values fewer than its capacity.  As the block currently being written to
+    /// is never evicted, values pushed while every value in the bucket is in that block are dropped
+    /// instead, which means that capacities smaller than a block behave like
+    /// [`OverflowPolicy::DropNew`].
+    DropOld,
+
+    /// Keeps every value, only counting the values pushed while the bucket is at capacity.
+    ///
+    /// This is useful for finding out whether a capacity is large enough before enforcing it.
+    CountOnly,
+}
+
+/// Capacity tracking for a bounded [`AtomicBucket`].
+#[derive(Debug)]
+struct Bound {
+    capacity: usize,
+    policy: OverflowPolicy,
+
+    // Number of values held by the bucket, including values whose write is still in-flight.
+    len: AtomicUsize,
+
+    // Number of values which overflowed the capacity of the bucket.
+    overflowed: AtomicU64,
+
+    // Whether blocks are currently being detached from the bucket, either to evict the oldest
+    // block or to clear the bucket.
+    //
+    // Eviction unlinks the oldest block from the block before it, while clearing walks the blocks
+    // it has detached and frees all of them.  If both happened at the same time, the oldest block
+    // could be freed twice, so only one of them is allowed to run at a time.
+    detaching: AtomicBool,
+}
+
+impl Bound {
+    fn try_lock(&self) -> Option<DetachGuard<'_>> {
+        self.detaching
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| DetachGuard(&self.detaching))
+    }
+
+    fn lock(&self) -> DetachGuard<'_> {
+        let backoff = Backoff::new();
+        loop {
+            match self.try_lock() {
+                Some(guard) => return guard,
+                None => backoff.snooze(),
+            }
+        }
+    }
+}
+
+struct DetachGuard<'a>(&'a AtomicBool);
+
+impl Drop for DetachGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
 /// A lock-free bucket with snapshot capabilities.
 ///
 /// This bucket is implemented as a singly-linked list of blocks, where each block is a small
@@ -185,15 +255,53 @@ impl<T> std::fmt::Debug for Block<T> {
 ///
 /// Block sizes are dependent on the target architecture, where each block can hold N items, and N
 /// is the number of bits in the target architecture's pointer width.
+///
+/// ## Bounded buckets
+///
+/// As buckets are unbounded by default, values accumulate until the bucket is cleared, which can
+/// exhaust memory if the bucket isn't cleared often enough, such as when an exporter is scraped
+/// slowly.  A bucket created with [`AtomicBucket::bounded`] instead holds at most a given number of
+/// values, handling any values pushed beyond that according to an [`OverflowPolicy`], and counts
+/// those values, which can be read via [`AtomicBucket::overflowed`].
 #[derive(Debug)]
 pub struct AtomicBucket<T> {
     tail: Atomic<Block<T>>,
+    bound: Option<Bound>,
 }
 
 impl<T> AtomicBucket<T> {
     /// Creates a new, empty bucket.
     pub fn new() -> Self {
-        AtomicBucket { tail: Atomic::null() }
+        AtomicBucket { tail: Atomic::null(), bound: None }
+    }
+
+    /// Creates a new, empty bucket which holds at most `capacity` values.
+    ///
+    /// Values pushed while the bucket is at capacity are handled according to `policy`.
+    pub fn bounded(capacity: usize, policy: OverflowPolicy) -> Self {
+        let bound = Bound {
+            capacity,
+            policy,
+            len: AtomicUsize::new(0),
+            overflowed: AtomicU64::new(0),
+            detaching: AtomicBool::new(false),
+        };
+
+        AtomicBucket { tail: Atomic::null(), bound: Some(bound) }
+    }
+
+    /// Gets the capacity of this bucket, if it is bounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.bound.as_ref().map(|bound| bound.capacity)
+    }
+
+    /// Gets the number of values which overflowed the capacity of this bucket.
+    ///
+    /// Depending on the overflow policy, this is the number of values which were dropped, evicted,
+    /// or pushed while the bucket was at capacity.  The count is never reset, and is always zero
+    /// for unbounded buckets.
+    pub fn overflowed(&self) -> u64 {
+        self.bound.as_ref().map_or(0, |bound| bound.overflowed.load(Ordering::Relaxed))
     }
 
     /// Checks whether or not this bucket is empty.
@@ -210,10 +318,29 @@ impl<T> AtomicBucket<T> {
         tail_block.len() == 0 && tail_block.next_len(guard) == 0
     }
 
+    /// Gets the number of elements written to the bucket.
+    ///
+    /// This walks every block in the bucket, in the same way as
+    /// [`data_with`](AtomicBucket::data_with), but without copying any of the elements.
+    pub fn len(&self) -> usize {
+        let mut len = 0;
+        self.data_with(|block| len += block.len());
+        len
+    }
+
     /// Pushes an element into the bucket.
+    ///
+    /// If the bucket is bounded, and at capacity, the element may be dropped, as described by its
+    /// [`OverflowPolicy`].
     pub fn push(&self, value: T) {
-        let mut original = value;
         let guard = &epoch_pin();
+        if let Some(bound) = &self.bound {
+            if !self.reserve(bound, guard) {
+                return;
+            }
+        }
+
+        let mut original = value;
         loop {
             // Load the tail block, or install a new one.
             let mut tail = self.tail.load(Ordering::Acquire, guard);
@@ -273,6 +400,71 @@ impl<T> AtomicBucket<T> {
         }
     }
 
+    /// Reserves room for a value in a bounded bucket, returning `false` if the value should be
+    /// dropped.
+    fn reserve(&self, bound: &Bound, guard: &Guard) -> bool {
+        if bound.len.fetch_add(1, Ordering::AcqRel) < bound.capacity {
+            return true;
+        }
+
+        match bound.policy {
+            OverflowPolicy::CountOnly => {
+                bound.overflowed.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            OverflowPolicy::DropOld if self.evict_oldest(bound, guard) => true,
+            _ => {
+                bound.len.fetch_sub(1, Ordering::AcqRel);
+                bound.overflowed.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Evicts the oldest block from the bucket, returning `false` if no block could be evicted.
+    fn evict_oldest(&self, bound: &Bound, guard: &Guard) -> bool {
+        // If another caller is already evicting, or clearing, we don't wait for them.
+        let _detaching = match bound.try_lock() {
+            Some(detaching) => detaching,
+            None => return false,
+        };
+
+        // The tail block is still being written to, so we can only evict the blocks after it.
+        let tail = self.tail.load(Ordering::Acquire, guard);
+        if tail.is_null() {
+            return false;
+        }
+
+        let mut previous = unsafe { tail.deref() };
+        let mut oldest = previous.next.load(Ordering::Acquire, guard);
+        if oldest.is_null() {
+            return false;
+        }
+
+        loop {
+            let block = unsafe { oldest.deref() };
+            let next = block.next.load(Ordering::Acquire, guard);
+            if next.is_null() {
+                break;
+            }
+
+            previous = block;
+            oldest = next;
+        }
+
+        // Every block after the tail block is full, as a new tail block is only installed once the
+        // previous one is full.  Writes to the evicted block may still be in-flight, which is fine,
+        // as blocks wait for in-flight writes to complete before being dropped.
+        previous.next.store(Shared::null(), Ordering::Release);
+        bound.len.fetch_sub(BLOCK_SIZE, Ordering::AcqRel);
+        bound.overflowed.fetch_add(BLOCK_SIZE as u64, Ordering::Relaxed);
+        unsafe {
+            guard.defer_unchecked(move || drop(oldest.into_owned()));
+        }
+
+        true
+    }
+
     /// Collects all of the elements written to the bucket.
     ///
     /// This operation can be slow as it involves allocating enough space to hold all of the
@@ -358,6 +550,7 @@ impl<T> AtomicBucket<T> {
         // still be in process of writing to the tail node, or reading the data, but new callers
         // will see it as empty until another write proceeds.
         let guard = &epoch_pin();
+        let _detaching = self.bound.as_ref().map(Bound::lock);
         let mut block_ptr = self.tail.load(Ordering::Acquire, guard);
         if !block_ptr.is_null()
             && self
@@ -388,6 +581,9 @@ impl<T> AtomicBucket<T> {
 
                 // Read the data out of the block.
                 let data = block.data();
+                if let Some(bound) = &self.bound {
+                    bound.len.fetch_sub(data.len(), Ordering::AcqRel);
+                }
                 f(data);
 
                 // Load the next block and take the shared reference to the current.
@@ -427,13 +623,13 @@ impl<T> AtomicBucket<T> {
 
 impl<T> Default for AtomicBucket<T> {
     fn default() -> Self {
-        Self { tail: Atomic::null() }
+        Self { tail: Atomic::null(), bound: None }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{AtomicBucket, Block, BLOCK_SIZE};
+    use super::{AtomicBucket, Block, OverflowPolicy, BLOCK_SIZE};
     use crossbeam_utils::thread::scope;
 
     #[test]
@@ -666,4 +862,96 @@ mod tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_bounded_drop_new() {
+        let bucket = AtomicBucket::bounded(BLOCK_SIZE + 2, OverflowPolicy::DropNew);
+        assert_eq!(bucket.capacity(), Some(BLOCK_SIZE + 2));
+
+        for i in 0..BLOCK_SIZE * 2 {
+            bucket.push(i);
+        }
+
+        let snapshot = bucket.data();
+        assert_eq!(snapshot.len(), BLOCK_SIZE + 2);
+        assert!(snapshot.iter().all(|i| *i < BLOCK_SIZE + 2));
+        assert_eq!(bucket.overflowed(), (BLOCK_SIZE - 2) as u64);
+
+        // Clearing the bucket makes room for new values again.
+        bucket.clear();
+        bucket.push(42);
+        assert_eq!(bucket.data(), vec![42]);
+        assert_eq!(bucket.overflowed(), (BLOCK_SIZE - 2) as u64);
+    }
+
+    #[test]
+    fn test_bounded_drop_old() {
+        let bucket = AtomicBucket::bounded(BLOCK_SIZE * 2, OverflowPolicy::DropOld);
+
+        for i in 0..BLOCK_SIZE * 4 {
+            bucket.push(i);
+        }
+
+        // The two oldest blocks were evicted, keeping the newest values.
+        let mut snapshot = bucket.data();
+        snapshot.sort_unstable();
+        assert_eq!(snapshot, (BLOCK_SIZE * 2..BLOCK_SIZE * 4).collect::<Vec<_>>());
+        assert_eq!(bucket.overflowed(), (BLOCK_SIZE * 2) as u64);
+
+        // One more value evicts another block.
+        bucket.push(BLOCK_SIZE * 4);
+        assert_eq!(bucket.data().len(), BLOCK_SIZE + 1);
+        assert_eq!(bucket.overflowed(), (BLOCK_SIZE * 3) as u64);
+    }
+
+    #[test]
+    fn test_bounded_drop_old_small_capacity() {
+        // With a capacity smaller than a block, there's never an older block to evict.
+        let bucket = AtomicBucket::bounded(2, OverflowPolicy::DropOld);
+        for i in 0..4 {
+            bucket.push(i);
+        }
+
+        assert_eq!(bucket.data(), vec![0, 1]);
+        assert_eq!(bucket.overflowed(), 2);
+    }
+
+    #[test]
+    fn test_bounded_count_only() {
+        let bucket = AtomicBucket::bounded(4, OverflowPolicy::CountOnly);
+        for i in 0..10 {
+            bucket.push(i);
+        }
+
+        assert_eq!(bucket.data().len(), 10);
+        assert_eq!(bucket.overflowed(), 6);
+
+        let unbounded = AtomicBucket::new();
+        unbounded.push(1);
+        assert_eq!(unbounded.capacity(), None);
+        assert_eq!(unbounded.overflowed(), 0);
+    }
+
+    #[test]
+    fn test_bounded_drop_old_mt() {
+        let bucket = AtomicBucket::bounded(BLOCK_SIZE * 8, OverflowPolicy::DropOld);
+
+        scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|_| {
+                    for i in 0..BLOCK_SIZE as u64 * 1000 {
+                        bucket.push(i);
+                    }
+                });
+            }
+            s.spawn(|_| {
+                for _ in 0..100 {
+                    bucket.clear();
+                }
+            });
+        })
+        .unwrap();
+
+        assert!(bucket.data().len() <= BLOCK_SIZE * 8);
+    }
 }
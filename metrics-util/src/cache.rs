@@ -0,0 +1,422 @@
+use std::collections::HashMap;
+use std::sync::{atomic::Ordering, RwLock};
+
+use metrics::{atomics::AtomicU64, Key};
+
+/// A hook invoked with the key and handle of every entry evicted from a [`HandleCache`].
+type EvictionHook<H> = Box<dyn Fn(&Key, &H) + Send + Sync>;
+
+/// A handle held by a bounded handle cache, along with the tick it was last used at.
+struct LruEntry<H> {
+    handle: H,
+    last_used: AtomicU64,
+}
+
+/// Least-recently-used state for a bounded handle cache.
+///
+/// Recency is tracked per entry, so that hits only need a read lock: using an entry stores the
+/// current tick in it, and only advances the tick if the entry wasn't the most recently used one
+/// already, so that repeated hits on the same entry don't write anything.
+struct LruState<H> {
+    entries: RwLock<HashMap<Key, LruEntry<H>>>,
+    tick: AtomicU64,
+    capacity: usize,
+}
+
+impl<H: Clone> LruState<H> {
+    fn new(capacity: usize) -> Self {
+        LruState { entries: RwLock::new(HashMap::new()), tick: AtomicU64::new(0), capacity }
+    }
+
+    fn touch(&self, entry: &LruEntry<H>) {
+        if entry.last_used.load(Ordering::Relaxed) != self.tick.load(Ordering::Relaxed) {
+            let tick = self.tick.fetch_add(1, Ordering::Relaxed) + 1;
+            entry.last_used.store(tick, Ordering::Relaxed);
+        }
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn get(&self, key: &Key) -> Option<H> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.get(key)?;
+        self.touch(entry);
+        Some(entry.handle.clone())
+    }
+
+    /// Inserts the given handle, unless one already exists for the key, returning the handle for
+    /// the key and pushing any entries evicted to make room for it onto `evicted`.
+    #[allow(clippy::mutable_key_type)]
+    fn insert(&self, key: &Key, handle: H, evicted: &mut Vec<(Key, H)>) -> H {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get(key) {
+            self.touch(entry);
+            return entry.handle.clone();
+        }
+
+        if entries.len() >= self.capacity {
+            // Finding the least recently used entries means going through all of them, so rather
+            // than doing so for every insert, an eighth of the capacity is freed up at once.
+            let count = (entries.len() + 1 - self.capacity).max(self.capacity / 8);
+            let mut candidates = entries
+                .iter()
+                .map(|(key, entry)| (entry.last_used.load(Ordering::Relaxed), key))
+                .collect::<Vec<_>>();
+            candidates.select_nth_unstable_by_key(count - 1, |(last_used, _)| *last_used);
+            let oldest =
+                candidates[..count].iter().map(|(_, key)| (*key).clone()).collect::<Vec<_>>();
+
+            for key in oldest {
+                if let Some(entry) = entries.remove(&key) {
+                    evicted.push((key, entry.handle));
+                }
+            }
+        }
+
+        let last_used = AtomicU64::new(self.tick.fetch_add(1, Ordering::Relaxed) + 1);
+        entries.insert(key.clone(), LruEntry { handle: handle.clone(), last_used });
+        handle
+    }
+}
+
+enum Entries<H> {
+    Unbounded(RwLock<HashMap<Key, H>>),
+    Lru(LruState<H>),
+}
+
+/// Statistics on the lookups made in a [`HandleCache`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheStats {
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl CacheStats {
+    /// Gets the number of lookups which found a cached handle.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Gets the number of lookups which didn't find a cached handle.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Gets the number of handles evicted to make room for others.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Combines these statistics with another set of statistics.
+    pub fn merge(self, other: CacheStats) -> CacheStats {
+        CacheStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            evictions: self.evictions + other.evictions,
+        }
+    }
+}
+
+/// A concurrent cache of metric handles, keyed by the key they were registered with.
+///
+/// Exporters, and layers such as [`CacheLayer`](crate::layers::CacheLayer), can use a
+/// `HandleCache` to avoid registering the same key over and over again, such as when metrics with
+/// dynamic labels are emitted in hot code paths.
+///
+/// Caches are either unbounded, or bounded via [`HandleCache::lru`], in which case the least
+/// recently used handles are evicted whenever the cache is full.  Lookups which find a cached
+/// handle only take a read lock on the cache, in either case.  A hook can be set via
+/// [`HandleCache::on_evict`] to be notified of every evicted handle, and the number of hits,
+/// misses, and evictions can be read via [`HandleCache::stats`].
+pub struct HandleCache<H> {
+    entries: Entries<H>,
+    on_evict: Option<EvictionHook<H>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<H: Clone> HandleCache<H> {
+    fn with_entries(entries: Entries<H>) -> HandleCache<H> {
+        HandleCache {
+            entries,
+            on_evict: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Creates a new, unbounded `HandleCache`.
+    pub fn unbounded() -> HandleCache<H> {
+        Self::with_entries(Entries::Unbounded(RwLock::new(HashMap::new())))
+    }
+
+    /// Creates a new `HandleCache` which holds up to `capacity` handles, evicting the least
+    /// recently used handles when full.
+    ///
+    /// To avoid going through every cached handle on every insert once the cache is full, an eighth
+    /// of the capacity is freed up at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn lru(capacity: usize) -> HandleCache<H> {
+        assert!(capacity > 0, "capacity must be greater than zero");
+        Self::with_entries(Entries::Lru(LruState::new(capacity)))
+    }
+
+    /// Sets a hook to be called with the key and handle of every entry evicted from the cache.
+    ///
+    /// The hook is called without holding any locks on the cache.  Entries removed via
+    /// [`remove`](HandleCache::remove) or [`clear`](HandleCache::clear) aren't considered evicted.
+    pub fn on_evict<F>(mut self, hook: F) -> HandleCache<H>
+    where
+        F: Fn(&Key, &H) + Send + Sync + 'static,
+    {
+        self.on_evict = Some(Box::new(hook));
+        self
+    }
+
+    /// Gets the maximum number of handles held by the cache, if it's bounded.
+    pub fn capacity(&self) -> Option<usize> {
+        match &self.entries {
+            Entries::Unbounded(_) => None,
+            Entries::Lru(state) => Some(state.capacity),
+        }
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn lookup(&self, key: &Key) -> Option<H> {
+        match &self.entries {
+            Entries::Unbounded(handles) => {
+                handles.read().unwrap_or_else(|e| e.into_inner()).get(key).cloned()
+            }
+            Entries::Lru(state) => state.get(key),
+        }
+    }
+
+    fn record_lookup(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Gets the cached handle for the given key, if it exists.
+    ///
+    /// For bounded caches, this marks the handle as the most recently used.
+    pub fn get(&self, key: &Key) -> Option<H> {
+        let handle = self.lookup(key);
+        self.record_lookup(handle.is_some());
+        handle
+    }
+
+    /// Gets the cached handle for the given key, or creates and caches a new one.
+    ///
+    /// The cache is not locked while creating the handle, so concurrent callers may both create a
+    /// handle for the same key, in which case the handle which was cached first is returned to both
+    /// of them.
+    pub fn get_or_insert_with<F>(&self, key: &Key, create: F) -> H
+    where
+        F: FnOnce() -> H,
+    {
+        self.get_or_replace_with(key, |_| false, create)
+    }
+
+    /// Gets the cached handle for the given key, or creates and caches a new one, replacing the
+    /// cached handle if `is_stale` returns `true` for it.
+    ///
+    /// This allows replacing handles which the recorder they were registered with no longer
+    /// observes, such as expired ones.  Looking up a stale handle counts as a miss.
+    #[allow(clippy::mutable_key_type)]
+    pub fn get_or_replace_with<S, F>(&self, key: &Key, is_stale: S, create: F) -> H
+    where
+        S: Fn(&H) -> bool,
+        F: FnOnce() -> H,
+    {
+        match self.lookup(key) {
+            Some(handle) if !is_stale(&handle) => {
+                self.record_lookup(true);
+                return handle;
+            }
+            Some(_) => self.remove_if(key, &is_stale),
+            None => {}
+        }
+        self.record_lookup(false);
+
+        let handle = create();
+        match &self.entries {
+            Entries::Unbounded(handles) => {
+                let mut handles = handles.write().unwrap_or_else(|e| e.into_inner());
+                handles.entry(key.clone()).or_insert(handle).clone()
+            }
+            Entries::Lru(state) => {
+                let mut evicted = Vec::new();
+                let handle = state.insert(key, handle, &mut evicted);
+
+                self.evictions.fetch_add(evicted.len() as u64, Ordering::Relaxed);
+                if let Some(hook) = &self.on_evict {
+                    for (key, handle) in &evicted {
+                        hook(key, handle);
+                    }
+                }
+                handle
+            }
+        }
+    }
+
+    /// Removes the cached handle for the given key, returning it if it existed.
+    #[allow(clippy::mutable_key_type)]
+    pub fn remove(&self, key: &Key) -> Option<H> {
+        match &self.entries {
+            Entries::Unbounded(handles) => {
+                handles.write().unwrap_or_else(|e| e.into_inner()).remove(key)
+            }
+            Entries::Lru(state) => state
+                .entries
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(key)
+                .map(|entry| entry.handle),
+        }
+    }
+
+    /// Removes the cached handle for the given key if `predicate` returns `true` for it, which
+    /// leaves a handle cached concurrently in the meantime in place.
+    #[allow(clippy::mutable_key_type)]
+    fn remove_if<P>(&self, key: &Key, predicate: P)
+    where
+        P: Fn(&H) -> bool,
+    {
+        match &self.entries {
+            Entries::Unbounded(handles) => {
+                let mut handles = handles.write().unwrap_or_else(|e| e.into_inner());
+                if handles.get(key).map_or(false, &predicate) {
+                    handles.remove(key);
+                }
+            }
+            Entries::Lru(state) => {
+                let mut entries = state.entries.write().unwrap_or_else(|e| e.into_inner());
+                if entries.get(key).map_or(false, |entry| predicate(&entry.handle)) {
+                    entries.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Removes every cached handle.
+    pub fn clear(&self) {
+        match &self.entries {
+            Entries::Unbounded(handles) => {
+                handles.write().unwrap_or_else(|e| e.into_inner()).clear()
+            }
+            Entries::Lru(state) => state.entries.write().unwrap_or_else(|e| e.into_inner()).clear(),
+        }
+    }
+
+    /// Gets the number of handles currently cached.
+    pub fn len(&self) -> usize {
+        match &self.entries {
+            Entries::Unbounded(handles) => handles.read().unwrap_or_else(|e| e.into_inner()).len(),
+            Entries::Lru(state) => state.entries.read().unwrap_or_else(|e| e.into_inner()).len(),
+        }
+    }
+
+    /// Returns `true` if no handles are currently cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the statistics of the lookups made in the cache so far.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use metrics::Key;
+
+    use super::HandleCache;
+
+    #[test]
+    fn test_lru_cache() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let hook_evicted = Arc::clone(&evicted);
+        let cache = HandleCache::lru(2).on_evict(move |key: &Key, handle: &u32| {
+            hook_evicted.lock().unwrap().push((key.clone(), *handle));
+        });
+        assert_eq!(cache.capacity(), Some(2));
+
+        let (a, b, c) = (Key::from_name("a"), Key::from_name("b"), Key::from_name("c"));
+        assert_eq!(cache.get_or_insert_with(&a, || 1), 1);
+        assert_eq!(cache.get_or_insert_with(&b, || 2), 2);
+
+        // Touching `a` makes `b` the least recently used key.
+        assert_eq!(cache.get_or_insert_with(&a, || unreachable!()), 1);
+        assert_eq!(cache.get_or_insert_with(&c, || 3), 3);
+        assert_eq!(*evicted.lock().unwrap(), vec![(b.clone(), 2)]);
+        assert_eq!(cache.get(&b), None);
+        assert_eq!(cache.len(), 2);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits(), stats.misses(), stats.evictions()), (1, 4, 1));
+
+        assert_eq!(cache.remove(&a), Some(1));
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(evicted.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_lru_batch_eviction() {
+        let cache = HandleCache::lru(16);
+        for i in 0..16 {
+            cache.get_or_insert_with(&Key::from_name(format!("key_{}", i)), || i);
+        }
+
+        // Touching the first key makes the next two the least recently used ones, both of which are
+        // evicted at once to make room for more keys.
+        assert_eq!(cache.get(&Key::from_name("key_0")), Some(0));
+        cache.get_or_insert_with(&Key::from_name("key_16"), || 16);
+        assert_eq!(cache.len(), 15);
+        assert_eq!(cache.stats().evictions(), 2);
+        assert_eq!(cache.get(&Key::from_name("key_0")), Some(0));
+        assert_eq!(cache.get(&Key::from_name("key_1")), None);
+        assert_eq!(cache.get(&Key::from_name("key_2")), None);
+        assert_eq!(cache.get(&Key::from_name("key_3")), Some(3));
+    }
+
+    #[test]
+    fn test_replace_stale() {
+        for cache in [HandleCache::unbounded(), HandleCache::lru(2)] {
+            let key = Key::from_name("a");
+            assert_eq!(cache.get_or_replace_with(&key, |h| *h == 1, || 1), 1);
+            assert_eq!(cache.get_or_replace_with(&key, |h| *h == 2, || unreachable!()), 1);
+            assert_eq!(cache.get_or_replace_with(&key, |h| *h == 1, || 2), 2);
+            assert_eq!(cache.get(&key), Some(2));
+            assert_eq!(cache.len(), 1);
+
+            let stats = cache.stats();
+            assert_eq!((stats.hits(), stats.misses(), stats.evictions()), (2, 2, 0));
+        }
+    }
+
+    #[test]
+    fn test_unbounded_cache() {
+        let cache = HandleCache::unbounded();
+        assert_eq!(cache.capacity(), None);
+
+        for i in 0..100 {
+            cache.get_or_insert_with(&Key::from_name(format!("key_{}", i)), || i);
+        }
+        assert_eq!(cache.get(&Key::from_name("key_42")), Some(42));
+        assert_eq!(cache.len(), 100);
+        assert_eq!(cache.stats().evictions(), 0);
+    }
+}
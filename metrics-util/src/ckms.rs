@@ -0,0 +1,328 @@
+use std::cmp::Ordering;
+
+/// A sample in a CKMS summary: a value, the number of values it stands in for, and the uncertainty
+/// of its rank.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Sample {
+    value: f64,
+    width: u64,
+    delta: u64,
+}
+
+/// A streaming quantile estimator based on the [CKMS][ckms] algorithm for targeted quantiles.
+///
+/// `Ckms` is configured with a set of target quantiles, each with its own error bound: an estimate
+/// of a target quantile `q` with an error of `e` is guaranteed to have a rank within `e * n` of
+/// the rank of the true value, where `n` is the number of samples.  Only as many samples as are
+/// needed to uphold these guarantees are kept, so tight bounds can be placed on the quantiles that
+/// matter, such as `0.99` or `0.999`, without storing every sample.  Quantiles other than the
+/// targets can still be estimated, but without any guarantee on their error.
+///
+/// Samples are buffered before being merged into the summary, so a summary with buffered samples
+/// is compressed on the fly whenever a quantile is requested.  When querying many quantiles at
+/// once, calling [`Ckms::compress`] beforehand avoids doing so repeatedly.
+///
+/// [ckms]: https://doi.org/10.1109/ICDE.2005.55
+#[derive(Clone, Debug)]
+pub struct Ckms {
+    targets: Vec<(f64, f64)>,
+    samples: Vec<Sample>,
+    buffer: Vec<f64>,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+const BUFFER_CAPACITY: usize = 500;
+
+impl Ckms {
+    /// Creates a new [`Ckms`] with the given targets, as pairs of quantile and error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no targets are given, or if any quantile or error is not strictly between `0.0`
+    /// and `1.0`.
+    pub fn new(targets: &[(f64, f64)]) -> Ckms {
+        assert!(!targets.is_empty(), "at least one target must be given");
+        for &(quantile, error) in targets {
+            assert!(
+                quantile > 0.0 && quantile < 1.0,
+                "target quantile must be between 0.0 and 1.0, exclusive"
+            );
+            assert!(
+                error > 0.0 && error < 1.0,
+                "target error must be between 0.0 and 1.0, exclusive"
+            );
+        }
+
+        Ckms {
+            targets: targets.to_vec(),
+            samples: Vec::new(),
+            buffer: Vec::with_capacity(BUFFER_CAPACITY),
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Creates a new [`Ckms`] targeting the `0.5`, `0.9`, and `0.99` quantiles, with errors of
+    /// `0.05`, `0.01`, and `0.001`, respectively.
+    pub fn with_defaults() -> Ckms {
+        Ckms::new(&[(0.5, 0.05), (0.9, 0.01), (0.99, 0.001)])
+    }
+
+    /// Gets the targets of this summary, as pairs of quantile and error.
+    pub fn targets(&self) -> &[(f64, f64)] {
+        &self.targets
+    }
+
+    /// Adds a sample to the summary.
+    ///
+    /// Samples which aren't finite are ignored.
+    pub fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+
+        self.buffer.push(value);
+        if self.buffer.len() >= BUFFER_CAPACITY {
+            self.compress();
+        }
+    }
+
+    /// Gets the estimated value at the given quantile.
+    ///
+    /// If the summary is empty, or if the quantile is less than 0.0 or greater than 1.0, then the
+    /// result will be `None`.
+    ///
+    /// If the 0.0 or 1.0 quantile is requested, this function will return self.min() or self.max()
+    /// instead of the estimated value.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if !(0.0..=1.0).contains(&q) || self.is_empty() {
+            return None;
+        }
+
+        if self.buffer.is_empty() {
+            return Some(self.quantile_compressed(q));
+        }
+
+        let mut summary = self.clone();
+        summary.compress();
+        Some(summary.quantile_compressed(q))
+    }
+
+    /// Merges any buffered samples into the summary, and discards the samples no longer needed to
+    /// uphold the error guarantees of its targets.
+    pub fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        // The count already includes the buffered samples, so the rank invariant is computed
+        // against the number of samples merged so far.
+        let mut n = self.count - buffer.len() as u64;
+        let mut merged = Vec::with_capacity(self.samples.len() + buffer.len());
+        let mut existing = std::mem::take(&mut self.samples).into_iter().peekable();
+        let mut rank = 0;
+        for value in buffer.drain(..) {
+            while let Some(sample) = existing.next_if(|sample| sample.value <= value) {
+                rank += sample.width;
+                merged.push(sample);
+            }
+
+            // Samples inserted at either end of the summary have an exactly known rank.
+            let delta = if merged.is_empty() || existing.peek().is_none() {
+                0
+            } else {
+                (self.invariant(rank as f64, n as f64).floor() as u64).saturating_sub(1)
+            };
+            merged.push(Sample { value, width: 1, delta });
+            rank += 1;
+            n += 1;
+        }
+        merged.extend(existing);
+
+        self.samples = merged;
+        self.buffer = buffer;
+        self.merge_samples();
+    }
+
+    /// Gets the minimum value this summary has seen so far.
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    /// Gets the maximum value this summary has seen so far.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// Gets the sum of all samples in this summary.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Whether or not this summary is empty.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Gets the number of samples in this summary.
+    pub fn count(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Gets the estimated size of this summary, in bytes.
+    pub fn estimated_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.targets.capacity() * std::mem::size_of::<(f64, f64)>()
+            + self.samples.capacity() * std::mem::size_of::<Sample>()
+            + self.buffer.capacity() * std::mem::size_of::<f64>()
+    }
+
+    /// Gets the maximum allowed uncertainty, in rank, of a sample at rank `r` out of `n`.
+    ///
+    /// This is the targeted invariant from the CKMS paper: the tightest of the bounds required by
+    /// each target, where samples far from a target quantile are allowed a larger uncertainty.
+    fn invariant(&self, r: f64, n: f64) -> f64 {
+        self.targets
+            .iter()
+            .map(|&(quantile, error)| {
+                if r >= quantile * n {
+                    2.0 * error * r / quantile
+                } else {
+                    2.0 * error * (n - r) / (1.0 - quantile)
+                }
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// Merges adjacent samples whose combined uncertainty is still within the invariant.
+    fn merge_samples(&mut self) {
+        if self.samples.len() < 3 {
+            return;
+        }
+
+        let n = self.count as f64;
+        let mut merged = Vec::with_capacity(self.samples.len());
+        let mut samples = std::mem::take(&mut self.samples).into_iter().rev();
+
+        // The samples are walked from the highest rank down, folding each sample into the one
+        // above it when allowed.  The minimum is never folded away, so it stays exact.
+        let mut current = samples.next().expect("samples should not be empty");
+        let mut rank = self.count - current.width;
+        while let Some(sample) = samples.next() {
+            rank -= sample.width;
+            let is_minimum = samples.len() == 0;
+            if !is_minimum
+                && (sample.width + current.width + current.delta) as f64
+                    <= self.invariant(rank as f64, n)
+            {
+                current.width += sample.width;
+            } else {
+                merged.push(current);
+                current = sample;
+            }
+        }
+        merged.push(current);
+        merged.reverse();
+
+        self.samples = merged;
+    }
+
+    fn quantile_compressed(&self, q: f64) -> f64 {
+        if q == 0.0 {
+            return self.min;
+        }
+        if q == 1.0 {
+            return self.max;
+        }
+
+        let n = self.count as f64;
+        let target = (q * n).ceil();
+        let threshold = target + (self.invariant(target, n) / 2.0).ceil();
+
+        let mut rank = 0;
+        let mut previous = &self.samples[0];
+        for sample in &self.samples[1..] {
+            rank += previous.width;
+            if (rank + sample.width + sample.delta) as f64 > threshold {
+                return previous.value;
+            }
+            previous = sample;
+        }
+
+        previous.value
+    }
+}
+
+impl Default for Ckms {
+    fn default() -> Self {
+        Ckms::with_defaults()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ckms;
+
+    #[test]
+    fn test_basics() {
+        let mut ckms = Ckms::with_defaults();
+        assert!(ckms.is_empty());
+        assert_eq!(ckms.quantile(0.5), None);
+
+        ckms.add(42.0);
+        ckms.add(f64::NAN);
+        assert_eq!(ckms.count(), 1);
+        assert_eq!(ckms.quantile(0.0), Some(42.0));
+        assert_eq!(ckms.quantile(0.5), Some(42.0));
+        assert_eq!(ckms.quantile(1.0), Some(42.0));
+        assert_eq!(ckms.quantile(1.5), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_target() {
+        let _ = Ckms::new(&[(1.0, 0.01)]);
+    }
+
+    #[test]
+    fn test_targets() {
+        let n = 100_000;
+        let targets = [(0.5, 0.05), (0.99, 0.001), (0.999, 0.0001)];
+        let mut ckms = Ckms::new(&targets);
+        for i in 0..n {
+            // Interleave the samples so they're not added in order.
+            ckms.add(((i * 7919) % n) as f64);
+        }
+        ckms.compress();
+
+        assert_eq!(ckms.count(), n);
+        assert_eq!(ckms.min(), 0.0);
+        assert_eq!(ckms.max(), (n - 1) as f64);
+        assert!(ckms.samples.len() < n / 10);
+
+        for &(q, error) in &targets {
+            let estimate = ckms.quantile(q).expect("value should exist");
+            let expected = q * n as f64;
+            assert!(
+                (estimate - expected).abs() <= error * n as f64 + 1.0,
+                "q={} estimate={} expected={}",
+                q,
+                estimate,
+                expected
+            );
+        }
+    }
+}
@@ -20,7 +20,7 @@ use crate::{
 use indexmap::IndexMap;
 use metrics::{
     Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SetRecorderError, SharedString,
-    Unit,
+    Summary, Unit,
 };
 use ordered_float::OrderedFloat;
 
@@ -36,6 +36,7 @@ impl CompositeKeyName {
 }
 
 /// A point-in-time snapshot of all metrics in [`DebuggingRecorder`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Snapshot(Vec<(CompositeKey, Option<Unit>, Option<SharedString>, DebugValue)>);
 
 impl Snapshot {
@@ -58,6 +59,7 @@ impl Snapshot {
 
 /// A point-in-time value for a metric exposing raw values.
 #[derive(Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DebugValue {
     /// Counter.
     Counter(u64),
@@ -209,6 +211,19 @@ impl Recorder for DebuggingRecorder {
 
         self.inner.registry.get_or_create_histogram(key, |h| Histogram::from_arc(h.clone()))
     }
+
+    // There's no separate metric kind for summaries, so they're tracked as histograms, which means
+    // that a summary and a histogram with the same key share the same storage.
+    fn describe_summary(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.describe_histogram(key, unit, description);
+    }
+
+    fn register_summary(&self, key: &Key, _metadata: &Metadata<'_>) -> Summary {
+        let ckey = CompositeKey::new(MetricKind::Histogram, key.clone());
+        self.track_metric(ckey);
+
+        self.inner.registry.get_or_create_histogram(key, |h| Summary::from_arc(h.clone()))
+    }
 }
 
 impl Default for DebuggingRecorder {
@@ -0,0 +1,427 @@
+/// The maximum scale of an [`ExponentialHistogram`], as defined by OpenTelemetry.
+const MAX_SCALE: i32 = 20;
+
+/// The minimum scale of an [`ExponentialHistogram`], as defined by OpenTelemetry.
+const MIN_SCALE: i32 = -10;
+
+/// A contiguous range of buckets in an [`ExponentialHistogram`].
+///
+/// Buckets are identified by their index, with bucket `i` holding the count of values greater than
+/// `base^i` and less than or equal to `base^(i + 1)`, where `base` is determined by the scale of the
+/// histogram.  The counts are stored densely, starting from the bucket at [`offset`], which matches
+/// the representation used by OTLP, while [`iter`] skips empty buckets, which matches the sparse
+/// representation used by Prometheus native histograms.
+///
+/// [`offset`]: ExponentialBuckets::offset
+/// [`iter`]: ExponentialBuckets::iter
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExponentialBuckets {
+    offset: i32,
+    counts: Vec<u64>,
+}
+
+impl ExponentialBuckets {
+    /// Gets the index of the first bucket.
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    /// Gets the counts of each bucket, starting from the bucket at [`offset`](Self::offset).
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns `true` if no values have been counted in any bucket.
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Iterates over the index and count of every non-empty bucket, in order of index.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, u64)> + '_ {
+        let offset = self.offset;
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(move |(i, count)| (offset + i as i32, *count))
+    }
+
+    /// Gets the lowest and highest bucket index, if any buckets exist.
+    fn range(&self) -> Option<(i32, i32)> {
+        if self.counts.is_empty() {
+            None
+        } else {
+            Some((self.offset, self.offset + self.counts.len() as i32 - 1))
+        }
+    }
+
+    fn increment(&mut self, index: i32, count: u64) {
+        match self.range() {
+            None => {
+                self.offset = index;
+                self.counts.push(0);
+            }
+            Some((low, _)) if index < low => {
+                let prepended = (low - index) as usize;
+                self.counts.splice(0..0, std::iter::repeat(0).take(prepended));
+                self.offset = index;
+            }
+            Some((_, high)) if index > high => {
+                self.counts.resize(self.counts.len() + (index - high) as usize, 0);
+            }
+            Some(_) => {}
+        }
+
+        self.counts[(index - self.offset) as usize] += count;
+    }
+
+    /// Merges every `2^change` adjacent buckets into one, as when decreasing the scale by `change`.
+    fn downscale(&mut self, change: u32) {
+        if change == 0 || self.counts.is_empty() {
+            return;
+        }
+
+        let counts = std::mem::take(&mut self.counts);
+        let offset = self.offset;
+        for (i, count) in counts.into_iter().enumerate() {
+            if count > 0 {
+                self.increment((offset + i as i32) >> change, count);
+            }
+        }
+    }
+}
+
+/// A histogram with exponentially sized buckets, as used by OpenTelemetry exponential histograms
+/// and Prometheus native histograms.
+///
+/// Values are counted in buckets whose boundaries are powers of `base = 2^(2^-scale)`, so every
+/// bucket has the same relative width, and the scale sets the resolution of the histogram: at a
+/// scale of `0`, the boundaries are powers of two, and each increment of the scale halves the
+/// relative width of the buckets.  Positive and negative values are counted in separate sets of
+/// buckets, and zeros in a dedicated zero bucket.
+///
+/// The number of buckets used for each sign is limited to a maximum size.  The histogram starts at
+/// its maximum scale, and whenever the recorded values span too many buckets to fit, the scale is
+/// decreased, merging adjacent buckets, until they do.  The resulting buckets can be exported
+/// directly, without having to keep, or re-aggregate, the raw values.
+///
+/// Histograms can be merged with each other, regardless of their scale or maximum size.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExponentialHistogram {
+    max_size: usize,
+    scale: i32,
+    zero_count: u64,
+    positive: ExponentialBuckets,
+    negative: ExponentialBuckets,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl ExponentialHistogram {
+    /// Creates a new [`ExponentialHistogram`] using at most `max_size` buckets for each sign, and
+    /// starting at the given maximum scale.
+    ///
+    /// The maximum size is clamped to a minimum of `2`, and the scale to between `-10` and `20`, as
+    /// defined by OpenTelemetry.  Prometheus native histograms only support scales, which they
+    /// call schemas, between `-4` and `8`.
+    pub fn new(max_size: usize, max_scale: i32) -> ExponentialHistogram {
+        ExponentialHistogram {
+            max_size: max_size.max(2),
+            scale: max_scale.clamp(MIN_SCALE, MAX_SCALE),
+            zero_count: 0,
+            positive: ExponentialBuckets::default(),
+            negative: ExponentialBuckets::default(),
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Creates a new [`ExponentialHistogram`] with a maximum size of `160` buckets and a maximum
+    /// scale of `20`, which are the defaults used by OpenTelemetry.
+    pub fn with_defaults() -> ExponentialHistogram {
+        ExponentialHistogram::new(160, MAX_SCALE)
+    }
+
+    /// Records a value into the histogram.
+    ///
+    /// Values which aren't finite are ignored.
+    pub fn record(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+
+        if value == 0.0 {
+            self.zero_count += 1;
+            return;
+        }
+
+        let index = bucket_index(value.abs(), self.scale);
+        let buckets = if value > 0.0 { &self.positive } else { &self.negative };
+        let change = self.downscale_needed(buckets, index, index);
+        self.downscale(change);
+
+        let index = index >> change;
+        let buckets = if value > 0.0 { &mut self.positive } else { &mut self.negative };
+        buckets.increment(index, 1);
+    }
+
+    /// Merges another histogram into this one.
+    ///
+    /// The merged histogram uses the lower of the two scales, further decreased if needed for the
+    /// merged buckets to fit within the maximum size of this histogram.
+    pub fn merge(&mut self, other: &ExponentialHistogram) {
+        if other.count == 0 {
+            return;
+        }
+
+        let mut other = other.clone();
+        let scale = self.scale.min(other.scale);
+        self.downscale_to(scale);
+        other.downscale_to(scale);
+
+        let mut change = 0;
+        for (ours, theirs) in [(&self.positive, &other.positive), (&self.negative, &other.negative)]
+        {
+            if let Some((low, high)) = theirs.range() {
+                change = change.max(self.downscale_needed(ours, low, high));
+            }
+        }
+        self.downscale(change);
+        other.downscale_to(self.scale);
+
+        for (index, count) in other.positive.iter() {
+            self.positive.increment(index, count);
+        }
+        for (index, count) in other.negative.iter() {
+            self.negative.increment(index, count);
+        }
+
+        self.zero_count += other.zero_count;
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Gets the current scale of the histogram.
+    pub fn scale(&self) -> i32 {
+        self.scale
+    }
+
+    /// Gets the maximum number of buckets used for each sign.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Gets the lower and upper boundaries of the bucket at the given index, at the current scale.
+    ///
+    /// The lower boundary is exclusive, and the upper boundary is inclusive.
+    pub fn bucket_bounds(&self, index: i32) -> (f64, f64) {
+        let base = 2.0f64.powf(2.0f64.powi(-self.scale));
+        (base.powi(index), base.powi(index + 1))
+    }
+
+    /// Gets the buckets counting positive values.
+    pub fn positive(&self) -> &ExponentialBuckets {
+        &self.positive
+    }
+
+    /// Gets the buckets counting negative values.
+    ///
+    /// The buckets count the absolute value of the recorded values, so the boundaries of the
+    /// buckets are the same as for positive values, but negated.
+    pub fn negative(&self) -> &ExponentialBuckets {
+        &self.negative
+    }
+
+    /// Gets the number of values equal to zero.
+    pub fn zero_count(&self) -> u64 {
+        self.zero_count
+    }
+
+    /// Gets the minimum value this histogram has seen so far.
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    /// Gets the maximum value this histogram has seen so far.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// Gets the sum of all values in this histogram.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Whether or not this histogram is empty.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Gets the number of values in this histogram.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Gets the decrease in scale needed for `buckets` to also cover `low` to `high`.
+    fn downscale_needed(&self, buckets: &ExponentialBuckets, low: i32, high: i32) -> u32 {
+        let (mut low, mut high) = match buckets.range() {
+            Some((current_low, current_high)) => (low.min(current_low), high.max(current_high)),
+            None => (low, high),
+        };
+
+        let mut change = 0;
+        while (high - low) as usize >= self.max_size && self.scale - (change as i32) > MIN_SCALE {
+            low >>= 1;
+            high >>= 1;
+            change += 1;
+        }
+        change
+    }
+
+    fn downscale(&mut self, change: u32) {
+        self.positive.downscale(change);
+        self.negative.downscale(change);
+        self.scale -= change as i32;
+    }
+
+    fn downscale_to(&mut self, scale: i32) {
+        self.downscale((self.scale - scale) as u32);
+    }
+}
+
+impl Default for ExponentialHistogram {
+    fn default() -> Self {
+        ExponentialHistogram::with_defaults()
+    }
+}
+
+/// Gets the index of the bucket holding the given positive, finite value, at the given scale.
+fn bucket_index(value: f64, scale: i32) -> i32 {
+    let bits = value.to_bits();
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+    let mantissa = bits & ((1 << 52) - 1);
+
+    // Bucket upper boundaries are inclusive, so exact powers of two belong in the bucket below the
+    // one their exponent would otherwise place them in.
+    let (exponent, exact) = if biased_exponent == 0 {
+        // Subnormal values don't have an implicit leading bit, so fall back to the logarithm.
+        let exponent = value.log2().floor();
+        (exponent as i32, 2.0f64.powf(exponent) == value)
+    } else {
+        (biased_exponent - 1023, mantissa == 0)
+    };
+
+    if scale <= 0 {
+        return (exponent - i32::from(exact)) >> -scale;
+    }
+    if exact {
+        return (exponent << scale) - 1;
+    }
+
+    // Computing the index from the logarithm can be off by one right at a bucket boundary, so the
+    // index is kept within the range covered by the exponent.
+    let index = (value.log2() * f64::from(1 << scale)).ceil() as i32 - 1;
+    index.clamp(exponent << scale, ((exponent + 1) << scale) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bucket_index, ExponentialHistogram};
+
+    #[test]
+    fn test_bucket_index() {
+        // At scale 0, buckets are (1, 2], (2, 4], and so on.
+        assert_eq!(bucket_index(1.0, 0), -1);
+        assert_eq!(bucket_index(1.5, 0), 0);
+        assert_eq!(bucket_index(2.0, 0), 0);
+        assert_eq!(bucket_index(3.0, 0), 1);
+        assert_eq!(bucket_index(0.5, 0), -2);
+
+        // At scale -1, buckets are (1, 4], (4, 16], and so on.
+        assert_eq!(bucket_index(4.0, -1), 0);
+        assert_eq!(bucket_index(5.0, -1), 1);
+
+        // At scale 1, buckets are (1, sqrt(2)], (sqrt(2), 2], and so on.
+        assert_eq!(bucket_index(1.4, 1), 0);
+        assert_eq!(bucket_index(1.5, 1), 1);
+        assert_eq!(bucket_index(2.0, 1), 1);
+        assert_eq!(bucket_index(4.0, 3), 15);
+
+        // Subnormal values.
+        assert_eq!(bucket_index(f64::from_bits(1), 0), -1075);
+    }
+
+    #[test]
+    fn test_record() {
+        let mut histogram = ExponentialHistogram::new(4, 0);
+        for value in [1.5, 2.0, 3.0, 0.0, -3.0, f64::NAN] {
+            histogram.record(value);
+        }
+
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.sum(), 3.5);
+        assert_eq!(histogram.zero_count(), 1);
+        assert_eq!(histogram.scale(), 0);
+        assert_eq!(histogram.positive().offset(), 0);
+        assert_eq!(histogram.positive().counts(), &[2, 1]);
+        assert_eq!(histogram.negative().iter().collect::<Vec<_>>(), vec![(1, 1)]);
+        assert_eq!(histogram.bucket_bounds(1), (2.0, 4.0));
+
+        // Spanning more than four buckets decreases the scale.
+        histogram.record(100.0);
+        assert_eq!(histogram.scale(), -1);
+        assert_eq!(histogram.positive().iter().collect::<Vec<_>>(), vec![(0, 3), (3, 1)]);
+        assert_eq!(histogram.negative().iter().collect::<Vec<_>>(), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_max_size() {
+        let mut histogram = ExponentialHistogram::with_defaults();
+        for i in 1..=100_000 {
+            histogram.record(f64::from(i) / 1000.0);
+        }
+
+        assert_eq!(histogram.count(), 100_000);
+        assert!(histogram.positive().counts().len() <= 160);
+        assert_eq!(histogram.positive().counts().iter().sum::<u64>(), 100_000);
+
+        // The scale is the highest at which every value fits.
+        let scale = histogram.scale();
+        let mut higher = ExponentialHistogram::new(160, scale + 1);
+        higher.record(0.001);
+        higher.record(100.0);
+        assert!(higher.scale() < scale + 1);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut low = ExponentialHistogram::new(160, 8);
+        let mut high = ExponentialHistogram::new(160, 4);
+        for i in 1..=1000 {
+            low.record(f64::from(i));
+            high.record(-f64::from(i) * 1000.0);
+        }
+
+        let mut merged = ExponentialHistogram::with_defaults();
+        merged.merge(&low);
+        merged.merge(&high);
+
+        assert_eq!(merged.count(), 2000);
+        assert_eq!(merged.min(), -1_000_000.0);
+        assert_eq!(merged.max(), 1000.0);
+        assert!(merged.scale() <= low.scale().min(high.scale()));
+        assert_eq!(merged.positive().counts().iter().sum::<u64>(), 1000);
+        assert_eq!(merged.negative().counts().iter().sum::<u64>(), 1000);
+    }
+}
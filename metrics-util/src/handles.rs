@@ -1,8 +1,14 @@
 use crate::AtomicBucket;
-use metrics::HistogramFn;
+use metrics::{HistogramFn, SummaryFn};
 
 impl HistogramFn for AtomicBucket<f64> {
     fn record(&self, value: f64) {
         self.push(value);
     }
 }
+
+impl SummaryFn for AtomicBucket<f64> {
+    fn record(&self, value: f64) {
+        self.push(value);
+    }
+}
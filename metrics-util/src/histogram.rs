@@ -8,6 +8,7 @@
 /// This type is most useful with systems that prefer bucketed data, such as Prometheus'
 /// histogram type, as opposed to its summary type, which deals with quantiles.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Histogram {
     count: u64,
     bounds: Vec<f64>,
@@ -3,6 +3,7 @@ use metrics::Key;
 
 /// A composite key that stores both the metric key and the metric kind.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CompositeKey(MetricKind, Key);
 
 impl CompositeKey {
@@ -7,6 +7,7 @@ use std::ops::BitOr;
 /// - gauges
 /// - histograms
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MetricKind {
     /// Counter type.
     Counter,
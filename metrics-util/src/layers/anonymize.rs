@@ -0,0 +1,317 @@
+use crate::layers::Layer;
+use hmac::{Hmac, Mac};
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString,
+    Summary, Unit,
+};
+use sha2::{Digest, Sha256};
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+const DEFAULT_LENGTH: usize = 16;
+
+/// The hash function applied to label values.
+#[derive(Clone)]
+enum Hasher {
+    /// Plain SHA-256.
+    Sha256,
+
+    /// HMAC-SHA256, keyed with the given secret.
+    HmacSha256(Vec<u8>),
+}
+
+impl Hasher {
+    fn hash(&self, value: &str, length: usize) -> String {
+        match self {
+            Hasher::Sha256 => to_hex(&Sha256::digest(value.as_bytes()), length),
+            Hasher::HmacSha256(secret) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+                    .expect("HMAC can take a key of any size");
+                mac.update(value.as_bytes());
+                to_hex(&mac.finalize().into_bytes(), length)
+            }
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8], length: usize) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        hex.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        hex.push(HEX_DIGITS[(byte & 0xf) as usize] as char);
+    }
+    hex.truncate(length);
+    hex
+}
+
+/// Hashes the values of specific labels on every metric key.
+///
+/// More information on the behavior of the layer can be found in [`AnonymizeLayer`].
+pub struct Anonymize<R> {
+    label_keys: Vec<SharedString>,
+    hasher: Hasher,
+    length: usize,
+    inner: R,
+}
+
+impl<R> Anonymize<R> {
+    fn anonymize_key(&self, key: &Key) -> Key {
+        let needs_hashing =
+            key.labels().any(|label| self.label_keys.iter().any(|k| k.as_ref() == label.key()));
+        if !needs_hashing {
+            return key.clone();
+        }
+
+        let labels = key
+            .labels()
+            .map(|label| {
+                if self.label_keys.iter().any(|k| k.as_ref() == label.key()) {
+                    let hashed = self.hasher.hash(label.value(), self.length);
+                    Label::new(label.key().to_owned(), hashed)
+                } else {
+                    label.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Key::from_parts(key.name().to_owned(), labels)
+    }
+}
+
+impl<R: Recorder> Recorder for Anonymize<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.anonymize_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.anonymize_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.anonymize_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.anonymize_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for hashing the values of specific labels on every metric key.
+///
+/// Values of the configured labels are replaced with the lowercase hexadecimal digest of the
+/// original value, truncated to 16 characters by default.  As equal values always produce equal
+/// digests, the number of distinct values of a label is preserved, which allows analyzing the
+/// cardinality of sensitive labels, such as user IDs or IP addresses, without exposing their raw
+/// values to exporters.
+///
+/// Plain SHA-256 digests of values drawn from a small or predictable set can be reversed by simply
+/// hashing every candidate value.  When that's a concern, use [`AnonymizeLayer::hmac_sha256`] with
+/// a secret key instead.
+pub struct AnonymizeLayer {
+    label_keys: Vec<SharedString>,
+    hasher: Hasher,
+    length: usize,
+}
+
+impl AnonymizeLayer {
+    /// Creates a new `AnonymizeLayer` which hashes label values with SHA-256.
+    pub fn sha256() -> AnonymizeLayer {
+        AnonymizeLayer::with_hasher(Hasher::Sha256)
+    }
+
+    /// Creates a new `AnonymizeLayer` which hashes label values with HMAC-SHA256, using the given
+    /// secret as the key.
+    pub fn hmac_sha256<S: AsRef<[u8]>>(secret: S) -> AnonymizeLayer {
+        AnonymizeLayer::with_hasher(Hasher::HmacSha256(secret.as_ref().to_vec()))
+    }
+
+    fn with_hasher(hasher: Hasher) -> AnonymizeLayer {
+        AnonymizeLayer { label_keys: Vec::new(), hasher, length: DEFAULT_LENGTH }
+    }
+
+    /// Hashes the value of any label with the given key.
+    pub fn label<K: Into<SharedString>>(&mut self, key: K) -> &mut AnonymizeLayer {
+        let key = key.into();
+        if !self.label_keys.contains(&key) {
+            self.label_keys.push(key);
+        }
+        self
+    }
+
+    /// Sets the number of hexadecimal characters of the digest to keep.
+    ///
+    /// Defaults to 16.  Values larger than the full length of the digest, 64 characters, keep the
+    /// full digest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `length` is zero.
+    pub fn length(&mut self, length: usize) -> &mut AnonymizeLayer {
+        assert!(length > 0, "digest length must be greater than zero");
+        self.length = length;
+        self
+    }
+}
+
+impl<R> Layer<R> for AnonymizeLayer {
+    type Output = Anonymize<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Anonymize {
+            label_keys: self.label_keys.clone(),
+            hasher: self.hasher.clone(),
+            length: self.length,
+            inner,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnonymizeLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    fn inputs() -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("user_id", "alice"), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("method", "GET")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("user_id", "alice")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ]
+    }
+
+    fn expectations(hashed: &'static str) -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("user_id", hashed), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("method", "GET")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("user_id", hashed)]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_sha256() {
+        let recorder = MockBasicRecorder::from_operations(expectations("2bd806c97f0e00af"));
+        let mut layer = AnonymizeLayer::sha256();
+        layer.label("user_id");
+        let anonymize = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&anonymize);
+        }
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        let recorder = MockBasicRecorder::from_operations(expectations("4360c67bc8102511"));
+        let mut layer = AnonymizeLayer::hmac_sha256("secret");
+        layer.label("user_id");
+        let anonymize = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&anonymize);
+        }
+    }
+
+    #[test]
+    fn test_length() {
+        let recorder = MockBasicRecorder::from_operations(expectations("2bd806c9"));
+        let mut layer = AnonymizeLayer::sha256();
+        layer.label("user_id").length(8);
+        let anonymize = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&anonymize);
+        }
+
+        // Lengths beyond that of the full digest keep the full digest.
+        let recorder = MockBasicRecorder::from_operations(expectations(
+            "2bd806c97f0e00af1a1fc3328fa763a9269723c8db8fac4f93af71db186d6e90",
+        ));
+        let mut layer = AnonymizeLayer::sha256();
+        layer.label("user_id").length(128);
+        let anonymize = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&anonymize);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use crate::layers::Layer;
+use crate::{CacheStats, HandleCache};
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
+
+/// Caches the handles returned by the inner recorder.
+///
+/// More information on the behavior of the layer can be found in [`CacheLayer`].
+pub struct Cache<R> {
+    inner: R,
+    counters: HandleCache<Counter>,
+    gauges: HandleCache<Gauge>,
+    histograms: HandleCache<Histogram>,
+    summaries: HandleCache<Summary>,
+}
+
+impl<R> Cache<R> {
+    /// Gets the number of handles currently cached, across all metric kinds.
+    pub fn len(&self) -> usize {
+        self.counters.len() + self.gauges.len() + self.histograms.len() + self.summaries.len()
+    }
+
+    /// Returns `true` if no handles are currently cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the statistics of the lookups made in the cache so far, across all metric kinds.
+    pub fn stats(&self) -> CacheStats {
+        self.counters
+            .stats()
+            .merge(self.gauges.stats())
+            .merge(self.histograms.stats())
+            .merge(self.summaries.stats())
+    }
+}
+
+impl<R: Recorder> Recorder for Cache<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.counters.get_or_replace_with(key, Counter::is_expired, || {
+            self.inner.register_counter(key, metadata)
+        })
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        self.gauges.get_or_replace_with(key, Gauge::is_expired, || {
+            self.inner.register_gauge(key, metadata)
+        })
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        self.histograms.get_or_replace_with(key, Histogram::is_expired, || {
+            self.inner.register_histogram(key, metadata)
+        })
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        // Summaries don't report whether they've expired, so they're cached for good.
+        self.summaries.get_or_insert_with(key, || self.inner.register_summary(key, metadata))
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for caching the handles returned by the inner recorder.
+///
+/// The first time a key is registered, the registration is passed through to the inner recorder,
+/// and the handle it returns is cached.  Subsequent registrations of the same key return the cached
+/// handle without calling the inner recorder at all, which avoids the cost of looking up the key
+/// in the inner recorder when metrics with dynamic labels are emitted in hot code paths.
+///
+/// As registrations of cached keys never reach the inner recorder, the metadata given for them is
+/// ignored, and any layers wrapped by this one only see the first registration of each key.  Once a
+/// cached counter, gauge or histogram has expired, such as after an exporter removed it for being
+/// idle, registering its key again passes through to the inner recorder, and caches the handle it
+/// returns instead.
+///
+/// By default, the cache is unbounded.  To cap memory usage, a maximum number of handles can be set
+/// via [`CacheLayer::lru`], in which case the least recently used handle is evicted whenever the
+/// cache is full.  Evicted handles remain valid, and registering their key again simply caches the
+/// handle returned by the inner recorder once more.
+#[derive(Default)]
+pub struct CacheLayer {
+    capacity: Option<usize>,
+}
+
+impl CacheLayer {
+    /// Creates a new, unbounded `CacheLayer`.
+    pub fn new() -> CacheLayer {
+        CacheLayer { capacity: None }
+    }
+
+    /// Creates a new `CacheLayer` which caches up to `capacity` handles for each metric kind,
+    /// evicting the least recently used handle when full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn lru(capacity: usize) -> CacheLayer {
+        assert!(capacity > 0, "capacity must be greater than zero");
+        CacheLayer { capacity: Some(capacity) }
+    }
+}
+
+impl<R> Layer<R> for CacheLayer {
+    type Output = Cache<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Cache {
+            inner,
+            counters: handle_cache(self.capacity),
+            gauges: handle_cache(self.capacity),
+            histograms: handle_cache(self.capacity),
+            summaries: handle_cache(self.capacity),
+        }
+    }
+}
+
+fn handle_cache<H: Clone>(capacity: Option<usize>) -> HandleCache<H> {
+    match capacity {
+        None => HandleCache::unbounded(),
+        Some(capacity) => HandleCache::lru(capacity),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CacheLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, Label};
+    use mockall::predicate;
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    fn key(user: &'static str) -> Key {
+        Key::from_parts("counter_key", vec![Label::new("user", user)])
+    }
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::RegisterCounter(key("a"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterCounter(key("a"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterCounter(key("b"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::RegisterCounter(key("a"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterCounter(key("b"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let cache = CacheLayer::new().layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&cache);
+        }
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let inputs = vec![
+            RecorderOperation::RegisterCounter(key("a"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterCounter(key("b"), Counter::noop(), &METADATA),
+            // Touching `a` makes `b` the least recently used key.
+            RecorderOperation::RegisterCounter(key("a"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterCounter(key("c"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterCounter(key("a"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterCounter(key("c"), Counter::noop(), &METADATA),
+        ];
+
+        let mut mock = MockBasicRecorder::from_operations(vec![
+            RecorderOperation::RegisterCounter(key("a"), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterCounter(key("c"), Counter::noop(), &METADATA),
+        ]);
+        // `b` is registered twice: once initially, and once more after being evicted.
+        let evicted = key("b");
+        mock.expect_register_counter()
+            .times(2)
+            .with(predicate::function(move |k: &Key| k == &evicted), predicate::always())
+            .return_const(Counter::noop());
+        let cache = CacheLayer::lru(2).layer(mock);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&cache);
+        }
+        RecorderOperation::RegisterCounter(key("b"), Counter::noop(), &METADATA)
+            .apply_to_recorder(&cache);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats().evictions(), 2);
+    }
+
+    #[cfg(feature = "recency")]
+    #[test]
+    fn test_expired_handles() {
+        use crate::registry::{GenerationalAtomicStorage, Registry};
+        use metrics::{KeyName, Metadata, Recorder, SharedString, Unit};
+        use std::sync::atomic::Ordering;
+
+        struct RegistryRecorder(Registry<Key, GenerationalAtomicStorage>);
+
+        impl Recorder for RegistryRecorder {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+                self.0.get_or_create_counter(key, |c| c.clone().into())
+            }
+
+            fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+                self.0.get_or_create_gauge(key, |g| g.clone().into())
+            }
+
+            fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+                self.0.get_or_create_histogram(key, |h| h.clone().into())
+            }
+        }
+
+        let cache = CacheLayer::new()
+            .layer(RegistryRecorder(Registry::new(GenerationalAtomicStorage::atomic())));
+        let key = key("a");
+
+        cache.register_counter(&key, &METADATA).increment(1);
+        assert!(cache.inner.0.delete_counter(&key));
+
+        // The cached handle expired along with the metric, so the key is registered again.
+        let counter = cache.register_counter(&key, &METADATA);
+        assert!(!counter.is_expired());
+        counter.increment(2);
+        let stored = cache.inner.0.get_counter(&key).expect("counter should be registered again");
+        assert_eq!(stored.get_inner().load(Ordering::Relaxed), 2);
+        assert_eq!(cache.stats().misses(), 2);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
+
+/// Puts the labels of every metric key into canonical form.
+///
+/// More information on the behavior of the layer can be found in [`CanonicalizeLayer`].
+pub struct Canonicalize<R> {
+    inner: R,
+}
+
+impl<R: Recorder> Recorder for Canonicalize<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        if key.is_canonical() {
+            return self.inner.register_counter(key, metadata);
+        }
+        self.inner.register_counter(&key.canonicalize(), metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        if key.is_canonical() {
+            return self.inner.register_gauge(key, metadata);
+        }
+        self.inner.register_gauge(&key.canonicalize(), metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        if key.is_canonical() {
+            return self.inner.register_histogram(key, metadata);
+        }
+        self.inner.register_histogram(&key.canonicalize(), metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        if key.is_canonical() {
+            return self.inner.register_summary(key, metadata);
+        }
+        self.inner.register_summary(&key.canonicalize(), metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for putting the labels of every metric key into canonical form.
+///
+/// Keys are compared, and hashed, using their labels in the order they were given, so the same
+/// metric emitted with its labels in a different order at different callsites ends up as two
+/// separate series.  This layer sorts the labels of every key by label key before passing it to the
+/// inner recorder, and drops all but the last label for any label key given more than once, as
+/// described by [`Key::canonicalize`].
+///
+/// Keys whose labels are already in canonical form are passed through without being copied.
+#[derive(Default)]
+pub struct CanonicalizeLayer;
+
+impl<R> Layer<R> for CanonicalizeLayer {
+    type Output = Canonicalize<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Canonicalize { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CanonicalizeLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Key, Label};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("method", "GET"), Label::new("code", "200")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts(
+                    "gauge_key",
+                    vec![
+                        Label::new("region", "eu"),
+                        Label::new("host", "a"),
+                        Label::new("region", "us"),
+                    ],
+                ),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("code", "200"), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts(
+                    "gauge_key",
+                    vec![Label::new("host", "a"), Label::new("region", "us")],
+                ),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let canonicalize = CanonicalizeLayer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&canonicalize);
+        }
+    }
+}
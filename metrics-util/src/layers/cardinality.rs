@@ -0,0 +1,457 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Level, Metadata, Recorder,
+    SharedString, Summary, Unit,
+};
+
+static METADATA: Metadata<'static> =
+    Metadata::new(module_path!(), Level::INFO, Some(module_path!()));
+
+const DEFAULT_DROPPED_SERIES_NAME: &str = "cardinality_limit_dropped_series";
+const DEFAULT_MAX_NAMES: usize = 10_000;
+
+/// The number of rejected series remembered for each metric name, to count each of them once.
+const REJECTED_SLOTS: usize = 1024;
+
+/// What to do with a new series once a metric has reached its cardinality limit.
+#[derive(Clone, Debug)]
+enum OverflowPolicy {
+    /// Drops the series, handing back a no-op handle.
+    Drop,
+
+    /// Routes the series to an overflow key with the given labels.
+    Overflow(Vec<Label>),
+}
+
+/// The series seen for a single metric name.
+#[derive(Default)]
+struct Series {
+    admitted: HashSet<Key>,
+    /// Hashes of recently rejected keys, each in the slot picked by its hash, which is allocated
+    /// once the first key is rejected.
+    rejected: Vec<u64>,
+}
+
+impl Series {
+    /// Remembers the given key as rejected, returning whether it wasn't remembered already.
+    ///
+    /// As only a fixed number of rejected keys are remembered, a key may be forgotten once another
+    /// key lands in its slot, in which case it's reported as new again.
+    fn reject(&mut self, key: &Key) -> bool {
+        if self.rejected.is_empty() {
+            self.rejected = vec![0; REJECTED_SLOTS];
+        }
+        let hash = key.get_hash();
+        let slot = &mut self.rejected[(hash % REJECTED_SLOTS as u64) as usize];
+        let new = *slot != hash;
+        *slot = hash;
+        new
+    }
+}
+
+/// Whether or not a series is within the cardinality limit of its metric name.
+#[derive(Debug, PartialEq, Eq)]
+enum Admission {
+    /// The series is within the limit.
+    Admitted,
+
+    /// The series is beyond the limit, and has not been seen before.
+    NewlyRejected,
+
+    /// The series is beyond the limit, and has already been seen.
+    Rejected,
+
+    /// The metric name is new, and the maximum number of metric names are already tracked.
+    Untracked,
+}
+
+/// Limits the number of distinct label sets registered for each metric name.
+///
+/// Once a metric name has seen the configured number of distinct series, any series that has not
+/// been seen before is either dropped or routed to a single overflow series for that metric name,
+/// depending on how [`CardinalityLimitLayer`] was configured. Series which were registered before
+/// the limit was reached continue to work as normal.
+///
+/// The first time a distinct series is dropped or routed to the overflow series, a meta-counter is
+/// incremented on the inner recorder, such that it counts the number of series beyond the limit
+/// rather than the number of times they were registered. By default, this counter is named
+/// `cardinality_limit_dropped_series`, with a label -- `metric` -- holding the name of the metric
+/// whose series was limited.  Only the last 1,024 distinct series beyond the limit are remembered
+/// for each metric name, so the count is approximate once more series than that are limited.
+///
+/// The number of metric names tracked is capped as well, defaulting to 10,000, which can be
+/// changed via [`CardinalityLimitLayer::max_names`].  Once the cap is reached, every series of any
+/// other metric name is dropped, regardless of the overflow policy, and counted by the meta-counter
+/// every time it's registered, without a `metric` label.  This bounds the memory used by the layer,
+/// and the number of series it registers, even when metric names are generated without bound.
+pub struct CardinalityLimit<R> {
+    inner: R,
+    max_series: usize,
+    max_names: usize,
+    policy: OverflowPolicy,
+    dropped_series_name: SharedString,
+    series: RwLock<HashMap<KeyName, Series>>,
+}
+
+impl<R> CardinalityLimit<R> {
+    #[allow(clippy::mutable_key_type)]
+    fn admit(&self, key: &Key) -> Admission {
+        // Series are typically registered again and again long after they were first admitted, so
+        // check for those under the read lock before contending on the write lock.
+        {
+            let series = self.series.read().unwrap_or_else(|e| e.into_inner());
+            if series.get(key.name()).map_or(false, |s| s.admitted.contains(key)) {
+                return Admission::Admitted;
+            }
+        }
+
+        let mut series = self.series.write().unwrap_or_else(|e| e.into_inner());
+        if !series.contains_key(key.name()) && series.len() >= self.max_names {
+            return Admission::Untracked;
+        }
+        let entry = series.entry(KeyName::from(key.name().to_owned())).or_default();
+        if entry.admitted.contains(key) {
+            Admission::Admitted
+        } else if entry.admitted.len() < self.max_series {
+            entry.admitted.insert(key.clone());
+            Admission::Admitted
+        } else if entry.reject(key) {
+            Admission::NewlyRejected
+        } else {
+            Admission::Rejected
+        }
+    }
+}
+
+impl<R: Recorder> CardinalityLimit<R> {
+    fn limit_key(&self, key: &Key) -> Option<Key> {
+        match self.admit(key) {
+            Admission::Admitted => return Some(key.clone()),
+            Admission::NewlyRejected => {
+                let dropped_key = Key::from_parts(
+                    self.dropped_series_name.clone(),
+                    vec![Label::new("metric", key.name().to_owned())],
+                );
+                self.inner.register_counter(&dropped_key, &METADATA).increment(1);
+            }
+            Admission::Rejected => {}
+            Admission::Untracked => {
+                let dropped_key = Key::from_name(self.dropped_series_name.clone());
+                self.inner.register_counter(&dropped_key, &METADATA).increment(1);
+                return None;
+            }
+        }
+
+        match &self.policy {
+            OverflowPolicy::Drop => None,
+            OverflowPolicy::Overflow(labels) => {
+                Some(Key::from_parts(key.name().to_owned(), labels.clone()))
+            }
+        }
+    }
+}
+
+impl<R: Recorder> Recorder for CardinalityLimit<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        match self.limit_key(key) {
+            Some(new_key) => self.inner.register_counter(&new_key, metadata),
+            None => Counter::noop(),
+        }
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        match self.limit_key(key) {
+            Some(new_key) => self.inner.register_gauge(&new_key, metadata),
+            None => Gauge::noop(),
+        }
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        match self.limit_key(key) {
+            Some(new_key) => self.inner.register_histogram(&new_key, metadata),
+            None => Histogram::noop(),
+        }
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        match self.limit_key(key) {
+            Some(new_key) => self.inner.register_summary(&new_key, metadata),
+            None => Summary::noop(),
+        }
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for limiting the number of distinct series registered for each metric name.
+///
+/// More information on the behavior of the layer can be found in [`CardinalityLimit`].
+pub struct CardinalityLimitLayer {
+    max_series: usize,
+    max_names: usize,
+    policy: OverflowPolicy,
+    dropped_series_name: SharedString,
+}
+
+impl CardinalityLimitLayer {
+    /// Creates a new `CardinalityLimitLayer` which allows up to `max_series` distinct series for
+    /// each metric name.
+    ///
+    /// By default, series beyond the limit are dropped.
+    pub fn new(max_series: usize) -> CardinalityLimitLayer {
+        CardinalityLimitLayer {
+            max_series,
+            max_names: DEFAULT_MAX_NAMES,
+            policy: OverflowPolicy::Drop,
+            dropped_series_name: DEFAULT_DROPPED_SERIES_NAME.into(),
+        }
+    }
+
+    /// Drops any series beyond the limit.
+    ///
+    /// This is the default behavior.
+    pub fn drop_overflow(&mut self) -> &mut CardinalityLimitLayer {
+        self.policy = OverflowPolicy::Drop;
+        self
+    }
+
+    /// Routes any series beyond the limit to an overflow series.
+    ///
+    /// The overflow series has the same name as the original series, with its labels replaced by
+    /// the single label given here, such as `overflow="true"`.
+    pub fn overflow_label<K, V>(&mut self, key: K, value: V) -> &mut CardinalityLimitLayer
+    where
+        K: Into<SharedString>,
+        V: Into<SharedString>,
+    {
+        self.policy = OverflowPolicy::Overflow(vec![Label::new(key, value)]);
+        self
+    }
+
+    /// Sets the maximum number of metric names to track.
+    ///
+    /// Once this many metric names are tracked, every series of any other metric name is dropped.
+    ///
+    /// Defaults to 10,000.
+    pub fn max_names(&mut self, max_names: usize) -> &mut CardinalityLimitLayer {
+        self.max_names = max_names;
+        self
+    }
+
+    /// Sets the name of the meta-counter which tracks the number of limited series.
+    ///
+    /// Defaults to `cardinality_limit_dropped_series`.
+    pub fn dropped_series_name<N: Into<SharedString>>(
+        &mut self,
+        name: N,
+    ) -> &mut CardinalityLimitLayer {
+        self.dropped_series_name = name.into();
+        self
+    }
+}
+
+impl<R> Layer<R> for CardinalityLimitLayer {
+    type Output = CardinalityLimit<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        CardinalityLimit {
+            inner,
+            max_series: self.max_series,
+            max_names: self.max_names,
+            policy: self.policy.clone(),
+            dropped_series_name: self.dropped_series_name.clone(),
+            series: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Admission, CardinalityLimitLayer};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, Label};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_drop_overflow() {
+        let inputs = vec![
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "a")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "b")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "c")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "c")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("user", "c")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        // The dropped series is only counted the first time it's registered.
+        let expectations = vec![
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "a")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "b")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "cardinality_limit_dropped_series",
+                    vec![Label::new("metric", "counter_key")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("user", "c")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let layer = CardinalityLimitLayer::new(2);
+        let limited = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&limited);
+        }
+    }
+
+    #[test]
+    fn test_overflow_label() {
+        let inputs = vec![
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("user", "a")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("user", "b")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("user", "a")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("limited", vec![Label::new("metric", "histogram_key")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("overflow", "true")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = CardinalityLimitLayer::new(1);
+        layer.overflow_label("overflow", "true").dropped_series_name("limited");
+        let limited = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&limited);
+        }
+    }
+
+    #[test]
+    fn test_existing_series_unaffected() {
+        let layer = CardinalityLimitLayer::new(1);
+        let limited = layer.layer(());
+
+        let first = Key::from_parts("key", vec![Label::new("user", "a")]);
+        let second = Key::from_parts("key", vec![Label::new("user", "b")]);
+
+        assert_eq!(limited.admit(&first), Admission::Admitted);
+        assert_eq!(limited.admit(&second), Admission::NewlyRejected);
+        assert_eq!(limited.admit(&second), Admission::Rejected);
+        assert_eq!(limited.admit(&first), Admission::Admitted);
+        assert_eq!(limited.admit(&Key::from_name("other_key")), Admission::Admitted);
+    }
+
+    #[test]
+    fn test_bounded_state() {
+        let mut layer = CardinalityLimitLayer::new(1);
+        layer.max_names(2);
+        let limited = layer.layer(());
+
+        let key = |name: &'static str, user: String| {
+            Key::from_parts(name, vec![Label::new("user", user)])
+        };
+
+        assert_eq!(limited.admit(&key("a", "0".into())), Admission::Admitted);
+        assert_eq!(limited.admit(&Key::from_name("b")), Admission::Admitted);
+        assert_eq!(limited.admit(&Key::from_name("c")), Admission::Untracked);
+        assert_eq!(limited.series.read().unwrap().len(), 2);
+
+        // Rejected series only ever take up a fixed number of slots.
+        for user in 1..10_000 {
+            assert_ne!(limited.admit(&key("a", user.to_string())), Admission::Admitted);
+        }
+        let series = limited.series.read().unwrap();
+        assert_eq!(series.get("a").unwrap().rejected.len(), super::REJECTED_SLOTS);
+    }
+}
@@ -0,0 +1,505 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, GaugeCallback, GaugeFn, Histogram, HistogramFn, Key, KeyName,
+    Label, Level, Metadata, Recorder, SharedString, Summary, SummaryFn, Unit,
+};
+
+static METADATA: Metadata<'static> =
+    Metadata::new(module_path!(), Level::INFO, Some(module_path!()));
+
+const DEFAULT_REJECTED_SAMPLES_NAME: &str = "clamp_rejected_samples";
+
+struct ValidatedGauge {
+    inner: Gauge,
+    rejected: Counter,
+}
+
+impl ValidatedGauge {
+    fn validate(&self, value: f64) -> Option<f64> {
+        if value.is_finite() {
+            Some(value)
+        } else {
+            self.rejected.increment(1);
+            None
+        }
+    }
+}
+
+impl GaugeFn for ValidatedGauge {
+    fn increment(&self, value: f64) {
+        if let Some(value) = self.validate(value) {
+            self.inner.increment(value);
+        }
+    }
+
+    fn decrement(&self, value: f64) {
+        if let Some(value) = self.validate(value) {
+            self.inner.decrement(value);
+        }
+    }
+
+    fn set(&self, value: f64) {
+        if let Some(value) = self.validate(value) {
+            self.inner.set(value);
+        }
+    }
+
+    fn set_max(&self, value: f64) {
+        if let Some(value) = self.validate(value) {
+            self.inner.set_max(value);
+        }
+    }
+
+    fn set_min(&self, value: f64) {
+        if let Some(value) = self.validate(value) {
+            self.inner.set_min(value);
+        }
+    }
+
+    fn set_callback(&self, callback: GaugeCallback) {
+        // The callback has to produce a value whenever it's invoked, so non-finite values are
+        // replaced by the last finite value it produced, or zero if it hasn't produced one yet.
+        let rejected = self.rejected.clone();
+        let last_value = AtomicU64::new(0.0f64.to_bits());
+        self.inner.set_callback(move || {
+            let value = callback.value();
+            if value.is_finite() {
+                last_value.store(value.to_bits(), Ordering::Relaxed);
+                value
+            } else {
+                rejected.increment(1);
+                f64::from_bits(last_value.load(Ordering::Relaxed))
+            }
+        });
+    }
+
+    fn is_expired(&self) -> bool {
+        self.inner.is_expired()
+    }
+}
+
+struct ValidatedHistogram<H> {
+    inner: H,
+    rejected: Counter,
+    range: Option<(f64, f64)>,
+    clamp: bool,
+}
+
+impl<H> ValidatedHistogram<H> {
+    fn validate(&self, value: f64) -> Option<f64> {
+        if value.is_nan() {
+            self.rejected.increment(1);
+            return None;
+        }
+
+        match self.range {
+            Some((min, max)) if value < min || value > max => {
+                if !self.clamp {
+                    self.rejected.increment(1);
+                    return None;
+                }
+                Some(value.clamp(min, max))
+            }
+            Some(_) => Some(value),
+            None if value.is_infinite() => {
+                self.rejected.increment(1);
+                None
+            }
+            None => Some(value),
+        }
+    }
+}
+
+impl HistogramFn for ValidatedHistogram<Histogram> {
+    fn record(&self, value: f64) {
+        if let Some(value) = self.validate(value) {
+            self.inner.record(value);
+        }
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        if let Some(value) = self.validate(value) {
+            self.inner.record_with_exemplar(value, exemplar.iter());
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.inner.is_expired()
+    }
+}
+
+impl SummaryFn for ValidatedHistogram<Summary> {
+    fn record(&self, value: f64) {
+        if let Some(value) = self.validate(value) {
+            self.inner.record(value);
+        }
+    }
+}
+
+/// Validates the values written to gauges and histograms.
+///
+/// More information on the behavior of the layer can be found in [`ClampLayer`].
+pub struct Clamp<R> {
+    inner: R,
+    histogram_range: Option<(f64, f64)>,
+    clamp: bool,
+    rejected_samples_name: SharedString,
+}
+
+impl<R: Recorder> Clamp<R> {
+    fn rejected_counter(&self, key: &Key) -> Counter {
+        let rejected_key = Key::from_parts(
+            self.rejected_samples_name.clone(),
+            vec![Label::new("metric", key.name().to_owned())],
+        );
+        self.inner.register_counter(&rejected_key, &METADATA)
+    }
+}
+
+impl<R: Recorder> Recorder for Clamp<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let gauge = ValidatedGauge {
+            inner: self.inner.register_gauge(key, metadata),
+            rejected: self.rejected_counter(key),
+        };
+        Gauge::from_arc(Arc::new(gauge))
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let histogram = ValidatedHistogram {
+            inner: self.inner.register_histogram(key, metadata),
+            rejected: self.rejected_counter(key),
+            range: self.histogram_range,
+            clamp: self.clamp,
+        };
+        Histogram::from_arc(Arc::new(histogram))
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let summary = ValidatedHistogram {
+            inner: self.inner.register_summary(key, metadata),
+            rejected: self.rejected_counter(key),
+            range: self.histogram_range,
+            clamp: self.clamp,
+        };
+        Summary::from_arc(Arc::new(summary))
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for validating the values written to gauges and histograms.
+///
+/// Pathological values are rejected before they reach the inner recorder:
+///
+/// - gauges reject any non-finite value (NaN, or positive/negative infinity), whether it's being
+///   used to increment, decrement, or set the gauge, while gauges backed by a callback report the
+///   last finite value of the callback, or zero, in place of a non-finite one
+/// - histograms and summaries reject NaN, and either reject or clamp values outside of the
+///   configured range (infinite values are rejected when no range is configured)
+///
+/// Counters are passed through untouched, as their values can only ever be non-negative integers.
+///
+/// Every rejected value increments a meta-counter on the inner recorder.  By default, this counter
+/// is named `clamp_rejected_samples`, with a label -- `metric` -- holding the name of the metric
+/// whose value was rejected.  The meta-counter is registered alongside every gauge and histogram.
+pub struct ClampLayer {
+    histogram_range: Option<(f64, f64)>,
+    clamp: bool,
+    rejected_samples_name: SharedString,
+}
+
+impl ClampLayer {
+    /// Sets the range of values allowed for histograms.
+    ///
+    /// Values outside of this range are rejected, or clamped to the range if
+    /// [`clamp_out_of_range`][Self::clamp_out_of_range] is enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max`, or if either value is NaN.
+    pub fn histogram_range(&mut self, min: f64, max: f64) -> &mut ClampLayer {
+        assert!(min <= max, "histogram range minimum must not be greater than maximum");
+        self.histogram_range = Some((min, max));
+        self
+    }
+
+    /// Sets whether histogram values outside of the configured range are clamped.
+    ///
+    /// Defaults to `false` i.e. out-of-range values are rejected.
+    pub fn clamp_out_of_range(&mut self, clamp: bool) -> &mut ClampLayer {
+        self.clamp = clamp;
+        self
+    }
+
+    /// Sets the name of the meta-counter which tracks the number of rejected values.
+    ///
+    /// Defaults to `clamp_rejected_samples`.
+    pub fn rejected_samples_name<N: Into<SharedString>>(&mut self, name: N) -> &mut ClampLayer {
+        self.rejected_samples_name = name.into();
+        self
+    }
+}
+
+impl Default for ClampLayer {
+    fn default() -> Self {
+        ClampLayer {
+            histogram_range: None,
+            clamp: false,
+            rejected_samples_name: DEFAULT_REJECTED_SAMPLES_NAME.into(),
+        }
+    }
+}
+
+impl<R> Layer<R> for ClampLayer {
+    type Output = Clamp<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Clamp {
+            inner,
+            histogram_range: self.histogram_range,
+            clamp: self.clamp,
+            rejected_samples_name: self.rejected_samples_name.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    };
+
+    use super::ClampLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{
+        Counter, CounterFn, Gauge, GaugeCallback, GaugeFn, Histogram, HistogramFn, Key, Label,
+        Recorder,
+    };
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[derive(Default)]
+    struct CapturingHistogram(Mutex<Vec<f64>>, Mutex<Vec<Vec<Label>>>);
+
+    impl HistogramFn for CapturingHistogram {
+        fn record(&self, value: f64) {
+            self.0.lock().unwrap().push(value);
+        }
+
+        fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+            self.record(value);
+            self.1.lock().unwrap().push(exemplar.to_vec());
+        }
+    }
+
+    #[derive(Default)]
+    struct CallbackGauge(Mutex<Option<GaugeCallback>>);
+
+    impl CallbackGauge {
+        fn value(&self) -> f64 {
+            self.0.lock().unwrap().as_ref().expect("callback should be set").value()
+        }
+    }
+
+    impl GaugeFn for CallbackGauge {
+        fn increment(&self, _value: f64) {}
+
+        fn decrement(&self, _value: f64) {}
+
+        fn set(&self, _value: f64) {}
+
+        fn set_callback(&self, callback: GaugeCallback) {
+            *self.0.lock().unwrap() = Some(callback);
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingCounter(AtomicU64);
+
+    impl CounterFn for CountingCounter {
+        fn increment(&self, value: u64) {
+            self.0.fetch_add(value, Ordering::Relaxed);
+        }
+
+        fn absolute(&self, value: u64) {
+            self.0.store(value, Ordering::Relaxed);
+        }
+    }
+
+    fn rejected_key(name: &'static str) -> Key {
+        Key::from_parts("clamp_rejected_samples", vec![Label::new("metric", name)])
+    }
+
+    #[test]
+    fn test_gauge_rejects_non_finite() {
+        let rejected = Arc::new(CountingCounter::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_gauge(&mut recorder, "gauge_key".into(), Gauge::noop());
+        expect_register_counter(
+            &mut recorder,
+            rejected_key("gauge_key"),
+            Counter::from_arc(Arc::clone(&rejected)),
+        );
+
+        let clamp = ClampLayer::default().layer(recorder);
+        let gauge = clamp.register_gauge(&"gauge_key".into(), &METADATA);
+        gauge.set(1.0);
+        gauge.set(f64::NAN);
+        gauge.increment(f64::INFINITY);
+        gauge.decrement(f64::NEG_INFINITY);
+
+        assert_eq!(rejected.0.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_gauge_callback_rejects_non_finite() {
+        let rejected = Arc::new(CountingCounter::default());
+        let captured = Arc::new(CallbackGauge::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_gauge(
+            &mut recorder,
+            "gauge_key".into(),
+            Gauge::from_arc(Arc::clone(&captured)),
+        );
+        expect_register_counter(
+            &mut recorder,
+            rejected_key("gauge_key"),
+            Counter::from_arc(Arc::clone(&rejected)),
+        );
+
+        let clamp = ClampLayer::default().layer(recorder);
+        let gauge = clamp.register_gauge(&"gauge_key".into(), &METADATA);
+        let value = Arc::new(Mutex::new(f64::NAN));
+        let callback_value = Arc::clone(&value);
+        gauge.set_callback(move || *callback_value.lock().unwrap());
+
+        assert_eq!(captured.value(), 0.0);
+        *value.lock().unwrap() = 4.0;
+        assert_eq!(captured.value(), 4.0);
+        *value.lock().unwrap() = f64::INFINITY;
+        assert_eq!(captured.value(), 4.0);
+
+        assert_eq!(rejected.0.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_histogram_range() {
+        for (clamp_out_of_range, expected_values, expected_rejected) in
+            [(false, vec![5.0], 4), (true, vec![5.0, 0.0, 10.0], 2)]
+        {
+            let captured = Arc::new(CapturingHistogram::default());
+            let rejected = Arc::new(CountingCounter::default());
+
+            let mut recorder = MockBasicRecorder::new();
+            expect_register_histogram(
+                &mut recorder,
+                "histogram_key".into(),
+                Histogram::from_arc(Arc::clone(&captured)),
+            );
+            expect_register_counter(
+                &mut recorder,
+                rejected_key("histogram_key"),
+                Counter::from_arc(Arc::clone(&rejected)),
+            );
+
+            let mut layer = ClampLayer::default();
+            layer.histogram_range(0.0, 10.0).clamp_out_of_range(clamp_out_of_range);
+            let clamp = layer.layer(recorder);
+
+            let histogram = clamp.register_histogram(&"histogram_key".into(), &METADATA);
+            histogram.record(5.0);
+            histogram.record(-1.0);
+            histogram.record(f64::INFINITY);
+            histogram.record(f64::NAN);
+            histogram.record(f64::NAN);
+
+            assert_eq!(*captured.0.lock().unwrap(), expected_values);
+            assert_eq!(rejected.0.load(Ordering::Relaxed), expected_rejected);
+        }
+    }
+
+    #[test]
+    fn test_histogram_exemplars() {
+        let captured = Arc::new(CapturingHistogram::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_histogram(
+            &mut recorder,
+            "histogram_key".into(),
+            Histogram::from_arc(Arc::clone(&captured)),
+        );
+        expect_register_counter(&mut recorder, rejected_key("histogram_key"), Counter::noop());
+
+        let mut layer = ClampLayer::default();
+        layer.histogram_range(0.0, 10.0).clamp_out_of_range(true);
+        let clamp = layer.layer(recorder);
+
+        let histogram = clamp.register_histogram(&"histogram_key".into(), &METADATA);
+        histogram.record_with_exemplar(15.0, &[("trace_id", "abc123")]);
+
+        assert_eq!(*captured.0.lock().unwrap(), vec![10.0]);
+        assert_eq!(*captured.1.lock().unwrap(), vec![vec![Label::new("trace_id", "abc123")]]);
+    }
+
+    #[test]
+    fn test_counter_passthrough() {
+        let inputs = vec![RecorderOperation::RegisterCounter(
+            "counter_key".into(),
+            Counter::noop(),
+            &METADATA,
+        )];
+        let expectations = inputs.clone();
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let clamp = ClampLayer::default().layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&clamp);
+        }
+    }
+}
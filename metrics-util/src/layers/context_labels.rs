@@ -0,0 +1,331 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, IntoLabels, Key, KeyName, Label, Metadata, Recorder,
+    SharedString, Summary, Unit,
+};
+
+thread_local! {
+    static SCOPES: RefCell<Vec<Vec<Label>>> = RefCell::new(Vec::new());
+}
+
+/// Gets the labels of the current context, with labels from inner scopes overriding labels with
+/// the same key from outer scopes.
+fn current_labels() -> Vec<Label> {
+    SCOPES.with(|scopes| {
+        let mut labels: Vec<Label> = Vec::new();
+        for label in scopes.borrow().iter().flatten() {
+            match labels.iter_mut().find(|existing| existing.key() == label.key()) {
+                Some(existing) => *existing = label.clone(),
+                None => labels.push(label.clone()),
+            }
+        }
+        labels
+    })
+}
+
+/// Labels attached to the current context, for use with [`ContextLabelsLayer`].
+///
+/// Labels can be attached to synchronous code for as long as a [`LabelContextGuard`] is held, via
+/// [`LabelContext::enter`], or to a future for as long as it's polled, via [`LabelContext::scope`].
+/// Scopes can be nested, in which case labels from inner scopes take precedence over labels with
+/// the same key from outer scopes.
+#[derive(Debug)]
+pub struct LabelContext(());
+
+impl LabelContext {
+    /// Attaches the given labels to the current thread until the returned guard is dropped.
+    ///
+    /// The guard cannot be sent to another thread.  To attach labels to a future, which may be
+    /// polled from any thread, use [`LabelContext::scope`] instead.
+    pub fn enter<L: IntoLabels>(labels: L) -> LabelContextGuard {
+        let depth = SCOPES.with(|scopes| {
+            let mut scopes = scopes.borrow_mut();
+            scopes.push(labels.into_labels());
+            scopes.len() - 1
+        });
+        LabelContextGuard { depth, _not_send: PhantomData }
+    }
+
+    /// Attaches the given labels to the given future.
+    ///
+    /// The labels are attached to the thread polling the future for the duration of each poll, so
+    /// they follow the future across threads, such as when running on a multi-threaded executor.
+    pub fn scope<L: IntoLabels, F: Future>(labels: L, future: F) -> LabelContextFuture<F> {
+        LabelContextFuture { labels: labels.into_labels(), future }
+    }
+
+    /// Gets the labels attached to the current context.
+    pub fn current() -> Vec<Label> {
+        current_labels()
+    }
+}
+
+/// Guard which keeps labels attached to the current thread.
+///
+/// Created by [`LabelContext::enter`].  The labels are detached when the guard is dropped.
+#[derive(Debug)]
+pub struct LabelContextGuard {
+    depth: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for LabelContextGuard {
+    fn drop(&mut self) {
+        // Guards may be dropped out of order, so detach this scope and any scopes nested within it.
+        SCOPES.with(|scopes| scopes.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Future which attaches labels to the current context while it's being polled.
+///
+/// Created by [`LabelContext::scope`].
+#[derive(Debug)]
+pub struct LabelContextFuture<F> {
+    labels: Vec<Label>,
+    future: F,
+}
+
+/// Restores a scope's labels back into its future once polling has finished, even if polling
+/// panics.
+struct ScopeRestore<'a> {
+    depth: usize,
+    labels: &'a mut Vec<Label>,
+}
+
+impl Drop for ScopeRestore<'_> {
+    fn drop(&mut self) {
+        SCOPES.with(|scopes| {
+            let mut scopes = scopes.borrow_mut();
+            if scopes.len() > self.depth {
+                *self.labels = scopes.swap_remove(self.depth);
+                scopes.truncate(self.depth);
+            }
+        });
+    }
+}
+
+impl<F: Future> Future for LabelContextFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is structurally pinned: it is never moved out of `self`, and is only
+        // ever accessed through a pinned reference.  `labels` is not structurally pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let labels = std::mem::take(&mut this.labels);
+        let depth = SCOPES.with(|scopes| {
+            let mut scopes = scopes.borrow_mut();
+            scopes.push(labels);
+            scopes.len() - 1
+        });
+        let _restore = ScopeRestore { depth, labels: &mut this.labels };
+
+        future.poll(cx)
+    }
+}
+
+/// Appends the labels of the current context to every metric key.
+///
+/// More information on the behavior of the layer can be found in [`ContextLabelsLayer`].
+pub struct ContextLabels<R> {
+    inner: R,
+}
+
+impl<R> ContextLabels<R> {
+    fn enrich_key(&self, key: &Key) -> Key {
+        let extra_labels = current_labels()
+            .into_iter()
+            .filter(|label| !key.labels().any(|existing| existing.key() == label.key()))
+            .collect::<Vec<_>>();
+        if extra_labels.is_empty() {
+            return key.clone();
+        }
+
+        key.with_extra_labels(extra_labels)
+    }
+}
+
+impl<R: Recorder> Recorder for ContextLabels<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.enrich_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.enrich_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.enrich_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.enrich_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for appending the labels of the current context to every metric key.
+///
+/// Labels, such as a request ID or tenant, are attached to the current context with
+/// [`LabelContext`], either for a block of synchronous code or for an entire future, and are then
+/// appended to the key of every metric registered within that context.  Labels given at the call
+/// site take precedence over context labels with the same key.
+///
+/// As labels are appended at registration time, handles which are registered outside of a context
+/// and held on to will not pick up the labels of the context they're later used in.  The metric
+/// macros register a metric each time they're called, and so always use the current context.
+#[derive(Clone, Debug, Default)]
+pub struct ContextLabelsLayer(());
+
+impl<R> Layer<R> for ContextLabelsLayer {
+    type Output = ContextLabels<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        ContextLabels { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use super::{ContextLabelsLayer, LabelContext};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, Label};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// A future which yields once before completing.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn test_basic_functionality() {
+        let expectations = vec![
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts(
+                    "gauge_key",
+                    vec![Label::new("request_id", "abc"), Label::new("tenant", "acme")],
+                ),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts(
+                    "histogram_key",
+                    vec![Label::new("tenant", "globex"), Label::new("request_id", "def")],
+                ),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let context_labels = ContextLabelsLayer::default().layer(recorder);
+
+        RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA)
+            .apply_to_recorder(&context_labels);
+
+        let _outer = LabelContext::enter(vec![Label::new("tenant", "acme")]);
+        {
+            let _inner = LabelContext::enter(vec![Label::new("request_id", "abc")]);
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("request_id", "abc")]),
+                Gauge::noop(),
+                &METADATA,
+            )
+            .apply_to_recorder(&context_labels);
+        }
+
+        let _inner = LabelContext::enter(vec![
+            Label::new("tenant", "globex"),
+            Label::new("request_id", "def"),
+        ]);
+        RecorderOperation::RegisterHistogram("histogram_key".into(), Histogram::noop(), &METADATA)
+            .apply_to_recorder(&context_labels);
+    }
+
+    #[test]
+    fn test_future_scope() {
+        let mut future = Box::pin(LabelContext::scope(vec![Label::new("tenant", "acme")], async {
+            assert_eq!(LabelContext::current(), vec![Label::new("tenant", "acme")]);
+            YieldOnce(false).await;
+            assert_eq!(LabelContext::current(), vec![Label::new("tenant", "acme")]);
+        }));
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert!(LabelContext::current().is_empty());
+        assert!(future.as_mut().poll(&mut cx).is_ready());
+        assert!(LabelContext::current().is_empty());
+    }
+}
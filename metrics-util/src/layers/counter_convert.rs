@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{layers::Layer, CounterTracker};
+use metrics::{
+    Attributes, Counter, CounterFn, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder,
+    SharedString, Summary, Unit,
+};
+
+/// Direction in which counter operations are converted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterConversion {
+    /// Converts absolute values into increments.
+    ///
+    /// Each call to [`Counter::absolute`] is turned into an increment by the difference between the
+    /// new value and the previous absolute value, as tracked by a [`CounterTracker`].  If the new
+    /// value is lower than the previous value, the source is assumed to have been reset, and the
+    /// counter is incremented by the new value in its entirety.
+    ///
+    /// Increments are passed through as-is.
+    AbsoluteToDelta,
+
+    /// Converts increments into absolute values.
+    ///
+    /// A running total is kept for each counter, and each call to [`Counter::increment`] is turned
+    /// into a call to [`Counter::absolute`] with the updated total.  Calls to
+    /// [`Counter::absolute`] are passed through as-is, and replace the running total.  Exemplars
+    /// can't be attached to absolute values, so they're dropped.
+    ///
+    /// Updates to the running total are serialized with passing it on, so that concurrent
+    /// increments reach the inner counter in order, and the total it's given never goes backwards.
+    DeltaToAbsolute,
+}
+
+/// State shared between every handle registered for the same counter.
+#[derive(Clone)]
+enum CounterState {
+    /// Previous absolute value, for [`CounterConversion::AbsoluteToDelta`].
+    Tracker(Arc<Mutex<CounterTracker>>),
+
+    /// Running total, for [`CounterConversion::DeltaToAbsolute`].
+    Total(Arc<Mutex<u64>>),
+}
+
+impl CounterState {
+    fn new(conversion: CounterConversion) -> Self {
+        match conversion {
+            CounterConversion::AbsoluteToDelta => {
+                CounterState::Tracker(Arc::new(Mutex::new(CounterTracker::new())))
+            }
+            CounterConversion::DeltaToAbsolute => CounterState::Total(Arc::new(Mutex::new(0))),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+struct ConvertedCounter {
+    inner: Counter,
+    state: CounterState,
+}
+
+impl CounterFn for ConvertedCounter {
+    fn increment(&self, value: u64) {
+        match &self.state {
+            CounterState::Tracker(_) => self.inner.increment(value),
+            CounterState::Total(total) => {
+                // The lock is held while passing the total on, so that a concurrent increment can't
+                // overwrite it with an older, lower total.
+                let mut total = lock(total);
+                *total = total.wrapping_add(value);
+                self.inner.absolute(*total);
+            }
+        }
+    }
+
+    fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+        match &self.state {
+            CounterState::Tracker(_) => self.inner.increment_with_exemplar(value, exemplar.iter()),
+            CounterState::Total(_) => self.increment(value),
+        }
+    }
+
+    fn absolute(&self, value: u64) {
+        match &self.state {
+            CounterState::Tracker(tracker) => {
+                let delta = lock(tracker).observe(value);
+                if delta > 0 {
+                    self.inner.increment(delta);
+                }
+            }
+            CounterState::Total(total) => {
+                let mut total = lock(total);
+                *total = value;
+                self.inner.absolute(value);
+            }
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.inner.is_expired()
+    }
+}
+
+/// Converts counter operations between absolute values and increments.
+///
+/// More information on the behavior of the layer can be found in [`CounterConvertLayer`].
+pub struct CounterConvert<R> {
+    inner: R,
+    conversion: CounterConversion,
+    state: Mutex<HashMap<Key, CounterState>>,
+}
+
+impl<R> CounterConvert<R> {
+    #[allow(clippy::mutable_key_type)]
+    fn state_for(&self, key: &Key) -> CounterState {
+        lock(&self.state)
+            .entry(key.clone())
+            .or_insert_with(|| CounterState::new(self.conversion))
+            .clone()
+    }
+}
+
+impl<R: Recorder> Recorder for CounterConvert<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let counter = ConvertedCounter {
+            inner: self.inner.register_counter(key, metadata),
+            state: self.state_for(key),
+        };
+        Counter::from_arc(Arc::new(counter))
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        self.inner.register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        self.inner.register_histogram(key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for converting counter operations between absolute values and increments.
+///
+/// Some sources only report the cumulative value of a counter, such as when scraping the counters
+/// of another system, while some recorders only deal in increments, or vice versa.  This layer
+/// converts the operations performed on counters in the direction given by [`CounterConversion`],
+/// so that the inner recorder only ever sees the kind of operation it understands.
+///
+/// The state used for the conversion is tracked per counter key, and shared between every handle
+/// registered for the same key, so the conversion works regardless of whether handles are held on
+/// to or re-registered for each operation, as the metric macros do.
+///
+/// Gauges and histograms are passed through untouched.
+#[derive(Clone, Debug)]
+pub struct CounterConvertLayer {
+    conversion: CounterConversion,
+}
+
+impl CounterConvertLayer {
+    /// Creates a new `CounterConvertLayer` which converts counter operations in the given direction.
+    pub fn new(conversion: CounterConversion) -> CounterConvertLayer {
+        CounterConvertLayer { conversion }
+    }
+}
+
+impl<R> Layer<R> for CounterConvertLayer {
+    type Output = CounterConvert<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        CounterConvert { inner, conversion: self.conversion, state: Mutex::new(HashMap::new()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::{CounterConversion, CounterConvertLayer};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, CounterFn, Label, Recorder};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[derive(Debug, PartialEq)]
+    enum Operation {
+        Increment(u64),
+        IncrementWithExemplar(u64, Vec<Label>),
+        Absolute(u64),
+    }
+
+    #[derive(Default)]
+    struct CapturingCounter(Mutex<Vec<Operation>>);
+
+    impl CounterFn for CapturingCounter {
+        fn increment(&self, value: u64) {
+            self.0.lock().unwrap().push(Operation::Increment(value));
+        }
+
+        fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+            self.0.lock().unwrap().push(Operation::IncrementWithExemplar(value, exemplar.to_vec()));
+        }
+
+        fn absolute(&self, value: u64) {
+            self.0.lock().unwrap().push(Operation::Absolute(value));
+        }
+    }
+
+    #[test]
+    fn test_absolute_to_delta() {
+        let captured = Arc::new(CapturingCounter::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_counter(
+            &mut recorder,
+            "counter_key".into(),
+            Counter::from_arc(Arc::clone(&captured)),
+        );
+
+        let convert = CounterConvertLayer::new(CounterConversion::AbsoluteToDelta).layer(recorder);
+        let counter = convert.register_counter(&"counter_key".into(), &METADATA);
+        counter.absolute(10);
+        counter.absolute(15);
+        counter.absolute(15);
+        counter.increment(2);
+        counter.increment_with_exemplar(1, vec![Label::new("trace_id", "abc")]);
+        // The source was reset, so the entire new value is treated as the increment.
+        counter.absolute(4);
+
+        assert_eq!(
+            *captured.0.lock().unwrap(),
+            vec![
+                Operation::Increment(10),
+                Operation::Increment(5),
+                Operation::Increment(2),
+                Operation::IncrementWithExemplar(1, vec![Label::new("trace_id", "abc")]),
+                Operation::Increment(4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_delta_to_absolute() {
+        let captured = Arc::new(CapturingCounter::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        recorder
+            .expect_register_counter()
+            .times(2)
+            .return_const(Counter::from_arc(Arc::clone(&captured)));
+
+        let convert = CounterConvertLayer::new(CounterConversion::DeltaToAbsolute).layer(recorder);
+
+        // The running total is shared between handles for the same key.
+        convert.register_counter(&"counter_key".into(), &METADATA).increment(3);
+        let counter = convert.register_counter(&"counter_key".into(), &METADATA);
+        counter.increment(4);
+        counter.absolute(20);
+        counter.increment(1);
+
+        assert_eq!(
+            *captured.0.lock().unwrap(),
+            vec![
+                Operation::Absolute(3),
+                Operation::Absolute(7),
+                Operation::Absolute(20),
+                Operation::Absolute(21),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_delta_to_absolute_concurrent() {
+        let captured = Arc::new(CapturingCounter::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_counter(
+            &mut recorder,
+            "counter_key".into(),
+            Counter::from_arc(Arc::clone(&captured)),
+        );
+
+        let convert = CounterConvertLayer::new(CounterConversion::DeltaToAbsolute).layer(recorder);
+        let counter = convert.register_counter(&"counter_key".into(), &METADATA);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        counter.increment(1);
+                    }
+                });
+            }
+        });
+
+        let totals = captured
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|operation| match operation {
+                Operation::Absolute(total) => *total,
+                operation => panic!("unexpected operation: {:?}", operation),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(totals, (1..=4000).collect::<Vec<_>>());
+    }
+}
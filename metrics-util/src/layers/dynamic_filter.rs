@@ -0,0 +1,510 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, OnceLock, RwLock,
+};
+use std::time::Duration;
+
+use crate::layers::Layer;
+use aho_corasick::AhoCorasick;
+use metrics::{
+    Attributes, Counter, CounterFn, Gauge, GaugeCallback, GaugeFn, Histogram, HistogramFn, Key,
+    KeyName, Label, Level, Metadata, Recorder, SharedString, Summary, SummaryFn, Unit,
+};
+
+struct FilterState {
+    automaton: RwLock<Arc<AhoCorasick>>,
+    generation: AtomicU64,
+}
+
+fn build_automaton<P, I>(patterns: P) -> AhoCorasick
+where
+    P: IntoIterator<Item = I>,
+    I: AsRef<[u8]>,
+{
+    // See the comment in `FilterLayer::layer` for why we consider this to be infallible.
+    AhoCorasick::new(patterns).expect("should not fail to build filter automaton")
+}
+
+/// Handle for changing the patterns used by a [`DynamicFilter`] at runtime.
+///
+/// Handles are cheap to clone, and every clone controls the same set of patterns.
+#[derive(Clone)]
+pub struct FilterHandle {
+    state: Arc<FilterState>,
+}
+
+impl FilterHandle {
+    fn new<P, I>(patterns: P) -> FilterHandle
+    where
+        P: IntoIterator<Item = I>,
+        I: AsRef<[u8]>,
+    {
+        FilterHandle {
+            state: Arc::new(FilterState {
+                automaton: RwLock::new(Arc::new(build_automaton(patterns))),
+                generation: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Replaces the current set of patterns.
+    ///
+    /// Every metric will be re-checked against the new patterns the next time it's used.  Metrics
+    /// which were filtered out when they were registered, and no longer match, will be registered
+    /// with the inner recorder at that point, while metrics which now match are filtered out.
+    pub fn set_patterns<P, I>(&self, patterns: P)
+    where
+        P: IntoIterator<Item = I>,
+        I: AsRef<str>,
+    {
+        let automaton = build_automaton(
+            patterns.into_iter().map(|s| s.as_ref().to_string()).collect::<Vec<_>>(),
+        );
+        *self.state.automaton.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(automaton);
+        self.state.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Removes all patterns, allowing every metric through.
+    pub fn clear(&self) {
+        self.set_patterns(std::iter::empty::<&str>());
+    }
+
+    fn generation(&self) -> u64 {
+        self.state.generation.load(Ordering::Acquire)
+    }
+
+    fn should_filter(&self, key: &str) -> bool {
+        let automaton = Arc::clone(&self.state.automaton.read().unwrap_or_else(|e| e.into_inner()));
+        automaton.is_match(key)
+    }
+}
+
+/// Owned version of [`Metadata`], so that registration can be deferred.
+struct OwnedMetadata {
+    target: String,
+    level: Level,
+    module_path: Option<String>,
+}
+
+impl OwnedMetadata {
+    fn from_metadata(metadata: &Metadata<'_>) -> OwnedMetadata {
+        OwnedMetadata {
+            target: metadata.target().to_owned(),
+            level: metadata.level().clone(),
+            module_path: metadata.module_path().map(ToOwned::to_owned),
+        }
+    }
+
+    fn as_metadata(&self) -> Metadata<'_> {
+        Metadata::new(&self.target, self.level.clone(), self.module_path.as_deref())
+    }
+}
+
+/// A handle for a metric registered through a [`DynamicFilter`].
+///
+/// Each time the handle is used, it checks if the filter patterns have changed since it last
+/// checked, which only takes an atomic load when they haven't, and if so, checks whether the metric
+/// is filtered out under the new patterns.  Operations on a metric which is filtered out are
+/// dropped, while the first operation on a metric which isn't registers it with the inner recorder,
+/// forwarding all operations to the resulting handle from then on.
+struct DynamicHandle<R, H> {
+    recorder: Arc<R>,
+    filter: FilterHandle,
+    key: Key,
+    metadata: OwnedMetadata,
+    checked_generation: AtomicU64,
+    filtered: AtomicBool,
+    handle: OnceLock<H>,
+}
+
+impl<R, H> DynamicHandle<R, H> {
+    fn new<F>(
+        recorder: Arc<R>,
+        filter: FilterHandle,
+        key: &Key,
+        metadata: &Metadata<'_>,
+        register: F,
+    ) -> Self
+    where
+        F: FnOnce(&R, &Key, &Metadata<'_>) -> H,
+    {
+        let checked_generation = AtomicU64::new(filter.generation());
+        let filtered = filter.should_filter(key.name());
+
+        // Metrics which aren't filtered out are registered straight away, as they would be without
+        // the filter.
+        let handle = OnceLock::new();
+        if !filtered {
+            let _ = handle.set(register(&recorder, key, metadata));
+        }
+
+        DynamicHandle {
+            recorder,
+            filter,
+            key: key.clone(),
+            metadata: OwnedMetadata::from_metadata(metadata),
+            checked_generation,
+            filtered: AtomicBool::new(filtered),
+            handle,
+        }
+    }
+
+    fn resolve<F>(&self, register: F) -> Option<&H>
+    where
+        F: FnOnce(&R, &Key, &Metadata<'_>) -> H,
+    {
+        let generation = self.filter.generation();
+        if self.checked_generation.load(Ordering::Acquire) != generation {
+            // Concurrent callers may both re-check the patterns, which is harmless, and a caller
+            // which re-checked against older patterns leaves an older generation behind, so the
+            // next caller checks again.
+            self.filtered.store(self.filter.should_filter(self.key.name()), Ordering::Release);
+            self.checked_generation.store(generation, Ordering::Release);
+        }
+
+        if self.filtered.load(Ordering::Acquire) {
+            return None;
+        }
+
+        Some(
+            self.handle
+                .get_or_init(|| register(&self.recorder, &self.key, &self.metadata.as_metadata())),
+        )
+    }
+}
+
+impl<R: Recorder> CounterFn for DynamicHandle<R, Counter> {
+    fn increment(&self, value: u64) {
+        if let Some(counter) = self.resolve(Recorder::register_counter) {
+            counter.increment(value);
+        }
+    }
+
+    fn absolute(&self, value: u64) {
+        if let Some(counter) = self.resolve(Recorder::register_counter) {
+            counter.absolute(value);
+        }
+    }
+
+    fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+        if let Some(counter) = self.resolve(Recorder::register_counter) {
+            counter.increment_with_exemplar(value, exemplar.iter());
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.handle.get().map_or(false, |handle| handle.is_expired())
+    }
+}
+
+impl<R: Recorder> GaugeFn for DynamicHandle<R, Gauge> {
+    fn increment(&self, value: f64) {
+        if let Some(gauge) = self.resolve(Recorder::register_gauge) {
+            gauge.increment(value);
+        }
+    }
+
+    fn decrement(&self, value: f64) {
+        if let Some(gauge) = self.resolve(Recorder::register_gauge) {
+            gauge.decrement(value);
+        }
+    }
+
+    fn set(&self, value: f64) {
+        if let Some(gauge) = self.resolve(Recorder::register_gauge) {
+            gauge.set(value);
+        }
+    }
+
+    fn set_max(&self, value: f64) {
+        if let Some(gauge) = self.resolve(Recorder::register_gauge) {
+            gauge.set_max(value);
+        }
+    }
+
+    fn set_min(&self, value: f64) {
+        if let Some(gauge) = self.resolve(Recorder::register_gauge) {
+            gauge.set_min(value);
+        }
+    }
+
+    fn set_callback(&self, callback: GaugeCallback) {
+        if let Some(gauge) = self.resolve(Recorder::register_gauge) {
+            gauge.set_callback(move || callback.value());
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.handle.get().map_or(false, |handle| handle.is_expired())
+    }
+}
+
+impl<R: Recorder> HistogramFn for DynamicHandle<R, Histogram> {
+    fn record(&self, value: f64) {
+        if let Some(histogram) = self.resolve(Recorder::register_histogram) {
+            histogram.record(value);
+        }
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        if let Some(histogram) = self.resolve(Recorder::register_histogram) {
+            histogram.record_with_exemplar(value, exemplar.iter());
+        }
+    }
+
+    fn record_duration(&self, duration: Duration) {
+        if let Some(histogram) = self.resolve(Recorder::register_histogram) {
+            histogram.record_duration(duration);
+        }
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        if let Some(histogram) = self.resolve(Recorder::register_histogram) {
+            histogram.record_many(values);
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.handle.get().map_or(false, |handle| handle.is_expired())
+    }
+}
+
+impl<R: Recorder> SummaryFn for DynamicHandle<R, Summary> {
+    fn record(&self, value: f64) {
+        if let Some(summary) = self.resolve(Recorder::register_summary) {
+            summary.record(value);
+        }
+    }
+}
+
+/// Filters and discards metrics matching certain name patterns, which can be changed at runtime.
+///
+/// More information on the behavior of the layer can be found in [`DynamicFilterLayer`].
+pub struct DynamicFilter<R> {
+    inner: Arc<R>,
+    filter: FilterHandle,
+}
+
+impl<R> DynamicFilter<R> {
+    /// Gets a handle for changing the patterns used by this filter.
+    pub fn handle(&self) -> FilterHandle {
+        self.filter.clone()
+    }
+
+    fn wrap<H, F>(&self, key: &Key, metadata: &Metadata<'_>, register: F) -> DynamicHandle<R, H>
+    where
+        F: FnOnce(&R, &Key, &Metadata<'_>) -> H,
+    {
+        DynamicHandle::new(Arc::clone(&self.inner), self.filter.clone(), key, metadata, register)
+    }
+}
+
+impl<R> Recorder for DynamicFilter<R>
+where
+    R: Recorder + Send + Sync + 'static,
+{
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        Counter::from_arc(Arc::new(self.wrap(key, metadata, Recorder::register_counter)))
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(Arc::new(self.wrap(key, metadata, Recorder::register_gauge)))
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(Arc::new(self.wrap(key, metadata, Recorder::register_histogram)))
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        Summary::from_arc(Arc::new(self.wrap(key, metadata, Recorder::register_summary)))
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for filtering and discarding metrics matching certain name patterns, where the patterns
+/// can be changed at runtime.
+///
+/// Patterns are matched across the entire key i.e. they are matched as substrings, in the same way
+/// as [`FilterLayer`][super::FilterLayer].  Unlike `FilterLayer`, the patterns can be replaced at
+/// any time through a [`FilterHandle`], which can be acquired from the layer or from any
+/// [`DynamicFilter`] it creates.  This allows, for example, temporarily enabling verbose metrics
+/// without restarting the process.
+///
+/// Metrics which are filtered out when they are registered are handed back as handles which do
+/// nothing, and are not registered with the inner recorder, until the patterns are changed such
+/// that the metric is no longer filtered out.  From that point on, the metric is registered with
+/// the inner recorder and behaves as normal.  Likewise, once the patterns are changed such that a
+/// metric is filtered out, updates to it are dropped, although it stays registered with the inner
+/// recorder.  Handles check whether the patterns have changed every time they're used, which only
+/// takes an atomic load when they haven't.
+///
+/// Descriptions are always passed through to the inner recorder, so that they are present if the
+/// metric they describe becomes live at a later point.
+pub struct DynamicFilterLayer {
+    filter: FilterHandle,
+}
+
+impl DynamicFilterLayer {
+    /// Creates a [`DynamicFilterLayer`] from an initial set of patterns.
+    pub fn from_patterns<P, I>(patterns: P) -> Self
+    where
+        P: IntoIterator<Item = I>,
+        I: AsRef<str>,
+    {
+        let patterns = patterns.into_iter().map(|s| s.as_ref().to_string()).collect::<Vec<_>>();
+        DynamicFilterLayer { filter: FilterHandle::new(patterns) }
+    }
+
+    /// Gets a handle for changing the patterns used by this layer.
+    ///
+    /// Every [`DynamicFilter`] created from this layer shares the same patterns, so changes made
+    /// through the handle apply to all of them.
+    pub fn handle(&self) -> FilterHandle {
+        self.filter.clone()
+    }
+}
+
+impl<R> Layer<R> for DynamicFilterLayer {
+    type Output = DynamicFilter<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        DynamicFilter { inner: Arc::new(inner), filter: self.filter.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    use super::DynamicFilterLayer;
+    use crate::{layers::Layer, test_util::*};
+    use metrics::{Counter, Gauge, Histogram, Key, Recorder, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "tokio.loops".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("tokio.loops".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("hyper.bytes_read".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "hyper.response_latency".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "tokio.loops".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterGauge("hyper.bytes_read".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "hyper.response_latency".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let filter = DynamicFilterLayer::from_patterns(["tokio"]);
+        let filter = filter.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&filter);
+        }
+    }
+
+    #[test]
+    fn test_filtered_registration_becomes_live() {
+        let key = Key::from_name("tokio.loops");
+
+        let value = Arc::new(AtomicU64::new(0));
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_counter(&mut recorder, key.clone(), Counter::from_arc(Arc::clone(&value)));
+
+        let layer = DynamicFilterLayer::from_patterns(["tokio"]);
+        let handle = layer.handle();
+        let filter = layer.layer(recorder);
+
+        // While filtered, using the counter does not register it with the inner recorder.
+        let counter = filter.register_counter(&key, &METADATA);
+        counter.increment(1);
+        counter.increment(1);
+
+        // Once the patterns change, the next use of the counter registers it, exactly once.
+        handle.set_patterns(["hyper"]);
+        counter.increment(1);
+        counter.increment(1);
+        assert_eq!(value.load(Ordering::Relaxed), 2);
+
+        // Filtering it out again drops updates, without registering it again once it's let through.
+        handle.set_patterns(["tokio"]);
+        counter.increment(1);
+        assert_eq!(value.load(Ordering::Relaxed), 2);
+        handle.clear();
+        counter.increment(1);
+        assert_eq!(value.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_live_registration_becomes_filtered() {
+        let key = Key::from_name("hyper.bytes_read");
+
+        let value = Arc::new(AtomicU64::new(0));
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_counter(&mut recorder, key.clone(), Counter::from_arc(Arc::clone(&value)));
+
+        let layer = DynamicFilterLayer::from_patterns(["tokio"]);
+        let handle = layer.handle();
+        let filter = layer.layer(recorder);
+
+        let counter = filter.register_counter(&key, &METADATA);
+        counter.increment(1);
+        handle.set_patterns(["hyper"]);
+        counter.increment(1);
+        assert_eq!(value.load(Ordering::Relaxed), 1);
+    }
+}
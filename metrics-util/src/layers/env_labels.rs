@@ -0,0 +1,146 @@
+use std::env;
+
+use crate::layers::{LabelInject, LabelInjectLayer, Layer};
+
+/// Resolves the hostname of the current machine.
+///
+/// The `HOSTNAME` environment variable is checked first, falling back to the platform-specific
+/// source of the hostname.
+fn resolve_hostname() -> Option<String> {
+    let hostname = env::var("HOSTNAME").ok().or_else(platform_hostname)?;
+    let hostname = hostname.trim();
+    if hostname.is_empty() {
+        None
+    } else {
+        Some(hostname.to_owned())
+    }
+}
+
+#[cfg(unix)]
+fn platform_hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .ok()
+}
+
+#[cfg(windows)]
+fn platform_hostname() -> Option<String> {
+    env::var("COMPUTERNAME").ok()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn platform_hostname() -> Option<String> {
+    None
+}
+
+/// A layer for attaching labels describing the host and environment to every metric key.
+///
+/// Labels can be added for the hostname, the process ID, and the values of arbitrary environment
+/// variables, such as `POD_NAME` or `DEPLOY_ENV`.  Each value is resolved once, at the time it's
+/// added to the layer, and the resulting labels are injected into every metric key the same way as
+/// [`LabelInjectLayer`]: labels given at the call site take precedence over injected ones.
+///
+/// Values which cannot be resolved, such as an environment variable which is not set, are skipped.
+#[derive(Default)]
+pub struct EnvLabelsLayer {
+    labels: LabelInjectLayer,
+}
+
+impl EnvLabelsLayer {
+    /// Adds the hostname of the current machine as the `hostname` label.
+    pub fn hostname(&mut self) -> &mut EnvLabelsLayer {
+        self.hostname_as("hostname")
+    }
+
+    /// Adds the hostname of the current machine as a label with the given key.
+    ///
+    /// The hostname is taken from the `HOSTNAME` environment variable, if set, and otherwise from
+    /// the operating system.
+    pub fn hostname_as<K: Into<String>>(&mut self, label_key: K) -> &mut EnvLabelsLayer {
+        if let Some(hostname) = resolve_hostname() {
+            self.labels.add_label(label_key.into(), hostname);
+        }
+        self
+    }
+
+    /// Adds the ID of the current process as the `pid` label.
+    pub fn pid(&mut self) -> &mut EnvLabelsLayer {
+        self.pid_as("pid")
+    }
+
+    /// Adds the ID of the current process as a label with the given key.
+    pub fn pid_as<K: Into<String>>(&mut self, label_key: K) -> &mut EnvLabelsLayer {
+        self.labels.add_label(label_key.into(), std::process::id().to_string());
+        self
+    }
+
+    /// Adds the value of the given environment variable as a label with the given key.
+    ///
+    /// If the environment variable is not set, or is not valid Unicode, no label is added.
+    pub fn env_var<V, K>(&mut self, var: V, label_key: K) -> &mut EnvLabelsLayer
+    where
+        V: AsRef<str>,
+        K: Into<String>,
+    {
+        if let Ok(value) = env::var(var.as_ref()) {
+            self.labels.add_label(label_key.into(), value);
+        }
+        self
+    }
+}
+
+impl<R> Layer<R> for EnvLabelsLayer {
+    type Output = LabelInject<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        self.labels.layer(inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnvLabelsLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Key, Label};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        std::env::set_var("METRICS_UTIL_ENV_LABELS_TEST", "staging");
+
+        let inputs = vec![RecorderOperation::RegisterCounter(
+            Key::from_parts("counter_key", vec![Label::new("env", "dev")]),
+            Counter::noop(),
+            &METADATA,
+        )];
+
+        let expectations = vec![RecorderOperation::RegisterCounter(
+            Key::from_parts(
+                "counter_key",
+                vec![
+                    Label::new("env", "dev"),
+                    Label::new("process_id", std::process::id().to_string()),
+                    Label::new("deploy_env", "staging"),
+                ],
+            ),
+            Counter::noop(),
+            &METADATA,
+        )];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = EnvLabelsLayer::default();
+        layer
+            .pid_as("process_id")
+            .env_var("METRICS_UTIL_ENV_LABELS_TEST", "deploy_env")
+            .env_var("METRICS_UTIL_ENV_LABELS_TEST", "env")
+            .env_var("METRICS_UTIL_ENV_LABELS_UNSET", "unset");
+        let env_labels = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&env_labels);
+        }
+    }
+}
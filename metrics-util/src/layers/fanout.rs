@@ -1,11 +1,20 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::sync::Arc;
+use std::time::Duration;
 
 use metrics::{
-    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
-    SharedString, Unit,
+    Attributes, Counter, CounterFn, Gauge, GaugeCallback, GaugeFn, Histogram, HistogramFn, Key,
+    KeyName, Label, Metadata, Recorder, SharedString, Summary, SummaryFn, Unit,
 };
 
-struct FanoutCounter {
+/// Runs the given closure, isolating the caller from any panic that occurs within it.
+///
+/// If the closure panics, `None` is returned.
+fn isolate<T, F: FnOnce() -> T>(f: F) -> Option<T> {
+    catch_unwind(AssertUnwindSafe(f)).ok()
+}
+
+pub(crate) struct FanoutCounter {
     counters: Vec<Counter>,
 }
 
@@ -18,15 +27,26 @@ impl FanoutCounter {
 impl CounterFn for FanoutCounter {
     fn increment(&self, value: u64) {
         for counter in &self.counters {
-            counter.increment(value);
+            let _ = isolate(|| counter.increment(value));
         }
     }
 
     fn absolute(&self, value: u64) {
         for counter in &self.counters {
-            counter.absolute(value);
+            let _ = isolate(|| counter.absolute(value));
         }
     }
+
+    fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+        for counter in &self.counters {
+            let _ = isolate(|| counter.increment_with_exemplar(value, exemplar.iter()));
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        // Updates are still observed as long as any of the recorders holds on to the metric.
+        !self.counters.is_empty() && self.counters.iter().all(|counter| counter.is_expired())
+    }
 }
 
 impl From<FanoutCounter> for Counter {
@@ -35,7 +55,7 @@ impl From<FanoutCounter> for Counter {
     }
 }
 
-struct FanoutGauge {
+pub(crate) struct FanoutGauge {
     gauges: Vec<Gauge>,
 }
 
@@ -48,21 +68,44 @@ impl FanoutGauge {
 impl GaugeFn for FanoutGauge {
     fn increment(&self, value: f64) {
         for gauge in &self.gauges {
-            gauge.increment(value);
+            let _ = isolate(|| gauge.increment(value));
         }
     }
 
     fn decrement(&self, value: f64) {
         for gauge in &self.gauges {
-            gauge.decrement(value);
+            let _ = isolate(|| gauge.decrement(value));
         }
     }
 
     fn set(&self, value: f64) {
         for gauge in &self.gauges {
-            gauge.set(value);
+            let _ = isolate(|| gauge.set(value));
+        }
+    }
+
+    fn set_max(&self, value: f64) {
+        for gauge in &self.gauges {
+            let _ = isolate(|| gauge.set_max(value));
+        }
+    }
+
+    fn set_min(&self, value: f64) {
+        for gauge in &self.gauges {
+            let _ = isolate(|| gauge.set_min(value));
         }
     }
+
+    fn set_callback(&self, callback: GaugeCallback) {
+        for gauge in &self.gauges {
+            let callback = callback.clone();
+            let _ = isolate(|| gauge.set_callback(move || callback.value()));
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        !self.gauges.is_empty() && self.gauges.iter().all(|gauge| gauge.is_expired())
+    }
 }
 
 impl From<FanoutGauge> for Gauge {
@@ -71,7 +114,7 @@ impl From<FanoutGauge> for Gauge {
     }
 }
 
-struct FanoutHistogram {
+pub(crate) struct FanoutHistogram {
     histograms: Vec<Histogram>,
 }
 
@@ -84,9 +127,32 @@ impl FanoutHistogram {
 impl HistogramFn for FanoutHistogram {
     fn record(&self, value: f64) {
         for histogram in &self.histograms {
-            histogram.record(value);
+            let _ = isolate(|| histogram.record(value));
+        }
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        for histogram in &self.histograms {
+            let _ = isolate(|| histogram.record_with_exemplar(value, exemplar.iter()));
         }
     }
+
+    fn record_duration(&self, duration: Duration) {
+        for histogram in &self.histograms {
+            let _ = isolate(|| histogram.record_duration(duration));
+        }
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        for histogram in &self.histograms {
+            let _ = isolate(|| histogram.record_many(values));
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        !self.histograms.is_empty()
+            && self.histograms.iter().all(|histogram| histogram.is_expired())
+    }
 }
 
 impl From<FanoutHistogram> for Histogram {
@@ -95,56 +161,156 @@ impl From<FanoutHistogram> for Histogram {
     }
 }
 
+pub(crate) struct FanoutSummary {
+    summaries: Vec<Summary>,
+}
+
+impl FanoutSummary {
+    pub fn from_summaries(summaries: Vec<Summary>) -> Self {
+        Self { summaries }
+    }
+}
+
+impl SummaryFn for FanoutSummary {
+    fn record(&self, value: f64) {
+        for summary in &self.summaries {
+            let _ = isolate(|| summary.record(value));
+        }
+    }
+}
+
+impl From<FanoutSummary> for Summary {
+    fn from(summary: FanoutSummary) -> Summary {
+        Summary::from_arc(Arc::new(summary))
+    }
+}
+
+type TargetFilter = Box<dyn Fn(&str) -> bool>;
+
+struct FanoutTarget {
+    recorder: Box<dyn Recorder>,
+    filter: Option<TargetFilter>,
+}
+
+impl FanoutTarget {
+    fn accepts(&self, name: &str) -> bool {
+        self.filter.as_ref().map_or(true, |filter| filter(name))
+    }
+}
+
 /// Fans out metrics to multiple recorders.
+///
+/// Each recorder can optionally have a filter attached, in which case only metrics whose name is
+/// accepted by the filter are sent to that recorder.
+///
+/// Recorders are isolated from each other: if a recorder, or any of the metric handles it returns,
+/// panics, the panic is caught so that the remaining recorders still receive the operation.  A
+/// recorder which panics during registration is given no handle for that metric, while the other
+/// recorders are unaffected.
 pub struct Fanout {
-    recorders: Vec<Box<dyn Recorder>>,
+    targets: Vec<FanoutTarget>,
+}
+
+impl Fanout {
+    fn targets_for<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a dyn Recorder> + 'a {
+        self.targets
+            .iter()
+            .filter(move |target| target.accepts(name))
+            .map(|target| target.recorder.as_ref())
+    }
 }
 
 impl Recorder for Fanout {
     fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
-        for recorder in &self.recorders {
-            recorder.describe_counter(key_name.clone(), unit, description.clone());
+        for recorder in self.targets_for(key_name.as_str()) {
+            let _ = isolate(|| {
+                recorder.describe_counter(key_name.clone(), unit.clone(), description.clone())
+            });
         }
     }
 
     fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
-        for recorder in &self.recorders {
-            recorder.describe_gauge(key_name.clone(), unit, description.clone());
+        for recorder in self.targets_for(key_name.as_str()) {
+            let _ = isolate(|| {
+                recorder.describe_gauge(key_name.clone(), unit.clone(), description.clone())
+            });
         }
     }
 
     fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
-        for recorder in &self.recorders {
-            recorder.describe_histogram(key_name.clone(), unit, description.clone());
+        for recorder in self.targets_for(key_name.as_str()) {
+            let _ = isolate(|| {
+                recorder.describe_histogram(key_name.clone(), unit.clone(), description.clone())
+            });
         }
     }
 
     fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
         let counters = self
-            .recorders
-            .iter()
-            .map(|recorder| recorder.register_counter(key, metadata))
+            .targets_for(key.name())
+            .filter_map(|recorder| isolate(|| recorder.register_counter(key, metadata)))
             .collect();
 
         FanoutCounter::from_counters(counters).into()
     }
 
     fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
-        let gauges =
-            self.recorders.iter().map(|recorder| recorder.register_gauge(key, metadata)).collect();
+        let gauges = self
+            .targets_for(key.name())
+            .filter_map(|recorder| isolate(|| recorder.register_gauge(key, metadata)))
+            .collect();
 
         FanoutGauge::from_gauges(gauges).into()
     }
 
     fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
         let histograms = self
-            .recorders
-            .iter()
-            .map(|recorder| recorder.register_histogram(key, metadata))
+            .targets_for(key.name())
+            .filter_map(|recorder| isolate(|| recorder.register_histogram(key, metadata)))
             .collect();
 
         FanoutHistogram::from_histograms(histograms).into()
     }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        for recorder in self.targets_for(key_name.as_str()) {
+            let _ = isolate(|| {
+                recorder.describe_summary(key_name.clone(), unit.clone(), description.clone())
+            });
+        }
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let summaries = self
+            .targets_for(key.name())
+            .filter_map(|recorder| isolate(|| recorder.register_summary(key, metadata)))
+            .collect();
+
+        FanoutSummary::from_summaries(summaries).into()
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        for recorder in self.targets_for(key_name.as_str()) {
+            let _ = isolate(|| recorder.describe_attributes(key_name.clone(), attributes.clone()));
+        }
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.targets_for(key_name.as_str())
+            .any(|recorder| isolate(|| recorder.enabled(key_name, metadata)).unwrap_or(false))
+    }
+
+    fn flush(&self) {
+        for target in &self.targets {
+            let _ = isolate(|| target.recorder.flush());
+        }
+    }
+
+    fn shutdown(&self) {
+        for target in &self.targets {
+            let _ = isolate(|| target.recorder.shutdown());
+        }
+    }
 }
 
 /// A layer for fanning out metrics to multiple recorders.
@@ -152,7 +318,7 @@ impl Recorder for Fanout {
 /// More information on the behavior of the layer can be found in [`Fanout`].
 #[derive(Default)]
 pub struct FanoutBuilder {
-    recorders: Vec<Box<dyn Recorder>>,
+    targets: Vec<FanoutTarget>,
 }
 
 impl FanoutBuilder {
@@ -161,21 +327,68 @@ impl FanoutBuilder {
     where
         R: Recorder + 'static,
     {
-        self.recorders.push(Box::new(recorder));
+        self.targets.push(FanoutTarget { recorder: Box::new(recorder), filter: None });
+        self
+    }
+
+    /// Adds a recorder to the fanout list, which only receives metrics accepted by `filter`.
+    ///
+    /// `filter` is called with the name of each metric, and should return `true` if the metric
+    /// should be sent to the recorder.
+    pub fn add_recorder_with_filter<R, F>(mut self, recorder: R, filter: F) -> FanoutBuilder
+    where
+        R: Recorder + 'static,
+        F: Fn(&str) -> bool + 'static,
+    {
+        self.targets
+            .push(FanoutTarget { recorder: Box::new(recorder), filter: Some(Box::new(filter)) });
         self
     }
 
     /// Builds the `Fanout` layer.
     pub fn build(self) -> Fanout {
-        Fanout { recorders: self.recorders }
+        Fanout { targets: self.targets }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::FanoutBuilder;
+    use std::sync::{atomic::Ordering, Arc};
+
+    use super::{FanoutBuilder, FanoutCounter};
     use crate::test_util::*;
-    use metrics::{Counter, Gauge, Histogram, Unit};
+    use metrics::{
+        atomics::AtomicU64, AbsoluteCounter, Counter, CounterFn, Gauge, Histogram, Key, KeyName,
+        Metadata, Recorder, SharedString, Unit,
+    };
+
+    struct PanickingRecorder;
+
+    impl Recorder for PanickingRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {
+            panic!("describe_counter");
+        }
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {
+            panic!("describe_gauge");
+        }
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {
+            panic!("describe_histogram");
+        }
+
+        fn register_counter(&self, _: &Key, _: &Metadata<'_>) -> Counter {
+            panic!("register_counter");
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            panic!("register_gauge");
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            panic!("register_histogram");
+        }
+    }
 
     static METADATA: metrics::Metadata =
         metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
@@ -216,4 +429,122 @@ mod tests {
             operation.apply_to_recorder(&fanout);
         }
     }
+
+    #[test]
+    fn test_absolute_counter() {
+        let first = Arc::new(AtomicU64::new(0));
+        let second = Arc::new(AtomicU64::new(0));
+
+        let mut recorder1 = MockBasicRecorder::new();
+        expect_register_counter(
+            &mut recorder1,
+            "counter_key".into(),
+            Counter::from_arc(first.clone()),
+        );
+        let mut recorder2 = MockBasicRecorder::new();
+        expect_register_counter(
+            &mut recorder2,
+            "counter_key".into(),
+            Counter::from_arc(second.clone()),
+        );
+
+        let fanout =
+            FanoutBuilder::default().add_recorder(recorder1).add_recorder(recorder2).build();
+        let counter = AbsoluteCounter::from_counter(
+            fanout.register_counter(&"counter_key".into(), &METADATA),
+        );
+
+        counter.set(42);
+        counter.set(17);
+
+        assert_eq!(first.load(Ordering::Acquire), 42);
+        assert_eq!(second.load(Ordering::Acquire), 42);
+    }
+
+    #[test]
+    fn test_per_recorder_filter() {
+        let operations = vec![
+            RecorderOperation::DescribeCounter(
+                "db.queries".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("db.queries".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("http.connections".into(), Gauge::noop(), &METADATA),
+        ];
+
+        let db_operations = operations[..2].to_vec();
+        let all_recorder = MockBasicRecorder::from_operations(operations.clone());
+        let db_recorder = MockBasicRecorder::from_operations(db_operations);
+        let fanout = FanoutBuilder::default()
+            .add_recorder(all_recorder)
+            .add_recorder_with_filter(db_recorder, |name| name.starts_with("db."))
+            .build();
+
+        for operation in operations {
+            operation.apply_to_recorder(&fanout);
+        }
+    }
+
+    #[test]
+    fn test_is_expired() {
+        struct ExpiringCounter(bool);
+
+        impl CounterFn for ExpiringCounter {
+            fn increment(&self, _: u64) {}
+
+            fn absolute(&self, _: u64) {}
+
+            fn is_expired(&self) -> bool {
+                self.0
+            }
+        }
+
+        let counter = |expired| Counter::from_arc(Arc::new(ExpiringCounter(expired)));
+
+        let partial = FanoutCounter::from_counters(vec![counter(true), counter(false)]);
+        assert!(!partial.is_expired());
+
+        let all = FanoutCounter::from_counters(vec![counter(true), counter(true)]);
+        assert!(all.is_expired());
+
+        let empty = FanoutCounter::from_counters(Vec::new());
+        assert!(!empty.is_expired());
+    }
+
+    #[test]
+    fn test_panic_isolation() {
+        let operations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(operations.clone());
+        let fanout =
+            FanoutBuilder::default().add_recorder(PanickingRecorder).add_recorder(recorder).build();
+
+        for operation in operations {
+            operation.apply_to_recorder(&fanout);
+        }
+    }
 }
@@ -1,6 +1,9 @@
 use crate::layers::Layer;
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder, AhoCorasickKind};
-use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
 
 /// Filters and discards metrics matching certain name patterns.
 ///
@@ -58,6 +61,39 @@ impl<R: Recorder> Recorder for Filter<R> {
         }
         self.inner.register_histogram(key, metadata)
     }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        if self.should_filter(key_name.as_str()) {
+            return;
+        }
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        if self.should_filter(key.name()) {
+            return Summary::noop();
+        }
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        if self.should_filter(key_name.as_str()) {
+            return;
+        }
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        !self.should_filter(key_name.as_str()) && self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
 }
 
 /// A layer for filtering and discarding metrics matching certain name patterns.
@@ -155,7 +191,7 @@ impl<R> Layer<R> for FilterLayer {
 mod tests {
     use super::FilterLayer;
     use crate::{layers::Layer, test_util::*};
-    use metrics::{Counter, Gauge, Histogram, Unit};
+    use metrics::{Attributes, Counter, Gauge, Histogram, Summary, TimeBase, Unit};
 
     static METADATA: metrics::Metadata =
         metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
@@ -201,6 +237,29 @@ mod tests {
                 &METADATA,
             ),
             RecorderOperation::RegisterGauge("bb8.pooled_conns".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::DescribeSummary(
+                "tokio.poll_time".into(),
+                Some(Unit::Seconds),
+                "summary desc".into(),
+            ),
+            RecorderOperation::RegisterSummary(
+                "tokio.poll_time".into(),
+                Summary::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterSummary(
+                "hyper.request_size".into(),
+                Summary::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::DescribeAttributes(
+                "tokio.poll_time".into(),
+                Attributes::new().with(TimeBase::Microseconds),
+            ),
+            RecorderOperation::DescribeAttributes(
+                "hyper.response_latency".into(),
+                Attributes::new().with(TimeBase::Milliseconds),
+            ),
         ];
 
         let expectations = vec![
@@ -220,10 +279,19 @@ mod tests {
                 Histogram::noop(),
                 &METADATA,
             ),
+            RecorderOperation::RegisterSummary(
+                "hyper.request_size".into(),
+                Summary::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::DescribeAttributes(
+                "hyper.response_latency".into(),
+                Attributes::new().with(TimeBase::Milliseconds),
+            ),
         ];
 
         let recorder = MockBasicRecorder::from_operations(expectations);
-        let filter = FilterLayer::from_patterns(&["tokio", "bb8"]);
+        let filter = FilterLayer::from_patterns(["tokio", "bb8"]);
         let filter = filter.layer(recorder);
 
         for operation in inputs {
@@ -294,7 +362,7 @@ mod tests {
         ];
 
         let recorder = MockBasicRecorder::from_operations(expectations);
-        let mut filter = FilterLayer::from_patterns(&["tokio", "bb8"]);
+        let mut filter = FilterLayer::from_patterns(["tokio", "bb8"]);
         let filter = filter.case_insensitive(true).layer(recorder);
 
         for operation in inputs {
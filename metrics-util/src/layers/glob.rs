@@ -0,0 +1,83 @@
+/// A shell-style glob pattern.
+///
+/// Supports `*`, which matches any sequence of characters (including an empty one), and `?`, which
+/// matches exactly one character.  All other characters match themselves.
+#[derive(Clone, Debug)]
+pub(crate) struct Glob {
+    pattern: Vec<char>,
+}
+
+impl Glob {
+    pub fn new<P: AsRef<str>>(pattern: P) -> Glob {
+        Glob { pattern: pattern.as_ref().chars().collect() }
+    }
+
+    pub fn matches(&self, input: &str) -> bool {
+        let input = input.chars().collect::<Vec<_>>();
+
+        let (mut p, mut i) = (0, 0);
+        // Position of the last `*` seen in the pattern, and the input position it was matched at,
+        // so that we can backtrack and let the wildcard consume one more character on a mismatch.
+        let mut backtrack = None;
+
+        while i < input.len() {
+            match self.pattern.get(p) {
+                Some('*') => {
+                    backtrack = Some((p, i));
+                    p += 1;
+                }
+                Some(&c) if c == '?' || c == input[i] => {
+                    p += 1;
+                    i += 1;
+                }
+                _ => match backtrack {
+                    Some((star_p, star_i)) => {
+                        backtrack = Some((star_p, star_i + 1));
+                        p = star_p + 1;
+                        i = star_i + 1;
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        self.pattern[p..].iter().all(|c| *c == '*')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Glob;
+
+    #[test]
+    fn test_matches() {
+        let cases = [
+            ("foo", "foo", true),
+            ("foo", "foobar", false),
+            ("foo*", "foo", true),
+            ("foo*", "foo.bar.baz", true),
+            ("*.bar", "foo.bar", true),
+            ("*.bar", "foo.baz", false),
+            ("db.*.latency", "db.postgres.latency", true),
+            ("db.*.latency", "db.postgres.errors", false),
+            ("f?o", "foo", true),
+            ("f?o", "fo", false),
+            ("*a*b*", "xxaxxbxx", true),
+            ("*a*b*", "xxbxxaxx", false),
+            ("*", "", true),
+            ("", "", true),
+            ("", "foo", false),
+            ("ü?", "üñ", true),
+        ];
+
+        for (pattern, input, expected) in cases {
+            assert_eq!(
+                Glob::new(pattern).matches(input),
+                expected,
+                "pattern {:?} vs input {:?}",
+                pattern,
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use crate::layers::{glob::Glob, Layer};
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
+
+/// Whether metrics matching the configured patterns are kept or discarded.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GlobFilterMode {
+    /// Only metrics which match at least one pattern are kept.
+    Allow,
+
+    /// Metrics which match at least one pattern are discarded.
+    #[default]
+    Deny,
+}
+
+/// Filters and discards metrics based on shell-style glob patterns.
+///
+/// More information on the behavior of the layer can be found in [`GlobFilterLayer`].
+pub struct GlobFilter<R> {
+    inner: R,
+    globs: Vec<Glob>,
+    mode: GlobFilterMode,
+}
+
+impl<R> GlobFilter<R> {
+    fn should_filter(&self, key: &str) -> bool {
+        let matched = self.globs.iter().any(|glob| glob.matches(key));
+        match self.mode {
+            GlobFilterMode::Allow => !matched,
+            GlobFilterMode::Deny => matched,
+        }
+    }
+}
+
+impl<R: Recorder> Recorder for GlobFilter<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        if self.should_filter(key_name.as_str()) {
+            return;
+        }
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        if self.should_filter(key_name.as_str()) {
+            return;
+        }
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        if self.should_filter(key_name.as_str()) {
+            return;
+        }
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        if self.should_filter(key.name()) {
+            return Counter::noop();
+        }
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        if self.should_filter(key.name()) {
+            return Gauge::noop();
+        }
+        self.inner.register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        if self.should_filter(key.name()) {
+            return Histogram::noop();
+        }
+        self.inner.register_histogram(key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        if self.should_filter(key_name.as_str()) {
+            return;
+        }
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        if self.should_filter(key.name()) {
+            return Summary::noop();
+        }
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        if self.should_filter(key_name.as_str()) {
+            return;
+        }
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        !self.should_filter(key_name.as_str()) && self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for filtering and discarding metrics based on shell-style glob patterns.
+///
+/// Patterns are matched against the entire metric name, where `*` matches any number of characters
+/// (including none) and `?` matches exactly one character.  For example, `db.*` matches
+/// `db.query.latency`, but not `http.db.query`.
+///
+/// In deny mode, which is the default, a metric matching any of the configured patterns will be
+/// skipped entirely.  In allow mode, only metrics matching at least one of the configured patterns
+/// are kept, and every other metric is skipped entirely.  This applies equally to metric
+/// registration and metric emission.
+#[derive(Default)]
+pub struct GlobFilterLayer {
+    patterns: Vec<String>,
+    mode: GlobFilterMode,
+}
+
+impl GlobFilterLayer {
+    /// Creates a [`GlobFilterLayer`] from an existing set of patterns.
+    pub fn from_patterns<P, I>(patterns: P) -> Self
+    where
+        P: IntoIterator<Item = I>,
+        I: AsRef<str>,
+    {
+        GlobFilterLayer {
+            patterns: patterns.into_iter().map(|s| s.as_ref().to_string()).collect(),
+            mode: GlobFilterMode::Deny,
+        }
+    }
+
+    /// Adds a pattern to match.
+    pub fn add_pattern<P>(&mut self, pattern: P) -> &mut GlobFilterLayer
+    where
+        P: AsRef<str>,
+    {
+        self.patterns.push(pattern.as_ref().to_string());
+        self
+    }
+
+    /// Sets whether matching metrics are kept or discarded.
+    ///
+    /// Defaults to [`GlobFilterMode::Deny`] i.e. matching metrics are discarded.
+    pub fn mode(&mut self, mode: GlobFilterMode) -> &mut GlobFilterLayer {
+        self.mode = mode;
+        self
+    }
+}
+
+impl<R> Layer<R> for GlobFilterLayer {
+    type Output = GlobFilter<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        let globs = self.patterns.iter().map(Glob::new).collect();
+        GlobFilter { inner, globs, mode: self.mode }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GlobFilterLayer, GlobFilterMode};
+    use crate::{layers::Layer, test_util::*};
+    use metrics::{Counter, Gauge, Histogram, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    fn inputs() -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::DescribeCounter(
+                "db.query.count".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "http.connections".into(),
+                Some(Unit::Count),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("db.query.count".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("http.connections".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "db.pool.latency".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                "http.latency".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_deny_mode() {
+        let expectations = vec![
+            RecorderOperation::DescribeGauge(
+                "http.connections".into(),
+                Some(Unit::Count),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::RegisterGauge("http.connections".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "http.latency".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let filter = GlobFilterLayer::from_patterns(["db.*"]);
+        let filter = filter.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&filter);
+        }
+    }
+
+    #[test]
+    fn test_allow_mode() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "db.query.count".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("db.query.count".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "db.pool.latency".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                "http.latency".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut filter = GlobFilterLayer::default();
+        filter.add_pattern("db.query.*").add_pattern("*.latency").mode(GlobFilterMode::Allow);
+        let filter = filter.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&filter);
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Summary, SummaryFn, Unit,
+};
+
+/// A cheaper representation to downgrade a histogram to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DowngradeMode {
+    /// Records the number of observations in a counter named `<name>_count`, and the sum of all
+    /// observations in a gauge named `<name>_sum`.
+    ///
+    /// The sum is kept in a gauge, rather than a counter, as observations are floating-point values.
+    CountSum,
+
+    /// Records the most recent observation in a gauge with the same name as the histogram.
+    LastValue,
+}
+
+struct CountSumHistogram {
+    count: Counter,
+    sum: Gauge,
+}
+
+impl HistogramFn for CountSumHistogram {
+    fn record(&self, value: f64) {
+        self.count.increment(1);
+        self.sum.increment(value);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        self.count.increment(values.len() as u64);
+        self.sum.increment(values.iter().sum::<f64>());
+    }
+
+    fn is_expired(&self) -> bool {
+        self.count.is_expired() || self.sum.is_expired()
+    }
+}
+
+impl SummaryFn for CountSumHistogram {
+    fn record(&self, value: f64) {
+        HistogramFn::record(self, value)
+    }
+}
+
+struct LastValueHistogram {
+    gauge: Gauge,
+}
+
+impl HistogramFn for LastValueHistogram {
+    fn record(&self, value: f64) {
+        self.gauge.set(value);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        if let Some(&value) = values.last() {
+            self.gauge.set(value);
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.gauge.is_expired()
+    }
+}
+
+impl SummaryFn for LastValueHistogram {
+    fn record(&self, value: f64) {
+        HistogramFn::record(self, value)
+    }
+}
+
+fn suffixed(name: &str, suffix: &str) -> String {
+    let mut new_name = String::with_capacity(name.len() + suffix.len());
+    new_name.push_str(name);
+    new_name.push_str(suffix);
+    new_name
+}
+
+/// Downgrades specific histograms to a cheaper representation.
+///
+/// More information on the behavior of the layer can be found in [`HistogramDowngradeLayer`].
+pub struct HistogramDowngrade<R> {
+    rules: HashMap<String, DowngradeMode>,
+    inner: R,
+}
+
+impl<R: Recorder> Recorder for HistogramDowngrade<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        match self.rules.get(key_name.as_str()) {
+            Some(DowngradeMode::CountSum) => {
+                let count_name = suffixed(key_name.as_str(), "_count");
+                let sum_name = suffixed(key_name.as_str(), "_sum");
+                self.inner.describe_counter(
+                    count_name.into(),
+                    Some(Unit::Count),
+                    description.clone(),
+                );
+                self.inner.describe_gauge(sum_name.into(), unit, description)
+            }
+            Some(DowngradeMode::LastValue) => {
+                self.inner.describe_gauge(key_name, unit, description)
+            }
+            None => self.inner.describe_histogram(key_name, unit, description),
+        }
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        self.inner.register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        match self.rules.get(key.name()) {
+            Some(DowngradeMode::CountSum) => {
+                let count_key = Key::from_parts(suffixed(key.name(), "_count"), key.labels());
+                let sum_key = Key::from_parts(suffixed(key.name(), "_sum"), key.labels());
+                let count = self.inner.register_counter(&count_key, metadata);
+                let sum = self.inner.register_gauge(&sum_key, metadata);
+                Histogram::from_arc(Arc::new(CountSumHistogram { count, sum }))
+            }
+            Some(DowngradeMode::LastValue) => {
+                let gauge = self.inner.register_gauge(key, metadata);
+                Histogram::from_arc(Arc::new(LastValueHistogram { gauge }))
+            }
+            None => self.inner.register_histogram(key, metadata),
+        }
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        if self.rules.contains_key(key_name.as_str()) {
+            return self.describe_histogram(key_name, unit, description);
+        }
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        match self.rules.get(key.name()) {
+            Some(DowngradeMode::CountSum) => {
+                let count_key = Key::from_parts(suffixed(key.name(), "_count"), key.labels());
+                let sum_key = Key::from_parts(suffixed(key.name(), "_sum"), key.labels());
+                let count = self.inner.register_counter(&count_key, metadata);
+                let sum = self.inner.register_gauge(&sum_key, metadata);
+                Summary::from_arc(Arc::new(CountSumHistogram { count, sum }))
+            }
+            Some(DowngradeMode::LastValue) => {
+                let gauge = self.inner.register_gauge(key, metadata);
+                Summary::from_arc(Arc::new(LastValueHistogram { gauge }))
+            }
+            None => self.inner.register_summary(key, metadata),
+        }
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        match self.rules.get(key_name.as_str()) {
+            Some(DowngradeMode::CountSum) => {
+                let count_name = suffixed(key_name.as_str(), "_count");
+                let sum_name = suffixed(key_name.as_str(), "_sum");
+                self.inner.describe_attributes(count_name.into(), attributes.clone());
+                self.inner.describe_attributes(sum_name.into(), attributes)
+            }
+            _ => self.inner.describe_attributes(key_name, attributes),
+        }
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for downgrading specific histograms to a cheaper representation.
+///
+/// Full histograms can be expensive to store and export, and for some metrics, such as timings of
+/// rarely inspected operations, the distribution of values isn't needed at all.  This layer
+/// replaces the histograms with a configured name by either a count and a sum of observations, or
+/// by the most recent observation, as described by [`DowngradeMode`].
+///
+/// Summaries with a configured name are downgraded in the same way as histograms.
+///
+/// Descriptions of downgraded histograms are rewritten to describe the metrics that replace them.
+/// All other metrics are passed through unchanged.
+#[derive(Default)]
+pub struct HistogramDowngradeLayer {
+    rules: HashMap<String, DowngradeMode>,
+}
+
+impl HistogramDowngradeLayer {
+    /// Downgrades the histogram with the given name using the given mode.
+    ///
+    /// If the histogram was already configured to be downgraded, its mode will be overwritten.
+    pub fn downgrade<N: Into<String>>(
+        &mut self,
+        name: N,
+        mode: DowngradeMode,
+    ) -> &mut HistogramDowngradeLayer {
+        let _ = self.rules.insert(name.into(), mode);
+        self
+    }
+}
+
+impl<R> Layer<R> for HistogramDowngradeLayer {
+    type Output = HistogramDowngrade<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        HistogramDowngrade { rules: self.rules.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DowngradeMode, HistogramDowngradeLayer};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Attributes, Counter, Gauge, Histogram, Key, Label, Summary, TimeBase, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeHistogram(
+                "request_time".into(),
+                Some(Unit::Seconds),
+                "request time".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "queue_depth".into(),
+                Some(Unit::Count),
+                "queue depth".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Bytes),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("request_time", vec![Label::new("method", "GET")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                "queue_depth".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "request_time_count".into(),
+                Some(Unit::Count),
+                "request time".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "request_time_sum".into(),
+                Some(Unit::Seconds),
+                "request time".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "queue_depth".into(),
+                Some(Unit::Count),
+                "queue depth".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Bytes),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("request_time_count", vec![Label::new("method", "GET")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("request_time_sum", vec![Label::new("method", "GET")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge("queue_depth".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = HistogramDowngradeLayer::default();
+        layer
+            .downgrade("request_time", DowngradeMode::CountSum)
+            .downgrade("queue_depth", DowngradeMode::LastValue);
+        let downgrade = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&downgrade);
+        }
+    }
+
+    #[test]
+    fn test_summaries() {
+        let inputs = vec![
+            RecorderOperation::DescribeSummary(
+                "queue_depth".into(),
+                Some(Unit::Count),
+                "queue depth".into(),
+            ),
+            RecorderOperation::DescribeSummary(
+                "summary_key".into(),
+                Some(Unit::Bytes),
+                "summary desc".into(),
+            ),
+            RecorderOperation::RegisterSummary("request_time".into(), Summary::noop(), &METADATA),
+            RecorderOperation::RegisterSummary("queue_depth".into(), Summary::noop(), &METADATA),
+            RecorderOperation::RegisterSummary("summary_key".into(), Summary::noop(), &METADATA),
+            RecorderOperation::DescribeAttributes(
+                "request_time".into(),
+                Attributes::new().with(TimeBase::Milliseconds),
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeGauge(
+                "queue_depth".into(),
+                Some(Unit::Count),
+                "queue depth".into(),
+            ),
+            RecorderOperation::DescribeSummary(
+                "summary_key".into(),
+                Some(Unit::Bytes),
+                "summary desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                "request_time_count".into(),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge("request_time_sum".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("queue_depth".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterSummary("summary_key".into(), Summary::noop(), &METADATA),
+            RecorderOperation::DescribeAttributes(
+                "request_time_count".into(),
+                Attributes::new().with(TimeBase::Milliseconds),
+            ),
+            RecorderOperation::DescribeAttributes(
+                "request_time_sum".into(),
+                Attributes::new().with(TimeBase::Milliseconds),
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = HistogramDowngradeLayer::default();
+        layer
+            .downgrade("request_time", DowngradeMode::CountSum)
+            .downgrade("queue_depth", DowngradeMode::LastValue);
+        let downgrade = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&downgrade);
+        }
+    }
+
+    #[cfg(feature = "debugging")]
+    #[test]
+    fn test_downgraded_values() {
+        use crate::debugging::{DebugValue, DebuggingRecorder};
+        use crate::{CompositeKey, MetricKind};
+        use metrics::Recorder;
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let mut layer = HistogramDowngradeLayer::default();
+        layer
+            .downgrade("request_time", DowngradeMode::CountSum)
+            .downgrade("queue_depth", DowngradeMode::LastValue);
+        let downgrade = layer.layer(recorder);
+
+        let request_time = downgrade.register_histogram(&"request_time".into(), &METADATA);
+        request_time.record(1.5);
+        request_time.record_many(&[2.5, 4.0]);
+        let queue_depth = downgrade.register_histogram(&"queue_depth".into(), &METADATA);
+        queue_depth.record(3.0);
+        queue_depth.record_many(&[5.0, 7.0]);
+
+        #[allow(clippy::mutable_key_type)]
+        let snapshot = snapshotter.snapshot().into_hashmap();
+        let value = |kind, name| {
+            snapshot.get(&CompositeKey::new(kind, Key::from_static_name(name))).map(|(_, _, v)| v)
+        };
+
+        assert_eq!(value(MetricKind::Counter, "request_time_count"), Some(&DebugValue::Counter(3)));
+        assert_eq!(
+            value(MetricKind::Gauge, "request_time_sum"),
+            Some(&DebugValue::Gauge(8.0.into()))
+        );
+        assert_eq!(value(MetricKind::Gauge, "queue_depth"), Some(&DebugValue::Gauge(7.0.into())));
+        assert_eq!(value(MetricKind::Histogram, "request_time"), None);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, IntoLabels, Key, KeyName, Label, Metadata, Recorder,
+    SharedString, Summary, Unit,
+};
+
+/// Injects a fixed set of labels into every metric key.
+///
+/// Labels which already exist on a key, by label key, are not overridden: the value given at the
+/// call site always takes precedence over the injected value.
+pub struct LabelInject<R> {
+    labels: Vec<Label>,
+    inner: R,
+}
+
+impl<R> LabelInject<R> {
+    fn inject_labels(&self, key: &Key) -> Key {
+        let extra_labels = self
+            .labels
+            .iter()
+            .filter(|label| !key.labels().any(|existing| existing.key() == label.key()))
+            .cloned()
+            .collect();
+
+        key.with_extra_labels(extra_labels)
+    }
+}
+
+impl<R: Recorder> Recorder for LabelInject<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.inject_labels(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.inject_labels(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.inject_labels(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.inject_labels(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for injecting a fixed set of labels into every metric key.
+///
+/// More information on the behavior of the layer can be found in [`LabelInject`].
+#[derive(Default)]
+pub struct LabelInjectLayer {
+    labels: Vec<Label>,
+}
+
+impl LabelInjectLayer {
+    /// Creates a new `LabelInjectLayer` based on the given labels.
+    pub fn new<L: IntoLabels>(labels: L) -> LabelInjectLayer {
+        LabelInjectLayer { labels: labels.into_labels() }
+    }
+
+    /// Adds a label to inject.
+    ///
+    /// If a label with the same key has already been added, its value is replaced.
+    pub fn add_label<K, V>(&mut self, key: K, value: V) -> &mut LabelInjectLayer
+    where
+        K: Into<SharedString>,
+        V: Into<SharedString>,
+    {
+        let label = Label::new(key, value);
+        match self.labels.iter_mut().find(|existing| existing.key() == label.key()) {
+            Some(existing) => *existing = label,
+            None => self.labels.push(label),
+        }
+        self
+    }
+}
+
+impl<R> Layer<R> for LabelInjectLayer {
+    type Output = LabelInject<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        LabelInject { labels: self.labels.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LabelInjectLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("env", "dev")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("region", "us")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("service", "api"), Label::new("region", "eu")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts(
+                    "gauge_key",
+                    vec![
+                        Label::new("env", "dev"),
+                        Label::new("service", "api"),
+                        Label::new("region", "eu"),
+                    ],
+                ),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts(
+                    "histogram_key",
+                    vec![Label::new("region", "us"), Label::new("service", "api")],
+                ),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = LabelInjectLayer::new(vec![Label::new("service", "api")]);
+        layer.add_label("region", "eu");
+        let label_inject = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&label_inject);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString,
+    Summary, Unit,
+};
+
+/// What to do with a label whose value is too long.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum LengthAction {
+    /// Truncates the value, appending the given suffix.
+    Truncate(SharedString),
+
+    /// Removes the label entirely.
+    Drop,
+}
+
+/// Truncates the given value so that, with the suffix appended, it fits within `max_len` bytes.
+///
+/// Values are only ever truncated on character boundaries.  If the suffix alone doesn't fit, the
+/// value is truncated to `max_len` bytes without a suffix.
+fn truncate_value(value: &str, max_len: usize, suffix: &str) -> String {
+    let (budget, suffix) =
+        if suffix.len() < max_len { (max_len - suffix.len(), suffix) } else { (max_len, "") };
+
+    let mut end = budget;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let mut truncated = String::with_capacity(end + suffix.len());
+    truncated.push_str(&value[..end]);
+    truncated.push_str(suffix);
+    truncated
+}
+
+/// Limits the length of label values on every metric key.
+///
+/// More information on the behavior of the layer can be found in [`LabelLengthLayer`].
+pub struct LabelLength<R> {
+    max_len: usize,
+    action: LengthAction,
+    inner: R,
+}
+
+impl<R> LabelLength<R> {
+    fn limit_key(&self, key: &Key) -> Key {
+        if key.labels().all(|label| label.value().len() <= self.max_len) {
+            return key.clone();
+        }
+
+        let labels = key
+            .labels()
+            .filter_map(|label| {
+                if label.value().len() <= self.max_len {
+                    return Some(label.clone());
+                }
+
+                match &self.action {
+                    LengthAction::Drop => None,
+                    LengthAction::Truncate(suffix) => {
+                        let value = truncate_value(label.value(), self.max_len, suffix);
+                        Some(Label::new(label.key().to_owned(), value))
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Key::from_parts(key.name().to_owned(), labels)
+    }
+}
+
+impl<R: Recorder> Recorder for LabelLength<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.limit_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.limit_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.limit_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.limit_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for limiting the length of label values on every metric key.
+///
+/// Label values longer than the configured number of bytes, such as full URLs or SQL statements,
+/// are either truncated or dropped entirely.  By default, values are truncated, on a character
+/// boundary, to fit within the limit.  An optional suffix, such as `"…"`, can be appended to
+/// truncated values to make the truncation visible, in which case the value is shortened further
+/// so that it fits within the limit along with the suffix.
+#[derive(Clone, Debug)]
+pub struct LabelLengthLayer {
+    max_len: usize,
+    action: LengthAction,
+}
+
+impl LabelLengthLayer {
+    /// Creates a new `LabelLengthLayer` which limits label values to `max_len` bytes.
+    pub fn new(max_len: usize) -> LabelLengthLayer {
+        LabelLengthLayer { max_len, action: LengthAction::Truncate(SharedString::const_str("")) }
+    }
+
+    /// Truncates values longer than the limit, appending the given suffix.
+    pub fn truncate_with_suffix<S: Into<SharedString>>(
+        &mut self,
+        suffix: S,
+    ) -> &mut LabelLengthLayer {
+        self.action = LengthAction::Truncate(suffix.into());
+        self
+    }
+
+    /// Drops labels whose value is longer than the limit, instead of truncating them.
+    pub fn drop_oversized(&mut self) -> &mut LabelLengthLayer {
+        self.action = LengthAction::Drop;
+        self
+    }
+}
+
+impl<R> Layer<R> for LabelLengthLayer {
+    type Output = LabelLength<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        LabelLength { max_len: self.max_len, action: self.action.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate_value, LabelLengthLayer};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Key, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    fn inputs() -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("path", "/api/v1/users/42"), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("region", "eu")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_truncate() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("path", "/api/v1/"), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("region", "eu")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let layer = LabelLengthLayer::new(8);
+        let limited = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&limited);
+        }
+    }
+
+    #[test]
+    fn test_truncate_with_suffix() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("path", "/api/…"), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("region", "eu")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = LabelLengthLayer::new(8);
+        layer.truncate_with_suffix("…");
+        let limited = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&limited);
+        }
+    }
+
+    #[test]
+    fn test_drop_oversized() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("method", "GET")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("region", "eu")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = LabelLengthLayer::new(8);
+        layer.drop_oversized();
+        let limited = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&limited);
+        }
+    }
+
+    #[test]
+    fn test_truncate_value() {
+        assert_eq!(truncate_value("abcdef", 4, ""), "abcd");
+        assert_eq!(truncate_value("abcdef", 4, "..."), "a...");
+
+        // Suffixes which don't fit are left off.
+        assert_eq!(truncate_value("abcdef", 2, "..."), "ab");
+
+        // Multi-byte characters are never split.
+        assert_eq!(truncate_value("héllo", 2, ""), "h");
+        assert_eq!(truncate_value("日本語", 7, ""), "日本");
+    }
+}
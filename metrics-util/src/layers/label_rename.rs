@@ -0,0 +1,216 @@
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString,
+    Summary, Unit,
+};
+
+/// Renames label keys on every metric key.
+///
+/// More information on the behavior of the layer can be found in [`LabelRenameLayer`].
+pub struct LabelRename<R> {
+    renames: Vec<(SharedString, SharedString)>,
+    inner: R,
+}
+
+impl<R> LabelRename<R> {
+    fn renamed(&self, label_key: &str) -> Option<&SharedString> {
+        self.renames.iter().find(|(from, _)| from.as_ref() == label_key).map(|(_, to)| to)
+    }
+
+    fn rename_key(&self, key: &Key) -> Key {
+        let needs_renaming = key.labels().any(|label| self.renamed(label.key()).is_some());
+        if !needs_renaming {
+            return key.clone();
+        }
+
+        let mut labels: Vec<Label> = Vec::new();
+        for label in key.labels() {
+            let new_key = match self.renamed(label.key()) {
+                Some(new_key) => new_key.as_ref(),
+                None => label.key(),
+            };
+
+            // Labels which already had the new key take precedence over renamed ones.
+            match labels.iter().position(|existing| existing.key() == new_key) {
+                Some(_) if new_key != label.key() => {}
+                Some(i) => labels[i] = label.clone(),
+                None if new_key == label.key() => labels.push(label.clone()),
+                None => labels.push(Label::new(new_key.to_owned(), label.value().to_owned())),
+            }
+        }
+
+        Key::from_parts(key.name().to_owned(), labels)
+    }
+}
+
+impl<R: Recorder> Recorder for LabelRename<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.rename_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.rename_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.rename_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.rename_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for renaming label keys on every metric key.
+///
+/// Label keys are rewritten according to a fixed mapping, such as `status_code` to `code`, which
+/// allows unifying label naming across libraries without changing them.  Label values, and the
+/// order of labels, are left as-is.
+///
+/// If a key ends up with more than one label with the same key after renaming, such as when a key
+/// has both `svc` and `service` labels and `svc` is renamed to `service`, the label which already
+/// had that key is kept and the renamed label is dropped.
+#[derive(Default)]
+pub struct LabelRenameLayer {
+    renames: Vec<(SharedString, SharedString)>,
+}
+
+impl LabelRenameLayer {
+    /// Renames any label with the key `from` to have the key `to` instead.
+    ///
+    /// If a rename already exists for `from`, it will be overwritten.
+    pub fn rename_label<F, T>(&mut self, from: F, to: T) -> &mut LabelRenameLayer
+    where
+        F: Into<SharedString>,
+        T: Into<SharedString>,
+    {
+        let (from, to) = (from.into(), to.into());
+        match self.renames.iter_mut().find(|(k, _)| *k == from) {
+            Some(rename) => rename.1 = to,
+            None => self.renames.push((from, to)),
+        }
+        self
+    }
+}
+
+impl<R> Layer<R> for LabelRenameLayer {
+    type Output = LabelRename<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        LabelRename { renames: self.renames.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LabelRenameLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("status_code", "200"), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts(
+                    "gauge_key",
+                    vec![Label::new("svc", "api"), Label::new("service", "web")],
+                ),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("region", "eu")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("code", "200"), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("service", "web")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("region", "eu")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = LabelRenameLayer::default();
+        layer.rename_label("status_code", "code").rename_label("svc", "service");
+        let rename = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&rename);
+        }
+    }
+}
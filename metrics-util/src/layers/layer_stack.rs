@@ -0,0 +1,208 @@
+use crate::layers::Layer;
+use metrics::{Recorder, SetRecorderError};
+
+/// A layer which leaves the object it wraps unchanged.
+///
+/// Used as the starting point of a [`LayerStack`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Identity;
+
+impl<R> Layer<R> for Identity {
+    type Output = R;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        inner
+    }
+}
+
+/// Two layers composed together, with `outer` wrapping the output of `inner`.
+///
+/// Created by [`LayerStack::push`].
+#[derive(Clone, Debug)]
+pub struct Stacked<I, O> {
+    inner: I,
+    outer: O,
+}
+
+impl<R, I, O> Layer<R> for Stacked<I, O>
+where
+    I: Layer<R>,
+    O: Layer<I::Output>,
+{
+    type Output = O::Output;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        self.outer.layer(self.inner.layer(inner))
+    }
+}
+
+/// Builder for composing layers together before applying them to a recorder.
+///
+/// Unlike [`Stack`](crate::layers::Stack), which wraps a recorder as each layer is pushed, a
+/// `LayerStack` only holds the layers themselves, so it can be defined up front, passed around, and
+/// applied to a recorder later on, or even to several recorders.
+///
+/// Layers are applied in the same order as with `Stack`: each layer pushed wraps all of the layers
+/// pushed before it, so the last layer pushed is the first to see each operation.
+///
+/// Applying the stack preserves the concrete type of the resulting recorder, via
+/// [`LayerStack::build`], or erases it to a `Box<dyn Recorder>`, via [`LayerStack::build_boxed`],
+/// which is useful when the layers in use are chosen at runtime.
+///
+/// ```no_run
+/// # use metrics::NoopRecorder as BasicRecorder;
+/// # use metrics_util::layers::{FilterLayer, LayerStack, PrefixLayer};
+/// # fn main() {
+/// LayerStack::new()
+///     .push(FilterLayer::from_patterns(["internal"]))
+///     .push(PrefixLayer::new("app"))
+///     .install(BasicRecorder)
+///     .expect("failed to install stack");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LayerStack<L> {
+    layers: L,
+}
+
+impl LayerStack<Identity> {
+    /// Creates a new, empty `LayerStack`.
+    pub fn new() -> Self {
+        LayerStack { layers: Identity }
+    }
+}
+
+impl Default for LayerStack<Identity> {
+    fn default() -> Self {
+        LayerStack::new()
+    }
+}
+
+impl<L> LayerStack<L> {
+    /// Pushes the given layer on to the stack, wrapping all of the layers pushed so far.
+    pub fn push<O>(self, layer: O) -> LayerStack<Stacked<L, O>> {
+        LayerStack { layers: Stacked { inner: self.layers, outer: layer } }
+    }
+
+    /// Applies the layers in this stack to the given recorder.
+    pub fn build<R>(&self, recorder: R) -> L::Output
+    where
+        L: Layer<R>,
+    {
+        self.layers.layer(recorder)
+    }
+
+    /// Applies the layers in this stack to the given recorder, erasing the type of the result.
+    pub fn build_boxed<R>(&self, recorder: R) -> Box<dyn Recorder + Send + Sync>
+    where
+        L: Layer<R>,
+        L::Output: Recorder + Send + Sync + 'static,
+    {
+        Box::new(self.build(recorder))
+    }
+
+    /// Applies the layers in this stack to the given recorder, and installs the result as the
+    /// global recorder.
+    ///
+    /// An error will be returned if there's an issue with installing the result as the global
+    /// recorder.
+    pub fn install<R>(&self, recorder: R) -> Result<(), SetRecorderError<L::Output>>
+    where
+        L: Layer<R>,
+        L::Output: Recorder + 'static,
+    {
+        metrics::set_global_recorder(self.build(recorder))
+    }
+}
+
+impl<R, L: Layer<R>> Layer<R> for LayerStack<L> {
+    type Output = L::Output;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        self.layers.layer(inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LayerStack;
+    use crate::layers::{LabelInjectLayer, PrefixLayer, SuffixLayer};
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Key, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    fn inputs() -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+        ]
+    }
+
+    fn expectations() -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::DescribeCounter(
+                "app.counter_key.v2".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("app.counter_key.v2", vec![Label::new("service", "api")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("app.gauge_key.v2", vec![Label::new("service", "api")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_build() {
+        let recorder = MockBasicRecorder::from_operations(expectations());
+        let layered = LayerStack::new()
+            .push(LabelInjectLayer::new(vec![Label::new("service", "api")]))
+            .push(SuffixLayer::new("v2"))
+            .push(PrefixLayer::new("app"))
+            .build(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&layered);
+        }
+    }
+
+    #[test]
+    fn test_build_boxed() {
+        let recorder = MockBasicRecorder::from_operations(expectations());
+        let layered = LayerStack::new()
+            .push(LabelInjectLayer::new(vec![Label::new("service", "api")]))
+            .push(SuffixLayer::new("v2"))
+            .push(PrefixLayer::new("app"))
+            .build_boxed(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&layered);
+        }
+    }
+
+    #[test]
+    fn test_nested_stack() {
+        let recorder = MockBasicRecorder::from_operations(expectations());
+        let inner = LayerStack::new()
+            .push(LabelInjectLayer::new(vec![Label::new("service", "api")]))
+            .push(SuffixLayer::new("v2"));
+        let layered = LayerStack::new().push(inner).push(PrefixLayer::new("app")).build(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&layered);
+        }
+    }
+}
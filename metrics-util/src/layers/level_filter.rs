@@ -0,0 +1,333 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Level, Metadata, Recorder, SharedString,
+    Summary, Unit,
+};
+
+/// A minimum level for a target, where `None` disables the target entirely.
+type Directive = (String, Option<Level>);
+
+/// Returns `true` if the given target is the directive target itself, or one of its submodules.
+fn target_matches(target: &str, directive: &str) -> bool {
+    match target.strip_prefix(directive) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+/// Parses a level, or `off`, ignoring case.
+fn parse_level(s: &str) -> Option<Option<Level>> {
+    if s.eq_ignore_ascii_case("off") {
+        Some(None)
+    } else {
+        s.parse::<Level>().ok().map(Some)
+    }
+}
+
+/// Error returned when parsing level filter directives fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseDirectiveError {
+    directive: String,
+}
+
+impl fmt::Display for ParseDirectiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid level filter directive: `{}`", self.directive)
+    }
+}
+
+impl Error for ParseDirectiveError {}
+
+/// Filters metrics based on the level and target of their metadata.
+///
+/// More information on the behavior of the layer can be found in [`LevelFilterLayer`].
+pub struct LevelFilter<R> {
+    inner: R,
+    default: Option<Level>,
+    directives: Vec<Directive>,
+}
+
+impl<R> LevelFilter<R> {
+    fn allows(&self, metadata: &Metadata<'_>) -> bool {
+        let min_level = self
+            .directives
+            .iter()
+            .find(|(target, _)| target_matches(metadata.target(), target))
+            .map_or(&self.default, |(_, level)| level);
+
+        match min_level {
+            Some(min_level) => metadata.level() >= min_level,
+            None => false,
+        }
+    }
+}
+
+impl<R: Recorder> Recorder for LevelFilter<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        if !self.allows(metadata) {
+            return Counter::noop();
+        }
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        if !self.allows(metadata) {
+            return Gauge::noop();
+        }
+        self.inner.register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        if !self.allows(metadata) {
+            return Histogram::noop();
+        }
+        self.inner.register_histogram(key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        if !self.allows(metadata) {
+            return Summary::noop();
+        }
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.allows(metadata) && self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for filtering metrics based on the level and target of their metadata.
+///
+/// Every metric is registered with a [`Level`] and a target, which defaults to the module path of
+/// the callsite, via the `level:` and `target:` arguments of the registration macros.  This layer
+/// discards metrics whose level is below the minimum level configured for their target, returning
+/// no-op handles for them, in the same way that `RUST_LOG` controls the verbosity of logging.
+///
+/// Minimum levels can be set for specific targets, and apply to the target itself as well as all of
+/// its submodules, i.e. a level set for `my_app::db` also applies to `my_app::db::pool`, but not
+/// to `my_app::dbx`.  When multiple targets match, the most specific one wins.  Metrics whose
+/// target doesn't match any configured target use the default minimum level.
+///
+/// Levels can also be configured from a string of comma-separated directives, via
+/// [`LevelFilterLayer::from_directives`], using the same syntax as `RUST_LOG`:
+///
+/// ```
+/// # use metrics_util::layers::LevelFilterLayer;
+/// // Only record metrics at `INFO` or above, except for `my_app::db`, where metrics at `DEBUG` or
+/// // above are recorded, and `noisy_dependency`, where no metrics are recorded at all.
+/// let layer = LevelFilterLayer::from_directives("info,my_app::db=debug,noisy_dependency=off")
+///     .expect("directives should be valid");
+/// ```
+///
+/// As descriptions carry no metadata, they are always passed through.
+#[derive(Clone, Debug)]
+pub struct LevelFilterLayer {
+    default: Option<Level>,
+    directives: Vec<Directive>,
+}
+
+impl LevelFilterLayer {
+    /// Creates a new `LevelFilterLayer` which only records metrics at the given level or above,
+    /// unless configured otherwise for their target.
+    pub fn new(default: Level) -> LevelFilterLayer {
+        LevelFilterLayer { default: Some(default), directives: Vec::new() }
+    }
+
+    /// Creates a new `LevelFilterLayer` from a string of comma-separated directives.
+    ///
+    /// Each directive is either a level, which sets the default minimum level, a target and a
+    /// level, separated by `=`, which sets the minimum level for that target, or a bare target,
+    /// which enables all levels for that target.  Levels are parsed case-insensitively, and `off`
+    /// can be used in place of a level to disable metrics entirely.  If no default level is given,
+    /// metrics at all levels are recorded for targets without a directive.
+    ///
+    /// An error will be returned if any of the directives are invalid.
+    pub fn from_directives(directives: &str) -> Result<LevelFilterLayer, ParseDirectiveError> {
+        let mut layer = LevelFilterLayer::new(Level::TRACE);
+
+        for directive in directives.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let invalid = || ParseDirectiveError { directive: directive.to_owned() };
+
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    let level = parse_level(level.trim()).ok_or_else(invalid)?;
+                    if target.is_empty() {
+                        return Err(invalid());
+                    }
+                    layer.set_directive(target, level);
+                }
+                None => match parse_level(directive) {
+                    Some(level) => layer.default = level,
+                    None => layer.set_directive(directive, Some(Level::TRACE)),
+                },
+            }
+        }
+
+        Ok(layer)
+    }
+
+    /// Sets the minimum level for the given target, and all of its submodules.
+    ///
+    /// If a minimum level was already set for the target, it will be overwritten.
+    pub fn target_level<T: Into<String>>(
+        &mut self,
+        target: T,
+        level: Level,
+    ) -> &mut LevelFilterLayer {
+        self.set_directive(target, Some(level));
+        self
+    }
+
+    /// Disables all metrics for the given target, and all of its submodules.
+    pub fn disable_target<T: Into<String>>(&mut self, target: T) -> &mut LevelFilterLayer {
+        self.set_directive(target, None);
+        self
+    }
+
+    fn set_directive<T: Into<String>>(&mut self, target: T, level: Option<Level>) {
+        let target = target.into();
+        self.directives.retain(|(existing, _)| existing != &target);
+        self.directives.push((target, level));
+
+        // Keep the most specific targets first, so that the first match is the best match.
+        self.directives.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+    }
+}
+
+impl<R> Layer<R> for LevelFilterLayer {
+    type Output = LevelFilter<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        LevelFilter { inner, default: self.default.clone(), directives: self.directives.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LevelFilterLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, KeyName, Level, Metadata, Recorder};
+
+    static APP_INFO: Metadata = Metadata::new("my_app", Level::INFO, None);
+    static APP_DEBUG: Metadata = Metadata::new("my_app::http", Level::DEBUG, None);
+    static DB_DEBUG: Metadata = Metadata::new("my_app::db::pool", Level::DEBUG, None);
+    static DB_TRACE: Metadata = Metadata::new("my_app::db", Level::TRACE, None);
+    static DBX_DEBUG: Metadata = Metadata::new("my_app::dbx", Level::DEBUG, None);
+    static NOISY_ERROR: Metadata = Metadata::new("noisy_dependency", Level::ERROR, None);
+
+    fn inputs() -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::RegisterCounter("app_info".into(), Counter::noop(), &APP_INFO),
+            RecorderOperation::RegisterCounter("app_debug".into(), Counter::noop(), &APP_DEBUG),
+            RecorderOperation::RegisterGauge("db_debug".into(), Gauge::noop(), &DB_DEBUG),
+            RecorderOperation::RegisterGauge("db_trace".into(), Gauge::noop(), &DB_TRACE),
+            RecorderOperation::RegisterHistogram("dbx_debug".into(), Histogram::noop(), &DBX_DEBUG),
+            RecorderOperation::RegisterHistogram(
+                "noisy_error".into(),
+                Histogram::noop(),
+                &NOISY_ERROR,
+            ),
+        ]
+    }
+
+    fn expectations() -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::RegisterCounter("app_info".into(), Counter::noop(), &APP_INFO),
+            RecorderOperation::RegisterGauge("db_debug".into(), Gauge::noop(), &DB_DEBUG),
+        ]
+    }
+
+    #[test]
+    fn test_basic_functionality() {
+        let recorder = MockBasicRecorder::from_operations(expectations());
+        let mut layer = LevelFilterLayer::new(Level::INFO);
+        layer.target_level("my_app::db", Level::DEBUG).disable_target("noisy_dependency");
+        let filter = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&filter);
+        }
+    }
+
+    #[test]
+    fn test_enabled() {
+        let recorder = MockBasicRecorder::from_operations(Vec::new());
+        let mut layer = LevelFilterLayer::new(Level::INFO);
+        layer.target_level("my_app::db", Level::DEBUG);
+        let filter = layer.layer(recorder);
+
+        let name = KeyName::from("any");
+        assert!(filter.enabled(&name, &APP_INFO));
+        assert!(!filter.enabled(&name, &APP_DEBUG));
+        assert!(filter.enabled(&name, &DB_DEBUG));
+        assert!(!filter.enabled(&name, &DB_TRACE));
+    }
+
+    #[test]
+    fn test_from_directives() {
+        let recorder = MockBasicRecorder::from_operations(expectations());
+        let layer =
+            LevelFilterLayer::from_directives(" INFO, my_app::db=debug ,noisy_dependency=off,")
+                .expect("directives should be valid");
+        let filter = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&filter);
+        }
+    }
+
+    #[test]
+    fn test_from_directives_without_default() {
+        let mut expectations = inputs();
+        expectations.pop();
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let layer = LevelFilterLayer::from_directives("noisy_dependency=off")
+            .expect("directives should be valid");
+        let filter = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&filter);
+        }
+    }
+
+    #[test]
+    fn test_invalid_directives() {
+        assert!(LevelFilterLayer::from_directives("my_app=verbose").is_err());
+        assert!(LevelFilterLayer::from_directives("=debug").is_err());
+        assert!(LevelFilterLayer::from_directives("my_app=").is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
+
+/// Applies an arbitrary transformation to every metric key.
+///
+/// More information on the behavior of the layer can be found in [`MapLayer`].
+pub struct Map<R, F> {
+    f: F,
+    inner: R,
+}
+
+impl<R, F> Map<R, F>
+where
+    F: Fn(Key) -> Key,
+{
+    fn map_key(&self, key: &Key) -> Key {
+        (self.f)(key.clone())
+    }
+
+    fn map_key_name(&self, key_name: KeyName) -> KeyName {
+        let (name, _) = (self.f)(Key::from_name(key_name)).into_parts();
+        name
+    }
+}
+
+impl<R, F> Recorder for Map<R, F>
+where
+    R: Recorder,
+    F: Fn(Key) -> Key,
+{
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_counter(new_key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_gauge(new_key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_histogram(new_key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.map_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.map_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.map_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_summary(new_key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.map_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_attributes(new_key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(&self.map_key_name(key_name.clone()), metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for applying an arbitrary transformation to every metric key.
+///
+/// The given function is called with the key of every metric being registered, and the key it
+/// returns is used in its place, which allows for changing both the name and labels of a metric.
+///
+/// As descriptions only carry a metric name, the function is called with a key holding the name
+/// being described and no labels, and only the name of the returned key is used.  This keeps
+/// descriptions consistent with registrations for any transformation of metric names which does
+/// not depend on labels.
+pub struct MapLayer<F> {
+    f: F,
+}
+
+impl<F> MapLayer<F>
+where
+    F: Fn(Key) -> Key,
+{
+    /// Creates a new `MapLayer` based on the given function.
+    pub fn new(f: F) -> MapLayer<F> {
+        MapLayer { f }
+    }
+}
+
+impl<R, F> Layer<R> for MapLayer<F>
+where
+    F: Fn(Key) -> Key + Clone,
+{
+    type Output = Map<R, F>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Map { f: self.f.clone(), inner }
+    }
+}
+
+/// Applies an arbitrary transformation to every metric name.
+///
+/// More information on the behavior of the layer can be found in [`MapNameLayer`].
+pub struct MapName<R, F> {
+    f: F,
+    inner: R,
+}
+
+impl<R, F> MapName<R, F>
+where
+    F: Fn(KeyName) -> KeyName,
+{
+    fn map_key(&self, key: &Key) -> Key {
+        let new_name = (self.f)(KeyName::from(key.name().to_owned()));
+        Key::from_parts(new_name, key.labels())
+    }
+
+    fn map_key_name(&self, key_name: KeyName) -> KeyName {
+        (self.f)(key_name)
+    }
+}
+
+impl<R, F> Recorder for MapName<R, F>
+where
+    R: Recorder,
+    F: Fn(KeyName) -> KeyName,
+{
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_counter(new_key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_gauge(new_key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_histogram(new_key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.map_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.map_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.map_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_summary(new_key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.map_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        let new_key_name = self.map_key_name(key_name);
+        self.inner.describe_attributes(new_key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(&self.map_key_name(key_name.clone()), metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for applying an arbitrary transformation to every metric name.
+///
+/// The given function is called with the name of every metric being described or registered, and
+/// the name it returns is used in its place.  Labels are passed through unchanged.
+pub struct MapNameLayer<F> {
+    f: F,
+}
+
+impl<F> MapNameLayer<F>
+where
+    F: Fn(KeyName) -> KeyName,
+{
+    /// Creates a new `MapNameLayer` based on the given function.
+    pub fn new(f: F) -> MapNameLayer<F> {
+        MapNameLayer { f }
+    }
+}
+
+impl<R, F> Layer<R> for MapNameLayer<F>
+where
+    F: Fn(KeyName) -> KeyName + Clone,
+{
+    type Output = MapName<R, F>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        MapName { f: self.f.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MapLayer, MapNameLayer};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    fn inputs() -> Vec<RecorderOperation> {
+        vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("method", "GET")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_map_key() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "COUNTER_KEY".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "GAUGE_KEY".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "HISTOGRAM_KEY".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "COUNTER_KEY",
+                    vec![Label::new("method", "GET"), Label::new("mapped", "true")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("GAUGE_KEY", vec![Label::new("mapped", "true")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("HISTOGRAM_KEY", vec![Label::new("mapped", "true")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let layer = MapLayer::new(|key: Key| {
+            let (name, mut labels) = key.into_parts();
+            labels.push(Label::new("mapped", "true"));
+            Key::from_parts(name.as_str().to_uppercase(), labels)
+        });
+        let map = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&map);
+        }
+    }
+
+    #[test]
+    fn test_map_key_name() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key_total".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key_total".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key_total".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key_total", vec![Label::new("method", "GET")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge("gauge_key_total".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key_total".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let layer =
+            MapNameLayer::new(|key_name: KeyName| format!("{}_total", key_name.as_str()).into());
+        let map = layer.layer(recorder);
+
+        for operation in inputs() {
+            operation.apply_to_recorder(&map);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Level, Metadata, Recorder,
+    SharedString, Summary, Unit,
+};
+
+static METADATA: Metadata<'static> =
+    Metadata::new(module_path!(), Level::INFO, Some(module_path!()));
+
+const DEFAULT_PREFIX: &str = "metrics_meta";
+const DEFAULT_MAX_KEYS: usize = 10_000;
+
+const COUNTER: &str = "counter";
+const GAUGE: &str = "gauge";
+const HISTOGRAM: &str = "histogram";
+const SUMMARY: &str = "summary";
+
+/// Records metrics about the metric activity passing through it.
+///
+/// The following metrics are recorded into the inner recorder, each with a label -- `kind` --
+/// holding the kind of the metric involved, one of `counter`, `gauge`, `histogram`, or `summary`:
+///
+/// - `<prefix>_registrations` (counter): the number of times a metric was registered
+/// - `<prefix>_descriptions` (counter): the number of times a metric was described
+/// - `<prefix>_distinct_keys` (gauge): the number of distinct keys registered so far
+///
+/// Additionally, `<prefix>_attribute_descriptions` (counter), which has no `kind` label, records
+/// the number of times the attributes of a metric were described.
+///
+/// The prefix defaults to `metrics_meta`, and can be changed via [`MetaMetricsLayer::prefix`].
+///
+/// `<prefix>_distinct_keys` counts every key registered through this layer since it was created,
+/// and never decreases, even if the inner recorder has since removed the metric, such as when
+/// idle metrics are expired.  Every distinct key is tracked to compute it, up to a cap which
+/// defaults to 10,000 keys and can be changed via [`MetaMetricsLayer::max_keys`].  Once the cap is
+/// reached, keys which aren't already tracked are no longer counted, which bounds the memory used
+/// by the layer.
+pub struct MetaMetrics<R> {
+    inner: R,
+    registrations_name: KeyName,
+    descriptions_name: KeyName,
+    distinct_keys_name: KeyName,
+    attribute_descriptions_name: KeyName,
+    max_keys: usize,
+    seen: Mutex<HashSet<(&'static str, Key)>>,
+}
+
+impl<R: Recorder> MetaMetrics<R> {
+    fn record_description(&self, kind: &'static str) {
+        let key = Key::from_parts(self.descriptions_name.clone(), vec![Label::new("kind", kind)]);
+        self.inner.register_counter(&key, &METADATA).increment(1);
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn record_registration(&self, kind: &'static str, key: &Key) {
+        let registrations_key =
+            Key::from_parts(self.registrations_name.clone(), vec![Label::new("kind", kind)]);
+        self.inner.register_counter(&registrations_key, &METADATA).increment(1);
+
+        let is_new = {
+            let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+            seen.len() < self.max_keys && seen.insert((kind, key.clone()))
+        };
+        if is_new {
+            let distinct_keys_key =
+                Key::from_parts(self.distinct_keys_name.clone(), vec![Label::new("kind", kind)]);
+            self.inner.register_gauge(&distinct_keys_key, &METADATA).increment(1.0);
+        }
+    }
+}
+
+impl<R: Recorder> Recorder for MetaMetrics<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.record_description(COUNTER);
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.record_description(GAUGE);
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.record_description(HISTOGRAM);
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.record_registration(COUNTER, key);
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        self.record_registration(GAUGE, key);
+        self.inner.register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        self.record_registration(HISTOGRAM, key);
+        self.inner.register_histogram(key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.record_description(SUMMARY);
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        self.record_registration(SUMMARY, key);
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        let key = Key::from_name(self.attribute_descriptions_name.clone());
+        self.inner.register_counter(&key, &METADATA).increment(1);
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for recording metrics about the metric activity passing through it.
+///
+/// More information on the behavior of the layer can be found in [`MetaMetrics`].
+pub struct MetaMetricsLayer {
+    prefix: SharedString,
+    max_keys: usize,
+}
+
+impl MetaMetricsLayer {
+    /// Creates a new `MetaMetricsLayer`.
+    pub fn new() -> MetaMetricsLayer {
+        MetaMetricsLayer { prefix: DEFAULT_PREFIX.into(), max_keys: DEFAULT_MAX_KEYS }
+    }
+
+    /// Sets the prefix of the names of the recorded metrics.
+    ///
+    /// Defaults to `metrics_meta`.
+    pub fn prefix<P: Into<SharedString>>(&mut self, prefix: P) -> &mut MetaMetricsLayer {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the maximum number of distinct keys to track.
+    ///
+    /// Once this many keys are tracked, any other key is no longer counted by
+    /// `<prefix>_distinct_keys`.
+    ///
+    /// Defaults to 10,000.
+    pub fn max_keys(&mut self, max_keys: usize) -> &mut MetaMetricsLayer {
+        self.max_keys = max_keys;
+        self
+    }
+
+    fn name(&self, suffix: &str) -> KeyName {
+        KeyName::from(format!("{}_{}", self.prefix, suffix))
+    }
+}
+
+impl Default for MetaMetricsLayer {
+    fn default() -> Self {
+        MetaMetricsLayer::new()
+    }
+}
+
+impl<R> Layer<R> for MetaMetricsLayer {
+    type Output = MetaMetrics<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        MetaMetrics {
+            inner,
+            registrations_name: self.name("registrations"),
+            descriptions_name: self.name("descriptions"),
+            distinct_keys_name: self.name("distinct_keys"),
+            attribute_descriptions_name: self.name("attribute_descriptions"),
+            max_keys: self.max_keys,
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "debugging"))]
+mod tests {
+    use super::MetaMetricsLayer;
+    use crate::debugging::{DebugValue, DebuggingRecorder};
+    use crate::layers::Layer;
+    use crate::{CompositeKey, MetricKind};
+    use metrics::{Attributes, Key, Label, Recorder, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    fn meta_key(kind: MetricKind, name: &'static str, metric_kind: &'static str) -> CompositeKey {
+        CompositeKey::new(kind, Key::from_parts(name, vec![Label::new("kind", metric_kind)]))
+    }
+
+    #[test]
+    fn test_basic_functionality() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let meta = MetaMetricsLayer::new().layer(recorder);
+
+        meta.describe_counter("counter_key".into(), Some(Unit::Count), "counter desc".into());
+        meta.register_counter(&Key::from_name("counter_key"), &METADATA).increment(1);
+        meta.register_counter(&Key::from_name("counter_key"), &METADATA).increment(1);
+        meta.register_counter(
+            &Key::from_parts("counter_key", vec![Label::new("method", "GET")]),
+            &METADATA,
+        )
+        .increment(1);
+        meta.register_histogram(&Key::from_name("histogram_key"), &METADATA).record(1.0);
+        meta.describe_summary("summary_key".into(), None, "summary desc".into());
+        meta.register_summary(&Key::from_name("summary_key"), &METADATA).record(1.0);
+        meta.describe_attributes("counter_key".into(), Attributes::new());
+
+        #[allow(clippy::mutable_key_type)]
+        let snapshot = snapshotter.snapshot().into_hashmap();
+
+        let value = |key: CompositeKey| snapshot.get(&key).map(|(_, _, value)| value);
+        assert_eq!(
+            value(meta_key(MetricKind::Counter, "metrics_meta_registrations", "counter")),
+            Some(&DebugValue::Counter(3))
+        );
+        assert_eq!(
+            value(meta_key(MetricKind::Counter, "metrics_meta_registrations", "histogram")),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(meta_key(MetricKind::Counter, "metrics_meta_descriptions", "counter")),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(meta_key(MetricKind::Gauge, "metrics_meta_distinct_keys", "counter")),
+            Some(&DebugValue::Gauge(2.0.into()))
+        );
+        assert_eq!(
+            value(meta_key(MetricKind::Gauge, "metrics_meta_distinct_keys", "histogram")),
+            Some(&DebugValue::Gauge(1.0.into()))
+        );
+        assert_eq!(
+            value(meta_key(MetricKind::Counter, "metrics_meta_descriptions", "summary")),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(meta_key(MetricKind::Gauge, "metrics_meta_distinct_keys", "summary")),
+            Some(&DebugValue::Gauge(1.0.into()))
+        );
+        assert_eq!(value(meta_key(MetricKind::Gauge, "metrics_meta_distinct_keys", "gauge")), None);
+        assert_eq!(
+            value(CompositeKey::new(
+                MetricKind::Counter,
+                Key::from_name("metrics_meta_attribute_descriptions")
+            )),
+            Some(&DebugValue::Counter(1))
+        );
+    }
+
+    #[test]
+    fn test_max_keys() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let meta = MetaMetricsLayer::new().max_keys(2).layer(recorder);
+
+        for name in &["first", "second", "third", "first"] {
+            meta.register_counter(&Key::from_name(*name), &METADATA).increment(1);
+        }
+
+        #[allow(clippy::mutable_key_type)]
+        let snapshot = snapshotter.snapshot().into_hashmap();
+
+        let value = |key: CompositeKey| snapshot.get(&key).map(|(_, _, value)| value);
+        assert_eq!(
+            value(meta_key(MetricKind::Counter, "metrics_meta_registrations", "counter")),
+            Some(&DebugValue::Counter(4))
+        );
+        assert_eq!(
+            value(meta_key(MetricKind::Gauge, "metrics_meta_distinct_keys", "counter")),
+            Some(&DebugValue::Gauge(2.0.into()))
+        );
+    }
+
+    #[test]
+    fn test_prefix() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let meta = MetaMetricsLayer::new().prefix("app_meta").layer(recorder);
+
+        meta.register_gauge(&Key::from_name("gauge_key"), &METADATA).set(1.0);
+
+        #[allow(clippy::mutable_key_type)]
+        let snapshot = snapshotter.snapshot().into_hashmap();
+        assert!(snapshot.contains_key(&meta_key(
+            MetricKind::Counter,
+            "app_meta_registrations",
+            "gauge"
+        )));
+        assert!(snapshot.contains_key(&meta_key(
+            MetricKind::Gauge,
+            "app_meta_distinct_keys",
+            "gauge"
+        )));
+    }
+}
@@ -107,10 +107,46 @@
 //!     .expect("failed to install stack");
 //! # }
 //! ```
-use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
 
 use metrics::SetRecorderError;
 
+#[cfg(feature = "layer-anonymize")]
+mod anonymize;
+#[cfg(feature = "layer-anonymize")]
+pub use anonymize::{Anonymize, AnonymizeLayer};
+
+mod cache;
+pub use cache::{Cache, CacheLayer};
+
+mod canonicalize;
+pub use canonicalize::{Canonicalize, CanonicalizeLayer};
+
+mod cardinality;
+pub use cardinality::{CardinalityLimit, CardinalityLimitLayer};
+
+mod clamp;
+pub use clamp::{Clamp, ClampLayer};
+
+mod context_labels;
+pub use context_labels::{
+    ContextLabels, ContextLabelsLayer, LabelContext, LabelContextFuture, LabelContextGuard,
+};
+
+mod counter_convert;
+pub use counter_convert::{CounterConversion, CounterConvert, CounterConvertLayer};
+
+#[cfg(feature = "layer-filter")]
+mod dynamic_filter;
+#[cfg(feature = "layer-filter")]
+pub use dynamic_filter::{DynamicFilter, DynamicFilterLayer, FilterHandle};
+
+mod env_labels;
+pub use env_labels::EnvLabelsLayer;
+
 mod fanout;
 pub use fanout::{Fanout, FanoutBuilder};
 
@@ -119,13 +155,77 @@ mod filter;
 #[cfg(feature = "layer-filter")]
 pub use filter::{Filter, FilterLayer};
 
+pub(crate) mod glob;
+
+mod glob_filter;
+pub use glob_filter::{GlobFilter, GlobFilterLayer, GlobFilterMode};
+
+mod histogram_downgrade;
+pub use histogram_downgrade::{DowngradeMode, HistogramDowngrade, HistogramDowngradeLayer};
+
+mod label_inject;
+pub use label_inject::{LabelInject, LabelInjectLayer};
+
+mod label_length;
+pub use label_length::{LabelLength, LabelLengthLayer};
+
+mod label_rename;
+pub use label_rename::{LabelRename, LabelRenameLayer};
+
+mod layer_stack;
+pub use layer_stack::{Identity, LayerStack, Stacked};
+
+mod level_filter;
+pub use level_filter::{LevelFilter, LevelFilterLayer, ParseDirectiveError};
+
+mod map;
+pub use map::{Map, MapLayer, MapName, MapNameLayer};
+
+mod meta_metrics;
+pub use meta_metrics::{MetaMetrics, MetaMetricsLayer};
+
 mod prefix;
 pub use prefix::{Prefix, PrefixLayer};
 
+mod rate_limit;
+pub use rate_limit::{RateLimit, RateLimitLayer};
+
+#[cfg(feature = "layer-rename")]
+mod rename;
+#[cfg(feature = "layer-rename")]
+pub use rename::{Rename, RenameLayer};
+
 #[cfg(feature = "layer-router")]
 mod router;
 #[cfg(feature = "layer-router")]
-pub use router::{Router, RouterBuilder};
+pub use router::{Router, RouterBuilder, RouterLayer};
+
+mod sample;
+pub use sample::{Sample, SampleLayer};
+
+mod sanitize;
+pub use sanitize::{Sanitize, SanitizeLayer, SanitizePolicy};
+
+mod scrub;
+pub use scrub::{Scrub, ScrubLayer};
+
+mod suffix;
+pub use suffix::{Suffix, SuffixLayer};
+
+mod tee;
+pub use tee::{Tee, TeeLayer};
+
+mod tenant;
+pub use tenant::{Tenant, TenantLayer, TenantMode};
+
+mod time_base;
+pub use time_base::{TimeBaseConvert, TimeBaseConvertLayer};
+
+mod toggle;
+pub use toggle::{Toggle, ToggleHandle, ToggleLayer};
+
+mod unit_convert;
+pub use unit_convert::{UnitConvert, UnitConvertLayer};
 
 /// Decorates an object by wrapping it within another type.
 pub trait Layer<R> {
@@ -186,4 +286,28 @@ impl<R: Recorder> Recorder for Stack<R> {
     fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
         self.inner.register_histogram(key, metadata)
     }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description);
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes);
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown();
+    }
 }
@@ -1,5 +1,8 @@
 use crate::layers::Layer;
-use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
 
 /// Applies a prefix to every metric key.
 ///
@@ -59,17 +62,44 @@ impl<R: Recorder> Recorder for Prefix<R> {
         let new_key = self.prefix_key(key);
         self.inner.register_histogram(&new_key, metadata)
     }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.prefix_key_name(key_name);
+        self.inner.describe_summary(new_key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.prefix_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        let new_key_name = self.prefix_key_name(key_name);
+        self.inner.describe_attributes(new_key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(&self.prefix_key_name(key_name.clone()), metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
 }
 
 /// A layer for applying a prefix to every metric key.
 ///
 /// More information on the behavior of the layer can be found in [`Prefix`].
-pub struct PrefixLayer(&'static str);
+pub struct PrefixLayer(SharedString);
 
 impl PrefixLayer {
     /// Creates a new `PrefixLayer` based on the given prefix.
     pub fn new<S: Into<String>>(prefix: S) -> PrefixLayer {
-        PrefixLayer(Box::leak(prefix.into().into_boxed_str()))
+        PrefixLayer(prefix.into().into())
     }
 }
 
@@ -77,7 +107,7 @@ impl<R> Layer<R> for PrefixLayer {
     type Output = Prefix<R>;
 
     fn layer(&self, inner: R) -> Self::Output {
-        Prefix { prefix: self.0.into(), inner }
+        Prefix { prefix: self.0.clone(), inner }
     }
 }
 
@@ -86,7 +116,7 @@ mod tests {
     use super::{Prefix, PrefixLayer};
     use crate::layers::Layer;
     use crate::test_util::*;
-    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Unit};
+    use metrics::{Attributes, Counter, Gauge, Histogram, Key, KeyName, Summary, TimeBase, Unit};
 
     static METADATA: metrics::Metadata =
         metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
@@ -116,6 +146,16 @@ mod tests {
                 Histogram::noop(),
                 &METADATA,
             ),
+            RecorderOperation::DescribeSummary(
+                "summary_key".into(),
+                Some(Unit::Seconds),
+                "summary desc".into(),
+            ),
+            RecorderOperation::RegisterSummary("summary_key".into(), Summary::noop(), &METADATA),
+            RecorderOperation::DescribeAttributes(
+                "histogram_key".into(),
+                Attributes::new().with(TimeBase::Nanoseconds),
+            ),
         ];
 
         let expectations = vec![
@@ -145,6 +185,20 @@ mod tests {
                 Histogram::noop(),
                 &METADATA,
             ),
+            RecorderOperation::DescribeSummary(
+                "testing.summary_key".into(),
+                Some(Unit::Seconds),
+                "summary desc".into(),
+            ),
+            RecorderOperation::RegisterSummary(
+                "testing.summary_key".into(),
+                Summary::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::DescribeAttributes(
+                "testing.histogram_key".into(),
+                Attributes::new().with(TimeBase::Nanoseconds),
+            ),
         ];
 
         let recorder = MockBasicRecorder::from_operations(expectations);
@@ -0,0 +1,404 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Level, Metadata, Recorder,
+    SharedString, Summary, Unit,
+};
+
+static METADATA: Metadata<'static> =
+    Metadata::new(module_path!(), Level::INFO, Some(module_path!()));
+
+const DEFAULT_REJECTED_NAME: &str = "registration_rate_limit_rejected";
+const DEFAULT_MAX_NAMES: usize = 10_000;
+const DEFAULT_MAX_SERIES: usize = 100_000;
+
+/// The reason a registration was rejected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Rejection {
+    /// The bucket of the metric name was empty.
+    Rate,
+    /// The metric name was new, and the maximum number of metric names were already tracked.
+    Names,
+    /// The maximum number of series were already admitted.
+    Series,
+}
+
+impl Rejection {
+    fn as_str(self) -> &'static str {
+        match self {
+            Rejection::Rate => "rate",
+            Rejection::Names => "names",
+            Rejection::Series => "series",
+        }
+    }
+}
+
+/// Registration state for a single metric name.
+struct NameState {
+    tokens: f64,
+    last_refill: Instant,
+    /// Hashes of the keys admitted so far.
+    series: HashSet<u64>,
+}
+
+#[derive(Default)]
+struct State {
+    names: HashMap<KeyName, NameState>,
+    series: usize,
+}
+
+/// Limits the rate at which new series are registered for each metric name.
+///
+/// Each metric name has its own token bucket, which starts out full and refills continuously at the
+/// configured rate, up to the configured burst size.  Registering a key that has not been seen
+/// before takes a token from the bucket of its metric name, while registering a key that has
+/// already been admitted is always allowed.  When the bucket is empty, the registration is
+/// rejected, and a no-op handle is returned.  Rejected keys are not remembered, so they may be
+/// admitted by a later registration once the bucket has refilled.
+///
+/// As every metric name gets a full bucket, the number of metric names tracked is capped, and once
+/// the cap is reached, registrations under any other metric name are rejected.  Likewise, the layer
+/// only remembers a hash of each admitted key, and once the cap on admitted series is reached, every
+/// new series is rejected.  This bounds the memory used by the layer, even when names or labels are
+/// generated without bound.  The caps default to 10,000 metric names and 100,000 series, and can be
+/// changed via [`RateLimitLayer::max_names`] and [`RateLimitLayer::max_series`].
+///
+/// Every time a registration is rejected, a meta-counter is incremented on the inner recorder. By
+/// default, this counter is named `registration_rate_limit_rejected`, with a label -- `reason` --
+/// holding why the registration was rejected: `rate` when the bucket of its metric name was empty,
+/// `names` when the cap on metric names was reached, or `series` when the cap on series was
+/// reached.
+pub struct RateLimit<R> {
+    inner: R,
+    rate: f64,
+    burst: f64,
+    max_names: usize,
+    max_series: usize,
+    rejected_name: SharedString,
+    state: Mutex<State>,
+}
+
+impl<R> RateLimit<R> {
+    fn is_allowed(&self, key: &Key, now: Instant) -> Result<(), Rejection> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let State { names, series } = &mut *state;
+        if !names.contains_key(key.name()) {
+            if names.len() >= self.max_names {
+                return Err(Rejection::Names);
+            }
+            let name_state =
+                NameState { tokens: self.burst, last_refill: now, series: HashSet::new() };
+            names.insert(KeyName::from(key.name().to_owned()), name_state);
+        }
+        let name_state = names.get_mut(key.name()).expect("state for metric name must exist");
+
+        let hash = key.get_hash();
+        if name_state.series.contains(&hash) {
+            return Ok(());
+        }
+        if *series >= self.max_series {
+            return Err(Rejection::Series);
+        }
+
+        let elapsed = now.saturating_duration_since(name_state.last_refill).as_secs_f64();
+        name_state.tokens = (name_state.tokens + elapsed * self.rate).min(self.burst);
+        name_state.last_refill = now;
+
+        if name_state.tokens >= 1.0 {
+            name_state.tokens -= 1.0;
+            name_state.series.insert(hash);
+            *series += 1;
+            Ok(())
+        } else {
+            Err(Rejection::Rate)
+        }
+    }
+}
+
+impl<R: Recorder> RateLimit<R> {
+    fn admit(&self, key: &Key) -> bool {
+        let rejection = match self.is_allowed(key, Instant::now()) {
+            Ok(()) => return true,
+            Err(rejection) => rejection,
+        };
+
+        let rejected_key = Key::from_parts(
+            self.rejected_name.clone(),
+            vec![Label::new("reason", rejection.as_str())],
+        );
+        self.inner.register_counter(&rejected_key, &METADATA).increment(1);
+        false
+    }
+}
+
+impl<R: Recorder> Recorder for RateLimit<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        if self.admit(key) {
+            self.inner.register_counter(key, metadata)
+        } else {
+            Counter::noop()
+        }
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        if self.admit(key) {
+            self.inner.register_gauge(key, metadata)
+        } else {
+            Gauge::noop()
+        }
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        if self.admit(key) {
+            self.inner.register_histogram(key, metadata)
+        } else {
+            Histogram::noop()
+        }
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        if self.admit(key) {
+            self.inner.register_summary(key, metadata)
+        } else {
+            Summary::noop()
+        }
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for limiting the rate at which new series are registered for each metric name.
+///
+/// More information on the behavior of the layer can be found in [`RateLimit`].
+pub struct RateLimitLayer {
+    rate: f64,
+    burst: f64,
+    max_names: usize,
+    max_series: usize,
+    rejected_name: SharedString,
+}
+
+impl RateLimitLayer {
+    /// Creates a new `RateLimitLayer` which allows, for each metric name, up to `rate` new series
+    /// per second, with bursts of up to `burst` new series at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is negative or not finite.
+    pub fn new(rate: f64, burst: u32) -> RateLimitLayer {
+        assert!(rate.is_finite() && rate >= 0.0, "rate must be a finite, non-negative number");
+
+        RateLimitLayer {
+            rate,
+            burst: f64::from(burst),
+            max_names: DEFAULT_MAX_NAMES,
+            max_series: DEFAULT_MAX_SERIES,
+            rejected_name: DEFAULT_REJECTED_NAME.into(),
+        }
+    }
+
+    /// Sets the maximum number of metric names to track.
+    ///
+    /// Once this many metric names are tracked, registrations under any other metric name are
+    /// rejected.
+    ///
+    /// Defaults to 10,000.
+    pub fn max_names(&mut self, max_names: usize) -> &mut RateLimitLayer {
+        self.max_names = max_names;
+        self
+    }
+
+    /// Sets the maximum number of series to admit, across all metric names.
+    ///
+    /// Once this many series have been admitted, registrations of any other series are rejected.
+    ///
+    /// Defaults to 100,000.
+    pub fn max_series(&mut self, max_series: usize) -> &mut RateLimitLayer {
+        self.max_series = max_series;
+        self
+    }
+
+    /// Sets the name of the meta-counter which tracks the number of rejected registrations.
+    ///
+    /// Defaults to `registration_rate_limit_rejected`.
+    pub fn rejected_name<N: Into<SharedString>>(&mut self, name: N) -> &mut RateLimitLayer {
+        self.rejected_name = name.into();
+        self
+    }
+}
+
+impl<R> Layer<R> for RateLimitLayer {
+    type Output = RateLimit<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        RateLimit {
+            inner,
+            rate: self.rate,
+            burst: self.burst,
+            max_names: self.max_names,
+            max_series: self.max_series,
+            rejected_name: self.rejected_name.clone(),
+            state: Mutex::new(State::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{RateLimitLayer, Rejection};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Key, Label};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "a")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "b")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "c")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("user", "c")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "a")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("user", "b")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("rejected", vec![Label::new("reason", "rate")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("user", "c")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = RateLimitLayer::new(0.0, 2);
+        layer.rejected_name("rejected");
+        let limited = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&limited);
+        }
+    }
+
+    #[test]
+    fn test_refill() {
+        let layer = RateLimitLayer::new(2.0, 1);
+        let limited = layer.layer(());
+
+        let start = Instant::now();
+        let key = |user: &'static str| Key::from_parts("key", vec![Label::new("user", user)]);
+
+        assert!(limited.is_allowed(&key("a"), start).is_ok());
+        assert_eq!(limited.is_allowed(&key("b"), start), Err(Rejection::Rate));
+
+        // Keys which were already admitted don't need a token.
+        assert!(limited.is_allowed(&key("a"), start).is_ok());
+
+        // Each metric name has its own bucket.
+        assert!(limited.is_allowed(&Key::from_name("other_key"), start).is_ok());
+
+        // At two tokens per second, a new token is available after half a second, and the bucket
+        // never holds more than the burst size.
+        assert_eq!(
+            limited.is_allowed(&key("b"), start + Duration::from_millis(250)),
+            Err(Rejection::Rate)
+        );
+        assert!(limited.is_allowed(&key("b"), start + Duration::from_millis(500)).is_ok());
+        assert!(limited.is_allowed(&key("c"), start + Duration::from_secs(10)).is_ok());
+        assert_eq!(
+            limited.is_allowed(&key("d"), start + Duration::from_secs(10)),
+            Err(Rejection::Rate)
+        );
+    }
+
+    #[test]
+    fn test_caps() {
+        let mut layer = RateLimitLayer::new(0.0, 2);
+        layer.max_names(2).max_series(3);
+        let limited = layer.layer(());
+
+        let start = Instant::now();
+        let key = |name: &'static str, user: &'static str| {
+            Key::from_parts(name, vec![Label::new("user", user)])
+        };
+
+        assert!(limited.is_allowed(&key("a", "1"), start).is_ok());
+        assert!(limited.is_allowed(&key("a", "2"), start).is_ok());
+        assert!(limited.is_allowed(&key("b", "1"), start).is_ok());
+
+        // New names are rejected once the cap is reached, without being tracked.
+        assert_eq!(limited.is_allowed(&key("c", "1"), start), Err(Rejection::Names));
+        assert_eq!(limited.state.lock().unwrap().names.len(), 2);
+
+        // New series are rejected once the cap is reached, even with tokens left, while admitted
+        // series are still allowed.
+        assert_eq!(limited.is_allowed(&key("b", "2"), start), Err(Rejection::Series));
+        assert!(limited.is_allowed(&key("a", "1"), start).is_ok());
+    }
+}
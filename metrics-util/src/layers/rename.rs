@@ -0,0 +1,254 @@
+use std::borrow::Cow;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
+use regex::Regex;
+
+/// Renames metrics based on regular expression rules.
+///
+/// More information on the behavior of the layer can be found in [`RenameLayer`].
+pub struct Rename<R> {
+    rules: Vec<(Regex, String)>,
+    inner: R,
+}
+
+impl<R> Rename<R> {
+    fn rename<'a>(&self, name: &'a str) -> Option<Cow<'a, str>> {
+        self.rules
+            .iter()
+            .find(|(regex, _)| regex.is_match(name))
+            .map(|(regex, replacement)| regex.replace(name, replacement.as_str()))
+    }
+
+    fn rename_key(&self, key: &Key) -> Key {
+        match self.rename(key.name()) {
+            Some(new_name) => Key::from_parts(new_name.into_owned(), key.labels()),
+            None => key.clone(),
+        }
+    }
+
+    fn rename_key_name(&self, key_name: KeyName) -> KeyName {
+        match self.rename(key_name.as_str()) {
+            Some(new_name) => KeyName::from(new_name.into_owned()),
+            None => key_name,
+        }
+    }
+}
+
+impl<R: Recorder> Recorder for Rename<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.rename_key_name(key_name);
+        self.inner.describe_counter(new_key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.rename_key_name(key_name);
+        self.inner.describe_gauge(new_key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.rename_key_name(key_name);
+        self.inner.describe_histogram(new_key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.rename_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.rename_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.rename_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.rename_key_name(key_name);
+        self.inner.describe_summary(new_key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.rename_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        let new_key_name = self.rename_key_name(key_name);
+        self.inner.describe_attributes(new_key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(&self.rename_key_name(key_name.clone()), metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for renaming metrics based on regular expression rules.
+///
+/// Each rule is made up of a regular expression and a replacement string, which can refer to
+/// capture groups in the regular expression using the syntax supported by [`Regex::replace`], such
+/// as `$1` or `${name}`.  For example, a rule of `^legacy_(.*)` with a replacement of `$1` would
+/// rename `legacy_requests` to `requests`.
+///
+/// Rules are checked in the order they were added, and only the first rule which matches a metric
+/// name is applied.  Metric names which match no rules are passed through unchanged.  Renaming
+/// applies equally to metric descriptions and metric registrations.
+#[derive(Default)]
+pub struct RenameLayer {
+    rules: Vec<(Regex, String)>,
+}
+
+impl RenameLayer {
+    /// Adds a rule.
+    ///
+    /// # Errors
+    ///
+    /// If `pattern` is not a valid regular expression, an error variant will be returned.
+    pub fn add_rule<P, S>(
+        &mut self,
+        pattern: P,
+        replacement: S,
+    ) -> Result<&mut RenameLayer, regex::Error>
+    where
+        P: AsRef<str>,
+        S: Into<String>,
+    {
+        let regex = Regex::new(pattern.as_ref())?;
+        self.rules.push((regex, replacement.into()));
+        Ok(self)
+    }
+}
+
+impl<R> Layer<R> for RenameLayer {
+    type Output = Rename<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Rename { rules: self.rules.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenameLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "legacy_counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "svc_histogram_key".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("legacy_counter_key", vec![Label::new("method", "GET")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "svc_histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "svc.histogram_key".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("method", "GET")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "svc.histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = RenameLayer::default();
+        layer
+            .add_rule("^legacy_(.*)", "$1")
+            .and_then(|layer| {
+                layer.add_rule("^(?P<service>[a-z]+)_(?P<rest>.*)_key$", "$service.${rest}_key")
+            })
+            .expect("rules should be valid");
+        let rename = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&rename);
+        }
+    }
+
+    #[test]
+    fn test_key_vs_key_name() {
+        let mut layer = RenameLayer::default();
+        layer.add_rule("^legacy_(.*)", "$1").expect("rule should be valid");
+        let rename = layer.layer(());
+
+        let key_name = KeyName::from("legacy_my_key");
+        let key = Key::from_name(key_name.clone());
+
+        let renamed_key = rename.rename_key(&key);
+        let renamed_key_name = rename.rename_key_name(key_name);
+
+        assert_eq!(
+            renamed_key.name(),
+            renamed_key_name.as_str(),
+            "renamed key and renamed key name should match"
+        );
+        assert_eq!(renamed_key.name(), "my_key");
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        let mut layer = RenameLayer::default();
+        assert!(layer.add_rule("legacy_(", "$1").is_err());
+    }
+}
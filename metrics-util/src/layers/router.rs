@@ -1,90 +1,240 @@
-use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::Arc;
+
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
 use radix_trie::{Trie, TrieCommon};
 
+use crate::layers::{glob::Glob, Layer};
 use crate::{MetricKind, MetricKindMask};
 
+/// Pattern used to match a route against metric names.
+enum RoutePattern {
+    Exact(String),
+    Prefix(String),
+    Glob(Glob),
+}
+
+/// Routes for a single metric kind.
+#[derive(Clone, Default)]
+struct Routes {
+    exact: HashMap<String, usize>,
+    prefix: Trie<String, usize>,
+    glob: Vec<(Glob, usize)>,
+}
+
+impl Routes {
+    fn insert(&mut self, pattern: &RoutePattern, target_idx: usize) {
+        match pattern {
+            RoutePattern::Exact(name) => {
+                let _ = self.exact.insert(name.clone(), target_idx);
+            }
+            RoutePattern::Prefix(prefix) => {
+                let _ = self.prefix.insert(prefix.clone(), target_idx);
+            }
+            RoutePattern::Glob(glob) => self.glob.push((glob.clone(), target_idx)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<usize> {
+        // Exact routes take precedence over prefix routes, which take precedence over glob routes.
+        // Prefix routes match the longest prefix, and glob routes match in the order they were added.
+        self.exact
+            .get(key)
+            .copied()
+            .or_else(|| self.prefix.get_ancestor(key).and_then(|st| st.value().copied()))
+            .or_else(|| self.glob.iter().find(|(glob, _)| glob.matches(key)).map(|(_, idx)| *idx))
+    }
+}
+
+/// The set of routes and target recorders shared by [`RouterBuilder`] and [`RouterLayer`].
+#[derive(Clone)]
+struct RouteTable {
+    global_mask: MetricKindMask,
+    targets: Vec<Arc<dyn Recorder>>,
+    counter_routes: Routes,
+    gauge_routes: Routes,
+    histogram_routes: Routes,
+}
+
+impl Default for RouteTable {
+    fn default() -> Self {
+        RouteTable {
+            global_mask: MetricKindMask::NONE,
+            targets: Vec::new(),
+            counter_routes: Routes::default(),
+            gauge_routes: Routes::default(),
+            histogram_routes: Routes::default(),
+        }
+    }
+}
+
+impl RouteTable {
+    fn add_route(
+        &mut self,
+        mask: MetricKindMask,
+        pattern: RoutePattern,
+        recorder: Arc<dyn Recorder>,
+    ) {
+        let target_idx = self.targets.len();
+        self.targets.push(recorder);
+
+        self.global_mask = self.global_mask | mask;
+
+        match mask {
+            MetricKindMask::ALL => {
+                self.counter_routes.insert(&pattern, target_idx);
+                self.gauge_routes.insert(&pattern, target_idx);
+                self.histogram_routes.insert(&pattern, target_idx);
+            }
+            MetricKindMask::COUNTER => self.counter_routes.insert(&pattern, target_idx),
+            MetricKindMask::GAUGE => self.gauge_routes.insert(&pattern, target_idx),
+            MetricKindMask::HISTOGRAM => self.histogram_routes.insert(&pattern, target_idx),
+            _ => panic!("cannot add route for unknown or empty metric kind mask"),
+        };
+    }
+}
+
 /// Routes metrics to specific target recorders.
 ///
 /// More information on the behavior of the layer can be found in [`RouterBuilder`].
 pub struct Router {
     default: Box<dyn Recorder>,
-    global_mask: MetricKindMask,
-    targets: Vec<Box<dyn Recorder>>,
-    counter_routes: Trie<String, usize>,
-    gauge_routes: Trie<String, usize>,
-    histogram_routes: Trie<String, usize>,
+    table: RouteTable,
 }
 
 impl Router {
-    fn route(
-        &self,
-        kind: MetricKind,
-        key: &str,
-        search_routes: &Trie<String, usize>,
-    ) -> &dyn Recorder {
+    fn route(&self, kind: MetricKind, key: &str) -> &dyn Recorder {
         // The global mask is essentially a Bloom filter of overridden route types.  If it doesn't
         // match our metric, we know for a fact there's no route and must use the default recorder.
-        if !self.global_mask.matches(kind) {
+        if !self.table.global_mask.matches(kind) {
             self.default.as_ref()
         } else {
+            let search_routes = match kind {
+                MetricKind::Counter => &self.table.counter_routes,
+                MetricKind::Gauge => &self.table.gauge_routes,
+                MetricKind::Histogram => &self.table.histogram_routes,
+            };
+
             // SAFETY: We derive the `idx` value that is inserted into our route maps by using the
             // length of `targets` itself before adding a new target.  Ergo, the index is provably
             // populated if the `idx` has been stored.
             search_routes
-                .get_ancestor(key)
-                .map(|st| unsafe { self.targets.get_unchecked(*st.value().unwrap()).as_ref() })
+                .get(key)
+                .map(|idx| unsafe { self.table.targets.get_unchecked(idx).as_ref() })
                 .unwrap_or_else(|| self.default.as_ref())
         }
     }
 }
 
+fn same_recorder(a: &dyn Recorder, b: &dyn Recorder) -> bool {
+    ptr::eq(a as *const dyn Recorder as *const (), b as *const dyn Recorder as *const ())
+}
+
 impl Recorder for Router {
     fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
-        let target = self.route(MetricKind::Counter, key_name.as_str(), &self.counter_routes);
+        let target = self.route(MetricKind::Counter, key_name.as_str());
         target.describe_counter(key_name, unit, description)
     }
 
     fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
-        let target = self.route(MetricKind::Gauge, key_name.as_str(), &self.gauge_routes);
+        let target = self.route(MetricKind::Gauge, key_name.as_str());
         target.describe_gauge(key_name, unit, description)
     }
 
     fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
-        let target = self.route(MetricKind::Histogram, key_name.as_str(), &self.histogram_routes);
+        let target = self.route(MetricKind::Histogram, key_name.as_str());
         target.describe_histogram(key_name, unit, description)
     }
 
     fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
-        let target = self.route(MetricKind::Counter, key.name(), &self.counter_routes);
+        let target = self.route(MetricKind::Counter, key.name());
         target.register_counter(key, metadata)
     }
 
     fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
-        let target = self.route(MetricKind::Gauge, key.name(), &self.gauge_routes);
+        let target = self.route(MetricKind::Gauge, key.name());
         target.register_gauge(key, metadata)
     }
 
     fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
-        let target = self.route(MetricKind::Histogram, key.name(), &self.histogram_routes);
+        let target = self.route(MetricKind::Histogram, key.name());
         target.register_histogram(key, metadata)
     }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let target = self.route(MetricKind::Histogram, key_name.as_str());
+        target.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let target = self.route(MetricKind::Histogram, key.name());
+        target.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        // Attributes aren't tied to a metric kind, so they go to every recorder that any kind of
+        // metric with this name would be routed to.
+        let mut targets: Vec<&dyn Recorder> = Vec::with_capacity(3);
+        for kind in [MetricKind::Counter, MetricKind::Gauge, MetricKind::Histogram] {
+            let target = self.route(kind, key_name.as_str());
+            if !targets.iter().any(|existing| same_recorder(*existing, target)) {
+                targets.push(target);
+            }
+        }
+
+        for target in targets {
+            target.describe_attributes(key_name.clone(), attributes.clone());
+        }
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        // The kind of the metric isn't known yet, so it's enabled if any recorder that any kind of
+        // metric with this name would be routed to has it enabled.
+        [MetricKind::Counter, MetricKind::Gauge, MetricKind::Histogram]
+            .iter()
+            .any(|kind| self.route(*kind, key_name.as_str()).enabled(key_name, metadata))
+    }
+
+    fn flush(&self) {
+        self.default.flush();
+        for target in &self.table.targets {
+            target.flush();
+        }
+    }
+
+    fn shutdown(&self) {
+        self.default.shutdown();
+        for target in &self.table.targets {
+            target.shutdown();
+        }
+    }
 }
 
 /// Routes metrics to specific target recorders.
 ///
-/// Routes are defined as a prefix to check against the metric name, and a mask for the metric type.
-/// For example,  a route with the pattern of "foo" would match "foo", "or "foo.submetric", but not
-/// "something.foo". Likewise, a metric mask of "all" would apply this route to counters, gauges,
-/// and histograms, while any specific mask would only apply to the given metric kind.
+/// Routes are defined as a pattern to check against the metric name, and a mask for the metric
+/// type. Patterns come in three forms:
+///
+/// - prefix, where a pattern of "foo" would match "foo", or "foo.submetric", but not
+///   "something.foo"
+/// - exact, where a pattern of "foo" would only match "foo"
+/// - glob, where a pattern of "foo.*.bar" would match "foo.baz.bar", but not "foo.baz.quux"
+///
+/// When multiple routes match a metric name, exact routes are preferred over prefix routes, which
+/// are preferred over glob routes. Between prefix routes, the longest matching prefix wins, and
+/// between glob routes, the route added first wins.
+///
+/// Likewise, a metric mask of "all" would apply this route to counters, gauges, and histograms,
+/// while any specific mask would only apply to the given metric kind.
 ///
 /// A default route (recorder) is always present and used in the case that no specific route exists.
 pub struct RouterBuilder {
     default: Box<dyn Recorder>,
-    global_mask: MetricKindMask,
-    targets: Vec<Box<dyn Recorder>>,
-    counter_routes: Trie<String, usize>,
-    gauge_routes: Trie<String, usize>,
-    histogram_routes: Trie<String, usize>,
+    table: RouteTable,
 }
 
 impl RouterBuilder {
@@ -95,14 +245,7 @@ impl RouterBuilder {
     where
         R: Recorder + 'static,
     {
-        RouterBuilder {
-            default: Box::new(recorder),
-            global_mask: MetricKindMask::NONE,
-            targets: Vec::new(),
-            counter_routes: Trie::new(),
-            gauge_routes: Trie::new(),
-            histogram_routes: Trie::new(),
-        }
+        RouterBuilder { default: Box::new(recorder), table: RouteTable::default() }
     }
 
     /// Adds a route.
@@ -121,41 +264,128 @@ impl RouterBuilder {
         P: AsRef<str>,
         R: Recorder + 'static,
     {
-        let target_idx = self.targets.len();
-        self.targets.push(Box::new(recorder));
+        let pattern = RoutePattern::Prefix(pattern.as_ref().to_string());
+        self.table.add_route(mask, pattern, Arc::new(recorder));
+        self
+    }
 
-        self.global_mask = self.global_mask | mask;
+    /// Adds an exact route.
+    ///
+    /// `mask` defines which metric kinds will match the given route, and `name` is the metric name
+    /// which must be matched exactly.
+    ///
+    /// If a matching route already exists, it will be overwritten.
+    pub fn add_exact_route<N, R>(
+        &mut self,
+        mask: MetricKindMask,
+        name: N,
+        recorder: R,
+    ) -> &mut RouterBuilder
+    where
+        N: AsRef<str>,
+        R: Recorder + 'static,
+    {
+        let pattern = RoutePattern::Exact(name.as_ref().to_string());
+        self.table.add_route(mask, pattern, Arc::new(recorder));
+        self
+    }
 
-        match mask {
-            MetricKindMask::ALL => {
-                let _ = self.counter_routes.insert(pattern.as_ref().to_string(), target_idx);
-                let _ = self.gauge_routes.insert(pattern.as_ref().to_string(), target_idx);
-                let _ = self.histogram_routes.insert(pattern.as_ref().to_string(), target_idx);
-            }
-            MetricKindMask::COUNTER => {
-                let _ = self.counter_routes.insert(pattern.as_ref().to_string(), target_idx);
-            }
-            MetricKindMask::GAUGE => {
-                let _ = self.gauge_routes.insert(pattern.as_ref().to_string(), target_idx);
-            }
-            MetricKindMask::HISTOGRAM => {
-                let _ = self.histogram_routes.insert(pattern.as_ref().to_string(), target_idx);
-            }
-            _ => panic!("cannot add route for unknown or empty metric kind mask"),
-        };
+    /// Adds a glob route.
+    ///
+    /// `mask` defines which metric kinds will match the given route, and `pattern` is a shell-style
+    /// glob used to match against metric names, where `*` matches any number of characters and `?`
+    /// matches a single character.
+    pub fn add_glob_route<P, R>(
+        &mut self,
+        mask: MetricKindMask,
+        pattern: P,
+        recorder: R,
+    ) -> &mut RouterBuilder
+    where
+        P: AsRef<str>,
+        R: Recorder + 'static,
+    {
+        self.table.add_route(mask, RoutePattern::Glob(Glob::new(pattern)), Arc::new(recorder));
         self
     }
 
     /// Builds the configured [`Router`].
     pub fn build(self) -> Router {
-        Router {
-            default: self.default,
-            global_mask: self.global_mask,
-            targets: self.targets,
-            counter_routes: self.counter_routes,
-            gauge_routes: self.gauge_routes,
-            histogram_routes: self.histogram_routes,
-        }
+        Router { default: self.default, table: self.table }
+    }
+}
+
+/// A layer for routing metrics to specific target recorders.
+///
+/// Routes are configured in the same way as they are for [`RouterBuilder`], with the wrapped
+/// recorder being used as the default route when no other specific route exists.
+///
+/// Target recorders are shared between every [`Router`] created from the same layer.
+#[derive(Default)]
+pub struct RouterLayer {
+    table: RouteTable,
+}
+
+impl RouterLayer {
+    /// Adds a route.
+    ///
+    /// See [`RouterBuilder::add_route`] for more information.
+    pub fn add_route<P, R>(
+        &mut self,
+        mask: MetricKindMask,
+        pattern: P,
+        recorder: R,
+    ) -> &mut RouterLayer
+    where
+        P: AsRef<str>,
+        R: Recorder + 'static,
+    {
+        let pattern = RoutePattern::Prefix(pattern.as_ref().to_string());
+        self.table.add_route(mask, pattern, Arc::new(recorder));
+        self
+    }
+
+    /// Adds an exact route.
+    ///
+    /// See [`RouterBuilder::add_exact_route`] for more information.
+    pub fn add_exact_route<N, R>(
+        &mut self,
+        mask: MetricKindMask,
+        name: N,
+        recorder: R,
+    ) -> &mut RouterLayer
+    where
+        N: AsRef<str>,
+        R: Recorder + 'static,
+    {
+        let pattern = RoutePattern::Exact(name.as_ref().to_string());
+        self.table.add_route(mask, pattern, Arc::new(recorder));
+        self
+    }
+
+    /// Adds a glob route.
+    ///
+    /// See [`RouterBuilder::add_glob_route`] for more information.
+    pub fn add_glob_route<P, R>(
+        &mut self,
+        mask: MetricKindMask,
+        pattern: P,
+        recorder: R,
+    ) -> &mut RouterLayer
+    where
+        P: AsRef<str>,
+        R: Recorder + 'static,
+    {
+        self.table.add_route(mask, RoutePattern::Glob(Glob::new(pattern)), Arc::new(recorder));
+        self
+    }
+}
+
+impl<R: Recorder + 'static> Layer<R> for RouterLayer {
+    type Output = Router;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Router { default: Box::new(inner), table: self.table.clone() }
     }
 }
 
@@ -168,10 +398,11 @@ mod tests {
     };
     use std::borrow::Cow;
 
-    use super::RouterBuilder;
-    use crate::MetricKindMask;
+    use super::{RouterBuilder, RouterLayer};
+    use crate::{layers::Layer, MetricKindMask};
     use metrics::{
-        Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+        Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString,
+        TimeBase, Unit,
     };
 
     mock! {
@@ -185,6 +416,7 @@ mod tests {
             fn register_counter<'a>(&'a self, key: &'a Key, metadata: &'a Metadata<'a>) -> Counter;
             fn register_gauge<'a>(&'a self, key: &'a Key, metadata: &'a Metadata<'a>) -> Gauge;
             fn register_histogram<'a>(&'a self, key: &'a Key, metadata: &'a Metadata<'a>) -> Histogram;
+            fn describe_attributes(&self, key_name: KeyName, attributes: Attributes);
         }
     }
 
@@ -195,7 +427,7 @@ mod tests {
         let mut builder = RouterBuilder::from_recorder(MockTestRecorder::new());
         builder
             .add_route(MetricKindMask::COUNTER, "foo", MockTestRecorder::new())
-            .add_route(MetricKindMask::GAUGE, "bar".to_owned(), MockTestRecorder::new())
+            .add_route(MetricKindMask::GAUGE, "bar", MockTestRecorder::new())
             .add_route(MetricKindMask::HISTOGRAM, Cow::Borrowed("baz"), MockTestRecorder::new())
             .add_route(MetricKindMask::ALL, "quux", MockTestRecorder::new());
         let _ = builder.build();
@@ -265,4 +497,127 @@ mod tests {
         let _ = recorder.register_counter(&all_override, &METADATA);
         let _ = recorder.register_histogram(&all_override, &METADATA);
     }
+
+    #[test]
+    fn test_attributes() {
+        let counter_override: KeyName = "counter_override.foo".into();
+        let all_override: KeyName = "all_override.foo".into();
+
+        let mut default_mock = MockTestRecorder::new();
+        let mut counter_mock = MockTestRecorder::new();
+        let mut all_mock = MockTestRecorder::new();
+
+        // Gauges and histograms named `counter_override.foo` both go to the default recorder, which
+        // should still only see the attributes once.
+        default_mock
+            .expect_describe_attributes()
+            .times(1)
+            .with(eq(counter_override.clone()), always())
+            .return_const(());
+        counter_mock
+            .expect_describe_attributes()
+            .times(1)
+            .with(eq(counter_override.clone()), always())
+            .return_const(());
+        all_mock
+            .expect_describe_attributes()
+            .times(1)
+            .with(eq(all_override.clone()), always())
+            .return_const(());
+
+        let mut builder = RouterBuilder::from_recorder(default_mock);
+        builder.add_route(MetricKindMask::COUNTER, "counter_override", counter_mock).add_route(
+            MetricKindMask::ALL,
+            "all_override",
+            all_mock,
+        );
+        let recorder = builder.build();
+
+        let attributes = Attributes::new().with(TimeBase::Milliseconds);
+        recorder.describe_attributes(counter_override, attributes.clone());
+        recorder.describe_attributes(all_override, attributes);
+    }
+
+    #[test]
+    fn test_pattern_precedence() {
+        let exact: Key = "db.query.latency".into();
+        let prefix: Key = "db.query.errors".into();
+        let glob: Key = "db.pool.latency".into();
+        let default: Key = "http.latency".into();
+
+        static METADATA: metrics::Metadata =
+            metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+        let mut default_mock = MockTestRecorder::new();
+        let mut exact_mock = MockTestRecorder::new();
+        let mut prefix_mock = MockTestRecorder::new();
+        let mut glob_mock = MockTestRecorder::new();
+
+        default_mock
+            .expect_register_histogram()
+            .times(1)
+            .with(eq(default.clone()), always())
+            .returning(|_, _| Histogram::noop());
+
+        exact_mock
+            .expect_register_histogram()
+            .times(1)
+            .with(eq(exact.clone()), always())
+            .returning(|_, _| Histogram::noop());
+
+        prefix_mock
+            .expect_register_histogram()
+            .times(1)
+            .with(eq(prefix.clone()), always())
+            .returning(|_, _| Histogram::noop());
+
+        glob_mock
+            .expect_register_histogram()
+            .times(1)
+            .with(eq(glob.clone()), always())
+            .returning(|_, _| Histogram::noop());
+
+        let mut builder = RouterBuilder::from_recorder(default_mock);
+        builder
+            .add_glob_route(MetricKindMask::HISTOGRAM, "db.*.latency", glob_mock)
+            .add_route(MetricKindMask::HISTOGRAM, "db.query", prefix_mock)
+            .add_exact_route(MetricKindMask::HISTOGRAM, "db.query.latency", exact_mock);
+        let recorder = builder.build();
+
+        let _ = recorder.register_histogram(&exact, &METADATA);
+        let _ = recorder.register_histogram(&prefix, &METADATA);
+        let _ = recorder.register_histogram(&glob, &METADATA);
+        let _ = recorder.register_histogram(&default, &METADATA);
+    }
+
+    #[test]
+    fn test_layer() {
+        let default_gauge: Key = "gauge_default".into();
+        let routed_gauge: Key = "db.connections".into();
+
+        static METADATA: metrics::Metadata =
+            metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+        let mut default_mock = MockTestRecorder::new();
+        let mut db_mock = MockTestRecorder::new();
+
+        default_mock
+            .expect_register_gauge()
+            .times(1)
+            .with(eq(default_gauge.clone()), always())
+            .returning(|_, _| Gauge::noop());
+
+        db_mock
+            .expect_register_gauge()
+            .times(1)
+            .with(eq(routed_gauge.clone()), always())
+            .returning(|_, _| Gauge::noop());
+
+        let mut layer = RouterLayer::default();
+        layer.add_glob_route(MetricKindMask::ALL, "db.*", db_mock);
+        let recorder = layer.layer(default_mock);
+
+        let _ = recorder.register_gauge(&default_gauge, &METADATA);
+        let _ = recorder.register_gauge(&routed_gauge, &METADATA);
+    }
 }
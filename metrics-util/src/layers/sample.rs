@@ -0,0 +1,309 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Label, Metadata, Recorder,
+    SharedString, Summary, SummaryFn, Unit,
+};
+
+thread_local! {
+    static RNG_STATE: Cell<u64> = Cell::new(initial_rng_state());
+}
+
+fn initial_rng_state() -> u64 {
+    // `RandomState` is randomly seeded per thread, which gives us a cheap source of entropy
+    // without needing to pull in a dedicated RNG crate.  xorshift must never be seeded with zero.
+    RandomState::new().build_hasher().finish() | 1
+}
+
+/// Generates the next pseudo-random value for the current thread, using xorshift64*.
+fn next_random() -> u64 {
+    RNG_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+struct SampledHistogram<H> {
+    inner: H,
+    threshold: u64,
+}
+
+impl HistogramFn for SampledHistogram<Histogram> {
+    fn record(&self, value: f64) {
+        if next_random() < self.threshold {
+            self.inner.record(value);
+        }
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        if next_random() < self.threshold {
+            self.inner.record_with_exemplar(value, exemplar.iter());
+        }
+    }
+
+    fn record_duration(&self, duration: Duration) {
+        if next_random() < self.threshold {
+            self.inner.record_duration(duration);
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.inner.is_expired()
+    }
+}
+
+impl From<SampledHistogram<Histogram>> for Histogram {
+    fn from(histogram: SampledHistogram<Histogram>) -> Histogram {
+        Histogram::from_arc(Arc::new(histogram))
+    }
+}
+
+impl SummaryFn for SampledHistogram<Summary> {
+    fn record(&self, value: f64) {
+        if next_random() < self.threshold {
+            self.inner.record(value);
+        }
+    }
+}
+
+impl From<SampledHistogram<Summary>> for Summary {
+    fn from(summary: SampledHistogram<Summary>) -> Summary {
+        Summary::from_arc(Arc::new(summary))
+    }
+}
+
+/// Samples observations recorded to histograms and summaries.
+///
+/// Each histogram or summary handle only forwards a fraction of its observations to the inner
+/// handle, with each observation being independently selected at random according to the
+/// configured sample rate.  Counters and gauges are passed through untouched.
+///
+/// As histogram counts and sums are scaled down by the sample rate, the rate can optionally be
+/// attached to every histogram and summary key as a label, which allows the correction factor to be
+/// applied downstream.
+pub struct Sample<R> {
+    rate: f64,
+    rate_label: Option<Label>,
+    inner: R,
+}
+
+impl<R> Sample<R> {
+    fn sample_key(&self, key: &Key) -> Key {
+        match &self.rate_label {
+            Some(label) => key.with_extra_labels(vec![label.clone()]),
+            None => key.clone(),
+        }
+    }
+
+    fn sample_histogram(&self, histogram: Histogram) -> Histogram {
+        if self.rate >= 1.0 {
+            histogram
+        } else if self.rate <= 0.0 {
+            Histogram::noop()
+        } else {
+            let threshold = (self.rate * u64::MAX as f64) as u64;
+            SampledHistogram { inner: histogram, threshold }.into()
+        }
+    }
+
+    fn sample_summary(&self, summary: Summary) -> Summary {
+        if self.rate >= 1.0 {
+            summary
+        } else if self.rate <= 0.0 {
+            Summary::noop()
+        } else {
+            let threshold = (self.rate * u64::MAX as f64) as u64;
+            SampledHistogram { inner: summary, threshold }.into()
+        }
+    }
+}
+
+impl<R: Recorder> Recorder for Sample<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        self.inner.register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.sample_key(key);
+        let histogram = self.inner.register_histogram(&new_key, metadata);
+        self.sample_histogram(histogram)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.sample_key(key);
+        let summary = self.inner.register_summary(&new_key, metadata);
+        self.sample_summary(summary)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for sampling observations recorded to histograms.
+///
+/// More information on the behavior of the layer can be found in [`Sample`].
+pub struct SampleLayer {
+    rate: f64,
+    rate_label: Option<SharedString>,
+}
+
+impl SampleLayer {
+    /// Creates a new `SampleLayer` which forwards the given fraction of histogram observations.
+    ///
+    /// The rate is clamped to the range of `0.0` to `1.0`, where `0.0` drops every observation and
+    /// `1.0` forwards every observation.
+    pub fn new(rate: f64) -> SampleLayer {
+        SampleLayer { rate: rate.clamp(0.0, 1.0), rate_label: None }
+    }
+
+    /// Attaches the sample rate to every histogram key as a label with the given name.
+    pub fn rate_label<S: Into<SharedString>>(&mut self, name: S) -> &mut SampleLayer {
+        self.rate_label = Some(name.into());
+        self
+    }
+}
+
+impl<R> Layer<R> for SampleLayer {
+    type Output = Sample<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        let rate_label =
+            self.rate_label.as_ref().map(|name| Label::new(name.clone(), self.rate.to_string()));
+
+        Sample { rate: self.rate, rate_label, inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::SampleLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, HistogramFn, Key, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[derive(Default)]
+    struct CountingHistogram(AtomicUsize);
+
+    impl HistogramFn for CountingHistogram {
+        fn record(&self, _value: f64) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("sample_rate", "0.25")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = SampleLayer::new(0.25);
+        layer.rate_label("sample_rate");
+        let sample = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&sample);
+        }
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        const ITERATIONS: usize = 10_000;
+
+        for (rate, min, max) in [(0.0, 0, 0), (1.0, ITERATIONS, ITERATIONS), (0.5, 4_000, 6_000)] {
+            let counts = Arc::new(CountingHistogram::default());
+            let sample = SampleLayer::new(rate).layer(());
+
+            let histogram = sample.sample_histogram(Histogram::from_arc(Arc::clone(&counts)));
+            for _ in 0..ITERATIONS {
+                histogram.record(1.0);
+            }
+
+            let recorded = counts.0.load(Ordering::Relaxed);
+            assert!(
+                recorded >= min && recorded <= max,
+                "rate {} recorded {} observations, expected between {} and {}",
+                rate,
+                recorded,
+                min,
+                max
+            );
+        }
+    }
+}
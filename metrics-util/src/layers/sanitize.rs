@@ -0,0 +1,307 @@
+use std::borrow::Cow;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString,
+    Summary, Unit,
+};
+
+/// Character policy used when sanitizing metric names and label keys.
+#[derive(Clone, Copy, Debug)]
+pub enum SanitizePolicy {
+    /// Prometheus-compatible names.
+    ///
+    /// Metric names may only contain `[a-zA-Z0-9_:]`, and label keys may only contain
+    /// `[a-zA-Z0-9_]`.  Neither may start with a digit.
+    Prometheus,
+
+    /// Graphite-compatible, dot-separated names.
+    ///
+    /// Metric names and label keys may only contain `[a-zA-Z0-9_.-]`.
+    Graphite,
+
+    /// Custom policy.
+    ///
+    /// The function is called for each character of metric names and label keys, and should return
+    /// `true` if the character is allowed.
+    Custom(fn(char) -> bool),
+}
+
+impl SanitizePolicy {
+    fn is_valid_name_char(&self, c: char, first: bool) -> bool {
+        match self {
+            SanitizePolicy::Prometheus => {
+                c.is_ascii_alphabetic() || c == '_' || c == ':' || (!first && c.is_ascii_digit())
+            }
+            SanitizePolicy::Graphite => is_valid_graphite_char(c),
+            SanitizePolicy::Custom(f) => f(c),
+        }
+    }
+
+    fn is_valid_label_key_char(&self, c: char, first: bool) -> bool {
+        match self {
+            SanitizePolicy::Prometheus => {
+                c.is_ascii_alphabetic() || c == '_' || (!first && c.is_ascii_digit())
+            }
+            SanitizePolicy::Graphite => is_valid_graphite_char(c),
+            SanitizePolicy::Custom(f) => f(c),
+        }
+    }
+}
+
+fn is_valid_graphite_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-'
+}
+
+fn sanitize<F>(input: &str, replacement: char, is_valid: F) -> Cow<'_, str>
+where
+    F: Fn(char, bool) -> bool,
+{
+    let is_valid_at = |(i, c): (usize, char)| is_valid(c, i == 0);
+    if input.chars().enumerate().all(is_valid_at) {
+        return Cow::Borrowed(input);
+    }
+
+    let mut output = String::with_capacity(input.len());
+    for (i, c) in input.chars().enumerate() {
+        if is_valid(c, i == 0) {
+            output.push(c);
+        } else if i == 0 && is_valid(c, false) {
+            // The character is valid, just not as the first character, so keep it but prefix it
+            // with the replacement character.
+            output.push(replacement);
+            output.push(c);
+        } else {
+            output.push(replacement);
+        }
+    }
+    Cow::Owned(output)
+}
+
+/// Sanitizes metric names and label keys.
+///
+/// More information on the behavior of the layer can be found in [`SanitizeLayer`].
+pub struct Sanitize<R> {
+    policy: SanitizePolicy,
+    replacement: char,
+    inner: R,
+}
+
+impl<R> Sanitize<R> {
+    fn sanitize_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        sanitize(name, self.replacement, |c, first| self.policy.is_valid_name_char(c, first))
+    }
+
+    fn sanitize_label_key<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        sanitize(key, self.replacement, |c, first| self.policy.is_valid_label_key_char(c, first))
+    }
+
+    fn sanitize_key(&self, key: &Key) -> Key {
+        let name = self.sanitize_name(key.name());
+        let labels_need_sanitizing =
+            key.labels().any(|label| matches!(self.sanitize_label_key(label.key()), Cow::Owned(_)));
+        if matches!(name, Cow::Borrowed(_)) && !labels_need_sanitizing {
+            return key.clone();
+        }
+
+        let labels = key
+            .labels()
+            .map(|label| match self.sanitize_label_key(label.key()) {
+                Cow::Borrowed(_) => label.clone(),
+                Cow::Owned(new_key) => Label::new(new_key, label.value().to_owned()),
+            })
+            .collect::<Vec<_>>();
+
+        Key::from_parts(name.into_owned(), labels)
+    }
+
+    fn sanitize_key_name(&self, key_name: KeyName) -> KeyName {
+        match self.sanitize_name(key_name.as_str()) {
+            Cow::Borrowed(_) => key_name,
+            Cow::Owned(new_name) => KeyName::from(new_name),
+        }
+    }
+}
+
+impl<R: Recorder> Recorder for Sanitize<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.sanitize_key_name(key_name);
+        self.inner.describe_counter(new_key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.sanitize_key_name(key_name);
+        self.inner.describe_gauge(new_key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.sanitize_key_name(key_name);
+        self.inner.describe_histogram(new_key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.sanitize_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.sanitize_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.sanitize_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.sanitize_key_name(key_name);
+        self.inner.describe_summary(new_key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.sanitize_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        let new_key_name = self.sanitize_key_name(key_name);
+        self.inner.describe_attributes(new_key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(&self.sanitize_key_name(key_name.clone()), metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for sanitizing metric names and label keys.
+///
+/// Any character in a metric name or label key which is not allowed by the configured
+/// [`SanitizePolicy`] is replaced with the replacement character, which defaults to `_`.  If a
+/// character is allowed, but not as the first character, such as a leading digit under the
+/// Prometheus policy, the replacement character is inserted in front of it instead.
+///
+/// Label values are passed through untouched.
+#[derive(Clone, Debug)]
+pub struct SanitizeLayer {
+    policy: SanitizePolicy,
+    replacement: char,
+}
+
+impl SanitizeLayer {
+    /// Creates a new `SanitizeLayer` based on the given policy.
+    pub fn new(policy: SanitizePolicy) -> SanitizeLayer {
+        SanitizeLayer { policy, replacement: '_' }
+    }
+
+    /// Sets the character used to replace invalid characters.
+    ///
+    /// Defaults to `_`.
+    pub fn replacement(&mut self, replacement: char) -> &mut SanitizeLayer {
+        self.replacement = replacement;
+        self
+    }
+}
+
+impl<R> Layer<R> for SanitizeLayer {
+    type Output = Sanitize<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Sanitize { policy: self.policy, replacement: self.replacement, inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SanitizeLayer, SanitizePolicy};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "http.requests-total".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("http.requests-total", vec![Label::new("status.code", "2xx")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("1st_gauge", vec![Label::new("a:b", "c:d")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                "already_valid:histogram".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "http_requests_total".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("http_requests_total", vec![Label::new("status_code", "2xx")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("_1st_gauge", vec![Label::new("a_b", "c:d")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                "already_valid:histogram".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let sanitize = SanitizeLayer::new(SanitizePolicy::Prometheus).layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&sanitize);
+        }
+    }
+
+    #[test]
+    fn test_policies() {
+        let cases = [
+            (SanitizePolicy::Prometheus, '_', "svc.latency ms", "svc_latency_ms"),
+            (SanitizePolicy::Graphite, '_', "svc.latency ms", "svc.latency_ms"),
+            (SanitizePolicy::Graphite, '-', "svc/latency:p99", "svc-latency-p99"),
+            (SanitizePolicy::Custom(|c| c.is_ascii_lowercase()), 'x', "aBc", "axc"),
+        ];
+
+        for (policy, replacement, input, expected) in cases {
+            let mut layer = SanitizeLayer::new(policy);
+            layer.replacement(replacement);
+            let sanitize = layer.layer(());
+
+            let key = Key::from_name(input);
+            assert_eq!(sanitize.sanitize_key(&key).name(), expected);
+            assert_eq!(sanitize.sanitize_key_name(KeyName::from(input)).as_str(), expected);
+        }
+    }
+}
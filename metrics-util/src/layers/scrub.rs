@@ -0,0 +1,218 @@
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString,
+    Summary, Unit,
+};
+
+/// An action to take against a label with a matching key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ScrubAction {
+    /// Removes the label entirely.
+    Drop,
+
+    /// Replaces the label value with the given value.
+    Replace(SharedString),
+}
+
+/// Scrubs labels from every metric key.
+///
+/// Labels are matched by their key. Matching labels are either dropped from the key entirely, or
+/// have their value replaced, depending on how the label was configured in [`ScrubLayer`].
+pub struct Scrub<R> {
+    rules: Vec<(SharedString, ScrubAction)>,
+    inner: R,
+}
+
+impl<R> Scrub<R> {
+    fn scrub_key(&self, key: &Key) -> Key {
+        let needs_scrubbing =
+            key.labels().any(|label| self.rules.iter().any(|(k, _)| k.as_ref() == label.key()));
+        if !needs_scrubbing {
+            return key.clone();
+        }
+
+        let labels = key
+            .labels()
+            .filter_map(|label| match self.rules.iter().find(|(k, _)| k.as_ref() == label.key()) {
+                None => Some(label.clone()),
+                Some((_, ScrubAction::Drop)) => None,
+                Some((_, ScrubAction::Replace(value))) => {
+                    Some(Label::new(label.key().to_owned(), value.clone()))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Key::from_parts(key.name().to_owned(), labels)
+    }
+}
+
+impl<R: Recorder> Recorder for Scrub<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.scrub_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.scrub_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.scrub_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.scrub_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for scrubbing labels from every metric key.
+///
+/// More information on the behavior of the layer can be found in [`Scrub`].
+#[derive(Default)]
+pub struct ScrubLayer {
+    rules: Vec<(SharedString, ScrubAction)>,
+}
+
+impl ScrubLayer {
+    /// Drops any label with the given key.
+    pub fn drop_label<K: Into<SharedString>>(&mut self, key: K) -> &mut ScrubLayer {
+        self.add_rule(key.into(), ScrubAction::Drop)
+    }
+
+    /// Replaces the value of any label with the given key.
+    pub fn replace_label<K, V>(&mut self, key: K, value: V) -> &mut ScrubLayer
+    where
+        K: Into<SharedString>,
+        V: Into<SharedString>,
+    {
+        self.add_rule(key.into(), ScrubAction::Replace(value.into()))
+    }
+
+    /// Replaces the value of any label with the given key with `"<redacted>"`.
+    pub fn redact_label<K: Into<SharedString>>(&mut self, key: K) -> &mut ScrubLayer {
+        self.replace_label(key, "<redacted>")
+    }
+
+    fn add_rule(&mut self, key: SharedString, action: ScrubAction) -> &mut ScrubLayer {
+        match self.rules.iter_mut().find(|(k, _)| *k == key) {
+            Some(rule) => rule.1 = action,
+            None => self.rules.push((key, action)),
+        }
+        self
+    }
+}
+
+impl<R> Layer<R> for ScrubLayer {
+    type Output = Scrub<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Scrub { rules: self.rules.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScrubLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, Label, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("user_id", "42"), Label::new("method", "GET")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("email", "jane@example.com")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("region", "eu")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("method", "GET")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("email", "<redacted>")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterHistogram(
+                Key::from_parts("histogram_key", vec![Label::new("region", "eu")]),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = ScrubLayer::default();
+        layer.drop_label("user_id").redact_label("email");
+        let scrub = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&scrub);
+        }
+    }
+}
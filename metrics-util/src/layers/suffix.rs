@@ -0,0 +1,205 @@
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
+
+/// Applies a suffix to every metric key.
+///
+/// Keys will be suffixed in the format of `<remaining>.<suffix>`.
+pub struct Suffix<R> {
+    suffix: SharedString,
+    inner: R,
+}
+
+impl<R> Suffix<R> {
+    fn suffix_key(&self, key: &Key) -> Key {
+        let mut new_name = String::with_capacity(key.name().len() + 1 + self.suffix.len());
+        new_name.push_str(key.name());
+        new_name.push('.');
+        new_name.push_str(self.suffix.as_ref());
+
+        Key::from_parts(new_name, key.labels())
+    }
+
+    fn suffix_key_name(&self, key_name: KeyName) -> KeyName {
+        let mut new_name = String::with_capacity(key_name.as_str().len() + 1 + self.suffix.len());
+        new_name.push_str(key_name.as_str());
+        new_name.push('.');
+        new_name.push_str(self.suffix.as_ref());
+
+        KeyName::from(new_name)
+    }
+}
+
+impl<R: Recorder> Recorder for Suffix<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.suffix_key_name(key_name);
+        self.inner.describe_counter(new_key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.suffix_key_name(key_name);
+        self.inner.describe_gauge(new_key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.suffix_key_name(key_name);
+        self.inner.describe_histogram(new_key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.suffix_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.suffix_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.suffix_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.suffix_key_name(key_name);
+        self.inner.describe_summary(new_key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.suffix_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        let new_key_name = self.suffix_key_name(key_name);
+        self.inner.describe_attributes(new_key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(&self.suffix_key_name(key_name.clone()), metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for applying a suffix to every metric key.
+///
+/// More information on the behavior of the layer can be found in [`Suffix`].
+pub struct SuffixLayer(SharedString);
+
+impl SuffixLayer {
+    /// Creates a new `SuffixLayer` based on the given suffix.
+    pub fn new<S: Into<SharedString>>(suffix: S) -> SuffixLayer {
+        SuffixLayer(suffix.into())
+    }
+}
+
+impl<R> Layer<R> for SuffixLayer {
+    type Output = Suffix<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Suffix { suffix: self.0.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Suffix, SuffixLayer};
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key.testing".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key.testing".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key.testing".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                "counter_key.testing".into(),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge("gauge_key.testing".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key.testing".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let suffix = SuffixLayer::new("testing");
+        let suffix = suffix.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&suffix);
+        }
+    }
+
+    #[test]
+    fn test_key_vs_key_name() {
+        let suffix = Suffix { suffix: "foobar".into(), inner: () };
+
+        let key_name = KeyName::from("my_key");
+        let key = Key::from_name(key_name.clone());
+
+        let suffixed_key = suffix.suffix_key(&key);
+        let suffixed_key_name = suffix.suffix_key_name(key_name);
+
+        assert_eq!(
+            suffixed_key.name(),
+            suffixed_key_name.as_str(),
+            "suffixed key and suffixed key name should match"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::sync::Arc;
+
+#[cfg(feature = "debugging")]
+use crate::debugging::{DebuggingRecorder, Snapshotter};
+use crate::layers::fanout::{FanoutCounter, FanoutGauge, FanoutHistogram, FanoutSummary};
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
+
+/// Mirrors all operations to a secondary recorder.
+///
+/// More information on the behavior of the layer can be found in [`TeeLayer`].
+pub struct Tee<R, S> {
+    sink: Arc<S>,
+    inner: R,
+}
+
+impl<R: Recorder, S: Recorder> Recorder for Tee<R, S> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.sink.describe_counter(key_name.clone(), unit.clone(), description.clone());
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.sink.describe_gauge(key_name.clone(), unit.clone(), description.clone());
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.sink.describe_histogram(key_name.clone(), unit.clone(), description.clone());
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let counters = vec![
+            self.inner.register_counter(key, metadata),
+            self.sink.register_counter(key, metadata),
+        ];
+        FanoutCounter::from_counters(counters).into()
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let gauges =
+            vec![self.inner.register_gauge(key, metadata), self.sink.register_gauge(key, metadata)];
+        FanoutGauge::from_gauges(gauges).into()
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let histograms = vec![
+            self.inner.register_histogram(key, metadata),
+            self.sink.register_histogram(key, metadata),
+        ];
+        FanoutHistogram::from_histograms(histograms).into()
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.sink.describe_summary(key_name.clone(), unit.clone(), description.clone());
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let summaries = vec![
+            self.inner.register_summary(key, metadata),
+            self.sink.register_summary(key, metadata),
+        ];
+        FanoutSummary::from_summaries(summaries).into()
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.sink.describe_attributes(key_name.clone(), attributes.clone());
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.sink.enabled(key_name, metadata) || self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        self.sink.flush();
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown();
+        self.sink.shutdown();
+    }
+}
+
+/// A layer for mirroring all operations to a secondary recorder.
+///
+/// Every description and registration is forwarded to both the inner recorder and the sink, and
+/// every operation on the resulting metric handles is applied to both, so the sink sees the same
+/// metrics as the inner recorder.  This allows, for example, exposing an in-process introspection
+/// endpoint alongside a normal exporter.
+///
+/// The sink is shared between every [`Tee`] created from the layer.  A sink which is a
+/// [`DebuggingRecorder`] can be created directly with [`TeeLayer::debugging`], and then inspected
+/// with the [`Snapshotter`] returned by [`TeeLayer::snapshotter`].
+pub struct TeeLayer<S> {
+    sink: Arc<S>,
+}
+
+impl<S> TeeLayer<S> {
+    /// Creates a new `TeeLayer` that mirrors operations to the given sink.
+    pub fn new(sink: S) -> TeeLayer<S> {
+        TeeLayer { sink: Arc::new(sink) }
+    }
+
+    /// Gets a reference to the sink.
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+}
+
+#[cfg(feature = "debugging")]
+#[cfg_attr(docsrs, doc(cfg(feature = "debugging")))]
+impl TeeLayer<DebuggingRecorder> {
+    /// Creates a new `TeeLayer` that mirrors operations to a new [`DebuggingRecorder`].
+    pub fn debugging() -> TeeLayer<DebuggingRecorder> {
+        TeeLayer::new(DebuggingRecorder::new())
+    }
+
+    /// Gets a [`Snapshotter`] attached to the underlying [`DebuggingRecorder`].
+    pub fn snapshotter(&self) -> Snapshotter {
+        self.sink.snapshotter()
+    }
+}
+
+impl<S> Clone for TeeLayer<S> {
+    fn clone(&self) -> Self {
+        TeeLayer { sink: Arc::clone(&self.sink) }
+    }
+}
+
+impl<R, S> Layer<R> for TeeLayer<S> {
+    type Output = Tee<R, S>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Tee { sink: Arc::clone(&self.sink), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TeeLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let operations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Nanoseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let inner = MockBasicRecorder::from_operations(operations.clone());
+        let sink = MockBasicRecorder::from_operations(operations.clone());
+        let tee = TeeLayer::new(sink).layer(inner);
+
+        for operation in operations {
+            operation.apply_to_recorder(&tee);
+        }
+    }
+
+    #[cfg(feature = "debugging")]
+    #[test]
+    fn test_debugging_sink() {
+        use crate::debugging::DebugValue;
+        use crate::{CompositeKey, MetricKind};
+        use metrics::{Key, Recorder};
+
+        let inner = MockBasicRecorder::from_operations(vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+        ]);
+        let layer = TeeLayer::debugging();
+        let snapshotter = layer.snapshotter();
+        let tee = layer.layer(inner);
+
+        tee.describe_counter("counter_key".into(), Some(Unit::Count), "counter desc".into());
+        tee.register_counter(&"counter_key".into(), &METADATA).increment(42);
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        assert_eq!(
+            snapshot,
+            vec![(
+                CompositeKey::new(MetricKind::Counter, Key::from_name("counter_key")),
+                Some(Unit::Count),
+                Some("counter desc".into()),
+                DebugValue::Counter(42),
+            )]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString,
+    Summary, Unit,
+};
+
+/// How the current tenant is applied to a metric key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TenantMode {
+    /// The tenant is applied as a prefix to the metric name, in the format of
+    /// `<tenant>.<remaining>`.
+    Prefix,
+
+    /// The tenant is applied as a label with the given key.
+    ///
+    /// If the key already has a label with the same key, the value given at the call site takes
+    /// precedence and the tenant label is not added.
+    Label(SharedString),
+}
+
+/// Separates metrics by the tenant they were recorded for.
+///
+/// More information on the behavior of the layer can be found in [`TenantLayer`].
+pub struct Tenant<R, F> {
+    f: F,
+    mode: TenantMode,
+    inner: R,
+}
+
+impl<R, F> Tenant<R, F>
+where
+    F: Fn() -> Option<SharedString>,
+{
+    fn tenant_key(&self, key: &Key) -> Key {
+        let tenant = match (self.f)() {
+            Some(tenant) => tenant,
+            None => return key.clone(),
+        };
+
+        match &self.mode {
+            TenantMode::Prefix => {
+                let mut new_name = String::with_capacity(tenant.len() + 1 + key.name().len());
+                new_name.push_str(tenant.as_ref());
+                new_name.push('.');
+                new_name.push_str(key.name());
+
+                Key::from_parts(new_name, key.labels())
+            }
+            TenantMode::Label(label_key) => {
+                let label = Label::new(label_key.clone(), tenant);
+                if key.labels().any(|existing| existing.key() == label.key()) {
+                    key.clone()
+                } else {
+                    key.with_extra_labels(vec![label])
+                }
+            }
+        }
+    }
+
+    fn tenant_key_name(&self, key_name: KeyName) -> KeyName {
+        if self.mode != TenantMode::Prefix {
+            return key_name;
+        }
+
+        match (self.f)() {
+            Some(tenant) => {
+                let mut new_name =
+                    String::with_capacity(tenant.len() + 1 + key_name.as_str().len());
+                new_name.push_str(tenant.as_ref());
+                new_name.push('.');
+                new_name.push_str(key_name.as_str());
+
+                KeyName::from(new_name)
+            }
+            None => key_name,
+        }
+    }
+}
+
+impl<R, F> Recorder for Tenant<R, F>
+where
+    R: Recorder,
+    F: Fn() -> Option<SharedString>,
+{
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.tenant_key_name(key_name);
+        self.inner.describe_counter(new_key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.tenant_key_name(key_name);
+        self.inner.describe_gauge(new_key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.tenant_key_name(key_name);
+        self.inner.describe_histogram(new_key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let new_key = self.tenant_key(key);
+        self.inner.register_counter(&new_key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let new_key = self.tenant_key(key);
+        self.inner.register_gauge(&new_key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let new_key = self.tenant_key(key);
+        self.inner.register_histogram(&new_key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_key_name = self.tenant_key_name(key_name);
+        self.inner.describe_summary(new_key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let new_key = self.tenant_key(key);
+        self.inner.register_summary(&new_key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        let new_key_name = self.tenant_key_name(key_name);
+        self.inner.describe_attributes(new_key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for separating metrics by the tenant they were recorded for.
+///
+/// The given function is called whenever a metric is described or registered, and returns the
+/// tenant the current operation belongs to, typically read from a thread-local or task-local
+/// variable.  The tenant is then applied to the metric key either as a name prefix or as a label,
+/// depending on the [`TenantMode`].  When the function returns `None`, the key is passed through
+/// unchanged.
+///
+/// As handles are bound to the key they were registered with, the tenant is resolved at
+/// registration time: a handle registered on behalf of one tenant keeps recording to that tenant's
+/// series even if it is later used on behalf of another.
+///
+/// Descriptions only carry a metric name, so they are only affected in prefix mode.
+pub struct TenantLayer<F> {
+    f: F,
+    mode: TenantMode,
+}
+
+impl<F> TenantLayer<F>
+where
+    F: Fn() -> Option<SharedString>,
+{
+    /// Creates a new `TenantLayer` which prefixes metric names with the current tenant.
+    pub fn prefix(f: F) -> TenantLayer<F> {
+        TenantLayer { f, mode: TenantMode::Prefix }
+    }
+
+    /// Creates a new `TenantLayer` which adds the current tenant as a label with the given key.
+    pub fn label<K: Into<SharedString>>(label_key: K, f: F) -> TenantLayer<F> {
+        TenantLayer { f, mode: TenantMode::Label(label_key.into()) }
+    }
+}
+
+impl<R, F> Layer<R> for TenantLayer<F>
+where
+    F: Fn() -> Option<SharedString> + Clone,
+{
+    type Output = Tenant<R, F>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Tenant { f: self.f.clone(), mode: self.mode.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::TenantLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Key, Label, SharedString, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    thread_local! {
+        static CURRENT_TENANT: RefCell<Option<&'static str>> = RefCell::new(None);
+    }
+
+    fn current_tenant() -> Option<SharedString> {
+        CURRENT_TENANT.with(|tenant| tenant.borrow().map(SharedString::const_str))
+    }
+
+    fn set_tenant(tenant: Option<&'static str>) {
+        CURRENT_TENANT.with(|current| *current.borrow_mut() = tenant);
+    }
+
+    #[test]
+    fn test_prefix_mode() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "acme.counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                "acme.counter_key".into(),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge("globex.gauge_key".into(), Gauge::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let layer = TenantLayer::prefix(current_tenant);
+        let tenant = layer.layer(recorder);
+
+        set_tenant(Some("acme"));
+        RecorderOperation::DescribeCounter(
+            "counter_key".into(),
+            Some(Unit::Count),
+            "counter desc".into(),
+        )
+        .apply_to_recorder(&tenant);
+        RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA)
+            .apply_to_recorder(&tenant);
+
+        set_tenant(Some("globex"));
+        RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA)
+            .apply_to_recorder(&tenant);
+
+        set_tenant(None);
+        RecorderOperation::RegisterHistogram("histogram_key".into(), Histogram::noop(), &METADATA)
+            .apply_to_recorder(&tenant);
+    }
+
+    #[test]
+    fn test_label_mode() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts(
+                    "counter_key",
+                    vec![Label::new("method", "GET"), Label::new("tenant", "acme")],
+                ),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("tenant", "override")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let layer = TenantLayer::label("tenant", current_tenant);
+        let tenant = layer.layer(recorder);
+
+        set_tenant(Some("acme"));
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter(
+                Key::from_parts("counter_key", vec![Label::new("method", "GET")]),
+                Counter::noop(),
+                &METADATA,
+            ),
+            RecorderOperation::RegisterGauge(
+                Key::from_parts("gauge_key", vec![Label::new("tenant", "override")]),
+                Gauge::noop(),
+                &METADATA,
+            ),
+        ];
+
+        for operation in inputs {
+            operation.apply_to_recorder(&tenant);
+        }
+        set_tenant(None);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Label, Metadata, Recorder,
+    SharedString, Summary, TimeBase, Unit,
+};
+
+struct TimeBaseHistogram {
+    inner: Histogram,
+    time_base: TimeBase,
+}
+
+impl HistogramFn for TimeBaseHistogram {
+    fn record(&self, value: f64) {
+        self.inner.record(value);
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        self.inner.record_with_exemplar(value, exemplar.iter());
+    }
+
+    fn record_duration(&self, duration: Duration) {
+        self.inner.record(self.time_base.convert(duration));
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        self.inner.record_many(values);
+    }
+
+    fn is_expired(&self) -> bool {
+        self.inner.is_expired()
+    }
+}
+
+/// Records durations into histograms in the time base described for them.
+///
+/// More information on the behavior of the layer can be found in [`TimeBaseConvertLayer`].
+pub struct TimeBaseConvert<R> {
+    default: TimeBase,
+    time_bases: RwLock<HashMap<String, TimeBase>>,
+    inner: R,
+}
+
+impl<R> TimeBaseConvert<R> {
+    fn time_base(&self, key_name: &str) -> TimeBase {
+        let time_bases = self.time_bases.read().unwrap_or_else(PoisonError::into_inner);
+        time_bases.get(key_name).copied().unwrap_or(self.default)
+    }
+}
+
+impl<R: Recorder> Recorder for TimeBaseConvert<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        if let Some(time_base) = attributes.get::<TimeBase>() {
+            let mut time_bases = self.time_bases.write().unwrap_or_else(PoisonError::into_inner);
+            let _ = time_bases.insert(key_name.as_str().to_owned(), *time_base);
+        }
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        self.inner.register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let histogram = self.inner.register_histogram(key, metadata);
+        match self.time_base(key.name()) {
+            TimeBase::Seconds => histogram,
+            time_base => {
+                Histogram::from_arc(Arc::new(TimeBaseHistogram { inner: histogram, time_base }))
+            }
+        }
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        // Summaries only record plain numbers, so there are no durations to convert.
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for recording durations into histograms in the time base described for them.
+///
+/// Durations recorded via [`Histogram::record_duration`] are recorded in seconds by default.  This
+/// layer records them in the [`TimeBase`] described for each histogram instead, via the
+/// [`describe_attributes!`](metrics::describe_attributes) macro, or in the default time base of
+/// the layer if none was described.  Only histograms registered after their time base was
+/// described are affected, and values recorded as plain numbers are passed through unchanged.
+#[derive(Default)]
+pub struct TimeBaseConvertLayer {
+    default: TimeBase,
+}
+
+impl TimeBaseConvertLayer {
+    /// Creates a new `TimeBaseConvertLayer` which records durations in seconds by default.
+    pub fn new() -> TimeBaseConvertLayer {
+        TimeBaseConvertLayer::default()
+    }
+
+    /// Sets the time base used for histograms which have no time base described.
+    pub fn default_time_base(&mut self, time_base: TimeBase) -> &mut TimeBaseConvertLayer {
+        self.default = time_base;
+        self
+    }
+}
+
+impl<R> Layer<R> for TimeBaseConvertLayer {
+    type Output = TimeBaseConvert<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        TimeBaseConvert { default: self.default, time_bases: RwLock::new(HashMap::new()), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::TimeBaseConvertLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Attributes, Histogram, HistogramFn, Recorder, TimeBase};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[derive(Default)]
+    struct CapturingHistogram(Mutex<Vec<f64>>);
+
+    impl HistogramFn for CapturingHistogram {
+        fn record(&self, value: f64) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    #[test]
+    fn test_time_base() {
+        let captured = Arc::new(CapturingHistogram::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        for name in ["histogram_key", "other_histogram_key"] {
+            expect_register_histogram(
+                &mut recorder,
+                name.into(),
+                Histogram::from_arc(Arc::clone(&captured)),
+            );
+        }
+        expect_describe_attributes(&mut recorder, "histogram_key".into());
+
+        let convert = TimeBaseConvertLayer::new().layer(recorder);
+        convert.describe_attributes(
+            "histogram_key".into(),
+            Attributes::new().with(TimeBase::Milliseconds),
+        );
+
+        let duration = Duration::from_millis(1500);
+        let histogram = convert.register_histogram(&"histogram_key".into(), &METADATA);
+        histogram.record_duration(duration);
+        histogram.record(2.0);
+        convert
+            .register_histogram(&"other_histogram_key".into(), &METADATA)
+            .record_duration(duration);
+
+        assert_eq!(*captured.0.lock().unwrap(), vec![1500.0, 2.0, 1.5]);
+    }
+
+    #[test]
+    fn test_default_time_base() {
+        let captured = Arc::new(CapturingHistogram::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_histogram(
+            &mut recorder,
+            "histogram_key".into(),
+            Histogram::from_arc(Arc::clone(&captured)),
+        );
+
+        let mut layer = TimeBaseConvertLayer::new();
+        layer.default_time_base(TimeBase::Microseconds);
+        let convert = layer.layer(recorder);
+
+        convert
+            .register_histogram(&"histogram_key".into(), &METADATA)
+            .record_duration(Duration::from_millis(2));
+
+        assert_eq!(*captured.0.lock().unwrap(), vec![2000.0]);
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
+
+/// Handle for enabling or disabling a [`Toggle`] at runtime.
+///
+/// Handles are cheap to clone, and every clone controls the same switch.
+#[derive(Clone, Debug)]
+pub struct ToggleHandle {
+    enabled: Arc<AtomicBool>,
+}
+
+impl ToggleHandle {
+    /// Enables metrics.
+    pub fn enable(&self) {
+        self.set_enabled(true);
+    }
+
+    /// Disables metrics.
+    pub fn disable(&self) {
+        self.set_enabled(false);
+    }
+
+    /// Sets whether or not metrics are enabled.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether or not metrics are currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
+
+/// Enables or disables metrics at runtime.
+///
+/// More information on the behavior of the layer can be found in [`ToggleLayer`].
+pub struct Toggle<R> {
+    handle: ToggleHandle,
+    inner: R,
+}
+
+impl<R> Toggle<R> {
+    /// Gets a handle for enabling or disabling this recorder.
+    pub fn handle(&self) -> ToggleHandle {
+        self.handle.clone()
+    }
+}
+
+impl<R: Recorder> Recorder for Toggle<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key_name, unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key_name, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        if !self.handle.is_enabled() {
+            return Counter::noop();
+        }
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        if !self.handle.is_enabled() {
+            return Gauge::noop();
+        }
+        self.inner.register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        if !self.handle.is_enabled() {
+            return Histogram::noop();
+        }
+        self.inner.register_histogram(key, metadata)
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_summary(key_name, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        if !self.handle.is_enabled() {
+            return Summary::noop();
+        }
+        self.inner.register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.handle.is_enabled() && self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for enabling or disabling metrics at runtime.
+///
+/// While disabled, every registration hands back a no-op handle without calling the inner recorder
+/// at all, which removes nearly all of the overhead of emitting metrics.  As the metric macros
+/// register a metric each time they're called, this covers emission through the macros as well.
+/// Handles which were registered while enabled, and held on to, are not affected.
+///
+/// Descriptions are always passed through to the inner recorder.
+///
+/// The switch is controlled through a [`ToggleHandle`], which can be acquired from the layer or from
+/// any [`Toggle`] it creates, or by passing in an existing [`AtomicBool`] with
+/// [`ToggleLayer::from_flag`].
+#[derive(Clone, Debug)]
+pub struct ToggleLayer {
+    handle: ToggleHandle,
+}
+
+impl ToggleLayer {
+    /// Creates a new `ToggleLayer`, initially enabled or disabled based on `enabled`.
+    pub fn new(enabled: bool) -> ToggleLayer {
+        ToggleLayer::from_flag(Arc::new(AtomicBool::new(enabled)))
+    }
+
+    /// Creates a new `ToggleLayer` controlled by the given flag.
+    ///
+    /// Metrics are enabled while the flag is `true`, and disabled while it is `false`.
+    pub fn from_flag(enabled: Arc<AtomicBool>) -> ToggleLayer {
+        ToggleLayer { handle: ToggleHandle { enabled } }
+    }
+
+    /// Gets a handle for enabling or disabling metrics.
+    ///
+    /// Every [`Toggle`] created from this layer shares the same switch, so changes made through the
+    /// handle apply to all of them.
+    pub fn handle(&self) -> ToggleHandle {
+        self.handle.clone()
+    }
+}
+
+impl Default for ToggleLayer {
+    fn default() -> Self {
+        ToggleLayer::new(true)
+    }
+}
+
+impl<R> Layer<R> for ToggleLayer {
+    type Output = Toggle<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        Toggle { handle: self.handle.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use super::ToggleLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[test]
+    fn test_basic_functionality() {
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterHistogram(
+                "histogram_key".into(),
+                Histogram::noop(),
+                &METADATA,
+            ),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let layer = ToggleLayer::new(false);
+        let handle = layer.handle();
+        let toggle = layer.layer(recorder);
+
+        // Descriptions pass through even while disabled, but registrations do not.
+        RecorderOperation::DescribeCounter(
+            "counter_key".into(),
+            Some(Unit::Count),
+            "counter desc".into(),
+        )
+        .apply_to_recorder(&toggle);
+        RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA)
+            .apply_to_recorder(&toggle);
+
+        handle.enable();
+        RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA)
+            .apply_to_recorder(&toggle);
+        RecorderOperation::RegisterHistogram("histogram_key".into(), Histogram::noop(), &METADATA)
+            .apply_to_recorder(&toggle);
+
+        handle.disable();
+        RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA)
+            .apply_to_recorder(&toggle);
+    }
+
+    #[test]
+    fn test_from_flag() {
+        let flag = Arc::new(AtomicBool::new(true));
+        let layer = ToggleLayer::from_flag(Arc::clone(&flag));
+        let handle = layer.layer(()).handle();
+
+        assert!(handle.is_enabled());
+        flag.store(false, Ordering::Relaxed);
+        assert!(!handle.is_enabled());
+        handle.set_enabled(true);
+        assert!(flag.load(Ordering::Relaxed));
+    }
+}
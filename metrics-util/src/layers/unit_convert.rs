@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::layers::Layer;
+use metrics::{
+    Attributes, Counter, Gauge, GaugeCallback, GaugeFn, Histogram, HistogramFn, Key, KeyName,
+    Label, Metadata, Recorder, SharedString, Summary, SummaryFn, Unit,
+};
+
+/// The dimension of time-based units, whose base unit is the second.
+const TIME: u8 = 0;
+
+/// Gets the dimension of the given unit, and how many of the dimension's base unit it represents.
+///
+/// Units are only convertible between each other if they share the same dimension.
+fn unit_scale(unit: &Unit) -> Option<(u8, f64)> {
+    const DATA: u8 = 1;
+    const DATA_RATE: u8 = 2;
+
+    let scale = match unit {
+        Unit::Seconds => (TIME, 1.0),
+        Unit::Milliseconds => (TIME, 1e-3),
+        Unit::Microseconds => (TIME, 1e-6),
+        Unit::Nanoseconds => (TIME, 1e-9),
+        Unit::Tebibytes => (DATA, 1024.0 * 1024.0 * 1024.0 * 1024.0),
+        Unit::Gigibytes => (DATA, 1024.0 * 1024.0 * 1024.0),
+        Unit::Mebibytes => (DATA, 1024.0 * 1024.0),
+        Unit::Kibibytes => (DATA, 1024.0),
+        Unit::Bytes => (DATA, 1.0),
+        Unit::TerabitsPerSecond => (DATA_RATE, 1e12),
+        Unit::GigabitsPerSecond => (DATA_RATE, 1e9),
+        Unit::MegabitsPerSecond => (DATA_RATE, 1e6),
+        Unit::KilobitsPerSecond => (DATA_RATE, 1e3),
+        Unit::BitsPerSecond => (DATA_RATE, 1.0),
+        Unit::Count
+        | Unit::Percent
+        | Unit::CountPerSecond
+        | Unit::Ratio
+        | Unit::Celsius
+        | Unit::Joules
+        | Unit::Packets
+        | Unit::Custom(_) => return None,
+    };
+    Some(scale)
+}
+
+#[derive(Clone, Debug)]
+struct Conversion {
+    factor: f64,
+    /// The number of seconds in the unit values are recorded in, for time-based units.
+    from_seconds: Option<f64>,
+    to: Unit,
+}
+
+struct ScaledGauge {
+    inner: Gauge,
+    factor: f64,
+}
+
+impl GaugeFn for ScaledGauge {
+    fn increment(&self, value: f64) {
+        self.inner.increment(value * self.factor);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.inner.decrement(value * self.factor);
+    }
+
+    fn set(&self, value: f64) {
+        self.inner.set(value * self.factor);
+    }
+
+    fn set_max(&self, value: f64) {
+        self.inner.set_max(value * self.factor);
+    }
+
+    fn set_min(&self, value: f64) {
+        self.inner.set_min(value * self.factor);
+    }
+
+    fn set_callback(&self, callback: GaugeCallback) {
+        let factor = self.factor;
+        self.inner.set_callback(move || callback.value() * factor);
+    }
+
+    fn is_expired(&self) -> bool {
+        self.inner.is_expired()
+    }
+}
+
+struct ScaledHistogram<H> {
+    inner: H,
+    factor: f64,
+    from_seconds: Option<f64>,
+}
+
+impl HistogramFn for ScaledHistogram<Histogram> {
+    fn record(&self, value: f64) {
+        self.inner.record(value * self.factor);
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        self.inner.record_with_exemplar(value * self.factor, exemplar.iter());
+    }
+
+    fn record_duration(&self, duration: Duration) {
+        // Durations are expressed in the unit values are recorded in before being scaled, and only
+        // have a meaning for time-based units.
+        match self.from_seconds {
+            Some(from_seconds) => self.record(duration.as_secs_f64() / from_seconds),
+            None => self.inner.record_duration(duration),
+        }
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        let scaled = values.iter().map(|value| value * self.factor).collect::<Vec<_>>();
+        self.inner.record_many(&scaled);
+    }
+
+    fn is_expired(&self) -> bool {
+        self.inner.is_expired()
+    }
+}
+
+impl SummaryFn for ScaledHistogram<Summary> {
+    fn record(&self, value: f64) {
+        self.inner.record(value * self.factor);
+    }
+}
+
+/// Converts the values of specific metrics from one unit to another.
+///
+/// More information on the behavior of the layer can be found in [`UnitConvertLayer`].
+pub struct UnitConvert<R> {
+    conversions: HashMap<String, Conversion>,
+    inner: R,
+}
+
+impl<R> UnitConvert<R> {
+    fn convert_unit(&self, key_name: &KeyName, unit: Option<Unit>) -> Option<Unit> {
+        self.conversions.get(key_name.as_str()).map(|conversion| conversion.to.clone()).or(unit)
+    }
+}
+
+impl<R: Recorder> Recorder for UnitConvert<R> {
+    fn describe_counter(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key_name, unit, description)
+    }
+
+    fn describe_gauge(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_unit = self.convert_unit(&key_name, unit);
+        self.inner.describe_gauge(key_name, new_unit, description)
+    }
+
+    fn describe_histogram(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_unit = self.convert_unit(&key_name, unit);
+        self.inner.describe_histogram(key_name, new_unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.inner.register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let gauge = self.inner.register_gauge(key, metadata);
+        match self.conversions.get(key.name()) {
+            Some(conversion) => {
+                Gauge::from_arc(Arc::new(ScaledGauge { inner: gauge, factor: conversion.factor }))
+            }
+            None => gauge,
+        }
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let histogram = self.inner.register_histogram(key, metadata);
+        match self.conversions.get(key.name()) {
+            Some(conversion) => Histogram::from_arc(Arc::new(ScaledHistogram {
+                inner: histogram,
+                factor: conversion.factor,
+                from_seconds: conversion.from_seconds,
+            })),
+            None => histogram,
+        }
+    }
+
+    fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString) {
+        let new_unit = self.convert_unit(&key_name, unit);
+        self.inner.describe_summary(key_name, new_unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let summary = self.inner.register_summary(key, metadata);
+        match self.conversions.get(key.name()) {
+            Some(conversion) => Summary::from_arc(Arc::new(ScaledHistogram {
+                inner: summary,
+                factor: conversion.factor,
+                from_seconds: None,
+            })),
+            None => summary,
+        }
+    }
+
+    fn describe_attributes(&self, key_name: KeyName, attributes: Attributes) {
+        self.inner.describe_attributes(key_name, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A layer for converting the values of specific metrics from one unit to another.
+///
+/// Conversions are configured per metric name, with the unit that values are recorded in, and the
+/// unit that they should be converted to.  For example, a library might record a histogram in
+/// milliseconds while the rest of an application records in seconds.
+///
+/// Values written to gauges, histograms and summaries with a configured conversion are scaled
+/// before being forwarded to the inner recorder, and any description of those metrics has its unit
+/// rewritten to the converted unit.  Counters are not converted, as their integer values cannot be
+/// scaled without losing precision.
+///
+/// Only units of the same kind can be converted between each other: time-based units, data-based
+/// units, and data rate-based units.
+#[derive(Default)]
+pub struct UnitConvertLayer {
+    conversions: HashMap<String, Conversion>,
+}
+
+impl UnitConvertLayer {
+    /// Adds a conversion for the given metric.
+    ///
+    /// If a conversion already exists for the given metric, it will be overwritten.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` cannot be converted to `to`, such as when converting from seconds to bytes,
+    /// or if either unit is not convertible at all, such as `Unit::Count`.
+    pub fn add_conversion<N>(&mut self, name: N, from: Unit, to: Unit) -> &mut UnitConvertLayer
+    where
+        N: Into<String>,
+    {
+        let (dimension, from_scale, to_scale) = match (unit_scale(&from), unit_scale(&to)) {
+            (Some((from_dim, from_scale)), Some((to_dim, to_scale))) if from_dim == to_dim => {
+                (from_dim, from_scale, to_scale)
+            }
+            _ => panic!("cannot convert from {} to {}", from.as_str(), to.as_str()),
+        };
+
+        let conversion = Conversion {
+            factor: from_scale / to_scale,
+            from_seconds: (dimension == TIME).then_some(from_scale),
+            to,
+        };
+        let _ = self.conversions.insert(name.into(), conversion);
+        self
+    }
+}
+
+impl<R> Layer<R> for UnitConvertLayer {
+    type Output = UnitConvert<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        UnitConvert { conversions: self.conversions.clone(), inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::UnitConvertLayer;
+    use crate::layers::Layer;
+    use crate::test_util::*;
+    use metrics::{Counter, Gauge, Histogram, HistogramFn, Recorder, Unit};
+
+    static METADATA: metrics::Metadata =
+        metrics::Metadata::new(module_path!(), metrics::Level::INFO, Some(module_path!()));
+
+    #[derive(Default)]
+    struct CapturingHistogram(Mutex<Vec<f64>>);
+
+    impl HistogramFn for CapturingHistogram {
+        fn record(&self, value: f64) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    #[test]
+    fn test_basic_functionality() {
+        let inputs = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key".into(),
+                Some(Unit::Kibibytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Milliseconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "other_histogram_key".into(),
+                Some(Unit::Milliseconds),
+                "other histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+        ];
+
+        let expectations = vec![
+            RecorderOperation::DescribeCounter(
+                "counter_key".into(),
+                Some(Unit::Count),
+                "counter desc".into(),
+            ),
+            RecorderOperation::DescribeGauge(
+                "gauge_key".into(),
+                Some(Unit::Bytes),
+                "gauge desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "histogram_key".into(),
+                Some(Unit::Seconds),
+                "histogram desc".into(),
+            ),
+            RecorderOperation::DescribeHistogram(
+                "other_histogram_key".into(),
+                Some(Unit::Milliseconds),
+                "other histogram desc".into(),
+            ),
+            RecorderOperation::RegisterCounter("counter_key".into(), Counter::noop(), &METADATA),
+            RecorderOperation::RegisterGauge("gauge_key".into(), Gauge::noop(), &METADATA),
+        ];
+
+        let recorder = MockBasicRecorder::from_operations(expectations);
+        let mut layer = UnitConvertLayer::default();
+        layer.add_conversion("gauge_key", Unit::Kibibytes, Unit::Bytes).add_conversion(
+            "histogram_key",
+            Unit::Milliseconds,
+            Unit::Seconds,
+        );
+        let convert = layer.layer(recorder);
+
+        for operation in inputs {
+            operation.apply_to_recorder(&convert);
+        }
+    }
+
+    #[test]
+    fn test_value_scaling() {
+        let captured = Arc::new(CapturingHistogram::default());
+
+        let mut recorder = MockBasicRecorder::new();
+        expect_register_histogram(
+            &mut recorder,
+            "histogram_key".into(),
+            Histogram::from_arc(Arc::clone(&captured)),
+        );
+        expect_register_histogram(
+            &mut recorder,
+            "other_histogram_key".into(),
+            Histogram::from_arc(Arc::clone(&captured)),
+        );
+
+        let mut layer = UnitConvertLayer::default();
+        layer.add_conversion("histogram_key", Unit::Milliseconds, Unit::Seconds);
+        let convert = layer.layer(recorder);
+
+        let histogram = convert.register_histogram(&"histogram_key".into(), &METADATA);
+        histogram.record(1500.0);
+        histogram.record_duration(Duration::from_secs(2));
+        histogram.record_many(&[250.0, 500.0]);
+        convert.register_histogram(&"other_histogram_key".into(), &METADATA).record(1500.0);
+
+        let values = captured.0.lock().unwrap().clone();
+        assert_eq!(values.len(), 5);
+        for (value, expected) in values.iter().zip([1.5, 2.0, 0.25, 0.5, 1500.0]) {
+            assert!((value - expected).abs() < 1e-9, "expected {}, got {}", expected, value);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_incompatible_units() {
+        let mut layer = UnitConvertLayer::default();
+        layer.add_conversion("histogram_key", Unit::Milliseconds, Unit::Bytes);
+    }
+}
@@ -6,7 +6,7 @@
 mod bucket;
 #[cfg(feature = "handles")]
 #[cfg_attr(docsrs, doc(cfg(feature = "handles")))]
-pub use bucket::AtomicBucket;
+pub use bucket::{AtomicBucket, OverflowPolicy};
 
 #[cfg(feature = "debugging")]
 #[cfg_attr(docsrs, doc(cfg(feature = "debugging")))]
@@ -37,11 +37,26 @@ pub use histogram::Histogram;
 mod recoverable;
 pub use recoverable::RecoverableRecorder;
 
+mod reset;
+pub use reset::CounterTracker;
+
+mod cache;
+pub use cache::{CacheStats, HandleCache};
+
 #[cfg(feature = "summary")]
 mod summary;
 #[cfg(feature = "summary")]
 #[cfg_attr(docsrs, doc(cfg(feature = "summary")))]
-pub use summary::Summary;
+pub use summary::{Summary, SummaryWindow};
+
+mod tdigest;
+pub use tdigest::TDigest;
+
+mod ckms;
+pub use ckms::Ckms;
+
+mod exponential;
+pub use exponential::{ExponentialBuckets, ExponentialHistogram};
 
 pub mod layers;
 
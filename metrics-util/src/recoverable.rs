@@ -1,8 +1,8 @@
 use std::sync::{Arc, Weak};
 
 use metrics::{
-    Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SetRecorderError, SharedString,
-    Unit,
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SetRecorderError,
+    SharedString, Summary, Unit,
 };
 
 pub struct RecoveryHandle<R> {
@@ -140,6 +140,45 @@ impl<R: Recorder> Recorder for WeakRecorder<R> {
             Histogram::noop()
         }
     }
+
+    fn describe_summary(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        if let Some(recorder) = self.recorder.upgrade() {
+            recorder.describe_summary(key, unit, description);
+        }
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        if let Some(recorder) = self.recorder.upgrade() {
+            recorder.register_summary(key, metadata)
+        } else {
+            Summary::noop()
+        }
+    }
+
+    fn describe_attributes(&self, key: KeyName, attributes: Attributes) {
+        if let Some(recorder) = self.recorder.upgrade() {
+            recorder.describe_attributes(key, attributes);
+        }
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        match self.recorder.upgrade() {
+            Some(recorder) => recorder.enabled(key_name, metadata),
+            None => false,
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(recorder) = self.recorder.upgrade() {
+            recorder.flush();
+        }
+    }
+
+    fn shutdown(&self) {
+        if let Some(recorder) = self.recorder.upgrade() {
+            recorder.shutdown();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -193,6 +232,14 @@ mod tests {
         fn set(&self, value: f64) {
             self.0.store(value as u64, Ordering::Release);
         }
+
+        fn set_max(&self, value: f64) {
+            self.0.fetch_max(value as u64, Ordering::Release);
+        }
+
+        fn set_min(&self, value: f64) {
+            self.0.fetch_min(value as u64, Ordering::Release);
+        }
     }
 
     impl HistogramFn for HistogramWrapper {
@@ -0,0 +1,170 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use metrics::{atomics::AtomicU64, HistogramFn};
+
+use crate::registry::Storage;
+use crate::Ckms;
+
+/// A histogram backed by a [`Ckms`] summary.
+///
+/// Values are added to a summary which keeps only as many samples as are needed to estimate its
+/// target quantiles within their configured errors.  Values which aren't finite are ignored.
+///
+/// Clones of a `CkmsHistogram` share the same underlying summary.
+#[derive(Clone)]
+pub struct CkmsHistogram {
+    inner: Arc<Mutex<Ckms>>,
+    config: CkmsStorage,
+}
+
+impl CkmsHistogram {
+    fn with_summary<F, V>(&self, f: F) -> V
+    where
+        F: FnOnce(&mut Ckms) -> V,
+    {
+        let mut summary = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut summary)
+    }
+
+    /// Gets the number of values recorded into the histogram.
+    pub fn count(&self) -> usize {
+        self.with_summary(|summary| summary.count())
+    }
+
+    /// Returns `true` if no values have been recorded into the histogram.
+    pub fn is_empty(&self) -> bool {
+        self.with_summary(|summary| summary.is_empty())
+    }
+
+    /// Gets the estimated value at the given quantile.
+    ///
+    /// Returns `None` if no values have been recorded, or if the quantile is less than `0.0` or
+    /// greater than `1.0`.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        self.with_summary(|summary| {
+            summary.compress();
+            summary.quantile(q)
+        })
+    }
+
+    /// Gets a copy of the underlying summary.
+    pub fn snapshot(&self) -> Ckms {
+        self.with_summary(|summary| {
+            summary.compress();
+            summary.clone()
+        })
+    }
+
+    /// Gets a copy of the underlying summary, and resets it.
+    ///
+    /// This is suitable for exporters which push the values recorded since their last push.
+    pub fn take(&self) -> Ckms {
+        let empty = self.config.summary();
+        let mut taken = self.with_summary(|summary| std::mem::replace(summary, empty));
+        taken.compress();
+        taken
+    }
+}
+
+impl HistogramFn for CkmsHistogram {
+    fn record(&self, value: f64) {
+        self.record_many(&[value]);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        self.with_summary(|summary| {
+            for value in values {
+                summary.add(*value);
+            }
+        })
+    }
+}
+
+/// CKMS-based metric storage.
+///
+/// Counters and gauges are stored in the same way as [`AtomicStorage`](super::AtomicStorage), while
+/// histograms are stored as a [`CkmsHistogram`], which estimates a set of target quantiles, each
+/// within its own error bound, without storing every recorded value.  This suits exporters which
+/// need tight guarantees on specific quantiles, such as `0.99` and `0.999`, at the cost of a lock
+/// being taken whenever a value is recorded.
+///
+/// The targets are the same as those of [`Ckms::new`], and default to those of
+/// [`Ckms::with_defaults`].
+///
+/// ```
+/// # use metrics::Key;
+/// # use metrics_util::registry::{CkmsStorage, Registry};
+/// // The 0.99 and 0.999 quantiles, within 0.1% and 0.01% of their true rank.
+/// let storage = CkmsStorage::new(&[(0.99, 0.001), (0.999, 0.0001)]);
+/// let registry = Registry::<Key, _>::new(storage);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CkmsStorage {
+    template: Ckms,
+}
+
+impl CkmsStorage {
+    /// Creates a new `CkmsStorage` whose summaries have the given targets, as pairs of quantile and
+    /// error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the targets are invalid, as described in [`Ckms::new`].
+    pub fn new(targets: &[(f64, f64)]) -> Self {
+        Self { template: Ckms::new(targets) }
+    }
+
+    /// Creates an empty summary with the targets of this storage.
+    pub fn summary(&self) -> Ckms {
+        self.template.clone()
+    }
+}
+
+impl<K> Storage<K> for CkmsStorage {
+    type Counter = Arc<AtomicU64>;
+    type Gauge = Arc<AtomicU64>;
+    type Histogram = CkmsHistogram;
+
+    fn counter(&self, _: &K) -> Self::Counter {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn gauge(&self, _: &K) -> Self::Gauge {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn histogram(&self, _: &K) -> Self::Histogram {
+        CkmsHistogram { inner: Arc::new(Mutex::new(self.summary())), config: self.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{HistogramFn, Key};
+
+    use super::CkmsStorage;
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_ckms_storage() {
+        let storage = CkmsStorage::new(&[(0.5, 0.01), (0.99, 0.001)]);
+        let registry = Registry::new(storage);
+
+        let histogram = registry.get_or_create_histogram(&Key::from_name("latency"), |h| h.clone());
+        for i in 1..=10_000 {
+            histogram.record(f64::from(i));
+        }
+        histogram.record(f64::NAN);
+
+        assert_eq!(histogram.count(), 10_000);
+        let p50 = histogram.quantile(0.5).unwrap();
+        assert!((p50 - 5_000.0).abs() <= 100.0, "p50={}", p50);
+        let p99 = histogram.quantile(0.99).unwrap();
+        assert!((p99 - 9_900.0).abs() <= 10.0, "p99={}", p99);
+
+        let taken = histogram.take();
+        assert_eq!(taken.count(), 10_000);
+        assert_eq!(taken.targets(), &[(0.5, 0.01), (0.99, 0.001)][..]);
+        assert!(histogram.is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use metrics::{atomics::AtomicU64, HistogramFn};
+
+use crate::registry::Storage;
+use crate::Summary;
+
+/// A histogram backed by a [DDSketch][ddsketch].
+///
+/// Values are added to a [`Summary`], which provides quantiles with relative-error guarantees
+/// using a fixed amount of memory, and which can be merged with other summaries created with the
+/// same configuration.  Values which aren't finite are ignored.
+///
+/// Clones of a `DDSketchHistogram` share the same underlying sketch.
+///
+/// [ddsketch]: https://arxiv.org/abs/1908.10693
+#[derive(Clone)]
+pub struct DDSketchHistogram {
+    inner: Arc<Mutex<Summary>>,
+    config: DDSketchStorage,
+}
+
+impl DDSketchHistogram {
+    fn with_summary<F, V>(&self, f: F) -> V
+    where
+        F: FnOnce(&mut Summary) -> V,
+    {
+        let mut summary = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut summary)
+    }
+
+    /// Gets the number of values recorded into the histogram.
+    pub fn count(&self) -> usize {
+        self.with_summary(|summary| summary.count())
+    }
+
+    /// Returns `true` if no values have been recorded into the histogram.
+    pub fn is_empty(&self) -> bool {
+        self.with_summary(|summary| summary.is_empty())
+    }
+
+    /// Gets the estimated value at the given quantile.
+    ///
+    /// Returns `None` if no values have been recorded, or if the quantile is less than `0.0` or
+    /// greater than `1.0`.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        self.with_summary(|summary| summary.quantile(q))
+    }
+
+    /// Gets a copy of the underlying sketch.
+    pub fn snapshot(&self) -> Summary {
+        self.with_summary(|summary| summary.clone())
+    }
+
+    /// Gets a copy of the underlying sketch, and resets it.
+    ///
+    /// This is suitable for exporters which push the values recorded since their last push.
+    pub fn take(&self) -> Summary {
+        let empty = self.config.summary();
+        self.with_summary(|summary| std::mem::replace(summary, empty))
+    }
+
+    /// Merges the underlying sketch into the given summary.
+    ///
+    /// This can be used to aggregate histograms from multiple shards, or multiple registries, into
+    /// a single sketch, which is only possible if `other` was created with the same configuration
+    /// as this histogram.  Returns `false`, leaving `other` unchanged, if it wasn't.
+    pub fn merge_into(&self, other: &mut Summary) -> bool {
+        self.with_summary(|summary| other.merge(summary).is_ok())
+    }
+}
+
+impl HistogramFn for DDSketchHistogram {
+    fn record(&self, value: f64) {
+        self.record_many(&[value]);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        self.with_summary(|summary| {
+            for value in values.iter().filter(|value| value.is_finite()) {
+                summary.add(*value);
+            }
+        })
+    }
+}
+
+/// DDSketch-based metric storage.
+///
+/// Counters and gauges are stored in the same way as [`AtomicStorage`](super::AtomicStorage), while
+/// histograms are stored as a [`DDSketchHistogram`], which aggregates values into a sketch as they
+/// are recorded rather than buffering every value.  Exporters which push pre-aggregated
+/// distributions can then read, or merge, the sketches directly, at the cost of a lock being taken
+/// whenever a value is recorded.
+///
+/// The parameters of the sketches are the same as those of [`Summary::new`], and default to those
+/// of [`Summary::with_defaults`].
+///
+/// ```
+/// # use metrics::Key;
+/// # use metrics_util::registry::{DDSketchStorage, Registry};
+/// // Quantiles within 1% of their true value.
+/// let storage = DDSketchStorage::new(0.01, 2048, 1.0e-9);
+/// let registry = Registry::<Key, _>::new(storage);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DDSketchStorage {
+    alpha: f64,
+    max_buckets: u32,
+    min_value: f64,
+}
+
+impl DDSketchStorage {
+    /// Creates a new `DDSketchStorage` whose sketches have the given relative error, maximum number
+    /// of buckets, and minimum value distinguishable from zero.
+    ///
+    /// See [`Summary::new`] for more information on the parameters.
+    pub fn new(alpha: f64, max_buckets: u32, min_value: f64) -> Self {
+        Self { alpha, max_buckets, min_value }
+    }
+
+    /// Creates an empty summary with the configuration of this storage.
+    ///
+    /// Summaries created this way can have histograms from this storage merged into them, via
+    /// [`DDSketchHistogram::merge_into`].
+    pub fn summary(&self) -> Summary {
+        Summary::new(self.alpha, self.max_buckets, self.min_value)
+    }
+}
+
+impl Default for DDSketchStorage {
+    fn default() -> Self {
+        Self::new(0.0001, 32_768, 1.0e-9)
+    }
+}
+
+impl<K> Storage<K> for DDSketchStorage {
+    type Counter = Arc<AtomicU64>;
+    type Gauge = Arc<AtomicU64>;
+    type Histogram = DDSketchHistogram;
+
+    fn counter(&self, _: &K) -> Self::Counter {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn gauge(&self, _: &K) -> Self::Gauge {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn histogram(&self, _: &K) -> Self::Histogram {
+        DDSketchHistogram { inner: Arc::new(Mutex::new(self.summary())), config: self.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use metrics::{HistogramFn, Key};
+
+    use super::DDSketchStorage;
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_ddsketch_storage() {
+        let storage = DDSketchStorage::new(0.01, 2048, 1.0e-9);
+        let registry = Registry::new(storage.clone());
+
+        let first = registry.get_or_create_histogram(&Key::from_name("first"), |h| h.clone());
+        let second = registry.get_or_create_histogram(&Key::from_name("second"), |h| h.clone());
+        for i in 1..=100 {
+            first.record(f64::from(i));
+            second.record(f64::from(i + 100));
+        }
+        first.record(f64::NAN);
+
+        assert_eq!(first.count(), 100);
+        assert_relative_eq!(first.quantile(0.5).unwrap(), 50.0, max_relative = 0.01);
+
+        let mut merged = storage.summary();
+        assert!(first.merge_into(&mut merged));
+        assert!(second.merge_into(&mut merged));
+        assert_eq!(merged.count(), 200);
+        assert_relative_eq!(merged.quantile(0.99).unwrap(), 198.0, max_relative = 0.01);
+
+        // Sketches with a different configuration can't be merged.
+        let mut other = DDSketchStorage::default().summary();
+        assert!(!first.merge_into(&mut other));
+
+        let taken = second.take();
+        assert_eq!(taken.count(), 100);
+        assert_relative_eq!(taken.sum(), 15050.0);
+        assert!(second.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use metrics::{atomics::AtomicU64, HistogramFn};
+
+use crate::registry::Storage;
+use crate::ExponentialHistogram;
+
+/// A histogram backed by an [`ExponentialHistogram`].
+///
+/// Values are counted in exponentially sized buckets as they're recorded, so exporters can read
+/// the buckets directly, such as for OTLP exponential histograms or Prometheus native histograms,
+/// rather than re-aggregating raw values.  Values which aren't finite are ignored.
+///
+/// Clones of an `ExponentialBucketHistogram` share the same underlying histogram.
+#[derive(Clone)]
+pub struct ExponentialBucketHistogram {
+    inner: Arc<Mutex<ExponentialHistogram>>,
+    config: ExponentialBucketStorage,
+}
+
+impl ExponentialBucketHistogram {
+    fn with_histogram<F, V>(&self, f: F) -> V
+    where
+        F: FnOnce(&mut ExponentialHistogram) -> V,
+    {
+        let mut histogram = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut histogram)
+    }
+
+    /// Gets the number of values recorded into the histogram.
+    pub fn count(&self) -> u64 {
+        self.with_histogram(|histogram| histogram.count())
+    }
+
+    /// Returns `true` if no values have been recorded into the histogram.
+    pub fn is_empty(&self) -> bool {
+        self.with_histogram(|histogram| histogram.is_empty())
+    }
+
+    /// Gets a copy of the underlying histogram.
+    pub fn snapshot(&self) -> ExponentialHistogram {
+        self.with_histogram(|histogram| histogram.clone())
+    }
+
+    /// Gets a copy of the underlying histogram, and resets it.
+    ///
+    /// This is suitable for exporters which push the values recorded since their last push, such
+    /// as with delta temporality.  The reset histogram starts again from the maximum scale.
+    pub fn take(&self) -> ExponentialHistogram {
+        let empty = self.config.empty_histogram();
+        self.with_histogram(|histogram| std::mem::replace(histogram, empty))
+    }
+
+    /// Merges the underlying histogram into the given histogram.
+    pub fn merge_into(&self, other: &mut ExponentialHistogram) {
+        self.with_histogram(|histogram| other.merge(histogram))
+    }
+}
+
+impl HistogramFn for ExponentialBucketHistogram {
+    fn record(&self, value: f64) {
+        self.record_many(&[value]);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        self.with_histogram(|histogram| {
+            for value in values {
+                histogram.record(*value);
+            }
+        })
+    }
+}
+
+/// Exponential bucket histogram metric storage.
+///
+/// Counters and gauges are stored in the same way as [`AtomicStorage`](super::AtomicStorage), while
+/// histograms are stored as an [`ExponentialBucketHistogram`], which counts values in buckets with
+/// exponentially increasing boundaries, using a bounded amount of memory, at the cost of a lock
+/// being taken whenever a value is recorded.
+///
+/// The parameters of the histograms are the same as those of [`ExponentialHistogram::new`], and
+/// default to those of [`ExponentialHistogram::with_defaults`].
+///
+/// ```
+/// # use metrics::Key;
+/// # use metrics_util::registry::{ExponentialBucketStorage, Registry};
+/// // At most 160 buckets per sign, at the highest scale supported by Prometheus native histograms.
+/// let storage = ExponentialBucketStorage::new(160, 8);
+/// let registry = Registry::<Key, _>::new(storage);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExponentialBucketStorage {
+    max_size: usize,
+    max_scale: i32,
+}
+
+impl ExponentialBucketStorage {
+    /// Creates a new `ExponentialBucketStorage` whose histograms use at most `max_size` buckets for
+    /// each sign, starting at the given maximum scale.
+    ///
+    /// See [`ExponentialHistogram::new`] for more information on the parameters.
+    pub fn new(max_size: usize, max_scale: i32) -> Self {
+        Self { max_size, max_scale }
+    }
+
+    /// Creates an empty histogram with the configuration of this storage.
+    pub fn empty_histogram(&self) -> ExponentialHistogram {
+        ExponentialHistogram::new(self.max_size, self.max_scale)
+    }
+}
+
+impl Default for ExponentialBucketStorage {
+    fn default() -> Self {
+        let histogram = ExponentialHistogram::with_defaults();
+        Self::new(histogram.max_size(), histogram.scale())
+    }
+}
+
+impl<K> Storage<K> for ExponentialBucketStorage {
+    type Counter = Arc<AtomicU64>;
+    type Gauge = Arc<AtomicU64>;
+    type Histogram = ExponentialBucketHistogram;
+
+    fn counter(&self, _: &K) -> Self::Counter {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn gauge(&self, _: &K) -> Self::Gauge {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn histogram(&self, _: &K) -> Self::Histogram {
+        ExponentialBucketHistogram {
+            inner: Arc::new(Mutex::new(self.empty_histogram())),
+            config: self.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{HistogramFn, Key};
+
+    use super::ExponentialBucketStorage;
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_exponential_storage() {
+        let storage = ExponentialBucketStorage::new(20, 8);
+        let registry = Registry::new(storage.clone());
+
+        let first = registry.get_or_create_histogram(&Key::from_name("first"), |h| h.clone());
+        let second = registry.get_or_create_histogram(&Key::from_name("second"), |h| h.clone());
+        for i in 1..=100 {
+            first.record(f64::from(i));
+            second.record(-f64::from(i));
+        }
+        first.record(f64::INFINITY);
+
+        let snapshot = first.snapshot();
+        assert_eq!(snapshot.count(), 100);
+        assert!(snapshot.scale() < 8);
+        assert!(snapshot.positive().counts().len() <= 20);
+
+        let mut merged = storage.empty_histogram();
+        first.merge_into(&mut merged);
+        second.merge_into(&mut merged);
+        assert_eq!(merged.count(), 200);
+        assert_eq!(merged.negative().counts().iter().sum::<u64>(), 100);
+
+        let taken = second.take();
+        assert_eq!(taken.count(), 100);
+        assert!(second.is_empty());
+        assert_eq!(second.snapshot().scale(), 8);
+    }
+}
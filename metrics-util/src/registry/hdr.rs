@@ -0,0 +1,243 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use hdrhistogram::{CreationError, Histogram};
+use metrics::{atomics::AtomicU64, HistogramFn};
+
+use crate::registry::Storage;
+
+/// A histogram backed by an [HdrHistogram][hdrhistogram].
+///
+/// Values are multiplied by the scale of the histogram, and rounded to the nearest integer, before
+/// being recorded, as HdrHistogram only tracks integer values.  Negative values, and values which
+/// aren't finite, are ignored.  Values read back out of the histogram are divided by the scale
+/// again, so they're in the same unit as the values which were recorded.
+///
+/// Clones of an `HdrHistogram` share the same underlying histogram.
+///
+/// [hdrhistogram]: https://docs.rs/hdrhistogram
+#[derive(Clone)]
+pub struct HdrHistogram {
+    inner: Arc<Mutex<Histogram<u64>>>,
+    scale: f64,
+}
+
+impl HdrHistogram {
+    fn with_histogram<F, V>(&self, f: F) -> V
+    where
+        F: FnOnce(&mut Histogram<u64>) -> V,
+    {
+        let mut histogram = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut histogram)
+    }
+
+    /// Gets the number of values recorded into the histogram.
+    pub fn len(&self) -> u64 {
+        self.with_histogram(|histogram| histogram.len())
+    }
+
+    /// Returns `true` if no values have been recorded into the histogram.
+    pub fn is_empty(&self) -> bool {
+        self.with_histogram(|histogram| histogram.is_empty())
+    }
+
+    /// Gets the value at the given quantile, which must be between `0.0` and `1.0`.
+    ///
+    /// Returns `0.0` if no values have been recorded.
+    pub fn value_at_quantile(&self, quantile: f64) -> f64 {
+        self.with_histogram(|histogram| histogram.value_at_quantile(quantile)) as f64 / self.scale
+    }
+
+    /// Gets the lowest value recorded into the histogram.
+    ///
+    /// Returns `0.0` if no values have been recorded.
+    pub fn min(&self) -> f64 {
+        self.with_histogram(|histogram| histogram.min()) as f64 / self.scale
+    }
+
+    /// Gets the highest value recorded into the histogram.
+    ///
+    /// Returns `0.0` if no values have been recorded.
+    pub fn max(&self) -> f64 {
+        self.with_histogram(|histogram| histogram.max()) as f64 / self.scale
+    }
+
+    /// Gets the mean of the values recorded into the histogram.
+    ///
+    /// Returns `0.0` if no values have been recorded.
+    pub fn mean(&self) -> f64 {
+        self.with_histogram(|histogram| histogram.mean()) / self.scale
+    }
+
+    /// Gets the scale applied to values before they're recorded.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Gets a copy of the underlying histogram.
+    ///
+    /// Values in the copy are scaled, as described in [`HdrHistogram`].
+    pub fn snapshot(&self) -> Histogram<u64> {
+        self.with_histogram(|histogram| histogram.clone())
+    }
+
+    /// Gets a copy of the underlying histogram, and resets it.
+    ///
+    /// This is suitable for exporters which push the values recorded since their last push.
+    /// Values in the copy are scaled, as described in [`HdrHistogram`].
+    pub fn take(&self) -> Histogram<u64> {
+        self.with_histogram(|histogram| {
+            let snapshot = histogram.clone();
+            histogram.reset();
+            snapshot
+        })
+    }
+}
+
+impl HistogramFn for HdrHistogram {
+    fn record(&self, value: f64) {
+        self.record_many(&[value]);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        self.with_histogram(|histogram| {
+            for value in values {
+                let scaled = (value * self.scale).round();
+                if scaled.is_finite() && scaled >= 0.0 {
+                    histogram.saturating_record(scaled as u64);
+                }
+            }
+        })
+    }
+}
+
+/// HdrHistogram-based metric storage.
+///
+/// Counters and gauges are stored in the same way as [`AtomicStorage`](super::AtomicStorage), while
+/// histograms are stored as an [`HdrHistogram`], which tracks values within a configurable number
+/// of significant digits, across a high dynamic range, using a fixed amount of memory.  This gives
+/// accurate quantiles, such as for latencies, without having to keep every recorded value around,
+/// at the cost of a lock being taken whenever a value is recorded.
+///
+/// As HdrHistogram only tracks integer values, a scale can be set, via
+/// [`HdrHistogramStorage::scale`], to preserve the precision of fractional values: for example, a
+/// scale of `1_000_000.0` records durations in seconds at a resolution of one microsecond.
+///
+/// ```
+/// # use metrics::Key;
+/// # use metrics_util::registry::{HdrHistogramStorage, Registry};
+/// let storage = HdrHistogramStorage::new(3).expect("valid configuration").scale(1_000_000.0);
+/// let registry = Registry::<Key, _>::new(storage);
+/// ```
+#[derive(Clone, Debug)]
+pub struct HdrHistogramStorage {
+    bounds: Option<(u64, u64)>,
+    sigfig: u8,
+    scale: f64,
+}
+
+impl HdrHistogramStorage {
+    /// Creates a new `HdrHistogramStorage` whose histograms track values with the given number of
+    /// significant digits, between `0` and `5`, resizing as needed to track any value.
+    ///
+    /// An error will be returned if the number of significant digits is invalid.
+    pub fn new(sigfig: u8) -> Result<Self, CreationError> {
+        Histogram::<u64>::new(sigfig)?;
+        Ok(Self { bounds: None, sigfig, scale: 1.0 })
+    }
+
+    /// Creates a new `HdrHistogramStorage` whose histograms track values between `low` and `high`,
+    /// after scaling, with the given number of significant digits, between `0` and `5`.
+    ///
+    /// Histograms with bounds use a fixed amount of memory, and values outside of the bounds are
+    /// clamped to them.
+    ///
+    /// An error will be returned if the bounds, or the number of significant digits, are invalid.
+    pub fn with_bounds(low: u64, high: u64, sigfig: u8) -> Result<Self, CreationError> {
+        Histogram::<u64>::new_with_bounds(low, high, sigfig)?;
+        Ok(Self { bounds: Some((low, high)), sigfig, scale: 1.0 })
+    }
+
+    /// Sets the scale applied to values before they're recorded.
+    ///
+    /// Defaults to `1.0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is not a positive, finite number.
+    pub fn scale(mut self, scale: f64) -> Self {
+        assert!(scale.is_finite() && scale > 0.0, "scale must be positive and finite");
+        self.scale = scale;
+        self
+    }
+}
+
+impl<K> Storage<K> for HdrHistogramStorage {
+    type Counter = Arc<AtomicU64>;
+    type Gauge = Arc<AtomicU64>;
+    type Histogram = HdrHistogram;
+
+    fn counter(&self, _: &K) -> Self::Counter {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn gauge(&self, _: &K) -> Self::Gauge {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn histogram(&self, _: &K) -> Self::Histogram {
+        // The configuration was validated when the storage was created.
+        let histogram = match self.bounds {
+            Some((low, high)) => Histogram::new_with_bounds(low, high, self.sigfig),
+            None => Histogram::new(self.sigfig),
+        }
+        .expect("histogram configuration should be valid");
+
+        HdrHistogram { inner: Arc::new(Mutex::new(histogram)), scale: self.scale }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{HistogramFn, Key};
+
+    use super::HdrHistogramStorage;
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_hdr_storage() {
+        let storage = HdrHistogramStorage::new(3).unwrap().scale(1000.0);
+        let registry = Registry::new(storage);
+        let key = Key::from_name("latency");
+
+        let histogram = registry.get_or_create_histogram(&key, |h| h.clone());
+        for i in 1..=1000 {
+            histogram.record(f64::from(i) / 1000.0);
+        }
+        histogram.record(-1.0);
+        histogram.record(f64::NAN);
+
+        assert_eq!(histogram.len(), 1000);
+        assert_eq!(histogram.min(), 0.001);
+        assert_eq!(histogram.max(), 1.0);
+        assert_eq!(histogram.value_at_quantile(0.5), 0.5);
+        assert_eq!(histogram.value_at_quantile(0.99), 0.99);
+
+        let snapshot = histogram.take();
+        assert_eq!(snapshot.len(), 1000);
+        assert!(histogram.is_empty());
+    }
+
+    #[test]
+    fn test_hdr_storage_bounds() {
+        assert!(HdrHistogramStorage::with_bounds(1, 1, 3).is_err());
+        assert!(HdrHistogramStorage::new(6).is_err());
+
+        let storage = HdrHistogramStorage::with_bounds(1, 1000, 2).unwrap();
+        let registry = Registry::new(storage);
+        let histogram = registry.get_or_create_histogram(&Key::from_name("bounded"), |h| h.clone());
+        histogram.record(5000.0);
+
+        assert_eq!(histogram.len(), 1);
+        assert!(histogram.max() <= 1000.0 * 1.01);
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    hash::BuildHasherDefault,
+    iter::repeat,
+    sync::{PoisonError, RwLock},
+};
+
+use hashbrown::{hash_map::RawEntryMut, HashMap};
+
+use crate::Hashable;
+
+/// A concurrent map holding the metrics of a single kind in a [`Registry`](super::Registry).
+///
+/// Methods which look up a single key are given the hash of the key, as computed by
+/// [`Hashable::hashable`], so that keys are only hashed once per operation.  Implementations which
+/// hash keys themselves, such as when growing, must do so consistently with it.
+pub trait MetricMap<K, V>: Default {
+    /// Calls `op` with the value stored under `key`, if it exists.
+    fn get<O, R>(&self, hash: u64, key: &K, op: O) -> Option<R>
+    where
+        O: FnOnce(&V) -> R;
+
+    /// Calls `op` with the value stored under `key`, first inserting the value returned by `make`
+    /// if it does not already exist.
+    fn get_or_insert_with<F, O, R>(&self, hash: u64, key: &K, make: F, op: O) -> R
+    where
+        F: FnOnce() -> V,
+        O: FnOnce(&V) -> R;
+
+    /// Removes the value stored under `key`.
+    ///
+    /// Returns `true` if the value existed and was removed, `false` otherwise.
+    fn remove(&self, hash: u64, key: &K) -> bool;
+
+    /// Calls `f` with every key and value in the map.
+    ///
+    /// Entries which are added or removed while the map is being visited may not be observed.
+    fn visit<F>(&self, f: F)
+    where
+        F: FnMut(&K, &V);
+
+    /// Calls `f` with a function which visits every key and value in the map, while preventing
+    /// entries from being added or removed until `f` returns.
+    ///
+    /// This allows visiting several maps at a single point in time, by nesting calls.  The default
+    /// implementation doesn't prevent entries from being added or removed, and simply visits the
+    /// map in the same way as [`visit`](MetricMap::visit).
+    fn visit_frozen<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&dyn Fn(&mut dyn FnMut(&K, &V))) -> R,
+    {
+        f(&|visitor: &mut dyn FnMut(&K, &V)| self.visit(visitor))
+    }
+
+    /// Calls `f` with every key and value in the map, visiting entries in parallel where possible.
+    ///
+    /// Entries which are added or removed while the map is being visited may not be observed.  The
+    /// default implementation visits the map serially, in the same way as
+    /// [`visit`](MetricMap::visit).
+    #[cfg(feature = "rayon")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+    fn visit_par<F>(&self, f: F)
+    where
+        K: Send + Sync,
+        V: Send + Sync,
+        F: Fn(&K, &V) + Send + Sync,
+    {
+        self.visit(f);
+    }
+
+    /// Removes every entry for which `f` returns `false`.
+    fn retain<F>(&self, f: F)
+    where
+        F: FnMut(&K, &V) -> bool;
+
+    /// Removes every entry.
+    fn clear(&self);
+}
+
+/// The map implementation backing a [`Registry`](super::Registry).
+///
+/// A backend provides one [`MetricMap`] per kind of metric, each holding that kind's handles.  The
+/// default backend, [`ShardedBackend`], suits most workloads, but others can be provided, such as
+/// one based on a different concurrent map, or on a map optimized for a key set which rarely
+/// changes.
+pub trait MapBackend<K> {
+    /// The map used to store metric handles of type `V`.
+    type Map<V>: MetricMap<K, V>;
+}
+
+/// The default [`MapBackend`] of a [`Registry`](super::Registry), using [`ShardedMap`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShardedBackend;
+
+impl<K> MapBackend<K> for ShardedBackend
+where
+    K: Clone + Eq + Hashable,
+{
+    type Map<V> = ShardedMap<K, V>;
+}
+
+type ShardMap<K, V> = HashMap<K, V, BuildHasherDefault<<K as Hashable>::Hasher>>;
+
+/// A [`MetricMap`] which spreads its entries across a number of hash maps, each behind its own
+/// read-write lock.
+///
+/// There's one shard per CPU, rounded up to the next power of two, which keeps writers inserting
+/// different keys from contending with each other.  Keys are hashed with the hasher of their
+/// [`Hashable`] implementation.
+pub struct ShardedMap<K: Hashable, V> {
+    shards: Vec<RwLock<ShardMap<K, V>>>,
+    shard_mask: usize,
+}
+
+impl<K: Hashable, V> ShardedMap<K, V> {
+    #[inline]
+    fn shard(&self, hash: u64) -> &RwLock<ShardMap<K, V>> {
+        // SAFETY: We initialize the vector of shards with a power-of-two value, and
+        // `self.shard_mask` is `self.shards.len() - 1`, thus we can never have a result from the
+        // masking operation that results in a value which is not in bounds of our shards vector.
+        unsafe { self.shards.get_unchecked(hash as usize & self.shard_mask) }
+    }
+}
+
+impl<K: Hashable, V> Default for ShardedMap<K, V> {
+    fn default() -> Self {
+        let shard_count = std::cmp::max(1, num_cpus::get()).next_power_of_two();
+        let shards =
+            repeat(()).take(shard_count).map(|_| RwLock::new(ShardMap::default())).collect();
+
+        Self { shards, shard_mask: shard_count - 1 }
+    }
+}
+
+impl<K, V> MetricMap<K, V> for ShardedMap<K, V>
+where
+    K: Clone + Eq + Hashable,
+{
+    fn get<O, R>(&self, hash: u64, key: &K, op: O) -> Option<R>
+    where
+        O: FnOnce(&V) -> R,
+    {
+        let shard_read = self.shard(hash).read().unwrap_or_else(PoisonError::into_inner);
+        shard_read.raw_entry().from_key_hashed_nocheck(hash, key).map(|(_, v)| op(v))
+    }
+
+    fn get_or_insert_with<F, O, R>(&self, hash: u64, key: &K, make: F, op: O) -> R
+    where
+        F: FnOnce() -> V,
+        O: FnOnce(&V) -> R,
+    {
+        let shard = self.shard(hash);
+
+        // Try and get the value if it exists, running our operation if we succeed.
+        let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, v)) = shard_read.raw_entry().from_key_hashed_nocheck(hash, key) {
+            op(v)
+        } else {
+            // Switch to write guard and insert the value first.
+            drop(shard_read);
+            let mut shard_write = shard.write().unwrap_or_else(PoisonError::into_inner);
+            let (_, v) = shard_write
+                .raw_entry_mut()
+                .from_key_hashed_nocheck(hash, key)
+                .or_insert_with(|| (key.clone(), make()));
+
+            op(v)
+        }
+    }
+
+    fn remove(&self, hash: u64, key: &K) -> bool {
+        let mut shard_write = self.shard(hash).write().unwrap_or_else(PoisonError::into_inner);
+        let entry = shard_write.raw_entry_mut().from_key_hashed_nocheck(hash, key);
+        if let RawEntryMut::Occupied(entry) = entry {
+            let _ = entry.remove_entry();
+            return true;
+        }
+
+        false
+    }
+
+    fn visit<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        for shard in &self.shards {
+            let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
+            for (key, value) in shard_read.iter() {
+                f(key, value);
+            }
+        }
+    }
+
+    fn visit_frozen<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&dyn Fn(&mut dyn FnMut(&K, &V))) -> R,
+    {
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| shard.read().unwrap_or_else(PoisonError::into_inner))
+            .collect::<Vec<_>>();
+
+        f(&|visitor: &mut dyn FnMut(&K, &V)| {
+            for (key, value) in shards.iter().flat_map(|shard| shard.iter()) {
+                visitor(key, value);
+            }
+        })
+    }
+
+    #[cfg(feature = "rayon")]
+    fn visit_par<F>(&self, f: F)
+    where
+        K: Send + Sync,
+        V: Send + Sync,
+        F: Fn(&K, &V) + Send + Sync,
+    {
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+        // Each shard is visited by a single task, as there's one shard per CPU.
+        self.shards.par_iter().for_each(|shard| {
+            let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
+            for (key, value) in shard_read.iter() {
+                f(key, value);
+            }
+        });
+    }
+
+    fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        for shard in &self.shards {
+            let mut shard_write = shard.write().unwrap_or_else(PoisonError::into_inner);
+            shard_write.retain(|k, v| f(k, v));
+        }
+    }
+
+    fn clear(&self) {
+        for shard in &self.shards {
+            shard.write().unwrap_or_else(PoisonError::into_inner).clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, PoisonError};
+
+    use metrics::{CounterFn, Key};
+
+    use super::{MapBackend, MetricMap};
+    use crate::registry::{AtomicStorage, Registry};
+
+    /// A map which stores its entries in a single list, for checking that backends are pluggable.
+    struct ListMap<V>(Mutex<Vec<(Key, V)>>);
+
+    impl<V> Default for ListMap<V> {
+        fn default() -> Self {
+            Self(Mutex::new(Vec::new()))
+        }
+    }
+
+    impl<V> MetricMap<Key, V> for ListMap<V> {
+        fn get<O, R>(&self, _: u64, key: &Key, op: O) -> Option<R>
+        where
+            O: FnOnce(&V) -> R,
+        {
+            let entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            entries.iter().find(|(k, _)| k == key).map(|(_, v)| op(v))
+        }
+
+        fn get_or_insert_with<F, O, R>(&self, _: u64, key: &Key, make: F, op: O) -> R
+        where
+            F: FnOnce() -> V,
+            O: FnOnce(&V) -> R,
+        {
+            let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            let index = match entries.iter().position(|(k, _)| k == key) {
+                Some(index) => index,
+                None => {
+                    entries.push((key.clone(), make()));
+                    entries.len() - 1
+                }
+            };
+
+            op(&entries[index].1)
+        }
+
+        fn remove(&self, _: u64, key: &Key) -> bool {
+            let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            let len = entries.len();
+            entries.retain(|(k, _)| k != key);
+            entries.len() != len
+        }
+
+        fn visit<F>(&self, mut f: F)
+        where
+            F: FnMut(&Key, &V),
+        {
+            let entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            for (key, value) in entries.iter() {
+                f(key, value);
+            }
+        }
+
+        fn retain<F>(&self, mut f: F)
+        where
+            F: FnMut(&Key, &V) -> bool,
+        {
+            let mut entries = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            entries.retain(|(k, v)| f(k, v));
+        }
+
+        fn clear(&self) {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner).clear();
+        }
+    }
+
+    struct ListBackend;
+
+    impl MapBackend<Key> for ListBackend {
+        type Map<V> = ListMap<V>;
+    }
+
+    #[test]
+    fn test_custom_backend() {
+        let registry = Registry::<Key, AtomicStorage, ListBackend>::with_map_backend(AtomicStorage);
+        let foo = Key::from_name("foo");
+        let bar = Key::from_name("bar");
+
+        registry.get_or_create_counter(&foo, |c| c.increment(1));
+        registry.get_or_create_counter(&foo, |c| c.increment(2));
+        registry.get_or_create_gauge(&bar, |_| ());
+        assert_eq!(registry.get_counter_handles().len(), 1);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.counters(), &[(foo.clone(), 3)][..]);
+        assert_eq!(snapshot.gauges().len(), 1);
+
+        assert!(registry.remove(&foo));
+        assert!(registry.get_counter(&foo).is_none());
+        assert!(registry.get_gauge(&bar).is_some());
+    }
+}
@@ -1,29 +1,69 @@
 //! High-performance metrics storage.
 
+mod map;
+mod router;
+mod sharded;
+mod snapshot;
 mod storage;
-use std::{
-    hash::BuildHasherDefault,
-    iter::repeat,
-    sync::{PoisonError, RwLock},
-};
-
-use hashbrown::{hash_map::RawEntryMut, HashMap};
-use metrics::{Key, KeyHasher};
+mod usage;
+
+use hashbrown::HashMap;
+pub use map::{MapBackend, MetricMap, ShardedBackend, ShardedMap};
+use metrics::Key;
+pub use router::{Routed, StorageRouter};
+pub use sharded::{ShardedCounter, ShardedHistogram, ShardedStorage};
+pub use snapshot::{Snapshot, SnapshotStorage};
 pub use storage::{AtomicStorage, Storage};
+pub use usage::{KeySize, MemoryUsage};
+
+#[cfg(feature = "storage-ckms")]
+mod ckms;
+
+#[cfg(feature = "storage-ckms")]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage-ckms")))]
+pub use ckms::{CkmsHistogram, CkmsStorage};
+
+#[cfg(feature = "storage-compressed")]
+mod compressed;
+
+#[cfg(feature = "storage-compressed")]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage-compressed")))]
+pub use compressed::{CompressedHistogram, CompressedStorage};
+
+#[cfg(feature = "storage-ddsketch")]
+mod ddsketch;
+
+#[cfg(feature = "storage-ddsketch")]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage-ddsketch")))]
+pub use ddsketch::{DDSketchHistogram, DDSketchStorage};
+
+#[cfg(feature = "storage-exponential")]
+mod exponential;
+
+#[cfg(feature = "storage-exponential")]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage-exponential")))]
+pub use exponential::{ExponentialBucketHistogram, ExponentialBucketStorage};
+
+#[cfg(feature = "storage-hdrhistogram")]
+mod hdr;
+
+#[cfg(feature = "storage-hdrhistogram")]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage-hdrhistogram")))]
+pub use hdr::{HdrHistogram, HdrHistogramStorage};
 
 #[cfg(feature = "recency")]
 mod recency;
 
+#[cfg(feature = "recency")]
+use recency::ExpiryHook;
 #[cfg(feature = "recency")]
 #[cfg_attr(docsrs, doc(cfg(feature = "recency")))]
 pub use recency::{
-    Generation, Generational, GenerationalAtomicStorage, GenerationalStorage, Recency,
+    Generation, GenerationTracker, Generational, GenerationalAtomicStorage, GenerationalStorage,
+    Recency,
 };
 
-use crate::Hashable;
-
-type RegistryHasher = KeyHasher;
-type RegistryHashMap<K, V> = HashMap<K, V, BuildHasherDefault<RegistryHasher>>;
+use crate::{Hashable, MetricKind};
 
 /// A high-performance metric registry.
 ///
@@ -42,68 +82,93 @@ type RegistryHashMap<K, V> = HashMap<K, V, BuildHasherDefault<RegistryHasher>>;
 /// In some cases, users may prefer [`GenerationalAtomicStorage`] when know if a metric has been
 /// touched, even if its value has not changed since the last time it was observed, is necessary.
 ///
+/// ## Expiring idle metrics
+///
+/// Registries using [`GenerationalStorage`] can remove metrics which haven't been updated for a
+/// given amount of time, via [`Registry::with_idle_timeout`] and [`Registry::expire_idle`], rather
+/// than exporters tracking the recency of each metric themselves.  A hook can be set via
+/// [`Registry::on_expire`] to be notified of every metric removed this way.
+///
 /// ## Performance
 ///
 /// `Registry` is optimized for reads.
-pub struct Registry<K, S>
+///
+/// Metrics are stored in the maps provided by the map backend `M`, which defaults to
+/// [`ShardedBackend`], spreading metrics across a number of locked hash maps.  Keys are hashed with
+/// the hasher of their [`Hashable`] implementation, so the hasher can be changed by using a key
+/// type with a different `Hashable::Hasher`.  Where the default backend isn't a good fit, such as
+/// for a key set which rarely changes, a different backend can be provided by implementing
+/// [`MapBackend`] and [`MetricMap`], and creating the registry via
+/// [`Registry::with_map_backend`].
+pub struct Registry<K, S, M = ShardedBackend>
 where
     S: Storage<K>,
+    M: MapBackend<K>,
 {
-    counters: Vec<RwLock<RegistryHashMap<K, S::Counter>>>,
-    gauges: Vec<RwLock<RegistryHashMap<K, S::Gauge>>>,
-    histograms: Vec<RwLock<RegistryHashMap<K, S::Histogram>>>,
-    shard_mask: usize,
+    counters: M::Map<S::Counter>,
+    gauges: M::Map<S::Gauge>,
+    histograms: M::Map<S::Histogram>,
     storage: S,
+    #[cfg(feature = "recency")]
+    recency: Option<Recency<K>>,
+    #[cfg(feature = "recency")]
+    expiry_hook: Option<ExpiryHook<K>>,
 }
 
 impl Registry<Key, AtomicStorage> {
     /// Creates a new `Registry` using a regular [`Key`] and atomic storage.
     pub fn atomic() -> Self {
-        let shard_count = std::cmp::max(1, num_cpus::get()).next_power_of_two();
-        let shard_mask = shard_count - 1;
-        let counters =
-            repeat(()).take(shard_count).map(|_| RwLock::new(RegistryHashMap::default())).collect();
-        let gauges =
-            repeat(()).take(shard_count).map(|_| RwLock::new(RegistryHashMap::default())).collect();
-        let histograms =
-            repeat(()).take(shard_count).map(|_| RwLock::new(RegistryHashMap::default())).collect();
-
-        Self { counters, gauges, histograms, shard_mask, storage: AtomicStorage }
+        Self::new(AtomicStorage)
     }
 }
 
 impl<K, S> Registry<K, S>
 where
     S: Storage<K>,
+    ShardedBackend: MapBackend<K>,
 {
     /// Creates a new `Registry`.
     pub fn new(storage: S) -> Self {
-        let shard_count = std::cmp::max(1, num_cpus::get()).next_power_of_two();
-        let shard_mask = shard_count - 1;
-        let counters =
-            repeat(()).take(shard_count).map(|_| RwLock::new(RegistryHashMap::default())).collect();
-        let gauges =
-            repeat(()).take(shard_count).map(|_| RwLock::new(RegistryHashMap::default())).collect();
-        let histograms =
-            repeat(()).take(shard_count).map(|_| RwLock::new(RegistryHashMap::default())).collect();
+        Self::with_map_backend(storage)
+    }
+}
 
-        Self { counters, gauges, histograms, shard_mask, storage }
+impl<K, S, M> Registry<K, S, M>
+where
+    S: Storage<K>,
+    M: MapBackend<K>,
+{
+    /// Creates a new `Registry` using the map backend `M`.
+    pub fn with_map_backend(storage: S) -> Self {
+        Self {
+            counters: Default::default(),
+            gauges: Default::default(),
+            histograms: Default::default(),
+            storage,
+            #[cfg(feature = "recency")]
+            recency: None,
+            #[cfg(feature = "recency")]
+            expiry_hook: None,
+        }
     }
 
     /// Removes all metrics from the registry.
     ///
     /// This operation is eventually consistent: metrics will be removed piecemeal, and this method
     /// does not ensure that callers will see the registry as entirely empty at any given point.
+    ///
+    /// Handles to removed metrics remain safe to use, but updates made through them are no longer
+    /// reflected in the registry.  Handles backed by [`GenerationalStorage`] also report themselves
+    /// as expired.
     pub fn clear(&self) {
-        for shard in &self.counters {
-            shard.write().unwrap_or_else(PoisonError::into_inner).clear();
-        }
-        for shard in &self.gauges {
-            shard.write().unwrap_or_else(PoisonError::into_inner).clear();
-        }
-        for shard in &self.histograms {
-            shard.write().unwrap_or_else(PoisonError::into_inner).clear();
+        #[cfg(feature = "recency")]
+        if let Some(recency) = &self.recency {
+            recency.forget_all();
         }
+
+        self.counters.clear();
+        self.gauges.clear();
+        self.histograms.clear();
     }
 
     /// Visits every counter stored in this registry.
@@ -113,16 +178,11 @@ where
     /// metric that existed at the exact moment that `visit_counters` was called may not actually be observed
     /// if it is deleted before that subshard is reached.  Likewise, a metric that is added after
     /// the call to `visit_counters`, but before `visit_counters` finishes, may also not be observed.
-    pub fn visit_counters<F>(&self, mut collect: F)
+    pub fn visit_counters<F>(&self, collect: F)
     where
         F: FnMut(&K, &S::Counter),
     {
-        for subshard in self.counters.iter() {
-            let shard_read = subshard.read().unwrap_or_else(PoisonError::into_inner);
-            for (key, counter) in shard_read.iter() {
-                collect(key, counter);
-            }
-        }
+        self.counters.visit(collect);
     }
     /// Visits every gauge stored in this registry.
     ///
@@ -131,16 +191,11 @@ where
     /// metric that existed at the exact moment that `visit_gauges` was called may not actually be observed
     /// if it is deleted before that subshard is reached.  Likewise, a metric that is added after
     /// the call to `visit_gauges`, but before `visit_gauges` finishes, may also not be observed.
-    pub fn visit_gauges<F>(&self, mut collect: F)
+    pub fn visit_gauges<F>(&self, collect: F)
     where
         F: FnMut(&K, &S::Gauge),
     {
-        for subshard in self.gauges.iter() {
-            let shard_read = subshard.read().unwrap_or_else(PoisonError::into_inner);
-            for (key, gauge) in shard_read.iter() {
-                collect(key, gauge);
-            }
-        }
+        self.gauges.visit(collect);
     }
 
     /// Visits every histogram stored in this registry.
@@ -150,127 +205,59 @@ where
     /// metric that existed at the exact moment that `visit_histograms` was called may not actually be observed
     /// if it is deleted before that subshard is reached.  Likewise, a metric that is added after
     /// the call to `visit_histograms`, but before `visit_histograms` finishes, may also not be observed.
-    pub fn visit_histograms<F>(&self, mut collect: F)
+    pub fn visit_histograms<F>(&self, collect: F)
     where
         F: FnMut(&K, &S::Histogram),
     {
-        for subshard in self.histograms.iter() {
-            let shard_read = subshard.read().unwrap_or_else(PoisonError::into_inner);
-            for (key, histogram) in shard_read.iter() {
-                collect(key, histogram);
-            }
-        }
+        self.histograms.visit(collect);
     }
 
     /// Retains only counters specified by the predicate.
     ///
     /// Remove all counters for which f(&k, &c) returns false. This operation proceeds
     /// through the "subshards" in the same way as `visit_counters`.
-    pub fn retain_counters<F>(&self, mut f: F)
+    pub fn retain_counters<F>(&self, f: F)
     where
         F: FnMut(&K, &S::Counter) -> bool,
     {
-        for subshard in self.counters.iter() {
-            let mut shard_write = subshard.write().unwrap_or_else(PoisonError::into_inner);
-            shard_write.retain(|k, c| f(k, c));
-        }
+        self.counters.retain(f);
     }
 
     /// Retains only gauges specified by the predicate.
     ///
     /// Remove all gauges for which f(&k, &g) returns false. This operation proceeds
     /// through the "subshards" in the same way as `visit_gauges`.
-    pub fn retain_gauges<F>(&self, mut f: F)
+    pub fn retain_gauges<F>(&self, f: F)
     where
         F: FnMut(&K, &S::Gauge) -> bool,
     {
-        for subshard in self.gauges.iter() {
-            let mut shard_write = subshard.write().unwrap_or_else(PoisonError::into_inner);
-            shard_write.retain(|k, g| f(k, g));
-        }
+        self.gauges.retain(f);
     }
 
     /// Retains only histograms specified by the predicate.
     ///
     /// Remove all histograms for which f(&k, &h) returns false. This operation proceeds
     /// through the "subshards" in the same way as `visit_histograms`.
-    pub fn retain_histograms<F>(&self, mut f: F)
+    pub fn retain_histograms<F>(&self, f: F)
     where
         F: FnMut(&K, &S::Histogram) -> bool,
     {
-        for subshard in self.histograms.iter() {
-            let mut shard_write = subshard.write().unwrap_or_else(PoisonError::into_inner);
-            shard_write.retain(|k, h| f(k, h));
-        }
+        self.histograms.retain(f);
     }
 }
 
-impl<K, S> Registry<K, S>
-where
-    S: Storage<K>,
-    K: Hashable,
-{
-    #[inline]
-    fn get_hash_and_shard_for_counter(
-        &self,
-        key: &K,
-    ) -> (u64, &RwLock<RegistryHashMap<K, S::Counter>>) {
-        let hash = key.hashable();
-
-        // SAFETY: We initialize vector of subshards with a power-of-two value, and
-        // `self.shard_mask` is `self.counters.len() - 1`, thus we can never have a result from the
-        // masking operation that results in a value which is not in bounds of our subshards vector.
-        let shard = unsafe { self.counters.get_unchecked(hash as usize & self.shard_mask) };
-
-        (hash, shard)
-    }
-
-    #[inline]
-    fn get_hash_and_shard_for_gauge(
-        &self,
-        key: &K,
-    ) -> (u64, &RwLock<RegistryHashMap<K, S::Gauge>>) {
-        let hash = key.hashable();
-
-        // SAFETY: We initialize the vector of subshards with a power-of-two value, and
-        // `self.shard_mask` is `self.gauges.len() - 1`, thus we can never have a result from the
-        // masking operation that results in a value which is not in bounds of our subshards vector.
-        let shard = unsafe { self.gauges.get_unchecked(hash as usize & self.shard_mask) };
-
-        (hash, shard)
-    }
-
-    #[inline]
-    fn get_hash_and_shard_for_histogram(
-        &self,
-        key: &K,
-    ) -> (u64, &RwLock<RegistryHashMap<K, S::Histogram>>) {
-        let hash = key.hashable();
-
-        // SAFETY: We initialize the vector of subshards with a power-of-two value, and
-        // `self.shard_mask` is `self.histograms.len() - 1`, thus we can never have a result from
-        // the masking operation that results in a value which is not in bounds of our subshards
-        // vector.
-        let shard = unsafe { self.histograms.get_unchecked(hash as usize & self.shard_mask) };
-
-        (hash, shard)
-    }
-}
-
-impl<K, S> Registry<K, S>
+impl<K, S, M> Registry<K, S, M>
 where
     S: Storage<K>,
+    M: MapBackend<K>,
     K: Eq + Hashable,
 {
     /// Deletes a counter from the registry.
     ///
     /// Returns `true` if the counter existed and was removed, `false` otherwise.
     pub fn delete_counter(&self, key: &K) -> bool {
-        let (hash, shard) = self.get_hash_and_shard_for_counter(key);
-        let mut shard_write = shard.write().unwrap_or_else(PoisonError::into_inner);
-        let entry = shard_write.raw_entry_mut().from_key_hashed_nocheck(hash, key);
-        if let RawEntryMut::Occupied(entry) = entry {
-            let _ = entry.remove_entry();
+        if self.counters.remove(key.hashable(), key) {
+            self.forget_recency(key);
             return true;
         }
 
@@ -281,11 +268,8 @@ where
     ///
     /// Returns `true` if the gauge existed and was removed, `false` otherwise.
     pub fn delete_gauge(&self, key: &K) -> bool {
-        let (hash, shard) = self.get_hash_and_shard_for_gauge(key);
-        let mut shard_write = shard.write().unwrap_or_else(PoisonError::into_inner);
-        let entry = shard_write.raw_entry_mut().from_key_hashed_nocheck(hash, key);
-        if let RawEntryMut::Occupied(entry) = entry {
-            let _ = entry.remove_entry();
+        if self.gauges.remove(key.hashable(), key) {
+            self.forget_recency(key);
             return true;
         }
 
@@ -296,42 +280,83 @@ where
     ///
     /// Returns `true` if the histogram existed and was removed, `false` otherwise.
     pub fn delete_histogram(&self, key: &K) -> bool {
-        let (hash, shard) = self.get_hash_and_shard_for_histogram(key);
-        let mut shard_write = shard.write().unwrap_or_else(PoisonError::into_inner);
-        let entry = shard_write.raw_entry_mut().from_key_hashed_nocheck(hash, key);
-        if let RawEntryMut::Occupied(entry) = entry {
-            let _ = entry.remove_entry();
+        if self.histograms.remove(key.hashable(), key) {
+            self.forget_recency(key);
             return true;
         }
 
         false
     }
 
+    /// Removes every metric, of any kind, registered under the given key.
+    ///
+    /// Returns `true` if any metric existed and was removed, `false` otherwise.  Handles to removed
+    /// metrics remain safe to use, as described in [`clear`](Registry::clear).
+    pub fn remove(&self, key: &K) -> bool {
+        let counter = self.delete_counter(key);
+        let gauge = self.delete_gauge(key);
+        let histogram = self.delete_histogram(key);
+        counter || gauge || histogram
+    }
+
+    /// Retains only the metrics specified by the predicate.
+    ///
+    /// Removes every metric for which `f(&key, kind)` returns `false`, such as all metrics labeled
+    /// with a tenant which has been deleted.  This operation proceeds through the "subshards" in the
+    /// same way as the `visit_*` methods, and handles to removed metrics remain safe to use, as
+    /// described in [`clear`](Registry::clear).
+    pub fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, MetricKind) -> bool,
+    {
+        self.retain_kind(&self.counters, MetricKind::Counter, &mut f);
+        self.retain_kind(&self.gauges, MetricKind::Gauge, &mut f);
+        self.retain_kind(&self.histograms, MetricKind::Histogram, &mut f);
+    }
+
+    fn retain_kind<F, V>(&self, map: &impl MetricMap<K, V>, kind: MetricKind, f: &mut F)
+    where
+        F: FnMut(&K, MetricKind) -> bool,
+    {
+        map.retain(|key, _| {
+            let keep = f(key, kind);
+            if !keep {
+                self.forget_recency(key);
+            }
+            keep
+        });
+    }
+
+    #[cfg(feature = "recency")]
+    fn forget_recency(&self, key: &K) {
+        if let Some(recency) = &self.recency {
+            recency.forget(key);
+        }
+    }
+
+    #[cfg(not(feature = "recency"))]
+    fn forget_recency(&self, _: &K) {}
+
     /// Gets a copy of an existing counter.
     pub fn get_counter(&self, key: &K) -> Option<S::Counter> {
-        let (hash, shard) = self.get_hash_and_shard_for_counter(key);
-        let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
-        shard_read.raw_entry().from_key_hashed_nocheck(hash, key).map(|(_, v)| v.clone())
+        self.counters.get(key.hashable(), key, |v| v.clone())
     }
 
     /// Gets a copy of an existing gauge.
     pub fn get_gauge(&self, key: &K) -> Option<S::Gauge> {
-        let (hash, shard) = self.get_hash_and_shard_for_gauge(key);
-        let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
-        shard_read.raw_entry().from_key_hashed_nocheck(hash, key).map(|(_, v)| v.clone())
+        self.gauges.get(key.hashable(), key, |v| v.clone())
     }
 
     /// Gets a copy of an existing histogram.
     pub fn get_histogram(&self, key: &K) -> Option<S::Histogram> {
-        let (hash, shard) = self.get_hash_and_shard_for_histogram(key);
-        let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
-        shard_read.raw_entry().from_key_hashed_nocheck(hash, key).map(|(_, v)| v.clone())
+        self.histograms.get(key.hashable(), key, |v| v.clone())
     }
 }
 
-impl<K, S> Registry<K, S>
+impl<K, S, M> Registry<K, S, M>
 where
     S: Storage<K>,
+    M: MapBackend<K>,
     K: Clone + Eq + Hashable,
 {
     /// Gets or creates the given counter.
@@ -342,30 +367,7 @@ where
     where
         O: FnOnce(&S::Counter) -> V,
     {
-        let (hash, shard) = self.get_hash_and_shard_for_counter(key);
-
-        // Try and get the handle if it exists, running our operation if we succeed.
-        let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
-        if let Some((_, v)) = shard_read.raw_entry().from_key_hashed_nocheck(hash, key) {
-            op(v)
-        } else {
-            // Switch to write guard and insert the handle first.
-            drop(shard_read);
-            let mut shard_write = shard.write().unwrap_or_else(PoisonError::into_inner);
-            let v = if let Some((_, v)) = shard_write.raw_entry().from_key_hashed_nocheck(hash, key)
-            {
-                v
-            } else {
-                let (_, v) = shard_write
-                    .raw_entry_mut()
-                    .from_key_hashed_nocheck(hash, key)
-                    .or_insert_with(|| (key.clone(), self.storage.counter(key)));
-
-                v
-            };
-
-            op(v)
-        }
+        self.counters.get_or_insert_with(key.hashable(), key, || self.storage.counter(key), op)
     }
 
     /// Gets or creates the given gauge.
@@ -376,30 +378,7 @@ where
     where
         O: FnOnce(&S::Gauge) -> V,
     {
-        let (hash, shard) = self.get_hash_and_shard_for_gauge(key);
-
-        // Try and get the handle if it exists, running our operation if we succeed.
-        let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
-        if let Some((_, v)) = shard_read.raw_entry().from_key_hashed_nocheck(hash, key) {
-            op(v)
-        } else {
-            // Switch to write guard and insert the handle first.
-            drop(shard_read);
-            let mut shard_write = shard.write().unwrap_or_else(PoisonError::into_inner);
-            let v = if let Some((_, v)) = shard_write.raw_entry().from_key_hashed_nocheck(hash, key)
-            {
-                v
-            } else {
-                let (_, v) = shard_write
-                    .raw_entry_mut()
-                    .from_key_hashed_nocheck(hash, key)
-                    .or_insert_with(|| (key.clone(), self.storage.gauge(key)));
-
-                v
-            };
-
-            op(v)
-        }
+        self.gauges.get_or_insert_with(key.hashable(), key, || self.storage.gauge(key), op)
     }
 
     /// Gets or creates the given histogram.
@@ -410,30 +389,7 @@ where
     where
         O: FnOnce(&S::Histogram) -> V,
     {
-        let (hash, shard) = self.get_hash_and_shard_for_histogram(key);
-
-        // Try and get the handle if it exists, running our operation if we succeed.
-        let shard_read = shard.read().unwrap_or_else(PoisonError::into_inner);
-        if let Some((_, v)) = shard_read.raw_entry().from_key_hashed_nocheck(hash, key) {
-            op(v)
-        } else {
-            // Switch to write guard and insert the handle first.
-            drop(shard_read);
-            let mut shard_write = shard.write().unwrap_or_else(PoisonError::into_inner);
-            let v = if let Some((_, v)) = shard_write.raw_entry().from_key_hashed_nocheck(hash, key)
-            {
-                v
-            } else {
-                let (_, v) = shard_write
-                    .raw_entry_mut()
-                    .from_key_hashed_nocheck(hash, key)
-                    .or_insert_with(|| (key.clone(), self.storage.histogram(key)));
-
-                v
-            };
-
-            op(v)
-        }
+        self.histograms.get_or_insert_with(key.hashable(), key, || self.storage.histogram(key), op)
     }
     /// Gets a map of all present counters, mapped by key.
     ///
@@ -467,6 +423,151 @@ where
         });
         histograms
     }
+
+    /// Takes a point-in-time snapshot of every metric in the registry, along with its value.
+    ///
+    /// Unlike the `visit_*` methods, which proceed through the "subshards" one at a time, every
+    /// subshard is locked for the duration of the snapshot, so the snapshot contains exactly the
+    /// metrics which were registered at a single point in time: metrics can't be added or removed
+    /// while it's being taken.  Updates to existing metrics aren't blocked, so while each value is
+    /// read atomically, the values of different metrics may be read a moment apart.
+    ///
+    /// This relies on [`MetricMap::visit_frozen`], so custom map backends which don't override it
+    /// only provide the same guarantees as the `visit_*` methods.
+    ///
+    /// Histograms are not cleared by taking a snapshot.
+    pub fn snapshot(&self) -> Snapshot<K>
+    where
+        S: SnapshotStorage<K>,
+    {
+        self.counters.visit_frozen(|visit_counters| {
+            self.gauges.visit_frozen(|visit_gauges| {
+                self.histograms.visit_frozen(|visit_histograms| {
+                    let mut snapshot = Snapshot {
+                        counters: Vec::new(),
+                        gauges: Vec::new(),
+                        histograms: Vec::new(),
+                    };
+                    visit_counters(&mut |key, counter| {
+                        let value = self.storage.counter_value(counter);
+                        snapshot.counters.push((key.clone(), value));
+                    });
+                    visit_gauges(&mut |key, gauge| {
+                        let value = self.storage.gauge_value(gauge);
+                        snapshot.gauges.push((key.clone(), value));
+                    });
+                    visit_histograms(&mut |key, histogram| {
+                        let values = self.storage.histogram_values(histogram);
+                        snapshot.histograms.push((key.clone(), values));
+                    });
+                    snapshot
+                })
+            })
+        })
+    }
+
+    /// Gets the approximate memory footprint of the registry.
+    ///
+    /// This reports the number of metrics of each kind, the number of bytes taken up by their keys,
+    /// and the number of samples held by histograms, which together account for most of the memory
+    /// used by a registry.  This proceeds through the "subshards" in the same way as the `visit_*`
+    /// methods, and histograms are not cleared.
+    pub fn memory_usage(&self) -> MemoryUsage
+    where
+        K: KeySize,
+        S: SnapshotStorage<K>,
+    {
+        let mut usage = MemoryUsage::default();
+        self.visit_counters(|key, _| {
+            usage.counters += 1;
+            usage.key_bytes += key.key_bytes();
+        });
+        self.visit_gauges(|key, _| {
+            usage.gauges += 1;
+            usage.key_bytes += key.key_bytes();
+        });
+        self.visit_histograms(|key, histogram| {
+            usage.histograms += 1;
+            usage.key_bytes += key.key_bytes();
+            usage.histogram_samples += self.storage.histogram_len(histogram);
+        });
+        usage
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+impl<K, S, M> Registry<K, S, M>
+where
+    S: Storage<K>,
+    M: MapBackend<K>,
+    K: Send + Sync,
+{
+    /// Visits every counter stored in this registry, in parallel.
+    ///
+    /// Subshards are visited concurrently on the [`rayon`] thread pool, so `collect` may be called
+    /// from several threads at once.  Otherwise, this behaves in the same way as
+    /// [`visit_counters`](Registry::visit_counters).
+    pub fn visit_counters_par<F>(&self, collect: F)
+    where
+        S::Counter: Send + Sync,
+        F: Fn(&K, &S::Counter) + Send + Sync,
+    {
+        self.counters.visit_par(collect);
+    }
+
+    /// Visits every gauge stored in this registry, in parallel.
+    ///
+    /// Subshards are visited concurrently on the [`rayon`] thread pool, so `collect` may be called
+    /// from several threads at once.  Otherwise, this behaves in the same way as
+    /// [`visit_gauges`](Registry::visit_gauges).
+    pub fn visit_gauges_par<F>(&self, collect: F)
+    where
+        S::Gauge: Send + Sync,
+        F: Fn(&K, &S::Gauge) + Send + Sync,
+    {
+        self.gauges.visit_par(collect);
+    }
+
+    /// Visits every histogram stored in this registry, in parallel.
+    ///
+    /// Subshards are visited concurrently on the [`rayon`] thread pool, so `collect` may be called
+    /// from several threads at once.  Otherwise, this behaves in the same way as
+    /// [`visit_histograms`](Registry::visit_histograms).
+    pub fn visit_histograms_par<F>(&self, collect: F)
+    where
+        S::Histogram: Send + Sync,
+        F: Fn(&K, &S::Histogram) + Send + Sync,
+    {
+        self.histograms.visit_par(collect);
+    }
+
+    /// Visits every counter, gauge, and histogram stored in this registry, in parallel.
+    ///
+    /// Each kind of metric is visited concurrently with the others, and the subshards of each kind
+    /// are visited as described in [`visit_counters_par`](Registry::visit_counters_par).  This is
+    /// intended for exporters rendering a large number of metrics, where visiting every metric
+    /// serially dominates the time taken to render them.
+    pub fn visit_par<C, G, H>(&self, counters: C, gauges: G, histograms: H)
+    where
+        Self: Sync,
+        S::Counter: Send + Sync,
+        S::Gauge: Send + Sync,
+        S::Histogram: Send + Sync,
+        C: Fn(&K, &S::Counter) + Send + Sync,
+        G: Fn(&K, &S::Gauge) + Send + Sync,
+        H: Fn(&K, &S::Histogram) + Send + Sync,
+    {
+        rayon::join(
+            || self.visit_counters_par(counters),
+            || {
+                rayon::join(
+                    || self.visit_gauges_par(gauges),
+                    || self.visit_histograms_par(histograms),
+                )
+            },
+        );
+    }
 }
 
 #[cfg(test)]
@@ -529,4 +630,222 @@ mod tests {
         let entries = registry.get_counter_handles();
         assert_eq!(entries.len(), 0);
     }
+
+    #[test]
+    fn test_retain_and_remove() {
+        use super::GenerationalAtomicStorage;
+        use crate::MetricKind;
+        use metrics::{Counter, Gauge, Label};
+
+        let registry = Registry::new(GenerationalAtomicStorage::atomic());
+        let tenant_a = Key::from_parts("requests", vec![Label::new("tenant", "a")]);
+        let tenant_b = Key::from_parts("requests", vec![Label::new("tenant", "b")]);
+
+        let counter_a: Counter = registry.get_or_create_counter(&tenant_a, |c| c.clone().into());
+        let gauge_a: Gauge = registry.get_or_create_gauge(&tenant_a, |g| g.clone().into());
+        let counter_b: Counter = registry.get_or_create_counter(&tenant_b, |c| c.clone().into());
+        registry.get_or_create_histogram(&tenant_b, |_| ());
+
+        // Drop every gauge, and every metric for tenant `a`.
+        registry.retain(|key, kind| {
+            kind != MetricKind::Gauge && key.labels().all(|label| label.value() != "a")
+        });
+        assert!(counter_a.is_expired());
+        assert!(gauge_a.is_expired());
+        assert!(!counter_b.is_expired());
+
+        // Handles to removed metrics can still be used.
+        counter_a.increment(1);
+        gauge_a.set(1.0);
+
+        assert!(registry.remove(&tenant_b));
+        assert!(!registry.remove(&tenant_b));
+        assert!(counter_b.is_expired());
+        assert!(registry.get_histogram(&tenant_b).is_none());
+
+        let counter_a: Counter = registry.get_or_create_counter(&tenant_a, |c| c.clone().into());
+        registry.clear();
+        assert!(counter_a.is_expired());
+        assert!(registry.get_counter_handles().is_empty());
+    }
+
+    #[test]
+    fn test_expired_handles() {
+        use super::GenerationalAtomicStorage;
+        use metrics::Counter;
+
+        let registry = Registry::new(GenerationalAtomicStorage::atomic());
+        let key = Key::from_name("foobar");
+
+        let counter: Counter = registry.get_or_create_counter(&key, |c| c.clone().into());
+        counter.increment(1);
+        assert!(!counter.is_expired());
+
+        assert!(registry.delete_counter(&key));
+        assert!(counter.is_expired());
+
+        // Registering the metric again hands out a live handle.
+        let counter: Counter = registry.get_or_create_counter(&key, |c| c.clone().into());
+        assert!(!counter.is_expired());
+    }
+
+    #[test]
+    fn test_expire_idle() {
+        use super::GenerationalAtomicStorage;
+        use crate::{MetricKind, MetricKindMask};
+        use metrics::{Counter, Gauge};
+        use quanta::Clock;
+        use std::sync::Mutex;
+        use std::time::Duration;
+
+        let (clock, mock) = Clock::mock();
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        let hook_expired = Arc::clone(&expired);
+        let registry = Registry::new(GenerationalAtomicStorage::atomic())
+            .with_idle_timeout(clock, MetricKindMask::COUNTER, Duration::from_secs(10))
+            .on_expire(move |key: &Key, kind| {
+                hook_expired.lock().unwrap().push((key.clone(), kind));
+            });
+
+        let idle_key = Key::from_name("idle");
+        let busy_key = Key::from_name("busy");
+        let idle: Counter = registry.get_or_create_counter(&idle_key, |c| c.clone().into());
+        let busy: Counter = registry.get_or_create_counter(&busy_key, |c| c.clone().into());
+        let gauge: Gauge = registry.get_or_create_gauge(&idle_key, |g| g.clone().into());
+
+        // The first pass only starts tracking the metrics.
+        assert_eq!(registry.expire_idle(), 0);
+
+        mock.increment(Duration::from_secs(11));
+        busy.increment(1);
+        assert_eq!(registry.expire_idle(), 1);
+        assert_eq!(*expired.lock().unwrap(), vec![(idle_key.clone(), MetricKind::Counter)]);
+        assert!(idle.is_expired());
+        assert!(!busy.is_expired());
+
+        // Gauges aren't covered by the mask, so they're never removed.
+        assert!(!gauge.is_expired());
+        assert!(registry.get_gauge(&idle_key).is_some());
+
+        mock.increment(Duration::from_secs(11));
+        assert_eq!(registry.expire_idle(), 1);
+        assert!(busy.is_expired());
+        assert!(registry.get_counter_handles().is_empty());
+    }
+
+    #[test]
+    fn test_recency_idle_timeout_fn() {
+        use super::{GenerationalAtomicStorage, Recency};
+        use crate::{MetricKind, MetricKindMask};
+        use quanta::Clock;
+        use std::time::Duration;
+
+        let (clock, mock) = Clock::mock();
+        let recency =
+            Recency::with_idle_timeout_fn(clock, MetricKindMask::ALL, |key: &Key, kind| {
+                match (key.name(), kind) {
+                    ("build_info", _) => None,
+                    (_, MetricKind::Histogram) => Some(Duration::from_secs(5)),
+                    _ => Some(Duration::from_secs(60)),
+                }
+            });
+        let registry = Registry::new(GenerationalAtomicStorage::atomic());
+
+        let info_key = Key::from_name("build_info");
+        let latency_key = Key::from_name("latency");
+        let requests_key = Key::from_name("requests");
+        let info = registry.get_or_create_gauge(&info_key, |g| g.get_generation());
+        let latency = registry.get_or_create_histogram(&latency_key, |h| h.get_generation());
+        let requests = registry.get_or_create_counter(&requests_key, |c| c.get_generation());
+
+        // The first check only starts tracking the metrics.
+        assert!(recency.should_store_gauge(&info_key, info, &registry));
+        assert!(recency.should_store_histogram(&latency_key, latency, &registry));
+        assert!(recency.should_store_counter(&requests_key, requests, &registry));
+
+        mock.increment(Duration::from_secs(10));
+        assert!(recency.should_store_gauge(&info_key, info, &registry));
+        assert!(!recency.should_store_histogram(&latency_key, latency, &registry));
+        assert!(recency.should_store_counter(&requests_key, requests, &registry));
+
+        mock.increment(Duration::from_secs(3600));
+        assert!(recency.should_store_gauge(&info_key, info, &registry));
+        assert!(!recency.should_store_counter(&requests_key, requests, &registry));
+        assert!(registry.get_gauge(&info_key).is_some());
+    }
+
+    #[test]
+    fn test_visit_updated() {
+        use super::{GenerationTracker, GenerationalAtomicStorage};
+        use crate::MetricKind;
+        use metrics::{CounterFn, GaugeFn};
+
+        let registry = Registry::new(GenerationalAtomicStorage::atomic());
+        let mut tracker = GenerationTracker::new();
+        let foo = Key::from_name("foo");
+        let bar = Key::from_name("bar");
+        registry.get_or_create_counter(&foo, |c| CounterFn::increment(c, 1));
+        registry.get_or_create_counter(&bar, |c| CounterFn::increment(c, 1));
+        registry.get_or_create_gauge(&foo, |g| g.set(1.0));
+
+        let visit_counters = |tracker: &mut GenerationTracker<Key>| {
+            let mut updated = Vec::new();
+            registry.visit_updated_counters(tracker, |key, _| updated.push(key.clone()));
+            updated.sort();
+            updated
+        };
+
+        // Every metric is visited when first observed, and none are visited again until updated.
+        assert_eq!(visit_counters(&mut tracker), vec![bar.clone(), foo.clone()]);
+        assert!(visit_counters(&mut tracker).is_empty());
+
+        registry.get_or_create_counter(&foo, |c| CounterFn::increment(c, 0));
+        assert_eq!(visit_counters(&mut tracker), vec![foo]);
+
+        // Gauges are tracked separately from counters under the same key.
+        let mut gauges = 0;
+        registry.visit_updated_gauges(&mut tracker, |_, _| gauges += 1);
+        assert_eq!(gauges, 1);
+
+        tracker.forget(&bar, MetricKind::Counter);
+        assert_eq!(visit_counters(&mut tracker), vec![bar.clone()]);
+
+        tracker.retain(|_, kind| kind != MetricKind::Counter);
+        assert!(!tracker.is_empty());
+        tracker.retain(|_, _| false);
+        assert!(tracker.is_empty());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_visit_par() {
+        use metrics::{CounterFn, GaugeFn, HistogramFn};
+
+        let registry = Registry::atomic();
+        for i in 0..1000 {
+            let key = Key::from_name(format!("metric_{}", i));
+            registry.get_or_create_counter(&key, |c| CounterFn::increment(c, i));
+            registry.get_or_create_gauge(&key, |g| g.set(1.0));
+            registry.get_or_create_histogram(&key, |h| h.record(1.0));
+        }
+
+        let counters = AtomicU64::new(0);
+        let gauges = AtomicU64::new(0);
+        let histograms = AtomicU64::new(0);
+        registry.visit_par(
+            |_, c| {
+                counters.fetch_add(c.load(Ordering::Relaxed), Ordering::Relaxed);
+            },
+            |_, _| {
+                gauges.fetch_add(1, Ordering::Relaxed);
+            },
+            |_, h| {
+                histograms.fetch_add(h.data().len() as u64, Ordering::Relaxed);
+            },
+        );
+
+        assert_eq!(counters.load(Ordering::Relaxed), (0..1000).sum::<u64>());
+        assert_eq!(gauges.load(Ordering::Relaxed), 1000);
+        assert_eq!(histograms.load(Ordering::Relaxed), 1000);
+    }
 }
@@ -23,17 +23,20 @@
 //! observed, to build a complete picture that allows deciding if a given metric has gone "idle" or
 //! not, and thus whether it should actually be deleted.
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
 use std::time::Duration;
 use std::{collections::HashMap, ops::DerefMut};
 
-use metrics::{Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn};
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeCallback, GaugeFn, Histogram, HistogramFn, Label, Summary,
+    SummaryFn,
+};
 use quanta::{Clock, Instant};
 
 use crate::Hashable;
 use crate::{
     kind::MetricKindMask,
-    registry::{AtomicStorage, Registry, Storage},
+    registry::{AtomicStorage, MapBackend, MetricMap, Registry, SnapshotStorage, Storage},
     MetricKind,
 };
 
@@ -54,16 +57,42 @@ pub struct Generation(usize);
 /// again at a later point in time, it could have changed in between the two observations.  It also
 /// may not have changed, and thus `Generational` provides a way to determine if either of these
 /// events occurred.
-#[derive(Clone)]
+///
+/// The value created by [`GenerationalStorage`] is the one held by the registry, and all clones of
+/// it, such as those handed out as metric handles, are tied to it: once the registry drops its
+/// value, such as when an idle metric is removed by [`Recency`], every clone reports itself as
+/// expired via [`Generational::is_expired`].
 pub struct Generational<T> {
     inner: T,
     gen: Arc<AtomicUsize>,
+    entry: Entry,
+}
+
+/// The link between a generational value and the entry for it in the registry.
+enum Entry {
+    /// The value held by the registry.
+    Owner(Arc<()>),
+
+    /// A clone of the value held by the registry.
+    Observer(Weak<()>),
 }
 
 impl<T> Generational<T> {
     /// Creates a new `Generational<T>`.
     fn new(inner: T) -> Generational<T> {
-        Generational { inner, gen: Arc::new(AtomicUsize::new(0)) }
+        Generational {
+            inner,
+            gen: Arc::new(AtomicUsize::new(0)),
+            entry: Entry::Owner(Arc::new(())),
+        }
+    }
+
+    /// Returns `true` if the registry no longer holds the value this one was cloned from.
+    pub fn is_expired(&self) -> bool {
+        match &self.entry {
+            Entry::Owner(_) => false,
+            Entry::Observer(entry) => entry.strong_count() == 0,
+        }
     }
 
     /// Gets a reference to the inner value.
@@ -76,10 +105,15 @@ impl<T> Generational<T> {
         Generation(self.gen.load(Ordering::Acquire))
     }
 
+    /// Returns `true` if the value has been updated since it was at the given generation.
+    pub fn has_changed_since(&self, gen: Generation) -> bool {
+        self.get_generation() != gen
+    }
+
     /// Acquires a reference to the inner value, and increments the generation.
     pub fn with_increment<F, V>(&self, f: F) -> V
     where
-        F: Fn(&T) -> V,
+        F: FnOnce(&T) -> V,
     {
         let result = f(&self.inner);
         let _ = self.gen.fetch_add(1, Ordering::AcqRel);
@@ -87,6 +121,17 @@ impl<T> Generational<T> {
     }
 }
 
+impl<T: Clone> Clone for Generational<T> {
+    fn clone(&self) -> Self {
+        let entry = match &self.entry {
+            Entry::Owner(entry) => Entry::Observer(Arc::downgrade(entry)),
+            Entry::Observer(entry) => Entry::Observer(entry.clone()),
+        };
+
+        Generational { inner: self.inner.clone(), gen: Arc::clone(&self.gen), entry }
+    }
+}
+
 impl<T> CounterFn for Generational<T>
 where
     T: CounterFn,
@@ -98,6 +143,14 @@ where
     fn absolute(&self, value: u64) {
         self.with_increment(|c| c.absolute(value))
     }
+
+    fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+        self.with_increment(|c| c.increment_with_exemplar(value, exemplar))
+    }
+
+    fn is_expired(&self) -> bool {
+        Generational::is_expired(self) || self.inner.is_expired()
+    }
 }
 
 impl<T> GaugeFn for Generational<T>
@@ -115,6 +168,22 @@ where
     fn set(&self, value: f64) {
         self.with_increment(|g| g.set(value))
     }
+
+    fn set_max(&self, value: f64) {
+        self.with_increment(|g| g.set_max(value))
+    }
+
+    fn set_min(&self, value: f64) {
+        self.with_increment(|g| g.set_min(value))
+    }
+
+    fn set_callback(&self, callback: GaugeCallback) {
+        self.with_increment(|g| g.set_callback(callback))
+    }
+
+    fn is_expired(&self) -> bool {
+        Generational::is_expired(self) || self.inner.is_expired()
+    }
 }
 
 impl<T> HistogramFn for Generational<T>
@@ -124,6 +193,31 @@ where
     fn record(&self, value: f64) {
         self.with_increment(|h| h.record(value))
     }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        self.with_increment(|h| h.record_with_exemplar(value, exemplar))
+    }
+
+    fn record_duration(&self, duration: Duration) {
+        self.with_increment(|h| h.record_duration(duration))
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        self.with_increment(|h| h.record_many(values))
+    }
+
+    fn is_expired(&self) -> bool {
+        Generational::is_expired(self) || self.inner.is_expired()
+    }
+}
+
+impl<T> SummaryFn for Generational<T>
+where
+    T: SummaryFn,
+{
+    fn record(&self, value: f64) {
+        self.with_increment(|s| s.record(value))
+    }
 }
 
 impl<T> From<Generational<T>> for Counter
@@ -153,6 +247,15 @@ where
     }
 }
 
+impl<T> From<Generational<T>> for Summary
+where
+    T: SummaryFn + Send + Sync + 'static,
+{
+    fn from(inner: Generational<T>) -> Self {
+        Summary::from_arc(Arc::new(inner))
+    }
+}
+
 /// Generational metric storage.
 ///
 /// Tracks the "generation" of a metric, which is used to detect updates to metrics where the value
@@ -188,6 +291,24 @@ impl<K, S: Storage<K>> Storage<K> for GenerationalStorage<S> {
     }
 }
 
+impl<K, S: SnapshotStorage<K>> SnapshotStorage<K> for GenerationalStorage<S> {
+    fn counter_value(&self, counter: &Self::Counter) -> u64 {
+        self.inner.counter_value(counter.get_inner())
+    }
+
+    fn gauge_value(&self, gauge: &Self::Gauge) -> f64 {
+        self.inner.gauge_value(gauge.get_inner())
+    }
+
+    fn histogram_values(&self, histogram: &Self::Histogram) -> Vec<f64> {
+        self.inner.histogram_values(histogram.get_inner())
+    }
+
+    fn histogram_len(&self, histogram: &Self::Histogram) -> usize {
+        self.inner.histogram_len(histogram.get_inner())
+    }
+}
+
 /// Generational atomic metric storage.
 ///
 /// `GenerationalAtomicStorage` is based on [`AtomicStorage`], but additionally tracks the
@@ -202,6 +323,112 @@ impl GenerationalAtomicStorage {
     }
 }
 
+/// Tracks the generation at which metrics were last observed.
+///
+/// Exporters which only need to know whether a metric has been updated since the last time they
+/// observed it, such as to skip unchanged metrics or to compute the delta of a counter between two
+/// observations, can keep a `GenerationTracker` alongside a registry using [`GenerationalStorage`],
+/// and pass it to [`Registry::visit_updated_counters`] and its siblings.  Unlike [`Recency`], this
+/// doesn't involve measuring time, or removing metrics from the registry.
+///
+/// Each tracker holds its own observations, so several consumers of the same registry can each
+/// track which metrics have been updated since they last observed them.
+#[derive(Debug)]
+pub struct GenerationTracker<K> {
+    observed: HashMap<K, [Option<Generation>; 3]>,
+}
+
+fn kind_index(kind: MetricKind) -> usize {
+    match kind {
+        MetricKind::Counter => 0,
+        MetricKind::Gauge => 1,
+        MetricKind::Histogram => 2,
+    }
+}
+
+impl<K> GenerationTracker<K>
+where
+    K: Clone + Eq + Hashable,
+{
+    /// Creates a new, empty [`GenerationTracker`].
+    pub fn new() -> Self {
+        Self { observed: HashMap::new() }
+    }
+
+    /// Records an observation of the given metric at the given generation.
+    ///
+    /// Returns `true` if the metric is being observed for the first time, or if its generation has
+    /// changed since it was last observed, and `false` otherwise.
+    pub fn observe(&mut self, key: &K, kind: MetricKind, gen: Generation) -> bool {
+        let last_gen = match self.observed.get_mut(key) {
+            Some(gens) => &mut gens[kind_index(kind)],
+            None => {
+                let mut gens = [None; 3];
+                gens[kind_index(kind)] = Some(gen);
+                self.observed.insert(key.clone(), gens);
+                return true;
+            }
+        };
+
+        last_gen.replace(gen) != Some(gen)
+    }
+
+    /// Stops tracking the given metric, such that its next observation is treated as its first.
+    pub fn forget(&mut self, key: &K, kind: MetricKind) {
+        if let Some(gens) = self.observed.get_mut(key) {
+            gens[kind_index(kind)] = None;
+            if gens.iter().all(Option::is_none) {
+                let _ = self.observed.remove(key);
+            }
+        }
+    }
+
+    /// Retains only the observations specified by the predicate.
+    ///
+    /// Observations of metrics which have been removed from the registry are kept until they're
+    /// forgotten, so this can be used to prune them, such as from an expiry hook.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, MetricKind) -> bool,
+    {
+        self.observed.retain(|key, gens| {
+            for kind in [MetricKind::Counter, MetricKind::Gauge, MetricKind::Histogram] {
+                let gen = &mut gens[kind_index(kind)];
+                if gen.is_some() && !f(key, kind) {
+                    *gen = None;
+                }
+            }
+
+            gens.iter().any(Option::is_some)
+        });
+    }
+
+    /// Returns `true` if no metrics are being tracked.
+    pub fn is_empty(&self) -> bool {
+        self.observed.is_empty()
+    }
+}
+
+impl<K> Default for GenerationTracker<K>
+where
+    K: Clone + Eq + Hashable,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type RecencyMap<K> = HashMap<K, (Generation, Instant)>;
+type IdleTimeoutFn<K> = Box<dyn Fn(&K, MetricKind) -> Option<Duration> + Send + Sync>;
+
+/// The idle timeout of the metrics tracked by a [`Recency`].
+enum IdleTimeout<K> {
+    /// The same idle timeout applies to every metric.
+    Fixed(Option<Duration>),
+    /// The idle timeout of each metric is given by a function of its key and kind.
+    PerMetric(IdleTimeoutFn<K>),
+}
+
 /// Tracks recency of metric updates by their registry generation and time.
 ///
 /// In many cases, a user may have a long-running process where metrics are stored over time using
@@ -217,8 +444,8 @@ impl GenerationalAtomicStorage {
 /// tracking recency does not matter, despite their otherwise tight coupling.
 pub struct Recency<K> {
     mask: MetricKindMask,
-    inner: Mutex<(Clock, HashMap<K, (Generation, Instant)>)>,
-    idle_timeout: Option<Duration>,
+    inner: Mutex<(Clock, RecencyMap<K>)>,
+    idle_timeout: IdleTimeout<K>,
 }
 
 impl<K> Recency<K>
@@ -238,7 +465,32 @@ where
     /// Refer to the documentation for [`MetricKindMask`](crate::MetricKindMask) for more
     /// information on defining a metric kind mask.
     pub fn new(clock: Clock, mask: MetricKindMask, idle_timeout: Option<Duration>) -> Self {
-        Recency { mask, inner: Mutex::new((clock, HashMap::new())), idle_timeout }
+        Recency {
+            mask,
+            inner: Mutex::new((clock, HashMap::new())),
+            idle_timeout: IdleTimeout::Fixed(idle_timeout),
+        }
+    }
+
+    /// Creates a new [`Recency`] whose idle timeout is chosen for each metric.
+    ///
+    /// `idle_timeout` is called with the key and kind of a metric whenever it's checked, and
+    /// returns the idle timeout of the metric, or `None` if it should never be deleted.  This allows
+    /// different metrics of the same kind to be deleted after different amounts of time, such as
+    /// expiring per-request histograms quickly while keeping build information gauges forever.  As
+    /// it's called on every check, it should be cheap.
+    ///
+    /// As with [`new`](Recency::new), only metrics whose kind is contained in `mask` are covered by
+    /// the recency logic.
+    pub fn with_idle_timeout_fn<F>(clock: Clock, mask: MetricKindMask, idle_timeout: F) -> Self
+    where
+        F: Fn(&K, MetricKind) -> Option<Duration> + Send + Sync + 'static,
+    {
+        Recency {
+            mask,
+            inner: Mutex::new((clock, HashMap::new())),
+            idle_timeout: IdleTimeout::PerMetric(Box::new(idle_timeout)),
+        }
     }
 
     /// Checks if the given counter should be stored, based on its known recency.
@@ -247,14 +499,15 @@ where
     /// method will return `true` and will update the last update time internally.  If the given key
     /// has not been updated recently enough, the key will be removed from the given registry if the
     /// given generation also matches.
-    pub fn should_store_counter<S>(
+    pub fn should_store_counter<S, M>(
         &self,
         key: &K,
         gen: Generation,
-        registry: &Registry<K, S>,
+        registry: &Registry<K, S, M>,
     ) -> bool
     where
         S: Storage<K>,
+        M: MapBackend<K>,
     {
         self.should_store(key, gen, registry, MetricKind::Counter, |registry, key| {
             registry.delete_counter(key)
@@ -267,9 +520,15 @@ where
     /// method will return `true` and will update the last update time internally.  If the given key
     /// has not been updated recently enough, the key will be removed from the given registry if the
     /// given generation also matches.
-    pub fn should_store_gauge<S>(&self, key: &K, gen: Generation, registry: &Registry<K, S>) -> bool
+    pub fn should_store_gauge<S, M>(
+        &self,
+        key: &K,
+        gen: Generation,
+        registry: &Registry<K, S, M>,
+    ) -> bool
     where
         S: Storage<K>,
+        M: MapBackend<K>,
     {
         self.should_store(key, gen, registry, MetricKind::Gauge, |registry, key| {
             registry.delete_gauge(key)
@@ -282,64 +541,235 @@ where
     /// method will return `true` and will update the last update time internally.  If the given key
     /// has not been updated recently enough, the key will be removed from the given registry if the
     /// given generation also matches.
-    pub fn should_store_histogram<S>(
+    pub fn should_store_histogram<S, M>(
         &self,
         key: &K,
         gen: Generation,
-        registry: &Registry<K, S>,
+        registry: &Registry<K, S, M>,
     ) -> bool
     where
         S: Storage<K>,
+        M: MapBackend<K>,
     {
         self.should_store(key, gen, registry, MetricKind::Histogram, |registry, key| {
             registry.delete_histogram(key)
         })
     }
 
-    fn should_store<F, S>(
+    fn should_store<F, S, M>(
         &self,
         key: &K,
         gen: Generation,
-        registry: &Registry<K, S>,
+        registry: &Registry<K, S, M>,
         kind: MetricKind,
         delete_op: F,
     ) -> bool
     where
-        F: Fn(&Registry<K, S>, &K) -> bool,
+        F: Fn(&Registry<K, S, M>, &K) -> bool,
         S: Storage<K>,
+        M: MapBackend<K>,
     {
-        if let Some(idle_timeout) = self.idle_timeout {
-            if self.mask.matches(kind) {
-                let mut guard = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
-                let (clock, entries) = guard.deref_mut();
-
-                let now = clock.now();
-                let deleted = if let Some((last_gen, last_update)) = entries.get_mut(key) {
-                    // If the value is the same as the latest value we have internally, and
-                    // we're over the idle timeout period, then remove it and continue.
-                    if *last_gen == gen {
-                        // If the delete returns false, that means that our generation counter is
-                        // out-of-date, and that the metric has been updated since, so we don't
-                        // actually want to delete it yet.
-                        (now - *last_update) > idle_timeout && delete_op(registry, key)
-                    } else {
-                        // Value has changed, so mark it such.
-                        *last_update = now;
-                        *last_gen = gen;
-                        false
-                    }
-                } else {
-                    entries.insert(key.clone(), (gen, now));
-                    false
-                };
-
-                if deleted {
-                    entries.remove(key);
-                    return false;
-                }
-            }
+        // If the delete returns false, that means that the metric has already been removed from
+        // the registry by someone else, so we keep tracking it until it's next observed.
+        if self.is_idle(key, gen, kind) && delete_op(registry, key) {
+            self.forget(key);
+            return false;
         }
 
         true
     }
+
+    /// Checks if the given metric has gone idle, based on its known recency.
+    ///
+    /// Metrics which have been updated since they were last checked have their last update time
+    /// refreshed, and are never considered idle.
+    fn is_idle(&self, key: &K, gen: Generation, kind: MetricKind) -> bool {
+        if !self.mask.matches(kind) {
+            return false;
+        }
+        let idle_timeout = match &self.idle_timeout {
+            IdleTimeout::Fixed(idle_timeout) => *idle_timeout,
+            IdleTimeout::PerMetric(idle_timeout) => idle_timeout(key, kind),
+        };
+        let idle_timeout = match idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return false,
+        };
+
+        let mut guard = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let (clock, entries) = guard.deref_mut();
+
+        let now = clock.now();
+        if let Some((last_gen, last_update)) = entries.get_mut(key) {
+            // If the value is the same as the latest value we have internally, and we're over the
+            // idle timeout period, then the metric is idle.
+            if *last_gen == gen {
+                (now - *last_update) > idle_timeout
+            } else {
+                // Value has changed, so mark it such.
+                *last_update = now;
+                *last_gen = gen;
+                false
+            }
+        } else {
+            entries.insert(key.clone(), (gen, now));
+            false
+        }
+    }
+}
+
+impl<K> Recency<K> {
+    /// Stops tracking the recency of the given metric.
+    pub(super) fn forget(&self, key: &K)
+    where
+        K: Eq + Hashable,
+    {
+        let mut guard = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = guard.1.remove(key);
+    }
+
+    /// Stops tracking the recency of all metrics.
+    pub(super) fn forget_all(&self) {
+        let mut guard = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        guard.1.clear();
+    }
+}
+
+/// A hook invoked with the key and kind of every metric removed from a registry for being idle.
+pub(super) type ExpiryHook<K> = Box<dyn Fn(&K, MetricKind) + Send + Sync>;
+
+impl<K, S, M> Registry<K, GenerationalStorage<S>, M>
+where
+    S: Storage<K>,
+    M: MapBackend<K>,
+    K: Clone + Eq + Hashable,
+{
+    /// Configures the registry to remove metrics which haven't been updated for longer than
+    /// `idle_timeout`.
+    ///
+    /// Only metrics whose kind is contained in `mask` are subject to expiration, and idle metrics
+    /// are only removed when [`expire_idle`](Registry::expire_idle) is called.  The provided
+    /// `clock` is used for tracking time.
+    pub fn with_idle_timeout(
+        mut self,
+        clock: Clock,
+        mask: MetricKindMask,
+        idle_timeout: Duration,
+    ) -> Self {
+        self.recency = Some(Recency::new(clock, mask, Some(idle_timeout)));
+        self
+    }
+
+    /// Sets a hook to be called with the key and kind of every metric removed for being idle.
+    ///
+    /// The hook is called by [`expire_idle`](Registry::expire_idle) after the metrics have been
+    /// removed, and without holding any locks on the registry, so it's free to access the registry
+    /// itself, for example to clean up state that an exporter keeps alongside each metric.
+    pub fn on_expire<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&K, MetricKind) + Send + Sync + 'static,
+    {
+        self.expiry_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Removes every metric which has gone idle, returning the number of metrics removed.
+    ///
+    /// A metric is idle if it hasn't been updated for longer than the idle timeout set via
+    /// [`with_idle_timeout`](Registry::with_idle_timeout), as measured between calls to this
+    /// method: a metric is first tracked the first time it's checked, and its last update time is
+    /// refreshed whenever it's found to have changed since the previous check.  This method should
+    /// therefore be called periodically, such as whenever the registry is exported.  If no idle
+    /// timeout has been set, nothing is removed.
+    ///
+    /// Handles to removed metrics remain usable, but report themselves as expired, and updates made
+    /// through them are no longer reflected in the registry.  Registering the same metric again
+    /// creates a new, empty metric.
+    pub fn expire_idle(&self) -> usize {
+        let recency = match &self.recency {
+            Some(recency) => recency,
+            None => return 0,
+        };
+
+        let mut expired = Vec::new();
+        expire_map(&self.counters, MetricKind::Counter, recency, &mut expired);
+        expire_map(&self.gauges, MetricKind::Gauge, recency, &mut expired);
+        expire_map(&self.histograms, MetricKind::Histogram, recency, &mut expired);
+
+        if let Some(hook) = &self.expiry_hook {
+            for (key, kind) in &expired {
+                hook(key, *kind);
+            }
+        }
+
+        expired.len()
+    }
+
+    /// Visits every counter which has been updated since it was last observed by `tracker`.
+    ///
+    /// Counters observed for the first time are also visited.  This proceeds through the
+    /// "subshards" in the same way as [`visit_counters`](Registry::visit_counters).
+    pub fn visit_updated_counters<F>(&self, tracker: &mut GenerationTracker<K>, mut collect: F)
+    where
+        F: FnMut(&K, &Generational<S::Counter>),
+    {
+        self.visit_counters(|key, counter| {
+            if tracker.observe(key, MetricKind::Counter, counter.get_generation()) {
+                collect(key, counter);
+            }
+        });
+    }
+
+    /// Visits every gauge which has been updated since it was last observed by `tracker`.
+    ///
+    /// Gauges observed for the first time are also visited.  This proceeds through the "subshards"
+    /// in the same way as [`visit_gauges`](Registry::visit_gauges).
+    pub fn visit_updated_gauges<F>(&self, tracker: &mut GenerationTracker<K>, mut collect: F)
+    where
+        F: FnMut(&K, &Generational<S::Gauge>),
+    {
+        self.visit_gauges(|key, gauge| {
+            if tracker.observe(key, MetricKind::Gauge, gauge.get_generation()) {
+                collect(key, gauge);
+            }
+        });
+    }
+
+    /// Visits every histogram which has been updated since it was last observed by `tracker`.
+    ///
+    /// Histograms observed for the first time are also visited.  This proceeds through the
+    /// "subshards" in the same way as [`visit_histograms`](Registry::visit_histograms).
+    pub fn visit_updated_histograms<F>(&self, tracker: &mut GenerationTracker<K>, mut collect: F)
+    where
+        F: FnMut(&K, &Generational<S::Histogram>),
+    {
+        self.visit_histograms(|key, histogram| {
+            if tracker.observe(key, MetricKind::Histogram, histogram.get_generation()) {
+                collect(key, histogram);
+            }
+        });
+    }
+}
+
+fn expire_map<K, T>(
+    map: &impl MetricMap<K, Generational<T>>,
+    kind: MetricKind,
+    recency: &Recency<K>,
+    expired: &mut Vec<(K, MetricKind)>,
+) where
+    K: Clone + Eq + Hashable,
+{
+    if !recency.mask.matches(kind) {
+        return;
+    }
+
+    map.retain(|key, value| {
+        if recency.is_idle(key, value.get_generation(), kind) {
+            recency.forget(key);
+            expired.push((key.clone(), kind));
+            return false;
+        }
+
+        true
+    });
 }
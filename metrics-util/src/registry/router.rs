@@ -0,0 +1,324 @@
+use std::time::Duration;
+
+use metrics::{CounterFn, GaugeCallback, GaugeFn, HistogramFn, Key, Label};
+
+use crate::layers::glob::Glob;
+use crate::registry::{SnapshotStorage, Storage};
+
+/// A metric stored by either the default or the routed storage of a [`StorageRouter`].
+#[derive(Clone, Debug)]
+pub enum Routed<D, R> {
+    /// A metric stored by the default storage.
+    Default(D),
+
+    /// A metric stored by the routed storage.
+    Routed(R),
+}
+
+impl<D, R> CounterFn for Routed<D, R>
+where
+    D: CounterFn,
+    R: CounterFn,
+{
+    fn increment(&self, value: u64) {
+        match self {
+            Routed::Default(inner) => inner.increment(value),
+            Routed::Routed(inner) => inner.increment(value),
+        }
+    }
+
+    fn absolute(&self, value: u64) {
+        match self {
+            Routed::Default(inner) => inner.absolute(value),
+            Routed::Routed(inner) => inner.absolute(value),
+        }
+    }
+
+    fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+        match self {
+            Routed::Default(inner) => inner.increment_with_exemplar(value, exemplar),
+            Routed::Routed(inner) => inner.increment_with_exemplar(value, exemplar),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        match self {
+            Routed::Default(inner) => inner.is_expired(),
+            Routed::Routed(inner) => inner.is_expired(),
+        }
+    }
+}
+
+impl<D, R> GaugeFn for Routed<D, R>
+where
+    D: GaugeFn,
+    R: GaugeFn,
+{
+    fn increment(&self, value: f64) {
+        match self {
+            Routed::Default(inner) => inner.increment(value),
+            Routed::Routed(inner) => inner.increment(value),
+        }
+    }
+
+    fn decrement(&self, value: f64) {
+        match self {
+            Routed::Default(inner) => inner.decrement(value),
+            Routed::Routed(inner) => inner.decrement(value),
+        }
+    }
+
+    fn set(&self, value: f64) {
+        match self {
+            Routed::Default(inner) => inner.set(value),
+            Routed::Routed(inner) => inner.set(value),
+        }
+    }
+
+    fn set_max(&self, value: f64) {
+        match self {
+            Routed::Default(inner) => inner.set_max(value),
+            Routed::Routed(inner) => inner.set_max(value),
+        }
+    }
+
+    fn set_min(&self, value: f64) {
+        match self {
+            Routed::Default(inner) => inner.set_min(value),
+            Routed::Routed(inner) => inner.set_min(value),
+        }
+    }
+
+    fn set_callback(&self, callback: GaugeCallback) {
+        match self {
+            Routed::Default(inner) => inner.set_callback(callback),
+            Routed::Routed(inner) => inner.set_callback(callback),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        match self {
+            Routed::Default(inner) => inner.is_expired(),
+            Routed::Routed(inner) => inner.is_expired(),
+        }
+    }
+}
+
+impl<D, R> HistogramFn for Routed<D, R>
+where
+    D: HistogramFn,
+    R: HistogramFn,
+{
+    fn record(&self, value: f64) {
+        match self {
+            Routed::Default(inner) => inner.record(value),
+            Routed::Routed(inner) => inner.record(value),
+        }
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        match self {
+            Routed::Default(inner) => inner.record_with_exemplar(value, exemplar),
+            Routed::Routed(inner) => inner.record_with_exemplar(value, exemplar),
+        }
+    }
+
+    fn record_duration(&self, duration: Duration) {
+        match self {
+            Routed::Default(inner) => inner.record_duration(duration),
+            Routed::Routed(inner) => inner.record_duration(duration),
+        }
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        match self {
+            Routed::Default(inner) => inner.record_many(values),
+            Routed::Routed(inner) => inner.record_many(values),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        match self {
+            Routed::Default(inner) => inner.is_expired(),
+            Routed::Routed(inner) => inner.is_expired(),
+        }
+    }
+}
+
+/// Storage which picks between two storages based on the name of each metric.
+///
+/// Metrics whose name matches any of the configured patterns are stored by the routed storage,
+/// while all other metrics are stored by the default storage.  This allows using more expensive
+/// storage only for the metrics which need it, such as storing latency histograms as
+/// [`HdrHistogram`](super::HdrHistogram)s while leaving all other histograms as plain buckets.
+///
+/// Patterns are shell-style globs, where `*` matches any sequence of characters, and `?` matches
+/// exactly one character.  More than two storages can be used by nesting routers, as the routed
+/// storage can itself be a `StorageRouter`.
+///
+/// ```
+/// # use metrics::Key;
+/// # use metrics_util::registry::{AtomicStorage, Registry, StorageRouter};
+/// # let latency_storage = AtomicStorage;
+/// let storage = StorageRouter::new(AtomicStorage, latency_storage).route("*.latency_seconds");
+/// let registry = Registry::<Key, _>::new(storage);
+/// ```
+pub struct StorageRouter<D, R> {
+    default: D,
+    routed: R,
+    patterns: Vec<Glob>,
+}
+
+impl<D, R> StorageRouter<D, R> {
+    /// Creates a new `StorageRouter` which stores every metric in `default`, until routes are
+    /// added.
+    pub fn new(default: D, routed: R) -> Self {
+        Self { default, routed, patterns: Vec::new() }
+    }
+
+    /// Routes metrics whose name matches the given pattern to the routed storage.
+    pub fn route<P: AsRef<str>>(mut self, pattern: P) -> Self {
+        self.patterns.push(Glob::new(pattern));
+        self
+    }
+
+    fn is_routed(&self, key: &Key) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(key.name()))
+    }
+}
+
+impl<D, R> Storage<Key> for StorageRouter<D, R>
+where
+    D: Storage<Key>,
+    R: Storage<Key>,
+{
+    type Counter = Routed<D::Counter, R::Counter>;
+    type Gauge = Routed<D::Gauge, R::Gauge>;
+    type Histogram = Routed<D::Histogram, R::Histogram>;
+
+    fn counter(&self, key: &Key) -> Self::Counter {
+        if self.is_routed(key) {
+            Routed::Routed(self.routed.counter(key))
+        } else {
+            Routed::Default(self.default.counter(key))
+        }
+    }
+
+    fn gauge(&self, key: &Key) -> Self::Gauge {
+        if self.is_routed(key) {
+            Routed::Routed(self.routed.gauge(key))
+        } else {
+            Routed::Default(self.default.gauge(key))
+        }
+    }
+
+    fn histogram(&self, key: &Key) -> Self::Histogram {
+        if self.is_routed(key) {
+            Routed::Routed(self.routed.histogram(key))
+        } else {
+            Routed::Default(self.default.histogram(key))
+        }
+    }
+}
+
+impl<D, R> SnapshotStorage<Key> for StorageRouter<D, R>
+where
+    D: SnapshotStorage<Key>,
+    R: SnapshotStorage<Key>,
+{
+    fn counter_value(&self, counter: &Self::Counter) -> u64 {
+        match counter {
+            Routed::Default(inner) => self.default.counter_value(inner),
+            Routed::Routed(inner) => self.routed.counter_value(inner),
+        }
+    }
+
+    fn gauge_value(&self, gauge: &Self::Gauge) -> f64 {
+        match gauge {
+            Routed::Default(inner) => self.default.gauge_value(inner),
+            Routed::Routed(inner) => self.routed.gauge_value(inner),
+        }
+    }
+
+    fn histogram_values(&self, histogram: &Self::Histogram) -> Vec<f64> {
+        match histogram {
+            Routed::Default(inner) => self.default.histogram_values(inner),
+            Routed::Routed(inner) => self.routed.histogram_values(inner),
+        }
+    }
+
+    fn histogram_len(&self, histogram: &Self::Histogram) -> usize {
+        match histogram {
+            Routed::Default(inner) => self.default.histogram_len(inner),
+            Routed::Routed(inner) => self.routed.histogram_len(inner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::Ordering, Arc};
+
+    use metrics::{atomics::AtomicU64, HistogramFn, Key};
+
+    use super::{Routed, StorageRouter};
+    use crate::registry::{AtomicStorage, Registry, Storage};
+
+    /// Storage whose histograms count how many values have been recorded into them.
+    struct CountingStorage;
+
+    impl Storage<Key> for CountingStorage {
+        type Counter = Arc<AtomicU64>;
+        type Gauge = Arc<AtomicU64>;
+        type Histogram = Arc<CountingHistogram>;
+
+        fn counter(&self, _: &Key) -> Self::Counter {
+            Arc::new(AtomicU64::new(0))
+        }
+
+        fn gauge(&self, _: &Key) -> Self::Gauge {
+            Arc::new(AtomicU64::new(0))
+        }
+
+        fn histogram(&self, _: &Key) -> Self::Histogram {
+            Arc::new(CountingHistogram(AtomicU64::new(0)))
+        }
+    }
+
+    struct CountingHistogram(AtomicU64);
+
+    impl HistogramFn for CountingHistogram {
+        fn record(&self, _: f64) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_storage_router() {
+        let storage = StorageRouter::new(AtomicStorage, CountingStorage)
+            .route("*.latency_seconds")
+            .route("db.query_?");
+        let registry = Registry::new(storage);
+
+        for (name, routed) in [
+            ("http.latency_seconds", true),
+            ("db.query_1", true),
+            ("db.query_10", false),
+            ("http.requests", false),
+        ] {
+            let histogram = registry.get_or_create_histogram(&Key::from_name(name), |h| h.clone());
+            histogram.record(1.0);
+
+            match histogram {
+                Routed::Default(bucket) => {
+                    assert!(!routed, "{} should be routed", name);
+                    assert_eq!(bucket.data(), vec![1.0]);
+                }
+                Routed::Routed(counting) => {
+                    assert!(routed, "{} should not be routed", name);
+                    assert_eq!(counting.0.load(Ordering::Relaxed), 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crossbeam_utils::CachePadded;
+use metrics::{atomics::AtomicU64, CounterFn, HistogramFn};
+
+use crate::registry::{SnapshotStorage, Storage};
+use crate::AtomicBucket;
+
+thread_local! {
+    static SHARD_INDEX: Cell<Option<usize>> = Cell::new(None);
+}
+
+/// Gets the shard index of the current thread.
+///
+/// Threads are assigned indexes in a round-robin fashion the first time they touch a sharded metric,
+/// which spreads them evenly across shards, regardless of how many shards a given metric has.
+fn shard_index(shards: usize) -> usize {
+    static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+    let index = SHARD_INDEX.with(|index| match index.get() {
+        Some(index) => index,
+        None => {
+            let next = NEXT_INDEX.fetch_add(1, Ordering::Relaxed);
+            index.set(Some(next));
+            next
+        }
+    });
+
+    index % shards
+}
+
+/// A counter whose value is striped across multiple shards.
+///
+/// Each thread increments the shard it's assigned to, and the value of the counter is the sum of
+/// all of its shards.  Shards are padded to a cache line, so threads incrementing the same counter
+/// don't contend with each other as long as they're assigned to different shards.
+#[derive(Debug)]
+pub struct ShardedCounter {
+    shards: Box<[CachePadded<AtomicU64>]>,
+}
+
+impl ShardedCounter {
+    fn new(shards: usize) -> Self {
+        Self { shards: (0..shards).map(|_| CachePadded::new(AtomicU64::new(0))).collect() }
+    }
+
+    fn local(&self) -> &AtomicU64 {
+        &self.shards[shard_index(self.shards.len())]
+    }
+
+    /// Gets the current value of the counter, by summing all of its shards.
+    ///
+    /// As shards are read one after the other, increments made while the value is being read may
+    /// only be partially reflected in it.
+    pub fn value(&self) -> u64 {
+        self.shards.iter().fold(0, |sum, shard| sum.wrapping_add(shard.load(Ordering::Acquire)))
+    }
+}
+
+impl CounterFn for ShardedCounter {
+    fn increment(&self, value: u64) {
+        self.local().fetch_add(value, Ordering::Release);
+    }
+
+    fn absolute(&self, value: u64) {
+        // The difference is added to the local shard, so concurrent calls may push the value of the
+        // counter past the given value.  Absolute values are rarely set from multiple threads at
+        // once, so this is an acceptable trade-off for keeping increments uncontended.
+        let current = self.value();
+        if value > current {
+            self.local().fetch_add(value - current, Ordering::Release);
+        }
+    }
+}
+
+/// A histogram whose values are striped across multiple shards.
+///
+/// Each thread records values into the bucket of the shard it's assigned to, and reading the
+/// histogram merges the values of all of its shards.
+#[derive(Debug)]
+pub struct ShardedHistogram {
+    shards: Box<[CachePadded<AtomicBucket<f64>>]>,
+}
+
+impl ShardedHistogram {
+    fn new(shards: usize) -> Self {
+        Self { shards: (0..shards).map(|_| CachePadded::new(AtomicBucket::new())).collect() }
+    }
+
+    fn local(&self) -> &AtomicBucket<f64> {
+        &self.shards[shard_index(self.shards.len())]
+    }
+
+    /// Returns `true` if no values have been recorded into any shard.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.is_empty())
+    }
+
+    /// Gets the number of values recorded into the histogram, across all shards.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    /// Gets all of the values recorded into the histogram, across all shards.
+    ///
+    /// Values are grouped by shard, and are not in the order they were recorded in.
+    pub fn data(&self) -> Vec<f64> {
+        let mut values = Vec::new();
+        self.data_with(|block| values.extend_from_slice(block));
+        values
+    }
+
+    /// Iterates all of the values recorded into the histogram, invoking `f` for each block of
+    /// values, across all shards.
+    pub fn data_with<F>(&self, mut f: F)
+    where
+        F: FnMut(&[f64]),
+    {
+        for shard in self.shards.iter() {
+            shard.data_with(&mut f);
+        }
+    }
+
+    /// Clears the histogram, invoking `f` for every block of values that will be cleared, across
+    /// all shards.
+    ///
+    /// See [`AtomicBucket::clear_with`] for more details.
+    pub fn clear_with<F>(&self, mut f: F)
+    where
+        F: FnMut(&[f64]),
+    {
+        for shard in self.shards.iter() {
+            shard.clear_with(&mut f);
+        }
+    }
+}
+
+impl HistogramFn for ShardedHistogram {
+    fn record(&self, value: f64) {
+        self.local().push(value);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        let local = self.local();
+        for value in values {
+            local.push(*value);
+        }
+    }
+}
+
+/// Sharded metric storage, for write-heavy workloads.
+///
+/// With [`AtomicStorage`](super::AtomicStorage), every thread updating a given counter or
+/// histogram writes to the same memory, which causes heavy cache-line contention when many threads
+/// update the same metric at once.  `ShardedStorage` instead stripes the state of counters and
+/// histograms across a number of shards, each on its own cache line, with each thread writing to
+/// the shard it's assigned to.  Reads merge the state of all shards, which makes them more
+/// expensive, so this storage is best suited to metrics which are updated far more often than they
+/// are read.
+///
+/// Gauges are stored in the same way as [`AtomicStorage`](super::AtomicStorage), as setting a
+/// gauge can't be striped across shards.
+///
+/// ```
+/// # use metrics::Key;
+/// # use metrics_util::registry::{Registry, ShardedStorage};
+/// let registry = Registry::<Key, _>::new(ShardedStorage::new());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ShardedStorage {
+    shards: usize,
+}
+
+impl ShardedStorage {
+    /// Creates a new `ShardedStorage` with one shard per CPU.
+    pub fn new() -> Self {
+        Self::with_shards(num_cpus::get())
+    }
+
+    /// Creates a new `ShardedStorage` with the given number of shards.
+    ///
+    /// The number of shards is clamped to a minimum of `1`.
+    pub fn with_shards(shards: usize) -> Self {
+        Self { shards: shards.max(1) }
+    }
+
+    /// Gets the number of shards each counter and histogram is striped across.
+    pub fn shards(&self) -> usize {
+        self.shards
+    }
+}
+
+impl Default for ShardedStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> Storage<K> for ShardedStorage {
+    type Counter = Arc<ShardedCounter>;
+    type Gauge = Arc<AtomicU64>;
+    type Histogram = Arc<ShardedHistogram>;
+
+    fn counter(&self, _: &K) -> Self::Counter {
+        Arc::new(ShardedCounter::new(self.shards))
+    }
+
+    fn gauge(&self, _: &K) -> Self::Gauge {
+        Arc::new(AtomicU64::new(0))
+    }
+
+    fn histogram(&self, _: &K) -> Self::Histogram {
+        Arc::new(ShardedHistogram::new(self.shards))
+    }
+}
+
+impl<K> SnapshotStorage<K> for ShardedStorage {
+    fn counter_value(&self, counter: &Self::Counter) -> u64 {
+        counter.value()
+    }
+
+    fn gauge_value(&self, gauge: &Self::Gauge) -> f64 {
+        f64::from_bits(gauge.load(Ordering::Acquire))
+    }
+
+    fn histogram_values(&self, histogram: &Self::Histogram) -> Vec<f64> {
+        histogram.data()
+    }
+
+    fn histogram_len(&self, histogram: &Self::Histogram) -> usize {
+        histogram.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{CounterFn, HistogramFn, Key};
+
+    use super::ShardedStorage;
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_sharded_storage() {
+        let registry = Registry::new(ShardedStorage::with_shards(4));
+        let key = Key::from_name("requests");
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let counter = registry.get_or_create_counter(&key, |c| c.clone());
+                    let histogram = registry.get_or_create_histogram(&key, |h| h.clone());
+                    for i in 0..1000 {
+                        counter.increment(1);
+                        histogram.record(f64::from(i));
+                    }
+                });
+            }
+        });
+
+        let counter = registry.get_or_create_counter(&key, |c| c.clone());
+        assert_eq!(counter.value(), 8000);
+        counter.absolute(10_000);
+        assert_eq!(counter.value(), 10_000);
+        counter.absolute(5);
+        assert_eq!(counter.value(), 10_000);
+
+        let histogram = registry.get_or_create_histogram(&key, |h| h.clone());
+        assert_eq!(histogram.data().len(), 8000);
+
+        let mut cleared = 0;
+        histogram.clear_with(|block| cleared += block.len());
+        assert_eq!(cleared, 8000);
+        assert!(histogram.is_empty());
+    }
+
+    #[test]
+    fn test_minimum_shards() {
+        assert_eq!(ShardedStorage::with_shards(0).shards(), 1);
+        assert!(ShardedStorage::new().shards() >= 1);
+    }
+}
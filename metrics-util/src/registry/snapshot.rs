@@ -0,0 +1,121 @@
+use crate::registry::Storage;
+
+/// Storage whose metrics can be read back out, for taking a [`Snapshot`] of a
+/// [`Registry`](super::Registry).
+pub trait SnapshotStorage<K>: Storage<K> {
+    /// Gets the current value of a counter.
+    fn counter_value(&self, counter: &Self::Counter) -> u64;
+
+    /// Gets the current value of a gauge.
+    fn gauge_value(&self, gauge: &Self::Gauge) -> f64;
+
+    /// Gets the values currently held by a histogram.
+    ///
+    /// The histogram is left untouched: values are not cleared from it.
+    fn histogram_values(&self, histogram: &Self::Histogram) -> Vec<f64>;
+
+    /// Gets the number of values currently held by a histogram.
+    ///
+    /// The default implementation counts the values returned by
+    /// [`histogram_values`](SnapshotStorage::histogram_values), so storages which can count their
+    /// values without copying them should override it.
+    fn histogram_len(&self, histogram: &Self::Histogram) -> usize {
+        self.histogram_values(histogram).len()
+    }
+}
+
+/// A point-in-time snapshot of the metrics in a [`Registry`](super::Registry).
+///
+/// Snapshots are taken via [`Registry::snapshot`](super::Registry::snapshot).
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot<K> {
+    pub(super) counters: Vec<(K, u64)>,
+    pub(super) gauges: Vec<(K, f64)>,
+    pub(super) histograms: Vec<(K, Vec<f64>)>,
+}
+
+impl<K> Snapshot<K> {
+    /// Gets the counters in the snapshot, and their values.
+    pub fn counters(&self) -> &[(K, u64)] {
+        &self.counters
+    }
+
+    /// Gets the gauges in the snapshot, and their values.
+    pub fn gauges(&self) -> &[(K, f64)] {
+        &self.gauges
+    }
+
+    /// Gets the histograms in the snapshot, and their values.
+    pub fn histograms(&self) -> &[(K, Vec<f64>)] {
+        &self.histograms
+    }
+
+    /// Gets the number of metrics in the snapshot, across all kinds.
+    pub fn len(&self) -> usize {
+        self.counters.len() + self.gauges.len() + self.histograms.len()
+    }
+
+    /// Returns `true` if the snapshot contains no metrics.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{CounterFn, GaugeFn, HistogramFn, Key};
+
+    use crate::registry::{AtomicStorage, Registry};
+
+    #[test]
+    fn test_snapshot() {
+        let registry = Registry::new(AtomicStorage);
+        assert!(registry.snapshot().is_empty());
+
+        let key = Key::from_name("requests");
+        registry.get_or_create_counter(&key, |c| CounterFn::increment(c, 3));
+        registry.get_or_create_gauge(&key, |g| g.set(1.5));
+        registry.get_or_create_histogram(&key, |h| h.record_many(&[1.0, 2.0]));
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(snapshot.counters(), &[(key.clone(), 3)]);
+        assert_eq!(snapshot.gauges(), &[(key.clone(), 1.5)]);
+        assert_eq!(snapshot.histograms(), &[(key.clone(), vec![1.0, 2.0])]);
+
+        // Snapshots don't change as the registry is updated, and don't drain histograms.
+        registry.get_or_create_counter(&key, |c| CounterFn::increment(c, 1));
+        assert_eq!(snapshot.counters(), &[(key.clone(), 3)]);
+        assert_eq!(registry.snapshot().counters(), &[(key.clone(), 4)]);
+        assert_eq!(registry.snapshot().histograms(), &[(key, vec![1.0, 2.0])]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snapshot_serde() {
+        let registry = Registry::new(AtomicStorage);
+        let key = Key::from_parts("requests", vec![metrics::Label::new("method", "GET")]);
+        registry.get_or_create_counter(&key, |c| CounterFn::increment(c, 3));
+        registry.get_or_create_histogram(&key, |h| h.record(0.5));
+
+        let snapshot = registry.snapshot();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let roundtripped: super::Snapshot<Key> = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtripped.counters(), snapshot.counters());
+        assert_eq!(roundtripped.gauges(), snapshot.gauges());
+        assert_eq!(roundtripped.histograms(), snapshot.histograms());
+    }
+
+    #[cfg(feature = "recency")]
+    #[test]
+    fn test_snapshot_generational() {
+        use crate::registry::GenerationalAtomicStorage;
+
+        let registry = Registry::new(GenerationalAtomicStorage::atomic());
+        let key = Key::from_name("requests");
+        registry.get_or_create_counter(&key, |c| CounterFn::increment(c, 2));
+
+        assert_eq!(registry.snapshot().counters(), &[(key, 2)]);
+    }
+}
@@ -1,7 +1,8 @@
-use std::sync::Arc;
+use std::sync::{atomic::Ordering, Arc};
 
 use metrics::{atomics::AtomicU64, CounterFn, GaugeFn, HistogramFn};
 
+use crate::registry::SnapshotStorage;
 use crate::AtomicBucket;
 
 /// Defines the underlying storage for metrics as well as how to create them.
@@ -48,3 +49,21 @@ impl<K> Storage<K> for AtomicStorage {
         Arc::new(AtomicBucket::new())
     }
 }
+
+impl<K> SnapshotStorage<K> for AtomicStorage {
+    fn counter_value(&self, counter: &Self::Counter) -> u64 {
+        counter.load(Ordering::Acquire)
+    }
+
+    fn gauge_value(&self, gauge: &Self::Gauge) -> f64 {
+        f64::from_bits(gauge.load(Ordering::Acquire))
+    }
+
+    fn histogram_values(&self, histogram: &Self::Histogram) -> Vec<f64> {
+        histogram.data()
+    }
+
+    fn histogram_len(&self, histogram: &Self::Histogram) -> usize {
+        histogram.len()
+    }
+}
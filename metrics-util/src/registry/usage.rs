@@ -0,0 +1,94 @@
+use metrics::Key;
+
+use crate::{CompositeKey, MetricKind};
+
+/// A key whose size can be estimated, for reporting the [`MemoryUsage`] of a
+/// [`Registry`](super::Registry).
+pub trait KeySize {
+    /// Gets the number of bytes taken up by the contents of this key, such as its name and labels.
+    ///
+    /// This excludes the size of the key itself, and any overhead of the allocations holding its
+    /// contents.
+    fn key_bytes(&self) -> usize;
+}
+
+impl KeySize for Key {
+    fn key_bytes(&self) -> usize {
+        self.name().len()
+            + self.labels().map(|label| label.key().len() + label.value().len()).sum::<usize>()
+    }
+}
+
+impl KeySize for CompositeKey {
+    fn key_bytes(&self) -> usize {
+        self.key().key_bytes()
+    }
+}
+
+/// The approximate memory footprint of a [`Registry`](super::Registry).
+///
+/// Memory usage is reported via [`Registry::memory_usage`](super::Registry::memory_usage).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MemoryUsage {
+    pub(super) counters: usize,
+    pub(super) gauges: usize,
+    pub(super) histograms: usize,
+    pub(super) key_bytes: usize,
+    pub(super) histogram_samples: usize,
+}
+
+impl MemoryUsage {
+    /// Gets the number of metrics of the given kind.
+    pub fn series(&self, kind: MetricKind) -> usize {
+        match kind {
+            MetricKind::Counter => self.counters,
+            MetricKind::Gauge => self.gauges,
+            MetricKind::Histogram => self.histograms,
+        }
+    }
+
+    /// Gets the number of metrics, across all kinds.
+    pub fn total_series(&self) -> usize {
+        self.counters + self.gauges + self.histograms
+    }
+
+    /// Gets the number of bytes taken up by the keys of every metric, as reported by [`KeySize`].
+    ///
+    /// Keys shared by metrics of different kinds are counted once per kind.
+    pub fn key_bytes(&self) -> usize {
+        self.key_bytes
+    }
+
+    /// Gets the number of samples held by every histogram.
+    pub fn histogram_samples(&self) -> usize {
+        self.histogram_samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::{CounterFn, HistogramFn, Key, Label};
+
+    use super::KeySize;
+    use crate::registry::{AtomicStorage, Registry};
+    use crate::MetricKind;
+
+    #[test]
+    fn test_memory_usage() {
+        let registry = Registry::new(AtomicStorage);
+        let key = Key::from_parts("requests", vec![Label::new("method", "GET")]);
+        assert_eq!(key.key_bytes(), 17);
+
+        registry.get_or_create_counter(&key, |c| c.increment(1));
+        registry.get_or_create_histogram(&key, |h| h.record_many(&[1.0, 2.0, 3.0]));
+        registry.get_or_create_histogram(&Key::from_name("latency"), |h| h.record(1.0));
+
+        let usage = registry.memory_usage();
+        assert_eq!(usage.series(MetricKind::Counter), 1);
+        assert_eq!(usage.series(MetricKind::Gauge), 0);
+        assert_eq!(usage.series(MetricKind::Histogram), 2);
+        assert_eq!(usage.total_series(), 3);
+        assert_eq!(usage.key_bytes(), 17 + 17 + 7);
+        assert_eq!(usage.histogram_samples(), 4);
+    }
+}
@@ -0,0 +1,116 @@
+/// Tracks a cumulative counter read from an external source, producing monotonic deltas.
+///
+/// Collectors which scrape cumulative values from elsewhere, such as from procfs or from another
+/// service, can't pass them through to a counter directly: the source may be reset, such as when
+/// the process it belongs to restarts, after which its value starts again from zero.  A
+/// `CounterTracker` is given each value read from the source in turn, and produces the amount the
+/// counter has increased by since the previous value, treating any decrease as a reset.  The deltas
+/// can then be added to a counter, which stays monotonic across resets of the source.
+///
+/// When a reset is detected, the new value is taken to be the increase since the reset, so any
+/// increase between the last value read before the reset and the reset itself is lost.
+///
+/// ```
+/// # use metrics_util::CounterTracker;
+/// let mut tracker = CounterTracker::new();
+/// assert_eq!(tracker.observe(10), 10);
+/// assert_eq!(tracker.observe(15), 5);
+///
+/// // The source was reset, and has since counted up to 3.
+/// assert_eq!(tracker.observe(3), 3);
+/// assert_eq!(tracker.resets(), 1);
+/// assert_eq!(tracker.total(), 18);
+///
+/// // Deltas can be fed through to a counter.
+/// metrics::counter!("bytes_received").increment(tracker.observe(7));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CounterTracker {
+    last: Option<u64>,
+    total: u64,
+    resets: u64,
+}
+
+impl CounterTracker {
+    /// Creates a new, empty [`CounterTracker`].
+    ///
+    /// The first value observed is treated as an increase from zero, so a counter fed with the
+    /// deltas matches the source.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`CounterTracker`] which has already observed the given value.
+    ///
+    /// This is useful when only the increase from the current value of the source matters, such as
+    /// when the source has been counting since long before it was first read.
+    pub fn with_baseline(value: u64) -> Self {
+        Self { last: Some(value), total: 0, resets: 0 }
+    }
+
+    /// Observes the current value of the source, returning the increase since the previous value.
+    ///
+    /// If the value is lower than the previous value, the source is assumed to have been reset, and
+    /// the value itself is returned as the increase.
+    pub fn observe(&mut self, value: u64) -> u64 {
+        let delta = match self.last {
+            Some(last) if value >= last => value - last,
+            Some(_) => {
+                self.resets += 1;
+                value
+            }
+            None => value,
+        };
+
+        self.last = Some(value);
+        self.total = self.total.saturating_add(delta);
+        delta
+    }
+
+    /// Gets the last value observed, if any.
+    pub fn last(&self) -> Option<u64> {
+        self.last
+    }
+
+    /// Gets the sum of every increase produced so far.
+    ///
+    /// Unlike the values of the source, this never decreases.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Gets the number of resets detected so far.
+    pub fn resets(&self) -> u64 {
+        self.resets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CounterTracker;
+
+    #[test]
+    fn test_counter_tracker() {
+        let mut tracker = CounterTracker::new();
+        assert_eq!(tracker.last(), None);
+        assert_eq!(tracker.observe(5), 5);
+        assert_eq!(tracker.observe(5), 0);
+        assert_eq!(tracker.observe(8), 3);
+        assert_eq!(tracker.resets(), 0);
+
+        assert_eq!(tracker.observe(0), 0);
+        assert_eq!(tracker.observe(4), 4);
+        assert_eq!(tracker.resets(), 1);
+        assert_eq!(tracker.total(), 12);
+        assert_eq!(tracker.last(), Some(4));
+    }
+
+    #[test]
+    fn test_counter_tracker_baseline() {
+        let mut tracker = CounterTracker::with_baseline(1000);
+        assert_eq!(tracker.observe(1010), 10);
+        assert_eq!(tracker.observe(20), 20);
+        assert_eq!(tracker.total(), 30);
+        assert_eq!(tracker.resets(), 1);
+    }
+}
@@ -1,5 +1,7 @@
 use sketches_ddsketch::{Config, DDSketch};
 use std::fmt;
+use std::num::NonZeroU32;
+use std::time::Duration;
 
 /// A quantile sketch with relative-error guarantees.
 ///
@@ -41,6 +43,7 @@ use std::fmt;
 /// [ddsketch]: https://arxiv.org/abs/1908.10693
 /// [hdrhistogram]: https://docs.rs/hdrhistogram
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Summary {
     sketch: DDSketch,
 }
@@ -128,6 +131,11 @@ impl Summary {
         self.sketch.max().unwrap_or(f64::NEG_INFINITY)
     }
 
+    /// Gets the sum of all samples in this summary.
+    pub fn sum(&self) -> f64 {
+        self.sketch.sum().unwrap_or(0.0)
+    }
+
     /// Whether or not this summary is empty.
     pub fn is_empty(&self) -> bool {
         self.count() == 0
@@ -147,6 +155,71 @@ impl Summary {
     }
 }
 
+/// The sliding window over which a rolling summary computes quantiles.
+///
+/// Rolling summaries divide their window into a number of buckets of a fixed duration, and drop the
+/// oldest bucket as it ages out, so quantiles only reflect recent samples.  The total duration of
+/// the window is the number of buckets times the duration of each bucket: for example, 3 buckets of
+/// 20 seconds each cover the last minute, with the oldest 20 seconds of samples being dropped at a
+/// time as the window rolls forward.
+///
+/// Use more buckets with a shorter duration to roll off smaller amounts of data at a time, at the
+/// cost of merging more buckets whenever quantiles are computed.
+///
+/// Defaults to 3 buckets of 20 seconds each.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SummaryWindow {
+    bucket_count: NonZeroU32,
+    bucket_duration: Duration,
+}
+
+impl SummaryWindow {
+    /// Creates a new [`SummaryWindow`] with the given number of buckets, each covering the given
+    /// duration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bucket_duration` is zero.
+    pub fn new(bucket_count: NonZeroU32, bucket_duration: Duration) -> SummaryWindow {
+        assert!(!bucket_duration.is_zero(), "bucket duration must be greater than zero");
+        SummaryWindow { bucket_count, bucket_duration }
+    }
+
+    /// Creates a new [`SummaryWindow`] covering the given total duration, divided evenly into the
+    /// given number of buckets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `duration` is too short to be divided into `bucket_count` buckets of at least one
+    /// nanosecond each.
+    pub fn from_duration(duration: Duration, bucket_count: NonZeroU32) -> SummaryWindow {
+        SummaryWindow::new(bucket_count, duration / bucket_count.get())
+    }
+
+    /// Gets the number of buckets in the window.
+    pub fn bucket_count(&self) -> NonZeroU32 {
+        self.bucket_count
+    }
+
+    /// Gets the duration covered by each bucket.
+    pub fn bucket_duration(&self) -> Duration {
+        self.bucket_duration
+    }
+
+    /// Gets the total duration covered by the window.
+    pub fn duration(&self) -> Duration {
+        self.bucket_duration * self.bucket_count.get()
+    }
+}
+
+impl Default for SummaryWindow {
+    fn default() -> Self {
+        let bucket_count = NonZeroU32::new(3).expect("bucket count should be non-zero");
+        SummaryWindow::new(bucket_count, Duration::from_secs(20))
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct MergeError {}
 
@@ -160,7 +233,8 @@ impl std::error::Error for MergeError {}
 
 #[cfg(test)]
 mod tests {
-    use super::Summary;
+    use super::{Summary, SummaryWindow};
+    use std::num::NonZeroU32;
 
     use quickcheck_macros::quickcheck;
 
@@ -175,6 +249,18 @@ mod tests {
     use rand::{distributions::Distribution, thread_rng};
     use rand_distr::Uniform;
 
+    #[test]
+    fn test_summary_window() {
+        let window = SummaryWindow::default();
+        assert_eq!(window.bucket_count().get(), 3);
+        assert_eq!(window.duration(), std::time::Duration::from_secs(60));
+
+        let fifteen_minutes = std::time::Duration::from_secs(900);
+        let window = SummaryWindow::from_duration(fifteen_minutes, NonZeroU32::new(15).unwrap());
+        assert_eq!(window.bucket_duration(), std::time::Duration::from_secs(60));
+        assert_eq!(window.duration(), fifteen_minutes);
+    }
+
     #[test]
     fn test_basics() {
         let alpha = 0.0001;
@@ -0,0 +1,319 @@
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+
+/// A centroid in a t-digest: the mean of a cluster of samples, and the number of samples in it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+impl Centroid {
+    fn merge(&mut self, other: &Centroid) {
+        self.weight += other.weight;
+        self.mean += (other.mean - self.mean) * other.weight / self.weight;
+    }
+}
+
+/// A quantile sketch based on the merging variant of the [t-digest][tdigest].
+///
+/// `TDigest` clusters samples into a bounded number of centroids, keeping the clusters at the
+/// extremes of the distribution small, so that quantiles close to `0.0` and `1.0` are estimated
+/// far more accurately than those in the middle of the distribution.  This makes it a good fit for
+/// tail latencies, and an alternative to [`Summary`](crate::Summary), which instead provides a
+/// relative-error guarantee across all quantiles.
+///
+/// The accuracy, and memory usage, of a digest are controlled by its compression: the digest holds
+/// at most around `compression` centroids, and the error of its estimates shrinks as the
+/// compression grows.  A compression of `100` is a common default, which yields errors well below
+/// one percent of the rank at the extremes of the distribution.
+///
+/// Digests can be merged with each other, regardless of their compression, which makes them
+/// suitable for aggregating samples recorded separately, such as across shards or processes.
+///
+/// Samples are buffered before being clustered, so a digest with buffered samples is compressed on
+/// the fly whenever a quantile is requested.  When querying many quantiles at once, calling
+/// [`TDigest::compress`] beforehand avoids doing so repeatedly.
+///
+/// [tdigest]: https://arxiv.org/abs/1902.04023
+#[derive(Clone, Debug)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    buffer_capacity: usize,
+    count: f64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    /// Creates a new [`TDigest`] with the given compression.
+    ///
+    /// The compression is clamped to a minimum of `10`.
+    pub fn new(compression: f64) -> TDigest {
+        let compression = if compression.is_nan() { 100.0 } else { compression.max(10.0) };
+        let buffer_capacity = (compression * 5.0).ceil() as usize;
+
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::with_capacity(buffer_capacity),
+            buffer_capacity,
+            count: 0.0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Creates a new [`TDigest`] with a compression of `100`.
+    pub fn with_defaults() -> TDigest {
+        TDigest::new(100.0)
+    }
+
+    /// Gets the compression of this digest.
+    pub fn compression(&self) -> f64 {
+        self.compression
+    }
+
+    /// Adds a sample to the digest.
+    ///
+    /// Samples which aren't finite are ignored.
+    pub fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+
+        self.insert(Centroid { mean: value, weight: 1.0 });
+    }
+
+    /// Merges another digest into this one.
+    ///
+    /// The merged digest keeps the compression of this digest.
+    pub fn merge(&mut self, other: &TDigest) {
+        for centroid in other.centroids.iter().chain(other.buffer.iter()) {
+            self.insert(*centroid);
+        }
+
+        // The minimum and maximum of the other digest may not be the mean of any of its centroids.
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Gets the estimated value at the given quantile.
+    ///
+    /// If the digest is empty, or if the quantile is less than 0.0 or greater than 1.0, then the
+    /// result will be `None`.
+    ///
+    /// If the 0.0 or 1.0 quantile is requested, this function will return self.min() or self.max()
+    /// instead of the estimated value.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if !(0.0..=1.0).contains(&q) || self.is_empty() {
+            return None;
+        }
+
+        if self.buffer.is_empty() {
+            return Some(self.quantile_compressed(q));
+        }
+
+        let mut digest = self.clone();
+        digest.compress();
+        Some(digest.quantile_compressed(q))
+    }
+
+    /// Clusters any buffered samples into the centroids of the digest.
+    pub fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let mut centroids = std::mem::take(&mut self.centroids);
+        centroids.append(&mut self.buffer);
+        centroids.sort_by(|a, b| a.mean.partial_cmp(&b.mean).unwrap_or(Ordering::Equal));
+
+        let total = self.count;
+        let mut merged = Vec::with_capacity(centroids.len().min(self.compression as usize * 2));
+        let mut centroids = centroids.into_iter();
+        let mut current = centroids.next().expect("buffer should not be empty");
+        let mut weight_so_far = 0.0;
+        let mut q_limit = self.q_limit(0.0);
+
+        for centroid in centroids {
+            let q = (weight_so_far + current.weight + centroid.weight) / total;
+            if q <= q_limit {
+                current.merge(&centroid);
+            } else {
+                weight_so_far += current.weight;
+                merged.push(current);
+                current = centroid;
+                q_limit = self.q_limit(weight_so_far / total);
+            }
+        }
+        merged.push(current);
+
+        self.centroids = merged;
+    }
+
+    /// Gets the minimum value this digest has seen so far.
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    /// Gets the maximum value this digest has seen so far.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// Gets the sum of all samples in this digest.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Whether or not this digest is empty.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0.0
+    }
+
+    /// Gets the number of samples in this digest.
+    pub fn count(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Gets the estimated size of this digest, in bytes.
+    pub fn estimated_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + (self.centroids.capacity() + self.buffer.capacity()) * std::mem::size_of::<Centroid>()
+    }
+
+    fn insert(&mut self, centroid: Centroid) {
+        self.count += centroid.weight;
+        self.sum += centroid.mean * centroid.weight;
+        self.min = self.min.min(centroid.mean);
+        self.max = self.max.max(centroid.mean);
+
+        self.buffer.push(centroid);
+        if self.buffer.len() >= self.buffer_capacity {
+            self.compress();
+        }
+    }
+
+    /// Gets the upper bound, as a quantile, of the centroid starting at quantile `q`.
+    ///
+    /// This uses the `k1` scale function from the t-digest paper, which allows each centroid to
+    /// span a single unit of `k`, keeping centroids at the tails of the distribution small.
+    fn q_limit(&self, q: f64) -> f64 {
+        let normalizer = self.compression / (2.0 * PI);
+        let k = normalizer * (2.0 * q - 1.0).asin() + 1.0;
+        if k >= normalizer * (PI / 2.0) {
+            return 1.0;
+        }
+
+        ((k / normalizer).sin() + 1.0) / 2.0
+    }
+
+    fn quantile_compressed(&self, q: f64) -> f64 {
+        if q == 0.0 {
+            return self.min;
+        }
+        if q == 1.0 {
+            return self.max;
+        }
+
+        let rank = q * self.count;
+        let first = &self.centroids[0];
+        if rank < first.weight / 2.0 {
+            // Between the minimum and the center of the first centroid.
+            return self.min + (first.mean - self.min) * rank / (first.weight / 2.0);
+        }
+
+        let mut weight_so_far = 0.0;
+        for pair in self.centroids.windows(2) {
+            let (left, right) = (&pair[0], &pair[1]);
+            let left_center = weight_so_far + left.weight / 2.0;
+            let right_center = weight_so_far + left.weight + right.weight / 2.0;
+            if rank <= right_center {
+                let fraction = (rank - left_center) / (right_center - left_center);
+                return left.mean + (right.mean - left.mean) * fraction;
+            }
+            weight_so_far += left.weight;
+        }
+
+        // Between the center of the last centroid and the maximum.
+        let last = &self.centroids[self.centroids.len() - 1];
+        let last_center = self.count - last.weight / 2.0;
+        let fraction = (rank - last_center) / (last.weight / 2.0);
+        last.mean + (self.max - last.mean) * fraction.min(1.0)
+    }
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        TDigest::with_defaults()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TDigest;
+
+    fn assert_rank_error(digest: &TDigest, n: usize, max_error: f64) {
+        for &q in &[0.001, 0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 0.999] {
+            let estimate = digest.quantile(q).expect("value should exist");
+            let expected = q * n as f64;
+            let error = (estimate - expected).abs() / n as f64;
+            assert!(error <= max_error, "q={} estimate={} expected={}", q, estimate, expected);
+        }
+    }
+
+    #[test]
+    fn test_basics() {
+        let mut digest = TDigest::with_defaults();
+        assert!(digest.is_empty());
+        assert_eq!(digest.quantile(0.5), None);
+
+        digest.add(42.0);
+        digest.add(f64::NAN);
+        assert_eq!(digest.count(), 1);
+        assert_eq!(digest.quantile(0.0), Some(42.0));
+        assert_eq!(digest.quantile(0.5), Some(42.0));
+        assert_eq!(digest.quantile(1.0), Some(42.0));
+        assert_eq!(digest.quantile(1.5), None);
+    }
+
+    #[test]
+    fn test_uniform() {
+        let n = 100_000;
+        let mut digest = TDigest::new(100.0);
+        for i in 0..n {
+            // Interleave the samples so they're not added in order.
+            digest.add(((i * 7919) % n) as f64);
+        }
+        digest.compress();
+
+        assert_eq!(digest.count(), n);
+        assert_eq!(digest.min(), 0.0);
+        assert_eq!(digest.max(), (n - 1) as f64);
+        assert!(digest.centroids.len() <= 200);
+        assert_rank_error(&digest, n, 0.01);
+    }
+
+    #[test]
+    fn test_merge() {
+        let n = 100_000;
+        let mut shards = vec![TDigest::new(100.0), TDigest::new(100.0), TDigest::new(100.0)];
+        for i in 0..n {
+            shards[i % 3].add(i as f64);
+        }
+
+        let mut merged = TDigest::new(100.0);
+        for shard in &shards {
+            merged.merge(shard);
+        }
+
+        assert_eq!(merged.count(), n);
+        assert_eq!(merged.sum(), (n * (n - 1) / 2) as f64);
+        assert_rank_error(&merged, n, 0.01);
+    }
+}
@@ -1,4 +1,7 @@
-use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use metrics::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary,
+    Unit,
+};
 use mockall::{
     mock,
     predicate::{self, always, eq},
@@ -10,9 +13,12 @@ pub enum RecorderOperation {
     DescribeCounter(KeyName, Option<Unit>, SharedString),
     DescribeGauge(KeyName, Option<Unit>, SharedString),
     DescribeHistogram(KeyName, Option<Unit>, SharedString),
+    DescribeSummary(KeyName, Option<Unit>, SharedString),
+    DescribeAttributes(KeyName, Attributes),
     RegisterCounter(Key, Counter, &'static Metadata<'static>),
     RegisterGauge(Key, Gauge, &'static Metadata<'static>),
     RegisterHistogram(Key, Histogram, &'static Metadata<'static>),
+    RegisterSummary(Key, Summary, &'static Metadata<'static>),
 }
 
 impl RecorderOperation {
@@ -27,6 +33,12 @@ impl RecorderOperation {
             RecorderOperation::DescribeHistogram(key_name, unit, desc) => {
                 expect_describe_histogram(mock, key_name, unit, desc)
             }
+            RecorderOperation::DescribeSummary(key_name, unit, desc) => {
+                expect_describe_summary(mock, key_name, unit, desc)
+            }
+            RecorderOperation::DescribeAttributes(key_name, _) => {
+                expect_describe_attributes(mock, key_name)
+            }
             RecorderOperation::RegisterCounter(key, counter, _) => {
                 expect_register_counter(mock, key, counter)
             }
@@ -36,6 +48,9 @@ impl RecorderOperation {
             RecorderOperation::RegisterHistogram(key, histogram, _) => {
                 expect_register_histogram(mock, key, histogram)
             }
+            RecorderOperation::RegisterSummary(key, summary, _) => {
+                expect_register_summary(mock, key, summary)
+            }
         }
     }
 
@@ -53,6 +68,12 @@ impl RecorderOperation {
             RecorderOperation::DescribeHistogram(key_name, unit, desc) => {
                 recorder.describe_histogram(key_name, unit, desc);
             }
+            RecorderOperation::DescribeSummary(key_name, unit, desc) => {
+                recorder.describe_summary(key_name, unit, desc);
+            }
+            RecorderOperation::DescribeAttributes(key_name, attributes) => {
+                recorder.describe_attributes(key_name, attributes);
+            }
             RecorderOperation::RegisterCounter(key, _, metadata) => {
                 let _ = recorder.register_counter(&key, metadata);
             }
@@ -62,6 +83,9 @@ impl RecorderOperation {
             RecorderOperation::RegisterHistogram(key, _, metadata) => {
                 let _ = recorder.register_histogram(&key, metadata);
             }
+            RecorderOperation::RegisterSummary(key, _, metadata) => {
+                let _ = recorder.register_summary(&key, metadata);
+            }
         }
     }
 }
@@ -76,6 +100,9 @@ mock! {
         fn register_counter<'a>(&'a self, key: &'a Key, metadata: &'a Metadata<'a>) -> Counter;
         fn register_gauge<'a>(&'a self, key: &'a Key, metadata: &'a Metadata<'a>) -> Gauge;
         fn register_histogram<'a>(&'a self, key: &'a Key, metadata: &'a Metadata<'a>) -> Histogram;
+        fn describe_summary(&self, key_name: KeyName, unit: Option<Unit>, description: SharedString);
+        fn register_summary<'a>(&'a self, key: &'a Key, metadata: &'a Metadata<'a>) -> Summary;
+        fn describe_attributes(&self, key_name: KeyName, attributes: Attributes);
     }
 }
 
@@ -128,6 +155,23 @@ pub fn expect_describe_histogram(
         .return_const(());
 }
 
+pub fn expect_describe_summary(
+    mock: &mut MockBasicRecorder,
+    key_name: KeyName,
+    unit: Option<Unit>,
+    description: SharedString,
+) {
+    mock.expect_describe_summary()
+        .times(1)
+        .with(eq(key_name), eq(unit), eq(description))
+        .return_const(());
+}
+
+// Attributes can't be compared, so only the key name they're described for is checked.
+pub fn expect_describe_attributes(mock: &mut MockBasicRecorder, key_name: KeyName) {
+    mock.expect_describe_attributes().times(1).with(eq(key_name), always()).return_const(());
+}
+
 pub fn expect_register_counter(mock: &mut MockBasicRecorder, key: Key, counter: Counter) {
     mock.expect_register_counter().times(1).with(ref_eq(key), always()).return_const(counter);
 }
@@ -140,6 +184,10 @@ pub fn expect_register_histogram(mock: &mut MockBasicRecorder, key: Key, histogr
     mock.expect_register_histogram().times(1).with(ref_eq(key), always()).return_const(histogram);
 }
 
+pub fn expect_register_summary(mock: &mut MockBasicRecorder, key: Key, summary: Summary) {
+    mock.expect_register_summary().times(1).with(ref_eq(key), always()).return_const(summary);
+}
+
 fn ref_eq<T: PartialEq>(value: T) -> impl Predicate<T> {
     predicate::function(move |item: &T| item == &value)
 }
@@ -37,6 +37,14 @@ impl GaugeFn for PrintHandle {
     fn set(&self, value: f64) {
         println!("gauge set for '{}': {}", self.0, value);
     }
+
+    fn set_max(&self, value: f64) {
+        println!("gauge set_max for '{}': {}", self.0, value);
+    }
+
+    fn set_min(&self, value: f64) {
+        println!("gauge set_min for '{}': {}", self.0, value);
+    }
 }
 
 impl HistogramFn for PrintHandle {
@@ -10,12 +10,12 @@
 //! As such, the atomic types that we provide handle implementations for are publicly re-exporter
 //! here for downstream crates to utilize.
 
-use std::sync::atomic::Ordering;
+use core::sync::atomic::Ordering;
 
+#[cfg(not(target_pointer_width = "32"))]
+pub use core::sync::atomic::AtomicU64;
 #[cfg(target_pointer_width = "32")]
 pub use portable_atomic::AtomicU64;
-#[cfg(not(target_pointer_width = "32"))]
-pub use std::sync::atomic::AtomicU64;
 
 use super::{CounterFn, GaugeFn};
 
@@ -61,4 +61,65 @@ impl GaugeFn for AtomicU64 {
     fn set(&self, value: f64) {
         let _ = self.swap(value.to_bits(), Ordering::AcqRel);
     }
+
+    fn set_max(&self, value: f64) {
+        let _ = self.fetch_update(Ordering::AcqRel, Ordering::Relaxed, |curr| {
+            let input = f64::from_bits(curr);
+            let output = input.max(value);
+            (output.to_bits() != curr).then_some(output.to_bits())
+        });
+    }
+
+    fn set_min(&self, value: f64) {
+        let _ = self.fetch_update(Ordering::AcqRel, Ordering::Relaxed, |curr| {
+            let input = f64::from_bits(curr);
+            let output = input.min(value);
+            (output.to_bits() != curr).then_some(output.to_bits())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicU64;
+    use crate::GaugeFn;
+    use std::sync::atomic::Ordering;
+
+    fn value(atomic: &AtomicU64) -> f64 {
+        f64::from_bits(atomic.load(Ordering::Acquire))
+    }
+
+    #[test]
+    fn test_gauge_set_max() {
+        let gauge = AtomicU64::new(0.0f64.to_bits());
+
+        gauge.set_max(5.0);
+        assert_eq!(value(&gauge), 5.0);
+
+        gauge.set_max(3.0);
+        assert_eq!(value(&gauge), 5.0);
+
+        gauge.set_max(f64::NAN);
+        assert_eq!(value(&gauge), 5.0);
+
+        gauge.set_max(7.5);
+        assert_eq!(value(&gauge), 7.5);
+    }
+
+    #[test]
+    fn test_gauge_set_min() {
+        let gauge = AtomicU64::new(0.0f64.to_bits());
+
+        gauge.set_min(-2.0);
+        assert_eq!(value(&gauge), -2.0);
+
+        gauge.set_min(1.0);
+        assert_eq!(value(&gauge), -2.0);
+
+        gauge.set_min(f64::NAN);
+        assert_eq!(value(&gauge), -2.0);
+
+        gauge.set_min(-4.5);
+        assert_eq!(value(&gauge), -4.5);
+    }
 }
@@ -0,0 +1,326 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::any::Any;
+use core::fmt;
+use core::time::Duration;
+
+use crate::{SharedString, Unit};
+
+/// Helper trait for converting attributes to [`Any`], which allows downcasting them.
+///
+/// This trait is implemented for all `'static` types, and should not need to be implemented
+/// manually.
+#[doc(hidden)]
+pub trait AsAny {
+    /// Converts this value to a reference to [`Any`].
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A typed piece of information describing a metric.
+///
+/// Units and descriptions are the most common information attached to a metric, but exporters and
+/// layers may care about other information, such as which team owns a metric, or whether a metric
+/// is deprecated.  Attributes provide an open-ended way to attach this information, where any type
+/// can be used as an attribute by implementing this trait.
+///
+/// Recorders receive attributes as a set, via [`Attributes`], and can look up the attributes they
+/// understand by type, via [`Attributes::get`], or visit all of them, via [`Attributes::iter`] and
+/// [`downcast_ref`](trait.Attribute.html#method.downcast_ref).  Attributes which a recorder does
+/// not understand can simply be ignored.
+///
+/// A standard set of attributes is provided: [`Stability`], [`Owner`], [`Deprecated`],
+/// [`BucketHint`], and [`TimeBase`].
+pub trait Attribute: AsAny + fmt::Debug + Send + Sync + 'static {}
+
+impl dyn Attribute {
+    /// Returns `true` if this attribute is of type `T`.
+    pub fn is<T: Attribute>(&self) -> bool {
+        self.as_any().is::<T>()
+    }
+
+    /// Returns a reference to this attribute as type `T`, if it is of that type.
+    pub fn downcast_ref<T: Attribute>(&self) -> Option<&T> {
+        self.as_any().downcast_ref::<T>()
+    }
+}
+
+/// A set of attributes describing a metric.
+///
+/// Holds at most one attribute of any given type: inserting an attribute replaces any existing
+/// attribute of the same type.
+#[derive(Clone, Debug, Default)]
+pub struct Attributes {
+    inner: Vec<Arc<dyn Attribute>>,
+}
+
+impl Attributes {
+    /// Creates an empty set of attributes.
+    pub fn new() -> Self {
+        Self { inner: Vec::new() }
+    }
+
+    /// Adds the given attribute to the set, replacing any existing attribute of the same type.
+    pub fn with<A: Attribute>(mut self, attribute: A) -> Self {
+        self.insert(attribute);
+        self
+    }
+
+    /// Inserts the given attribute into the set, replacing any existing attribute of the same type.
+    pub fn insert<A: Attribute>(&mut self, attribute: A) {
+        match self.inner.iter_mut().find(|existing| existing.is::<A>()) {
+            Some(existing) => *existing = Arc::new(attribute),
+            None => self.inner.push(Arc::new(attribute)),
+        }
+    }
+
+    /// Gets the attribute of type `A`, if one is present in the set.
+    pub fn get<A: Attribute>(&self) -> Option<&A> {
+        self.inner.iter().find_map(|attribute| attribute.downcast_ref::<A>())
+    }
+
+    /// Returns `true` if an attribute of type `A` is present in the set.
+    pub fn contains<A: Attribute>(&self) -> bool {
+        self.get::<A>().is_some()
+    }
+
+    /// Gets an iterator over all attributes in the set, in the order they were first inserted.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Attribute> {
+        self.inner.iter().map(|attribute| &**attribute)
+    }
+
+    /// Gets the number of attributes in the set.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the set holds no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+/// The stability of a metric.
+///
+/// Allows consumers of a metric to know whether its name, labels, or meaning may change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stability {
+    /// The metric is experimental, and may change or be removed at any time.
+    Experimental,
+
+    /// The metric is stable, and will not change without notice.
+    Stable,
+}
+
+impl Attribute for Stability {}
+
+/// The owner of a metric, such as a team or a service.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Owner(SharedString);
+
+impl Owner {
+    /// Creates a new `Owner` with the given name.
+    pub fn new<S: Into<SharedString>>(owner: S) -> Self {
+        Self(owner.into())
+    }
+
+    /// Gets the name of the owner.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Attribute for Owner {}
+
+/// Marks a metric as deprecated.
+///
+/// An optional note can be given, such as the name of the metric that replaces it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Deprecated(Option<SharedString>);
+
+impl Deprecated {
+    /// Creates a new `Deprecated` attribute without a note.
+    pub fn new() -> Self {
+        Self(None)
+    }
+
+    /// Creates a new `Deprecated` attribute with the given note.
+    pub fn with_note<S: Into<SharedString>>(note: S) -> Self {
+        Self(Some(note.into()))
+    }
+
+    /// Gets the note explaining the deprecation, if any.
+    pub fn note(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+impl Attribute for Deprecated {}
+
+/// Suggested bucket boundaries for a histogram.
+///
+/// Recorders which aggregate histograms into buckets may use these boundaries in place of their
+/// default buckets.  The boundaries are sorted in ascending order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BucketHint(Vec<f64>);
+
+impl BucketHint {
+    /// Creates a new `BucketHint` from the given bucket boundaries.
+    ///
+    /// Boundaries which are not finite are ignored.
+    pub fn new<I: IntoIterator<Item = f64>>(buckets: I) -> Self {
+        let mut buckets = buckets.into_iter().filter(|b| b.is_finite()).collect::<Vec<_>>();
+        buckets.sort_by(|a, b| a.total_cmp(b));
+        buckets.dedup();
+        Self(buckets)
+    }
+
+    /// Gets the bucket boundaries.
+    pub fn buckets(&self) -> &[f64] {
+        &self.0
+    }
+}
+
+impl Attribute for BucketHint {}
+
+/// The fraction of observations recorded into a histogram.
+///
+/// Histograms which are sampled at the callsite, such as via
+/// [`sampled_histogram!`](crate::sampled_histogram), only record a fraction of their observations.
+/// Recorders which support this attribute can use the rate to scale counts and sums back up to
+/// estimates of their true values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampleRate(f64);
+
+impl SampleRate {
+    /// Creates a new `SampleRate` with the given rate.
+    ///
+    /// The rate is clamped to the range of `0.0` to `1.0`.
+    pub fn new(rate: f64) -> Self {
+        Self(rate.clamp(0.0, 1.0))
+    }
+
+    /// Gets the sample rate.
+    pub fn rate(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Attribute for SampleRate {}
+
+/// The time base in which durations recorded into a histogram are measured.
+///
+/// Durations recorded via [`Histogram::record_duration`](crate::Histogram::record_duration) are
+/// recorded in seconds by default.  Recorders which support this attribute record them in the given
+/// time base instead, so that callers can record durations directly, rather than each converting
+/// them to a number by hand.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TimeBase {
+    /// Durations are measured in seconds.
+    #[default]
+    Seconds,
+
+    /// Durations are measured in milliseconds.
+    Milliseconds,
+
+    /// Durations are measured in microseconds.
+    Microseconds,
+
+    /// Durations are measured in nanoseconds.
+    Nanoseconds,
+}
+
+impl TimeBase {
+    /// Converts the given duration to a value in this time base.
+    pub fn convert(&self, duration: Duration) -> f64 {
+        match self {
+            TimeBase::Seconds => duration.as_secs_f64(),
+            TimeBase::Milliseconds => duration.as_nanos() as f64 / 1e6,
+            TimeBase::Microseconds => duration.as_nanos() as f64 / 1e3,
+            TimeBase::Nanoseconds => duration.as_nanos() as f64,
+        }
+    }
+
+    /// Gets the unit of values in this time base.
+    pub fn unit(&self) -> Unit {
+        match self {
+            TimeBase::Seconds => Unit::Seconds,
+            TimeBase::Milliseconds => Unit::Milliseconds,
+            TimeBase::Microseconds => Unit::Microseconds,
+            TimeBase::Nanoseconds => Unit::Nanoseconds,
+        }
+    }
+}
+
+impl Attribute for TimeBase {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        Attribute, Attributes, BucketHint, Deprecated, Owner, SampleRate, Stability, TimeBase,
+    };
+
+    #[test]
+    fn test_get_and_replace() {
+        let attributes = Attributes::new()
+            .with(Owner::new("storage"))
+            .with(Stability::Experimental)
+            .with(Stability::Stable);
+
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes.get::<Owner>().map(Owner::as_str), Some("storage"));
+        assert_eq!(attributes.get::<Stability>(), Some(&Stability::Stable));
+        assert!(!attributes.contains::<Deprecated>());
+    }
+
+    #[test]
+    fn test_visit() {
+        #[derive(Debug)]
+        struct Custom;
+
+        impl Attribute for Custom {}
+
+        let attributes = Attributes::new()
+            .with(Deprecated::with_note("use `requests_total` instead"))
+            .with(Custom);
+
+        let mut visited = 0;
+        for attribute in attributes.iter() {
+            if let Some(deprecated) = attribute.downcast_ref::<Deprecated>() {
+                assert_eq!(deprecated.note(), Some("use `requests_total` instead"));
+                visited += 1;
+            } else if attribute.is::<Custom>() {
+                visited += 1;
+            }
+        }
+        assert_eq!(visited, 2);
+    }
+
+    #[test]
+    fn test_bucket_hint() {
+        let hint = BucketHint::new([1.0, f64::NAN, 0.5, 1.0, f64::INFINITY, 2.5]);
+        assert_eq!(hint.buckets(), &[0.5, 1.0, 2.5]);
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        assert_eq!(SampleRate::new(0.25).rate(), 0.25);
+        assert_eq!(SampleRate::new(2.0).rate(), 1.0);
+        assert_eq!(SampleRate::new(-1.0).rate(), 0.0);
+    }
+
+    #[test]
+    fn test_time_base() {
+        let duration = Duration::from_micros(1500);
+        assert_eq!(TimeBase::default().convert(duration), 0.0015);
+        assert_eq!(TimeBase::Milliseconds.convert(duration), 1.5);
+        assert_eq!(TimeBase::Microseconds.convert(duration), 1500.0);
+        assert_eq!(TimeBase::Nanoseconds.convert(duration), 1_500_000.0);
+    }
+}
@@ -1,8 +1,9 @@
-use std::hash::Hasher;
+use core::hash::Hasher;
 
 use ahash::AHasher;
 
 use crate::cow::Cow;
+use crate::HistogramFn;
 
 /// An allocation-optimized string.
 ///
@@ -62,7 +63,9 @@ impl GaugeValue {
 ///
 /// While metrics do not necessarily need to be tied to a particular unit to be recorded, some
 /// downstream systems natively support defining units and so they can be specified during registration.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+///
+/// Units which aren't covered by the built-in variants can be given by name via [`Unit::Custom`].
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Unit {
     /// Count.
     Count,
@@ -120,11 +123,51 @@ pub enum Unit {
     BitsPerSecond,
     /// Count per second.
     CountPerSecond,
+    /// Ratio.
+    ///
+    /// A ratio of 1.0 is equal to 100 percent.
+    Ratio,
+    /// Degrees Celsius.
+    Celsius,
+    /// Joules.
+    Joules,
+    /// Packets.
+    Packets,
+    /// A unit which isn't covered by any of the other variants, given by name.
+    ///
+    /// Names should follow the same conventions as the built-in units, i.e. lowercase and plural,
+    /// such as `requests` or `watts`, so that they render consistently across exporters.
+    Custom(SharedString),
+}
+
+/// Units are serialized as their canonical string representation, as given by [`Unit::as_str`].
+#[cfg(feature = "serde")]
+impl serde::Serialize for Unit {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Unit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let unit: alloc::string::String = serde::Deserialize::deserialize(deserializer)?;
+        Unit::from_string(&unit)
+            .ok_or_else(|| serde::de::Error::custom(format_args!("unknown unit `{}`", unit)))
+    }
 }
 
 impl Unit {
     /// Gets the string form of this `Unit`.
-    pub fn as_str(&self) -> &'static str {
+    ///
+    /// For custom units, this is the name of the unit.
+    pub fn as_str(&self) -> &str {
         match self {
             Unit::Count => "count",
             Unit::Percent => "percent",
@@ -143,6 +186,11 @@ impl Unit {
             Unit::KilobitsPerSecond => "kilobits_per_second",
             Unit::BitsPerSecond => "bits_per_second",
             Unit::CountPerSecond => "count_per_second",
+            Unit::Ratio => "ratio",
+            Unit::Celsius => "celsius",
+            Unit::Joules => "joules",
+            Unit::Packets => "packets",
+            Unit::Custom(name) => name,
         }
     }
 
@@ -151,8 +199,9 @@ impl Unit {
     /// For example, the canonical label for `Seconds` would be `s`, while for `Nanoseconds`,
     /// it would be `ns`.
     ///
-    /// Not all units have a meaningful display label and so some may be empty.
-    pub fn as_canonical_label(&self) -> &'static str {
+    /// Not all units have a meaningful display label and so some may be empty.  Custom units use
+    /// their name as their label.
+    pub fn as_canonical_label(&self) -> &str {
         match self {
             Unit::Count => "",
             Unit::Percent => "%",
@@ -171,12 +220,18 @@ impl Unit {
             Unit::KilobitsPerSecond => "kbps",
             Unit::BitsPerSecond => "bps",
             Unit::CountPerSecond => "/s",
+            Unit::Ratio => "",
+            Unit::Celsius => "°C",
+            Unit::Joules => "J",
+            Unit::Packets => "pkt",
+            Unit::Custom(name) => name,
         }
     }
 
     /// Converts the string representation of a unit back into `Unit` if possible.
     ///
-    /// The value passed here should match the output of [`Unit::as_str`].
+    /// The value passed here should match the output of [`Unit::as_str`].  Custom units can't be
+    /// distinguished from unknown ones, and so `None` is returned for them.
     pub fn from_string(s: &str) -> Option<Unit> {
         match s {
             "count" => Some(Unit::Count),
@@ -196,6 +251,10 @@ impl Unit {
             "kilobits_per_second" => Some(Unit::KilobitsPerSecond),
             "bits_per_second" => Some(Unit::BitsPerSecond),
             "count_per_second" => Some(Unit::CountPerSecond),
+            "ratio" => Some(Unit::Ratio),
+            "celsius" => Some(Unit::Celsius),
+            "joules" => Some(Unit::Joules),
+            "packets" => Some(Unit::Packets),
             _ => None,
         }
     }
@@ -242,6 +301,18 @@ impl Unit {
 pub trait IntoF64 {
     /// Converts this object to its `f64` representation.
     fn into_f64(self) -> f64;
+
+    /// Records this object into the given histogram handler.
+    ///
+    /// Durations are recorded via [`HistogramFn::record_duration`], so that they're recorded in the
+    /// time base described for the histogram, while every other value is recorded as-is.
+    #[doc(hidden)]
+    fn record_into(self, histogram: &dyn HistogramFn)
+    where
+        Self: Sized,
+    {
+        histogram.record(self.into_f64())
+    }
 }
 
 impl IntoF64 for f64 {
@@ -254,6 +325,10 @@ impl IntoF64 for core::time::Duration {
     fn into_f64(self) -> f64 {
         self.as_secs_f64()
     }
+
+    fn record_into(self, histogram: &dyn HistogramFn) {
+        histogram.record_duration(self)
+    }
 }
 
 into_f64!(i8, u8, i16, u16, i32, u32, f32);
@@ -304,6 +379,10 @@ mod tests {
             Unit::KilobitsPerSecond,
             Unit::BitsPerSecond,
             Unit::CountPerSecond,
+            Unit::Ratio,
+            Unit::Celsius,
+            Unit::Joules,
+            Unit::Packets,
         ];
 
         for variant in all_variants {
@@ -313,6 +392,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_custom_unit() {
+        let unit = Unit::Custom("requests".into());
+        assert_eq!(unit.as_str(), "requests");
+        assert_eq!(unit.as_canonical_label(), "requests");
+        assert!(!unit.is_time_based());
+        assert_eq!(Unit::from_string(unit.as_str()), None);
+    }
+
     #[test]
     fn into_f64() {
         fn test<T: IntoF64>(val: T) {
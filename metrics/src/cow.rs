@@ -1,4 +1,10 @@
-use std::{
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
     borrow::Borrow,
     cmp::Ordering,
     fmt,
@@ -7,7 +13,6 @@ use std::{
     mem::ManuallyDrop,
     ops::Deref,
     ptr::{slice_from_raw_parts, NonNull},
-    sync::Arc,
 };
 
 #[derive(Clone, Copy)]
@@ -31,15 +36,15 @@ enum Kind {
 /// to allocate entire copies of a string, instead using a lightweight smart pointer that can live
 /// on the stack.
 ///
-/// # Why not `std::borrow::Cow`?
+/// # Why not `alloc::borrow::Cow`?
 ///
-/// The standard library already provides a clone-on-write smart pointer, `std::borrow::Cow`, which
+/// The standard library already provides a clone-on-write smart pointer, `alloc::borrow::Cow`, which
 /// works well in many cases. However, `metrics` strives to provide minimal overhead where possible,
-/// and so `std::borrow::Cow` falls down in one particular way: it uses an enum representation which
+/// and so `alloc::borrow::Cow` falls down in one particular way: it uses an enum representation which
 /// consumes an additional word of storage.
 ///
-/// As an example, let's look at strings. A string in `std::borrow::Cow` implies that `T` is `str`,
-/// and the owned version of `str` is simply `String`. Thus, for `std::borrow::Cow`, the in-memory
+/// As an example, let's look at strings. A string in `alloc::borrow::Cow` implies that `T` is `str`,
+/// and the owned version of `str` is simply `String`. Thus, for `alloc::borrow::Cow`, the in-memory
 /// layout looks like this:
 ///
 /// ```text
@@ -314,17 +319,17 @@ where
     }
 }
 
-impl<'a> From<std::borrow::Cow<'a, str>> for Cow<'a, str> {
+impl<'a> From<alloc::borrow::Cow<'a, str>> for Cow<'a, str> {
     #[inline]
-    fn from(s: std::borrow::Cow<'a, str>) -> Self {
+    fn from(s: alloc::borrow::Cow<'a, str>) -> Self {
         match s {
-            std::borrow::Cow::Borrowed(bs) => Cow::from_borrowed(bs),
-            std::borrow::Cow::Owned(os) => Cow::from_owned(os),
+            alloc::borrow::Cow::Borrowed(bs) => Cow::from_borrowed(bs),
+            alloc::borrow::Cow::Owned(os) => Cow::from_owned(os),
         }
     }
 }
 
-impl<'a, T: Cowable> From<Cow<'a, T>> for std::borrow::Cow<'a, T> {
+impl<'a, T: Cowable> From<Cow<'a, T>> for alloc::borrow::Cow<'a, T> {
     #[inline]
     fn from(value: Cow<'a, T>) -> Self {
         match value.metadata.kind() {
@@ -338,6 +343,26 @@ impl<'a, T: Cowable> From<Cow<'a, T>> for std::borrow::Cow<'a, T> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Cow<'_, str> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Cow<'_, str> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        <String as serde::Deserialize>::deserialize(deserializer).map(Cow::from_owned)
+    }
+}
+
 impl From<String> for Cow<'_, str> {
     #[inline]
     fn from(s: String) -> Self {
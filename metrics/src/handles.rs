@@ -1,6 +1,39 @@
-use std::sync::Arc;
+use alloc::sync::Arc;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
 
-use crate::IntoF64;
+use crate::{IntoF64, IntoLabels, Label};
+
+/// A callback which provides the current value of a gauge.
+///
+/// Created via [`Gauge::set_callback`], and handed to the gauge handler so that recorders can invoke
+/// it whenever they collect the value of the gauge.
+#[derive(Clone)]
+pub struct GaugeCallback {
+    inner: Arc<dyn Fn() -> f64 + Send + Sync>,
+}
+
+impl GaugeCallback {
+    /// Creates a new `GaugeCallback` from the given closure.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn() -> f64 + Send + Sync + 'static,
+    {
+        Self { inner: Arc::new(f) }
+    }
+
+    /// Invokes the callback, returning the current value of the gauge.
+    pub fn value(&self) -> f64 {
+        (self.inner)()
+    }
+}
+
+impl core::fmt::Debug for GaugeCallback {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GaugeCallback").finish_non_exhaustive()
+    }
+}
 
 /// A counter handler.
 pub trait CounterFn {
@@ -18,6 +51,27 @@ pub trait CounterFn {
     /// This method must cope with those cases.  An example of doing so atomically can be found in
     /// `AtomicCounter`.
     fn absolute(&self, value: u64);
+
+    /// Increments the counter by the given amount, attaching an exemplar to the increment.
+    ///
+    /// Exemplars are a set of labels, such as a trace ID, which identify a specific occurrence of
+    /// the event being counted.  Recorders which support exemplars can expose them alongside the
+    /// counter, while the default implementation ignores the exemplar and increments the counter.
+    fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+        let _ = exemplar;
+        self.increment(value)
+    }
+
+    /// Whether or not the metric backing this counter has expired.
+    ///
+    /// Recorders which remove idle metrics, such as those built on the `Recency` type from
+    /// `metrics-util`, can return `true` once the metric has been removed, as updates made through
+    /// this handle are no longer observed from then on.
+    ///
+    /// By default, this always returns `false`.
+    fn is_expired(&self) -> bool {
+        false
+    }
 }
 
 /// A gauge handler.
@@ -30,12 +84,105 @@ pub trait GaugeFn {
 
     /// Sets the gauge to the given amount.
     fn set(&self, value: f64);
+
+    /// Sets the gauge to the given amount if it is greater than the current value.
+    ///
+    /// This is intended to support tracking high watermarks, such as the peak depth of a queue, from
+    /// multiple callers at once.  As with [`CounterFn::absolute`], implementations must update the
+    /// gauge without racing against other callers, such that the gauge always ends up holding the
+    /// largest value it was given.  An example of doing so atomically can be found in the
+    /// implementation for `AtomicU64`.
+    ///
+    /// By default, this does nothing, as handlers have no way to read the current value of the gauge
+    /// in general, and setting it unconditionally could overwrite a higher watermark.  Handlers
+    /// which support watermarks must override this.
+    fn set_max(&self, value: f64) {
+        let _ = value;
+    }
+
+    /// Sets the gauge to the given amount if it is less than the current value.
+    ///
+    /// This is the counterpart to [`GaugeFn::set_max`], for tracking low watermarks, and comes with
+    /// the same requirements.  By default, this likewise does nothing.
+    fn set_min(&self, value: f64) {
+        let _ = value;
+    }
+
+    /// Backs the gauge with the given callback.
+    ///
+    /// Recorders which support callbacks should invoke the callback whenever they collect the
+    /// current value of the gauge, such as when rendering or pushing metrics, instead of using the
+    /// last value the gauge was set to.  Setting a new callback replaces the previous one.
+    ///
+    /// By default, the gauge is set to the current value of the callback once, as recorders which
+    /// don't support callbacks have no way to invoke it later on.
+    fn set_callback(&self, callback: GaugeCallback) {
+        self.set(callback.value())
+    }
+
+    /// Whether or not the metric backing this gauge has expired.
+    ///
+    /// Recorders which remove idle metrics, such as those built on the `Recency` type from
+    /// `metrics-util`, can return `true` once the metric has been removed, as updates made through
+    /// this handle are no longer observed from then on.
+    ///
+    /// By default, this always returns `false`.
+    fn is_expired(&self) -> bool {
+        false
+    }
 }
 
 /// A histogram handler.
 pub trait HistogramFn {
     /// Records a value into the histogram.
     fn record(&self, value: f64);
+
+    /// Records a value into the histogram, attaching an exemplar to the observation.
+    ///
+    /// Exemplars are a set of labels, such as a trace ID, which identify a specific observation.
+    /// Recorders which support exemplars can expose them alongside the histogram, while the default
+    /// implementation ignores the exemplar and records the value.
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        let _ = exemplar;
+        self.record(value)
+    }
+
+    /// Records a duration into the histogram.
+    ///
+    /// The default implementation records the duration in seconds.  Recorders which support the
+    /// [`TimeBase`](crate::TimeBase) attribute can override this to record durations in the time
+    /// base described for the histogram instead.
+    fn record_duration(&self, duration: Duration) {
+        self.record(duration.as_secs_f64())
+    }
+
+    /// Records multiple values into the histogram.
+    ///
+    /// The default implementation records each value individually.  Recorders which can store a
+    /// batch of values more efficiently than one at a time, such as by taking a lock or reading the
+    /// clock only once for the whole batch, can override this.
+    fn record_many(&self, values: &[f64]) {
+        for &value in values {
+            self.record(value);
+        }
+    }
+
+    /// Whether or not the metric backing this histogram has expired.
+    ///
+    /// Recorders which remove idle metrics, such as those built on the `Recency` type from
+    /// `metrics-util`, can return `true` once the metric has been removed, as updates made through
+    /// this handle are no longer observed from then on.
+    ///
+    /// By default, this always returns `false`.
+    fn is_expired(&self) -> bool {
+        false
+    }
+}
+
+/// A summary handler.
+pub trait SummaryFn {
+    /// Records a value into the summary.
+    fn record(&self, value: f64);
 }
 
 /// A counter.
@@ -59,12 +206,19 @@ pub struct Histogram {
     inner: Option<Arc<dyn HistogramFn + Send + Sync>>,
 }
 
+/// A summary.
+#[derive(Clone)]
+#[must_use = "summaries do nothing unless you use them"]
+pub struct Summary {
+    inner: Option<Arc<dyn SummaryFn + Send + Sync>>,
+}
+
 impl Counter {
     /// Creates a no-op `Counter` which does nothing.
     ///
     /// Suitable when a handle must be provided that does nothing i.e. a no-op recorder or a layer
     /// that disables specific metrics, and so on.
-    pub fn noop() -> Self {
+    pub const fn noop() -> Self {
         Self { inner: None }
     }
 
@@ -86,6 +240,34 @@ impl Counter {
             c.absolute(value)
         }
     }
+
+    /// Increments the counter, attaching the given exemplar to the increment.
+    ///
+    /// Exemplars are ignored by recorders which don't support them.
+    pub fn increment_with_exemplar<L: IntoLabels>(&self, value: u64, exemplar: L) {
+        if let Some(c) = &self.inner {
+            c.increment_with_exemplar(value, &exemplar.into_labels())
+        }
+    }
+
+    /// Increments the counter by each of the given amounts.
+    ///
+    /// The amounts are summed up front, saturating on overflow, so that the counter is only
+    /// incremented once for the whole batch.
+    pub fn increment_many(&self, values: &[u64]) {
+        if let Some(c) = &self.inner {
+            c.increment(values.iter().fold(0, |sum, value| sum.saturating_add(*value)))
+        }
+    }
+
+    /// Returns `true` if the metric backing this counter has expired.
+    ///
+    /// Updates made through an expired counter are no longer observed by the recorder, so long-lived
+    /// handles, such as those cached by a library, should be registered again once they expire.
+    /// No-op counters never expire.
+    pub fn is_expired(&self) -> bool {
+        self.inner.as_ref().map_or(false, |c| c.is_expired())
+    }
 }
 
 impl Gauge {
@@ -93,7 +275,7 @@ impl Gauge {
     ///
     /// Suitable when a handle must be provided that does nothing i.e. a no-op recorder or a layer
     /// that disables specific metrics, and so on.
-    pub fn noop() -> Self {
+    pub const fn noop() -> Self {
         Self { inner: None }
     }
 
@@ -122,6 +304,52 @@ impl Gauge {
             g.set(value.into_f64())
         }
     }
+
+    /// Sets the gauge to the given value if it is greater than the current value.
+    pub fn set_max<T: IntoF64>(&self, value: T) {
+        if let Some(g) = &self.inner {
+            g.set_max(value.into_f64())
+        }
+    }
+
+    /// Sets the gauge to the given value if it is less than the current value.
+    pub fn set_min<T: IntoF64>(&self, value: T) {
+        if let Some(g) = &self.inner {
+            g.set_min(value.into_f64())
+        }
+    }
+
+    /// Backs the gauge with the given callback.
+    ///
+    /// The callback is invoked by the recorder whenever it collects the current value of the gauge,
+    /// which suits values like the depth of a queue or the size of a cache that are cheap to read
+    /// on demand but would otherwise need a background task to keep the gauge up to date.
+    ///
+    /// Recorders which don't support callbacks set the gauge to the current value of the callback
+    /// once instead.
+    pub fn set_callback<F>(&self, callback: F)
+    where
+        F: Fn() -> f64 + Send + Sync + 'static,
+    {
+        if let Some(g) = &self.inner {
+            g.set_callback(GaugeCallback::new(callback))
+        }
+    }
+
+    /// Increments the gauge, returning a guard which decrements it again when dropped.
+    pub fn guard(&self) -> GaugeGuard {
+        self.increment(1.0);
+        GaugeGuard { gauge: self.clone() }
+    }
+
+    /// Returns `true` if the metric backing this gauge has expired.
+    ///
+    /// Updates made through an expired gauge are no longer observed by the recorder, so long-lived
+    /// handles, such as those cached by a library, should be registered again once they expire.
+    /// No-op gauges never expire.
+    pub fn is_expired(&self) -> bool {
+        self.inner.as_ref().map_or(false, |g| g.is_expired())
+    }
 }
 
 impl Histogram {
@@ -129,7 +357,7 @@ impl Histogram {
     ///
     /// Suitable when a handle must be provided that does nothing i.e. a no-op recorder or a layer
     /// that disables specific metrics, and so on.
-    pub fn noop() -> Self {
+    pub const fn noop() -> Self {
         Self { inner: None }
     }
 
@@ -139,6 +367,68 @@ impl Histogram {
     }
 
     /// Records a value in the histogram.
+    pub fn record<T: IntoF64>(&self, value: T) {
+        if let Some(ref inner) = self.inner {
+            value.record_into(&**inner)
+        }
+    }
+
+    /// Records a value in the histogram, attaching the given exemplar to the observation.
+    ///
+    /// Exemplars are ignored by recorders which don't support them.
+    pub fn record_with_exemplar<T: IntoF64, L: IntoLabels>(&self, value: T, exemplar: L) {
+        if let Some(ref inner) = self.inner {
+            inner.record_with_exemplar(value.into_f64(), &exemplar.into_labels())
+        }
+    }
+
+    /// Records a duration in the histogram.
+    ///
+    /// Durations are recorded in seconds, unless the recorder supports the
+    /// [`TimeBase`](crate::TimeBase) attribute and a different time base was described for this
+    /// histogram.  Recording a [`Duration`] via [`Histogram::record`] is equivalent.
+    pub fn record_duration(&self, duration: Duration) {
+        if let Some(ref inner) = self.inner {
+            inner.record_duration(duration)
+        }
+    }
+
+    /// Records multiple values in the histogram.
+    ///
+    /// This is equivalent to recording each value individually, but allows recorders to handle the
+    /// whole batch at once, which is useful when samples have already been collected locally, such
+    /// as the timings of each step of a request.
+    pub fn record_many(&self, values: &[f64]) {
+        if let Some(ref inner) = self.inner {
+            inner.record_many(values)
+        }
+    }
+
+    /// Returns `true` if the metric backing this histogram has expired.
+    ///
+    /// Updates made through an expired histogram are no longer observed by the recorder, so long-lived
+    /// handles, such as those cached by a library, should be registered again once they expire.
+    /// No-op histograms never expire.
+    pub fn is_expired(&self) -> bool {
+        self.inner.as_ref().map_or(false, |inner| inner.is_expired())
+    }
+}
+
+impl Summary {
+    /// Creates a no-op `Summary` which does nothing.
+    ///
+    /// Suitable when a handle must be provided that does nothing i.e. a no-op recorder or a layer
+    /// that disables specific metrics, and so on.
+    pub const fn noop() -> Self {
+        Self { inner: None }
+    }
+
+    /// Creates a `Summary` based on a shared handler.
+    pub fn from_arc<F: SummaryFn + Send + Sync + 'static>(a: Arc<F>) -> Self {
+        Self { inner: Some(a) }
+    }
+
+    /// Records a value in the summary.
     pub fn record<T: IntoF64>(&self, value: T) {
         if let Some(ref inner) = self.inner {
             inner.record(value.into_f64())
@@ -146,6 +436,143 @@ impl Histogram {
     }
 }
 
+/// An absolute counter.
+///
+/// Absolute counters mirror a cumulative counter maintained elsewhere, such as a counter read from
+/// `/proc` or reported by a dependency, by setting the underlying [`Counter`] to its latest value,
+/// rather than incrementing it.
+#[derive(Clone)]
+#[must_use = "absolute counters do nothing unless you use them"]
+pub struct AbsoluteCounter {
+    counter: Counter,
+}
+
+impl AbsoluteCounter {
+    /// Creates a no-op `AbsoluteCounter` which does nothing.
+    ///
+    /// Suitable when a handle must be provided that does nothing i.e. a no-op recorder or a layer
+    /// that disables specific metrics, and so on.
+    pub fn noop() -> Self {
+        Self { counter: Counter::noop() }
+    }
+
+    /// Creates an `AbsoluteCounter` which sets the given counter.
+    pub fn from_counter(counter: Counter) -> Self {
+        Self { counter }
+    }
+
+    /// Sets the counter to the given value.
+    ///
+    /// As with [`Counter::absolute`], the counter is only updated if the value is greater than its
+    /// current value, so that updates from multiple callers arriving out of order can never move the
+    /// counter backwards.
+    pub fn set(&self, value: u64) {
+        self.counter.absolute(value)
+    }
+}
+
+/// A timer.
+///
+/// Timers measure the duration of operations, recording the time elapsed between starting and
+/// stopping them, in seconds, into an underlying [`Histogram`].
+#[derive(Clone)]
+#[must_use = "timers do nothing unless you use them"]
+pub struct Timer {
+    histogram: Histogram,
+}
+
+/// A running measurement of a [`Timer`].
+///
+/// The elapsed time is recorded when the guard is stopped, via [`TimerGuard::stop`], or when it is
+/// dropped, whichever comes first.  A measurement can be discarded, without recording anything,
+/// via [`TimerGuard::cancel`].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[must_use = "dropping a timer guard immediately records the elapsed time"]
+pub struct TimerGuard {
+    histogram: Histogram,
+    start: Instant,
+    recorded: bool,
+}
+
+impl Timer {
+    /// Creates a no-op `Timer` which does nothing.
+    ///
+    /// Suitable when a handle must be provided that does nothing i.e. a no-op recorder or a layer
+    /// that disables specific metrics, and so on.
+    pub fn noop() -> Self {
+        Self { histogram: Histogram::noop() }
+    }
+
+    /// Creates a `Timer` which records durations into the given histogram.
+    pub fn from_histogram(histogram: Histogram) -> Self {
+        Self { histogram }
+    }
+
+    /// Starts measuring a new duration.
+    ///
+    /// The elapsed time is recorded once the returned guard is stopped or dropped.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn start(&self) -> TimerGuard {
+        TimerGuard { histogram: self.histogram.clone(), start: Instant::now(), recorded: false }
+    }
+
+    /// Records a duration that was measured elsewhere.
+    pub fn record(&self, duration: Duration) {
+        self.histogram.record_duration(duration)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TimerGuard {
+    /// Gets the time elapsed since the measurement was started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Stops the measurement, recording the elapsed time.
+    ///
+    /// Returns the elapsed time that was recorded.
+    pub fn stop(mut self) -> Duration {
+        let elapsed = self.start.elapsed();
+        self.histogram.record_duration(elapsed);
+        self.recorded = true;
+        elapsed
+    }
+
+    /// Stops the measurement without recording the elapsed time.
+    pub fn cancel(mut self) {
+        self.recorded = true;
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for TimerGuard {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.histogram.record_duration(self.start.elapsed());
+        }
+    }
+}
+
+/// A guard which holds a gauge incremented for as long as it lives.
+///
+/// The gauge is incremented by one when the guard is created, via [`Gauge::guard`], and decremented
+/// by one when the guard is dropped, including when unwinding from a panic.  This makes gauges which
+/// track the number of in-flight operations, such as requests being handled, correct by
+/// construction.
+#[must_use = "dropping a gauge guard immediately decrements the gauge"]
+pub struct GaugeGuard {
+    gauge: Gauge,
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.gauge.decrement(1.0);
+    }
+}
+
 impl<T> CounterFn for Arc<T>
 where
     T: CounterFn,
@@ -157,6 +584,14 @@ where
     fn absolute(&self, value: u64) {
         (**self).absolute(value)
     }
+
+    fn increment_with_exemplar(&self, value: u64, exemplar: &[Label]) {
+        (**self).increment_with_exemplar(value, exemplar)
+    }
+
+    fn is_expired(&self) -> bool {
+        (**self).is_expired()
+    }
 }
 impl<T> GaugeFn for Arc<T>
 where
@@ -173,11 +608,52 @@ where
     fn set(&self, value: f64) {
         (**self).set(value)
     }
+
+    fn set_max(&self, value: f64) {
+        (**self).set_max(value)
+    }
+
+    fn set_min(&self, value: f64) {
+        (**self).set_min(value)
+    }
+
+    fn set_callback(&self, callback: GaugeCallback) {
+        (**self).set_callback(callback)
+    }
+
+    fn is_expired(&self) -> bool {
+        (**self).is_expired()
+    }
 }
 
 impl<T> HistogramFn for Arc<T>
 where
     T: HistogramFn,
+{
+    fn record(&self, value: f64) {
+        (**self).record(value);
+    }
+
+    fn record_with_exemplar(&self, value: f64, exemplar: &[Label]) {
+        (**self).record_with_exemplar(value, exemplar);
+    }
+
+    fn record_duration(&self, duration: Duration) {
+        (**self).record_duration(duration);
+    }
+
+    fn record_many(&self, values: &[f64]) {
+        (**self).record_many(values);
+    }
+
+    fn is_expired(&self) -> bool {
+        (**self).is_expired()
+    }
+}
+
+impl<T> SummaryFn for Arc<T>
+where
+    T: SummaryFn,
 {
     fn record(&self, value: f64) {
         (**self).record(value);
@@ -210,3 +686,71 @@ where
         Histogram::from_arc(inner)
     }
 }
+
+impl<T> From<Arc<T>> for Summary
+where
+    T: SummaryFn + Send + Sync + 'static,
+{
+    fn from(inner: Arc<T>) -> Self {
+        Summary::from_arc(inner)
+    }
+}
+
+impl From<Counter> for AbsoluteCounter {
+    fn from(counter: Counter) -> Self {
+        AbsoluteCounter::from_counter(counter)
+    }
+}
+
+impl From<Histogram> for Timer {
+    fn from(histogram: Histogram) -> Self {
+        Timer::from_histogram(histogram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::{Counter, Gauge};
+    use crate::atomics::AtomicU64;
+
+    #[test]
+    fn counter_increment_many_saturates() {
+        let value = Arc::new(AtomicU64::new(0));
+        let counter = Counter::from_arc(Arc::clone(&value));
+
+        counter.increment_many(&[1, 2, 3]);
+        assert_eq!(value.load(Ordering::Acquire), 6);
+
+        value.store(0, Ordering::Release);
+        counter.increment_many(&[u64::MAX, 1]);
+        assert_eq!(value.load(Ordering::Acquire), u64::MAX);
+    }
+
+    #[test]
+    fn gauge_guard_decrements_on_drop() {
+        let value = Arc::new(AtomicU64::new(0));
+        let gauge = Gauge::from_arc(Arc::clone(&value));
+        let current = || f64::from_bits(value.load(Ordering::Acquire));
+
+        let outer = gauge.guard();
+        {
+            let _inner = gauge.guard();
+            assert_eq!(current(), 2.0);
+        }
+        assert_eq!(current(), 1.0);
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _guard = gauge.guard();
+            panic!("request failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(current(), 1.0);
+
+        drop(outer);
+        assert_eq!(current(), 0.0);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::SharedString;
+
+static GLOBAL_POOL: OnceLock<StringPool> = OnceLock::new();
+
+/// Statistics about the strings held by a [`StringPool`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Number of distinct strings in the pool.
+    pub entries: usize,
+
+    /// Total length, in bytes, of the distinct strings in the pool.
+    pub bytes: usize,
+
+    /// Number of times a string was interned which was already in the pool.
+    pub hits: u64,
+
+    /// Number of times a string was interned which was not yet in the pool.
+    pub misses: u64,
+}
+
+#[derive(Default)]
+struct PoolInner {
+    strings: HashSet<Arc<str>>,
+    stats: PoolStats,
+}
+
+/// A pool of interned strings.
+///
+/// Metric names and label values built at runtime, such as from a request path or a tenant ID,
+/// tend to repeat heavily, yet each one normally requires allocating a fresh `String`.  Interning
+/// a string returns a [`SharedString`] pointing at a single, shared copy of it, so that only the
+/// first occurrence of any given string allocates.
+///
+/// Strings are never removed from the pool, so interning should be reserved for strings drawn from
+/// a bounded set of values.
+///
+/// A process-wide pool is available via [`StringPool::global`], which is used by
+/// [`KeyName::intern`](crate::KeyName::intern) and [`Label::intern`](crate::Label::intern), but
+/// recorders and libraries can also create their own pools.
+#[derive(Default)]
+pub struct StringPool {
+    inner: Mutex<PoolInner>,
+}
+
+impl StringPool {
+    /// Creates an empty `StringPool`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets a reference to the global pool.
+    pub fn global() -> &'static StringPool {
+        GLOBAL_POOL.get_or_init(StringPool::new)
+    }
+
+    /// Interns the given string, returning a shared copy of it.
+    pub fn intern(&self, s: &str) -> SharedString {
+        // The pool is never left in an inconsistent state, so we can safely ignore poisoning.
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(existing) = inner.strings.get(s) {
+            let existing = Arc::clone(existing);
+            inner.stats.hits += 1;
+            return SharedString::from_shared(existing);
+        }
+
+        let interned: Arc<str> = Arc::from(s);
+        inner.strings.insert(Arc::clone(&interned));
+        inner.stats.entries += 1;
+        inner.stats.bytes += s.len();
+        inner.stats.misses += 1;
+        SharedString::from_shared(interned)
+    }
+
+    /// Gets statistics about the strings held by this pool.
+    pub fn stats(&self) -> PoolStats {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PoolStats, StringPool};
+    use crate::{KeyName, Label};
+
+    #[test]
+    fn test_intern() {
+        let pool = StringPool::new();
+
+        let first = pool.intern("http_requests_total");
+        let second = pool.intern("http_requests_total");
+        let other = pool.intern("db_queries_total");
+
+        assert_eq!(first, second);
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        assert_ne!(first, other);
+        assert_eq!(pool.stats(), PoolStats { entries: 2, bytes: 35, hits: 1, misses: 2 });
+    }
+
+    #[test]
+    fn test_intern_global() {
+        let first = KeyName::intern("intern_test_global_name");
+        let second = KeyName::intern("intern_test_global_name");
+        assert_eq!(first.as_str().as_ptr(), second.as_str().as_ptr());
+
+        let label = Label::intern("intern_test_key", "intern_test_value");
+        assert_eq!(label, Label::new("intern_test_key", "intern_test_value"));
+        assert!(StringPool::global().stats().hits >= 1);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{atomics::AtomicU64, cow::Cow, IntoLabels, KeyHasher, Label, SharedString};
-use std::{
+use alloc::vec::Vec;
+use core::{
     borrow::Borrow,
     cmp, fmt,
     hash::{Hash, Hasher},
@@ -11,6 +12,7 @@ const NO_LABELS: [Label; 0] = [];
 
 /// Name component of a key.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct KeyName(SharedString);
 
 impl KeyName {
@@ -19,6 +21,16 @@ impl KeyName {
         KeyName(SharedString::const_str(name))
     }
 
+    /// Creates a `KeyName` from a string interned in the global [`StringPool`](crate::StringPool).
+    ///
+    /// Only the first call for any given name allocates, which avoids allocating a fresh `String`
+    /// for names which are built dynamically but repeat heavily.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn intern(name: &str) -> Self {
+        KeyName(crate::StringPool::global().intern(name))
+    }
+
     /// Gets a reference to the strin used for this name.
     pub fn as_str(&self) -> &str {
         &self.0
@@ -40,6 +52,52 @@ impl Borrow<str> for KeyName {
     }
 }
 
+/// Checks a metric name, given as the tokens it was written with, against the naming policy,
+/// panicking if it doesn't follow it.
+///
+/// Names must be non-empty, must not start with a digit, and may only contain ASCII letters,
+/// digits, `_`, `.`, and `:`.  This is called by the emission macros in a constant context, so that
+/// invalid literal names are reported as compile errors rather than being rejected later on by an
+/// exporter.  The macros forward names to each other as expressions, which can't be matched as
+/// literals anymore, so names are checked by their stringified tokens instead.  Only plain string
+/// literals without escape sequences are checked, as any other name is only known at runtime.
+#[doc(hidden)]
+pub const fn validate_name_tokens(tokens: &str) {
+    let bytes = tokens.as_bytes();
+    let len = bytes.len();
+    if len < 2 || bytes[0] != b'"' || bytes[len - 1] != b'"' {
+        return;
+    }
+
+    let mut i = 1;
+    while i < len - 1 {
+        if bytes[i] == b'\\' {
+            return;
+        }
+        i += 1;
+    }
+
+    validate_name_bytes(bytes, 1, len - 1);
+}
+
+const fn validate_name_bytes(bytes: &[u8], start: usize, end: usize) {
+    if start == end {
+        panic!("metric names must not be empty");
+    }
+    if bytes[start].is_ascii_digit() {
+        panic!("metric names must not start with a digit");
+    }
+
+    let mut i = start;
+    while i < end {
+        let b = bytes[i];
+        if !(b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || b == b':') {
+            panic!("metric names may only contain ASCII letters, digits, `_`, `.`, and `:`");
+        }
+        i += 1;
+    }
+}
+
 /// A metric identifier.
 ///
 /// A key represents both the name and labels of a metric.
@@ -83,7 +141,10 @@ impl Key {
         L: IntoLabels,
     {
         let name = name.into();
-        let labels = Cow::from_owned(labels.into_labels());
+        let labels = match labels.static_labels() {
+            Some(labels) => Cow::const_slice(labels),
+            None => Cow::from_owned(labels.into_labels()),
+        };
 
         Self::builder(name, labels)
     }
@@ -154,6 +215,35 @@ impl Key {
         Self::builder(name, labels.into())
     }
 
+    /// Returns `true` if the labels of this key are in canonical form.
+    ///
+    /// Labels are in canonical form when they are sorted by key, and no two labels share a key.
+    pub fn is_canonical(&self) -> bool {
+        self.labels.windows(2).all(|pair| pair[0].key() < pair[1].key())
+    }
+
+    /// Clones this [`Key`], putting its labels into canonical form.
+    ///
+    /// Labels are sorted by key, and when multiple labels share a key, only the last one is kept.
+    /// Keys with the same name and the same set of labels are equal, and hash identically, once
+    /// canonicalized, regardless of the order their labels were originally given in.
+    pub fn canonicalize(&self) -> Self {
+        if self.is_canonical() {
+            return self.clone();
+        }
+
+        let mut labels = self.labels.clone().into_owned();
+        labels.sort_by(|a, b| a.key().cmp(b.key()));
+
+        // Sorting is stable, so reversing the labels puts the last label given for each key first,
+        // which is the one that deduplication keeps.
+        labels.reverse();
+        labels.dedup_by(|a, b| a.key() == b.key());
+        labels.reverse();
+
+        Self::builder(self.name.clone(), labels.into())
+    }
+
     /// Gets the hash value for this key.
     pub fn get_hash(&self) -> u64 {
         if self.hashed.load(Ordering::Acquire) {
@@ -215,6 +305,39 @@ impl Hash for Key {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Key {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Key", 2)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("labels", &*self.labels)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Key {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "Key")]
+        struct Parts {
+            name: KeyName,
+            labels: Vec<Label>,
+        }
+
+        let Parts { name, labels } = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Key::from_parts(name, labels))
+    }
+}
+
 impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.labels.is_empty() {
@@ -224,10 +347,10 @@ impl fmt::Display for Key {
             let mut first = true;
             for label in self.labels.as_ref() {
                 if first {
-                    write!(f, "{} = {}", label.0, label.1)?;
+                    write!(f, "{} = {}", label.key(), label.value())?;
                     first = false;
                 } else {
-                    write!(f, ", {} = {}", label.0, label.1)?;
+                    write!(f, ", {} = {}", label.key(), label.value())?;
                 }
             }
             write!(f, "])")
@@ -256,29 +379,23 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::Key;
+    use super::{validate_name_tokens, Key};
     use crate::{KeyName, Label};
     use std::{collections::HashMap, ops::Deref, sync::Arc};
 
-    static BORROWED_NAME: &'static str = "name";
-    static FOOBAR_NAME: &'static str = "foobar";
-    static BORROWED_BASIC: Key = Key::from_static_name(&BORROWED_NAME);
+    static BORROWED_NAME: &str = "name";
+    static FOOBAR_NAME: &str = "foobar";
+    static BORROWED_BASIC: Key = Key::from_static_name(BORROWED_NAME);
     static LABELS: [Label; 1] = [Label::from_static_parts("key", "value")];
-    static BORROWED_LABELS: Key = Key::from_static_parts(&BORROWED_NAME, &LABELS);
+    static BORROWED_LABELS: Key = Key::from_static_parts(BORROWED_NAME, &LABELS);
 
     #[test]
     fn test_key_ord_and_partialord() {
-        let keys_expected: Vec<Key> = vec![
-            Key::from_name("aaaa").into(),
-            Key::from_name("bbbb").into(),
-            Key::from_name("cccc").into(),
-        ];
-
-        let keys_unsorted: Vec<Key> = vec![
-            Key::from_name("bbbb").into(),
-            Key::from_name("cccc").into(),
-            Key::from_name("aaaa").into(),
-        ];
+        let keys_expected: Vec<Key> =
+            vec![Key::from_name("aaaa"), Key::from_name("bbbb"), Key::from_name("cccc")];
+
+        let keys_unsorted: Vec<Key> =
+            vec![Key::from_name("bbbb"), Key::from_name("cccc"), Key::from_name("aaaa")];
 
         let keys = {
             let mut keys = keys_unsorted.clone();
@@ -288,7 +405,7 @@ mod tests {
         assert_eq!(keys, keys_expected);
 
         let keys = {
-            let mut keys = keys_unsorted.clone();
+            let mut keys = keys_unsorted;
             keys.sort_by(|a, b| a.partial_cmp(b).unwrap());
             keys
         };
@@ -296,10 +413,11 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::mutable_key_type)]
     fn test_key_eq_and_hash() {
         let mut keys = HashMap::new();
 
-        let owned_basic: Key = Key::from_name("name").into();
+        let owned_basic: Key = Key::from_name("name");
         assert_eq!(&owned_basic, &BORROWED_BASIC);
 
         let previous = keys.insert(owned_basic, 42);
@@ -309,7 +427,7 @@ mod tests {
         assert_eq!(previous, Some(&42));
 
         let labels = LABELS.to_vec();
-        let owned_labels = Key::from_parts(&BORROWED_NAME[..], labels);
+        let owned_labels = Key::from_parts(BORROWED_NAME, labels);
         assert_eq!(&owned_labels, &BORROWED_LABELS);
 
         let previous = keys.insert(owned_labels, 43);
@@ -319,8 +437,7 @@ mod tests {
         assert_eq!(previous, Some(&43));
 
         let basic: Key = "constant_key".into();
-        let cloned_basic = basic.clone();
-        assert_eq!(basic, cloned_basic);
+        assert_eq!(basic.clone(), basic);
     }
 
     #[test]
@@ -329,19 +446,19 @@ mod tests {
         let result1 = key1.to_string();
         assert_eq!(result1, "Key(foobar)");
 
-        let key2 = Key::from_parts(&FOOBAR_NAME[..], vec![Label::new("system", "http")]);
+        let key2 = Key::from_parts(FOOBAR_NAME, vec![Label::new("system", "http")]);
         let result2 = key2.to_string();
         assert_eq!(result2, "Key(foobar, [system = http])");
 
         let key3 = Key::from_parts(
-            &FOOBAR_NAME[..],
+            FOOBAR_NAME,
             vec![Label::new("system", "http"), Label::new("user", "joe")],
         );
         let result3 = key3.to_string();
         assert_eq!(result3, "Key(foobar, [system = http, user = joe])");
 
         let key4 = Key::from_parts(
-            &FOOBAR_NAME[..],
+            FOOBAR_NAME,
             vec![
                 Label::new("black", "black"),
                 Label::new("lives", "lives"),
@@ -354,7 +471,7 @@ mod tests {
 
     #[test]
     fn test_key_name_equality() {
-        static KEY_NAME: &'static str = "key_name";
+        static KEY_NAME: &str = "key_name";
 
         let borrowed_const = KeyName::from_const_str(KEY_NAME);
         let borrowed_nonconst = KeyName::from(KEY_NAME);
@@ -395,4 +512,97 @@ mod tests {
         drop(shared);
         assert_eq!(shared_weak.strong_count(), 0);
     }
+
+    #[test]
+    fn test_key_canonicalize() {
+        let key = Key::from_parts(
+            "foobar",
+            vec![
+                Label::new("user", "joe"),
+                Label::new("system", "http"),
+                Label::new("user", "jane"),
+                Label::new("region", "eu"),
+            ],
+        );
+        assert!(!key.is_canonical());
+
+        let canonical = key.canonicalize();
+        assert!(canonical.is_canonical());
+        assert_eq!(
+            canonical.labels().cloned().collect::<Vec<_>>(),
+            vec![
+                Label::new("region", "eu"),
+                Label::new("system", "http"),
+                Label::new("user", "jane"),
+            ]
+        );
+
+        let reordered = Key::from_parts(
+            "foobar",
+            vec![
+                Label::new("user", "jane"),
+                Label::new("region", "eu"),
+                Label::new("system", "http"),
+            ],
+        );
+        assert_ne!(reordered, canonical);
+        assert_eq!(reordered.canonicalize(), canonical);
+        assert_eq!(reordered.canonicalize().get_hash(), canonical.get_hash());
+    }
+
+    #[test]
+    fn test_validate_name() {
+        validate_name_tokens(r#""requests_total""#);
+        validate_name_tokens(r#""http.server:duration_seconds""#);
+        validate_name_tokens(r#""_private""#);
+    }
+
+    #[test]
+    #[should_panic(expected = "metric names must not be empty")]
+    fn test_validate_name_empty() {
+        validate_name_tokens(r#""""#);
+    }
+
+    #[test]
+    #[should_panic(expected = "metric names must not start with a digit")]
+    fn test_validate_name_leading_digit() {
+        validate_name_tokens(r#""5xx_responses""#);
+    }
+
+    #[test]
+    #[should_panic(expected = "metric names may only contain")]
+    fn test_validate_name_invalid_character() {
+        validate_name_tokens(r#""requests total""#);
+    }
+
+    #[test]
+    fn test_validate_name_unchecked_tokens() {
+        validate_name_tokens(r#"format!("{} total", "requests")"#);
+        validate_name_tokens(r#""requests\x20total""#);
+        validate_name_tokens("REQUESTS_TOTAL");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let key = Key::from_parts(
+            "requests",
+            vec![
+                Label::new("method", "GET"),
+                Label::typed("status", 200),
+                Label::typed("ok", true),
+            ],
+        );
+
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(
+            json,
+            r#"{"name":"requests","labels":[["method","GET"],["status",200],["ok",true]]}"#
+        );
+
+        let roundtripped: Key = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtripped, key);
+        let kinds = roundtripped.labels().map(Label::kind).collect::<Vec<_>>();
+        assert_eq!(kinds, key.labels().map(Label::kind).collect::<Vec<_>>());
+    }
 }
@@ -1,7 +1,271 @@
-use std::slice::Iter;
+use alloc::{boxed::Box, string::String, string::ToString, sync::Arc, vec::Vec};
+use core::{
+    cell::Cell,
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    hash::{Hash, Hasher},
+    mem, ptr,
+    slice::Iter,
+    sync::atomic::{self, AtomicPtr},
+};
 
 use crate::SharedString;
 
+/// The type of a label value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LabelKind {
+    /// A string.
+    String,
+
+    /// A signed integer.
+    Int,
+
+    /// A floating-point number.
+    Float,
+
+    /// A boolean.
+    Bool,
+}
+
+/// A label value, in its original type.
+///
+/// Returned by [`Label::typed_value`], allowing exporters which support typed label values, or
+/// attributes, to render them natively.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LabelValue<'a> {
+    /// A string.
+    String(&'a str),
+
+    /// A signed integer.
+    Int(i64),
+
+    /// A floating-point number.
+    Float(f64),
+
+    /// A boolean.
+    Bool(bool),
+}
+
+/// A value that can be used as the value of a [`Label`].
+///
+/// Implemented for all types that can be converted to a [`SharedString`], as well as for integers,
+/// floating-point numbers, and booleans, which keep their type.
+pub trait IntoLabelValue {
+    /// Converts this value into a [`Label`] with the given key.
+    fn into_label(self, key: SharedString) -> Label;
+}
+
+impl<T> IntoLabelValue for T
+where
+    T: Into<SharedString>,
+{
+    fn into_label(self, key: SharedString) -> Label {
+        Label::new(key, self)
+    }
+}
+
+macro_rules! into_label_value {
+    ($variant:ident($inner:ty): $($ty:ty),*) => {
+        $(
+            impl IntoLabelValue for $ty {
+                fn into_label(self, key: SharedString) -> Label {
+                    Label(key, Value::typed(Typed::$variant(<$inner>::from(self))))
+                }
+            }
+        )*
+    };
+}
+
+into_label_value!(Int(i64): i8, i16, i32, i64, u8, u16, u32);
+into_label_value!(Float(f64): f64);
+into_label_value!(Float32(f32): f32);
+into_label_value!(Bool(bool): bool);
+
+impl IntoLabelValue for u64 {
+    /// Values which don't fit in an `i64` are kept as strings.
+    fn into_label(self, key: SharedString) -> Label {
+        Label(key, Value::from_u64(self))
+    }
+}
+
+impl IntoLabelValue for usize {
+    /// Values which don't fit in an `i64` are kept as strings.
+    fn into_label(self, key: SharedString) -> Label {
+        (self as u64).into_label(key)
+    }
+}
+
+/// A label value given to the registration macros, which may be a closure.
+///
+/// The registration macros resolve label values through this, so that a closure returning a label
+/// value is called only once the key of the metric is actually built, which happens after the
+/// recorder has said that the metric is [enabled](crate::Recorder::enabled).  Closures are picked
+/// up via [`ResolveLazyLabel`], which takes precedence over [`ResolveLabel`] by virtue of method
+/// resolution trying the receiver by value before auto-referencing it.
+#[doc(hidden)]
+pub struct LabelValueSlot<V>(Cell<Option<V>>);
+
+impl<V> LabelValueSlot<V> {
+    #[doc(hidden)]
+    pub const fn new(value: V) -> Self {
+        LabelValueSlot(Cell::new(Some(value)))
+    }
+
+    fn take(&self) -> V {
+        self.0.take().expect("label value should only be resolved once")
+    }
+}
+
+/// Resolves a closure given as a label value, by calling it.
+#[doc(hidden)]
+pub trait ResolveLazyLabel {
+    #[doc(hidden)]
+    fn resolve_label<K: Into<SharedString>>(&self, key: K) -> Label;
+}
+
+impl<F, V> ResolveLazyLabel for LabelValueSlot<F>
+where
+    F: FnOnce() -> V,
+    V: IntoLabelValue,
+{
+    fn resolve_label<K: Into<SharedString>>(&self, key: K) -> Label {
+        Label::typed(key, (self.take())())
+    }
+}
+
+/// Resolves a label value given as-is.
+#[doc(hidden)]
+pub trait ResolveLabel {
+    #[doc(hidden)]
+    fn resolve_label<K: Into<SharedString>>(&self, key: K) -> Label;
+}
+
+impl<V: IntoLabelValue> ResolveLabel for &LabelValueSlot<V> {
+    fn resolve_label<K: Into<SharedString>>(&self, key: K) -> Label {
+        Label::typed(key, self.take())
+    }
+}
+
+/// A typed label value.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Typed {
+    Int(i64),
+    Float(f64),
+    Float32(f32),
+    Bool(bool),
+}
+
+impl fmt::Display for Typed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Typed::Int(value) => value.fmt(f),
+            Typed::Float(value) => value.fmt(f),
+            Typed::Float32(value) => value.fmt(f),
+            Typed::Bool(value) => value.fmt(f),
+        }
+    }
+}
+
+/// The string representation of a typed label value, rendered the first time it's needed.
+pub(crate) struct Rendered(AtomicPtr<String>);
+
+impl Rendered {
+    const fn new() -> Self {
+        Rendered(AtomicPtr::new(ptr::null_mut()))
+    }
+
+    fn get_or_render(&self, value: &Typed) -> &str {
+        let current = self.0.load(atomic::Ordering::Acquire);
+        if !current.is_null() {
+            // SAFETY: Non-null pointers always come from `Box::into_raw`, and are only freed once
+            // `self` is dropped, or consumed.
+            return unsafe { &*current };
+        }
+
+        let rendered = Box::into_raw(Box::new(value.to_string()));
+        match self.0.compare_exchange(
+            ptr::null_mut(),
+            rendered,
+            atomic::Ordering::AcqRel,
+            atomic::Ordering::Acquire,
+        ) {
+            // SAFETY: We just stored the pointer, which came from `Box::into_raw`.
+            Ok(_) => unsafe { &*rendered },
+            Err(existing) => {
+                // Another thread rendered the value before we did, so use theirs instead.
+                //
+                // SAFETY: Our pointer came from `Box::into_raw`, and was never shared, while the
+                // existing pointer is valid for the same reasons as above.
+                unsafe {
+                    drop(Box::from_raw(rendered));
+                    &*existing
+                }
+            }
+        }
+    }
+
+    fn into_string(mut self, value: &Typed) -> String {
+        let rendered = mem::replace(self.0.get_mut(), ptr::null_mut());
+        if rendered.is_null() {
+            value.to_string()
+        } else {
+            // SAFETY: Non-null pointers always come from `Box::into_raw`, and we've taken ownership
+            // of it by swapping it out.
+            *unsafe { Box::from_raw(rendered) }
+        }
+    }
+}
+
+impl Drop for Rendered {
+    fn drop(&mut self) {
+        let rendered = *self.0.get_mut();
+        if !rendered.is_null() {
+            // SAFETY: Non-null pointers always come from `Box::into_raw`, and we have exclusive
+            // access to it.
+            drop(unsafe { Box::from_raw(rendered) });
+        }
+    }
+}
+
+/// A typed label value, along with its string representation once rendered.
+pub(crate) struct TypedValue {
+    value: Typed,
+    rendered: Rendered,
+}
+
+impl TypedValue {
+    fn as_str(&self) -> &str {
+        self.rendered.get_or_render(&self.value)
+    }
+}
+
+/// The value of a label.
+///
+/// Typed values are stored as-is, and are only converted to a string the first time their string
+/// representation is needed, such as for comparing or hashing the label.  They're shared between
+/// clones of a label, which keeps cloning cheap and means each value is rendered at most once.
+/// Keeping the render cache behind a pointer also keeps labels free of interior mutability, so
+/// that they can still be placed in statics.
+#[derive(Clone)]
+pub(crate) enum Value {
+    String(SharedString),
+    Typed(Arc<TypedValue>),
+}
+
+impl Value {
+    fn typed(value: Typed) -> Self {
+        Value::Typed(Arc::new(TypedValue { value, rendered: Rendered::new() }))
+    }
+
+    fn from_u64(value: u64) -> Self {
+        match i64::try_from(value) {
+            Ok(value) => Value::typed(Typed::Int(value)),
+            Err(_) => Value::String(value.to_string().into()),
+        }
+    }
+}
+
 /// Metadata for a metric key in the form of a key/value pair.
 ///
 /// Metrics are always defined by a name, but can optionally be assigned "labels", which are
@@ -12,8 +276,14 @@ use crate::SharedString;
 /// the request currently being processed, or the request path being processed.  Another example may
 /// be that if you were running a piece o code that was turned on or off by a feature toggle, you may
 /// wish to include a label in metrics to indicate whether or not they were using the feature toggle.
-#[derive(PartialEq, Eq, Hash, Clone, Debug, PartialOrd, Ord)]
-pub struct Label(pub(crate) SharedString, pub(crate) SharedString);
+///
+/// Label values are always available as strings, but can also be integers, floating-point numbers,
+/// or booleans, in which case their type is preserved and available via [`Label::typed_value`].
+/// Typed values are only converted to a string once their string representation is first needed.
+/// The type of a label value is only a hint for rendering it: labels are compared, and hashed,
+/// based on their key and the string representation of their value.
+#[derive(Clone)]
+pub struct Label(pub(crate) SharedString, pub(crate) Value);
 
 impl Label {
     /// Creates a [`Label`] from a key and value.
@@ -22,12 +292,34 @@ impl Label {
         K: Into<SharedString>,
         V: Into<SharedString>,
     {
-        Label(key.into(), value.into())
+        Label(key.into(), Value::String(value.into()))
+    }
+
+    /// Creates a [`Label`] from a key and a value which may be an integer, floating-point number,
+    /// or boolean, preserving the type of the value.
+    pub fn typed<K, V>(key: K, value: V) -> Self
+    where
+        K: Into<SharedString>,
+        V: IntoLabelValue,
+    {
+        value.into_label(key.into())
+    }
+
+    /// Creates a [`Label`] from a key and value interned in the global
+    /// [`StringPool`](crate::StringPool).
+    ///
+    /// Only the first call for any given key, or value, allocates, which avoids allocating fresh
+    /// `String`s for labels which are built dynamically but repeat heavily.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn intern(key: &str, value: &str) -> Self {
+        let pool = crate::StringPool::global();
+        Label(pool.intern(key), Value::String(pool.intern(value)))
     }
 
     /// Creates a [`Label`] from a static key and value.
     pub const fn from_static_parts(key: &'static str, value: &'static str) -> Self {
-        Label(SharedString::const_str(key), SharedString::const_str(value))
+        Label(SharedString::const_str(key), Value::String(SharedString::const_str(value)))
     }
 
     /// Key of this label.
@@ -36,33 +328,179 @@ impl Label {
     }
 
     /// Value of this label.
+    ///
+    /// Typed values are converted to a string the first time this is called.
     pub fn value(&self) -> &str {
-        self.1.as_ref()
+        match &self.1 {
+            Value::String(value) => value.as_ref(),
+            Value::Typed(value) => value.as_str(),
+        }
+    }
+
+    /// Type of the value of this label.
+    pub fn kind(&self) -> LabelKind {
+        match &self.1 {
+            Value::String(_) => LabelKind::String,
+            Value::Typed(value) => match value.value {
+                Typed::Int(_) => LabelKind::Int,
+                Typed::Float(_) | Typed::Float32(_) => LabelKind::Float,
+                Typed::Bool(_) => LabelKind::Bool,
+            },
+        }
+    }
+
+    /// Value of this label, in its original type.
+    pub fn typed_value(&self) -> LabelValue<'_> {
+        match &self.1 {
+            Value::String(value) => LabelValue::String(value.as_ref()),
+            Value::Typed(value) => match value.value {
+                Typed::Int(value) => LabelValue::Int(value),
+                Typed::Float(value) => LabelValue::Float(value),
+                Typed::Float32(value) => LabelValue::Float(f64::from(value)),
+                Typed::Bool(value) => LabelValue::Bool(value),
+            },
+        }
     }
 
     /// Consumes this [`Label`], returning the key and value.
     pub fn into_parts(self) -> (SharedString, SharedString) {
-        (self.0, self.1)
+        let value = match self.1 {
+            Value::String(value) => value,
+            Value::Typed(value) => match Arc::try_unwrap(value) {
+                Ok(TypedValue { value, rendered }) => rendered.into_string(&value).into(),
+                Err(value) => value.as_str().to_string().into(),
+            },
+        };
+        (self.0, value)
+    }
+}
+
+impl fmt::Debug for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Label").field(&self.0).field(&self.typed_value()).finish()
+    }
+}
+
+impl PartialEq for Label {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.value() == other.value()
+    }
+}
+
+impl Eq for Label {}
+
+impl PartialOrd for Label {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Label {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.0, self.value()).cmp(&(&other.0, other.value()))
+    }
+}
+
+impl Hash for Label {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+        self.value().hash(state);
+    }
+}
+
+/// Labels are serialized as a pair of their key and their value, with the value in its original
+/// type, so that typed values survive being serialized and deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Label {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeTuple;
+
+        let mut state = serializer.serialize_tuple(2)?;
+        state.serialize_element(self.key())?;
+        match self.typed_value() {
+            LabelValue::String(value) => state.serialize_element(value)?,
+            LabelValue::Int(value) => state.serialize_element(&value)?,
+            LabelValue::Float(value) => state.serialize_element(&value)?,
+            LabelValue::Bool(value) => state.serialize_element(&value)?,
+        }
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Label {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::{Error, Visitor};
+
+        struct TypedValue(Value);
+
+        impl<'de> serde::Deserialize<'de> for TypedValue {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct TypedValueVisitor;
+
+                impl Visitor<'_> for TypedValueVisitor {
+                    type Value = TypedValue;
+
+                    fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                        f.write_str("a string, integer, floating-point number, or boolean")
+                    }
+
+                    fn visit_str<E: Error>(self, value: &str) -> Result<TypedValue, E> {
+                        Ok(TypedValue(Value::String(value.to_string().into())))
+                    }
+
+                    fn visit_i64<E: Error>(self, value: i64) -> Result<TypedValue, E> {
+                        Ok(TypedValue(Value::typed(Typed::Int(value))))
+                    }
+
+                    fn visit_u64<E: Error>(self, value: u64) -> Result<TypedValue, E> {
+                        Ok(TypedValue(Value::from_u64(value)))
+                    }
+
+                    fn visit_f64<E: Error>(self, value: f64) -> Result<TypedValue, E> {
+                        Ok(TypedValue(Value::typed(Typed::Float(value))))
+                    }
+
+                    fn visit_bool<E: Error>(self, value: bool) -> Result<TypedValue, E> {
+                        Ok(TypedValue(Value::typed(Typed::Bool(value))))
+                    }
+                }
+
+                deserializer.deserialize_any(TypedValueVisitor)
+            }
+        }
+
+        let (key, TypedValue(value)) = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Label(key, value))
     }
 }
 
 impl<K, V> From<&(K, V)> for Label
 where
     K: Into<SharedString> + Clone,
-    V: Into<SharedString> + Clone,
+    V: IntoLabelValue + Clone,
 {
     fn from(pair: &(K, V)) -> Label {
-        Label::new(pair.0.clone(), pair.1.clone())
+        Label::typed(pair.0.clone(), pair.1.clone())
     }
 }
 
 impl<K, V> From<(&K, &V)> for Label
 where
     K: Into<SharedString> + Clone,
-    V: Into<SharedString> + Clone,
+    V: IntoLabelValue + Clone,
 {
     fn from(pair: (&K, &V)) -> Label {
-        Label::new(pair.0.clone(), pair.1.clone())
+        Label::typed(pair.0.clone(), pair.1.clone())
     }
 }
 
@@ -70,6 +508,13 @@ where
 pub trait IntoLabels {
     /// Consumes this value, turning it into a vector of [`Label`]s.
     fn into_labels(self) -> Vec<Label>;
+
+    /// Gets the labels of this value if they're stored in a static, allowing keys to borrow them
+    /// rather than allocating a copy.
+    #[doc(hidden)]
+    fn static_labels(&self) -> Option<&'static [Label]> {
+        None
+    }
 }
 
 impl IntoLabels for Vec<Label> {
@@ -94,6 +539,70 @@ where
     }
 }
 
+/// A set of labels stored in a static.
+///
+/// Created by the [`labels!`](crate::labels) macro, which builds the labels once, rather than every
+/// time a metric is emitted.  A `LabelSet` can be passed, by value or by reference, to any of the
+/// emission macros in place of a list of labels, and the key of the metric borrows the labels
+/// instead of copying them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LabelSet {
+    labels: &'static [Label],
+}
+
+impl LabelSet {
+    /// Creates a [`LabelSet`] from a static set of labels.
+    ///
+    /// This function is `const`, so it can be used in a static context.
+    pub const fn from_static(labels: &'static [Label]) -> Self {
+        Self { labels }
+    }
+
+    /// Gets the labels in this set.
+    pub fn labels(&self) -> &'static [Label] {
+        self.labels
+    }
+
+    /// Returns the number of labels in this set.
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Returns `true` if this set contains no labels.
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+impl IntoLabels for LabelSet {
+    fn into_labels(self) -> Vec<Label> {
+        self.labels.to_vec()
+    }
+
+    fn static_labels(&self) -> Option<&'static [Label]> {
+        Some(self.labels)
+    }
+}
+
+impl IntoLabels for &LabelSet {
+    fn into_labels(self) -> Vec<Label> {
+        self.labels.to_vec()
+    }
+
+    fn static_labels(&self) -> Option<&'static [Label]> {
+        Some(self.labels)
+    }
+}
+
+impl IntoIterator for LabelSet {
+    type Item = &'static Label;
+    type IntoIter = Iter<'static, Label>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.labels.iter()
+    }
+}
+
 #[cfg(test)]
 mod label_tests {
     use super::*;
@@ -119,4 +628,86 @@ mod label_tests {
         let expected = vec![Label::new("customer", "Rust Foundation")];
         assert_eq!(labels_btreemap.into_labels(), expected);
     }
+
+    #[test]
+    fn typed_labels() {
+        let cases = [
+            (Label::new("k", "v"), LabelKind::String, LabelValue::String("v")),
+            (Label::typed("k", "v"), LabelKind::String, LabelValue::String("v")),
+            (Label::typed("k", 200u16), LabelKind::Int, LabelValue::Int(200)),
+            (Label::typed("k", -3i64), LabelKind::Int, LabelValue::Int(-3)),
+            (Label::typed("k", 0.25), LabelKind::Float, LabelValue::Float(0.25)),
+            (Label::typed("k", true), LabelKind::Bool, LabelValue::Bool(true)),
+            (
+                Label::typed("k", u64::MAX),
+                LabelKind::String,
+                LabelValue::String("18446744073709551615"),
+            ),
+        ];
+
+        for (label, kind, value) in cases {
+            assert_eq!(label.kind(), kind);
+            assert_eq!(label.typed_value(), value);
+        }
+
+        // The type of a value doesn't affect equality.
+        assert_eq!(Label::typed("code", 200), Label::new("code", "200"));
+        assert_eq!(Label::typed("code", 200).value(), "200");
+    }
+
+    #[test]
+    fn typed_labels_render_lazily() {
+        let is_rendered = |label: &Label| match &label.1 {
+            Value::String(_) => true,
+            Value::Typed(value) => !value.rendered.0.load(atomic::Ordering::Acquire).is_null(),
+        };
+
+        let label = Label::typed("ratio", 0.1f32);
+        assert_eq!(label.typed_value(), LabelValue::Float(f64::from(0.1f32)));
+        assert!(!is_rendered(&label));
+
+        assert_eq!(label.value(), "0.1");
+        assert!(is_rendered(&label));
+
+        // Clones share the rendered value.
+        let cloned = label.clone();
+        assert!(is_rendered(&cloned));
+        assert_eq!(label.into_parts().1, SharedString::from("0.1"));
+        assert_eq!(cloned.into_parts().1, SharedString::from("0.1"));
+    }
+
+    #[test]
+    fn label_set_labels() {
+        static LABELS: [Label; 2] =
+            [Label::from_static_parts("x", "a"), Label::from_static_parts("y", "b")];
+        let set = LabelSet::from_static(&LABELS);
+
+        assert_eq!(set.static_labels(), Some(&LABELS[..]));
+        assert_eq!((&set).into_labels(), vec![Label::new("x", "a"), Label::new("y", "b")]);
+        assert_eq!(set.into_iter().count(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn label_set_macro() {
+        fn request_labels(method: &'static str) -> LabelSet {
+            crate::labels!("service" => "http", "method" => method)
+        }
+
+        let first = request_labels("GET");
+        let second = request_labels("POST");
+        assert!(core::ptr::eq(first.labels(), second.labels()));
+        assert_eq!(second.labels()[1], Label::new("method", "GET"));
+
+        let key = crate::Key::from_parts("requests", first);
+        assert!(core::ptr::eq(key.labels().as_slice(), first.labels()));
+    }
+
+    #[test]
+    fn typed_slice_labels() {
+        let labels = [("code", 200), ("attempt", 2)];
+        let labels = labels.into_labels();
+        assert_eq!(labels[0].typed_value(), LabelValue::Int(200));
+        assert_eq!(labels[1].typed_value(), LabelValue::Int(2));
+    }
 }
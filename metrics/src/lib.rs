@@ -8,7 +8,8 @@
 //! `metrics` exposes two main concepts: emitting a metric, and recording it.
 //!
 //! ## Metric types, or kinds
-//! This crate supports three fundamental metric types, or kinds: counters, gauges, and histograms.
+//! This crate supports four fundamental metric types, or kinds: counters, gauges, histograms, and
+//! summaries.
 //!
 //! ### Counters
 //! A counter is a cumulative metric that represents a monotonically increasing value which can only
@@ -32,6 +33,11 @@
 //!
 //! Gauges are floating-point 64-bit numbers.
 //!
+//! Gauges can also be backed by a callback, via [`Gauge::set_callback`], for values which are
+//! cheap to read on demand, such as the depth of a queue or the size of a cache.  Recorders which
+//! support callbacks invoke them whenever they collect the value of the gauge, so that no
+//! background task is needed to keep the gauge up to date.
+//!
 //! ### Histograms
 //! A histogram stores an arbitrary number of observations of a specific measurement and provides
 //! statistical analysis over the observed values.  Typically, measurements such as request latency
@@ -58,6 +64,17 @@
 //!
 //! Histograms take floating-point 64-bit numbers.
 //!
+//! ### Summaries
+//! A summary, like a histogram, stores observations of a specific measurement, but is explicitly
+//! meant to be exported as a count, a sum, and a set of quantiles computed from the observed values.
+//! This allows exporters which natively support summaries to export them as such, without having
+//! to be configured to treat specific histograms as summaries.
+//!
+//! Recorders without native support for summaries record them as histograms, so summaries can be
+//! used regardless of the exporter in use.
+//!
+//! Summaries take floating-point 64-bit numbers.
+//!
 //! ## Emission
 //!
 //! Metrics are emitted by utilizing the emission methods.  There is a macro for
@@ -66,12 +83,40 @@
 //! - [`counter!`] returns the [`Counter`] handle then
 //!     - [`Counter::increment`] increments the counter.
 //!     - [`Counter::absolute`] sets the counter.
+//! - [`absolute_counter!`] for mirroring counters maintained elsewhere, backed by a counter, then
+//!     - [`AbsoluteCounter::set`] sets the counter to its latest value.
 //! - [`gauge!`] returns the [`Gauge`] handle then
 //!     - [`Gauge::increment`] increments the gauge.
 //!     - [`Gauge::decrement`] decrements the gauge.
 //!     - [`Gauge::set`] sets the gauge.
+//! - [`gauge_guard!`] for tracking in-flight operations, backed by a gauge, returning a
+//!   [`GaugeGuard`] which decrements the gauge again when dropped.
 //! - [`histogram!`] for histograms then
 //!     - [`Histogram::record`] records a data point.
+//! - [`sampled_histogram!`] for recording only a fraction of data points into a histogram, at a
+//!   fixed sample rate.
+//! - [`timer!`] for timing operations, backed by a histogram, then
+//!     - [`Timer::start`] starts a measurement, recorded when the returned guard is dropped.
+//!     - [`TimerGuard::stop`] stops a measurement early, returning the elapsed time.
+//! - [`time!`] for timing a block, recording its duration into a histogram once the block exits.
+//! - [`summary!`] for summaries then
+//!     - [`Summary::record`] records a data point.
+//!
+//! Like the macros in `tracing`, each of the registration macros accepts optional `target:` and
+//! `level:` arguments ahead of the metric name, which default to the module path of the callsite
+//! and [`Level::INFO`] respectively.  They're passed to the recorder as part of the [`Metadata`] of
+//! the metric, so that recorders and layers can filter metrics based on them:
+//!
+//! ```
+//! # use metrics::{counter, histogram, Level};
+//! let counter = counter!(target: "my_app::db", level: Level::DEBUG, "queries_total");
+//! let histogram = histogram!(level: Level::TRACE, "query_duration_seconds", "table" => "users");
+//! # let _ = (counter, histogram);
+//! ```
+//!
+//! On hot paths, [`static_counter!`], [`static_gauge!`], and [`static_histogram!`] can be used for
+//! metrics with a constant name and labels, which only register the metric once, and return a
+//! handle cached at the callsite from then on.
 //!
 //! Additionally, metrics can be described -- setting either the unit of measure or long-form
 //! description -- by using the `describe_*` macros:
@@ -79,6 +124,18 @@
 //! - [`describe_counter!`] for counters
 //! - [`describe_gauge!`] for gauges
 //! - [`describe_histogram!`] for histograms
+//! - [`describe_summary!`] for summaries
+//!
+//! Metrics can also be described and registered in a single call, returning the handle, by using
+//! the `declare_*` macros, which keeps the description next to the code using the metric:
+//!
+//! - [`declare_counter!`] for counters
+//! - [`declare_gauge!`] for gauges
+//! - [`declare_histogram!`] for histograms
+//!
+//! Beyond units and descriptions, typed [`Attribute`]s -- such as the [`Owner`] of a metric, or
+//! whether it is [`Deprecated`] -- can be attached to a metric name by using
+//! [`describe_attributes!`].
 //!
 //! In order to register or emit a metric, you need a way to record these events, which is where
 //! [`Recorder`] comes into play.
@@ -170,12 +227,28 @@
 //!
 //! As the types are enforced/limited by the [`Recorder`] trait itself, the remaining piece is the
 //! identifier, which we handle by using [`Key`]. Keys hold both the metric name, and potentially,
-//! labels related to the metric. The metric name and labels are always string values.
+//! labels related to the metric. The metric name and label keys are always string values, while
+//! label values are always available as strings, but can also be integers, floating-point numbers,
+//! or booleans, whose type is preserved for exporters which can render them natively.  See
+//! [`Label::typed`] for more details.
+//!
+//! Metric names given to the macros as string literals are checked at compile time: they must be
+//! non-empty, must not start with a digit, and may only contain ASCII letters, digits, `_`, `.`,
+//! and `:`.  Invalid names are reported as a compile error, rather than being rejected, or
+//! silently rewritten, by an exporter later on.  Names built at runtime aren't checked.
+//!
+//! Keys are only built when a recorder is installed, and the recorder reports the metric as
+//! [enabled](Recorder::enabled) based on its name and metadata, so label values which are expensive
+//! to compute can be given to the macros as closures, e.g. `"tenant" => || tenant_name()`, to skip
+//! computing them entirely when the metric would be discarded.  Conversely, labels which are shared by many metrics, or emitted in a hot loop, can be
+//! built once via [`labels!`], which returns a [`LabelSet`] that keys borrow rather than copy.
 //!
 //! Internally, `metrics` uses a clone-on-write "smart pointer" for these values to optimize cases
 //! where the values are static strings, which can provide significant performance benefits.  These
 //! smart pointers can also hold owned `String` values, though, so users can mix and match static
-//! strings and owned strings without issue.
+//! strings and owned strings without issue.  Strings which are built dynamically, but repeat
+//! heavily, can be interned via [`KeyName::intern`] and [`Label::intern`], so that only the first
+//! occurrence of a given string allocates.
 //!
 //! Two [`Key`] objects can be checked for equality and considered to point to the same metric if
 //! they are equal.  Equality checks both the name of the key and the labels of a key.  Labels are
@@ -184,7 +257,9 @@
 //!
 //! It is an implementation detail if a recorder wishes to do an deeper equality check that ignores
 //! the order of labels, but practically speaking, metric emission, and thus labels, should be
-//! fixed in ordering in nearly all cases, and so it typically is not a problem.
+//! fixed in ordering in nearly all cases, and so it typically is not a problem.  When it is, keys
+//! can be put into a canonical form, with sorted and deduplicated labels, via
+//! [`Key::canonicalize`].
 //!
 //! ## Registration
 //!
@@ -259,7 +334,61 @@
 //! [`with_local_recorder`] allows you to do this by changing the recorder used by the emission macros for
 //! the duration of a given closure. While in that closure, the given recorder will act as if it was
 //! the global recorder for the current thread. Once the closure returns, the true global recorder
-//! takes priority again for the current thread.
+//! takes priority again for the current thread. Local recorders can be nested, with the innermost
+//! one taking priority.
+//!
+//! As futures can move between threads, and share a thread with other futures, asynchronous code
+//! should use [`with_local_recorder_async`] instead, which wraps a future so that the given
+//! recorder is set as the local recorder every time the future is polled.
+//!
+//! ### Replacing the global recorder
+//!
+//! While the global recorder can normally only be installed once, [`replace_global_recorder`] and
+//! [`clear_global_recorder`] allow swapping it out after installation, such as in integration tests
+//! which install several exporters over the course of a single process. Both return a
+//! [`GlobalRecorderGuard`], which restores the previous global recorder when dropped.
+//!
+//! ### Flushing recorders
+//!
+//! Push-based recorders, which periodically send metrics elsewhere, may be holding metrics which
+//! haven't been sent yet when the process exits.  Calling [`flush`] before exiting asks the current
+//! recorder to send them, via [`Recorder::flush`].  Recorders can also be shut down, flushing them
+//! and stopping any background work, via [`Recorder::shutdown`].
+//!
+//! ## Procedural macros
+//!
+//! With the `macros` feature enabled, functions can be instrumented via the [`instrument`]
+//! attribute, which records the number of calls to the function, the time spent in it, and,
+//! optionally, the number of calls which returned an error:
+//!
+//! ```ignore
+//! #[metrics::instrument(name = "db_query", labels(table = "users"), errors)]
+//! async fn fetch_user(id: u64) -> Result<User, DbError> {
+//!     // ...
+//! }
+//! ```
+//!
+//! Structs can also be used as label sets by deriving [`MetricLabels`], which turns each of their
+//! fields into a label:
+//!
+//! ```ignore
+//! #[derive(metrics::MetricLabels)]
+//! struct RequestLabels {
+//!     method: &'static str,
+//!     #[label(rename = "status_code")]
+//!     status: u16,
+//! }
+//!
+//! let labels = RequestLabels { method: "GET", status: 200 };
+//! metrics::counter!("requests", &labels).increment(1);
+//! ```
+//!
+//! ## `no_std` support
+//!
+//! This crate can be used in `no_std` environments which provide an allocator, by disabling the
+//! default `std` feature. The [`Recorder`] trait, the metric handles, and the emission macros all
+//! remain available, as does the global recorder, provided the target supports atomic operations
+//! on pointers. Local recorders, and [`Timer::start`], require the `std` feature.
 //!
 //! [metrics-exporter-tcp]: https://docs.rs/metrics-exporter-tcp
 //! [metrics-exporter-prometheus]: https://docs.rs/metrics-exporter-prometheus
@@ -268,9 +397,15 @@
 //! [Handle]: https://docs.rs/metrics-util/0.5.0/metrics_util/enum.Handle.html
 #![deny(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg), deny(rustdoc::broken_intra_doc_links))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
 pub mod atomics;
 
+mod attributes;
+pub use self::attributes::*;
+
 mod common;
 mod macros;
 pub use self::common::*;
@@ -280,6 +415,11 @@ mod cow;
 mod handles;
 pub use self::handles::*;
 
+#[cfg(feature = "std")]
+mod intern;
+#[cfg(feature = "std")]
+pub use self::intern::{PoolStats, StringPool};
+
 mod key;
 pub use self::key::*;
 
@@ -291,3 +431,16 @@ pub use self::metadata::*;
 
 mod recorder;
 pub use self::recorder::*;
+
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use metrics_macros::{instrument, MetricLabels};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::key::validate_name_tokens;
+    pub use crate::label::{LabelValueSlot, ResolveLabel, ResolveLazyLabel};
+    pub use alloc::vec;
+    #[cfg(feature = "std")]
+    pub use std::sync::OnceLock;
+}
@@ -5,7 +5,7 @@ macro_rules! metadata_var {
         static METADATA: $crate::Metadata<'static> = $crate::Metadata::new(
             $target,
             $level,
-            ::core::option::Option::Some(::std::module_path!()),
+            ::core::option::Option::Some(::core::module_path!()),
         );
         &METADATA
     }};
@@ -22,6 +22,14 @@ macro_rules! count {
     };
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! validate_name {
+    ($name:expr) => {
+        const _: () = $crate::__private::validate_name_tokens(::core::stringify!($name));
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! key_var {
@@ -46,14 +54,31 @@ macro_rules! key_var {
         $crate::Key::from_static_labels($name, &LABELS)
     }};
     ($name:expr, $($label_key:expr => $label_value:expr),*) => {{
-        let labels = ::std::vec![
-            $($crate::Label::new($label_key, $label_value)),*
+        #[allow(unused_imports)]
+        use $crate::__private::{ResolveLabel as _, ResolveLazyLabel as _};
+        let labels = $crate::__private::vec![
+            $((&$crate::__private::LabelValueSlot::new($label_value)).resolve_label($label_key)),*
         ];
         $crate::Key::from_parts($name, labels)
     }};
-    ($name:expr, $labels:expr) => {
+    ($name:expr, $labels:expr) => {{
         $crate::Key::from_parts($name, $labels)
-    }
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! register_var {
+    ($recorder:ident, $register:ident, $metadata:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)*) => {{
+        $crate::validate_name!($name);
+        let metric_name: $crate::KeyName = ::core::convert::Into::into($name);
+        if $recorder.enabled(&metric_name, $metadata) {
+            let metric_key = $crate::key_var!(metric_name $(, $label_key $(=> $label_value)?)*);
+            ::core::option::Option::Some($recorder.$register(&metric_key, $metadata))
+        } else {
+            ::core::option::Option::None
+        }
+    }};
 }
 
 /// Registers a counter.
@@ -75,6 +100,7 @@ macro_rules! key_var {
 /// # use ::std::format;
 /// # use ::std::string::String;
 /// # use metrics::counter;
+/// # use metrics::Level;
 /// # fn main() {
 /// // A basic counter:
 /// let counter = counter!("some_metric_name");
@@ -101,24 +127,95 @@ macro_rules! key_var {
 /// let counter = counter!(name);
 ///
 /// let counter = counter!(format!("{}_via_format", "name"));
+///
+/// // The target and level of the metric, which default to the module path of the callsite and
+/// // `INFO`, can be given ahead of the name, and are passed to the recorder as metadata:
+/// let counter = counter!(target: "my_app::db", level: Level::DEBUG, "some_metric_name");
+/// let counter = counter!(level: Level::TRACE, "some_metric_name", "service" => "http");
 /// # }
 /// ```
 #[macro_export]
 macro_rules! counter {
     (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {{
-        let metric_key = $crate::key_var!($name $(, $label_key $(=> $label_value)?)*);
         let metadata = $crate::metadata_var!($target, $level);
 
-        $crate::with_recorder(|recorder| recorder.register_counter(&metric_key, metadata))
+        $crate::try_with_recorder(|recorder| {
+            $crate::register_var!(recorder, register_counter, metadata, $name $(, $label_key $(=> $label_value)?)*)
+        })
+        .flatten()
+        .unwrap_or_else($crate::Counter::noop)
     }};
     (target: $target:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
         $crate::counter!(target: $target, level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
     };
     (level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
-        $crate::counter!(target: ::std::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
+        $crate::counter!(target: ::core::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
+    };
+    ($name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::counter!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+    };
+}
+
+/// Registers an absolute counter.
+///
+/// Absolute counters are counters which mirror a cumulative counter maintained elsewhere, such as a
+/// counter read from `/proc`, by being set to its latest value rather than incremented.
+///
+/// Metrics can be registered, which provides a handle to directly update that metric.  For
+/// absolute counters, [`AbsoluteCounter`](crate::AbsoluteCounter) is provided, which wraps the
+/// registered [`Counter`](crate::Counter) and can be set to its latest value.
+///
+/// Metric names are shown below using string literals, but they can also be owned `String` values,
+/// which includes using macros such as `format!` directly at the callsite. String literals are
+/// preferred for performance where possible.
+///
+/// # Example
+/// ```
+/// # #![no_implicit_prelude]
+/// # use ::std::convert::From;
+/// # use ::std::format;
+/// # use ::std::string::String;
+/// # use metrics::absolute_counter;
+/// # fn main() {
+/// // A basic absolute counter:
+/// let counter = absolute_counter!("some_metric_name");
+/// counter.set(42);
+///
+/// // Specifying labels inline, including using constants for either the key or value:
+/// let counter = absolute_counter!("some_metric_name", "service" => "http");
+///
+/// const SERVICE_LABEL: &'static str = "service";
+/// const SERVICE_HTTP: &'static str = "http";
+/// let counter = absolute_counter!("some_metric_name", SERVICE_LABEL => SERVICE_HTTP);
+///
+/// // We can also pass labels by giving a vector or slice of key/value pairs:
+/// let dynamic_val = "woo";
+/// let labels = [("dynamic_key", format!("{}!", dynamic_val))];
+/// let counter = absolute_counter!("some_metric_name", &labels);
+///
+/// // As mentioned in the documentation, metric names also can be owned strings, including ones
+/// // generated at the callsite via things like `format!`:
+/// let name = String::from("some_owned_metric_name");
+/// let counter = absolute_counter!(name);
+///
+/// let counter = absolute_counter!(format!("{}_via_format", "name"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! absolute_counter {
+    (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::AbsoluteCounter::from_counter(
+            $crate::counter!(target: $target, level: $level, $name $(, $label_key $(=> $label_value)?)*)
+        )
+    };
+    (target: $target:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::absolute_counter!(target: $target, level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+    };
+    (level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::absolute_counter!(target: ::core::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
     };
     ($name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
-        $crate::counter!(target: ::std::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+        $crate::absolute_counter!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
     };
 }
 
@@ -141,6 +238,7 @@ macro_rules! counter {
 /// # use ::std::format;
 /// # use ::std::convert::From;
 /// # use metrics::gauge;
+/// # use metrics::Level;
 /// # fn main() {
 /// // A basic gauge:
 /// let gauge = gauge!("some_metric_name");
@@ -168,24 +266,59 @@ macro_rules! counter {
 /// let gauge = gauge!(name);
 ///
 /// let gauge = gauge!(format!("{}_via_format", "name"));
+///
+/// // The target and level of the metric, which default to the module path of the callsite and
+/// // `INFO`, can be given ahead of the name, and are passed to the recorder as metadata:
+/// let gauge = gauge!(target: "my_app::db", level: Level::DEBUG, "some_metric_name");
+/// let gauge = gauge!(level: Level::TRACE, "some_metric_name", "service" => "http");
 /// # }
 /// ```
 #[macro_export]
 macro_rules! gauge {
     (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {{
-        let metric_key = $crate::key_var!($name $(, $label_key $(=> $label_value)?)*);
         let metadata = $crate::metadata_var!($target, $level);
 
-        $crate::with_recorder(|recorder| recorder.register_gauge(&metric_key, metadata))
+        $crate::try_with_recorder(|recorder| {
+            $crate::register_var!(recorder, register_gauge, metadata, $name $(, $label_key $(=> $label_value)?)*)
+        })
+        .flatten()
+        .unwrap_or_else($crate::Gauge::noop)
     }};
     (target: $target:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
         $crate::gauge!(target: $target, level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
     };
     (level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
-        $crate::gauge!(target: ::std::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
+        $crate::gauge!(target: ::core::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
     };
     ($name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
-        $crate::gauge!(target: ::std::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+        $crate::gauge!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+    };
+}
+
+/// Registers a gauge, and increments it for as long as the returned guard lives.
+///
+/// The gauge is incremented by one immediately, and decremented by one when the returned
+/// [`GaugeGuard`](crate::GaugeGuard) is dropped, including when unwinding from a panic.  This is
+/// suited to gauges which track the number of in-flight operations, such as requests currently
+/// being handled, as every increment is always paired with a decrement.
+///
+/// Arguments are the same as for [`gauge!`](crate::gauge).
+///
+/// # Example
+/// ```
+/// # use metrics::gauge_guard;
+/// # fn main() {
+/// fn handle_request() {
+///     let _inflight = gauge_guard!("inflight_requests", "service" => "http");
+///     // Handle the request...
+/// }
+/// # handle_request();
+/// # }
+/// ```
+#[macro_export]
+macro_rules! gauge_guard {
+    ($($arg:tt)+) => {
+        $crate::gauge!($($arg)+).guard()
     };
 }
 
@@ -208,6 +341,7 @@ macro_rules! gauge {
 /// # use ::std::format;
 /// # use ::std::convert::From;
 /// # use metrics::histogram;
+/// # use metrics::Level;
 /// # fn main() {
 /// // A basic histogram:
 /// let histogram = histogram!("some_metric_name");
@@ -232,31 +366,532 @@ macro_rules! gauge {
 /// let histogram = histogram!(name);
 ///
 /// let histogram = histogram!(format!("{}_via_format", "name"));
+///
+/// // The target and level of the metric, which default to the module path of the callsite and
+/// // `INFO`, can be given ahead of the name, and are passed to the recorder as metadata:
+/// let histogram = histogram!(target: "my_app::db", level: Level::DEBUG, "some_metric_name");
+/// let histogram = histogram!(level: Level::TRACE, "some_metric_name", "service" => "http");
 /// # }
 /// ```
 #[macro_export]
 macro_rules! histogram {
     (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {{
-        let metric_key = $crate::key_var!($name $(, $label_key $(=> $label_value)?)*);
         let metadata = $crate::metadata_var!($target, $level);
 
-        $crate::with_recorder(|recorder| recorder.register_histogram(&metric_key, metadata))
+        $crate::try_with_recorder(|recorder| {
+            $crate::register_var!(recorder, register_histogram, metadata, $name $(, $label_key $(=> $label_value)?)*)
+        })
+        .flatten()
+        .unwrap_or_else($crate::Histogram::noop)
     }};
     (target: $target:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
         $crate::histogram!(target: $target, level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
     };
     (level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
-        $crate::histogram!(target: ::std::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
+        $crate::histogram!(target: ::core::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
+    };
+    ($name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::histogram!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+    };
+}
+
+/// Records a value into a histogram, sampling one in every N calls.
+///
+/// Only one call in every `1 / rate` calls at a given callsite registers the histogram and records
+/// the value, while every other call only increments a counter, which makes this suitable for
+/// extremely hot code where recording every value would be too costly, but a statistical view of
+/// the distribution is still useful.  The first call at a callsite is always recorded, and the value
+/// is only evaluated for calls which are recorded.
+///
+/// The rate must be a constant greater than `0.0` and at most `1.0`, and is checked at compile time.
+/// It is attached to the histogram via the [`SampleRate`](crate::SampleRate) attribute, described
+/// the first time a value is recorded at the callsite, so that recorders can scale counts and sums
+/// back up.  As the rate is rounded to a whole period, a rate of `0.3` samples one in every three
+/// calls, for example.
+///
+/// Labels are given after the name, as with the other macros, but separated from the value by a
+/// semicolon rather than a comma.
+///
+/// # Example
+/// ```
+/// # use metrics::sampled_histogram;
+/// # fn main() {
+/// # let latency = 0.25;
+/// // Record one in every hundred values:
+/// sampled_histogram!(rate = 0.01, "some_metric_name", latency);
+///
+/// // Specifying labels inline, which are separated from the value by a semicolon:
+/// sampled_histogram!(rate = 0.01, "some_metric_name", "service" => "http"; latency);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! sampled_histogram {
+    (rate = $rate:expr, $name:expr, $value:expr $(,)?) => {
+        $crate::sampled_histogram!(rate = $rate, $name; $value)
+    };
+    (rate = $rate:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* ; $value:expr $(,)?) => {{
+        const SAMPLE_RATE: f64 = $rate;
+        const SAMPLE_PERIOD: usize = {
+            if !(SAMPLE_RATE > 0.0 && SAMPLE_RATE <= 1.0) {
+                ::core::panic!("sample rates must be greater than 0 and at most 1");
+            }
+            let period = (1.0 / SAMPLE_RATE + 0.5) as usize;
+            if period == 0 { 1 } else { period }
+        };
+        static CALLS: ::core::sync::atomic::AtomicUsize = ::core::sync::atomic::AtomicUsize::new(0);
+        static DESCRIBED: ::core::sync::atomic::AtomicBool = ::core::sync::atomic::AtomicBool::new(false);
+
+        if CALLS.fetch_add(1, ::core::sync::atomic::Ordering::Relaxed) % SAMPLE_PERIOD == 0 {
+            if !DESCRIBED.swap(true, ::core::sync::atomic::Ordering::Relaxed) {
+                $crate::describe_attributes!($name, $crate::SampleRate::new(SAMPLE_RATE));
+            }
+            $crate::histogram!($name $(, $label_key $(=> $label_value)?)*).record($value);
+        }
+    }};
+}
+
+/// Registers a timer.
+///
+/// Timers measure the duration of operations, and record the elapsed time, in seconds, into a
+/// histogram with the given name.
+///
+/// Metrics can be registered, which provides a handle to directly update that metric.  For timers,
+/// [`Timer`](crate::Timer) is provided which can be started, returning a guard that records the
+/// elapsed time when it is stopped or dropped.
+///
+/// Metric names are shown below using string literals, but they can also be owned `String` values,
+/// which includes using macros such as `format!` directly at the callsite. String literals are
+/// preferred for performance where possible.
+///
+/// # Example
+/// ```
+/// # #![no_implicit_prelude]
+/// # use ::std::string::String;
+/// # use ::std::format;
+/// # use ::std::convert::From;
+/// # use metrics::timer;
+/// # fn main() {
+/// // A basic timer, which records the elapsed time once the guard goes out of scope:
+/// let timer = timer!("some_metric_name");
+/// {
+///     let _guard = timer.start();
+///     // Do some work...
+/// }
+///
+/// // Stopping a timer explicitly, which also returns the elapsed time:
+/// let guard = timer!("some_metric_name").start();
+/// let elapsed = guard.stop();
+///
+/// // Specifying labels inline, including using constants for either the key or value:
+/// let timer = timer!("some_metric_name", "service" => "http");
+///
+/// const SERVICE_LABEL: &'static str = "service";
+/// const SERVICE_HTTP: &'static str = "http";
+/// let timer = timer!("some_metric_name", SERVICE_LABEL => SERVICE_HTTP);
+///
+/// // We can also pass labels by giving a vector or slice of key/value pairs:
+/// let dynamic_val = "woo";
+/// let labels = [("dynamic_key", format!("{}!", dynamic_val))];
+/// let timer = timer!("some_metric_name", &labels);
+///
+/// // As mentioned in the documentation, metric names also can be owned strings, including ones
+/// // generated at the callsite via things like `format!`:
+/// let name = String::from("some_owned_metric_name");
+/// let timer = timer!(name);
+///
+/// let timer = timer!(format!("{}_via_format", "name"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! timer {
+    (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {{
+        let metadata = $crate::metadata_var!($target, $level);
+
+        $crate::try_with_recorder(|recorder| {
+            $crate::register_var!(recorder, register_histogram, metadata, $name $(, $label_key $(=> $label_value)?)*)
+        })
+        .flatten()
+        .map($crate::Timer::from_histogram)
+        .unwrap_or_else($crate::Timer::noop)
+    }};
+    (target: $target:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::timer!(target: $target, level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+    };
+    (level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::timer!(target: ::core::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
+    };
+    ($name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::timer!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+    };
+}
+
+/// Times a block, recording its duration into a histogram.
+///
+/// The elapsed time, in seconds, is recorded into the histogram with the given name once the block
+/// finishes, and the value of the block is returned.  As the time is recorded by a guard, via
+/// [`timer!`](crate::timer), it is also recorded when the block exits early, whether via `return`,
+/// the `?` operator, or `break`.
+///
+/// Labels are given before the block, as with the other macros, but separated from it by a
+/// semicolon rather than a comma.
+///
+/// # Example
+/// ```
+/// # use metrics::time;
+/// # fn parse(input: &str) -> Result<u64, std::num::ParseIntError> {
+/// // A basic timed block:
+/// let sum = time!("some_metric_name", { 1 + 1 });
+///
+/// // Specifying labels inline, which are separated from the block by a semicolon:
+/// let sum = time!("some_metric_name", "service" => "http"; { 1 + 1 });
+///
+/// // The duration is recorded even if the block returns early:
+/// let value = time!("parse_duration_seconds", {
+///     let value = input.trim().parse::<u64>()?;
+///     value * 2
+/// });
+/// # Ok(value + sum)
+/// # }
+/// # fn main() { let _ = parse("1"); }
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[macro_export]
+macro_rules! time {
+    // The forms with labels are tried first, as a failed attempt at parsing labels as a block would
+    // otherwise abort the expansion.
+    (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* ; $body:block) => {{
+        let _guard = $crate::timer!(target: $target, level: $level, $name $(, $label_key $(=> $label_value)?)*).start();
+        $body
+    }};
+    (target: $target:expr, level: $level:expr, $name:expr, $body:block) => {
+        $crate::time!(target: $target, level: $level, $name; $body)
+    };
+    (target: $target:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* ; $body:block) => {
+        $crate::time!(target: $target, level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*; $body)
+    };
+    (target: $target:expr, $name:expr, $body:block) => {
+        $crate::time!(target: $target, level: $crate::Level::INFO, $name; $body)
+    };
+    (level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* ; $body:block) => {
+        $crate::time!(target: ::core::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*; $body)
+    };
+    (level: $level:expr, $name:expr, $body:block) => {
+        $crate::time!(target: ::core::module_path!(), level: $level, $name; $body)
+    };
+    ($name:expr $(, $label_key:expr $(=> $label_value:expr)?)* ; $body:block) => {
+        $crate::time!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*; $body)
+    };
+    ($name:expr, $body:block) => {
+        $crate::time!(target: ::core::module_path!(), level: $crate::Level::INFO, $name; $body)
+    };
+}
+
+/// Registers a summary.
+///
+/// Summaries measure the distribution of values for a given set of measurements, like histograms,
+/// but are exported as a count, a sum, and a set of quantiles computed from the observed values,
+/// rather than as bucketed values.
+///
+/// Metrics can be registered, which provides a handle to directly update that metric.  For
+/// summaries, [`Summary`](crate::Summary) is provided which can record values.
+///
+/// Recorders without native support for summaries record them as histograms instead.
+///
+/// Metric names are shown below using string literals, but they can also be owned `String` values,
+/// which includes using macros such as `format!` directly at the callsite. String literals are
+/// preferred for performance where possible.
+///
+/// # Example
+/// ```
+/// # #![no_implicit_prelude]
+/// # use ::std::string::String;
+/// # use ::std::format;
+/// # use ::std::convert::From;
+/// # use metrics::summary;
+/// # fn main() {
+/// // A basic summary:
+/// let summary = summary!("some_metric_name");
+/// summary.record(1.0);
+///
+/// // Specifying labels inline, including using constants for either the key or value:
+/// let summary = summary!("some_metric_name", "service" => "http");
+///
+/// const SERVICE_LABEL: &'static str = "service";
+/// const SERVICE_HTTP: &'static str = "http";
+/// let summary = summary!("some_metric_name", SERVICE_LABEL => SERVICE_HTTP);
+///
+/// // We can also pass labels by giving a vector or slice of key/value pairs:
+/// let dynamic_val = "woo";
+/// let labels = [("dynamic_key", format!("{}!", dynamic_val))];
+/// let summary = summary!("some_metric_name", &labels);
+///
+/// // As mentioned in the documentation, metric names also can be owned strings, including ones
+/// // generated at the callsite via things like `format!`:
+/// let name = String::from("some_owned_metric_name");
+/// let summary = summary!(name);
+///
+/// let summary = summary!(format!("{}_via_format", "name"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! summary {
+    (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {{
+        let metadata = $crate::metadata_var!($target, $level);
+
+        $crate::try_with_recorder(|recorder| {
+            $crate::register_var!(recorder, register_summary, metadata, $name $(, $label_key $(=> $label_value)?)*)
+        })
+        .flatten()
+        .unwrap_or_else($crate::Summary::noop)
+    }};
+    (target: $target:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::summary!(target: $target, level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+    };
+    (level: $level:expr, $name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
+        $crate::summary!(target: ::core::module_path!(), level: $level, $name $(, $label_key $(=> $label_value)?)*)
     };
     ($name:expr $(, $label_key:expr $(=> $label_value:expr)?)* $(,)?) => {
-        $crate::histogram!(target: ::std::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
+        $crate::summary!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key $(=> $label_value)?)*)
     };
 }
 
+/// Registers a counter once, returning a cached handle on subsequent calls.
+///
+/// The first call made while a recorder is installed registers the counter, as
+/// [`counter!`](crate::counter) would, and caches the handle in a static at the callsite. Every
+/// later call returns the cached handle directly, skipping building the key and looking the metric
+/// up in the recorder, which makes this suitable for hot paths. Calls made while no recorder is
+/// installed return a no-op handle, and aren't cached.
+///
+/// As the handle is cached, the name and labels are only evaluated once, and must not change from
+/// one call to the next: only constant label sets are supported, given as key/value pairs.
+/// Likewise, the handle remains bound to the recorder which was current when it was registered,
+/// even if a different local recorder is set later on.
+///
+/// # Example
+/// ```
+/// # use metrics::static_counter;
+/// # fn main() {
+/// // A basic counter:
+/// let counter = static_counter!("some_metric_name");
+/// counter.increment(1);
+///
+/// // Specifying constant labels inline:
+/// const SERVICE_LABEL: &'static str = "service";
+/// const SERVICE_HTTP: &'static str = "http";
+/// let counter = static_counter!("some_metric_name", SERVICE_LABEL => SERVICE_HTTP);
+/// counter.increment(1);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[macro_export]
+macro_rules! static_counter {
+    (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {{
+        static HANDLE: $crate::__private::OnceLock<$crate::Counter> = $crate::__private::OnceLock::new();
+        static NOOP: $crate::Counter = $crate::Counter::noop();
+
+        match HANDLE.get() {
+            ::core::option::Option::Some(handle) => handle,
+            ::core::option::Option::None => {
+                let metadata = $crate::metadata_var!($target, $level);
+                let handle = $crate::try_with_recorder(|recorder| {
+                    $crate::register_var!(recorder, register_counter, metadata, $name $(, $label_key => $label_value)*)
+                })
+                .flatten();
+                match handle {
+                    ::core::option::Option::Some(handle) => HANDLE.get_or_init(|| handle),
+                    ::core::option::Option::None => &NOOP,
+                }
+            }
+        }
+    }};
+    (target: $target:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_counter!(target: $target, level: $crate::Level::INFO, $name $(, $label_key => $label_value)*)
+    };
+    (level: $level:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_counter!(target: ::core::module_path!(), level: $level, $name $(, $label_key => $label_value)*)
+    };
+    ($name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_counter!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key => $label_value)*)
+    };
+}
+
+/// Registers a gauge once, returning a cached handle on subsequent calls.
+///
+/// The first call made while a recorder is installed registers the gauge, as
+/// [`gauge!`](crate::gauge) would, and caches the handle in a static at the callsite. Every later
+/// call returns the cached handle directly, skipping building the key and looking the metric up in
+/// the recorder, which makes this suitable for hot paths. Calls made while no recorder is installed
+/// return a no-op handle, and aren't cached.
+///
+/// As the handle is cached, the name and labels are only evaluated once, and must not change from
+/// one call to the next: only constant label sets are supported, given as key/value pairs.
+/// Likewise, the handle remains bound to the recorder which was current when it was registered,
+/// even if a different local recorder is set later on.
+///
+/// # Example
+/// ```
+/// # use metrics::static_gauge;
+/// # fn main() {
+/// // A basic gauge:
+/// let gauge = static_gauge!("some_metric_name");
+/// gauge.set(42.0);
+///
+/// // Specifying constant labels inline:
+/// const SERVICE_LABEL: &'static str = "service";
+/// const SERVICE_HTTP: &'static str = "http";
+/// let gauge = static_gauge!("some_metric_name", SERVICE_LABEL => SERVICE_HTTP);
+/// gauge.set(42.0);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[macro_export]
+macro_rules! static_gauge {
+    (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {{
+        static HANDLE: $crate::__private::OnceLock<$crate::Gauge> = $crate::__private::OnceLock::new();
+        static NOOP: $crate::Gauge = $crate::Gauge::noop();
+
+        match HANDLE.get() {
+            ::core::option::Option::Some(handle) => handle,
+            ::core::option::Option::None => {
+                let metadata = $crate::metadata_var!($target, $level);
+                let handle = $crate::try_with_recorder(|recorder| {
+                    $crate::register_var!(recorder, register_gauge, metadata, $name $(, $label_key => $label_value)*)
+                })
+                .flatten();
+                match handle {
+                    ::core::option::Option::Some(handle) => HANDLE.get_or_init(|| handle),
+                    ::core::option::Option::None => &NOOP,
+                }
+            }
+        }
+    }};
+    (target: $target:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_gauge!(target: $target, level: $crate::Level::INFO, $name $(, $label_key => $label_value)*)
+    };
+    (level: $level:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_gauge!(target: ::core::module_path!(), level: $level, $name $(, $label_key => $label_value)*)
+    };
+    ($name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_gauge!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key => $label_value)*)
+    };
+}
+
+/// Registers a histogram once, returning a cached handle on subsequent calls.
+///
+/// The first call made while a recorder is installed registers the histogram, as
+/// [`histogram!`](crate::histogram) would, and caches the handle in a static at the callsite. Every
+/// later call returns the cached handle directly, skipping building the key and looking the metric
+/// up in the recorder, which makes this suitable for hot paths. Calls made while no recorder is
+/// installed return a no-op handle, and aren't cached.
+///
+/// As the handle is cached, the name and labels are only evaluated once, and must not change from
+/// one call to the next: only constant label sets are supported, given as key/value pairs.
+/// Likewise, the handle remains bound to the recorder which was current when it was registered,
+/// even if a different local recorder is set later on.
+///
+/// # Example
+/// ```
+/// # use metrics::static_histogram;
+/// # fn main() {
+/// // A basic histogram:
+/// let histogram = static_histogram!("some_metric_name");
+/// histogram.record(0.25);
+///
+/// // Specifying constant labels inline:
+/// const SERVICE_LABEL: &'static str = "service";
+/// const SERVICE_HTTP: &'static str = "http";
+/// let histogram = static_histogram!("some_metric_name", SERVICE_LABEL => SERVICE_HTTP);
+/// histogram.record(0.25);
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[macro_export]
+macro_rules! static_histogram {
+    (target: $target:expr, level: $level:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {{
+        static HANDLE: $crate::__private::OnceLock<$crate::Histogram> = $crate::__private::OnceLock::new();
+        static NOOP: $crate::Histogram = $crate::Histogram::noop();
+
+        match HANDLE.get() {
+            ::core::option::Option::Some(handle) => handle,
+            ::core::option::Option::None => {
+                let metadata = $crate::metadata_var!($target, $level);
+                let handle = $crate::try_with_recorder(|recorder| {
+                    $crate::register_var!(recorder, register_histogram, metadata, $name $(, $label_key => $label_value)*)
+                })
+                .flatten();
+                match handle {
+                    ::core::option::Option::Some(handle) => HANDLE.get_or_init(|| handle),
+                    ::core::option::Option::None => &NOOP,
+                }
+            }
+        }
+    }};
+    (target: $target:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_histogram!(target: $target, level: $crate::Level::INFO, $name $(, $label_key => $label_value)*)
+    };
+    (level: $level:expr, $name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_histogram!(target: ::core::module_path!(), level: $level, $name $(, $label_key => $label_value)*)
+    };
+    ($name:expr $(, $label_key:expr => $label_value:expr)* $(,)?) => {
+        $crate::static_histogram!(target: ::core::module_path!(), level: $crate::Level::INFO, $name $(, $label_key => $label_value)*)
+    };
+}
+
+/// Builds a reusable set of labels.
+///
+/// The labels are built once, into a static at the callsite, and returned as a
+/// [`LabelSet`](crate::LabelSet), which can be passed by value or by reference to any of the
+/// emission macros in place of a list of labels.  Keys built from a label set borrow its labels
+/// rather than copying them, which avoids allocating the same labels over and over when a metric is
+/// emitted in a hot loop.
+///
+/// When the keys and values are all string literals, the labels are built at compile time.
+/// Otherwise, they're evaluated once, the first time the callsite is reached, and must not change
+/// from one call to the next, which requires the `std` feature.
+///
+/// # Example
+/// ```
+/// # use metrics::{counter, histogram, labels};
+/// # fn main() {
+/// let labels = labels!("service" => "http", "method" => "GET");
+/// for _ in 0..10 {
+///     counter!("requests_total", &labels).increment(1);
+///     histogram!("request_duration_seconds", &labels).record(0.25);
+/// }
+///
+/// // Values which aren't literals are evaluated only once:
+/// # let hostname = || "localhost";
+/// let labels = labels!("service" => "http", "host" => hostname());
+/// counter!("requests_total", &labels).increment(1);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! labels {
+    ($($label_key:literal => $label_value:literal),* $(,)?) => {{
+        static LABELS: [$crate::Label; $crate::count!($($label_key)*)] = [
+            $($crate::Label::from_static_parts($label_key, $label_value)),*
+        ];
+        $crate::LabelSet::from_static(&LABELS)
+    }};
+    ($($label_key:expr => $label_value:expr),* $(,)?) => {{
+        static LABELS: $crate::__private::OnceLock<$crate::__private::vec::Vec<$crate::Label>> =
+            $crate::__private::OnceLock::new();
+        $crate::LabelSet::from_static(LABELS.get_or_init(|| {
+            $crate::__private::vec![$($crate::Label::typed($label_key, $label_value)),*]
+        }))
+    }};
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! describe {
     ($method:ident, $name:expr, $unit:expr, $description:expr $(,)?) => {{
+        $crate::validate_name!($name);
         $crate::with_recorder(|recorder| {
             recorder.$method(
                 ::core::convert::Into::into($name),
@@ -266,6 +901,7 @@ macro_rules! describe {
         });
     }};
     ($method:ident, $name:expr, $description:expr $(,)?) => {{
+        $crate::validate_name!($name);
         $crate::with_recorder(|recorder| {
             recorder.$method(
                 ::core::convert::Into::into($name),
@@ -413,3 +1049,225 @@ macro_rules! describe_histogram {
         $crate::describe!(describe_histogram, $name, $description)
     };
 }
+
+/// Describes a summary.
+///
+/// Summaries measure the distribution of values for a given set of measurements, and are exported
+/// as a count, a sum, and a set of quantiles computed from the observed values.
+///
+/// Metrics can be described with a free-form string, and optionally, a unit can be provided to
+/// describe the value and/or rate of the metric measurements.  Whether or not the installed
+/// recorder does anything with the description, or optional unit, is implementation defined.
+///
+/// Metric names are shown below using string literals, but they can also be owned `String` values,
+/// which includes using macros such as `format!` directly at the callsite. String literals are
+/// preferred for performance where possible.
+///
+/// # Example
+/// ```
+/// # #![no_implicit_prelude]
+/// # use ::std::convert::From;
+/// # use ::std::format;
+/// # use ::std::string::String;
+/// # use metrics::describe_summary;
+/// # use metrics::Unit;
+/// # fn main() {
+/// // A basic summary:
+/// describe_summary!("some_metric_name", "my favorite summary");
+///
+/// // Providing a unit for a summary:
+/// describe_summary!("some_metric_name", Unit::Bytes, "my favorite summary");
+///
+/// // As mentioned in the documentation, metric names also can be owned strings, including ones
+/// // generated at the callsite via things like `format!`:
+/// let name = String::from("some_owned_metric_name");
+/// describe_summary!(name, "my favorite summary");
+///
+/// describe_summary!(format!("{}_via_format", "name"), "my favorite summary");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! describe_summary {
+    ($name:expr, $unit:expr, $description:expr $(,)?) => {
+        $crate::describe!(describe_summary, $name, $unit, $description)
+    };
+    ($name:expr, $description:expr $(,)?) => {
+        $crate::describe!(describe_summary, $name, $description)
+    };
+}
+
+/// Describes a metric with a set of attributes.
+///
+/// Attributes are typed pieces of information about a metric, such as the team that owns it, or
+/// whether it is deprecated, which go beyond its unit and description.  Any type implementing
+/// [`Attribute`](crate::Attribute) can be given, and attributes apply to all metrics with the given
+/// name, regardless of their kind.  Whether or not the installed recorder does anything with the
+/// attributes is implementation defined.
+///
+/// Metric names are shown below using string literals, but they can also be owned `String` values,
+/// which includes using macros such as `format!` directly at the callsite. String literals are
+/// preferred for performance where possible.
+///
+/// # Example
+/// ```
+/// # #![no_implicit_prelude]
+/// # use ::std::convert::From;
+/// # use ::std::format;
+/// # use ::std::string::String;
+/// # use metrics::describe_attributes;
+/// # use metrics::{BucketHint, Deprecated, Owner, Stability};
+/// # fn main() {
+/// // A single attribute:
+/// describe_attributes!("some_metric_name", Owner::new("storage-team"));
+///
+/// // Multiple attributes:
+/// describe_attributes!(
+///     "some_metric_name",
+///     Stability::Experimental,
+///     BucketHint::new([0.005, 0.01, 0.05, 0.1, 0.5, 1.0]),
+/// );
+///
+/// // As mentioned in the documentation, metric names also can be owned strings, including ones
+/// // generated at the callsite via things like `format!`:
+/// let name = String::from("some_owned_metric_name");
+/// describe_attributes!(name, Deprecated::with_note("use `some_metric_name` instead"));
+///
+/// describe_attributes!(format!("{}_via_format", "name"), Deprecated::new());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! describe_attributes {
+    ($name:expr, $($attribute:expr),+ $(,)?) => {{
+        $crate::with_recorder(|recorder| {
+            recorder.describe_attributes(
+                ::core::convert::Into::into($name),
+                $crate::Attributes::new()$(.with($attribute))+,
+            );
+        });
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! declare {
+    ($describe:ident, $register:ident, $name:expr, $($rest:expr),+ $(,)?) => {{
+        $crate::validate_name!($name);
+        let name: $crate::KeyName = ::core::convert::Into::into($name);
+        $crate::$describe!(::core::clone::Clone::clone(&name), $($rest),+);
+        $crate::$register!(name)
+    }};
+}
+
+/// Describes and registers a counter, returning its handle.
+///
+/// This is equivalent to calling [`describe_counter!`](crate::describe_counter) followed by
+/// [`counter!`](crate::counter), so that the unit and description of a metric live alongside the
+/// code that emits it, rather than in a separate call that is easily forgotten.  As describing a
+/// metric is typically only needed once, this is best suited to registering a handle up front and
+/// holding on to it, rather than being called every time the metric is updated.
+///
+/// The name is only evaluated once.
+///
+/// # Example
+/// ```
+/// # use metrics::declare_counter;
+/// # use metrics::Unit;
+/// # fn main() {
+/// // A basic counter:
+/// let counter = declare_counter!("some_metric_name", "my favorite counter");
+/// counter.increment(1);
+///
+/// // Providing a unit for a counter:
+/// let counter = declare_counter!("some_metric_name", Unit::Bytes, "my favorite counter");
+/// counter.increment(1);
+///
+/// // Metric names can also be owned strings, including ones generated at the callsite:
+/// let counter = declare_counter!(format!("{}_via_format", "name"), "my favorite counter");
+/// counter.increment(1);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! declare_counter {
+    ($name:expr, $unit:expr, $description:expr $(,)?) => {
+        $crate::declare!(describe_counter, counter, $name, $unit, $description)
+    };
+    ($name:expr, $description:expr $(,)?) => {
+        $crate::declare!(describe_counter, counter, $name, $description)
+    };
+}
+
+/// Describes and registers a gauge, returning its handle.
+///
+/// This is equivalent to calling [`describe_gauge!`](crate::describe_gauge) followed by
+/// [`gauge!`](crate::gauge), so that the unit and description of a metric live alongside the
+/// code that emits it, rather than in a separate call that is easily forgotten.  As describing a
+/// metric is typically only needed once, this is best suited to registering a handle up front and
+/// holding on to it, rather than being called every time the metric is updated.
+///
+/// The name is only evaluated once.
+///
+/// # Example
+/// ```
+/// # use metrics::declare_gauge;
+/// # use metrics::Unit;
+/// # fn main() {
+/// // A basic gauge:
+/// let gauge = declare_gauge!("some_metric_name", "my favorite gauge");
+/// gauge.set(42.0);
+///
+/// // Providing a unit for a gauge:
+/// let gauge = declare_gauge!("some_metric_name", Unit::Bytes, "my favorite gauge");
+/// gauge.set(42.0);
+///
+/// // Metric names can also be owned strings, including ones generated at the callsite:
+/// let gauge = declare_gauge!(format!("{}_via_format", "name"), "my favorite gauge");
+/// gauge.set(42.0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! declare_gauge {
+    ($name:expr, $unit:expr, $description:expr $(,)?) => {
+        $crate::declare!(describe_gauge, gauge, $name, $unit, $description)
+    };
+    ($name:expr, $description:expr $(,)?) => {
+        $crate::declare!(describe_gauge, gauge, $name, $description)
+    };
+}
+
+/// Describes and registers a histogram, returning its handle.
+///
+/// This is equivalent to calling [`describe_histogram!`](crate::describe_histogram) followed by
+/// [`histogram!`](crate::histogram), so that the unit and description of a metric live alongside the
+/// code that emits it, rather than in a separate call that is easily forgotten.  As describing a
+/// metric is typically only needed once, this is best suited to registering a handle up front and
+/// holding on to it, rather than being called every time the metric is updated.
+///
+/// The name is only evaluated once.
+///
+/// # Example
+/// ```
+/// # use metrics::declare_histogram;
+/// # use metrics::Unit;
+/// # fn main() {
+/// // A basic histogram:
+/// let histogram = declare_histogram!("some_metric_name", "my favorite histogram");
+/// histogram.record(0.25);
+///
+/// // Providing a unit for a histogram:
+/// let histogram = declare_histogram!("some_metric_name", Unit::Seconds, "my favorite histogram");
+/// histogram.record(0.25);
+///
+/// // Metric names can also be owned strings, including ones generated at the callsite:
+/// let histogram = declare_histogram!(format!("{}_via_format", "name"), "my favorite histogram");
+/// histogram.record(0.25);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! declare_histogram {
+    ($name:expr, $unit:expr, $description:expr $(,)?) => {
+        $crate::declare!(describe_histogram, histogram, $name, $unit, $description)
+    };
+    ($name:expr, $description:expr $(,)?) => {
+        $crate::declare!(describe_histogram, histogram, $name, $description)
+    };
+}
@@ -1,21 +1,69 @@
+use core::{fmt, str::FromStr};
+
 /// Describes the level of verbosity of a metric event.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Levels are ordered from the most verbose, [`Level::TRACE`], to the least verbose,
+/// [`Level::ERROR`], such that `Level::TRACE < Level::ERROR`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Level(LevelInner);
 
 impl Level {
-    /// The "error" level.
+    /// The "trace" level.
     pub const TRACE: Self = Self(LevelInner::Trace);
-    /// The "warn" level.
+    /// The "debug" level.
     pub const DEBUG: Self = Self(LevelInner::Debug);
     /// The "info" level.
     pub const INFO: Self = Self(LevelInner::Info);
-    /// The "debug" level.
+    /// The "warn" level.
     pub const WARN: Self = Self(LevelInner::Warn);
-    /// The "trace" level.
+    /// The "error" level.
     pub const ERROR: Self = Self(LevelInner::Error);
+
+    /// Returns the name of this level, in lowercase.
+    pub fn as_str(&self) -> &'static str {
+        match self.0 {
+            LevelInner::Trace => "trace",
+            LevelInner::Debug => "debug",
+            LevelInner::Info => "info",
+            LevelInner::Warn => "warn",
+            LevelInner::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
+impl FromStr for Level {
+    type Err = ParseLevelError;
+
+    /// Parses a level from its name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Level::TRACE, Level::DEBUG, Level::INFO, Level::WARN, Level::ERROR]
+            .iter()
+            .find(|level| s.eq_ignore_ascii_case(level.as_str()))
+            .cloned()
+            .ok_or(ParseLevelError(()))
+    }
+}
+
+/// Error returned when parsing a [`Level`] from a string fails.
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelError(());
+
+impl fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid level, expected one of: trace, debug, info, warn, error")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseLevelError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum LevelInner {
     Trace = 0,
     Debug = 1,
@@ -65,3 +113,25 @@ impl<'a> Metadata<'a> {
         self.module_path
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Level;
+
+    #[test]
+    fn test_level_ordering() {
+        assert!(Level::TRACE < Level::DEBUG);
+        assert!(Level::DEBUG < Level::INFO);
+        assert!(Level::INFO < Level::WARN);
+        assert!(Level::WARN < Level::ERROR);
+    }
+
+    #[test]
+    fn test_level_parse() {
+        for level in [Level::TRACE, Level::DEBUG, Level::INFO, Level::WARN, Level::ERROR] {
+            assert_eq!(level.as_str().parse::<Level>(), Ok(level.clone()));
+            assert_eq!(level.to_string().to_uppercase().parse::<Level>(), Ok(level));
+        }
+        assert!("verbose".parse::<Level>().is_err());
+    }
+}
@@ -1,69 +1,112 @@
 use super::{Recorder, SetRecorderError};
-use std::{
-    cell::UnsafeCell,
-    sync::atomic::{AtomicUsize, Ordering},
+use alloc::boxed::Box;
+use core::{
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
 };
 
-/// The recorder is uninitialized.
-const UNINITIALIZED: usize = 0;
+/// A reference to an installed recorder.
+///
+/// Recorders are only ever referred to through a thin pointer to one of these, so that the
+/// currently installed recorder can be swapped atomically.
+type Slot = &'static dyn Recorder;
 
-/// The recorder is currently being initialized.
-const INITIALIZING: usize = 1;
+/// Leaks the given recorder, returning a pointer to a slot which refers to it.
+///
+/// Along with the slot, the raw pointer to the recorder itself is returned, so that ownership of it
+/// can be reclaimed via [`reclaim`] if the slot was never published.
+fn leak<R>(recorder: R) -> (*mut Slot, *mut R)
+where
+    R: Recorder + 'static,
+{
+    let recorder = Box::into_raw(Box::new(recorder));
 
-/// The recorder has been initialized successfully and can be read.
-const INITIALIZED: usize = 2;
+    // SAFETY: The pointer was just created from a box, so it's valid, and it's never freed unless
+    // the slot is never published.
+    let slot: Slot = unsafe { &*recorder };
+    (Box::into_raw(Box::new(slot)), recorder)
+}
+
+/// Reclaims ownership of a recorder leaked by [`leak`].
+///
+/// # Safety
+///
+/// The slot must never have been published, so that no references to the recorder can exist.
+unsafe fn reclaim<R>(slot: *mut Slot, recorder: *mut R) -> R {
+    drop(Box::from_raw(slot));
+    *Box::from_raw(recorder)
+}
 
 /// An specialized version of `OnceCell` for `Recorder`.
+///
+/// While the cell can normally only be set once, the recorder can be replaced, and later restored,
+/// via [`RecorderOnceCell::replace`].  Recorders which are replaced are never dropped, as other
+/// threads may still be holding a reference to them.
 pub struct RecorderOnceCell {
-    recorder: UnsafeCell<Option<&'static dyn Recorder>>,
-    state: AtomicUsize,
+    slot: AtomicPtr<Slot>,
 }
 
 impl RecorderOnceCell {
     /// Creates an uninitialized `RecorderOnceCell`.
     pub const fn new() -> Self {
-        Self { recorder: UnsafeCell::new(None), state: AtomicUsize::new(UNINITIALIZED) }
+        Self { slot: AtomicPtr::new(ptr::null_mut()) }
     }
 
     pub fn set<R>(&self, recorder: R) -> Result<(), SetRecorderError<R>>
     where
         R: Recorder + 'static,
     {
-        // Try and transition the cell from `UNINITIALIZED` to `INITIALIZING`, which would give
-        // us exclusive access to set the recorder.
-        match self.state.compare_exchange(
-            UNINITIALIZED,
-            INITIALIZING,
-            Ordering::Acquire,
-            Ordering::Relaxed,
-        ) {
-            Ok(UNINITIALIZED) => {
-                unsafe {
-                    // SAFETY: Access is unique because we can only be here if we won the race
-                    // to transition from `UNINITIALIZED` to `INITIALIZING` above.
-                    self.recorder.get().write(Some(Box::leak(Box::new(recorder))));
-                }
+        // Check up front, so that we don't need to allocate if the cell has already been set.
+        if !self.slot.load(Ordering::Acquire).is_null() {
+            return Err(SetRecorderError(recorder));
+        }
 
-                // Mark the recorder as initialized, which will make it visible to readers.
-                self.state.store(INITIALIZED, Ordering::Release);
-                Ok(())
+        let (slot, raw) = leak(recorder);
+        match self.slot.compare_exchange(ptr::null_mut(), slot, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => Ok(()),
+            Err(_) => {
+                // SAFETY: We lost the race to set the cell, so the slot was never published.
+                Err(SetRecorderError(unsafe { reclaim(slot, raw) }))
             }
-            _ => Err(SetRecorderError(recorder)),
         }
     }
 
+    /// Replaces the recorder in the cell, whether or not it has been set, returning a token which
+    /// can be used to restore the previous recorder.
+    pub fn replace<R>(&self, recorder: R) -> Previous
+    where
+        R: Recorder + 'static,
+    {
+        let (slot, _) = leak(recorder);
+        Previous(self.slot.swap(slot, Ordering::AcqRel))
+    }
+
+    /// Clears the cell, whether or not it has been set, returning a token which can be used to
+    /// restore the previous recorder.
+    pub fn clear(&self) -> Previous {
+        Previous(self.slot.swap(ptr::null_mut(), Ordering::AcqRel))
+    }
+
+    /// Restores the recorder that was in the cell before the call to `replace` or `clear` which
+    /// returned the given token.
+    pub fn restore(&self, previous: Previous) {
+        self.slot.store(previous.0, Ordering::Release);
+    }
+
     pub fn try_load(&self) -> Option<&'static dyn Recorder> {
-        if self.state.load(Ordering::Acquire) != INITIALIZED {
-            None
-        } else {
-            // SAFETY: If the state is `INITIALIZED`, then we know that the recorder has been
-            // installed and is safe to read.
-            unsafe { self.recorder.get().read() }
-        }
+        let slot = self.slot.load(Ordering::Acquire);
+
+        // SAFETY: Slots are never freed once published, and are only published once fully
+        // initialized, so any non-null pointer we load is valid for the rest of the program.
+        unsafe { slot.as_ref().copied() }
     }
 }
 
-// SAFETY: We can only mutate through `set`, which is protected by the `state` and unsafe
-// function where the caller has to guarantee synced-ness.
-unsafe impl Send for RecorderOnceCell {}
-unsafe impl Sync for RecorderOnceCell {}
+/// The recorder which was in a `RecorderOnceCell` before it was replaced, if any.
+pub struct Previous(*mut Slot);
+
+// SAFETY: The pointer is only ever used to restore the recorder to the cell, which itself is
+// `Send` and `Sync`.
+unsafe impl Send for Previous {}
+unsafe impl Sync for Previous {}
@@ -1,4 +1,4 @@
-use std::{error::Error, fmt};
+use core::fmt;
 
 const SET_RECORDER_ERROR: &str =
     "attempted to set a recorder after the metrics system was already initialized";
@@ -25,4 +25,5 @@ impl<R> fmt::Display for SetRecorderError<R> {
     }
 }
 
-impl<R> Error for SetRecorderError<R> {}
+#[cfg(feature = "std")]
+impl<R> std::error::Error for SetRecorderError<R> {}
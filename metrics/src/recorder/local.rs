@@ -0,0 +1,103 @@
+use core::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    ptr::NonNull,
+    task::{Context, Poll},
+};
+
+use super::Recorder;
+
+thread_local! {
+    pub(super) static LOCAL_RECORDER: Cell<Option<NonNull<dyn Recorder>>> = Cell::new(None);
+}
+
+/// Guard for setting a local recorder.
+///
+/// When using a local recorder, we take a reference to the recorder and only hold it for as long as
+/// the duration of the closure. However, we must store this reference in a static variable
+/// (thread-local storage) so that it can be accessed by the macros. This guard ensures that the
+/// pointer we store to the reference is replaced with the previous local recorder, if any, when the
+/// guard is dropped, so that it can't be used after the closure has finished, even if the closure
+/// panics and unwinds the stack.
+struct LocalRecorderGuard {
+    previous: Option<NonNull<dyn Recorder>>,
+}
+
+impl LocalRecorderGuard {
+    /// Creates a new `LocalRecorderGuard` and sets the thread-local recorder.
+    fn new(recorder: &dyn Recorder) -> Self {
+        // SAFETY: While we take a lifetime-less pointer to the given reference, the reference we
+        // derive _from_ the pointer is never given a lifetime that exceeds the lifetime of the
+        // input reference.
+        let recorder_ptr = unsafe { NonNull::new_unchecked(recorder as *const _ as *mut _) };
+
+        let previous =
+            LOCAL_RECORDER.with(|local_recorder| local_recorder.replace(Some(recorder_ptr)));
+
+        Self { previous }
+    }
+}
+
+impl Drop for LocalRecorderGuard {
+    fn drop(&mut self) {
+        // Restore the previous thread-local recorder. Guards are always dropped in the reverse order
+        // they were created in, so the previous recorder is still valid at this point.
+        LOCAL_RECORDER.with(|local_recorder| {
+            local_recorder.set(self.previous);
+        });
+    }
+}
+
+/// A future which sets a local recorder whenever it is polled.
+///
+/// Created by [`with_local_recorder_async`].
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct LocalRecorderFuture<'a, R, F> {
+    recorder: &'a R,
+    future: F,
+}
+
+impl<'a, R, F> Future for LocalRecorderFuture<'a, R, F>
+where
+    R: Recorder,
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: The inner future is structurally pinned: it is never moved out of `self`, and we
+        // never hand out an unpinned reference to it.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let _local = LocalRecorderGuard::new(this.recorder);
+        future.poll(cx)
+    }
+}
+
+/// Runs the closure with the given recorder set as the global recorder for the duration.
+///
+/// The recorder is only set for the current thread, and takes precedence over the global recorder.
+/// Calls can be nested, in which case the innermost recorder is used, and the outer recorder is
+/// restored once the inner closure returns.
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn with_local_recorder<T>(recorder: &dyn Recorder, f: impl FnOnce() -> T) -> T {
+    let _local = LocalRecorderGuard::new(recorder);
+    f()
+}
+
+/// Wraps the future so that the given recorder is set as the local recorder whenever it is polled.
+///
+/// This is the asynchronous equivalent of [`with_local_recorder`]: as futures may be polled on any
+/// thread, and other futures may run on the same thread in between, the recorder is set only for
+/// the duration of each call to `poll`, rather than for the lifetime of the future.
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn with_local_recorder_async<R, F>(recorder: &R, future: F) -> LocalRecorderFuture<'_, R, F>
+where
+    R: Recorder,
+    F: Future,
+{
+    LocalRecorderFuture { recorder, future }
+}
@@ -1,23 +1,27 @@
-use std::{cell::Cell, ptr::NonNull};
+use alloc::{boxed::Box, sync::Arc};
 
 mod cell;
-use self::cell::RecorderOnceCell;
+use self::cell::{Previous, RecorderOnceCell};
 
 mod errors;
 pub use self::errors::SetRecorderError;
 
+#[cfg(feature = "std")]
+mod local;
+#[cfg(feature = "std")]
+pub use self::local::{with_local_recorder, with_local_recorder_async, LocalRecorderFuture};
+
 mod noop;
 pub use self::noop::NoopRecorder;
 
-use crate::{Counter, Gauge, Histogram, Key, KeyName, Metadata, SharedString, Unit};
+use crate::{
+    Attributes, Counter, Gauge, Histogram, Key, KeyName, Metadata, SharedString, Summary,
+    SummaryFn, Unit,
+};
 
 static NOOP_RECORDER: NoopRecorder = NoopRecorder;
 static GLOBAL_RECORDER: RecorderOnceCell = RecorderOnceCell::new();
 
-thread_local! {
-    static LOCAL_RECORDER: Cell<Option<NonNull<dyn Recorder>>> = Cell::new(None);
-}
-
 /// A trait for registering and recording metrics.
 ///
 /// This is the core trait that allows interoperability between exporter implementations and the
@@ -55,46 +59,143 @@ pub trait Recorder {
 
     /// Registers a histogram.
     fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram;
+
+    /// Describes a summary.
+    ///
+    /// Callers may provide the unit or a description of the summary being registered. Whether or
+    /// not a metric can be reregistered to provide a unit/description, if one was already passed
+    /// or not, as well as how units/descriptions are used by the underlying recorder, is an
+    /// implementation detail.
+    ///
+    /// By default, this describes a histogram with the same name, matching the default behavior of
+    /// [`register_summary`](Recorder::register_summary).
+    fn describe_summary(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.describe_histogram(key, unit, description)
+    }
+
+    /// Registers a summary.
+    ///
+    /// By default, this registers a histogram with the same key, and records every value of the
+    /// summary into it, for recorders which have no native support for summaries.
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        let histogram = self.register_histogram(key, metadata);
+        Summary::from_arc(Arc::new(HistogramSummary(histogram)))
+    }
+
+    /// Describes a metric with a set of attributes.
+    ///
+    /// Attributes apply to all metrics with the given name, regardless of their kind.  Recorders can
+    /// look up the attributes they understand via [`Attributes::get`], or visit all of them via
+    /// [`Attributes::iter`], and should ignore any they don't understand.
+    ///
+    /// By default, attributes are ignored.
+    fn describe_attributes(&self, key: KeyName, attributes: Attributes) {
+        let _ = (key, attributes);
+    }
+
+    /// Returns whether metrics with the given name and metadata may be registered.
+    ///
+    /// The registration macros call this before building the key of a metric, and only evaluate its
+    /// labels, including lazily evaluated ones, if it returns `true`.  Recorders, and layers, which
+    /// discard metrics based on their name or metadata alone should return `false` for them, so that
+    /// callers don't pay for labels which would be thrown away.  Returning `true` doesn't oblige the
+    /// recorder to keep the metric once it's registered.
+    ///
+    /// By default, returns `true`.
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        let _ = (key_name, metadata);
+        true
+    }
+
+    /// Flushes any buffered metrics.
+    ///
+    /// Push-based recorders, which periodically send metrics elsewhere, should send any metrics
+    /// which have been recorded since the last push, and return once they've done so, so that
+    /// callers can make sure nothing is lost before the process exits.  Callers will typically
+    /// flush the current recorder via [`flush`](crate::flush).
+    ///
+    /// By default, this does nothing.
+    fn flush(&self) {}
+
+    /// Shuts down the recorder.
+    ///
+    /// Recorders should flush any buffered metrics, as with [`flush`](Recorder::flush), and stop
+    /// any background work, such as upkeep tasks or network connections.  Metrics recorded after a
+    /// recorder has been shut down may be ignored.
+    ///
+    /// By default, this does nothing.
+    fn shutdown(&self) {}
 }
 
-/// Guard for setting a local recorder.
-///
-/// When using a local recorder, we take a reference to the recorder and only hold it for as long as
-/// the duration of the closure. However, we must store this reference in a static variable
-/// (thread-local storage) so that it can be accessed by the macros. This guard ensures that the
-/// pointer we store to the reference is cleared when the guard is dropped, so that it can't be used
-/// after the closure has finished, even if the closure panics and unwinds the stack.
-struct LocalRecorderGuard;
-
-impl LocalRecorderGuard {
-    /// Creates a new `LocalRecorderGuard` and sets the thread-local recorder.
-    fn new(recorder: &dyn Recorder) -> Self {
-        // SAFETY: While we take a lifetime-less pointer to the given reference, the reference we
-        // derive _from_ the pointer is never given a lifetime that exceeds the lifetime of the
-        // input reference.
-        let recorder_ptr = unsafe { NonNull::new_unchecked(recorder as *const _ as *mut _) };
-
-        LOCAL_RECORDER.with(|local_recorder| {
-            local_recorder.set(Some(recorder_ptr));
-        });
+/// A summary which records its values into a histogram.
+///
+/// Used by the default implementation of [`Recorder::register_summary`].
+struct HistogramSummary(Histogram);
 
-        Self
+impl SummaryFn for HistogramSummary {
+    fn record(&self, value: f64) {
+        self.0.record(value)
     }
 }
 
-impl Drop for LocalRecorderGuard {
-    fn drop(&mut self) {
-        // Clear the thread-local recorder.
-        LOCAL_RECORDER.with(|local_recorder| {
-            local_recorder.set(None);
-        });
+impl<R> Recorder for Box<R>
+where
+    R: Recorder + ?Sized,
+{
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        (**self).describe_counter(key, unit, description)
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        (**self).describe_gauge(key, unit, description)
+    }
+
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        (**self).describe_histogram(key, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        (**self).register_counter(key, metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        (**self).register_gauge(key, metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        (**self).register_histogram(key, metadata)
+    }
+
+    fn describe_summary(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        (**self).describe_summary(key, unit, description)
+    }
+
+    fn register_summary(&self, key: &Key, metadata: &Metadata<'_>) -> Summary {
+        (**self).register_summary(key, metadata)
+    }
+
+    fn describe_attributes(&self, key: KeyName, attributes: Attributes) {
+        (**self).describe_attributes(key, attributes)
+    }
+
+    fn enabled(&self, key_name: &KeyName, metadata: &Metadata<'_>) -> bool {
+        (**self).enabled(key_name, metadata)
+    }
+
+    fn flush(&self) {
+        (**self).flush()
+    }
+
+    fn shutdown(&self) {
+        (**self).shutdown()
     }
 }
 
 /// Sets the global recorder.
 ///
-/// This function may only be called once in the lifetime of a program. Any metrics recorded
-/// before this method is called will be completely ignored.
+/// This function may only be called once in the lifetime of a program, unless the global recorder
+/// is cleared via [`clear_global_recorder`]. Any metrics recorded before this method is called will
+/// be completely ignored.
 ///
 /// This function does not typically need to be called manually.  Metrics implementations should
 /// provide an initialization method that installs the recorder internally.
@@ -109,41 +210,120 @@ where
     GLOBAL_RECORDER.set(recorder)
 }
 
-/// Runs the closure with the given recorder set as the global recorder for the duration.
-pub fn with_local_recorder<T>(recorder: &dyn Recorder, f: impl FnOnce() -> T) -> T {
-    let _local = LocalRecorderGuard::new(recorder);
-    f()
+/// Guard which restores the previous global recorder when dropped.
+///
+/// Created by [`replace_global_recorder`] and [`clear_global_recorder`].
+#[must_use = "the previous global recorder is restored as soon as the guard is dropped"]
+pub struct GlobalRecorderGuard {
+    previous: Option<Previous>,
 }
 
-/// Runs the closure with a reference to the current recorder for this scope.
+impl Drop for GlobalRecorderGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            GLOBAL_RECORDER.restore(previous);
+        }
+    }
+}
+
+/// Replaces the global recorder, whether or not one has already been set.
 ///
-/// If a local recorder has been set, it will be used. Otherwise, the global recorder will be used.
-/// If neither a local recorder or global recorder have been set, a no-op recorder will be used.
+/// Returns a guard which restores the previous global recorder, or lack thereof, when dropped.
+/// This is primarily intended for test harnesses which need to install different recorders, such
+/// as different exporters, over the course of a single process.  Guards should be dropped in the
+/// reverse order they were created in, otherwise the global recorder will be left set to whichever
+/// recorder the last guard to be dropped replaced.
 ///
-/// This is used primarily by the generated code from the convenience macros used to record metrics.
-/// It should typically not be necessary to call this function directly.
-#[doc(hidden)]
-pub fn with_recorder<T>(f: impl FnOnce(&dyn Recorder) -> T) -> T {
-    LOCAL_RECORDER.with(|local_recorder| {
+/// As other threads may still be using a recorder after it has been replaced, replaced recorders
+/// are never dropped.  This includes the given recorder, once the guard has been dropped.
+pub fn replace_global_recorder<R>(recorder: R) -> GlobalRecorderGuard
+where
+    R: Recorder + 'static,
+{
+    GlobalRecorderGuard { previous: Some(GLOBAL_RECORDER.replace(recorder)) }
+}
+
+/// Clears the global recorder, whether or not one has already been set.
+///
+/// Returns a guard which restores the previous global recorder, or lack thereof, when dropped.
+/// While the guard is held, metrics are recorded to the local recorder, if any, or are otherwise
+/// discarded, and a new global recorder can be installed via [`set_global_recorder`].
+///
+/// See [`replace_global_recorder`] for more information on the behavior of the guard.
+pub fn clear_global_recorder() -> GlobalRecorderGuard {
+    GlobalRecorderGuard { previous: Some(GLOBAL_RECORDER.clear()) }
+}
+
+/// Runs the closure with a reference to the current recorder for this scope, if any.
+///
+/// If a local recorder has been set, it will be used. Otherwise, the global recorder will be used,
+/// if it has been set.
+#[cfg(feature = "std")]
+fn with_current_recorder<T>(f: impl FnOnce(Option<&dyn Recorder>) -> T) -> T {
+    local::LOCAL_RECORDER.with(|local_recorder| {
         if let Some(recorder) = local_recorder.get() {
             // SAFETY: If we have a local recorder, we know that it is valid because it can only be
             // set during the duration of a closure that is passed to `with_local_recorder`, which
             // is the only time this method can be called and have a local recorder set. This
             // ensures that the lifetime of the recorder is valid for the duration of this method
             // call.
-            unsafe { f(recorder.as_ref()) }
-        } else if let Some(global_recorder) = GLOBAL_RECORDER.try_load() {
-            f(global_recorder)
+            unsafe { f(Some(recorder.as_ref())) }
         } else {
-            f(&NOOP_RECORDER)
+            f(GLOBAL_RECORDER.try_load())
         }
     })
 }
 
+/// Runs the closure with a reference to the global recorder, if it has been set.
+#[cfg(not(feature = "std"))]
+fn with_current_recorder<T>(f: impl FnOnce(Option<&dyn Recorder>) -> T) -> T {
+    f(GLOBAL_RECORDER.try_load())
+}
+
+/// Flushes any buffered metrics in the current recorder.
+///
+/// If a local recorder has been set, it will be flushed.  Otherwise, the global recorder, if any,
+/// will be flushed.  This should typically be called before the process exits, so that push-based
+/// recorders don't lose the metrics recorded since they last pushed.
+///
+/// See [`Recorder::flush`] for more details.
+pub fn flush() {
+    with_current_recorder(|recorder| {
+        if let Some(recorder) = recorder {
+            recorder.flush();
+        }
+    })
+}
+
+/// Runs the closure with a reference to the current recorder for this scope.
+///
+/// If a local recorder has been set, it will be used. Otherwise, the global recorder will be used.
+/// If neither a local recorder or global recorder have been set, a no-op recorder will be used.
+///
+/// This is used primarily by the generated code from the convenience macros used to record metrics.
+/// It should typically not be necessary to call this function directly.
+#[doc(hidden)]
+pub fn with_recorder<T>(f: impl FnOnce(&dyn Recorder) -> T) -> T {
+    with_current_recorder(|recorder| f(recorder.unwrap_or(&NOOP_RECORDER)))
+}
+
+/// Runs the closure with a reference to the current recorder for this scope, if there is one.
+///
+/// Unlike [`with_recorder`], the closure isn't run at all if neither a local recorder or global
+/// recorder have been set, which allows the registration macros to skip building keys, including
+/// evaluating lazy label values, for metrics which would be discarded anyway.
+///
+/// This is used primarily by the generated code from the convenience macros used to record metrics.
+/// It should typically not be necessary to call this function directly.
+#[doc(hidden)]
+pub fn try_with_recorder<T>(f: impl FnOnce(&dyn Recorder) -> T) -> Option<T> {
+    with_current_recorder(|recorder| recorder.map(f))
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     };
 
@@ -227,4 +407,531 @@ mod tests {
         drop(second_set_result);
         assert!(was_dropped.load(Ordering::SeqCst));
     }
+
+    static METADATA: crate::Metadata<'static> =
+        crate::Metadata::new(module_path!(), crate::Level::INFO, None);
+
+    /// Recorder which counts how many counters have been registered through it, or its clones.
+    #[derive(Clone, Default)]
+    struct CountingRecorder(Arc<AtomicUsize>);
+
+    impl CountingRecorder {
+        fn count(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Recorder for CountingRecorder {
+        fn describe_counter(
+            &self,
+            _: crate::KeyName,
+            _: Option<crate::Unit>,
+            _: crate::SharedString,
+        ) {
+        }
+        fn describe_gauge(
+            &self,
+            _: crate::KeyName,
+            _: Option<crate::Unit>,
+            _: crate::SharedString,
+        ) {
+        }
+        fn describe_histogram(
+            &self,
+            _: crate::KeyName,
+            _: Option<crate::Unit>,
+            _: crate::SharedString,
+        ) {
+        }
+
+        fn register_counter(&self, _: &crate::Key, _: &crate::Metadata<'_>) -> crate::Counter {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            crate::Counter::noop()
+        }
+
+        fn register_gauge(&self, _: &crate::Key, _: &crate::Metadata<'_>) -> crate::Gauge {
+            crate::Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &crate::Key, _: &crate::Metadata<'_>) -> crate::Histogram {
+            crate::Histogram::noop()
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn register_counter() {
+        super::with_recorder(register_counter_with);
+    }
+
+    fn register_counter_with(recorder: &dyn Recorder) {
+        let _ = recorder.register_counter(&crate::Key::from_name("c"), &METADATA);
+    }
+
+    #[test]
+    fn replaced_recorder_restored() {
+        let recorder_cell = RecorderOnceCell::new();
+        let first = CountingRecorder::default();
+        let second = CountingRecorder::default();
+        let third = CountingRecorder::default();
+
+        assert!(recorder_cell.set(first.clone()).is_ok());
+        let replaced = recorder_cell.replace(second.clone());
+        register_counter_with(recorder_cell.try_load().unwrap());
+        assert_eq!(second.count(), 1);
+
+        // Clearing the cell allows it to be set again.
+        let cleared = recorder_cell.clear();
+        assert!(recorder_cell.try_load().is_none());
+        assert!(recorder_cell.set(third.clone()).is_ok());
+        assert!(recorder_cell.set(CountingRecorder::default()).is_err());
+        register_counter_with(recorder_cell.try_load().unwrap());
+        assert_eq!(third.count(), 1);
+
+        recorder_cell.restore(cleared);
+        register_counter_with(recorder_cell.try_load().unwrap());
+        assert_eq!(second.count(), 2);
+
+        recorder_cell.restore(replaced);
+        register_counter_with(recorder_cell.try_load().unwrap());
+        assert_eq!((first.count(), second.count(), third.count()), (1, 2, 1));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn nested_local_recorders_restore_outer() {
+        use super::with_local_recorder;
+
+        let outer = CountingRecorder::default();
+        let inner = CountingRecorder::default();
+
+        with_local_recorder(&outer, || {
+            register_counter();
+            with_local_recorder(&inner, register_counter);
+            register_counter();
+        });
+
+        assert_eq!(outer.count(), 2);
+        assert_eq!(inner.count(), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn flush_reaches_current_recorder() {
+        use super::with_local_recorder;
+
+        /// Recorder which counts how many times it has been flushed.
+        struct FlushingRecorder(CountingRecorder);
+
+        impl Recorder for FlushingRecorder {
+            fn describe_counter(
+                &self,
+                _: crate::KeyName,
+                _: Option<crate::Unit>,
+                _: crate::SharedString,
+            ) {
+            }
+            fn describe_gauge(
+                &self,
+                _: crate::KeyName,
+                _: Option<crate::Unit>,
+                _: crate::SharedString,
+            ) {
+            }
+            fn describe_histogram(
+                &self,
+                _: crate::KeyName,
+                _: Option<crate::Unit>,
+                _: crate::SharedString,
+            ) {
+            }
+
+            fn register_counter(&self, _: &crate::Key, _: &crate::Metadata<'_>) -> crate::Counter {
+                crate::Counter::noop()
+            }
+
+            fn register_gauge(&self, _: &crate::Key, _: &crate::Metadata<'_>) -> crate::Gauge {
+                crate::Gauge::noop()
+            }
+
+            fn register_histogram(
+                &self,
+                _: &crate::Key,
+                _: &crate::Metadata<'_>,
+            ) -> crate::Histogram {
+                crate::Histogram::noop()
+            }
+
+            fn flush(&self) {
+                (self.0).0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        // Flushing without a recorder installed is a no-op.
+        super::flush();
+
+        let flushes = CountingRecorder::default();
+        let recorder: Box<dyn Recorder> = Box::new(FlushingRecorder(flushes.clone()));
+        with_local_recorder(&recorder, super::flush);
+        recorder.flush();
+        assert_eq!(flushes.count(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn lazy_labels_only_evaluated_when_enabled() {
+        use super::with_local_recorder;
+        use std::cell::Cell;
+
+        let evaluated = Cell::new(0);
+        let register = || {
+            let _ = crate::counter!("lazy", "status" => 200, "value" => || {
+                evaluated.set(evaluated.get() + 1);
+                "expensive"
+            });
+        };
+
+        register();
+        assert_eq!(evaluated.get(), 0);
+
+        // The no-op recorder reports every metric as disabled.
+        with_local_recorder(&super::NoopRecorder, register);
+        assert_eq!(evaluated.get(), 0);
+
+        let recorder = CountingRecorder::default();
+        with_local_recorder(&recorder, register);
+        assert_eq!(evaluated.get(), 1);
+        assert_eq!(recorder.count(), 1);
+    }
+
+    /// Recorder which counts how many values have been recorded into its histograms.
+    #[derive(Default)]
+    struct HistogramRecorder(Arc<AtomicUsize>);
+
+    impl HistogramRecorder {
+        fn count(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    struct CountingHistogram(Arc<AtomicUsize>);
+
+    impl crate::HistogramFn for CountingHistogram {
+        fn record(&self, _: f64) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Recorder for HistogramRecorder {
+        fn describe_counter(
+            &self,
+            _: crate::KeyName,
+            _: Option<crate::Unit>,
+            _: crate::SharedString,
+        ) {
+        }
+        fn describe_gauge(
+            &self,
+            _: crate::KeyName,
+            _: Option<crate::Unit>,
+            _: crate::SharedString,
+        ) {
+        }
+        fn describe_histogram(
+            &self,
+            _: crate::KeyName,
+            _: Option<crate::Unit>,
+            _: crate::SharedString,
+        ) {
+        }
+
+        fn register_counter(&self, _: &crate::Key, _: &crate::Metadata<'_>) -> crate::Counter {
+            crate::Counter::noop()
+        }
+
+        fn register_gauge(&self, _: &crate::Key, _: &crate::Metadata<'_>) -> crate::Gauge {
+            crate::Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &crate::Key, _: &crate::Metadata<'_>) -> crate::Histogram {
+            crate::Histogram::from_arc(Arc::new(CountingHistogram(self.0.clone())))
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn macro_metadata_reaches_recorder() {
+        use super::with_local_recorder;
+        use crate::Level;
+        use std::sync::Mutex;
+
+        /// Recorder which captures the target and level of every metric registered through it.
+        #[derive(Default)]
+        struct MetadataRecorder(Mutex<Vec<(String, Level)>>);
+
+        impl MetadataRecorder {
+            fn capture(&self, metadata: &crate::Metadata<'_>) {
+                let entry = (metadata.target().to_owned(), metadata.level().clone());
+                self.0.lock().unwrap().push(entry);
+            }
+        }
+
+        impl Recorder for MetadataRecorder {
+            fn describe_counter(
+                &self,
+                _: crate::KeyName,
+                _: Option<crate::Unit>,
+                _: crate::SharedString,
+            ) {
+            }
+            fn describe_gauge(
+                &self,
+                _: crate::KeyName,
+                _: Option<crate::Unit>,
+                _: crate::SharedString,
+            ) {
+            }
+            fn describe_histogram(
+                &self,
+                _: crate::KeyName,
+                _: Option<crate::Unit>,
+                _: crate::SharedString,
+            ) {
+            }
+
+            fn register_counter(
+                &self,
+                _: &crate::Key,
+                metadata: &crate::Metadata<'_>,
+            ) -> crate::Counter {
+                self.capture(metadata);
+                crate::Counter::noop()
+            }
+
+            fn register_gauge(
+                &self,
+                _: &crate::Key,
+                metadata: &crate::Metadata<'_>,
+            ) -> crate::Gauge {
+                self.capture(metadata);
+                crate::Gauge::noop()
+            }
+
+            fn register_histogram(
+                &self,
+                _: &crate::Key,
+                metadata: &crate::Metadata<'_>,
+            ) -> crate::Histogram {
+                self.capture(metadata);
+                crate::Histogram::noop()
+            }
+        }
+
+        let recorder = MetadataRecorder::default();
+        with_local_recorder(&recorder, || {
+            let _ = crate::counter!("default");
+            let _ = crate::counter!(target: "my_app::db", level: Level::DEBUG, "both", "k" => "v");
+            let _ = crate::gauge!(target: "my_app::db", "target_only");
+            let _ = crate::histogram!(level: Level::TRACE, "level_only", "k" => "v");
+        });
+
+        let captured = recorder.0.into_inner().unwrap();
+        assert_eq!(
+            captured,
+            vec![
+                (module_path!().to_owned(), Level::INFO),
+                ("my_app::db".to_owned(), Level::DEBUG),
+                ("my_app::db".to_owned(), Level::INFO),
+                (module_path!().to_owned(), Level::TRACE),
+            ]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn declare_describes_and_registers() {
+        use super::with_local_recorder;
+        use crate::{KeyName, SharedString, Unit};
+        use std::sync::Mutex;
+
+        /// Recorder which captures descriptions, and counts registrations via `CountingRecorder`.
+        #[derive(Default)]
+        struct DescribingRecorder {
+            descriptions: Mutex<Vec<(KeyName, Option<Unit>, SharedString)>>,
+            registrations: CountingRecorder,
+        }
+
+        impl Recorder for DescribingRecorder {
+            fn describe_counter(
+                &self,
+                key: KeyName,
+                unit: Option<Unit>,
+                description: SharedString,
+            ) {
+                self.descriptions.lock().unwrap().push((key, unit, description));
+            }
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn register_counter(
+                &self,
+                key: &crate::Key,
+                metadata: &crate::Metadata<'_>,
+            ) -> crate::Counter {
+                self.registrations.register_counter(key, metadata)
+            }
+
+            fn register_gauge(
+                &self,
+                key: &crate::Key,
+                metadata: &crate::Metadata<'_>,
+            ) -> crate::Gauge {
+                self.registrations.register_gauge(key, metadata)
+            }
+
+            fn register_histogram(
+                &self,
+                key: &crate::Key,
+                metadata: &crate::Metadata<'_>,
+            ) -> crate::Histogram {
+                self.registrations.register_histogram(key, metadata)
+            }
+        }
+
+        let recorder = DescribingRecorder::default();
+        with_local_recorder(&recorder, || {
+            let _ = crate::declare_counter!("bytes_sent", Unit::Bytes, "bytes sent to clients");
+            let _ = crate::declare_counter!(String::from("requests"), "requests received");
+        });
+
+        assert_eq!(recorder.registrations.count(), 2);
+        assert_eq!(
+            *recorder.descriptions.lock().unwrap(),
+            vec![
+                (KeyName::from("bytes_sent"), Some(Unit::Bytes), "bytes sent to clients".into()),
+                (KeyName::from("requests"), None, "requests received".into()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn static_handles_registered_once() {
+        use super::with_local_recorder;
+
+        fn requests() -> &'static crate::Counter {
+            crate::static_counter!("requests", "service" => "http")
+        }
+
+        // Without a recorder, a no-op handle is returned, and nothing is cached.
+        for _ in 0..3 {
+            requests().increment(1);
+        }
+
+        let recorder = CountingRecorder::default();
+        with_local_recorder(&recorder, || {
+            for _ in 0..3 {
+                requests().increment(1);
+            }
+        });
+        assert_eq!(recorder.count(), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn sampled_histogram_records_one_in_n() {
+        use super::with_local_recorder;
+
+        let recorder = HistogramRecorder::default();
+        let evaluated = AtomicUsize::new(0);
+        with_local_recorder(&recorder, || {
+            for i in 0..8 {
+                crate::sampled_histogram!(rate = 0.25, "sampled", "k" => "v"; {
+                    evaluated.fetch_add(1, Ordering::SeqCst);
+                    i as f64
+                });
+            }
+        });
+        assert_eq!(recorder.count(), 2);
+        assert_eq!(evaluated.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn time_records_on_early_exit() {
+        use super::with_local_recorder;
+
+        fn parse(input: &str) -> Result<u64, std::num::ParseIntError> {
+            let value = crate::time!("parse", "stage" => "input"; {
+                input.parse::<u64>()?
+            });
+            Ok(value + 1)
+        }
+
+        let recorder = HistogramRecorder::default();
+        with_local_recorder(&recorder, || {
+            assert_eq!(crate::time!("sum", { 1 + 1 }), 2);
+            assert_eq!(parse("41"), Ok(42));
+            assert!(parse("forty-one").is_err());
+        });
+        assert_eq!(recorder.count(), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn local_recorder_async_applies_during_poll() {
+        use super::with_local_recorder_async;
+        use std::{
+            future::Future,
+            pin::Pin,
+            task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+        };
+
+        /// Future which returns `Pending` the first time it is polled.
+        struct YieldOnce(bool);
+
+        impl Future for YieldOnce {
+            type Output = ();
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+                if self.0 {
+                    Poll::Ready(())
+                } else {
+                    self.0 = true;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+
+        fn noop_raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                noop_raw_waker()
+            }
+            fn noop(_: *const ()) {}
+
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+
+        let recorder = CountingRecorder::default();
+        let mut future = Box::pin(with_local_recorder_async(&recorder, async {
+            register_counter();
+            YieldOnce(false).await;
+            register_counter();
+        }));
+
+        // SAFETY: The waker's vtable functions do nothing, so they trivially uphold the contract.
+        let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(recorder.count(), 1);
+
+        // The local recorder must not leak out of the poll.
+        register_counter();
+        assert_eq!(recorder.count(), 1);
+
+        assert!(future.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(recorder.count(), 2);
+    }
 }
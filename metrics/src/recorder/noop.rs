@@ -1,4 +1,6 @@
-use crate::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use crate::{
+    Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Summary, Unit,
+};
 
 /// A no-op recorder.
 ///
@@ -19,4 +21,11 @@ impl Recorder for NoopRecorder {
     fn register_histogram(&self, _key: &Key, _metadata: &Metadata<'_>) -> Histogram {
         Histogram::noop()
     }
+    fn describe_summary(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+    fn register_summary(&self, _key: &Key, _metadata: &Metadata<'_>) -> Summary {
+        Summary::noop()
+    }
+    fn enabled(&self, _key_name: &KeyName, _metadata: &Metadata<'_>) -> bool {
+        false
+    }
 }
@@ -4,4 +4,14 @@ pub fn macros() {
     t.pass("tests/macros/01_basic.rs");
     t.pass("tests/macros/02_trailing_comma.rs");
     t.pass("tests/macros/03_mod_aliasing.rs");
+    t.compile_fail("tests/macros/06_name_leading_digit.rs");
+    t.compile_fail("tests/macros/07_name_invalid_character.rs");
+}
+
+#[cfg(feature = "macros")]
+#[cfg_attr(not(miri), test)]
+pub fn attribute_macros() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/macros/04_instrument.rs");
+    t.pass("tests/macros/05_metric_labels.rs");
 }
@@ -0,0 +1,48 @@
+use std::io;
+
+#[metrics::instrument]
+fn plain() -> u64 {
+    42
+}
+
+#[metrics::instrument(name = "renamed", labels(service = "http", region = "eu"))]
+fn with_name_and_labels(value: u64) -> u64 {
+    if value == 0 {
+        return 1;
+    }
+
+    value * 2
+}
+
+#[metrics::instrument(errors)]
+fn fallible(value: &str) -> Result<u64, std::num::ParseIntError> {
+    let parsed = value.parse::<u64>()?;
+    Ok(parsed + 1)
+}
+
+#[metrics::instrument(name = "fetch", errors)]
+async fn fallible_async(id: u64) -> io::Result<String> {
+    if id == 0 {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "missing"));
+    }
+
+    let value = async { Ok::<_, io::Error>(id) }.await?;
+    Ok(value.to_string())
+}
+
+struct Service;
+
+impl Service {
+    #[metrics::instrument(errors)]
+    fn method(&self, value: u64) -> Result<u64, ()> {
+        Ok(value)
+    }
+}
+
+fn main() {
+    let _ = plain();
+    let _ = with_name_and_labels(1);
+    let _ = fallible("1");
+    let _ = fallible_async(1);
+    let _ = Service.method(1);
+}
@@ -0,0 +1,44 @@
+use metrics::{IntoLabels, Label, MetricLabels};
+
+#[derive(MetricLabels)]
+struct RequestLabels {
+    method: &'static str,
+    #[label(rename = "status_code")]
+    status: u16,
+    #[label(skip)]
+    #[allow(dead_code)]
+    request_id: u64,
+    r#type: String,
+}
+
+#[derive(MetricLabels)]
+struct GenericLabels<T: metrics::IntoLabelValue + Clone> {
+    value: T,
+}
+
+#[derive(MetricLabels)]
+struct NoLabels;
+
+fn main() {
+    let labels =
+        RequestLabels { method: "GET", status: 200, request_id: 1234, r#type: String::from("api") };
+    let expected = vec![
+        Label::new("method", "GET"),
+        Label::new("status_code", "200"),
+        Label::new("type", "api"),
+    ];
+
+    assert_eq!((&labels).into_labels(), expected);
+    assert_eq!(labels.into_labels(), expected);
+    assert_eq!(GenericLabels { value: true }.into_labels(), vec![Label::new("value", "true")]);
+    assert!(NoLabels.into_labels().is_empty());
+
+    let labels = RequestLabels {
+        method: "POST",
+        status: 500,
+        request_id: 5678,
+        r#type: String::from("api"),
+    };
+    let _ = metrics::counter!("requests", &labels);
+    let _ = metrics::histogram!("request_duration_seconds", labels);
+}
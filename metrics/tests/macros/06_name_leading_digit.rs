@@ -0,0 +1,8 @@
+use metrics::counter;
+
+#[allow(dead_code)]
+fn leading_digit() {
+    counter!("5xx").increment(1);
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use metrics::{counter, describe_gauge, histogram};
+
+#[allow(dead_code)]
+fn invalid_character() {
+    counter!("a b").increment(1);
+    histogram!(target: "app", "latency-ms", "method" => "GET").record(1.0);
+    describe_gauge!("queue depth", "Depth of the queue.");
+}
+
+fn main() {}